@@ -0,0 +1,85 @@
+//! Peak heap and wall time of the eager and streaming args readers over a
+//! synthetic 2M-value batch. Run with `cargo bench --bench args_stream`.
+//!
+//! A counting global allocator records the high-water mark of live heap
+//! bytes, so the numbers are the readers' own and not the process's RSS.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use zk100_host::args_stream;
+use zk100_host::cairo_abi::{self, Section};
+
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(live, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const VALUES: usize = 2_000_000;
+const CASES: usize = 1_000;
+
+/// Peak heap growth while `f` runs, and its wall time.
+fn measure<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let base = LIVE.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    let peak = PEAK.load(Ordering::Relaxed) - base;
+    println!("{:<28}{:>10.1} MiB peak {:>8.0} ms", name, peak as f64 / (1 << 20) as f64, elapsed.as_secs_f64() * 1e3);
+    result
+}
+
+fn main() {
+    // CASES cases whose inputs and expected values fill the array, then one
+    // 2x2 grid of short programs.
+    let path = std::env::temp_dir().join(format!("zk100-args-bench-{}.json", std::process::id()));
+    let per_array = (VALUES - 1 - 6) / CASES / 2 - 1;
+    {
+        let mut out = BufWriter::new(File::create(&path).unwrap());
+        write!(out, "[\"0x{:x}\"", CASES).unwrap();
+        for case in 0..CASES {
+            for _ in 0..2 {
+                write!(out, ",\"0x{:x}\"", per_array).unwrap();
+                for i in 0..per_array {
+                    write!(out, ",\"0x{:x}\"", (case * per_array + i) as u32).unwrap();
+                }
+            }
+        }
+        writeln!(out, ",\"0x5\",\"0x1\",\"0x2a\",\"0x0\",\"0x0\",\"0x0\"]").unwrap();
+    }
+    let size = fs::metadata(&path).unwrap().len();
+    println!("{} ({:.1} MiB, ~{} values)", path.display(), size as f64 / (1 << 20) as f64, VALUES);
+
+    let eager = measure("eager parse_batch_args", || {
+        cairo_abi::parse_batch_args(&fs::read_to_string(&path).unwrap()).unwrap().prog_words
+    });
+    let streamed = measure("streaming read_batch_args", || {
+        args_stream::read_batch_args(BufReader::new(File::open(&path).unwrap())).unwrap().prog_words
+    });
+    let projected = measure("streaming --only prog-words", || {
+        let reader = BufReader::new(File::open(&path).unwrap());
+        args_stream::inspect_args(reader, cairo_abi::BATCH_ABI, Some(Section::ProgWords)).unwrap().only.unwrap()
+    });
+    assert_eq!(eager, streamed);
+    assert_eq!(eager, projected);
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,6 @@
+//! Records the version and git commit the host is built from, for
+//! `metadata.json`.
+
+fn main() {
+    built::write_built_file().expect("failed to record build information");
+}
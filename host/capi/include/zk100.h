@@ -0,0 +1,148 @@
+/*
+ * Memory ownership rules
+ * ----------------------
+ * - Input strings and arrays are borrowed for the duration of the call only;
+ *   the library never retains or frees them.
+ * - Every Zk100Buffer filled in by the library (including Zk100Error.message)
+ *   is owned by the caller and must be released exactly once with
+ *   zk100_free_buffer. Never pass such a buffer to free().
+ * - Buffers are followed by a NUL byte that is not counted in `len`, so text
+ *   payloads may be used as C strings.
+ * - On failure no output buffer is allocated; only the error message (if an
+ *   error record was supplied) must be freed.
+ * - Rust panics never unwind into C: they are reported as ZK100_STATUS_PANIC.
+ */
+
+
+#ifndef ZK100_H
+#define ZK100_H
+
+/* Generated by cbindgen from src/capi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ Status codes returned by every `zk100_*` function.
+ */
+enum Zk100Status
+#if defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+  : int32_t
+#endif // defined(__cplusplus) || __STDC_VERSION__ >= 202311L
+ {
+  ZK100_STATUS_OK = 0,
+  /*
+   A required pointer argument was NULL.
+   */
+  ZK100_STATUS_NULL_ARGUMENT = 1,
+  /*
+   The source text was not valid UTF-8.
+   */
+  ZK100_STATUS_INVALID_UTF8 = 2,
+  /*
+   The source failed to assemble; see the error message.
+   */
+  ZK100_STATUS_ASSEMBLY = 3,
+  /*
+   Serialization or root computation failed.
+   */
+  ZK100_STATUS_INTERNAL = 4,
+  /*
+   A Rust panic was caught at the boundary.
+   */
+  ZK100_STATUS_PANIC = 5,
+};
+#ifndef __cplusplus
+#if __STDC_VERSION__ >= 202311L
+typedef enum Zk100Status Zk100Status;
+#else
+typedef int32_t Zk100Status;
+#endif // __STDC_VERSION__ >= 202311L
+#endif // __cplusplus
+
+/*
+ A byte buffer allocated by the library.
+
+ `data` is followed by a NUL byte that is not counted in `len`, so text
+ buffers can be used directly as C strings. An empty buffer has a NULL
+ `data` pointer.
+ */
+typedef struct Zk100Buffer {
+  uint8_t *data;
+  size_t len;
+} Zk100Buffer;
+
+/*
+ Error details filled in when a call fails.
+ */
+typedef struct Zk100Error {
+  int32_t code;
+  struct Zk100Buffer message;
+} Zk100Error;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Assemble `src` into a cairo-prove args.json document with no inputs and no
+ expected outputs.
+
+ # Safety
+ See `zk100_assemble_with_io`.
+ */
+int32_t zk100_assemble(const char *src, struct Zk100Buffer *out_args_json, struct Zk100Error *err);
+
+/*
+ Assemble `src` into a cairo-prove args.json document.
+
+ # Safety
+ `src` must be a NUL-terminated string. `inputs`/`expected` must point to
+ `inputs_len`/`expected_len` values or be NULL with a zero length.
+ `out_args_json` must point to writable storage; on success it receives a
+ buffer the caller must release with `zk100_free_buffer`. `err` may be NULL.
+ */
+int32_t zk100_assemble_with_io(const char *src,
+                               const uint32_t *inputs,
+                               size_t inputs_len,
+                               const uint32_t *expected,
+                               size_t expected_len,
+                               struct Zk100Buffer *out_args_json,
+                               struct Zk100Error *err);
+
+/*
+ Compute the 32-byte program merkle root of `src`.
+
+ # Safety
+ `src` must be a NUL-terminated string and `out_root` must point to 32
+ writable bytes. `err` may be NULL.
+ */
+int32_t zk100_program_root(const char *src, uint8_t *out_root, struct Zk100Error *err);
+
+/*
+ Describe what this build supports as a JSON document (see
+ `capabilities::Capabilities`).
+
+ # Safety
+ `out_json` must point to writable storage; on success it receives a buffer
+ the caller must release with `zk100_free_buffer`. `err` may be NULL.
+ */
+int32_t zk100_capabilities(struct Zk100Buffer *out_json, struct Zk100Error *err);
+
+/*
+ Release a buffer previously returned by this library and reset it to empty.
+
+ # Safety
+ `buf` must be NULL or point to a buffer filled in by a `zk100_*` function
+ that has not already been freed. Freeing an empty buffer is a no-op.
+ */
+void zk100_free_buffer(struct Zk100Buffer *buf);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ZK100_H */
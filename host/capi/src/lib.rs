@@ -0,0 +1,8 @@
+//! `libzk100`: the C ABI of `zk100_host::capi` built as a shared library.
+//!
+//! `zk100-host` itself is only an rlib, so services that link the Rust crate
+//! do not pay for a cdylib on every build. The exported functions live in
+//! `zk100_host::capi`; `include/zk100.h` is their cbindgen header, and
+//! `tests/capi.rs` fails when it drifts from what cbindgen generates.
+
+pub use zk100_host::capi::*;
@@ -0,0 +1,105 @@
+//! C ABI tests. The header checks always run; compiling and running the C
+//! smoke test needs a C compiler:
+//!
+//!     cargo test -p zk100-capi -- --ignored
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn capi_source() -> PathBuf {
+    manifest_dir().join("../src/capi.rs")
+}
+
+/// The checked-in header is exactly what cbindgen generates from capi.rs.
+#[test]
+fn header_matches_cbindgen() {
+    let config = cbindgen::Config::from_file(manifest_dir().join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(capi_source())
+        .generate()
+        .expect("cbindgen failed on src/capi.rs")
+        .write(&mut generated);
+    let header = fs::read_to_string(manifest_dir().join("include/zk100.h")).unwrap();
+    assert!(
+        String::from_utf8(generated).unwrap() == header,
+        "include/zk100.h is stale; regenerate it with the command in cbindgen.toml"
+    );
+}
+
+/// Every `extern "C"` function in capi.rs must be declared in the header.
+#[test]
+fn header_declares_every_export() {
+    let source = fs::read_to_string(capi_source()).unwrap();
+    let header = fs::read_to_string(manifest_dir().join("include/zk100.h")).unwrap();
+
+    let exports: Vec<&str> = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("pub unsafe extern \"C\" fn "))
+        .map(|rest| rest.split('(').next().unwrap())
+        .collect();
+    assert!(exports.len() >= 3, "no exports found in capi.rs");
+
+    for name in exports {
+        assert!(
+            header.contains(&format!("{}(", name)),
+            "{} is exported but missing from include/zk100.h; regenerate with cbindgen",
+            name
+        );
+    }
+}
+
+/// Directory holding the cdylib: target/<profile>/deps when built for tests,
+/// target/<profile> after a plain `cargo build`.
+fn artifact_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    let has_lib = |dir: &Path| {
+        fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .any(|e| e.file_name().to_string_lossy().starts_with("libzk100."))
+            })
+            .unwrap_or(false)
+    };
+    if has_lib(deps) {
+        deps.to_path_buf()
+    } else {
+        deps.parent().unwrap().to_path_buf()
+    }
+}
+
+#[test]
+#[ignore = "needs a C compiler; run with -p zk100-capi -- --ignored"]
+fn c_smoke_test() {
+    let lib_dir = artifact_dir();
+    let exe = lib_dir.join("zk100_capi_test");
+    let status = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(manifest_dir().join("tests/capi/test_capi.c"))
+        .arg("-I")
+        .arg(manifest_dir().join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lzk100")
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .expect("failed to invoke the C compiler");
+    assert!(status.success(), "compiling test_capi.c failed");
+
+    let output = Command::new(&exe).output().unwrap();
+    assert!(
+        output.status.success(),
+        "C smoke test failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "capi ok");
+}
@@ -0,0 +1,64 @@
+/* Smoke test for the C ABI; compiled and run by tests/capi.rs. */
+#include <stdio.h>
+#include <string.h>
+
+#include "zk100.h"
+
+#define CHECK(cond, msg)                                  \
+  do {                                                    \
+    if (!(cond)) {                                        \
+      fprintf(stderr, "FAIL %s:%d: %s\n", __FILE__, __LINE__, msg); \
+      return 1;                                           \
+    }                                                     \
+  } while (0)
+
+static const char *PROGRAM =
+    "NODE (0,0)\n"
+    "MOV IN, P:RIGHT\n"
+    "HLT\n"
+    "NODE (1,1)\n"
+    "MOV P:LEFT, OUT\n"
+    "HLT\n";
+
+int main(void) {
+  Zk100Buffer args = {0};
+  Zk100Error err = {0};
+  uint32_t inputs[] = {42};
+  uint32_t expected[] = {42};
+
+  int32_t rc = zk100_assemble_with_io(PROGRAM, inputs, 1, expected, 1, &args, &err);
+  CHECK(rc == ZK100_STATUS_OK, "assemble_with_io failed");
+  CHECK(args.data != NULL && args.len > 0, "empty args buffer");
+  CHECK(strncmp((const char *)args.data, "[\"0x1\",\"0x2a\",\"0x1\",\"0x2a\"", 26) == 0,
+        "unexpected args prefix");
+  zk100_free_buffer(&args);
+  CHECK(args.data == NULL && args.len == 0, "buffer not reset after free");
+
+  uint8_t root[32] = {0};
+  rc = zk100_program_root(PROGRAM, root, &err);
+  CHECK(rc == ZK100_STATUS_OK, "program_root failed");
+  int nonzero = 0;
+  for (int i = 0; i < 32; i++) nonzero |= root[i];
+  CHECK(nonzero, "root is all zeroes");
+
+  rc = zk100_assemble("NODE (0,0)\nMVO 1, ACC\n", &args, &err);
+  CHECK(rc == ZK100_STATUS_ASSEMBLY, "expected assembly error");
+  CHECK(err.code == ZK100_STATUS_ASSEMBLY, "error code mismatch");
+  CHECK(err.message.data != NULL && strstr((const char *)err.message.data, "MVO") != NULL,
+        "error message does not name the bad mnemonic");
+  CHECK(args.data == NULL, "output allocated on failure");
+  zk100_free_buffer(&err.message);
+
+  Zk100Buffer caps = {0};
+  rc = zk100_capabilities(&caps, &err);
+  CHECK(rc == ZK100_STATUS_OK, "capabilities failed");
+  CHECK(caps.data != NULL && strstr((const char *)caps.data, "\"commitment_version\"") != NULL,
+        "capabilities missing commitment_version");
+  zk100_free_buffer(&caps);
+
+  rc = zk100_program_root(NULL, root, NULL);
+  CHECK(rc == ZK100_STATUS_NULL_ARGUMENT, "expected NULL argument error");
+
+  printf("capi ok\n");
+  return 0;
+}
@@ -0,0 +1,19 @@
+//! Builds only if zk100-core is usable without std.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use zk100_core::instruction::Inst;
+use zk100_core::CoreError;
+
+/// Decode a node program the way a guest without std would.
+pub fn decode_program(words: &[u32]) -> Result<Vec<Inst>, CoreError> {
+    words.iter().map(|&w| Inst::decode(w)).collect()
+}
+
+/// Re-encode a decoded program.
+pub fn encode_program(program: &[Inst]) -> Vec<u32> {
+    program.iter().map(Inst::encode).collect()
+}
@@ -0,0 +1,49 @@
+use alloc::string::String;
+use core::fmt;
+
+/// Errors from parsing mnemonics/operands and decoding instruction words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoreError {
+    UnknownOperation(String),
+    UnknownPort(String),
+    InvalidSource(String),
+    InvalidDestination(String),
+    InvalidOpcode { code: u32, word: u32 },
+    InvalidSourceCode { code: u32, word: u32 },
+    InvalidDestinationCode { code: u32, word: u32 },
+    NonCanonical { word: u32 },
+}
+
+pub type Result<T> = core::result::Result<T, CoreError>;
+
+/// The port operands, in both accepted spellings.
+const PORTS: &str = "UP, DOWN, LEFT or RIGHT, with or without P:";
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoreError::UnknownOperation(s) => write!(f, "Unknown operation: {}", s),
+            CoreError::UnknownPort(s) => write!(f, "Unknown port: {} (expected {})", s, PORTS),
+            CoreError::InvalidSource(s) => {
+                write!(f, "Invalid source operand: {} (expected a literal, ACC, NIL, IN, LAST or a port: {})", s, PORTS)
+            }
+            CoreError::InvalidDestination(s) => {
+                write!(f, "Invalid destination operand: {} (expected ACC, NIL, OUT, LAST or a port: {})", s, PORTS)
+            }
+            CoreError::InvalidOpcode { code, word } => {
+                write!(f, "Invalid opcode {} in word 0x{:08x}", code, word)
+            }
+            CoreError::InvalidSourceCode { code, word } => {
+                write!(f, "Invalid source code {} in word 0x{:08x}", code, word)
+            }
+            CoreError::InvalidDestinationCode { code, word } => {
+                write!(f, "Invalid destination code {} in word 0x{:08x}", code, word)
+            }
+            CoreError::NonCanonical { word } => {
+                write!(f, "Non-canonical instruction word 0x{:08x}", word)
+            }
+        }
+    }
+}
+
+impl core::error::Error for CoreError {}
@@ -0,0 +1,565 @@
+use crate::error::{CoreError, Result};
+use alloc::string::ToString;
+use core::fmt;
+use core::str::FromStr;
+
+/// One bit field of an instruction word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub shift: u32,
+    pub mask: u32,
+}
+
+impl Field {
+    pub const fn get(self, word: u32) -> u32 {
+        (word >> self.shift) & self.mask
+    }
+
+    pub const fn put(self, value: u32) -> u32 {
+        (value & self.mask) << self.shift
+    }
+}
+
+/// Word layout: lit(8) | src_port(2) | dst_port(2) | op(4) | src(8) | dst(8).
+pub const LIT_FIELD: Field = Field { shift: 24, mask: 0xFF };
+pub const SRC_PORT_FIELD: Field = Field { shift: 22, mask: 0x3 };
+pub const DST_PORT_FIELD: Field = Field { shift: 20, mask: 0x3 };
+pub const OP_FIELD: Field = Field { shift: 16, mask: 0xF };
+pub const SRC_FIELD: Field = Field { shift: 8, mask: 0xFF };
+pub const DST_FIELD: Field = Field { shift: 0, mask: 0xFF };
+
+/// Every field, most significant first, with its name.
+pub const FIELDS: [(&str, Field); 6] = [
+    ("LIT", LIT_FIELD),
+    ("SRC_PORT", SRC_PORT_FIELD),
+    ("DST_PORT", DST_PORT_FIELD),
+    ("OP", OP_FIELD),
+    ("SRC", SRC_FIELD),
+    ("DST", DST_FIELD),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Mov = 1,
+    Add = 2,
+    Sub = 3,
+    Neg = 4,
+    Sav = 5,
+    Swp = 6,
+    Jmp = 7,
+    Jz = 8,
+    Jnz = 9,
+    Jgz = 10,
+    Jlz = 11,
+    Nop = 12,
+    Hlt = 13,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortTag {
+    Up = 0,
+    Down = 1,
+    Left = 2,
+    Right = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Src {
+    Lit(u32),
+    Acc,
+    Nil,
+    In,
+    P(PortTag),
+    Last,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dst {
+    Acc,
+    Nil,
+    Out,
+    P(PortTag),
+    Last,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inst {
+    pub op: Op,
+    pub src: Src,
+    pub dst: Dst,
+}
+
+impl FromStr for Op {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_uppercase().as_str() {
+            "MOV" => Ok(Op::Mov),
+            "ADD" => Ok(Op::Add),
+            "SUB" => Ok(Op::Sub),
+            "NEG" => Ok(Op::Neg),
+            "SAV" => Ok(Op::Sav),
+            "SWP" => Ok(Op::Swp),
+            "JMP" => Ok(Op::Jmp),
+            "JZ" => Ok(Op::Jz),
+            "JNZ" => Ok(Op::Jnz),
+            "JGZ" => Ok(Op::Jgz),
+            "JLZ" => Ok(Op::Jlz),
+            "NOP" => Ok(Op::Nop),
+            "HLT" => Ok(Op::Hlt),
+            _ => Err(CoreError::UnknownOperation(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for PortTag {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_uppercase().as_str() {
+            "UP" => Ok(PortTag::Up),
+            "DOWN" => Ok(PortTag::Down),
+            "LEFT" => Ok(PortTag::Left),
+            "RIGHT" => Ok(PortTag::Right),
+            _ => Err(CoreError::UnknownPort(s.to_string())),
+        }
+    }
+}
+
+/// A literal in decimal, `0x` hex or `0b` binary (either prefix in any
+/// case), optionally signed. Negative values wrap to their two's
+/// complement, so anything in `i32::MIN..=u32::MAX` parses.
+pub fn parse_literal(s: &str) -> Option<u32> {
+    let (negative, unsigned) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0x" | "0X") => (16, &unsigned[2..]),
+        Some("0b" | "0B") => (2, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let magnitude = u32::from_str_radix(digits, radix).ok()?;
+    match negative {
+        false => Some(magnitude),
+        true if magnitude <= 1 << 31 => Some(magnitude.wrapping_neg()),
+        true => None,
+    }
+}
+
+impl FromStr for Src {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let upper = s.to_uppercase();
+        match upper.as_str() {
+            "ACC" => Ok(Src::Acc),
+            "NIL" => Ok(Src::Nil),
+            "IN" => Ok(Src::In),
+            "LAST" => Ok(Src::Last),
+            _ => {
+                // Check for port
+                if let Some(port_str) = upper.strip_prefix("P:") {
+                    let port = PortTag::from_str(port_str)?;
+                    Ok(Src::P(port))
+                } else if let Ok(port) = PortTag::from_str(&upper) {
+                    // TIS-100 spelling: a bare direction.
+                    Ok(Src::P(port))
+                } else if let Some(num) = parse_literal(s) {
+                    Ok(Src::Lit(num))
+                } else {
+                    Err(CoreError::InvalidSource(s.to_string()))
+                }
+            }
+        }
+    }
+}
+
+impl Src {
+    /// One operand of each kind, in code order (`Lit` and `P` carry placeholders).
+    pub const KINDS: [Src; 6] = [Src::Lit(0), Src::Acc, Src::Nil, Src::In, Src::P(PortTag::Up), Src::Last];
+
+    /// Upper-case name of the operand kind, ignoring any payload.
+    pub fn kind_name(self) -> &'static str {
+        match self {
+            Src::Lit(_) => "LIT",
+            Src::Acc => "ACC",
+            Src::Nil => "NIL",
+            Src::In => "IN",
+            Src::P(_) => "P",
+            Src::Last => "LAST",
+        }
+    }
+
+    pub fn to_code(self) -> u8 {
+        match self {
+            Src::Lit(_) => 0,
+            Src::Acc => 1,
+            Src::Nil => 2,
+            Src::In => 3,
+            Src::P(_) => 4,
+            Src::Last => 5,
+        }
+    }
+}
+
+impl FromStr for Dst {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let upper = s.to_uppercase();
+        match upper.as_str() {
+            "ACC" => Ok(Dst::Acc),
+            "NIL" => Ok(Dst::Nil),
+            "OUT" => Ok(Dst::Out),
+            "LAST" => Ok(Dst::Last),
+            _ => {
+                // Check for port
+                if let Some(port_str) = upper.strip_prefix("P:") {
+                    let port = PortTag::from_str(port_str)?;
+                    Ok(Dst::P(port))
+                } else if let Ok(port) = PortTag::from_str(&upper) {
+                    // TIS-100 spelling: a bare direction.
+                    Ok(Dst::P(port))
+                } else {
+                    Err(CoreError::InvalidDestination(s.to_string()))
+                }
+            }
+        }
+    }
+}
+
+impl Dst {
+    /// One operand of each kind, in code order (`P` carries a placeholder).
+    pub const KINDS: [Dst; 5] = [Dst::Acc, Dst::Nil, Dst::Out, Dst::P(PortTag::Up), Dst::Last];
+
+    /// Upper-case name of the operand kind, ignoring any payload.
+    pub fn kind_name(self) -> &'static str {
+        match self {
+            Dst::Acc => "ACC",
+            Dst::Nil => "NIL",
+            Dst::Out => "OUT",
+            Dst::P(_) => "P",
+            Dst::Last => "LAST",
+        }
+    }
+
+    pub fn to_code(self) -> u8 {
+        match self {
+            Dst::Acc => 0,
+            Dst::Nil => 1,
+            Dst::Out => 2,
+            Dst::P(_) => 3,
+            Dst::Last => 4,
+        }
+    }
+}
+
+impl Inst {
+    pub fn encode(&self) -> u32 {
+        // Format: lit(8) | src_port(2) | dst_port(2) | op(4) | src(8) | dst(8) = 32 bits
+        let lit_val = match self.src {
+            Src::Lit(val) => val,
+            _ => 0,
+        };
+        
+        let src_port = match self.src {
+            Src::P(port) => port as u32,
+            _ => 0,
+        };
+        
+        let dst_port = match self.dst {
+            Dst::P(port) => port as u32,
+            _ => 0,
+        };
+        
+        LIT_FIELD.put(lit_val) |
+        SRC_PORT_FIELD.put(src_port) |
+        DST_PORT_FIELD.put(dst_port) |
+        OP_FIELD.put(self.op as u32) |
+        SRC_FIELD.put(self.src.to_code() as u32) |
+        DST_FIELD.put(self.dst.to_code() as u32)
+    }
+
+    /// Decode a word produced by `encode`.
+    ///
+    /// Decoding is strict: unknown codes and bits set in fields the operands
+    /// don't use are rejected, so `Inst::decode(w)?.encode() == w` always holds.
+    pub fn decode(word: u32) -> Result<Self> {
+        let lit_val = LIT_FIELD.get(word);
+        let src_port = SRC_PORT_FIELD.get(word);
+        let dst_port = DST_PORT_FIELD.get(word);
+        let op_val = OP_FIELD.get(word);
+        let src_val = SRC_FIELD.get(word);
+        let dst_val = DST_FIELD.get(word);
+
+        let op = Op::from_code(op_val as u8)
+            .ok_or(CoreError::InvalidOpcode { code: op_val, word })?;
+
+        let src = match src_val {
+            0 => Src::Lit(lit_val),
+            1 => Src::Acc,
+            2 => Src::Nil,
+            3 => Src::In,
+            4 => Src::P(PortTag::from_code(src_port as u8)),
+            5 => Src::Last,
+            _ => return Err(CoreError::InvalidSourceCode { code: src_val, word }),
+        };
+        let dst = match dst_val {
+            0 => Dst::Acc,
+            1 => Dst::Nil,
+            2 => Dst::Out,
+            3 => Dst::P(PortTag::from_code(dst_port as u8)),
+            4 => Dst::Last,
+            _ => return Err(CoreError::InvalidDestinationCode { code: dst_val, word }),
+        };
+
+        let inst = Inst { op, src, dst };
+        if inst.encode() != word {
+            return Err(CoreError::NonCanonical { word });
+        }
+        Ok(inst)
+    }
+}
+
+impl Op {
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Op::Mov),
+            2 => Some(Op::Add),
+            3 => Some(Op::Sub),
+            4 => Some(Op::Neg),
+            5 => Some(Op::Sav),
+            6 => Some(Op::Swp),
+            7 => Some(Op::Jmp),
+            8 => Some(Op::Jz),
+            9 => Some(Op::Jnz),
+            10 => Some(Op::Jgz),
+            11 => Some(Op::Jlz),
+            12 => Some(Op::Nop),
+            13 => Some(Op::Hlt),
+            _ => None,
+        }
+    }
+}
+
+impl PortTag {
+    /// Every port in code order.
+    pub const ALL: [PortTag; 4] = [PortTag::Up, PortTag::Down, PortTag::Left, PortTag::Right];
+
+    /// Decode a 2-bit port field.
+    pub fn from_code(code: u8) -> Self {
+        match code & 0x3 {
+            0 => PortTag::Up,
+            1 => PortTag::Down,
+            2 => PortTag::Left,
+            _ => PortTag::Right,
+        }
+    }
+}
+
+impl Op {
+    /// Every opcode in code order.
+    pub const ALL: [Op; 13] = [
+        Op::Mov, Op::Add, Op::Sub, Op::Neg, Op::Sav, Op::Swp, Op::Jmp,
+        Op::Jz, Op::Jnz, Op::Jgz, Op::Jlz, Op::Nop, Op::Hlt,
+    ];
+
+    /// Canonical upper-case mnemonic.
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Op::Mov => "MOV",
+            Op::Add => "ADD",
+            Op::Sub => "SUB",
+            Op::Neg => "NEG",
+            Op::Sav => "SAV",
+            Op::Swp => "SWP",
+            Op::Jmp => "JMP",
+            Op::Jz => "JZ",
+            Op::Jnz => "JNZ",
+            Op::Jgz => "JGZ",
+            Op::Jlz => "JLZ",
+            Op::Nop => "NOP",
+            Op::Hlt => "HLT",
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+impl fmt::Display for PortTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PortTag::Up => "UP",
+            PortTag::Down => "DOWN",
+            PortTag::Left => "LEFT",
+            PortTag::Right => "RIGHT",
+        })
+    }
+}
+
+impl fmt::Display for Src {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Src::Lit(v) => write!(f, "{}", v),
+            Src::Acc => f.write_str("ACC"),
+            Src::Nil => f.write_str("NIL"),
+            Src::In => f.write_str("IN"),
+            Src::P(port) => write!(f, "P:{}", port),
+            Src::Last => f.write_str("LAST"),
+        }
+    }
+}
+
+impl fmt::Display for Dst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dst::Acc => f.write_str("ACC"),
+            Dst::Nil => f.write_str("NIL"),
+            Dst::Out => f.write_str("OUT"),
+            Dst::P(port) => write!(f, "P:{}", port),
+            Dst::Last => f.write_str("LAST"),
+        }
+    }
+}
+
+/// Formats as assembly source that parses back to the same instruction.
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.op {
+            Op::Nop | Op::Hlt | Op::Neg | Op::Sav | Op::Swp => write!(f, "{}", self.op),
+            Op::Add | Op::Sub | Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz => {
+                write!(f, "{} {}", self.op, self.src)
+            }
+            Op::Mov => write!(f, "{} {}, {}", self.op, self.src, self.dst),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_op_from_str() {
+        assert_eq!(Op::from_str("MOV").unwrap(), Op::Mov);
+        assert_eq!(Op::from_str("add").unwrap(), Op::Add);
+        assert_eq!(Op::from_str("HLT").unwrap(), Op::Hlt);
+        assert!(Op::from_str("INVALID").is_err());
+    }
+
+    #[test]
+    fn test_src_from_str() {
+        assert_eq!(Src::from_str("ACC").unwrap(), Src::Acc);
+        assert_eq!(Src::from_str("42").unwrap(), Src::Lit(42));
+        assert_eq!(Src::from_str("-5").unwrap(), Src::Lit(0xFFFFFFFB));
+        assert_eq!(Src::from_str("P:UP").unwrap(), Src::P(PortTag::Up));
+        assert_eq!(Src::from_str("0x2A").unwrap(), Src::Lit(42));
+        assert_eq!(Src::from_str("0b1010").unwrap(), Src::Lit(10));
+    }
+
+    #[test]
+    fn test_parse_literal() {
+        for (text, value) in [
+            ("42", 42),
+            ("+42", 42),
+            ("0x2a", 42),
+            ("0X2A", 42),
+            ("0b101010", 42),
+            ("0B101010", 42),
+            ("-0x5", 0xFFFF_FFFB),
+            ("0xFFFFFFFF", u32::MAX),
+            ("-2147483648", 0x8000_0000),
+            ("-0b1", u32::MAX),
+        ] {
+            assert_eq!(parse_literal(text), Some(value), "{}", text);
+        }
+        for text in ["", "-", "0x", "0b", "0x+5", "--5", "-+5", "0b102", "0xG", "0x100000000", "-2147483649", "1_000"] {
+            assert_eq!(parse_literal(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_encode_instruction() {
+        let nop = Inst {
+            op: Op::Nop,
+            src: Src::Nil,
+            dst: Dst::Nil,
+        };
+        assert_eq!(nop.encode(), 0x00C0201);
+        
+        let mov_lit = Inst {
+            op: Op::Mov,
+            src: Src::Lit(42),
+            dst: Dst::Acc,
+        };
+        assert_eq!(mov_lit.encode(), 0x2A010000);
+    }
+
+    #[test]
+    fn test_decode_roundtrip() {
+        let insts = [
+            Inst { op: Op::Nop, src: Src::Nil, dst: Dst::Nil },
+            Inst { op: Op::Mov, src: Src::Lit(42), dst: Dst::Acc },
+            Inst { op: Op::Mov, src: Src::P(PortTag::Left), dst: Dst::P(PortTag::Down) },
+            Inst { op: Op::Add, src: Src::In, dst: Dst::Nil },
+            Inst { op: Op::Jnz, src: Src::Lit(3), dst: Dst::Nil },
+            Inst { op: Op::Mov, src: Src::Last, dst: Dst::Last },
+        ];
+        for inst in insts {
+            assert_eq!(Inst::decode(inst.encode()).unwrap(), inst);
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid_words() {
+        // Opcode 0 and 14 are unassigned
+        assert!(Inst::decode(0x0000_0201).is_err());
+        assert!(Inst::decode(0x000E_0201).is_err());
+        // Source code 6 is unassigned
+        assert!(Inst::decode(0x000C_0601).is_err());
+        // Literal bits set on a non-literal source
+        assert!(Inst::decode(0x010C_0201).is_err());
+        // Port bits set on a non-port destination
+        assert!(Inst::decode(0x001C_0201).is_err());
+    }
+
+    #[test]
+    fn test_display_instruction() {
+        let mov = Inst { op: Op::Mov, src: Src::P(PortTag::Left), dst: Dst::Out };
+        assert_eq!(mov.to_string(), "MOV P:LEFT, OUT");
+        let jmp = Inst { op: Op::Jmp, src: Src::Lit(3), dst: Dst::Nil };
+        assert_eq!(jmp.to_string(), "JMP 3");
+        let hlt = Inst { op: Op::Hlt, src: Src::Nil, dst: Dst::Nil };
+        assert_eq!(hlt.to_string(), "HLT");
+    }
+
+    #[test]
+    fn test_tables_match_codes() {
+        for (i, op) in Op::ALL.iter().enumerate() {
+            assert_eq!(Op::from_code(i as u8 + 1), Some(*op));
+        }
+        for (i, src) in Src::KINDS.iter().enumerate() {
+            assert_eq!(src.to_code() as usize, i);
+        }
+        for (i, dst) in Dst::KINDS.iter().enumerate() {
+            assert_eq!(dst.to_code() as usize, i);
+        }
+        // The fields tile the word exactly.
+        let mut covered = 0u64;
+        for (_, field) in FIELDS {
+            let bits = (field.mask as u64) << field.shift;
+            assert_eq!(covered & bits, 0);
+            covered |= bits;
+        }
+        assert_eq!(covered, 0xFFFF_FFFF);
+    }
+}
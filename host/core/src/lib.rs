@@ -0,0 +1,13 @@
+//! ZK-100 instruction set: operand parsing and the 32-bit word codec.
+//!
+//! `no_std` + `alloc` so the encoding can be shared with constrained guests.
+//! The host crate re-exports everything here under `zk100_host::instruction`.
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod error;
+pub mod instruction;
+
+pub use error::CoreError;
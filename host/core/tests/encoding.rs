@@ -0,0 +1,3 @@
+use zk100_core::instruction::{Dst, Inst, Op, PortTag, Src};
+
+include!("common/vectors.rs");
@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zk100_host::assembler::{GridSize, decode_grid, encode_programs};
+use zk100_host::instruction::Inst;
+
+// Binary program blobs: big-endian u32 words, as hashed by the merkle code.
+fuzz_target!(|data: &[u8]| {
+    let words: Vec<u32> = data
+        .chunks_exact(4)
+        .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+
+    for &word in &words {
+        if let Ok(inst) = Inst::decode(word) {
+            assert_eq!(inst.encode(), word, "decode accepted a non-canonical word");
+        }
+    }
+
+    if let Ok(programs) = decode_grid(&words, GridSize::default()) {
+        assert_eq!(
+            encode_programs(&programs).unwrap(),
+            words,
+            "re-encoding a decoded grid changed the words"
+        );
+    }
+});
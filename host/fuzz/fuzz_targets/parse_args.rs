@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zk100_host::cairo_abi::{generate_args, parse_args};
+
+// Third-party args.json files: parsing must not panic, and an accepted file
+// must be reproduced exactly by regenerating it from the parsed arrays.
+fuzz_target!(|data: &[u8]| {
+    let Ok(json) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(args) = parse_args(json) else {
+        return;
+    };
+    let regenerated = generate_args(&args.inputs, &args.expected, &args.prog_words).unwrap();
+    let reparsed = parse_args(&serde_json::to_string(&regenerated).unwrap()).unwrap();
+    assert_eq!(reparsed, args);
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zk100_host::assembler::{decode_grid, encode_programs, parse};
+
+// Playground submissions: arbitrary text must never panic the assembler, and
+// anything it accepts must survive an encode/decode round trip.
+fuzz_target!(|data: &[u8]| {
+    let Ok(code) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(parsed) = parse(code) else {
+        return;
+    };
+    let words = encode_programs(&parsed.grid.programs).expect("encoding a parsed grid failed");
+    if let Ok(decoded) = decode_grid(&words, parsed.grid.size()) {
+        assert_eq!(encode_programs(&decoded).unwrap(), words);
+    }
+});
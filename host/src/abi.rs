@@ -0,0 +1,166 @@
+//! Args layouts and the stream settings they carry, without the JSON codec.
+//!
+//! The ABI numbers, the IN/OUT nodes and the input sentinel are shared by the
+//! assembler, the simulator and `cairo_abi`; they live here so the first two
+//! build without `serde_json` (the `abi-json` feature). `cairo_abi`
+//! re-exports everything in this module.
+
+use crate::assembler::{GRID_COLS, GRID_ROWS, GridSize};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// One flat `expected` stream, checked against (1,1)'s `OUT` writes:
+/// `[inputs_len, ...inputs, expected_len, ...expected, prog_words_len, ...prog_words]`.
+pub const LEGACY_ABI: u32 = 1;
+/// Expected outputs attributed to the node that produces them:
+/// `[inputs_len, ...inputs, node_count, (row, col, len, ...values)*, prog_words_len, ...prog_words]`,
+/// nodes in row-major order.
+pub const NODE_ABI: u32 = 2;
+/// Several `LEGACY_ABI` cases proved against one program:
+/// `[case_count, (inputs_len, ...inputs, expected_len, ...expected)*, prog_words_len, ...prog_words]`.
+pub const BATCH_ABI: u32 = 3;
+/// `LEGACY_ABI` with each distinct node program sent once:
+/// `[inputs_len, ...inputs, expected_len, ...expected, unique_count, (prog_len, ...words)*, node_count, ...node_index]`.
+/// Unique programs appear in order of first use; `node_index` gives, per node
+/// in row-major order, the position of its program in that table, and
+/// `node_count` is always `GRID_ROWS * GRID_COLS`.
+pub const DEDUP_ABI: u32 = 4;
+/// `LEGACY_ABI` led by the designated IN and OUT nodes:
+/// `[in_row, in_col, out_row, out_col, inputs_len, ...inputs, expected_len, ...expected, prog_words_len, ...prog_words]`.
+pub const IO_ABI: u32 = 5;
+/// `IO_ABI` led by the input sentinel:
+/// `[sentinel_mode, sentinel_value, in_row, in_col, out_row, out_col, inputs_len, ...]`,
+/// `sentinel_mode` 0 for none (with `sentinel_value` 0), else
+/// `SentinelBehavior::mode`.
+pub const SENTINEL_ABI: u32 = 6;
+/// `SENTINEL_ABI` led by the grid size, for grids other than 2x2:
+/// `[rows, cols, sentinel_mode, sentinel_value, in_row, in_col, out_row, out_col, inputs_len, ...]`,
+/// with `rows * cols` node programs in `prog_words`, row-major.
+pub const GRID_ABI: u32 = 7;
+
+/// Values keyed by the `(row, col)` of the node that writes them to `OUT`.
+pub type NodeOutputs = BTreeMap<(usize, usize), Vec<u32>>;
+
+/// The node that reads the input stream with `IN` and the node whose `OUT`
+/// writes form the checked output stream. The default is the guest's
+/// hard-wired (0,0) and (1,1); other nodes need `IO_ABI`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+#[serde(default)]
+pub struct IoNodes {
+    /// Row and column of the node reading `IN`.
+    pub in_node: (usize, usize),
+    /// Row and column of the node writing the checked `OUT` stream.
+    pub out_node: (usize, usize),
+}
+
+impl Default for IoNodes {
+    fn default() -> Self {
+        IoNodes { in_node: (0, 0), out_node: (GRID_ROWS - 1, GRID_COLS - 1) }
+    }
+}
+
+impl IoNodes {
+    pub fn is_default(&self) -> bool {
+        *self == IoNodes::default()
+    }
+
+    /// `LEGACY_ABI` for the default nodes, else `IO_ABI`.
+    pub fn single_case_abi(&self) -> u32 {
+        if self.is_default() { LEGACY_ABI } else { IO_ABI }
+    }
+
+    /// Both nodes must lie in the default grid.
+    pub fn validate(&self) -> Result<()> {
+        self.validate_in(GridSize::default())
+    }
+
+    /// Both nodes must lie in a grid of `size`.
+    pub fn validate_in(&self, size: GridSize) -> Result<()> {
+        for (name, (r, c)) in [("IN", self.in_node), ("OUT", self.out_node)] {
+            if !size.contains((r, c)) {
+                return Err(anyhow!("{} node ({},{}) is outside the {} grid", name, r, c, size));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What reading the sentinel from `IN` does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum SentinelBehavior {
+    /// The sentinel is delivered like any value; the program watches for it.
+    #[default]
+    PassThrough,
+    /// The stream ends at the sentinel: `IN` blocks there as if the inputs
+    /// had run out, and the values after it are never read.
+    EndOfStream,
+}
+
+impl SentinelBehavior {
+    pub const ALL: [SentinelBehavior; 2] = [SentinelBehavior::PassThrough, SentinelBehavior::EndOfStream];
+
+    /// The `sentinel_mode` word of `SENTINEL_ABI`.
+    pub fn mode(self) -> u32 {
+        match self {
+            SentinelBehavior::PassThrough => 1,
+            SentinelBehavior::EndOfStream => 2,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SentinelBehavior::PassThrough => "pass-through",
+            SentinelBehavior::EndOfStream => "end-of-stream",
+        }
+    }
+}
+
+/// An input value that marks the end of the stream. Only `SENTINEL_ABI`
+/// carries one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct InputSentinel {
+    pub value: u32,
+    #[serde(default)]
+    pub behavior: SentinelBehavior,
+}
+
+impl InputSentinel {
+    /// The first position where the sentinel appears with inputs after it.
+    pub fn early_position(&self, inputs: &[u32]) -> Option<usize> {
+        inputs.iter().position(|&v| v == self.value).filter(|&i| i + 1 < inputs.len())
+    }
+}
+
+impl fmt::Display for InputSentinel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.value, self.behavior.name())
+    }
+}
+
+/// `NodeOutputs` as a JSON object keyed by `"row,col"`.
+pub mod node_keys {
+    use super::NodeOutputs;
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(map: &NodeOutputs, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|((r, c), values)| (format!("{},{}", r, c), values)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NodeOutputs, D::Error> {
+        let raw = BTreeMap::<String, Vec<u32>>::deserialize(deserializer)?;
+        raw.into_iter()
+            .map(|(key, values)| {
+                let node = crate::assembler::parse_node_coords(&key).map_err(D::Error::custom)?;
+                Ok((node, values))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,304 @@
+//! Streaming read path for args files too large to load whole.
+//!
+//! `cairo_abi::parse_args_abi` parses the document into a `Vec<Value>` before
+//! it looks at the layout; for the multi-megabyte batches a scheduler
+//! produces, that is most of the memory a command uses. `ArrayWords` instead
+//! yields the array's values one at a time from a `BufRead`, and feeds the
+//! same layout walker the eager path uses, so the two agree on every result
+//! and every layout error. `inspect_args` goes further and drops the sections
+//! it was not asked for as they stream past.
+
+use crate::assembler::GridSize;
+use crate::cairo_abi::{self, BATCH_ABI, CairoArgs, GRID_ABI, InputSentinel, NODE_ABI, Section, SectionSpan, Words};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+/// The values of a top-level JSON array, parsed lazily. Each value goes
+/// through the same u32 conversion as the eager path.
+pub struct ArrayWords<R> {
+    reader: R,
+    index: usize,
+    started: bool,
+    done: bool,
+    token: Vec<u8>,
+}
+
+impl<R: BufRead> ArrayWords<R> {
+    pub fn new(reader: R) -> Self {
+        ArrayWords { reader, index: 0, started: false, done: false, token: Vec::new() }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        let buf = self.reader.fill_buf().map_err(|e| anyhow!("cannot read args: {}", e))?;
+        Ok(buf.first().copied())
+    }
+
+    fn bump(&mut self) {
+        self.reader.consume(1);
+    }
+
+    fn skip_whitespace(&mut self) -> Result<Option<u8>> {
+        while let Some(byte) = self.peek()? {
+            if !byte.is_ascii_whitespace() {
+                return Ok(Some(byte));
+            }
+            self.bump();
+        }
+        Ok(None)
+    }
+
+    fn malformed(&self, what: &str) -> anyhow::Error {
+        anyhow!("args must be a JSON array: {} at args[{}]", what, self.index)
+    }
+
+    /// The next value's text, quotes and escapes included.
+    fn read_token(&mut self) -> Result<()> {
+        self.token.clear();
+        match self.peek()? {
+            Some(b'"') => {
+                self.token.push(b'"');
+                self.bump();
+                let mut escaped = false;
+                loop {
+                    let byte = self.peek()?.ok_or_else(|| self.malformed("unterminated string"))?;
+                    self.token.push(byte);
+                    self.bump();
+                    match byte {
+                        _ if escaped => escaped = false,
+                        b'\\' => escaped = true,
+                        b'"' => return Ok(()),
+                        _ => {}
+                    }
+                }
+            }
+            Some(b'[') => Err(anyhow!("args[{}]: expected a string or number, found an array", self.index)),
+            Some(b'{') => Err(anyhow!("args[{}]: expected a string or number, found an object", self.index)),
+            _ => {
+                while let Some(byte) = self.peek()? {
+                    if byte == b',' || byte == b']' || byte.is_ascii_whitespace() {
+                        break;
+                    }
+                    self.token.push(byte);
+                    self.bump();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn next_word(&mut self) -> Result<Option<u32>> {
+        if self.done {
+            return Ok(None);
+        }
+        if !self.started {
+            if self.skip_whitespace()? != Some(b'[') {
+                return Err(anyhow!("args must be a JSON array"));
+            }
+            self.bump();
+            self.started = true;
+            if self.skip_whitespace()? == Some(b']') {
+                return self.close();
+            }
+        } else {
+            match self.skip_whitespace()? {
+                Some(b',') => self.bump(),
+                Some(b']') => return self.close(),
+                _ => return Err(self.malformed("expected ',' or ']'")),
+            }
+        }
+        self.skip_whitespace()?;
+        self.read_token()?;
+        let value: Value = serde_json::from_slice(&self.token).map_err(|_| self.malformed("invalid value"))?;
+        let word = cairo_abi::u32_from_json_value(&value).map_err(|e| anyhow!("args[{}]: {}", self.index, e))?;
+        self.index += 1;
+        Ok(Some(word))
+    }
+
+    fn close(&mut self) -> Result<Option<u32>> {
+        self.bump();
+        self.done = true;
+        if self.skip_whitespace()?.is_some() {
+            return Err(anyhow!("args must be a JSON array: trailing characters after the closing ']'"));
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> Iterator for ArrayWords<R> {
+    type Item = Result<u32>;
+
+    fn next(&mut self) -> Option<Result<u32>> {
+        let word = self.next_word();
+        if word.is_err() {
+            self.done = true;
+        }
+        word.transpose()
+    }
+}
+
+/// `cairo_abi::parse_args_abi`, streamed from `reader`.
+pub fn read_args<R: BufRead>(reader: R, abi: u32) -> Result<CairoArgs> {
+    cairo_abi::check_abi(abi)?;
+    cairo_abi::read_single_case(Words::new(ArrayWords::new(reader), &Section::ALL), abi)
+}
+
+/// `cairo_abi::parse_batch_args`, streamed from `reader`.
+pub fn read_batch_args<R: BufRead>(reader: R) -> Result<cairo_abi::Batch> {
+    let mut words = Words::new(ArrayWords::new(reader), &Section::ALL);
+    let batch = cairo_abi::read_batch(&mut words)?;
+    words.finish(BATCH_ABI)?;
+    Ok(batch)
+}
+
+/// What `inspect_args` learned about an args file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArgsInspection {
+    pub abi: u32,
+    /// Values in the whole array.
+    pub values: usize,
+    /// Cases in a `BATCH_ABI` file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cases: Option<usize>,
+    pub sections: BTreeMap<Section, SectionSpan>,
+    /// The input sentinel a `SENTINEL_ABI` file declares.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sentinel: Option<InputSentinel>,
+    /// The grid size a `GRID_ABI` file declares.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grid: Option<GridSize>,
+    /// The values of the section asked for, in file order: every case's
+    /// under `BATCH_ABI`, every node's expected outputs under `NODE_ABI`,
+    /// and the expanded `prog_words` under `DEDUP_ABI`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only: Option<Vec<u32>>,
+}
+
+/// Check an args file of layout `abi` and report where its sections lie,
+/// keeping only the values of `only` in memory.
+pub fn inspect_args<R: BufRead>(reader: R, abi: u32, only: Option<Section>) -> Result<ArgsInspection> {
+    cairo_abi::check_abi(abi)?;
+    let keep: Vec<Section> = only.into_iter().collect();
+    let mut words = Words::new(ArrayWords::new(reader), &keep);
+    let (cases, values, sentinel, grid) = if abi == BATCH_ABI {
+        let batch = cairo_abi::read_batch(&mut words)?;
+        let values = only.map(|section| match section {
+            Section::Inputs => batch.cases.iter().flat_map(|c| c.inputs.iter().copied()).collect(),
+            Section::Expected => batch.cases.iter().flat_map(|c| c.expected.iter().copied()).collect(),
+            Section::ProgWords => batch.prog_words,
+        });
+        (Some(batch.cases.len()), values, None, None)
+    } else {
+        let args = cairo_abi::read_case(&mut words, abi)?;
+        let (sentinel, grid) = (args.sentinel, (abi == GRID_ABI).then_some(args.grid));
+        let values = only.map(|section| match section {
+            Section::Inputs => args.inputs,
+            Section::Expected if abi == NODE_ABI => args.node_expected.into_values().flatten().collect(),
+            Section::Expected => args.expected,
+            Section::ProgWords => args.prog_words,
+        });
+        (None, values, sentinel, grid)
+    };
+    words.finish(abi)?;
+    Ok(ArgsInspection { abi, values: words.position(), cases, sections: words.spans.clone(), sentinel, grid, only: values })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cairo_abi::{DEDUP_ABI, IO_ABI, LEGACY_ABI, SENTINEL_ABI};
+
+    const ABIS: [u32; 7] = [LEGACY_ABI, NODE_ABI, BATCH_ABI, DEDUP_ABI, IO_ABI, SENTINEL_ABI, GRID_ABI];
+
+    fn both(json: &str, abi: u32) -> (Result<CairoArgs, String>, Result<CairoArgs, String>) {
+        let eager = cairo_abi::parse_args_abi(json, abi).map_err(|e| e.to_string());
+        let streamed = read_args(json.as_bytes(), abi).map_err(|e| e.to_string());
+        (eager, streamed)
+    }
+
+    #[test]
+    fn test_streaming_matches_eager() {
+        let args = CairoArgs {
+            inputs: vec![1, 2, 3],
+            expected: vec![4],
+            prog_words: vec![1, 0x1234, 0, 0, 2, 5, 6],
+            ..Default::default()
+        };
+        for abi in [LEGACY_ABI, BATCH_ABI, DEDUP_ABI, IO_ABI, SENTINEL_ABI, GRID_ABI] {
+            let json = serde_json::to_string_pretty(&args.to_args(abi).unwrap()).unwrap();
+            let (eager, streamed) = both(&json, abi);
+            assert_eq!(eager.unwrap(), args);
+            assert_eq!(streamed.unwrap(), args, "ABI {}", abi);
+        }
+        // Decimal strings, numbers and escapes read the same either way.
+        let (eager, streamed) = both(r#" [ "1", "\u0037","0x0" , 0 ] "#, LEGACY_ABI);
+        assert_eq!(streamed.unwrap().inputs, [7]);
+        assert_eq!(eager.unwrap().inputs, [7]);
+    }
+
+    #[test]
+    fn test_streaming_rejects_what_eager_rejects() {
+        let malformed = [
+            "",
+            "{}",
+            "[",
+            r#"["0x0", "0x0"]"#,
+            r#"["0x5", "0x1", "0x0", "0x0"]"#,
+            r#"["0x0", "0x0", "0x0", "0x1"]"#,
+            r#"["0x100000000", "0x0", "0x0"]"#,
+            r#"[-1, "0x0", "0x0"]"#,
+            r#"[null, "0x0", "0x0"]"#,
+            r#"[[0], "0x0", "0x0"]"#,
+            r#"["0x0", "0x0", "0x0"] x"#,
+            r#"["0x0" "0x0", "0x0"]"#,
+            r#"["0x0", "0x0", "0x0""#,
+            "[0, 2, 1, 1, 0, 0, 1, 0, 0]",
+            "[0, 0, 1, 0, 4, 0, 0, 0, 1]",
+        ];
+        for json in malformed {
+            for abi in ABIS {
+                let (eager, streamed) = both(json, abi);
+                assert!(eager.is_err() && streamed.is_err(), "{:?} under ABI {}: {:?} / {:?}", json, abi, eager, streamed);
+            }
+        }
+        // One problem, one message.
+        for (json, abi) in [
+            (r#"["0x5", "0x1", "0x0", "0x0"]"#, LEGACY_ABI),
+            (r#"["0x1", "0x100000000", "0x0", "0x0"]"#, LEGACY_ABI),
+            ("[0, 2, 1, 1, 0, 0, 1, 0, 0]", NODE_ABI),
+            ("[0, 0, 1, 0, 4, 0, 0, 0, 1]", DEDUP_ABI),
+            ("[0, 0, 0, 7]", LEGACY_ABI),
+            ("[0, 0, 0]", 9),
+        ] {
+            let (eager, streamed) = both(json, abi);
+            assert_eq!(eager.unwrap_err(), streamed.unwrap_err());
+        }
+    }
+
+    #[test]
+    fn test_inspect_projects_one_section() {
+        let batch = cairo_abi::Batch {
+            cases: vec![
+                cairo_abi::BatchCase { inputs: vec![1, 2], expected: vec![3] },
+                cairo_abi::BatchCase { inputs: vec![4], expected: vec![] },
+            ],
+            prog_words: vec![1, 9, 0, 0, 0],
+        };
+        let json = serde_json::to_string(&batch.to_args()).unwrap();
+        assert_eq!(read_batch_args(json.as_bytes()).unwrap(), batch);
+
+        let inspection = inspect_args(json.as_bytes(), BATCH_ABI, Some(Section::Inputs)).unwrap();
+        assert_eq!(inspection.only, Some(vec![1, 2, 4]));
+        assert_eq!((inspection.values, inspection.cases), (15, Some(2)));
+        assert_eq!(inspection.sections[&Section::Inputs], SectionSpan { values: 3, start: 1, end: 8 });
+        assert_eq!(inspection.sections[&Section::ProgWords], SectionSpan { values: 5, start: 9, end: 15 });
+
+        let inspection = inspect_args(json.as_bytes(), BATCH_ABI, None).unwrap();
+        assert_eq!(inspection.only, None);
+        assert_eq!(inspection.sections.len(), 3);
+        let words = inspect_args(json.as_bytes(), BATCH_ABI, Some(Section::ProgWords)).unwrap().only.unwrap();
+        assert_eq!(words, batch.prog_words);
+    }
+}
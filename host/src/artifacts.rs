@@ -0,0 +1,231 @@
+//! The library side of `zk100 assemble` and the sinks its artifacts go to.
+//!
+//! `assemble` builds every artifact (args, metadata sidecar, symbols, hints,
+//! listing) in memory and hands them to an `ArtifactSink` only once all of
+//! them are complete, so a step that fails leaves nothing behind. The CLI writes
+//! through `FsSink`; services embedding the crate can implement the trait
+//! for their own store, and tests use `MemorySink`.
+
+use crate::assembler::{self, ParseResult};
+use crate::attest::{AssembleOptions, Metadata};
+use crate::cairo_abi::CairoArgs;
+use crate::hints::{HintContext, Registry};
+use crate::{exec, listing, trace};
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub trait ArtifactSink {
+    /// Store one complete artifact under `name`.
+    fn write_artifact(&mut self, name: &str, bytes: &[u8]) -> Result<()>;
+}
+
+/// Writes each artifact to `root/name`, replacing it atomically.
+#[derive(Debug, Clone, Default)]
+pub struct FsSink {
+    root: PathBuf,
+}
+
+impl FsSink {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsSink { root: root.into() }
+    }
+}
+
+impl ArtifactSink for FsSink {
+    fn write_artifact(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+        exec::write_atomic(&self.root.join(name), bytes)
+    }
+}
+
+/// Keeps artifacts in memory, by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemorySink {
+    pub artifacts: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemorySink {
+    /// An artifact as text, if it was written and is UTF-8.
+    pub fn text(&self, name: &str) -> Option<&str> {
+        self.artifacts.get(name).and_then(|bytes| std::str::from_utf8(bytes).ok())
+    }
+}
+
+impl ArtifactSink for MemorySink {
+    fn write_artifact(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+        self.artifacts.insert(name.to_string(), bytes.to_vec());
+        Ok(())
+    }
+}
+
+/// The names `assemble` writes its artifacts under; `None` skips one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Emit {
+    pub args: String,
+    pub metadata: Option<String>,
+    pub symbols: Option<String>,
+    /// The private `hints.json` for the prover (see `hints`).
+    pub hints: Option<String>,
+    /// The listing of `listing::source_listing`.
+    pub listing: Option<String>,
+}
+
+impl Default for Emit {
+    fn default() -> Self {
+        Emit { args: "args.json".to_string(), metadata: None, symbols: None, hints: None, listing: None }
+    }
+}
+
+/// What `assemble` computed, for callers that report on it.
+#[derive(Debug, Clone)]
+pub struct Assembled {
+    pub parsed: ParseResult,
+    pub prog_words: Vec<u32>,
+    pub args: Vec<Value>,
+    /// Artifact names in the order they were written.
+    pub written: Vec<String>,
+}
+
+/// Assemble `source` into args of `abi` with `options`, writing the artifacts
+/// named by `emit` to `sink`. `source_file` is recorded in the metadata.
+pub fn assemble(
+    source: &str,
+    source_file: &str,
+    abi: u32,
+    options: &AssembleOptions,
+    emit: &Emit,
+    sink: &mut dyn ArtifactSink,
+) -> Result<Assembled> {
+    assemble_with(source, source_file, abi, options, emit, &Registry::builtin(), sink)
+}
+
+/// `assemble`, with the hints in `emit.hints` from `hints`' generators.
+pub fn assemble_with(
+    source: &str,
+    source_file: &str,
+    abi: u32,
+    options: &AssembleOptions,
+    emit: &Emit,
+    hints: &Registry,
+    sink: &mut dyn ArtifactSink,
+) -> Result<Assembled> {
+    let parsed = assembler::parse_with_options(source, &options.dialect()?, options.parse_options())?;
+    let prog_words = assembler::encode_programs_with(&parsed.grid.programs, &options.limits)?;
+    let args = CairoArgs {
+        inputs: options.inputs.clone(),
+        expected: options.expected.clone(),
+        node_expected: options.node_expected.clone(),
+        prog_words: prog_words.clone(),
+        io: options.io,
+        sentinel: options.sentinel,
+        grid: parsed.grid.size(),
+    }
+    .to_args_with(abi, &options.limits)?;
+
+    let mut artifacts = vec![(emit.args.clone(), serde_json::to_string(&args)?)];
+    if let Some(name) = &emit.metadata {
+        let metadata = Metadata::generate(source, source_file, abi, options.clone())?;
+        artifacts.push((name.clone(), serde_json::to_string_pretty(&metadata)? + "\n"));
+    }
+    if let Some(name) = &emit.symbols {
+        artifacts.push((name.clone(), serde_json::to_string_pretty(&parsed.symbols())? + "\n"));
+    }
+    if let Some(name) = &emit.hints {
+        let programs = &parsed.grid.programs;
+        let context = HintContext::simulate(programs, &options.inputs, options.io, options.sentinel, options.limits)?;
+        artifacts.push((name.clone(), serde_json::to_string_pretty(&hints.generate(&context)?)? + "\n"));
+    }
+    if let Some(name) = &emit.listing {
+        artifacts.push((name.clone(), listing::source_listing(&parsed, source, &prog_words)));
+    }
+
+    let mut written = Vec::new();
+    for (name, contents) in artifacts {
+        let span = trace::span!("write_artifact", bytes);
+        span.record("bytes", contents.len() as u64);
+        sink.write_artifact(&name, contents.as_bytes())?;
+        written.push(name);
+    }
+    Ok(Assembled { parsed, prog_words, args, written })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cairo_abi::{IoNodes, LEGACY_ABI};
+
+    const PROGRAM: &str = "NODE (0,0)\nMOV IN, ACC\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+    #[test]
+    fn test_memory_sink_receives_every_artifact() {
+        let options = AssembleOptions { inputs: vec![3], expected: vec![3], ..Default::default() };
+        let emit = Emit {
+            metadata: Some("meta.json".into()),
+            symbols: Some("symbols.json".into()),
+            listing: Some("pass.lst".into()),
+            ..Default::default()
+        };
+        let mut sink = MemorySink::default();
+        let assembled = assemble(PROGRAM, "pass.asm", LEGACY_ABI, &options, &emit, &mut sink).unwrap();
+
+        assert_eq!(sink.artifacts.keys().collect::<Vec<_>>(), ["args.json", "meta.json", "pass.lst", "symbols.json"]);
+        assert_eq!(assembled.written, ["args.json", "meta.json", "symbols.json", "pass.lst"]);
+        assert!(sink.text("pass.lst").unwrap().starts_with("NODE (0,0)\n[0]     0x00000002  length 2\n[1]  0  0x00010300  2: MOV IN, ACC\n"));
+        assert_eq!(sink.text("args.json").unwrap(), serde_json::to_string(&assembled.args).unwrap());
+        let metadata: Metadata = serde_json::from_str(sink.text("meta.json").unwrap()).unwrap();
+        assert_eq!(metadata.source_file, "pass.asm");
+        assert!(crate::attest::attest(PROGRAM, &metadata).is_ok());
+    }
+
+    /// An `FsSink` whose writes lose the second half of every artifact, or
+    /// fail outright, the way a dying build box leaves them.
+    struct FaultySink {
+        root: PathBuf,
+        fail: bool,
+    }
+
+    impl ArtifactSink for FaultySink {
+        fn write_artifact(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+            let fail = self.fail;
+            exec::write_atomic_with(&self.root.join(name), bytes, |file, bytes| {
+                if fail {
+                    return Err(std::io::Error::other("disk gone"));
+                }
+                std::io::Write::write_all(file, &bytes[..bytes.len() / 2])
+            })
+        }
+    }
+
+    #[test]
+    fn test_fs_sink_detects_truncated_writes() {
+        let root = std::env::temp_dir().join(format!("zk100-artifacts-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let options = AssembleOptions { inputs: vec![3], expected: vec![3], ..Default::default() };
+        let stage = |fail: bool| {
+            let mut sink = FaultySink { root: root.clone(), fail };
+            let err = assemble(PROGRAM, "p.asm", LEGACY_ABI, &options, &Emit::default(), &mut sink).unwrap_err();
+            let err = err.downcast::<exec::WriteError>().unwrap();
+            assert_eq!(err.path, root.join("args.json"));
+            // Neither the temporary file nor a damaged args.json is left.
+            assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+            err.stage
+        };
+        assert_eq!(stage(false), exec::WriteStage::Verify);
+        assert_eq!(stage(true), exec::WriteStage::Write);
+
+        assemble(PROGRAM, "p.asm", LEGACY_ABI, &options, &Emit::default(), &mut FsSink::new(&root)).unwrap();
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 1);
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_failures_write_nothing() {
+        let emit = Emit { metadata: Some("meta.json".into()), ..Default::default() };
+        let mut sink = MemorySink::default();
+        let moved = AssembleOptions { io: IoNodes { in_node: (0, 1), ..Default::default() }, ..Default::default() };
+        assert!(assemble(PROGRAM, "p.asm", LEGACY_ABI, &moved, &emit, &mut sink).is_err());
+        assert!(assemble("NODE (0,0)\nFROB\n", "p.asm", LEGACY_ABI, &Default::default(), &emit, &mut sink).is_err());
+        assert!(sink.artifacts.is_empty());
+    }
+}
@@ -1,137 +1,2080 @@
-use crate::instruction::{Inst, Op, Src, Dst};
+use crate::abi::IoNodes;
+use crate::dialect::Dialect;
+use crate::directives;
+use crate::expr;
+use crate::grid::ProgramGrid;
+use crate::instruction::{Inst, Op, PortTag, Src, Dst};
+use crate::lexer::{self, Token, TokenKind};
+use crate::limits::{Limit, Limits};
+use crate::literals::{self, Lowered, Operand};
+use crate::tis100;
+use crate::trace;
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
 
 pub type Programs = Vec<Vec<Vec<Inst>>>;
 
-pub fn parse_assembly(code: &str) -> Result<Programs> {
-    let mut programs = vec![vec![vec![], vec![]], vec![vec![], vec![]]];
+/// Grid size the guest executes.
+pub const GRID_ROWS: usize = 2;
+pub const GRID_COLS: usize = 2;
+/// The most rows or columns a `GRID` directive or `--rows`/`--cols` declares.
+pub const MAX_GRID_SIDE: usize = 8;
+
+/// Rows and columns of a program grid. The default is the released guest's
+/// `GRID_ROWS` x `GRID_COLS`; any other size needs `abi::GRID_ABI`, which
+/// tells the guest how many node programs follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct GridSize {
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl Default for GridSize {
+    fn default() -> Self {
+        GridSize { rows: GRID_ROWS, cols: GRID_COLS }
+    }
+}
+
+impl fmt::Display for GridSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.rows, self.cols)
+    }
+}
+
+impl GridSize {
+    pub const MAX: GridSize = GridSize { rows: MAX_GRID_SIDE, cols: MAX_GRID_SIDE };
+
+    pub fn new(rows: usize, cols: usize) -> Result<Self> {
+        if !(1..=MAX_GRID_SIDE).contains(&rows) || !(1..=MAX_GRID_SIDE).contains(&cols) {
+            return Err(anyhow!("a grid has 1 to {} rows and columns, not {}x{}", MAX_GRID_SIDE, rows, cols));
+        }
+        Ok(GridSize { rows, cols })
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == GridSize::default()
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    pub fn contains(&self, (r, c): (usize, usize)) -> bool {
+        r < self.rows && c < self.cols
+    }
+
+    /// A grid of this size with every node empty.
+    pub fn empty_programs(&self) -> Programs {
+        vec![vec![vec![]; self.cols]; self.rows]
+    }
+}
+
+/// What one source line declares once comments are dropped.
+pub(crate) enum Line<'a> {
+    Blank,
+    /// `NODE` followed by its coordinate text as written, empty if none,
+    /// and whether it ends in `CONTINUE`.
+    Node(String, bool),
+    /// One or more `name:` prefixes and the instruction tokens after them,
+    /// if any. A lone `:` is a label with an empty name, and `P:UP:` one
+    /// with the `PortPrefix` kind and the port as its name.
+    Label(Vec<Token<'a>>, Vec<Token<'a>>),
+    /// `ASSERT` and the tokens after it.
+    Assert(Vec<Token<'a>>),
+    /// `GRID` and the tokens after it.
+    Grid(Vec<Token<'a>>),
+    /// `DEFINE NAME VALUE`, `NAME EQU VALUE` or `GLOBAL NAME VALUE`, as the
+    /// name, the tokens after it and whether it is `GLOBAL`.
+    Define(Token<'a>, Vec<Token<'a>>, bool),
+    /// A `.name` that is not a label (`.rept 8`), and the tokens after it.
+    Directive(Token<'a>, Vec<Token<'a>>),
+    Instruction(Vec<Token<'a>>),
+}
+
+pub(crate) fn classify<'a>(tokens: &[Token<'a>]) -> Line<'a> {
+    let tokens: Vec<Token<'a>> = tokens
+        .iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .copied()
+        .collect();
+    let mut names = Vec::new();
+    let mut rest = tokens.as_slice();
+    loop {
+        match rest {
+            [colon, after @ ..] if colon.kind == TokenKind::Colon => {
+                names.push(Token { text: "", ..*colon });
+                rest = after;
+            }
+            [name, colon, after @ ..]
+                if colon.kind == TokenKind::Colon
+                    && matches!(name.kind, TokenKind::Ident | TokenKind::Mnemonic | TokenKind::Number) =>
+            {
+                names.push(*name);
+                rest = after;
+            }
+            [prefix, port, colon, after @ ..]
+                if prefix.kind == TokenKind::PortPrefix && prefix.touches(port) && colon.kind == TokenKind::Colon =>
+            {
+                names.push(Token { kind: TokenKind::PortPrefix, col_start: prefix.col_start, ..*port });
+                rest = after;
+            }
+            _ => break,
+        }
+    }
+    if !names.is_empty() {
+        return Line::Label(names, rest.to_vec());
+    }
+    match tokens.as_slice() {
+        [] => Line::Blank,
+        [first, rest @ ..] if first.kind == TokenKind::NodeKeyword => {
+            let continued = rest.last().is_some_and(|t| t.kind == TokenKind::Ident && t.text.eq_ignore_ascii_case("CONTINUE"));
+            let rest = if continued { &rest[..rest.len() - 1] } else { rest };
+            let mut coords = String::new();
+            for (i, token) in rest.iter().enumerate() {
+                if i > 0 && !rest[i - 1].touches(token) {
+                    coords.push(' ');
+                }
+                coords.push_str(token.text);
+            }
+            Line::Node(coords, continued)
+        }
+        [first, rest @ ..] if first.kind == TokenKind::Ident && first.text.eq_ignore_ascii_case("ASSERT") => {
+            Line::Assert(rest.to_vec())
+        }
+        [first, rest @ ..] if first.kind == TokenKind::Ident && first.text.eq_ignore_ascii_case("GRID") => {
+            Line::Grid(rest.to_vec())
+        }
+        [first, name, rest @ ..] if first.kind == TokenKind::Ident && first.text.eq_ignore_ascii_case("DEFINE") => {
+            Line::Define(*name, rest.to_vec(), false)
+        }
+        [first, name, rest @ ..] if first.kind == TokenKind::Ident && first.text.eq_ignore_ascii_case("GLOBAL") => {
+            Line::Define(*name, rest.to_vec(), true)
+        }
+        [name, equ, rest @ ..] if equ.kind == TokenKind::Ident && equ.text.eq_ignore_ascii_case("EQU") => {
+            Line::Define(*name, rest.to_vec(), false)
+        }
+        [first, rest @ ..] if first.kind == TokenKind::Ident && is_local(first.text) => Line::Directive(*first, rest.to_vec()),
+        _ => Line::Instruction(tokens),
+    }
+}
+
+/// Split operand tokens into operands: commas and whitespace separate them,
+/// touching tokens (`P:` `LEFT`) form one operand.
+fn operands(tokens: &[Token]) -> Vec<String> {
+    operand_spans(tokens).into_iter().map(|(_, operand)| operand).collect()
+}
+
+/// The operand `tokens` as one expression's text, for the directives that
+/// take one.
+pub(crate) fn operand_text(tokens: &[Token]) -> String {
+    operands(tokens).join(" ")
+}
+
+/// `operands`, each with the 1-based column it starts at.
+fn operand_spans(tokens: &[Token]) -> Vec<(usize, String)> {
+    let mut operands: Vec<(usize, String)> = Vec::new();
+    let mut prev: Option<&Token> = None;
+    let mut depth = 0;
+    for token in tokens {
+        if token.kind == TokenKind::Comma {
+            prev = None;
+            continue;
+        }
+        match (prev, operands.last_mut()) {
+            (Some(p), Some((_, last))) if joins(p, token, depth) => {
+                if !p.touches(token) {
+                    last.push(' ');
+                }
+                last.push_str(token.text);
+            }
+            _ => operands.push((token.col_start, token.text.to_string())),
+        }
+        depth = nesting(token, depth);
+        prev = Some(token);
+    }
+    operands
+}
+
+/// True if `token` is part of the same operand as `prev` before it: the
+/// two touch (`P:` `LEFT`), or they are in an expression that spaces them
+/// out, inside its parentheses (`depth` of them open) or around one of its
+/// operators (`WIDTH * 2`).
+fn joins(prev: &Token, token: &Token, depth: usize) -> bool {
+    let operator = |t: &Token| t.kind == TokenKind::Unknown && matches!(t.text, "+" | "-" | "*" | "/");
+    prev.touches(token)
+        || depth > 0
+        || operator(prev)
+        || operator(token)
+        || prev.kind == TokenKind::LParen
+        || token.kind == TokenKind::RParen
+}
+
+/// How many parentheses are open after `token`, with `depth` open before it.
+fn nesting(token: &Token, depth: usize) -> usize {
+    match token.kind {
+        TokenKind::LParen => depth + 1,
+        TokenKind::RParen => depth.saturating_sub(1),
+        _ => depth,
+    }
+}
+
+/// `error` from the parse of source line `line_no`, prefixed with where it
+/// happened and followed by the line itself.
+fn located(code: &str, line_no: usize, col: usize, node: Option<String>, error: anyhow::Error) -> anyhow::Error {
+    let source = code.lines().nth(line_no - 1).unwrap_or("").trim();
+    let node = node.map(|node| format!(", node {}", node)).unwrap_or_default();
+    anyhow!("line {}, col {}{}: {} in `{}`", line_no, col, node, error, source)
+}
+
+/// The 1-based column (in characters, as the lexer counts) of the
+/// coordinates on a `NODE` line.
+fn coords_column(line: &str) -> usize {
+    let mut chars = line.chars().skip_while(|c| c.is_whitespace());
+    let keyword = line.chars().count() - chars.clone().count() + "NODE".len();
+    keyword + chars.by_ref().skip("NODE".len()).take_while(|c| c.is_whitespace()).count() + 1
+}
+
+/// Per-node label tables: node coordinates to label name to the pc it marks.
+pub type Labels = BTreeMap<(usize, usize), BTreeMap<String, usize>>;
+
+/// `DEFINE`/`EQU`/`GLOBAL` constants of one scope by name, each the value
+/// it was defined as (`i32::MIN..=u32::MAX`).
+pub type Constants = BTreeMap<String, i64>;
+
+/// The constants every node sees: `--define`s, `GLOBAL`s and the `DEFINE`s
+/// before the first `NODE`, with the line of each `GLOBAL`, which no node
+/// may define again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileScope {
+    pub constants: Constants,
+    pub globals: BTreeMap<String, usize>,
+}
+
+/// The constants one line sees: those of its node, if it is in one, over
+/// the file's. A name in neither fails naming both.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ConstantScope<'a> {
+    pub file: &'a Constants,
+    pub node: Option<((usize, usize), &'a Constants)>,
+}
+
+impl<'a> ConstantScope<'a> {
+    /// The file scope alone, for lines outside any node.
+    pub(crate) fn file(constants: &'a Constants) -> Self {
+        ConstantScope { file: constants, node: None }
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<i64> {
+        self.node.and_then(|(_, node)| node.get(name)).or_else(|| self.file.get(name)).copied()
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// The value constant `name` holds, for an expression.
+    pub(crate) fn value(&self, name: &str) -> Result<i64> {
+        self.get(name).ok_or_else(|| self.undefined(name))
+    }
+
+    pub(crate) fn undefined(&self, name: &str) -> anyhow::Error {
+        let names = |constants: &Constants| match constants.is_empty() {
+            true => "none".to_string(),
+            false => constants.keys().map(String::as_str).collect::<Vec<_>>().join(", "),
+        };
+        let node = match self.node {
+            Some(((r, c), node)) => format!("node ({},{}): {}; ", r, c, names(node)),
+            None => String::new(),
+        };
+        anyhow!("undefined constant '{}' (searched {}file: {})", name, node, names(self.file))
+    }
+}
+
+/// Per-node label provenance: pc to the label its `Src::Lit` operand was
+/// resolved from. Literal operands written as numbers have no entry.
+pub type LabelRefs = BTreeMap<(usize, usize), BTreeMap<usize, String>>;
+
+/// Source lines of each node's `NODE` blocks, header included: half-open
+/// ranges of 1-based line numbers, in source order. A node reopened by a
+/// second header has two ranges.
+pub type NodeRanges = BTreeMap<(usize, usize), Vec<Range<usize>>>;
+
+/// Stands in for an instruction a recovering parse could not read: a `NOP`
+/// with a literal no source line or word decodes to. It executes as a `NOP`,
+/// but `encode_programs` and the program root refuse it.
+pub const PLACEHOLDER: Inst = Inst { op: Op::Nop, src: Src::Lit(u32::MAX), dst: Dst::Nil };
+
+/// How `parse_with_options` treats errors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Keep going past errors in the code (a bad instruction, label or
+    /// `ASSERT`), recording each in `ParseResult::errors`, for the LSP and
+    /// other tools that want the rest of the file. A bad instruction becomes
+    /// a `PLACEHOLDER` holding its pc, so the pcs and labels after it stay
+    /// what the author wrote. A bad `NODE` header is an error too, and the
+    /// lines under it are skipped rather than read into the node before.
+    /// Without `recover` the parse reads on all the same, then fails with
+    /// every error it found (`AssemblyErrors`).
+    pub recover: bool,
+    /// A node over `node_instructions` fails the parse, recovering or not.
+    pub limits: Limits,
+    /// Build `MOV`s of literals too wide for the instruction word in ACC
+    /// over several instructions (`literals::lower`) instead of failing on
+    /// them. The limits apply to the expanded nodes.
+    pub synthesize_literals: bool,
+    /// The grid size from `--rows`/`--cols`. A `GRID` directive in the
+    /// source must agree with it; with neither, the grid is the default.
+    pub grid: Option<GridSize>,
+    /// Ignore operands past the ones an op takes, with a warning, instead
+    /// of failing on them (`--lenient`), as older versions did silently.
+    pub lenient: bool,
+    /// Keep labels that differ only in case apart (`--case-sensitive-labels`).
+    /// By default `Loop:` is the label `JMP LOOP` jumps to, and defining
+    /// both is a duplicate.
+    pub case_sensitive_labels: bool,
+    /// Constants from `--define`, defined before the first line and over
+    /// any `DEFINE` of the same name (`constants_with`).
+    pub defines: Constants,
+    /// Pad every node with code to this many instructions (`--pad-to`). A
+    /// `.pad` line in the source must agree with it.
+    pub pad_to: Option<usize>,
+    /// Hold the source to what TIS-100 runs (`--tis100`), with its streams
+    /// at these nodes: TIS-100 mnemonics and operands only (`tis100::op`,
+    /// `tis100::Strict`), at most `tis100::MAX_NODE_INSTRUCTIONS` per node and
+    /// none of zk-100's directives. Every violation is collected, then the
+    /// parse fails with them grouped by node (`tis100::report`).
+    pub tis100: Option<IoNodes>,
+}
+
+/// A problem in the source that does not stop assembly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based source line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Every error of a parse that did not `recover`, in line order: the parse
+/// reads the whole file before failing, so one run shows them all. Errors
+/// that leave nothing to read on (an unreadable `GRID` or `DEFINE`) still
+/// fail it alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyErrors {
+    pub errors: Vec<AssemblyError>,
+}
+
+/// One error of `AssemblyErrors`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    /// 1-based source line.
+    pub line: usize,
+    /// The node whose block the line is in, if any.
+    pub node: Option<(usize, usize)>,
+    /// The error as a parse failing on it alone reports it, line included.
+    pub message: String,
+}
+
+impl AssemblyErrors {
+    /// `errors`, whose messages are as reported, placed in the blocks of
+    /// `ranges`.
+    fn new(errors: &[Diagnostic], ranges: &NodeRanges) -> Self {
+        let errors = errors
+            .iter()
+            .map(|d| AssemblyError { line: d.line, node: node_at(ranges, d.line), message: d.message.clone() })
+            .collect();
+        AssemblyErrors { errors }
+    }
+}
+
+impl fmt::Display for AssemblyErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let [error] = self.errors.as_slice() {
+            return write!(f, "{}", error.message);
+        }
+        write!(f, "{} errors", self.errors.len())?;
+        for error in &self.errors {
+            write!(f, "\n  {}", error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AssemblyErrors {}
+
+/// The node whose block in `ranges` holds `line`, if any.
+pub(crate) fn node_at(ranges: &NodeRanges, line: usize) -> Option<(usize, usize)> {
+    ranges.iter().find(|(_, blocks)| blocks.iter().any(|b| b.contains(&line))).map(|(&node, _)| node)
+}
+
+/// How an `ASSERT` compares `ACC` with its literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    const ALL: [(&'static str, Cmp); 6] =
+        [("==", Cmp::Eq), ("!=", Cmp::Ne), ("<", Cmp::Lt), ("<=", Cmp::Le), (">", Cmp::Gt), (">=", Cmp::Ge)];
+
+    fn symbol(self) -> &'static str {
+        Cmp::ALL.iter().find(|(_, cmp)| *cmp == self).map(|(symbol, _)| *symbol).unwrap()
+    }
+}
+
+/// `ASSERT ACC <cmp> <literal>`: a debugging check the simulator makes
+/// whenever its node is about to execute `pc`. Asserts are not instructions;
+/// they never reach `prog_words` or the program root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assert {
+    pub cmp: Cmp,
+    /// Compared as signed, like `JGZ` and `JLZ` read `ACC`.
+    pub value: i32,
+    pub line: usize,
+}
+
+impl Assert {
+    pub fn holds(&self, acc: u32) -> bool {
+        let acc = acc as i32;
+        match self.cmp {
+            Cmp::Eq => acc == self.value,
+            Cmp::Ne => acc != self.value,
+            Cmp::Lt => acc < self.value,
+            Cmp::Le => acc <= self.value,
+            Cmp::Gt => acc > self.value,
+            Cmp::Ge => acc >= self.value,
+        }
+    }
+}
+
+impl fmt::Display for Assert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ASSERT ACC {} {}", self.cmp.symbol(), self.value)
+    }
+}
+
+/// Per-node asserts by the pc they guard, in source order.
+pub type Asserts = BTreeMap<(usize, usize), BTreeMap<usize, Vec<Assert>>>;
+
+/// Everything `parse` learns from a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseResult {
+    pub grid: ProgramGrid,
+    /// Every node that has a `NODE` header appears, even without labels.
+    pub labels: Labels,
+    /// The file scope's constants (`FileScope::constants`).
+    pub constants: Constants,
+    /// The constants each node's `DEFINE`s add, which only it sees.
+    pub node_constants: BTreeMap<(usize, usize), Constants>,
+    /// 1-based source line of each instruction, per node in pc order.
+    pub lines: BTreeMap<(usize, usize), Vec<usize>>,
+    /// 1-based source line of each label's (last) definition.
+    pub label_lines: BTreeMap<(usize, usize), BTreeMap<String, usize>>,
+    pub label_refs: LabelRefs,
+    pub node_ranges: NodeRanges,
+    pub diagnostics: Vec<Diagnostic>,
+    pub asserts: Asserts,
+    /// Errors a recovering parse skipped, by line. Never set otherwise.
+    pub errors: Vec<Diagnostic>,
+    /// The name a `NODE` header gave each node declared by name rather
+    /// than coordinates (`TOP_LEFT`, `@3`).
+    pub node_names: BTreeMap<(usize, usize), String>,
+    /// How many `NOP`s `.pad`/`--pad-to` appended to the end of each node it
+    /// lengthened. They have no source line.
+    pub padding: BTreeMap<(usize, usize), usize>,
+}
+
+/// A label with everything that refers to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct LabelSymbol {
+    pub name: String,
+    pub pc: usize,
+    /// 1-based source line of the definition.
+    pub line: usize,
+    /// Pcs whose operand was resolved from this label.
+    pub references: Vec<usize>,
+}
+
+/// A jump instruction and the label its target came from, if any.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct JumpSymbol {
+    pub pc: usize,
+    /// The pc jumped to.
+    pub target: u32,
+    /// The label the target was written as; null for a number.
+    pub label: Option<String>,
+}
+
+/// An `ASSERT` and the pc it guards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct AssertSymbol {
+    /// The instruction the assertion is checked before.
+    pub pc: usize,
+    pub line: usize,
+    pub condition: String,
+}
+
+/// The `--symbols` view of one node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct NodeSymbols {
+    /// Row and column.
+    pub node: (usize, usize),
+    pub labels: Vec<LabelSymbol>,
+    pub jumps: Vec<JumpSymbol>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub asserts: Vec<AssertSymbol>,
+}
+
+impl ParseResult {
+    /// `node` as diagnostics and listings show it, see `node_label`.
+    pub fn node_label(&self, node: (usize, usize)) -> String {
+        node_label(node, &self.node_names)
+    }
+
+    /// The source line of `node`'s instruction at `pc`; none for the `NOP`s
+    /// padding appended, which no line wrote.
+    pub fn source_line(&self, node: (usize, usize), pc: usize) -> Option<usize> {
+        self.lines.get(&node).and_then(|lines| lines.get(pc)).copied()
+    }
+
+    /// True if a recovering parse skipped errors: the grid may hold
+    /// `PLACEHOLDER`s and must not be encoded.
+    pub fn is_partial(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Labels and jumps per node, cross-referenced both ways.
+    pub fn symbols(&self) -> Vec<NodeSymbols> {
+        let mut nodes = Vec::new();
+        for (&(r, c), labels) in &self.labels {
+            let refs = self.label_refs.get(&(r, c));
+            let labels = labels
+                .iter()
+                .map(|(name, &pc)| LabelSymbol {
+                    name: name.clone(),
+                    pc,
+                    line: self.label_lines[&(r, c)][name],
+                    references: refs
+                        .into_iter()
+                        .flatten()
+                        .filter(|(_, label)| *label == name)
+                        .map(|(&at, _)| at)
+                        .collect(),
+                })
+                .collect();
+            let jumps = self.grid.programs[r][c]
+                .iter()
+                .enumerate()
+                .filter_map(|(pc, inst)| match (inst.op, inst.src) {
+                    (Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz, Src::Lit(target)) => Some(JumpSymbol {
+                        pc,
+                        target,
+                        label: refs.and_then(|refs| refs.get(&pc)).cloned(),
+                    }),
+                    _ => None,
+                })
+                .collect();
+            let asserts = self
+                .asserts
+                .get(&(r, c))
+                .into_iter()
+                .flatten()
+                .flat_map(|(&pc, asserts)| {
+                    asserts.iter().map(move |a| AssertSymbol { pc, line: a.line, condition: a.to_string() })
+                })
+                .collect();
+            nodes.push(NodeSymbols { node: (r, c), labels, jumps, asserts });
+        }
+        nodes
+    }
+
+    /// One warning per `IN` read outside `io.in_node`; those nodes never
+    /// receive a value and block forever.
+    pub fn io_warnings(&self, io: &IoNodes) -> Vec<Diagnostic> {
+        let mut warnings = Vec::new();
+        for (r, row) in self.grid.programs.iter().enumerate() {
+            for (c, program) in row.iter().enumerate() {
+                if (r, c) == io.in_node {
+                    continue;
+                }
+                for (pc, inst) in program.iter().enumerate() {
+                    if let (Src::In, Some(line)) = (inst.src, self.source_line((r, c), pc)) {
+                        warnings.push(Diagnostic {
+                            line,
+                            message: format!(
+                                "node {} reads IN, but the input stream goes to node ({},{})",
+                                self.node_label((r, c)),
+                                io.in_node.0,
+                                io.in_node.1
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        warnings.sort_by_key(|d| d.line);
+        warnings
+    }
+
+    /// One warning per `LAST` use its node can reach from pc 0. Only an
+    /// `ANY` transfer sets `LAST` and this ISA has no `ANY` port, so every
+    /// reachable use comes before anything set it and blocks in the guest.
+    /// Uses behind unconditional jumps are not reported.
+    pub fn last_warnings(&self) -> Vec<Diagnostic> {
+        let mut warnings = Vec::new();
+        for (r, row) in self.grid.programs.iter().enumerate() {
+            for (c, program) in row.iter().enumerate() {
+                let reached = reachable(program);
+                for (pc, inst) in program.iter().enumerate() {
+                    let Some(line) = self.source_line((r, c), pc) else {
+                        continue;
+                    };
+                    if reached[pc] && (inst.src == Src::Last || inst.dst == Dst::Last) {
+                        warnings.push(Diagnostic {
+                            line,
+                            message: format!(
+                                "node {} can reach LAST before any ANY transfer sets it, and there is no ANY port; the guest blocks here",
+                                self.node_label((r, c))
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        warnings.sort_by_key(|d| d.line);
+        warnings
+    }
+
+    /// One warning per port operand that points off the edge of the grid,
+    /// where no node will ever answer and the transfer blocks forever.
+    /// `IN` and `OUT` are not ports and never warn.
+    pub fn port_warnings(&self) -> Vec<Diagnostic> {
+        let size = self.grid.size();
+        let mut warnings = Vec::new();
+        for (r, row) in self.grid.programs.iter().enumerate() {
+            for (c, program) in row.iter().enumerate() {
+                for (pc, inst) in program.iter().enumerate() {
+                    let Some(line) = self.source_line((r, c), pc) else {
+                        continue;
+                    };
+                    let src = match inst.src {
+                        Src::P(port) => Some(("reads", port)),
+                        _ => None,
+                    };
+                    let dst = match inst.dst {
+                        Dst::P(port) => Some(("writes", port)),
+                        _ => None,
+                    };
+                    for (access, port) in src.into_iter().chain(dst) {
+                        let edge = match port {
+                            PortTag::Up if r == 0 => "top",
+                            PortTag::Down if r + 1 == size.rows => "bottom",
+                            PortTag::Left if c == 0 => "left",
+                            PortTag::Right if c + 1 == size.cols => "right",
+                            _ => continue,
+                        };
+                        warnings.push(Diagnostic {
+                            line,
+                            message: format!(
+                                "node {} pc {} {} P:{}, but the node is on the {} edge of the {} grid; the transfer blocks forever",
+                                self.node_label((r, c)),
+                                pc,
+                                access,
+                                port,
+                                edge,
+                                size
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        warnings.sort_by_key(|d| d.line);
+        warnings
+    }
+
+    /// One warning per instruction its node can never run, walking from pc 0
+    /// through fallthroughs and literal jump targets. `HLT` is a `NOP` in
+    /// the guest, so what follows it still runs and is not reported.
+    pub fn unreachable_warnings(&self) -> Vec<Diagnostic> {
+        let mut warnings = Vec::new();
+        for (r, row) in self.grid.programs.iter().enumerate() {
+            for (c, program) in row.iter().enumerate() {
+                // Padding behind a node's last jump is never reached by
+                // design, and has no line to warn on.
+                for (pc, reached) in reachable(program).into_iter().enumerate() {
+                    if let (false, Some(line)) = (reached, self.source_line((r, c), pc)) {
+                        warnings.push(Diagnostic {
+                            line,
+                            message: format!("node {} pc {} is never reached from pc 0", self.node_label((r, c)), pc),
+                        });
+                    }
+                }
+            }
+        }
+        warnings.sort_by_key(|d| d.line);
+        warnings
+    }
+
+    /// One warning per label nothing refers to, in source order.
+    pub fn unused_labels(&self) -> Vec<Diagnostic> {
+        let mut unused: Vec<Diagnostic> = self
+            .symbols()
+            .into_iter()
+            .flat_map(|node| {
+                let label = self.node_label(node.node);
+                node.labels.into_iter().filter(|l| l.references.is_empty()).map(move |l| Diagnostic {
+                    line: l.line,
+                    message: format!("label '{}' in node {} is never referenced", l.name, label),
+                })
+            })
+            .collect();
+        unused.sort_by_key(|d| d.line);
+        unused
+    }
+}
+
+/// Which pcs of `program` can run, walking forward from pc 0 with the
+/// guest's wrap-around. A jump to anything but a literal may go anywhere.
+fn reachable(program: &[Inst]) -> Vec<bool> {
+    let len = program.len();
+    let mut reached = vec![false; len];
+    let mut pending = if len == 0 { Vec::new() } else { vec![0] };
+    while let Some(pc) = pending.pop() {
+        if std::mem::replace(&mut reached[pc], true) {
+            continue;
+        }
+        let inst = program[pc];
+        let jump = |pending: &mut Vec<usize>| match inst.src {
+            Src::Lit(target) => pending.push(target as usize % len),
+            _ => pending.extend(0..len),
+        };
+        match inst.op {
+            Op::Jmp => jump(&mut pending),
+            Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz => {
+                jump(&mut pending);
+                pending.push((pc + 1) % len);
+            }
+            _ => pending.push((pc + 1) % len),
+        }
+    }
+    reached
+}
+
+/// Parse assembly source, keeping the label tables used to resolve jumps.
+pub fn parse(code: &str) -> Result<ParseResult> {
+    parse_with(code, &Dialect::default())
+}
+
+/// `parse`, accepting the mnemonic aliases of `dialect`.
+pub fn parse_with(code: &str, dialect: &Dialect) -> Result<ParseResult> {
+    parse_with_options(code, dialect, ParseOptions::default())
+}
+
+/// `parse_with`, recovering from errors as `options` says.
+pub fn parse_with_options(code: &str, dialect: &Dialect, options: ParseOptions) -> Result<ParseResult> {
+    let scope = file_scope(code, &options.defines)?;
+    parse_with_constants(code, dialect, options, scope)
+}
+
+/// An instruction's tokens and the label whose scope it is in.
+type ScopedLine<'a> = (Vec<Token<'a>>, Option<String>);
+
+/// `parse_with_options` with the file scope of the whole file, for a `code`
+/// that is one node's block of it; the file's `DEFINE` lines are not read
+/// again.
+pub(crate) fn parse_with_constants(
+    code: &str,
+    dialect: &Dialect,
+    options: ParseOptions,
+    file: FileScope,
+) -> Result<ParseResult> {
+    let span = trace::span!("parse_assembly", lines, instructions);
+    span.record("lines", code.lines().count() as u64);
+    let size = resolve_grid(code, options.grid)?;
+    let mut programs = size.empty_programs();
     let mut current_node: Option<(usize, usize)> = None;
+    let mut diagnostics = Vec::new();
+    let mut errors = Vec::new();
+    // Every error is collected, and without `options.recover` the parse
+    // fails at the end with them all. `recover` keeps each error's message
+    // alone for a caller that reports it by line (the LSP, `tis100::report`);
+    // otherwise it is kept as `AssemblyErrors` reports it.
+    let strict = options.tis100.is_some();
+    let recover = options.recover || strict;
+    if strict {
+        errors.extend(tis100_extensions(code));
+    }
     
-    // First pass: parse instructions and collect labels
-    let mut node_labels: HashMap<(usize, usize), HashMap<String, usize>> = HashMap::new();
-    let mut node_instructions: HashMap<(usize, usize), Vec<(String, Option<String>)>> = HashMap::new();
+    // First pass: classify lines and collect labels
+    let mut node_labels: Labels = BTreeMap::new();
+    let mut label_lines: BTreeMap<(usize, usize), BTreeMap<String, usize>> = BTreeMap::new();
+    let mut node_instructions: BTreeMap<(usize, usize), Vec<ScopedLine>> = BTreeMap::new();
+    let mut asserts: Asserts = BTreeMap::new();
+    let mut ranges = RangeTracker::default();
+    let mut headers: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    let mut node_names: BTreeMap<(usize, usize), String> = BTreeMap::new();
+    // The last non-local label of the current `NODE` block, which owns the
+    // `.local` labels after it.
+    let mut scope: Option<String> = None;
+    // Under a `NODE` header that failed, until the next header.
+    let mut skipping = false;
     
-    for line in code.lines() {
-        let line = line.trim();
-        
-        // Skip empty lines and comments
-        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+    let expanded = directives::expand(code, &file.constants)?;
+    let (node_constants, mut define_problems) = node_constants(&expanded, size, &file, &options.defines);
+    let empty = Constants::new();
+    let constant_scope =
+        |node| ConstantScope { file: &file.constants, node: Some((node, node_constants.get(&node).unwrap_or(&empty))) };
+    for (line_no, line) in expanded {
+        let tokens = lexer::lex_line(line, line_no);
+        let classified = classify(&tokens);
+        let header = matches!(classified, Line::Node(..));
+        if skipping && !header {
             continue;
         }
-        
-        // Handle node declaration
-        if line.starts_with("NODE") {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                let coords = parse_node_coords(parts[1])?;
+        // A `NODE` header is checked by `parse_node_line`; skipping it would
+        // put the lines after it in the node before.
+        if let Some((col, message)) = non_ascii(&tokens).filter(|_| !header) {
+            let message = match recover {
+                true => message,
+                false => located(code, line_no, col, current_node.map(|n| node_label(n, &node_names)), anyhow!(message)).to_string(),
+            };
+            errors.push(Diagnostic { line: line_no, message });
+            continue;
+        }
+        let problem = match (classified, current_node) {
+            // `declared_grid` and `file_scope` have checked these.
+            (Line::Blank | Line::Grid(_), _) | (Line::Define(..), None) => None,
+            // `node_constants` has read these.
+            (Line::Define(..), Some(_)) => define_problems.remove(&line_no),
+            // `directives::expand` has applied the others.
+            (Line::Directive(name, _), _) => directives::unknown_directive(&name),
+            (Line::Node(text, continued), _) => {
+                let opened = parse_node_line(&text, size)
+                    .map_err(|e| (e.to_string(), located(code, line_no, coords_column(line), None, e).to_string()))
+                    .and_then(|coords| match reopen_error(coords, continued, headers.get(&coords).copied(), line_no) {
+                        Some(message) => Err((message.clone(), format!("line {}: {}", line_no, message))),
+                        None => Ok(coords),
+                    });
+                let coords = match opened {
+                    Ok(coords) => coords,
+                    // Its block is read into no node, so the mistake shows
+                    // once rather than as errors on every line under it.
+                    Err((message, reported)) => {
+                        errors.push(Diagnostic { line: line_no, message: if recover { message } else { reported } });
+                        ranges.close(line_no);
+                        current_node = None;
+                        skipping = true;
+                        continue;
+                    }
+                };
+                skipping = false;
+                if let Some(name) = node_name(&text) {
+                    node_names.entry(coords).or_insert(name);
+                }
+                headers.entry(coords).or_insert(line_no);
+                ranges.open(coords, line_no);
                 current_node = Some(coords);
-                node_labels.entry(coords).or_insert_with(HashMap::new);
-                node_instructions.entry(coords).or_insert_with(Vec::new);
+                scope = None;
+                node_labels.entry(coords).or_default();
+                label_lines.entry(coords).or_default();
+                node_instructions.entry(coords).or_default();
+                None
+            }
+            (Line::Label(names, _), None) => Some(match names[0].text {
+                "" => EMPTY_LABEL.to_string(),
+                name => before_first_node(&format!("label '{}'", name)),
+            }),
+            (Line::Assert(_), None) => Some(before_first_node("ASSERT")),
+            (Line::Instruction(_), None) => Some(before_first_node("instruction")),
+            // Labels are scoped to their node; the same name in another node is fine.
+            // Every label on the line names the instruction that follows them.
+            (Line::Label(names, instruction), Some(node)) => {
+                let pc = node_instructions[&node].len();
+                let mut problem = None;
+                for token in &names {
+                    let name = token.text;
+                    let lines = label_lines.get_mut(&node).unwrap();
+                    let key = label_key(name, scope.as_deref());
+                    if name.is_empty() {
+                        problem.get_or_insert_with(|| EMPTY_LABEL.to_string());
+                    } else if name == "." {
+                        problem.get_or_insert_with(|| EMPTY_LOCAL_LABEL.to_string());
+                    } else if let Some(message) = reserved_label(token, dialect) {
+                        problem.get_or_insert(message);
+                    } else if strict && is_local(name) {
+                        problem.get_or_insert_with(|| format!("local label '{}' is a zk-100 extension", name));
+                    } else if let Some((earlier, first)) = find_label(lines, &key, options.case_sensitive_labels) {
+                        problem.get_or_insert_with(|| duplicate_label(&key, earlier, node, (first, line_no)));
+                    } else {
+                        lines.insert(key.clone(), line_no);
+                        node_labels.get_mut(&node).unwrap().insert(key, pc);
+                    }
+                    if !name.is_empty() && !is_local(name) {
+                        scope = Some(name.to_string());
+                    }
+                }
+                if !instruction.is_empty() {
+                    node_instructions.get_mut(&node).unwrap().push((instruction, scope.clone()));
+                }
+                problem
+            }
+            (Line::Assert(tokens), Some(node)) => {
+                let pc = node_instructions.get(&node).map_or(0, Vec::len);
+                match parse_assert(&tokens, line_no, &constant_scope(node)) {
+                    Ok(assert) => {
+                        asserts.entry(node).or_default().entry(pc).or_default().push(assert);
+                        None
+                    }
+                    Err(e) => Some(e.to_string()),
+                }
+            }
+            // Store instruction tokens for later parsing
+            (Line::Instruction(tokens), Some(node)) => {
+                node_instructions.get_mut(&node).unwrap().push((tokens, scope.clone()));
+                None
+            }
+        };
+        if let Some(message) = problem {
+            let message = if recover { message } else { format!("line {}: {}", line_no, message) };
+            errors.push(Diagnostic { line: line_no, message });
+        }
+    }
+    
+    let node_ranges = ranges.finish(code.lines().count());
+    
+    // Second pass: parse instructions with label knowledge, lowering each
+    // to the instructions the guest runs. `starts` is where each source pc
+    // begins in the lowered node, and `cols` the column of the operand each
+    // lowered pc came from, for the jump checks.
+    let mut lines = BTreeMap::new();
+    let mut label_refs: LabelRefs = BTreeMap::new();
+    let mut cols: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+    for ((r, c), inst_lines) in node_instructions {
+        let labels = &node_labels[&(r, c)];
+        let node_lines: &mut Vec<usize> = lines.entry((r, c)).or_default();
+        let node_cols = cols.entry((r, c)).or_default();
+        let mut starts = Vec::with_capacity(inst_lines.len() + 1);
+
+        for (mut tokens, scope) in inst_lines {
+            let pc = programs[r][c].len();
+            starts.push(pc);
+            if let Some((kept, _, message)) = extra_operands(&tokens, dialect).filter(|_| options.lenient) {
+                diagnostics.push(Diagnostic { line: tokens[0].line, message: format!("{}; ignoring them (--lenient)", message) });
+                tokens.truncate(kept);
+            }
+            let comma = dialect.op(tokens[0].text).ok().and_then(|op| comma_error(op, &tokens[1..]));
+            if let Some((_, message)) = comma.filter(|_| options.lenient) {
+                diagnostics.push(Diagnostic { line: tokens[0].line, message: format!("{} (--lenient)", message) });
             }
+            let lowered = match parse_instruction_at(&tokens, (r, c), labels, scope.as_deref(), &constant_scope((r, c)), dialect, &options) {
+                Ok(lowered) => lowered,
+                Err((col, e)) => {
+                    let message = match recover {
+                        true => e.to_string(),
+                        false => located(code, tokens[0].line, col, Some(node_label((r, c), &node_names)), e).to_string(),
+                    };
+                    errors.push(Diagnostic { line: tokens[0].line, message });
+                    programs[r][c].push(PLACEHOLDER);
+                    node_lines.push(tokens[0].line);
+                    node_cols.push(tokens[0].col_start);
+                    continue;
+                }
+            };
+            if let Some(message) = lowered.warning {
+                diagnostics.push(Diagnostic { line: tokens[0].line, message });
+            }
+            // Remember which label a literal operand came from
+            let is_jump = matches!(lowered.steps[0].op, Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz);
+            let operand = operands(&tokens[1..]).into_iter().next().filter(|_| is_jump);
+            let resolve = |s: &str| resolve_label(labels, s, scope.as_deref(), options.case_sensitive_labels);
+            let label = operand.and_then(|s| resolve(&s).or_else(|| split_offset(&s).and_then(|(name, _)| resolve(name))));
+            if let Some((label, _)) = label {
+                label_refs.entry((r, c)).or_default().insert(pc, label.to_string());
+            }
+            let col = operand_spans(&tokens[1..]).first().map_or(tokens[0].col_start, |(col, _)| *col);
+            let count = lowered.steps.len();
+            programs[r][c].extend(lowered.steps);
+            node_lines.extend(std::iter::repeat_n(tokens[0].line, count));
+            node_cols.extend(std::iter::repeat_n(col, count));
+        }
+        starts.push(programs[r][c].len());
+        literals::retarget(&mut programs[r][c], &starts, node_labels.get_mut(&(r, c)), asserts.get_mut(&(r, c)));
+        if let Some(&line) = node_lines.get(tis100::MAX_NODE_INSTRUCTIONS).filter(|_| strict) {
+            let message = format!(
+                "node ({},{}) has {} instructions, more than the {} a TIS-100 node holds",
+                r,
+                c,
+                node_lines.len(),
+                tis100::MAX_NODE_INSTRUCTIONS
+            );
+            errors.push(Diagnostic { line, message });
+        }
+    }
+    
+    // An assert after a node's last instruction guards nothing.
+    for (&(r, c), node_asserts) in &mut asserts {
+        let len = programs[r][c].len();
+        if let Some(assert) = node_asserts.get(&len).and_then(|a| a.first()) {
+            let message =
+                format!("ASSERT after the last instruction of node ({},{}); move it before an instruction", r, c);
+            let message = if recover { message } else { format!("line {}: {}", assert.line, message) };
+            errors.push(Diagnostic { line: assert.line, message });
+            node_asserts.remove(&len);
+        }
+    }
+    errors.sort_by_key(|d| d.line);
+    diagnostics.sort_by_key(|d| d.line);
+
+    let mut parsed = ParseResult {
+        grid: ProgramGrid::new(programs),
+        labels: node_labels,
+        constants: file.constants,
+        node_constants,
+        lines,
+        label_lines,
+        label_refs,
+        node_ranges,
+        diagnostics,
+        asserts,
+        errors,
+        node_names,
+        padding: BTreeMap::new(),
+    };
+    check_jumps(&mut parsed, code, &cols, recover);
+    if !options.recover && !parsed.errors.is_empty() {
+        return Err(match strict {
+            true => tis100::report(&parsed.errors, &parsed.node_ranges),
+            false => AssemblyErrors::new(&parsed.errors, &parsed.node_ranges).into(),
+        });
+    }
+    // After the jump checks, so a jump past the code is still caught, and
+    // before the limits, which apply to the padded nodes.
+    if let Some(target) = resolve_pad(code, options.pad_to, &parsed.constants)? {
+        pad_nodes(&mut parsed, target, options.limits)?;
+    }
+
+    for (r, row) in parsed.grid.programs.iter().enumerate() {
+        for (c, program) in row.iter().enumerate() {
+            let count = program.len() as u64;
+            options.limits.check(Limit::NodeInstructions, count, || format!("node ({},{}) has {} instructions", r, c, count))?;
+        }
+    }
+    span.record("instructions", parsed.grid.programs.iter().flatten().map(|p| p.len() as u64).sum());
+    Ok(parsed)
+}
+
+/// Appends `NOP`s to every node with code until it has `target`
+/// instructions. An empty node stays empty: it halts, where `NOP`s would keep
+/// it running and the run from ever deadlocking.
+fn pad_nodes(parsed: &mut ParseResult, target: usize, limits: Limits) -> Result<()> {
+    let size = parsed.grid.size();
+    for (r, c) in (0..size.nodes()).map(|i| (i / size.cols, i % size.cols)) {
+        let len = parsed.grid.programs[r][c].len();
+        if len == 0 {
             continue;
         }
-        
-        // Handle labels
-        if line.ends_with(':') {
-            if let Some((r, c)) = current_node {
-                let label_name = line.trim_end_matches(':');
-                let inst_count = node_instructions.get(&(r, c)).map(|v| v.len()).unwrap_or(0);
-                node_labels.get_mut(&(r, c)).unwrap().insert(label_name.to_string(), inst_count);
+        if len > target {
+            return Err(anyhow!(
+                "node {} has {} instructions, more than the {} every node is padded to",
+                parsed.node_label((r, c)),
+                len,
+                target
+            ));
+        }
+        // Checked before the `NOP`s exist, to not build a node past the limit.
+        limits.check(Limit::NodeInstructions, target as u64, || format!("node ({},{}) padded to {} instructions", r, c, target))?;
+        if len < target {
+            parsed.grid.programs[r][c].resize(target, Inst { op: Op::Nop, src: Src::Nil, dst: Dst::Nil });
+            parsed.padding.insert((r, c), target - len);
+        }
+    }
+    Ok(())
+}
+
+/// Records in `errors` each jump target past the end of its node, and each
+/// one the instruction word has no room for once `literals::retarget` has
+/// moved it, at the column of the jump's operand in `cols`; as reported
+/// unless `recover`.
+fn check_jumps(parsed: &mut ParseResult, code: &str, cols: &BTreeMap<(usize, usize), Vec<usize>>, recover: bool) {
+    for (r, row) in parsed.grid.programs.iter().enumerate() {
+        for (c, program) in row.iter().enumerate() {
+            for (pc, inst) in program.iter().enumerate() {
+                let Some(message) = literal_error(inst).or_else(|| jump_error(inst, pc, program.len())) else {
+                    continue;
+                };
+                let message = match parsed.label_refs.get(&(r, c)).and_then(|refs| refs.get(&pc)) {
+                    Some(label) => format!("{} (label '{}')", message, label),
+                    None => message,
+                };
+                let line = parsed.lines.get(&(r, c)).and_then(|lines| lines.get(pc)).copied().unwrap_or_default();
+                let message = match recover {
+                    true => message,
+                    false => {
+                        let col = cols.get(&(r, c)).and_then(|cols| cols.get(pc)).copied().unwrap_or(1);
+                        located(code, line, col, Some(parsed.node_label((r, c))), anyhow!(message)).to_string()
+                    }
+                };
+                parsed.errors.push(Diagnostic { line, message });
+            }
+        }
+    }
+    parsed.errors.sort_by_key(|d| d.line);
+}
+
+/// Why the jump `inst` at `pc` leaves a node of `len` instructions: the
+/// guest takes its literal target modulo `len`, so a target of `len` or more
+/// lands somewhere else, and a jump to exactly `len` restarts at pc 0
+/// rather than halting.
+fn jump_error(inst: &Inst, pc: usize, len: usize) -> Option<String> {
+    let (Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz, Src::Lit(target)) = (inst.op, inst.src) else {
+        return None;
+    };
+    let target = target as usize;
+    if target < len {
+        return None;
+    }
+    Some(format!(
+        "pc {} jumps to {}, past the end of the node's {} instructions (0..={}); the guest would land on pc {}",
+        pc,
+        target,
+        len,
+        len - 1,
+        target % len
+    ))
+}
+
+/// Why `inst` cannot be encoded as it is: a literal over
+/// `literals::MAX_LITERAL`, which `literals::lower` lets through only as a
+/// label's pc.
+fn literal_error(inst: &Inst) -> Option<String> {
+    let Src::Lit(value) = inst.src else {
+        return None;
+    };
+    if value <= literals::MAX_LITERAL || *inst == PLACEHOLDER {
+        return None;
+    }
+    Some(literals::too_wide(inst.op, inst.dst, i64::from(value)))
+}
+
+/// The operands of an `ASSERT` line: `ACC`, a comparison and a literal,
+/// which may be an expression over the constants of `constants`.
+pub(crate) fn parse_assert(tokens: &[Token], line: usize, constants: &ConstantScope) -> Result<Assert> {
+    let usage = || anyhow!("expected ASSERT ACC <op> <literal>, with op one of == != < <= > >=");
+    let [register, rest @ ..] = tokens else {
+        return Err(usage());
+    };
+    let split = rest.iter().position(|t| !t.text.chars().all(|c| "=!<>".contains(c))).unwrap_or(rest.len());
+    let (op, literal) = rest.split_at(split);
+    if !register.text.eq_ignore_ascii_case("ACC") || literal.is_empty() {
+        return Err(usage());
+    }
+    let op: String = op.iter().map(|t| t.text).collect();
+    let cmp = Cmp::ALL.iter().find(|(symbol, _)| *symbol == op).map(|(_, cmp)| *cmp).ok_or_else(usage)?;
+    let text = operand_text(literal);
+    let value = match literal {
+        [name] if name.kind != TokenKind::Ident && name.kind != TokenKind::Number => return Err(usage()),
+        _ => expr::eval_i64(&text, &|word| constants.value(word))?,
+    };
+    let value = i32::try_from(value)
+        .map_err(|_| anyhow!("ASSERT literal {} does not fit the signed 32 bits ACC is compared in", value))?;
+    Ok(Assert { cmp, value, line })
+}
+
+/// Closes each `NODE` block when the next header (or the end) is reached.
+#[derive(Default)]
+struct RangeTracker {
+    ranges: NodeRanges,
+    open: Option<((usize, usize), usize)>,
+}
+
+impl RangeTracker {
+    fn open(&mut self, node: (usize, usize), line_no: usize) {
+        self.close(line_no);
+        self.open = Some((node, line_no));
+    }
+
+    fn close(&mut self, next_line: usize) {
+        if let Some((node, start)) = self.open.take() {
+            self.ranges.entry(node).or_default().push(start..next_line);
+        }
+    }
+
+    fn finish(mut self, line_count: usize) -> NodeRanges {
+        self.close(line_count + 1);
+        self.ranges
+    }
+}
+
+/// The `NODE` blocks of `code` without parsing their instructions, as
+/// `ParseResult::node_ranges` reports them.
+pub fn node_ranges(code: &str) -> Result<NodeRanges> {
+    let size = grid_size(code)?;
+    let mut ranges = RangeTracker::default();
+    for (idx, line) in code.lines().enumerate() {
+        if let Line::Node(coords, _) = classify(&lexer::lex_line(line, idx + 1)) {
+            let coords = parse_node_line(&coords, size).map_err(|e| located(code, idx + 1, coords_column(line), None, e))?;
+            ranges.open(coords, idx + 1);
+        }
+    }
+    Ok(ranges.finish(code.lines().count()))
+}
+
+/// The grid `code` declares with `GRID <rows> <cols>`, or the default.
+pub fn grid_size(code: &str) -> Result<GridSize> {
+    resolve_grid(code, None)
+}
+
+/// The grid size of `code` when `asked` for one (`--rows`/`--cols`), which
+/// a `GRID` line must agree with.
+pub fn resolve_grid(code: &str, asked: Option<GridSize>) -> Result<GridSize> {
+    match (declared_grid(code)?, asked) {
+        (Some((line, declared)), Some(asked)) if declared != asked => {
+            Err(anyhow!("line {}: the source declares a {} grid, but {} was asked for", line, declared, asked))
+        }
+        (declared, asked) => Ok(asked.or(declared.map(|(_, size)| size)).unwrap_or_default()),
+    }
+}
+
+/// How many instructions every node with code is padded to: `asked`
+/// (`--pad-to`), which a `.pad` line must agree with, or the `.pad` line's.
+pub fn resolve_pad(code: &str, asked: Option<usize>, constants: &Constants) -> Result<Option<usize>> {
+    let target = match (directives::declared_pad(code, constants)?, asked) {
+        (Some((line, declared)), Some(asked)) if declared != asked => {
+            return Err(anyhow!("line {}: the source pads nodes to {} instructions, but --pad-to {} was asked for", line, declared, asked));
+        }
+        (declared, asked) => asked.or(declared.map(|(_, target)| target)),
+    };
+    match target {
+        Some(0) => Err(anyhow!("nodes cannot be padded to 0 instructions")),
+        target => Ok(target),
+    }
+}
+
+/// The `GRID` line of `code` and the size it declares. There may be one,
+/// before the first `NODE`.
+fn declared_grid(code: &str) -> Result<Option<(usize, GridSize)>> {
+    let mut declared: Option<(usize, GridSize)> = None;
+    let mut first_node = None;
+    for (idx, line) in code.lines().enumerate() {
+        let line_no = idx + 1;
+        match classify(&lexer::lex_line(line, line_no)) {
+            Line::Node(..) => {
+                first_node.get_or_insert(line_no);
+            }
+            Line::Grid(tokens) => {
+                if let Some((first, _)) = declared {
+                    return Err(anyhow!("line {}: GRID is declared twice (first on line {})", line_no, first));
+                }
+                if let Some(node) = first_node {
+                    return Err(anyhow!("line {}: GRID must come before the first NODE (line {})", line_no, node));
+                }
+                let size = grid_directive(&tokens).map_err(|e| anyhow!("line {}: {}", line_no, e))?;
+                declared = Some((line_no, size));
             }
+            _ => {}
+        }
+    }
+    Ok(declared)
+}
+
+/// The file-scope constants of `code`: `GLOBAL NAME VALUE`, and `DEFINE
+/// NAME VALUE` or `NAME EQU VALUE` before the first `NODE`. Each name is
+/// defined once and is usable in every node. A value may be an expression
+/// over the constants defined above it. A `DEFINE` inside a `NODE` block is
+/// its node's (`ParseResult::node_constants`).
+pub fn constants(code: &str) -> Result<Constants> {
+    constants_with(code, &Constants::new())
+}
+
+/// `constants`, starting from `defines` (`--define`), which are usable
+/// everywhere and win over a `DEFINE` or `GLOBAL` of the same name in the
+/// file: the file's value is the default the command line overrides. A
+/// `DEFINE` in a branch an `.if` drops defines nothing.
+pub fn constants_with(code: &str, defines: &Constants) -> Result<Constants> {
+    Ok(file_scope(code, defines)?.constants)
+}
+
+/// `constants_with`, keeping which names are `GLOBAL`.
+pub fn file_scope(code: &str, defines: &Constants) -> Result<FileScope> {
+    let mut scope = FileScope { constants: defines.clone(), globals: BTreeMap::new() };
+    let mut defined_on: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut conditions = directives::Conditions::default();
+    let mut first_node = None;
+    for (idx, line) in code.lines().enumerate() {
+        let line_no = idx + 1;
+        let lexed = lexer::lex_line(line, line_no);
+        let line = classify(&lexed);
+        let at = |e: anyhow::Error| anyhow!("line {}: {}", line_no, e);
+        if conditions.apply(&line, &scope.constants).map_err(at)? || !conditions.selected() {
             continue;
         }
-        
-        // Store instruction line for later parsing
-        if let Some((r, c)) = current_node {
-            node_instructions.get_mut(&(r, c)).unwrap().push((line.to_string(), None));
+        let (name, tokens, global) = match line {
+            Line::Node(..) => {
+                first_node.get_or_insert(line_no);
+                continue;
+            }
+            Line::Define(name, tokens, global) => (name, tokens, global),
+            _ => continue,
+        };
+        match first_node {
+            Some(node) if global => {
+                return Err(anyhow!(
+                    "line {}: GLOBAL {} must come before the first NODE (line {})",
+                    line_no,
+                    name.text,
+                    node
+                ));
+            }
+            Some(_) => continue,
+            None => {}
+        }
+        if let Some((_, message)) = non_ascii(&lexed) {
+            return Err(at(anyhow!(message)));
+        }
+        let value = define_directive(&name, &tokens, &ConstantScope::file(&scope.constants)).map_err(at)?;
+        if let Some(first) = defined_on.insert(name.text, line_no) {
+            return Err(anyhow!(
+                "line {}: constant '{}' is defined twice, on lines {} and {}",
+                line_no,
+                name.text,
+                first,
+                line_no
+            ));
+        }
+        if global {
+            scope.globals.insert(name.text.to_string(), line_no);
+        }
+        if !defines.contains_key(name.text) {
+            scope.constants.insert(name.text.to_string(), value);
         }
     }
-    
-    // Second pass: parse instructions with label knowledge
-    for ((r, c), inst_lines) in node_instructions {
-        let labels = node_labels.get(&(r, c)).unwrap();
-        
-        for (line, _) in inst_lines {
-            let inst = parse_instruction(&line, labels)?;
-            programs[r][c].push(inst);
+    match conditions.innermost() {
+        Some(line_no) => Err(directives::unclosed(line_no)),
+        None => Ok(scope),
+    }
+}
+
+/// The `DEFINE`s inside `NODE` blocks among the `lines` `directives::expand`
+/// kept, by node, and the problem with each one that defines nothing, by
+/// line. A node's constant may use the file's and the node's own defined
+/// above it; it may hide a file `DEFINE` but not a `GLOBAL`, and `--define`
+/// (in `file`, never hidden) wins over it. Headers that do not parse are left
+/// for the caller to report.
+fn node_constants(
+    lines: &[(usize, &str)],
+    size: GridSize,
+    file: &FileScope,
+    defines: &Constants,
+) -> (BTreeMap<(usize, usize), Constants>, BTreeMap<usize, String>) {
+    let mut scopes: BTreeMap<(usize, usize), Constants> = BTreeMap::new();
+    let mut defined_on: BTreeMap<((usize, usize), &str), usize> = BTreeMap::new();
+    let mut problems = BTreeMap::new();
+    let mut current = None;
+    for &(line_no, line) in lines {
+        let (name, tokens) = match classify(&lexer::lex_line(line, line_no)) {
+            Line::Node(text, _) => {
+                current = parse_node_line(&text, size).ok();
+                continue;
+            }
+            Line::Define(name, tokens, _) => (name, tokens),
+            _ => continue,
+        };
+        let Some(node) = current else {
+            continue;
+        };
+        let locals = scopes.entry(node).or_default();
+        let problem = if let Some(global) = file.globals.get(name.text) {
+            format!("node ({},{}) cannot define '{}'; it is GLOBAL (line {})", node.0, node.1, name.text, global)
+        } else if let Some(first) = defined_on.get(&(node, name.text)) {
+            format!("constant '{}' is defined twice in node ({},{}), on lines {} and {}", name.text, node.0, node.1, first, line_no)
+        } else {
+            let scope = ConstantScope { file: &file.constants, node: Some((node, locals)) };
+            match define_directive(&name, &tokens, &scope) {
+                Ok(value) => {
+                    defined_on.insert((node, name.text), line_no);
+                    if !defines.contains_key(name.text) {
+                        locals.insert(name.text.to_string(), value);
+                    }
+                    continue;
+                }
+                Err(e) => e.to_string(),
+            }
+        };
+        problems.insert(line_no, problem);
+    }
+    (scopes, problems)
+}
+
+/// A `--define` argument: `NAME=VALUE`, or `NAME` alone for 1. The value
+/// is a literal or an expression over literals.
+pub fn parse_define(s: &str) -> Result<(String, i64)> {
+    let (name, value) = s.split_once('=').unwrap_or((s, "1"));
+    let tokens = lexer::lex_line(value, 1);
+    let names = lexer::lex_line(name, 1);
+    let [name] = names.as_slice() else {
+        return Err(anyhow!("'{}' cannot name a constant", name.trim()));
+    };
+    let tokens: Vec<Token> = tokens.into_iter().filter(|t| t.kind != TokenKind::Comment).collect();
+    let value = define_directive(name, &tokens, &ConstantScope::file(&Constants::new()))?;
+    Ok((name.text.to_string(), value))
+}
+
+/// The value of a `DEFINE`/`EQU`/`GLOBAL` line naming `name`: one literal,
+/// or an expression over the constants of `scope`.
+pub(crate) fn define_directive(name: &Token, tokens: &[Token], scope: &ConstantScope) -> Result<i64> {
+    let reserved = Src::from_str(name.text).is_ok() || Dst::from_str(name.text).is_ok();
+    if name.kind != TokenKind::Ident || reserved || name.text.eq_ignore_ascii_case("NODE") {
+        return Err(anyhow!("'{}' cannot name a constant", name.text));
+    }
+    match tokens {
+        [value] if value.kind == TokenKind::Number => match expr::eval(value.text, &|word| scope.value(word)) {
+            Ok(value) => Ok(value),
+            _ => Err(anyhow!("constant {} = {} does not fit in 32 bits", name.text, value.text)),
+        },
+        [] => Err(anyhow!("expected DEFINE {} <literal>", name.text)),
+        _ => {
+            expr::eval(&operand_text(tokens), &|word| scope.value(word))
         }
     }
-    
-    Ok(programs)
 }
 
-fn parse_node_coords(s: &str) -> Result<(usize, usize)> {
-    let coords: Vec<&str> = s.trim_matches(|c| c == '(' || c == ')').split(',').collect();
-    if coords.len() != 2 {
-        return Err(anyhow!("Invalid node coordinates: {}", s));
+/// The value file-scope constant `name` holds, for an expression.
+pub(crate) fn constant_value(name: &str, constants: &Constants) -> Result<i64> {
+    ConstantScope::file(constants).value(name)
+}
+
+/// `parse` without the label tables and diagnostics.
+pub fn parse_assembly(code: &str) -> Result<Programs> {
+    Ok(parse(code)?.grid.programs)
+}
+
+/// The operands of a `GRID` line: rows and columns.
+pub(crate) fn grid_directive(tokens: &[Token]) -> Result<GridSize> {
+    let [rows, cols] = tokens else {
+        return Err(anyhow!("expected GRID <rows> <cols>"));
+    };
+    match (rows.text.parse(), cols.text.parse()) {
+        (Ok(rows), Ok(cols)) => GridSize::new(rows, cols),
+        _ => Err(anyhow!("expected GRID <rows> <cols>, found GRID {} {}", rows.text, cols.text)),
     }
-    let r = coords[0].trim().parse::<usize>()?;
-    let c = coords[1].trim().parse::<usize>()?;
-    if r >= 2 || c >= 2 {
-        return Err(anyhow!("Node coordinates must be in 2x2 grid: {}", s));
+}
+
+/// `(row,col)` in the default grid.
+pub fn parse_node_coords(s: &str) -> Result<(usize, usize)> {
+    parse_node_coords_in(s, GridSize::default())
+}
+
+/// The error for a `:` line with no label name before it.
+pub(crate) const EMPTY_LABEL: &str = "a label needs a name before its ':'";
+
+/// The error for a `.:` label, local but with no name after its `.`.
+pub(crate) const EMPTY_LOCAL_LABEL: &str = "a local label needs a name after its '.'";
+
+/// Why `name` cannot be a label: a jump to it would read as the operation,
+/// register or port it spells. Those words match in any case, as they do in
+/// operands.
+pub(crate) fn reserved_label(name: &Token, dialect: &Dialect) -> Option<String> {
+    let (written, upper) = match name.kind {
+        TokenKind::PortPrefix => (format!("P:{}", name.text), format!("P:{}", name.text.to_uppercase())),
+        _ => (name.text.to_string(), name.text.to_uppercase()),
+    };
+    let what = if name.kind == TokenKind::PortPrefix {
+        "a port"
+    } else if dialect.op(name.text).is_ok() {
+        "an operation"
+    } else if matches!(upper.as_str(), "ACC" | "NIL") {
+        "a register"
+    } else if matches!(upper.as_str(), "IN" | "OUT" | "LAST") || PortTag::from_str(name.text).is_ok() {
+        "a port"
+    } else {
+        return None;
+    };
+    Some(format!(
+        "label '{}' is reserved: {} is {} (reserved words match in any case)",
+        written, upper, what
+    ))
+}
+
+/// The error for label `name` on line `lines.1` of `node` when `earlier`
+/// was defined on line `lines.0` and matches it.
+pub(crate) fn duplicate_label(name: &str, earlier: &str, node: (usize, usize), lines: (usize, usize)) -> String {
+    let spelling = match earlier == name {
+        true => String::new(),
+        false => format!(" (first as '{}'; labels match in any case without --case-sensitive-labels)", earlier),
+    };
+    format!("label '{}' is defined twice in node ({},{}), on lines {} and {}{}", name, node.0, node.1, lines.0, lines.1, spelling)
+}
+
+/// The error for `what` (an instruction, label or `ASSERT`) outside any
+/// `NODE` block, which has nowhere to go.
+pub(crate) fn before_first_node(what: &str) -> String {
+    format!("{} before the first NODE; add a NODE (row,col) header above it", what)
+}
+
+/// Why a `NODE` header for `node` on `line_no` may not open a block, when
+/// the node's first header was on line `first`: only `NODE (r,c) CONTINUE`
+/// reopens a node, appending to it, and only once it has a block.
+pub(crate) fn reopen_error(node: (usize, usize), continued: bool, first: Option<usize>, line_no: usize) -> Option<String> {
+    let (r, c) = node;
+    match (first, continued) {
+        (Some(first), false) => Some(format!(
+            "node ({},{}) is declared twice, on lines {} and {}; write NODE ({},{}) CONTINUE to append to it",
+            r, c, first, line_no, r, c
+        )),
+        (None, true) => Some(format!("NODE ({},{}) CONTINUE has no earlier NODE ({},{}) block to append to", r, c, r, c)),
+        _ => None,
     }
-    Ok((r, c))
 }
 
-fn parse_instruction(
-    line: &str,
-    labels: &HashMap<String, usize>,
-) -> Result<Inst> {
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.is_empty() {
-        return Err(anyhow!("Empty instruction line"));
+/// The ways to write a `NODE` line, for errors about the others.
+const NODE_FORMS: &str =
+    "write NODE (row,col), NODE(row,col), NODE row,col, NODE @index or NODE TOP_LEFT, TOP_RIGHT, BOTTOM_LEFT or BOTTOM_RIGHT";
+
+/// Corner names a `NODE` line may give instead of coordinates, in any case.
+const NODE_NAMES: [&str; 4] = ["TOP_LEFT", "TOP_RIGHT", "BOTTOM_LEFT", "BOTTOM_RIGHT"];
+
+/// The name `text` gives a node on a `NODE` line, as `node_names` records
+/// it, or `None` when it gives coordinates.
+pub(crate) fn node_name(text: &str) -> Option<String> {
+    match text.starts_with(|c: char| c == '@' || c == '_' || c.is_ascii_alphabetic()) {
+        true => Some(text.split_whitespace().collect::<String>().to_uppercase()),
+        false => None,
     }
+}
+
+/// `node` as diagnostics show it: its coordinates, after the name its
+/// `NODE` header gave it if any (`TOP_LEFT (0,0)`).
+pub fn node_label(node: (usize, usize), names: &BTreeMap<(usize, usize), String>) -> String {
+    match names.get(&node) {
+        Some(name) => format!("{} ({},{})", name, node.0, node.1),
+        None => format!("({},{})", node.0, node.1),
+    }
+}
+
+/// The node a `NODE` line names in a grid of `size`: a corner, or `@n`
+/// counting nodes row by row from `@0` as TIS-100 does.
+fn named_node(name: &str, size: GridSize) -> Result<(usize, usize)> {
+    let last = size.nodes() - 1;
+    let names = || format!("the names are {} and @0..=@{} in the {} grid", NODE_NAMES.join(", "), last, size);
+    if let Some(index) = name.strip_prefix('@') {
+        return match index.parse::<usize>() {
+            Ok(i) if i <= last => Ok((i / size.cols, i % size.cols)),
+            _ => Err(anyhow!("no node is named {}; {}", name, names())),
+        };
+    }
+    match name {
+        "TOP_LEFT" => Ok((0, 0)),
+        "TOP_RIGHT" => Ok((0, size.cols - 1)),
+        "BOTTOM_LEFT" => Ok((size.rows - 1, 0)),
+        "BOTTOM_RIGHT" => Ok((size.rows - 1, size.cols - 1)),
+        _ => Err(anyhow!("no node is named {}; {}", name, names())),
+    }
+}
+
+/// `(row,col)` in a grid of `size`: two numbers separated by a comma,
+/// optionally in one pair of parentheses, with spaces anywhere between.
+pub fn parse_node_coords_in(s: &str, size: GridSize) -> Result<(usize, usize)> {
+    let coords = split_coords(s).map_err(|why| anyhow!("Invalid node coordinates: {} ({})", s, why))?;
+    in_grid(s, coords, size)
+}
+
+/// The coordinates of a `NODE` line, whose errors show how to write one.
+pub(crate) fn parse_node_line(s: &str, size: GridSize) -> Result<(usize, usize)> {
+    if s.is_empty() {
+        return Err(anyhow!("NODE needs coordinates; {}", NODE_FORMS));
+    }
+    if let Some(name) = node_name(s) {
+        return named_node(&name, size);
+    }
+    let coords = split_coords(s).map_err(|why| anyhow!("Invalid node coordinates: {} ({}); {}", s, why, NODE_FORMS))?;
+    in_grid(s, coords, size)
+}
+
+/// The row and column `s` spells, or why it spells none. Whitespace around
+/// them, a `\r` from a CRLF line say, is not part of them.
+fn split_coords(s: &str) -> std::result::Result<(usize, usize), String> {
+    let s = s.trim_matches(lexer::is_space);
+    let inner = match (s.strip_prefix('('), s.strip_suffix(')')) {
+        (Some(_), Some(_)) if s.len() >= 2 => &s[1..s.len() - 1],
+        (None, None) => s,
+        _ => return Err("unbalanced parentheses".to_string()),
+    };
+    if inner.contains(['(', ')']) {
+        return Err("unbalanced parentheses".to_string());
+    }
+    let coords: Vec<&str> = inner.split(',').collect();
+    if coords.len() != 2 {
+        return Err("expected a row and a column separated by a comma".to_string());
+    }
+    let parse_coord = |part: &str| {
+        let part = part.trim();
+        if part.is_empty() {
+            return Err("empty coordinate".to_string());
+        }
+        part.parse::<usize>().map_err(|_| format!("'{}' is not a number", part))
+    };
+    Ok((parse_coord(coords[0])?, parse_coord(coords[1])?))
+}
+
+fn in_grid(s: &str, coords: (usize, usize), size: GridSize) -> Result<(usize, usize)> {
+    if !size.contains(coords) {
+        return Err(anyhow!("Node coordinates must be in {} grid: {}", size, s));
+    }
+    Ok(coords)
+}
+
+/// One instruction of `node`, whose label table is `labels`, in the scope
+/// of the label `scope` for its `.local` labels and with the constants of
+/// `constants`, lowered to the instructions the guest runs
+/// (`literals::lower`), failing with the column of the offending token (or
+/// of the end of the line for a missing operand). `lenient` options let
+/// misplaced and missing commas through.
+pub(crate) fn parse_instruction_at(
+    tokens: &[Token],
+    node: (usize, usize),
+    labels: &BTreeMap<String, usize>,
+    scope: Option<&str>,
+    constants: &ConstantScope,
+    dialect: &Dialect,
+    options: &ParseOptions,
+) -> std::result::Result<Lowered, (usize, anyhow::Error)> {
+    let (op, src, dst, col) = parse_written(tokens, node, labels, scope, constants, dialect, options)?;
+    literals::lower(op, src, dst, options.synthesize_literals).map_err(|e| (col, e))
+}
+
+/// `parse_instruction_at` before lowering: the op, its operands as written
+/// and the column of the source operand (of the mnemonic if there is none).
+fn parse_written(
+    tokens: &[Token],
+    node: (usize, usize),
+    labels: &BTreeMap<String, usize>,
+    scope: Option<&str>,
+    constants: &ConstantScope,
+    dialect: &Dialect,
+    options: &ParseOptions,
+) -> std::result::Result<(Op, Operand, Dst, usize), (usize, anyhow::Error)> {
+    let labels = LabelScope { labels, scope, case_sensitive: options.case_sensitive_labels };
+    let Some((mnemonic, rest)) = tokens.split_first() else {
+        return Err((1, anyhow!("Empty instruction line")));
+    };
+    let parts = operand_spans(rest);
+    let end = tokens.last().map_or(1, |t| t.col_end);
     
-    let op = Op::from_str(parts[0])?;
+    if let Some(io) = options.tis100 {
+        return parse_tis100_instruction(mnemonic, &parts, end, &labels, tis100::Strict { node, io });
+    }
+    let op = dialect.op(mnemonic.text).map_err(|e| (mnemonic.col_start, e))?;
+    if let Some((_, col, message)) = extra_operands(tokens, dialect) {
+        return Err((col, anyhow!(message)));
+    }
+    if let Some((col, message)) = comma_error(op, rest).filter(|_| !options.lenient) {
+        return Err((col, anyhow!(message)));
+    }
     
     match op {
         Op::Nop | Op::Hlt | Op::Neg | Op::Sav | Op::Swp => {
             // No operands
-            Ok(Inst {
-                op,
-                src: Src::Nil,
-                dst: Dst::Nil,
-            })
+            Ok((op, Operand::Src(Src::Nil), Dst::Nil, mnemonic.col_start))
         }
         Op::Add | Op::Sub | Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz => {
             // One source operand
+            let Some((col, operand)) = parts.first() else {
+                return Err((end, anyhow!("Missing operand for {}", mnemonic.text)));
+            };
+            let src = parse_src_operand(operand, op, node, &labels, constants).map_err(|e| (*col, e))?;
+            Ok((op, src, Dst::Nil, *col))
+        }
+        Op::Mov => {
+            // Two operands
             if parts.len() < 2 {
-                return Err(anyhow!("Missing operand for {}", parts[0]));
+                return Err((end, anyhow!("MOV requires two operands")));
             }
-            let src = parse_src_operand(parts[1], labels)?;
-            Ok(Inst {
-                op,
-                src,
-                dst: Dst::Nil,
-            })
+            let src = parse_src_operand(&parts[0].1, op, node, &labels, constants).map_err(|e| (parts[0].0, e))?;
+            let dst = Dst::from_str(&parts[1].1).map_err(|e| (parts[1].0, e.into()))?;
+            Ok((op, src, dst, parts[0].0))
         }
+    }
+}
+
+/// `parse_instruction_at` under `--tis100`: a TIS-100 mnemonic and its
+/// operands, separated by commas or spaces as TIS-100 allows, and a jump to
+/// a label and nothing else.
+fn parse_tis100_instruction(
+    mnemonic: &Token,
+    parts: &[(usize, String)],
+    end: usize,
+    labels: &LabelScope,
+    strict: tis100::Strict,
+) -> std::result::Result<(Op, Operand, Dst, usize), (usize, anyhow::Error)> {
+    let op = tis100::op(mnemonic.text).map_err(|e| (mnemonic.col_start, e))?;
+    let expected = arity(op);
+    if parts.len() != expected {
+        let col = parts.get(expected).map_or(end, |(col, _)| *col);
+        return Err((col, anyhow!("{} takes {} operand(s), found {}", mnemonic.text, expected, parts.len())));
+    }
+    let src = |i: usize| strict.src(&parts[i].1).map_err(|e| (parts[i].0, e));
+    match op {
+        Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz => {
+            let (col, target) = &parts[0];
+            let pc = match labels.resolve(target) {
+                Some((_, pc)) => pc,
+                None if target.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') && !target.starts_with(|c: char| c.is_ascii_digit()) => {
+                    return Err((*col, labels.undefined(target, strict.node)));
+                }
+                None => return Err((*col, anyhow!("TIS-100 jumps to a label, not `{}`", target))),
+            };
+            Ok((op, Operand::Src(Src::Lit(pc as u32)), Dst::Nil, *col))
+        }
+        Op::Add | Op::Sub => Ok((op, src(0)?, Dst::Nil, parts[0].0)),
         Op::Mov => {
-            // Two operands
-            if parts.len() < 3 {
-                return Err(anyhow!("MOV requires two operands"));
+            let src = src(0)?;
+            let dst = strict.dst(&parts[1].1).map_err(|e| (parts[1].0, e))?;
+            strict.mov(src, dst).map_err(|e| (mnemonic.col_start, e))?;
+            Ok((op, src, dst, parts[0].0))
+        }
+        _ => Ok((op, Operand::Src(Src::Nil), Dst::Nil, mnemonic.col_start)),
+    }
+}
+
+/// The lines of `code` that use zk-100's own directives, which `--tis100`
+/// rejects. They are read before `directives::expand` applies them.
+fn tis100_extensions(code: &str) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    for (idx, line) in code.lines().enumerate() {
+        let what = match classify(&lexer::lex_line(line, idx + 1)) {
+            Line::Define(name, ..) => format!("the constant '{}'", name.text),
+            Line::Grid(_) => "GRID".to_string(),
+            Line::Assert(_) => "ASSERT".to_string(),
+            Line::Directive(name, _) => format!("the {} directive", name.text),
+            _ => continue,
+        };
+        errors.push(Diagnostic { line: idx + 1, message: format!("{} is a zk-100 extension", what) });
+    }
+    errors
+}
+
+/// The column of the first non-ASCII character in `tokens` outside a
+/// comment, and the error for it. Mnemonics, names and operands are ASCII, so
+/// an accented label or a look-alike letter (the Cyrillic `А` of `АCC`) fails
+/// as what it is rather than as an unknown operand.
+pub(crate) fn non_ascii(tokens: &[Token]) -> Option<(usize, String)> {
+    tokens.iter().filter(|t| t.kind != TokenKind::Comment).find_map(|token| {
+        let (offset, ch) = token.text.chars().enumerate().find(|(_, ch)| !ch.is_ascii())?;
+        let message = format!(
+            "'{}' has the non-ASCII character '{}' (U+{:04X}); outside comments, source is ASCII",
+            token.text, ch, ch as u32
+        );
+        Some((token.col_start + offset, message))
+    })
+}
+
+/// The first comma out of place among an instruction's operand `tokens`,
+/// or the first missing one, with its column: operands are separated by
+/// exactly one comma, with or without spaces around it.
+fn comma_error(op: Op, tokens: &[Token]) -> Option<(usize, String)> {
+    let mut count = 0;
+    let mut after_comma = false;
+    let mut prev: Option<&Token> = None;
+    let mut depth = 0;
+    for token in tokens {
+        if token.kind == TokenKind::Comma {
+            if count == 0 {
+                return Some((token.col_start, "unexpected ',' before the first operand".to_string()));
             }
-            let src = parse_src_operand(parts[1].trim_end_matches(','), labels)?;
-            let dst = Dst::from_str(parts[2])?;
-            Ok(Inst { op, src, dst })
+            if after_comma {
+                return Some((token.col_start, "expected an operand between ',' and ','".to_string()));
+            }
+            after_comma = true;
+            prev = None;
+            continue;
+        }
+        if !prev.is_some_and(|p| joins(p, token, depth)) {
+            if count > 0 && !after_comma {
+                let between = if op == Op::Mov && count == 1 { "source and destination" } else { "operands" };
+                return Some((token.col_start, format!("expected ',' between {}", between)));
+            }
+            count += 1;
+            after_comma = false;
+        }
+        depth = nesting(token, depth);
+        prev = Some(token);
+    }
+    match tokens.last() {
+        Some(last) if after_comma => Some((last.col_start, "unexpected ',' after the last operand".to_string())),
+        _ => None,
+    }
+}
+
+/// How many operands `op` takes.
+fn arity(op: Op) -> usize {
+    match op {
+        Op::Nop | Op::Hlt | Op::Neg | Op::Sav | Op::Swp => 0,
+        Op::Add | Op::Sub | Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz => 1,
+        Op::Mov => 2,
+    }
+}
+
+/// When the instruction `tokens` has operands past the ones its op takes:
+/// how many tokens to keep without them, the column of the first and the
+/// error naming them.
+fn extra_operands(tokens: &[Token], dialect: &Dialect) -> Option<(usize, usize, String)> {
+    let (mnemonic, rest) = tokens.split_first()?;
+    let expected = arity(dialect.op(mnemonic.text).ok()?);
+    let parts = operand_spans(rest);
+    let (first_extra, _) = parts.get(expected)?;
+    let mut kept = 1 + rest.iter().position(|t| t.col_start == *first_extra)?;
+    while kept > 1 && tokens[kept - 1].kind == TokenKind::Comma {
+        kept -= 1;
+    }
+    let takes = match expected {
+        0 => "takes no operands",
+        1 => "takes exactly one operand",
+        _ => "takes exactly two operands",
+    };
+    let extra: Vec<&str> = parts[expected..].iter().map(|(_, text)| text.as_str()).collect();
+    let message = format!("{} {}, found {} (extra: {})", mnemonic.text, takes, parts.len(), extra.join(", "));
+    Some((kept, *first_extra, message))
+}
+
+/// The labels a jump in one node can see: the node's table, the label
+/// whose scope the jump is in and how names are compared.
+struct LabelScope<'a> {
+    labels: &'a BTreeMap<String, usize>,
+    scope: Option<&'a str>,
+    case_sensitive: bool,
+}
+
+impl LabelScope<'_> {
+    fn resolve(&self, name: &str) -> Option<(&str, usize)> {
+        resolve_label(self.labels, name, self.scope, self.case_sensitive)
+    }
+
+    /// The error for the jump target `name`, which is no label in scope.
+    fn undefined(&self, name: &str, node: (usize, usize)) -> anyhow::Error {
+        if let Some((owner, local)) = qualified_local(self.labels, name, self.case_sensitive) {
+            let here = match self.scope {
+                Some(scope) => format!("`{}`", scope),
+                None => format!("the start of node ({},{})", node.0, node.1),
+            };
+            return anyhow!("`{}` is local to `{}`, not to {} where `{}` is used", local, owner, here, name);
+        }
+        if is_local(name) {
+            let owner = self.labels.keys().find_map(|key| {
+                let owner = key.get(..key.len().checked_sub(name.len())?)?;
+                let local = &key[owner.len()..];
+                let same = if self.case_sensitive { local == name } else { local.eq_ignore_ascii_case(name) };
+                same.then_some(owner)
+            });
+            if let Some(owner) = owner {
+                let owner = match owner {
+                    "" => format!("the start of node ({},{})", node.0, node.1),
+                    owner => format!("'{}'", owner),
+                };
+                let here = match self.scope {
+                    Some(scope) => format!("'{}'", scope),
+                    None => format!("the start of node ({},{})", node.0, node.1),
+                };
+                return anyhow!("local label '{}' belongs to {}, not to {} where it is used", name, owner, here);
+            }
+        }
+        let defined = match self.labels.is_empty() {
+            true => "no labels defined".to_string(),
+            false => format!("defined: {}", self.labels.keys().map(String::as_str).collect::<Vec<_>>().join(", ")),
+        };
+        let hint = match self.labels.keys().find(|label| label.eq_ignore_ascii_case(name)) {
+            Some(label) if self.case_sensitive => {
+                format!("; labels are case-sensitive under --case-sensitive-labels, did you mean '{}'?", label)
+            }
+            _ => String::new(),
+        };
+        anyhow!("undefined label '{}' in node ({},{}) ({}){}", name, node.0, node.1, defined, hint)
+    }
+}
+
+/// The source operand `s` of `op` in `node`. A jump's operand that is
+/// neither a number, a constant nor a register or port must be one of
+/// `labels`; any other op's must be one of `constants`, and naming a label
+/// there is an error rather than the label's pc. A number, constant or
+/// expression is the literal as written; a label is the pc it marks.
+fn parse_src_operand(s: &str, op: Op, node: (usize, usize), labels: &LabelScope, constants: &ConstantScope) -> Result<Operand> {
+    let is_jump = matches!(op, Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz);
+    let numeric = s.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+');
+    // Check if it's a label reference
+    match labels.resolve(s) {
+        Some((_, pc)) if is_jump => return Ok(Operand::Src(Src::Lit(pc as u32))),
+        Some(_) if !numeric && !constants.contains(s) => {
+            return Err(anyhow!("label '{}' cannot be used as a data operand (did you mean a literal?)", s));
+        }
+        _ => {}
+    }
+    if let Some(value) = constants.get(s) {
+        return Ok(Operand::Lit(value));
+    }
+    if let Some((name, offset)) = split_offset(s).filter(|_| is_jump) {
+        if let Some((label, pc)) = labels.resolve(name) {
+            let offset = expr::eval_i64(offset, &|word| constants.value(word))?;
+            let target = pc as i64 + offset;
+            return u32::try_from(target).map(|target| Operand::Src(Src::Lit(target))).map_err(|_| {
+                anyhow!("jump target `{}` is pc {}, outside the node (label '{}' is pc {})", s, target, label, pc)
+            });
+        }
+        if qualified_local(labels.labels, name, labels.case_sensitive).is_some() {
+            return Err(labels.undefined(name, node));
+        }
+    }
+    if expr::is_expression(s) {
+        let value = expr::eval_i64(s, &|word| match labels.resolve(word) {
+            Some(_) if !constants.contains(word) && is_jump => Err(anyhow!(
+                "label '{}' cannot be used in the expression `{}`; a jump takes {}+N or {}-N",
+                word, s, word, word
+            )),
+            Some(_) if !constants.contains(word) => {
+                Err(anyhow!("label '{}' cannot be used in the expression `{}`; only constants can", word, s))
+            }
+            _ => constants.value(word),
+        })?;
+        return Ok(Operand::Lit(value));
+    }
+    // A plain literal keeps its sign and width for `literals::lower`.
+    if let Some(value) = expr::eval_i64(s, &|word| Err(anyhow!("'{}' is not a literal", word))).ok().filter(|_| numeric) {
+        return Ok(Operand::Lit(value));
+    }
+    
+    // Try to parse as normal source operand
+    match Src::from_str(s) {
+        Err(_) if is_jump && !numeric => Err(labels.undefined(s, node)),
+        Err(_) if !numeric && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => Err(constants.undefined(s)),
+        result => Ok(Operand::Src(result?)),
+    }
+}
+
+/// A jump target written `name+N` or `name-N`, split before its first sign
+/// into the name and the signed offset, which may be a constant expression.
+/// A leading sign is a negative literal's, so `-1` never splits.
+fn split_offset(s: &str) -> Option<(&str, &str)> {
+    let at = s.char_indices().skip(1).find(|&(_, c)| c == '+' || c == '-')?.0;
+    let name = s[..at].trim();
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.').then_some((name, &s[at..]))
+}
+
+/// The label of `labels` that `name` refers to, as it was defined, and its
+/// pc: the same spelling, or any spelling unless `case_sensitive`.
+fn find_label<'a>(labels: &'a BTreeMap<String, usize>, name: &str, case_sensitive: bool) -> Option<(&'a str, usize)> {
+    let found = match case_sensitive {
+        true => labels.get_key_value(name),
+        false => labels.iter().find(|(label, _)| label.eq_ignore_ascii_case(name)),
+    };
+    found.map(|(label, &pc)| (label.as_str(), pc))
+}
+
+/// True for a `.name` label, which belongs to the label before it.
+pub(crate) fn is_local(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+/// The name label `name` goes by in its node's table when defined in the
+/// scope of label `scope`: a local `.loop` after `read:` is `read.loop`,
+/// and one before the node's first other label stays `.loop`.
+pub(crate) fn label_key(name: &str, scope: Option<&str>) -> String {
+    match (is_local(name), scope) {
+        (true, Some(scope)) => format!("{}{}", scope, name),
+        _ => name.to_string(),
+    }
+}
+
+/// `find_label` for a jump in the scope of label `scope`: a local `.name`
+/// is looked for in that scope first, then among the locals before the
+/// node's first label; any other name among the node's labels. A local
+/// written out in full (`read.loop`) resolves only inside its own scope.
+pub(crate) fn resolve_label<'a>(
+    labels: &'a BTreeMap<String, usize>,
+    name: &str,
+    scope: Option<&str>,
+    case_sensitive: bool,
+) -> Option<(&'a str, usize)> {
+    if let Some((owner, _)) = qualified_local(labels, name, case_sensitive) {
+        let inside = scope.is_some_and(|scope| if case_sensitive { scope == owner } else { scope.eq_ignore_ascii_case(owner) });
+        if !inside {
+            return None;
         }
     }
+    match scope.filter(|_| is_local(name)) {
+        Some(scope) => find_label(labels, &label_key(name, Some(scope)), case_sensitive)
+            .or_else(|| find_label(labels, name, case_sensitive)),
+        None => find_label(labels, name, case_sensitive),
+    }
 }
 
-fn parse_src_operand(
-    s: &str,
-    labels: &HashMap<String, usize>,
-) -> Result<Src> {
-    // Check if it's a label reference
-    if let Some(&pc) = labels.get(s) {
-        return Ok(Src::Lit(pc as u32));
+/// The owning label and the local `.name` of `name` when it is the full
+/// key `label_key` gave a local label: a label of the node followed by
+/// `.name`, and itself one of `labels`.
+fn qualified_local<'a>(labels: &BTreeMap<String, usize>, name: &'a str, case_sensitive: bool) -> Option<(&'a str, &'a str)> {
+    if is_local(name) || find_label(labels, name, case_sensitive).is_none() {
+        return None;
     }
-    
-    // Try to parse as normal source operand
-    Src::from_str(s)
+    name.match_indices('.')
+        .map(|(i, _)| name.split_at(i))
+        .find(|(owner, local)| local.len() > 1 && find_label(labels, owner, case_sensitive).is_some())
+}
+
+/// Fails on the first `PLACEHOLDER` in `programs`, so a partial grid from a
+/// recovering parse can never be encoded or committed to.
+pub fn check_complete(programs: &[Vec<Vec<Inst>>]) -> Result<()> {
+    for (r, row) in programs.iter().enumerate() {
+        for (c, program) in row.iter().enumerate() {
+            if let Some(pc) = program.iter().position(|inst| *inst == PLACEHOLDER) {
+                return Err(anyhow!(
+                    "node ({},{}) pc {} is a placeholder for a line that did not parse; fix the errors before encoding",
+                    r,
+                    c,
+                    pc
+                ));
+            }
+        }
+    }
+    Ok(())
 }
 
 pub fn encode_programs(programs: &Programs) -> Result<Vec<u32>> {
+    encode_programs_with(programs, &Limits::default())
+}
+
+/// `encode_programs`, failing if the words exceed `limits.prog_words`.
+pub fn encode_programs_with(programs: &Programs, limits: &Limits) -> Result<Vec<u32>> {
+    check_complete(programs)?;
+    for (r, row) in programs.iter().enumerate() {
+        for (c, program) in row.iter().enumerate() {
+            for (pc, inst) in program.iter().enumerate() {
+                if let Some(message) = literal_error(inst) {
+                    return Err(anyhow!("node ({},{}) pc {}: {}", r, c, pc, message));
+                }
+                if let Some(message) = jump_error(inst, pc, program.len()) {
+                    return Err(anyhow!("node ({},{}): {}", r, c, message));
+                }
+            }
+        }
+    }
+    let span = trace::span!("encode_programs", words);
     let mut prog_words = Vec::new();
     
     for row in programs {
@@ -146,13 +2089,79 @@ pub fn encode_programs(programs: &Programs) -> Result<Vec<u32>> {
         }
     }
     
+    span.record("words", prog_words.len() as u64);
+    let words = prog_words.len() as u64;
+    limits.check(Limit::ProgWords, words, || format!("the encoded grid has {} words", words))?;
     Ok(prog_words)
 }
 
+/// Where each node's length word sits in the `prog_words` of
+/// `encode_programs`; its instruction at pc `p` is the word at `offset + 1 + p`.
+pub fn word_offsets(programs: &Programs) -> BTreeMap<(usize, usize), usize> {
+    let mut offsets = BTreeMap::new();
+    let mut offset = 0;
+    for (r, row) in programs.iter().enumerate() {
+        for (c, program) in row.iter().enumerate() {
+            offsets.insert((r, c), offset);
+            offset += 1 + program.len();
+        }
+    }
+    offsets
+}
+
+/// Decode length-prefixed `prog_words` back into a grid of programs of
+/// `size`, which the words do not record (`GRID_ABI` args carry it ahead of
+/// them).
+///
+/// This is the inverse of `encode_programs`: truncated programs, invalid
+/// instruction words and trailing words are all errors.
+pub fn decode_grid(words: &[u32], size: GridSize) -> Result<Programs> {
+    let mut programs = GridSize::new(size.rows, size.cols)?.empty_programs();
+    let mut idx = 0;
+
+    for (r, row) in programs.iter_mut().enumerate() {
+        for (c, program) in row.iter_mut().enumerate() {
+            let len = *words
+                .get(idx)
+                .ok_or_else(|| anyhow!("Missing length word for node ({},{}) at index {}", r, c, idx))?
+                as usize;
+            idx += 1;
+
+            let body = words.get(idx..idx.saturating_add(len)).ok_or_else(|| {
+                anyhow!(
+                    "Node ({},{}) declares {} instructions but only {} words remain",
+                    r, c, len, words.len() - idx
+                )
+            })?;
+            for (pc, &word) in body.iter().enumerate() {
+                let inst = Inst::decode(word)
+                    .map_err(|e| anyhow!("Node ({},{}) pc {}: {}", r, c, pc, e))?;
+                program.push(inst);
+            }
+            idx += len;
+        }
+    }
+
+    if idx != words.len() {
+        return Err(anyhow!("{} trailing words after the last program", words.len() - idx));
+    }
+
+    Ok(programs)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_node_ranges() {
+        let code = "# intro\nNODE (0,0)\nNOP\nNODE (1,1)\nNOP\n\nNODE (0,0) CONTINUE\nNOP";
+        let ranges = node_ranges(code).unwrap();
+        assert_eq!(ranges[&(0, 0)], [2..4, 7..9]);
+        assert_eq!(ranges[&(1, 1)], vec![4..7]);
+        assert_eq!(parse(code).unwrap().node_ranges, ranges);
+    }
+
     #[test]
     fn test_parse_simple_program() {
         let code = r#"
@@ -192,6 +2201,31 @@ loop:
         }
     }
 
+    #[test]
+    fn test_trailing_comments() {
+        let code = "NODE (1,0) # consumer\nloop: # main loop\nMOV 42, ACC # initial value\n   // nothing\nSUB 1 // count down\nJNZ loop#again\n";
+        let parsed = parse(code).unwrap();
+        let node = &parsed.grid.programs[1][0];
+        assert_eq!(node.len(), 3);
+        assert_eq!(node[0], Inst { op: Op::Mov, src: Src::Lit(42), dst: Dst::Acc });
+        assert_eq!(node[2].src, Src::Lit(0));
+        assert_eq!(parsed.labels[&(1, 0)]["loop"], 0);
+    }
+
+    #[test]
+    fn test_semicolon_comments() {
+        // Snippets in the style of TIS-100 saves.
+        let corpus = [
+            ("; doubler\nNODE (0,0) ; input\nMOV IN, ACC ; read input\nADD ACC\nMOV ACC, P:DOWN", 3),
+            ("NODE (1,0)\nSTART: ; wait\n  MOV P:UP, ACC\n  JEZ START;spin\n", 2),
+            ("NODE (1,1)\n;;;;\nL:;relay\nMOV P:LEFT, OUT ;; forward\nJMP L ;", 2),
+        ];
+        for (code, len) in corpus {
+            let parsed = parse(code).unwrap_or_else(|e| panic!("{}: {}", code, e));
+            assert_eq!(parsed.grid.programs.iter().flatten().map(Vec::len).sum::<usize>(), len, "{}", code);
+        }
+    }
+
     #[test]
     fn test_parse_port_communication() {
         let code = r#"
@@ -227,4 +2261,1021 @@ HLT
         assert_eq!(words[4], 0); // Empty program
         assert_eq!(words[5], 0); // Empty program
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_decode_grid_roundtrip() {
+        let code = r#"
+NODE (0,0)
+MOV IN, P:RIGHT
+HLT
+
+NODE (1,1)
+loop:
+MOV P:LEFT, OUT
+JMP loop
+"#;
+        let programs = parse_assembly(code).unwrap();
+        let words = encode_programs(&programs).unwrap();
+        let decoded = decode_grid(&words, GridSize::default()).unwrap();
+        assert_eq!(decoded, programs);
+        assert_eq!(encode_programs(&decoded).unwrap(), words);
+    }
+
+    #[test]
+    fn test_decode_grid_roundtrip_3x3() {
+        let code = r#"
+GRID 3 3
+NODE (0,0)
+MOV IN, P:DOWN
+
+NODE (1,1)
+ADD 1
+NODE (2,2)
+MOV P:UP, OUT
+"#;
+        let programs = parse_assembly(code).unwrap();
+        let words = encode_programs(&programs).unwrap();
+        let size = GridSize::new(3, 3).unwrap();
+        let decoded = decode_grid(&words, size).unwrap();
+        assert_eq!(decoded, programs);
+        assert_eq!(encode_programs(&decoded).unwrap(), words);
+        // Nine length words are not a 2x2 grid.
+        assert!(decode_grid(&words, GridSize::default()).is_err());
+    }
+
+    #[test]
+    fn test_decode_grid_rejects_malformed() {
+        // Missing length words
+        assert!(decode_grid(&[0, 0, 0], GridSize::default()).is_err());
+        // Length larger than the remaining words
+        assert!(decode_grid(&[5, 0x000C0201, 0, 0, 0], GridSize::default()).is_err());
+        // Huge length must not overflow the slice arithmetic
+        assert!(decode_grid(&[u32::MAX, 0, 0, 0], GridSize::default()).is_err());
+        // Trailing words
+        assert!(decode_grid(&[0, 0, 0, 0, 7], GridSize::default()).is_err());
+        // Invalid instruction word
+        assert!(decode_grid(&[1, 0xFFFFFFFF, 0, 0, 0], GridSize::default()).is_err());
+    }
+
+    // Regression cases from fuzzing parse_assembly
+    #[test]
+    fn test_fuzz_regressions_node_coords() {
+        for code in ["NODE (,)", "NODE (0,)", "NODE (,1)", "NODE ()", "NODE (a,b)", "NODE (99999999999999999999,0)"] {
+            let err = parse_assembly(code).unwrap_err().to_string();
+            assert!(err.contains("Invalid node coordinates") || err.contains("2x2"), "{}: {}", code, err);
+        }
+    }
+
+    #[test]
+    fn test_last_warnings_follow_reachability() {
+        let code = "NODE (0,0)\nMOV IN, ACC\nJZ skip\nMOV LAST, ACC\nskip:\nMOV ACC, P:DOWN\n\
+                    NODE (0,1)\nloop:\nMOV P:DOWN, ACC\nJMP loop\nMOV ACC, LAST\n\
+                    NODE (1,1)\nJMP ACC\nADD LAST\n";
+        let warnings = parse(code).unwrap().last_warnings();
+        let lines: Vec<usize> = warnings.iter().map(|w| w.line).collect();
+        // (0,1)'s LAST sits behind an unconditional jump; (1,1)'s jump to ACC
+        // may land anywhere.
+        assert_eq!(lines, [4, 14]);
+        assert_eq!(
+            warnings[0].to_string(),
+            "line 4: node (0,0) can reach LAST before any ANY transfer sets it, and there is no ANY port; the guest blocks here"
+        );
+        assert_eq!(reachable(&parse_assembly("NODE (0,0)\nJMP 2\nNOP\nJMP 0\n").unwrap()[0][0]), [true, false, true]);
+    }
+
+    #[test]
+    fn test_unreachable_warnings() {
+        let code = "NODE (0,0)\nloop:\nMOV IN, ACC\nJMP loop\nNEG\nADD 1\n\
+                    NODE (0,1)\nJZ 2\nNOP\nHLT\nMOV ACC, P:DOWN\n\
+                    NODE (1,0)\nJMP ACC\nNOP\n";
+        let warnings = parse(code).unwrap().unreachable_warnings();
+        // (0,1) reaches everything through the conditional jump's fallthrough
+        // and past HLT; (1,0)'s jump to ACC may land anywhere.
+        let rendered: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            rendered,
+            ["line 5: node (0,0) pc 2 is never reached from pc 0", "line 6: node (0,0) pc 3 is never reached from pc 0"]
+        );
+        assert!(parse("NODE (0,0)\nJMP 0\n").unwrap().unreachable_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_parse_keeps_labels() {
+        let code = "NODE (0,0)\nstart:\ntop:\nMOV IN, ACC\nmid:\nJZ top\nend:\nNODE (1,1)\nNOP\n";
+        let result = parse(code).unwrap();
+        let node: Vec<(&str, usize)> = result.labels[&(0, 0)].iter().map(|(n, &pc)| (n.as_str(), pc)).collect();
+        assert_eq!(node, [("end", 2), ("mid", 1), ("start", 0), ("top", 0)]);
+        assert!(result.labels[&(1, 1)].is_empty());
+        assert!(!result.labels.contains_key(&(0, 1)));
+        assert!(result.diagnostics.is_empty());
+        // The compatibility wrapper returns exactly the parsed grid.
+        assert_eq!(parse_assembly(code).unwrap(), result.grid.programs);
+    }
+
+    #[test]
+    fn test_nothing_outside_a_node() {
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(err("MOV IN, ACC\nNODE (0,0)\nNOP\n"), "line 1: instruction before the first NODE; add a NODE (row,col) header above it");
+        assert_eq!(err("# solution\nstart:\nNODE (0,0)\n"), "line 2: label 'start' before the first NODE; add a NODE (row,col) header above it");
+        assert!(err("ASSERT ACC == 1\nNODE (0,0)\nNOP\n").starts_with("line 1: ASSERT before the first NODE"));
+        assert_eq!(err("NODE (0,0)\n:\nNOP\n"), "line 2: a label needs a name before its ':'");
+        // Comments, constants and GRID may still come first.
+        assert!(parse("# header\nDEFINE N 1\nNODE (0,0)\nADD N\n").is_ok());
+
+        let result = recover("MOV IN, ACC\nNODE (0,0)\nx:\nNOP\n");
+        assert_eq!(result.errors.iter().map(|d| d.line).collect::<Vec<_>>(), [1]);
+        assert_eq!(result.labels[&(0, 0)]["x"], 0);
+    }
+
+    #[test]
+    fn test_inline_labels() {
+        let inline = parse("NODE (0,0)\nstart: MOV IN, ACC\nLOOP: ADD 1 # count\nA: B: JGZ LOOP\nend:\nJMP start\n").unwrap();
+        let split = parse("NODE (0,0)\nstart:\nMOV IN, ACC\nLOOP:\nADD 1\nA:\nB:\nJGZ LOOP\nend:\nJMP start\n").unwrap();
+        assert_eq!(inline.grid, split.grid);
+        assert_eq!(inline.labels, split.labels);
+        assert_eq!(inline.labels[&(0, 0)]["A"], 2);
+        assert_eq!(inline.labels[&(0, 0)]["B"], 2);
+        assert_eq!(inline.lines[&(0, 0)], [2, 3, 4, 6]);
+        // A label with nothing after it still names the next instruction.
+        assert_eq!(inline.labels[&(0, 0)]["end"], 3);
+
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert!(err("NODE (0,0)\nx: FOO 1\n").starts_with("line 2, col 4"), "{}", err("NODE (0,0)\nx: FOO 1\n"));
+        assert_eq!(err("NODE (0,0)\nx: NOP\nx: NOP\n"), "line 3: label 'x' is defined twice in node (0,0), on lines 2 and 3");
+        assert_eq!(err("x: NOP\nNODE (0,0)\n"), "line 1: label 'x' before the first NODE; add a NODE (row,col) header above it");
+        assert_eq!(err("NODE (0,0)\n: NOP\n"), "line 2: a label needs a name before its ':'");
+    }
+
+    #[test]
+    fn test_labels_sharing_a_pc() {
+        let code = "NODE (0,0)\nstart:\nretry: MOV IN, ACC\nJZ retry\nJMP start\nNODE (1,1)\na: b:\nc:\nNOP\nJMP a\n";
+        let parsed = parse(code).unwrap();
+        assert_eq!(parsed.labels[&(0, 0)], BTreeMap::from([("retry".into(), 0), ("start".into(), 0)]));
+        assert_eq!(parsed.labels[&(1, 1)], BTreeMap::from([("a".into(), 0), ("b".into(), 0), ("c".into(), 0)]));
+        let targets: Vec<Src> = parsed.grid.programs[0][0][1..].iter().map(|i| i.src).collect();
+        assert_eq!(targets, [Src::Lit(0), Src::Lit(0)]);
+
+        // Each name keeps its own line and references.
+        let symbols = parsed.symbols();
+        let refs: Vec<(&str, usize, &[usize])> =
+            symbols[0].labels.iter().map(|l| (l.name.as_str(), l.line, l.references.as_slice())).collect();
+        assert_eq!(refs, [("retry", 3, &[1][..]), ("start", 2, &[2][..])]);
+        let unused: Vec<String> = parsed.unused_labels().into_iter().map(|d| d.message).collect();
+        assert_eq!(unused, ["label 'b' in node (1,1) is never referenced", "label 'c' in node (1,1) is never referenced"]);
+
+        // A second name at a pc is not a second definition of the first.
+        assert!(parse("NODE (0,0)\nx: y: x:\nNOP\n").unwrap_err().to_string().contains("label 'x' is defined twice"));
+    }
+
+    #[test]
+    fn test_reserved_label_names() {
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(
+            err("NODE (0,0)\nACC:\nJMP ACC\n"),
+            "line 2: label 'ACC' is reserved: ACC is a register (reserved words match in any case)"
+        );
+        assert!(err("NODE (0,0)\nadd: NOP\n").starts_with("line 2: label 'add' is reserved: ADD is an operation"));
+        assert!(err("NODE (0,0)\nLast:\nNOP\n").starts_with("line 2: label 'Last' is reserved: LAST is a port"));
+        assert!(err("NODE (0,0)\nup:\nNOP\n").starts_with("line 2: label 'up' is reserved: UP is a port"));
+        assert!(err("NODE (0,0)\nP:left:\nNOP\n").starts_with("line 2: label 'P:left' is reserved: P:LEFT is a port"));
+        // Dialect aliases are operations too.
+        let dialect = Dialect::new([("ADDI", "ADD")]).unwrap();
+        let aliased = parse_with("NODE (0,0)\naddi:\nNOP\n", &dialect);
+        assert!(aliased.unwrap_err().to_string().contains("ADDI is an operation"));
+    }
+
+    #[test]
+    fn test_label_case() {
+        let code = "NODE (0,0)\nLoop: MOV IN, ACC\nJZ LOOP\nJMP loop\n";
+        let sensitive = ParseOptions { case_sensitive_labels: true, ..Default::default() };
+        let parse_sensitive = |code: &str| parse_with_options(code, &Dialect::default(), sensitive.clone());
+
+        // By default a label matches in any case, and keeps its spelling.
+        let parsed = parse(code).unwrap();
+        assert_eq!(parsed.labels[&(0, 0)], BTreeMap::from([("Loop".into(), 0)]));
+        assert_eq!(parsed.label_refs[&(0, 0)], BTreeMap::from([(1, "Loop".into()), (2, "Loop".into())]));
+        assert!(parsed.unused_labels().is_empty());
+        assert_eq!(
+            parse("NODE (0,0)\nloop: NOP\nLOOP: JMP loop\n").unwrap_err().to_string(),
+            "line 3: label 'LOOP' is defined twice in node (0,0), on lines 2 and 3 (first as 'loop'; labels match in any case without --case-sensitive-labels)"
+        );
+        assert!(parse("NODE (0,0)\nloop:\nMOV LOOP, ACC\n").unwrap_err().to_string().contains("label 'LOOP' cannot be used as a data operand"));
+
+        // --case-sensitive-labels keeps them apart.
+        let err = parse_sensitive(code).unwrap_err().to_string();
+        assert!(
+            err.contains("undefined label 'LOOP' in node (0,0) (defined: Loop); labels are case-sensitive under --case-sensitive-labels, did you mean 'Loop'?"),
+            "{}",
+            err
+        );
+        let parsed = parse_sensitive("NODE (0,0)\nloop: NOP\nLOOP: JMP loop\nJMP LOOP\n").unwrap();
+        assert_eq!(parsed.labels[&(0, 0)], BTreeMap::from([("LOOP".into(), 1), ("loop".into(), 0)]));
+        assert_eq!(parsed.grid.programs[0][0][1..].iter().map(|i| i.src).collect::<Vec<_>>(), [Src::Lit(0), Src::Lit(1)]);
+    }
+
+    #[test]
+    fn test_local_labels() {
+        let code = "NODE (0,0)\n.init: MOV IN, ACC\nread:\n.loop: SUB 1\nJNZ .loop\nwrite:\n.loop: MOV ACC, OUT\nJMP .loop\nJZ .init\n";
+        let parsed = parse(code).unwrap();
+        let labels: Vec<(&str, usize)> = parsed.labels[&(0, 0)].iter().map(|(n, &pc)| (n.as_str(), pc)).collect();
+        assert_eq!(labels, [(".init", 0), ("read", 1), ("read.loop", 1), ("write", 3), ("write.loop", 3)]);
+        let targets: Vec<Src> = parsed.grid.programs[0][0].iter().filter(|i| i.op != Op::Mov && i.op != Op::Sub).map(|i| i.src).collect();
+        // Each `.loop` is its own routine's; the node's leading locals are in every scope.
+        assert_eq!(targets, [Src::Lit(1), Src::Lit(3), Src::Lit(0)]);
+        assert_eq!(parsed.label_refs[&(0, 0)][&2], "read.loop");
+        assert_eq!(parsed.label_refs[&(0, 0)][&4], "write.loop");
+
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert!(
+            err("NODE (0,0)\nread:\n.done: NOP\nwrite:\nJMP .done\n").contains("local label '.done' belongs to 'read', not to 'write' where it is used"),
+            "{}",
+            err("NODE (0,0)\nread:\n.done: NOP\nwrite:\nJMP .done\n")
+        );
+        assert!(err("NODE (0,0)\nJMP .top\nfirst:\n.top: NOP\n").contains("local label '.top' belongs to 'first', not to the start of node (0,0)"));
+        assert!(err("NODE (0,0)\nx:\nJMP .y\n").contains("undefined label '.y' in node (0,0) (defined: x)"));
+        assert_eq!(err("NODE (0,0)\nx:\n.a: NOP\n.a: NOP\n"), "line 4: label 'x.a' is defined twice in node (0,0), on lines 3 and 4");
+        assert_eq!(err("NODE (0,0)\n.: NOP\n"), "line 2: a local label needs a name after its '.'");
+        // A NODE header starts a new scope, even for a continued node.
+        assert!(err("NODE (0,0)\nx:\n.a: NOP\nNODE (0,0) CONTINUE\nJMP .a\n").contains("belongs to 'x', not to the start of node (0,0)"));
+
+        // Written out in full, a local is still only reachable from its own scope.
+        let qualified = "NODE (0,0)\nread:\n.loop: SUB 1\nJNZ READ.loop\nwrite:\nJMP TARGET\n";
+        assert_eq!(parse(&qualified.replace("TARGET", "write")).unwrap().grid.programs[0][0][1].src, Src::Lit(0));
+        for target in ["read.loop", "read.loop+1"] {
+            let message = err(&qualified.replace("TARGET", target));
+            assert!(message.contains("`.loop` is local to `read`, not to `write` where `read.loop` is used"), "{}", message);
+        }
+        assert!(err("NODE (0,0)\nJMP a.b\na:\n.b: NOP\n").contains("`.b` is local to `a`, not to the start of node (0,0)"));
+    }
+
+    #[test]
+    fn test_undefined_jump_labels() {
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        let code = "NODE (0,0)\nloop:\nMOV IN, ACC\nJNZ lop\ndone:\nHLT\nNODE (1,1)\nJMP loop\n";
+        // Both are reported: the parse reads on past the first.
+        assert_eq!(
+            err(code),
+            "2 errors\n  line 4, col 5, node (0,0): undefined label 'lop' in node (0,0) (defined: done, loop) in `JNZ lop`\n  \
+             line 8, col 5, node (1,1): undefined label 'loop' in node (1,1) (no labels defined) in `JMP loop`"
+        );
+        // Labels of other nodes are not in scope.
+        let code = code.replace("JNZ lop", "JNZ loop");
+        assert!(err(&code).contains("undefined label 'loop' in node (1,1) (no labels defined)"), "{}", err(&code));
+        // Numbers, registers and ports are still jump operands.
+        let jumps = parse("NODE (0,0)\nJMP 0\nJZ ACC\nJGZ P:DOWN\n").unwrap();
+        assert_eq!(jumps.grid.programs[0][0].iter().map(|i| i.src).collect::<Vec<_>>(), [Src::Lit(0), Src::Acc, Src::P(crate::instruction::PortTag::Down)]);
+        assert!(err("NODE (0,0)\nJMP -x\n").contains("undefined constant 'x' (searched node (0,0): none; file: none)"));
+    }
+
+    #[test]
+    fn test_constants() {
+        let code = "DEFINE THRESHOLD 17\nSTEP EQU -2\nNODE (0,0)\nADD THRESHOLD\nNODE (1,1)\ndefine LIMIT 2\nMOV LIMIT, ACC\nADD STEP\nJGZ LIMIT\n";
+        let parsed = parse(code).unwrap();
+        assert_eq!(parsed.constants, Constants::from([("STEP".into(), -2), ("THRESHOLD".into(), 17)]));
+        assert_eq!(parsed.node_constants, BTreeMap::from([((1, 1), Constants::from([("LIMIT".into(), 2)]))]));
+        assert_eq!(parsed.grid.programs[0][0][0].src, Src::Lit(17));
+        let node = &parsed.grid.programs[1][1];
+        assert_eq!([node[0].src, node[1].src, node[2].src], [Src::Lit(2), Src::Lit(2), Src::Lit(2)]);
+        assert_eq!(node[1].op, Op::Sub);
+
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(err("DEFINE N 1\nN EQU 2\n"), "line 2: constant 'N' is defined twice, on lines 1 and 2");
+        assert_eq!(err("DEFINE ACC 1\n"), "line 1: 'ACC' cannot name a constant");
+        assert_eq!(err("DEFINE N\n"), "line 1: expected DEFINE N <literal>");
+        assert!(err("DEFINE N 1\nNODE (0,0)\nADD M\n").contains("undefined constant 'M' (searched node (0,0): none; file: N)"));
+        assert!(err("NODE (0,0)\nMOV M, ACC\n").contains("undefined constant 'M' (searched node (0,0): none; file: none)"));
+        // A jump still reports a missing label, not a missing constant.
+        assert!(err("NODE (0,0)\nJMP M\n").contains("undefined label 'M'"));
+    }
+
+    #[test]
+    fn test_source_as_editors_save_it() {
+        let clean = parse("NODE (0,0)\nloop: MOV IN, ACC\nADD 1\nMOV ACC, OUT\nJMP loop\n").unwrap();
+        let sources = [
+            "NODE (0,0)\r\nloop: MOV IN, ACC\r\nADD 1\r\nMOV ACC, OUT\r\nJMP loop\r\n",
+            "\u{feff}NODE (0,0)\nloop: MOV IN, ACC\nADD 1\nMOV ACC, OUT\nJMP loop",
+            "NODE (0,0)\n\tloop:\tMOV\tIN,\tACC\n\tADD\t1\n\tMOV ACC, OUT\n\tJMP loop\n",
+            "NODE\u{a0}(0,0)\nloop: MOV IN,\u{a0}ACC\nADD\u{a0}1\nMOV ACC, OUT\nJMP\u{a0}loop\n",
+        ];
+        for code in sources {
+            let parsed = parse(code).unwrap_or_else(|e| panic!("{:?}: {}", code, e));
+            assert_eq!(parsed.grid.programs, clean.grid.programs, "{:?}", code);
+            assert_eq!(parsed.lines, clean.lines, "{:?}", code);
+        }
+        for coords in ["(0,0)\r", " (0,0) ", "0, 0\t"] {
+            assert_eq!(parse_node_coords(coords).unwrap(), (0, 0), "{:?}", coords);
+        }
+    }
+
+    #[test]
+    fn test_non_ascii_names() {
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(
+            err("NODE (0,0)\ncafé: NOP\n"),
+            "line 2, col 4, node (0,0): 'café' has the non-ASCII character 'é' (U+00E9); outside comments, source is ASCII \
+             in `café: NOP`"
+        );
+        // A Cyrillic А that reads as ACC.
+        assert!(err("NODE (0,0)\nMOV IN, \u{410}CC\n").starts_with("line 2, col 9, node (0,0): 'АCC' has the non-ASCII character 'А' (U+0410)"));
+        assert!(err("DEFINE ÉTÉ 1\n").starts_with("line 1: 'ÉTÉ' has the non-ASCII character 'É' (U+00C9)"));
+        assert!(parse("NODE (0,0) # é\nNOP ; → fine in a comment\n").is_ok());
+
+        let options = ParseOptions { recover: true, ..Default::default() };
+        let parsed = parse_with_options("NODE (0,0)\nMOV 1, ACC\nJMP fin→\nNOP\n", &Dialect::default(), options).unwrap();
+        assert_eq!(parsed.errors.iter().map(|d| d.line).collect::<Vec<_>>(), [3]);
+        assert_eq!(parsed.grid.programs[0][0].len(), 2);
+    }
+
+    #[test]
+    fn test_constant_scopes() {
+        let code = "GLOBAL LIMIT 10\nDEFINE STEP 1\nNODE (0,0)\nASSERT ACC < LOCAL\nADD LOCAL\nDEFINE LOCAL LIMIT - 7\n\
+                    DEFINE STEP 2\nSUB STEP\nNODE (1,1)\nADD STEP\nADD LIMIT\nNODE (0,0) CONTINUE\nADD STEP\n";
+        let parsed = parse(code).unwrap();
+        assert_eq!(parsed.constants, Constants::from([("LIMIT".into(), 10), ("STEP".into(), 1)]));
+        // A node's DEFINE counts in all of its blocks, above it too, and hides the file's.
+        let listed = |node: &[Inst]| node.iter().map(Inst::to_string).collect::<Vec<_>>();
+        assert_eq!(listed(&parsed.grid.programs[0][0]), ["ADD 3", "SUB 2", "ADD 2"]);
+        assert_eq!(listed(&parsed.grid.programs[1][1]), ["ADD 1", "ADD 10"]);
+        assert_eq!(parsed.asserts[&(0, 0)][&0][0].value, 3);
+        // `--define` wins in every scope.
+        let options = ParseOptions { defines: Constants::from([("STEP".into(), 5)]), ..Default::default() };
+        let defined = parse_with_options(code, &Dialect::default(), options).unwrap();
+        assert_eq!(listed(&defined.grid.programs[0][0]), ["ADD 3", "SUB 5", "ADD 5"]);
+
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(err("GLOBAL LIMIT 10\nNODE (0,1)\nDEFINE LIMIT 4\n"), "line 3: node (0,1) cannot define 'LIMIT'; it is GLOBAL (line 1)");
+        assert_eq!(err("NODE (0,0)\nGLOBAL LIMIT 10\n"), "line 2: GLOBAL LIMIT must come before the first NODE (line 1)");
+        assert_eq!(err("GLOBAL N 1\nDEFINE N 2\n"), "line 2: constant 'N' is defined twice, on lines 1 and 2");
+        assert_eq!(
+            err("NODE (0,0)\nDEFINE N 1\nNODE (0,0) CONTINUE\nN EQU 2\n"),
+            "line 4: constant 'N' is defined twice in node (0,0), on lines 2 and 4"
+        );
+        // Another node's constants are not in scope.
+        assert!(
+            err("NODE (0,0)\nDEFINE N 1\nNODE (1,1)\nDEFINE M 2\nADD N\n")
+                .contains("undefined constant 'N' (searched node (1,1): M; file: none)")
+        );
+    }
+
+    #[test]
+    fn test_constant_expressions() {
+        let code = "DEFINE WIDTH 4\nDEFINE LIMIT 10\nDEFINE AREA WIDTH * WIDTH\nNODE (0,0)\nMOV WIDTH*2+1, ACC\nADD (LIMIT-1)\nSUB ( LIMIT - 1 ) / 2\nADD 1-3\nASSERT ACC == AREA - 8\nJMP LIMIT-10\n";
+        let parsed = parse(code).unwrap();
+        assert_eq!(parsed.constants["AREA"], 16);
+        let listed: Vec<String> = parsed.grid.programs[0][0].iter().map(Inst::to_string).collect();
+        // A negative result is lowered like a written negative literal.
+        assert_eq!(listed, ["MOV 9, ACC", "ADD 9", "SUB 4", "SUB 2", "JMP 0"]);
+        assert_eq!(parsed.asserts[&(0, 0)][&4][0].value, 8);
+
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(
+            err("DEFINE N 0\nNODE (0,0)\nADD 10/N\n"),
+            "line 3, col 5, node (0,0): division by zero in `10/N` in `ADD 10/N`"
+        );
+        assert_eq!(err("DEFINE BIG 65536 * 65536\n"), "line 1: `65536 * 65536` is 4294967296, which does not fit in 32 bits");
+        assert!(err("NODE (0,0)\nADD 2*(3\n").contains("missing ')' in `2*(3`"));
+        // The field is checked on the result.
+        assert!(err("DEFINE WIDTH 100\nNODE (0,0)\nADD WIDTH*3\n").contains("literal 300 does not fit the 8-bit literal field"));
+        // On the whole i64 result, at the expression's column: not a u32 that
+        // wrapped, nor a value cut to 32 bits on the way.
+        assert_eq!(
+            err("NODE (0,0)\nNOP\nMOV 2147483647*2, ACC\n"),
+            "line 3, col 5, node (0,0): literal 4294967294 does not fit the 8-bit literal field (0..=255); \
+             --synthesize-literals builds it in ACC in `MOV 2147483647*2, ACC`"
+        );
+        assert!(err("NODE (0,0)\nSUB 0 - 4294967296\n").starts_with("line 2, col 5, node (0,0): literal -4294967296 does not fit"));
+        // Constants must be defined above a DEFINE that uses them.
+        assert!(err("DEFINE A B+1\nDEFINE B 1\n").contains("undefined constant 'B'"));
+        // Labels are no constants, even where a jump takes them.
+        let label = "NODE (0,0)\nloop: ADD loop+1\n";
+        assert!(err(label).contains("label 'loop' cannot be used in the expression `loop+1`; only constants can"), "{}", err(label));
+        // A space before a signed number still separates operands.
+        assert!(err("NODE (0,0)\nADD 1 -1\n").contains("ADD takes exactly one operand, found 2 (extra: -1)"));
+    }
+
+    #[test]
+    fn test_label_offsets() {
+        let code = "DEFINE SKIP 2\nNODE (0,0)\nloop: MOV IN, ACC\nSUB 1\nJNZ loop+1\nJMP loop + SKIP\nJGZ end-1\nend: JMP loop-0\n";
+        let parsed = parse(code).unwrap();
+        let targets: Vec<Src> = parsed.grid.programs[0][0][2..].iter().map(|i| i.src).collect();
+        assert_eq!(targets, [Src::Lit(1), Src::Lit(2), Src::Lit(4), Src::Lit(0)]);
+        let words = encode_programs(&parsed.grid.programs).unwrap();
+        assert_eq!(words[3], Inst { op: Op::Jnz, src: Src::Lit(1), dst: Dst::Nil }.encode());
+        assert_eq!(words[5], Inst { op: Op::Jgz, src: Src::Lit(4), dst: Dst::Nil }.encode());
+        // The offset counts as a reference to the label.
+        assert_eq!(parsed.label_refs[&(0, 0)][&2], "loop");
+        assert!(parsed.unused_labels().is_empty());
+        // Local labels take offsets too.
+        let local = parse("NODE (0,0)\nread:\n.top: NOP\nNOP\nJMP .top+1\n").unwrap();
+        assert_eq!(local.grid.programs[0][0][2].src, Src::Lit(1));
+
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        let short = "NODE (0,0)\nloop: NOP\nNOP\nNOP\nNOP\nJMP TARGET\n";
+        assert_eq!(
+            err(&short.replace("TARGET", "loop+99")),
+            "line 6, col 5, node (0,0): pc 4 jumps to 99, past the end of the node's 5 instructions (0..=4); the guest would land on pc 4 (label 'loop') in `JMP loop+99`"
+        );
+        assert!(err(&short.replace("TARGET", "loop-1")).contains("jump target `loop-1` is pc -1, outside the node (label 'loop' is pc 0)"));
+        assert!(err(&short.replace("TARGET", "2*loop")).contains("label 'loop' cannot be used in the expression `2*loop`; a jump takes loop+N or loop-N"));
+        // A leading minus is still a literal, and the field check rejects it.
+        assert!(err(&short.replace("TARGET", "-1")).contains("jump target -1 does not fit the 8-bit literal field"));
+    }
+
+    #[test]
+    fn test_rept_unrolls_before_labels() {
+        let code = "DEFINE TIMES 3\nNODE (0,0)\nloop: MOV IN, ACC\n.rept TIMES\n  ADD P:LEFT # one per copy\n.endr\ndone: MOV ACC, OUT\nJMP loop\n";
+        let parsed = parse(code).unwrap();
+        let unrolled = parse("NODE (0,0)\nloop: MOV IN, ACC\nADD P:LEFT\nADD P:LEFT\nADD P:LEFT\ndone: MOV ACC, OUT\nJMP loop\n").unwrap();
+        assert_eq!(parsed.grid, unrolled.grid);
+        assert_eq!(parsed.labels[&(0, 0)]["done"], 4);
+        assert_eq!(parsed.lines[&(0, 0)], [3, 5, 5, 5, 7, 8]);
+
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert!(err("NODE (0,0)\n.rept 2\nx: NOP\n.endr\n").starts_with("line 3: label 'x' inside the .rept on line 2"));
+        assert_eq!(err("NODE (0,0)\n.repeat 2\n"), "line 2: unknown directive '.repeat'; the directives are .rept, .endr, .if, .else, .endif, .pad");
+    }
+
+    #[test]
+    fn test_if_selects_a_variant() {
+        let code = "DEFINE DEBUG 0\nNODE (0,0)\nloop: MOV IN, ACC\n.if DEBUG\n  MOV ACC, P:RIGHT\n.else\n  ADD 1\n  ADD 1\n.endif\ndone: MOV ACC, OUT\nJMP loop\n";
+        let release = parse(code).unwrap();
+        assert_eq!(release.labels[&(0, 0)]["done"], 3);
+        assert_eq!(release.lines[&(0, 0)], [3, 7, 8, 10, 11]);
+
+        // `--define` wins over the file's `DEFINE`.
+        let options = ParseOptions { defines: Constants::from([("DEBUG".to_string(), 1)]), ..Default::default() };
+        let debug = parse_with_options(code, &Dialect::default(), options).unwrap();
+        assert_eq!(debug.labels[&(0, 0)]["done"], 2);
+        assert_eq!(debug.lines[&(0, 0)], [3, 5, 10, 11]);
+
+        // A DEFINE in a dropped branch defines nothing.
+        assert_eq!(constants(".if 0\nDEFINE N 1\n.else\nDEFINE N 2\n.endif\n").unwrap()["N"], 2);
+        assert_eq!(constants(".if 1\nDEFINE N 1\n.else\nDEFINE N 2\n.endif\n").unwrap()["N"], 1);
+        assert_eq!(constants_with("DEFINE N 1\n", &Constants::from([("N".to_string(), 5)])).unwrap()["N"], 5);
+
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(err("NODE (0,0)\n.if 1\nNOP\n"), "line 2: .if has no .endif");
+        assert_eq!(err("NODE (0,0)\n.endif\n"), "line 2: .endif without an .if");
+        assert!(err("NODE (0,0)\n.if DEBUG\n.endif\n").starts_with("line 2: undefined constant 'DEBUG'"));
+    }
+
+    #[test]
+    fn test_parse_define() {
+        assert_eq!(parse_define("DEBUG=1").unwrap(), ("DEBUG".to_string(), 1));
+        assert_eq!(parse_define("DEBUG").unwrap(), ("DEBUG".to_string(), 1));
+        assert_eq!(parse_define("WIDTH=2*3").unwrap(), ("WIDTH".to_string(), 6));
+        assert_eq!(parse_define("ACC=1").unwrap_err().to_string(), "'ACC' cannot name a constant");
+        assert_eq!(parse_define("A B=1").unwrap_err().to_string(), "'A B' cannot name a constant");
+    }
+
+    #[test]
+    fn test_jump_targets_stay_in_the_node() {
+        let node = "NODE (0,0)\nMOV IN, ACC\nJZ TARGET\nNEG\nMOV ACC, OUT\nNOP\n";
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        // The last pc is a target; one past it, the "end" of the node, is not:
+        // the guest would wrap it to pc 0 instead of halting.
+        assert_eq!(parse(&node.replace("TARGET", "4")).unwrap().grid.programs[0][0][1].src, Src::Lit(4));
+        assert_eq!(
+            err(&node.replace("TARGET", "5")),
+            "line 3, col 4, node (0,0): pc 1 jumps to 5, past the end of the node's 5 instructions (0..=4); the guest would land on pc 0 in `JZ 5`"
+        );
+        assert!(err(&node.replace("TARGET", "12")).contains("pc 1 jumps to 12, past the end of the node's 5 instructions (0..=4); the guest would land on pc 2"));
+        // A label after the last instruction is the end of the node too.
+        let code = node.replace("TARGET", "done") + "done:\n";
+        assert!(err(&code).ends_with("the guest would land on pc 0 (label 'done') in `JZ done`"), "{}", err(&code));
+        // Grids built without the parser are checked when encoded.
+        let mut programs = parse_assembly(&node.replace("TARGET", "4")).unwrap();
+        programs[0][0][1].src = Src::Lit(7);
+        assert_eq!(
+            encode_programs(&programs).unwrap_err().to_string(),
+            "node (0,0): pc 1 jumps to 7, past the end of the node's 5 instructions (0..=4); the guest would land on pc 2"
+        );
+    }
+
+    #[test]
+    fn test_hex_and_binary_literals() {
+        let decimal = parse("DEFINE MASK 15\nNODE (0,0)\nMOV 42, ACC\nADD MASK\nSUB -5\nJMP 0\n").unwrap();
+        let radix = parse("DEFINE MASK 0b1111\nNODE (0,0)\nMOV 0x2a, ACC\nADD MASK\nSUB -0X5\nJMP 0b0\n").unwrap();
+        assert_eq!(radix.grid, decimal.grid);
+        assert_eq!(encode_programs(&radix.grid.programs).unwrap(), encode_programs(&decimal.grid.programs).unwrap());
+        assert_eq!(parse_assert(&lexer::lex_line("ACC == 0x10", 1), 1, &ConstantScope::file(&Constants::new())).unwrap().value, 16);
+
+        // The field is checked on the value, whatever base it was written in.
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(err("NODE (0,0)\nADD 0x1FF\n"), "line 2, col 5, node (0,0): literal 511 does not fit the 8-bit literal field (0..=255) in `ADD 0x1FF`");
+        assert!(err("NODE (0,0)\nADD 0b2\n").contains("Invalid source operand: 0b2"));
+    }
+
+    #[test]
+    fn test_bare_port_operands() {
+        let bare = parse("NODE (0,0)\nMOV UP, DOWN\nMOV left, ACC\nADD Right\nJGZ UP\n").unwrap();
+        let prefixed = parse("NODE (0,0)\nMOV P:UP, P:DOWN\nMOV P:LEFT, ACC\nADD P:RIGHT\nJGZ P:UP\n").unwrap();
+        assert_eq!(bare.grid, prefixed.grid);
+        assert_eq!(encode_programs(&bare.grid.programs).unwrap(), encode_programs(&prefixed.grid.programs).unwrap());
+    }
+
+    #[test]
+    fn test_literals_must_fit_the_field() {
+        assert_eq!(parse("NODE (0,0)\nMOV 255, ACC\n").unwrap().grid.programs[0][0][0].src, Src::Lit(255));
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(
+            err("NODE (0,0)\nNOP\nMOV 256, ACC\n"),
+            "line 3, col 5, node (0,0): literal 256 does not fit the 8-bit literal field (0..=255); --synthesize-literals builds it in ACC in `MOV 256, ACC`"
+        );
+        // 300 does not fit, so neither does -300 as SUB 300.
+        assert_eq!(err("NODE (1,1)\nADD -300\n"), "line 2, col 5, node (1,1): literal -300 does not fit the 8-bit literal field (0..=255) in `ADD -300`");
+        assert_eq!(
+            err("NODE (0,0)\nMOV -300, P:DOWN\n"),
+            "line 2, col 5, node (0,0): literal -300 does not fit the 8-bit literal field (0..=255) in `MOV -300, P:DOWN`"
+        );
+        assert!(err("NODE (0,0)\nJNZ 300\n").contains("jump target 300 does not fit"));
+        // Checked as written, not as the u32 it would wrap to: 2147483648 is
+        // not -2147483648 and 4294967295 is not -1, so ADD 4294967295 is not
+        // SUB 1.
+        assert_eq!(
+            err("NODE (0,0)\nMOV 2147483648, ACC\n"),
+            "line 2, col 5, node (0,0): literal 2147483648 does not fit the 8-bit literal field (0..=255); --synthesize-literals builds it in ACC in `MOV 2147483648, ACC`"
+        );
+        assert_eq!(
+            err("NODE (0,0)\nADD 4294967295\n"),
+            "line 2, col 5, node (0,0): literal 4294967295 does not fit the 8-bit literal field (0..=255) in `ADD 4294967295`"
+        );
+        assert_eq!(
+            err("DEFINE ALL 0xFFFFFFFF\nNODE (0,0)\nSUB ALL\n"),
+            "line 3, col 5, node (0,0): literal 4294967295 does not fit the 8-bit literal field (0..=255) in `SUB ALL`"
+        );
+        assert!(err("NODE (0,0)\nADD 4294967296\n").contains("literal 4294967296 does not fit"));
+
+        let far = format!("NODE (0,0)\nJMP far\n{}far:\nHLT\n", "NOP\n".repeat(259));
+        assert_eq!(
+            err(&far),
+            "line 2, col 5, node (0,0): jump target 260 does not fit the 8-bit literal field (0..=255) (label 'far') in `JMP far`"
+        );
+        let mut limits = Limits::default();
+        limits.set(Limit::NodeInstructions, 300, crate::limits::Origin::Flag);
+        let recovered = parse_with_options(&far, &Dialect::default(), ParseOptions { recover: true, limits, ..Default::default() }).unwrap();
+        assert_eq!(recovered.errors.len(), 1);
+
+        // Programs built without the parser are checked when encoded.
+        let programs = vec![vec![vec![Inst { op: Op::Add, src: Src::Lit(300), dst: Dst::Nil }], vec![]], vec![vec![], vec![]]];
+        assert_eq!(encode_programs(&programs).unwrap_err().to_string(), "node (0,0) pc 0: literal 300 does not fit the 8-bit literal field (0..=255)");
+    }
+
+    #[test]
+    fn test_negative_literals_are_lowered() {
+        let code = "NODE (0,0)\nstart:\nADD -1\nSUB -255\nMOV -7, ACC\nJMP start\nJNZ 4\n";
+        let parsed = parse(code).unwrap();
+        let listed: Vec<String> = parsed.grid.programs[0][0].iter().map(Inst::to_string).collect();
+        // The NEG moves the jump to pc 4 along with the instruction there.
+        assert_eq!(listed, ["SUB 1", "ADD 255", "MOV 7, ACC", "NEG", "JMP 0", "JNZ 5"]);
+        assert_eq!(parsed.lines[&(0, 0)], [3, 4, 5, 5, 6, 7]);
+
+        let nil = parse("NODE (0,0)\nMOV -7, NIL\n").unwrap();
+        assert_eq!(nil.grid.programs[0][0], [Inst { op: Op::Mov, src: Src::Lit(7), dst: Dst::Nil }]);
+
+        // Sending one through ACC would clobber it, so that is spelled out
+        // rather than done behind the author's back.
+        assert_eq!(
+            parse("NODE (0,0)\nMOV IN, ACC\nMOV -7, P:DOWN\n").unwrap_err().to_string(),
+            "line 3, col 5, node (0,0): the literal field has no sign, so MOV -7 only goes into ACC or NIL; \
+             MOV 7, ACC; NEG; MOV ACC, P:DOWN sends it but leaves -7 in ACC in `MOV -7, P:DOWN`"
+        );
+        assert!(parse("NODE (1,1)\nMOV -1, OUT\n").unwrap_err().to_string().contains("MOV 1, ACC; NEG; MOV ACC, OUT sends it"));
+    }
+
+    /// The lowered code computes what the signed source says.
+    #[cfg(feature = "sim")]
+    #[test]
+    fn test_lowered_literals_simulate() {
+        let run = |code: &str| crate::sim::simulate(&parse_assembly(code).unwrap(), &[10], 20).unwrap().outputs;
+        assert_eq!(run("NODE (0,0)\nMOV IN, ACC\nADD -3\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n"), [7]);
+        assert_eq!(run("NODE (1,1)\nMOV -7, ACC\nMOV ACC, OUT\nMOV P:UP, ACC\n"), [(-7i32) as u32]);
+        let down = "NODE (0,0)\nMOV 7, ACC\nNEG\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+        // Node (0,0) loops, sending -7 each time round.
+        assert_eq!(run(down)[0], (-7i32) as u32);
+    }
+
+    #[test]
+    fn test_duplicate_labels_are_scoped_to_their_node() {
+        let code = "NODE (0,0)\nx:\nNOP\n# again\nx:\nJMP x\n";
+        assert_eq!(
+            parse(code).unwrap_err().to_string(),
+            "line 5: label 'x' is defined twice in node (0,0), on lines 2 and 5"
+        );
+        // Recovering keeps the first definition.
+        let result = recover(code);
+        assert_eq!(result.errors, [Diagnostic { line: 5, message: "label 'x' is defined twice in node (0,0), on lines 2 and 5".to_string() }]);
+        assert_eq!(result.labels[&(0, 0)]["x"], 0);
+        // A continued NODE block reopens the same node and its labels.
+        let err = parse("NODE (0,0)\nx:\nNOP\nNODE (1,1)\nNOP\nNODE (0,0) CONTINUE\nx:\nNOP\n").unwrap_err();
+        assert!(err.to_string().starts_with("line 7: label 'x' is defined twice in node (0,0), on lines 2 and 7"), "{}", err);
+
+        let reused = parse("NODE (0,0)\nloop:\nJMP loop\nNODE (1,1)\nNOP\nloop:\nJMP loop\n").unwrap();
+        assert_eq!(reused.labels[&(0, 0)]["loop"], 0);
+        assert_eq!(reused.labels[&(1, 1)]["loop"], 1);
+        assert_eq!(reused.grid.programs[1][1][1].src, Src::Lit(1));
+    }
+
+    #[test]
+    fn test_label_cross_reference() {
+        let code = "NODE (0,0)\nloop:\nMOV IN, ACC\nJZ done\nJGZ loop\nJMP loop\ndone:\nMOV 4, P:DOWN\nspare:\nJMP 0\n";
+        let result = parse(code).unwrap();
+        assert_eq!(result.label_refs[&(0, 0)], BTreeMap::from([(1, "done".into()), (2, "loop".into()), (3, "loop".into())]));
+
+        let node = &result.symbols()[0];
+        let refs: Vec<(&str, usize, &[usize])> =
+            node.labels.iter().map(|l| (l.name.as_str(), l.line, l.references.as_slice())).collect();
+        assert_eq!(refs, [("done", 7, &[1][..]), ("loop", 2, &[2, 3][..]), ("spare", 9, &[][..])]);
+        let jumps: Vec<(usize, Option<&str>)> = node.jumps.iter().map(|j| (j.pc, j.label.as_deref())).collect();
+        assert_eq!(jumps, [(1, Some("done")), (2, Some("loop")), (3, Some("loop")), (5, None)]);
+
+        let unused = result.unused_labels();
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].to_string(), "line 9: label 'spare' in node (0,0) is never referenced");
+    }
+
+    #[test]
+    fn test_labels_are_jump_operands() {
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        for inst in ["MOV loop, ACC", "ADD loop", "SUB loop"] {
+            let code = format!("NODE (0,0)\nloop:\n{}\nJMP loop\n", inst);
+            assert!(
+                err(&code).starts_with("line 3, col 5, node (0,0): label 'loop' cannot be used as a data operand (did you mean a literal?)"),
+                "{}",
+                err(&code)
+            );
+        }
+        // A constant of the same name is still data; a number is always a literal.
+        let parsed = parse("DEFINE loop 7\nNODE (0,0)\nloop:\nADD loop\nJMP loop\n1:\nADD 1\n").unwrap();
+        let srcs: Vec<Src> = parsed.grid.programs[0][0].iter().map(|i| i.src).collect();
+        assert_eq!(srcs, [Src::Lit(7), Src::Lit(0), Src::Lit(1)]);
+    }
+
+    #[test]
+    fn test_port_warnings() {
+        let code = "NODE (0,0)\nMOV IN, P:RIGHT\nMOV P:UP, P:LEFT\nNODE (0,1)\nMOV P:LEFT, P:DOWN\nNODE (1,1)\nMOV P:UP, OUT\nMOV ACC, RIGHT\n";
+        let messages: Vec<String> = parse(code).unwrap().port_warnings().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "line 3: node (0,0) pc 1 reads P:UP, but the node is on the top edge of the 2x2 grid; the transfer blocks forever",
+                "line 3: node (0,0) pc 1 writes P:LEFT, but the node is on the left edge of the 2x2 grid; the transfer blocks forever",
+                "line 8: node (1,1) pc 1 writes P:RIGHT, but the node is on the right edge of the 2x2 grid; the transfer blocks forever",
+            ]
+        );
+        // The grid decides where the edges are.
+        let wide = parse("GRID 3 3\nNODE (1,1)\nMOV P:UP, P:DOWN\nMOV P:LEFT, P:RIGHT\nNODE BOTTOM_RIGHT\nMOV P:DOWN, ACC\n").unwrap();
+        assert_eq!(wide.port_warnings().len(), 1);
+        assert!(wide.port_warnings()[0].message.starts_with("node BOTTOM_RIGHT (2,2) pc 0 reads P:DOWN, but the node is on the bottom edge of the 3x3 grid"));
+    }
+
+    #[test]
+    fn test_io_warnings_flag_in_outside_the_in_node() {
+        let code = "NODE (0,0)\nMOV IN, ACC\nNODE (1,0)\nNOP\nADD IN\n";
+        let result = parse(code).unwrap();
+        let warnings = result.io_warnings(&IoNodes::default());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].to_string(), "line 5: node (1,0) reads IN, but the input stream goes to node (0,0)");
+        let moved = IoNodes { in_node: (1, 0), ..Default::default() };
+        assert_eq!(result.io_warnings(&moved)[0].line, 2);
+    }
+
+    #[test]
+    fn test_asserts_are_a_side_table() {
+        let plain = "NODE (0,0)\nMOV IN, ACC\nSUB 1\nMOV ACC, P:DOWN\n";
+        let checked = "NODE (0,0)\nMOV IN, ACC\nassert acc >= 1\nSUB 1\nASSERT ACC == -1 # never\nMOV ACC, P:DOWN\n";
+        let result = parse(checked).unwrap();
+        assert_eq!(result.grid.programs, parse(plain).unwrap().grid.programs);
+        assert_eq!(result.lines[&(0, 0)], [2, 4, 6]);
+        let guards: Vec<(usize, String, usize)> = result.asserts[&(0, 0)]
+            .iter()
+            .flat_map(|(&pc, asserts)| asserts.iter().map(move |a| (pc, a.to_string(), a.line)))
+            .collect();
+        assert_eq!(guards, [(1, "ASSERT ACC >= 1".to_string(), 3), (2, "ASSERT ACC == -1".to_string(), 5)]);
+        assert!(result.asserts[&(0, 0)][&2][0].holds(u32::MAX));
+        assert_eq!(result.symbols()[0].asserts[0], AssertSymbol { pc: 1, line: 3, condition: "ASSERT ACC >= 1".into() });
+
+        for (code, message) in [
+            ("NODE (0,0)\nASSERT BAK > 0\nNOP\n", "line 2: expected ASSERT ACC"),
+            ("NODE (0,0)\nASSERT ACC => 0\nNOP\n", "line 2: expected ASSERT ACC"),
+            ("NODE (0,0)\nASSERT ACC > 4294967296\nNOP\n", "line 2: ASSERT literal 4294967296 does not fit"),
+            // ACC compares signed, so these would silently be -2147483648 and -1.
+            ("NODE (0,0)\nASSERT ACC > 2147483648\nNOP\n", "line 2: ASSERT literal 2147483648 does not fit the signed 32 bits"),
+            ("NODE (0,0)\nASSERT ACC == 4294967295\nNOP\n", "line 2: ASSERT literal 4294967295 does not fit the signed 32 bits"),
+            ("NODE (0,0)\nNOP\nASSERT ACC > 0\n", "line 3: ASSERT after the last instruction of node (0,0)"),
+        ] {
+            let err = parse(code).unwrap_err().to_string();
+            assert!(err.starts_with(message), "{}", err);
+        }
+    }
+
+    fn recover(code: &str) -> ParseResult {
+        parse_with_options(code, &Dialect::default(), ParseOptions { recover: true, ..Default::default() }).unwrap()
+    }
+
+    const FOUR_NODES: &str = "NODE (0,0)\nMOV IN, ACC\nADD ACC\nMOV ACC, P:RIGHT\n\
+NODE (0,1)\nMOV P:LEFT, ACC\nMOV ACC, P:DOWN\n\
+NODE (1,0)\nMOV 7, ACC\nSWP\n\
+NODE (1,1)\nMOV P:UP, ACC\nADD\nMOV ACC, OUT\n";
+
+    #[test]
+    fn test_errors_name_line_column_and_node() {
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        // Comments and blank lines still count.
+        let code = "# doubler\n\nNODE (0,0)\nMOV IN, ACC\n\nNODE (1,0)  # second\n  MVO P:UP, ACC\n";
+        assert_eq!(err(code), "line 7, col 3, node (1,0): Unknown operation: MVO in `MVO P:UP, ACC`");
+        assert_eq!(err("NODE (0,0)\nMOV P:UP, BAK\n"), "line 2, col 11, node (0,0): Invalid destination operand: BAK (expected ACC, NIL, OUT, LAST or a port: UP, DOWN, LEFT or RIGHT, with or without P:) in `MOV P:UP, BAK`");
+        assert_eq!(err("NODE (0,0)\nMOV nowhere, ACC\n"), "line 2, col 5, node (0,0): undefined constant 'nowhere' (searched node (0,0): none; file: none) in `MOV nowhere, ACC`");
+        assert_eq!(err("NODE (0,0)\nADD\n"), "line 2, col 4, node (0,0): Missing operand for ADD in `ADD`");
+        assert_eq!(
+            err("# x\nNODE  (0,x)\n"),
+            "line 2, col 7: Invalid node coordinates: (0,x) ('x' is not a number); write NODE (row,col), NODE(row,col), NODE row,col, NODE @index or NODE TOP_LEFT, TOP_RIGHT, BOTTOM_LEFT or BOTTOM_RIGHT in `NODE  (0,x)`"
+        );
+        assert_eq!(node_ranges("NODE (3,0)\n").unwrap_err().to_string(), "line 1, col 6: Node coordinates must be in 2x2 grid: (3,0) in `NODE (3,0)`");
+
+        // The jump checks run on the lowered node, and still point at the
+        // operand as written, past the instructions a negative literal added.
+        let jump = "NODE (0,0)\nMOV -1, ACC\nloop:  JNZ   loop + 9\n";
+        assert_eq!(
+            err(jump),
+            "line 3, col 14, node (0,0): pc 2 jumps to 11, past the end of the node's 3 instructions (0..=2); the guest would land on pc 2 (label 'loop') in `loop:  JNZ   loop + 9`"
+        );
+        let far = format!("NODE (0,0)\nMOV -1, ACC\n  JMP  far\n{}far: NOP\n", "NOP\n".repeat(300));
+        assert!(err(&far).starts_with("line 3, col 8, node (0,0): jump target 303 does not fit"), "{}", err(&far));
+        // Recovering, the message is the bare one.
+        assert!(recover(jump).errors[0].message.starts_with("pc 2 jumps to 11"));
+    }
+
+    #[test]
+    fn test_every_error_is_reported() {
+        let code = "NODE (0,0)\nFROB ACC\nADD 999\nNODE (9,9)\nNOP\nJMP nowhere\nNODE (1,1)\nx: NOP\nx: JMP y\n";
+        let err = parse(code).unwrap_err();
+        let errors = &err.downcast_ref::<AssemblyErrors>().unwrap().errors;
+        let found: Vec<(usize, Option<(usize, usize)>)> = errors.iter().map(|e| (e.line, e.node)).collect();
+        // The lines under the bad header are in no node and not read.
+        assert_eq!(found, [(2, Some((0, 0))), (3, Some((0, 0))), (4, None), (9, Some((1, 1))), (9, Some((1, 1)))]);
+        assert_eq!(errors[0].message, "line 2, col 1, node (0,0): Unknown operation: FROB in `FROB ACC`");
+        assert_eq!(errors[1].message, "line 3, col 5, node (0,0): literal 999 does not fit the 8-bit literal field (0..=255) in `ADD 999`");
+        assert!(err.to_string().starts_with("5 errors\n  line 2, col 1, node (0,0): Unknown operation: FROB"), "{}", err);
+
+        // A header reopening a node skips its block the same way.
+        let reopened = parse("NODE (0,0)\nNOP\nNODE (0,0)\nFROB\n").unwrap_err().to_string();
+        assert_eq!(reopened, "line 3: node (0,0) is declared twice, on lines 1 and 3; write NODE (0,0) CONTINUE to append to it");
+        let result = recover("NODE (9,9)\nFROB\nNODE (1,1)\nFROB\n");
+        assert_eq!(result.errors.iter().map(|d| d.line).collect::<Vec<_>>(), [1, 4]);
+        assert_eq!(result.grid.programs[1][1], [PLACEHOLDER]);
+    }
+
+    #[test]
+    fn test_extra_operands_are_errors() {
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(err("NODE (0,0)\nADD 1, ACC\n"), "line 2, col 8, node (0,0): ADD takes exactly one operand, found 2 (extra: ACC) in `ADD 1, ACC`");
+        assert!(err("NODE (0,0)\nNOP 5\n").contains("NOP takes no operands, found 1 (extra: 5)"));
+        assert!(err("NODE (0,0)\nHLT ACC\n").contains("HLT takes no operands, found 1 (extra: ACC)"));
+        assert!(err("NODE (0,0)\nMOV IN, ACC, NIL P:UP\n").contains("MOV takes exactly two operands, found 4 (extra: NIL, P:UP)"));
+
+        // --lenient drops them, as before, but says so.
+        let options = ParseOptions { lenient: true, ..Default::default() };
+        let parsed = parse_with_options("NODE (0,0)\nADD 1, ACC\nNOP 5\n", &Dialect::default(), options).unwrap();
+        assert_eq!(parsed.grid.programs[0][0], [Inst { op: Op::Add, src: Src::Lit(1), dst: Dst::Nil }, Inst { op: Op::Nop, src: Src::Nil, dst: Dst::Nil }]);
+        let warnings: Vec<String> = parsed.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            warnings,
+            [
+                "line 2: ADD takes exactly one operand, found 2 (extra: ACC); ignoring them (--lenient)",
+                "line 3: NOP takes no operands, found 1 (extra: 5); ignoring them (--lenient)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operand_commas() {
+        let mov = Inst { op: Op::Mov, src: Src::Lit(1), dst: Dst::Acc };
+        for code in ["MOV 1,ACC", "MOV 1, ACC", "MOV 1 , ACC", "MOV 1 ,ACC"] {
+            assert_eq!(parse(&format!("NODE (0,0)\n{}\n", code)).unwrap().grid.programs[0][0], [mov], "{}", code);
+        }
+        let err = |code: &str| parse(&format!("NODE (0,0)\n{}\n", code)).unwrap_err().to_string();
+        assert_eq!(err("MOV 1 ACC"), "line 2, col 7, node (0,0): expected ',' between source and destination in `MOV 1 ACC`");
+        assert!(err("MOV 1,, ACC").contains("col 7, node (0,0): expected an operand between ',' and ','"));
+        assert!(err("ADD ,1").contains("col 5, node (0,0): unexpected ',' before the first operand"));
+        assert!(err("MOV 1, ACC,").contains("col 11, node (0,0): unexpected ',' after the last operand"));
+        // Touching tokens are still one operand.
+        assert_eq!(parse("NODE (0,0)\nMOV P:UP,P:DOWN\n").unwrap().grid.programs[0][0][0].dst.to_string(), "P:DOWN");
+
+        let options = ParseOptions { lenient: true, ..Default::default() };
+        let parsed = parse_with_options("NODE (0,0)\nMOV 1 ACC\n", &Dialect::default(), options).unwrap();
+        assert_eq!(parsed.grid.programs[0][0], [mov]);
+        assert_eq!(parsed.diagnostics[0].to_string(), "line 2: expected ',' between source and destination (--lenient)");
+    }
+
+    #[test]
+    fn test_node_declarations() {
+        for header in ["NODE (0,1)", "NODE 0,1", "NODE (0, 1)", "NODE( 0 , 1 )", "NODE(0,1)", "NODE 0 , 1 # comment"] {
+            let parsed = parse(&format!("{}\nNOP\n", header)).unwrap();
+            assert_eq!(parsed.grid.programs[0][1].len(), 1, "{}", header);
+        }
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        let forms = "; write NODE (row,col), NODE(row,col), NODE row,col, NODE @index or NODE TOP_LEFT, TOP_RIGHT, BOTTOM_LEFT or BOTTOM_RIGHT in";
+        for (header, why) in [
+            ("NODE (0)", "Invalid node coordinates: (0) (expected a row and a column separated by a comma)"),
+            ("NODE (a,b)", "Invalid node coordinates: (a,b) ('a' is not a number)"),
+            ("NODE (0,1", "Invalid node coordinates: (0,1 (unbalanced parentheses)"),
+            ("NODE ((0,1))", "Invalid node coordinates: ((0,1)) (unbalanced parentheses)"),
+            ("NODE 0 1", "Invalid node coordinates: 0 1 (expected a row and a column separated by a comma)"),
+            ("NODE", "NODE needs coordinates"),
+        ] {
+            assert!(err(header).contains(&format!("{}{}", why, forms)), "{}: {}", header, err(header));
+        }
+    }
+
+    #[test]
+    fn test_node_names() {
+        let code = "GRID 2 3\nNODE top_left\nMOV IN, ACC\nNODE TOP_RIGHT\nNOP\nNODE Bottom_Left\nNOP\nNODE BOTTOM_RIGHT\nNOP\nNODE @4\nNEG\n";
+        let parsed = parse(code).unwrap();
+        let lens: Vec<Vec<usize>> = parsed.grid.programs.iter().map(|row| row.iter().map(Vec::len).collect()).collect();
+        assert_eq!(lens, [[1, 0, 1], [1, 1, 1]]);
+        assert_eq!(parsed.node_label((0, 2)), "TOP_RIGHT (0,2)");
+        assert_eq!(parsed.node_label((1, 1)), "@4 (1,1)");
+        assert_eq!(parsed.node_label((0, 1)), "(0,1)");
+        // A name and the coordinates are the same node.
+        assert!(parse("NODE TOP_LEFT\nNOP\nNODE (0,0) CONTINUE\nNOP\n").is_ok());
+        assert!(parse("NODE @0\nNOP\nNODE (0,0)\nNOP\n").unwrap_err().to_string().contains("node (0,0) is declared twice"));
+
+        // Diagnostics show the name with the coordinates.
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert!(err("NODE bottom_right\nADD\n").starts_with("line 2, col 4, node BOTTOM_RIGHT (1,1): "), "{}", err("NODE bottom_right\nADD\n"));
+        assert!(err("NODE @1\nADD 300\n").starts_with("line 2, col 5, node @1 (0,1): literal 300"));
+        let dead = parse("NODE TOP_LEFT\nx: JMP 0\nNOP\n").unwrap();
+        assert_eq!(dead.unreachable_warnings()[0].message, "node TOP_LEFT (0,0) pc 1 is never reached from pc 0");
+        assert_eq!(dead.unused_labels()[0].message, "label 'x' in node TOP_LEFT (0,0) is never referenced");
+
+        assert_eq!(
+            err("NODE MIDDLE\nNOP\n"),
+            "line 1, col 6: no node is named MIDDLE; the names are TOP_LEFT, TOP_RIGHT, BOTTOM_LEFT, BOTTOM_RIGHT and @0..=@3 in the 2x2 grid in `NODE MIDDLE`"
+        );
+        assert!(err("NODE @4\nNOP\n").contains("no node is named @4; the names are"));
+        assert!(err("NODE @x\nNOP\n").contains("no node is named @X;"));
+    }
+
+    #[test]
+    fn test_repeated_node_blocks() {
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert_eq!(
+            err("NODE (0,0)\nNOP\nNODE (1,1)\nNOP\nNODE (0,0)\nNEG\n"),
+            "line 5: node (0,0) is declared twice, on lines 1 and 5; write NODE (0,0) CONTINUE to append to it"
+        );
+        assert_eq!(err("NODE (0,0) CONTINUE\nNOP\n"), "line 1: NODE (0,0) CONTINUE has no earlier NODE (0,0) block to append to");
+
+        // A continuation appends, and its labels count pcs from the whole node.
+        let code = "NODE (0,0)\nMOV IN, ACC\nNODE (1,1)\nNOP\nNODE(0,0) continue\nagain:\nNEG\nJMP again\n";
+        let parsed = parse(code).unwrap();
+        assert_eq!(parsed.labels[&(0, 0)]["again"], 1);
+        assert_eq!(parsed.grid.programs[0][0][2], Inst { op: Op::Jmp, src: Src::Lit(1), dst: Dst::Nil });
+        assert_eq!(parsed.node_ranges[&(0, 0)], [1..3, 5..9]);
+    }
+
+    #[test]
+    fn test_grid_directive_sizes_the_grid() {
+        let parsed = parse("# wide\nGRID 1 3\nNODE (0,2)\nMOV IN, ACC\n").unwrap();
+        assert_eq!(parsed.grid.size(), GridSize::new(1, 3).unwrap());
+        assert_eq!(parsed.grid.programs[0][2].len(), 1);
+        assert_eq!(grid_size("NODE (0,0)\n").unwrap(), GridSize::default());
+
+        let err = |code: &str| parse(code).unwrap_err().to_string();
+        assert!(err("GRID 1 3\nNODE (1,0)\n").contains("Node coordinates must be in 1x3 grid: (1,0)"));
+        assert_eq!(err("GRID 9 1\n"), "line 1: a grid has 1 to 8 rows and columns, not 9x1");
+        assert_eq!(err("GRID 3\n"), "line 1: expected GRID <rows> <cols>");
+        assert_eq!(err("GRID 2 2\nGRID 3 3\n"), "line 2: GRID is declared twice (first on line 1)");
+        assert_eq!(err("NODE (0,0)\nGRID 3 3\n"), "line 2: GRID must come before the first NODE (line 1)");
+
+        let options = |rows, cols| ParseOptions { grid: Some(GridSize::new(rows, cols).unwrap()), ..Default::default() };
+        assert_eq!(parse_with_options("NODE (2,2)\n", &Dialect::default(), options(3, 3)).unwrap().grid.size().nodes(), 9);
+        assert!(parse_with_options("GRID 3 3\n", &Dialect::default(), options(3, 3)).is_ok());
+        let err = parse_with_options("GRID 3 3\n", &Dialect::default(), options(2, 4)).unwrap_err().to_string();
+        assert_eq!(err, "line 1: the source declares a 3x3 grid, but 2x4 was asked for");
+    }
+
+    #[test]
+    fn test_recover_keeps_the_other_nodes() {
+        // Line 13 lacks its operand; the default parse gives up on it.
+        assert!(parse(FOUR_NODES).unwrap_err().to_string().contains("Missing operand for ADD"));
+
+        let result = recover(FOUR_NODES);
+        assert!(result.is_partial());
+        assert_eq!(result.errors, [Diagnostic { line: 13, message: "Missing operand for ADD".to_string() }]);
+        let fixed = parse(&FOUR_NODES.replace("ADD\n", "ADD 1\n")).unwrap();
+        for node in [(0, 0), (0, 1), (1, 0)] {
+            assert_eq!(result.grid.programs[node.0][node.1], fixed.grid.programs[node.0][node.1]);
+        }
+        assert_eq!(result.grid.programs[1][1][1], PLACEHOLDER);
+        assert_eq!(result.lines[&(1, 1)], [12, 13, 14]);
+
+        // Nothing partial reaches args or a root.
+        let err = encode_programs(&result.grid.programs).unwrap_err().to_string();
+        assert!(err.starts_with("node (1,1) pc 1 is a placeholder"), "{}", err);
+        #[cfg(feature = "merkle")]
+        assert!(crate::merkle::program_root_hex(&result.grid.programs).is_err());
+        assert!(!fixed.is_partial());
+        assert!(encode_programs(&fixed.grid.programs).is_ok());
+    }
+
+    #[test]
+    fn test_recover_holds_pcs() {
+        let code = "NODE (0,0)\nMOV IN, ACC\nBOGUS 1\nASSERT BAK > 0\nLOOP:\nMOV ACC, OUT\nJMP LOOP\nASSERT ACC > 0\n";
+        let result = recover(code);
+        let lines: Vec<usize> = result.errors.iter().map(|d| d.line).collect();
+        assert_eq!(lines, [3, 4, 8]);
+        // The placeholder keeps pc 1, so LOOP is still pc 2 as written.
+        assert_eq!(result.labels[&(0, 0)]["LOOP"], 2);
+        let program = &result.grid.programs[0][0];
+        assert_eq!(program.len(), 4);
+        assert_eq!((program[1], program[3].src), (PLACEHOLDER, Src::Lit(2)));
+        assert!(result.asserts.get(&(0, 0)).is_none_or(BTreeMap::is_empty));
+    }
+
+    #[test]
+    fn test_limits() {
+        use crate::limits::{Limit, Limits, Origin};
+        let mut limits = Limits::default();
+        limits.set(Limit::NodeInstructions, 2, Origin::Config);
+        let code = FOUR_NODES.replace("ADD\n", "ADD 1\n");
+        let options = ParseOptions { limits, ..Default::default() };
+        let err = parse_with_options(&code, &Dialect::default(), options).unwrap_err().to_string();
+        assert_eq!(err, "node (0,0) has 3 instructions, over the node_instructions limit of 2 (from zk100.toml)");
+
+        let programs = parse(&code).unwrap().grid.programs;
+        let words = encode_programs(&programs).unwrap().len() as u64;
+        limits = Limits::default();
+        limits.set(Limit::ProgWords, words - 1, Origin::Flag);
+        let err = encode_programs_with(&programs, &limits).unwrap_err().to_string();
+        assert_eq!(err, format!("the encoded grid has {} words, over the prog_words limit of {} (from --limit)", words, words - 1));
+        limits.set(Limit::ProgWords, words, Origin::Flag);
+        assert!(encode_programs_with(&programs, &limits).is_ok());
+    }
+
+    #[test]
+    fn test_pad() {
+        let code = ".pad 4\nNODE (0,0)\nloop: MOV IN, ACC\nJMP loop\nNODE (1,1)\nMOV P:UP, OUT\n";
+        let parsed = parse(code).unwrap();
+        let nop = Inst { op: Op::Nop, src: Src::Nil, dst: Dst::Nil };
+        assert_eq!(parsed.grid.programs[0][0][2..], [nop, nop]);
+        assert_eq!(parsed.grid.programs.iter().flatten().map(Vec::len).collect::<Vec<_>>(), [4, 0, 0, 4]);
+        assert_eq!(parsed.padding, BTreeMap::from([((0, 0), 2), ((1, 1), 3)]));
+        assert_eq!(parsed.lines[&(0, 0)], [3, 4]);
+        // Padding behind the JMP is unreachable, with no line to warn on.
+        assert_eq!((parsed.source_line((0, 0), 1), parsed.source_line((0, 0), 2)), (Some(4), None));
+        assert!(parsed.unreachable_warnings().is_empty());
+
+        let pad_to = |n| ParseOptions { pad_to: Some(n), ..Default::default() };
+        let unpadded = code.replace(".pad 4\n", "");
+        assert_eq!(parse_with_options(&unpadded, &Dialect::default(), pad_to(4)).unwrap().grid, parsed.grid);
+        assert_eq!(parse_with_options(code, &Dialect::default(), pad_to(4)).unwrap().grid, parsed.grid);
+        let err = |code: &str, options| parse_with_options(code, &Dialect::default(), options).unwrap_err().to_string();
+        assert_eq!(err(code, pad_to(5)), "line 1: the source pads nodes to 4 instructions, but --pad-to 5 was asked for");
+        assert_eq!(err(&unpadded, pad_to(1)), "node (0,0) has 2 instructions, more than the 1 every node is padded to");
+        assert_eq!(err(&unpadded, pad_to(0)), "nodes cannot be padded to 0 instructions");
+        // The limit applies to the padded node.
+        let mut limits = Limits::default();
+        limits.set(Limit::NodeInstructions, 3, crate::limits::Origin::Flag);
+        let options = ParseOptions { limits, ..Default::default() };
+        assert_eq!(err(code, options), "node (0,0) padded to 4 instructions, over the node_instructions limit of 3 (from --limit)");
+    }
+
+    #[test]
+    fn test_tis100() {
+        let tis100 = || ParseOptions { tis100: Some(IoNodes::default()), ..Default::default() };
+        let parse_tis100 = |code: &str| parse_with_options(code, &Dialect::default(), tis100());
+        // Bare ports without commas, JEZ, LAST as NIL and the streams as UP and DOWN.
+        let code = "NODE (0,0)\nS: MOV UP ACC\nJEZ S\nMOV ACC, DOWN\nMOV LAST, NIL\nNODE (1,1)\nMOV LEFT ACC\nMOV ACC DOWN\n";
+        let parsed = parse_tis100(code).unwrap();
+        let zk100 = "NODE (0,0)\nS: MOV IN, ACC\nJZ S\nMOV ACC, P:DOWN\nMOV NIL, NIL\nNODE (1,1)\nMOV P:LEFT, ACC\nMOV ACC, OUT\n";
+        assert_eq!(parsed.grid, parse(zk100).unwrap().grid);
+        let sub = parse_tis100("NODE (1,0)\nADD -200\n").unwrap();
+        assert_eq!(sub.grid.programs[1][0], [Inst { op: Op::Sub, src: Src::Lit(200), dst: Dst::Nil }]);
+
+        let code = "DEFINE K 3\nNODE (0,0)\nMOV IN, ACC\nHLT\nNODE (0,1)\nMOV P:LEFT, ACC\nADD 1000\nADD K\n\
+                    NODE (1,1)\nMOV UP, LEFT\nJRO 2\nMOV ANY, ACC\nJMP 0\n.x: JZ .x\n";
+        assert_eq!(
+            parse_tis100(code).unwrap_err().to_string(),
+            "12 problems under --tis100\n\
+             before the first NODE:\n  line 1: the constant 'K' is a zk-100 extension\n\
+             node (0,0):\n  line 3: IN is a zk-100 operand; TIS-100 reads the input stream as UP in node (0,0)\n  \
+             line 4: HLT is a zk-100 extension; TIS-100 nodes never halt\n\
+             node (0,1):\n  line 6: the P: prefix is zk-100 syntax; TIS-100 writes LEFT\n  \
+             line 7: 1000 is outside TIS-100's -999..=999\n  \
+             line 8: 'K' is not a TIS-100 operand (constants and expressions are zk-100 extensions)\n\
+             node (1,1):\n  line 10: port-to-port MOV drops the value in the guest; MOV through ACC\n  \
+             line 11: JRO has no zk-100 op\n  line 12: ANY has no zk-100 port\n  \
+             line 13: TIS-100 jumps to a label, not `0`\n  line 14: local label '.x' is a zk-100 extension\n  \
+             line 14: JZ is a zk-100 mnemonic; TIS-100 writes JEZ"
+        );
+
+        let long = format!("NODE (0,0)\n{}", "NOP\n".repeat(17));
+        let err = parse_tis100(&long).unwrap_err().to_string();
+        assert!(err.ends_with("line 17: node (0,0) has 17 instructions, more than the 15 a TIS-100 node holds"), "{}", err);
+        assert_eq!(parse_with_options(&long, &Dialect::default(), ParseOptions::default()).unwrap().grid.programs[0][0].len(), 17);
+        // A recovering parse keeps the errors for the caller instead.
+        let options = ParseOptions { recover: true, ..tis100() };
+        assert_eq!(parse_with_options(&long, &Dialect::default(), options).unwrap().errors.len(), 1);
+    }
+}
@@ -0,0 +1,332 @@
+//! Reproducible-build metadata and attestation.
+//!
+//! `Metadata` is the `metadata.json` sidecar written by `assemble --metadata`
+//! and stored in bundles. It records everything that determines the program
+//! root and the args: the assemble options, the encoding, commitment and ABI
+//! versions, and the build of the tool. `attest` re-runs the pipeline with the
+//! recorded options and compares the results. The compatibility profile, if
+//! one was used, is recorded next to the settings it resolved to. An option or version this build
+//! cannot honor is reported as such; it is never replaced by a default.
+
+use crate::cairo_abi::{self, CairoArgs, InputSentinel, IoNodes, NodeOutputs};
+use crate::capabilities;
+use crate::dialect::Dialect;
+use crate::inputs::InputSpec;
+use crate::limits::Limits;
+use crate::merkle::HashFunction;
+use crate::program_id::ProgramId;
+use crate::sim::{self, Schedule};
+use crate::assembler::{Constants, GridSize, ParseOptions};
+use crate::{assembler, built_info, merkle};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Instruction word layout (see `zk100_core::instruction::FIELDS`).
+pub const ENCODING_VERSION: u32 = 1;
+pub use crate::merkle::COMMITMENT_VERSION;
+/// Newest args layout (`cairo_abi::GRID_ABI`); every layout back to
+/// `cairo_abi::LEGACY_ABI` is still written on request.
+pub const ABI_VERSION: u32 = cairo_abi::GRID_ABI;
+
+/// Options `assemble` was run with. Keys this build does not know are kept in
+/// `unknown` so attestation can name them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct AssembleOptions {
+    /// The input stream.
+    pub inputs: Vec<u32>,
+    /// The expected output stream (ABIs with one flat stream).
+    pub expected: Vec<u32>,
+    /// Expected outputs per node, keyed `"row,col"` (ABI 2).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty", with = "cairo_abi::node_keys")]
+    #[cfg_attr(feature = "schema", schemars(with = "BTreeMap<String, Vec<u32>>"))]
+    pub node_expected: NodeOutputs,
+    /// The generator `inputs` were expanded from, for the record.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs_gen: Option<InputSpec>,
+    #[serde(default, skip_serializing_if = "IoNodes::is_default")]
+    pub io: IoNodes,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sentinel: Option<InputSentinel>,
+    /// Compatibility profile (`capabilities::PROFILES`) the settings came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Project mnemonic aliases (`zk100.toml` `[aliases]`) the source was read with.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// Wide literals were built over several instructions
+    /// (`--synthesize-literals`), which changes the program.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub synthesize_literals: bool,
+    /// Extra operands were ignored (`--lenient`); without it the source
+    /// may not assemble.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub lenient: bool,
+    /// Labels were matched case-sensitively (`--case-sensitive-labels`),
+    /// which changes which jumps resolve.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub case_sensitive_labels: bool,
+    /// Constants from `--define`, which select `.if` branches and override
+    /// `DEFINE`s, so the same source assembles differently without them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defines: Constants,
+    /// Node length from `--pad-to`, which changes the program. A `.pad` line
+    /// is part of the source and needs no record.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pad_to: Option<usize>,
+    /// The source was read as TIS-100 (`--tis100`), where `UP` and `DOWN`
+    /// name the streams in the I/O nodes and `LAST` is `NIL`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tis100: bool,
+    /// Grid size from `--rows`/`--cols`. A `GRID` line is part of the
+    /// source and needs no record.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid: Option<GridSize>,
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+    /// Limits to assemble under; a property of the run, not of the result,
+    /// so not recorded.
+    #[serde(skip)]
+    pub limits: Limits,
+}
+
+impl AssembleOptions {
+    /// The dialect the source is read with: the built-in aliases plus `aliases`.
+    pub fn dialect(&self) -> Result<Dialect> {
+        Dialect::new(self.aliases.iter().map(|(alias, target)| (alias.as_str(), target.as_str())))
+    }
+
+    /// How the source is parsed under these options.
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            limits: self.limits,
+            synthesize_literals: self.synthesize_literals,
+            grid: self.grid,
+            lenient: self.lenient,
+            case_sensitive_labels: self.case_sensitive_labels,
+            defines: self.defines.clone(),
+            pad_to: self.pad_to,
+            tis100: self.tis100.then_some(self.io),
+            ..Default::default()
+        }
+    }
+}
+
+/// How an args file was produced, for reproducing it with `attest`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct Metadata {
+    /// Name and version of the tool that wrote the file.
+    pub tool: String,
+    pub version: String,
+    /// Commit the tool was built from, when it was built inside a git checkout.
+    pub git_hash: Option<String>,
+    /// File name the source was assembled from.
+    pub source_file: String,
+    /// When the file was written, in seconds since the Unix epoch.
+    pub created_unix: u64,
+    /// `ENCODING_VERSION` of the instruction words.
+    pub encoding_version: u32,
+    /// `COMMITMENT_VERSION` of `program_root`.
+    pub commitment_version: u32,
+    /// The args.json layout the args were written in.
+    pub abi_version: u32,
+    #[serde(default)]
+    pub hash_function: HashFunction,
+    #[serde(default)]
+    pub schedule: Schedule,
+    pub options: AssembleOptions,
+    /// SHA-256 of the source after `normalize_source`.
+    pub source_sha256: String,
+    /// `0x`-prefixed hex of the program Merkle root.
+    pub program_root: String,
+    /// Registry ID of the root; absent in metadata from older builds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+    pub program_id: Option<ProgramId>,
+    /// SHA-256 of the compact args JSON, as `assemble` writes it.
+    pub args_sha256: String,
+}
+
+/// Line endings and trailing whitespace do not change the program, so they do
+/// not change the source hash either.
+pub fn normalize_source(source: &str) -> String {
+    let mut lines: Vec<&str> = source.lines().map(str::trim_end).collect();
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.iter().map(|l| format!("{}\n", l)).collect()
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// The outputs the metadata pins, recomputed from source and options.
+struct Derived {
+    source_sha256: String,
+    program_root: String,
+    program_id: ProgramId,
+    args_sha256: String,
+}
+
+fn derive(source: &str, options: &AssembleOptions, abi: u32) -> Result<Derived> {
+    let grid = assembler::parse_with_options(source, &options.dialect()?, options.parse_options())?.grid;
+    let prog_words = assembler::encode_programs(&grid.programs)?;
+    let args = CairoArgs {
+        inputs: options.inputs.clone(),
+        expected: options.expected.clone(),
+        node_expected: options.node_expected.clone(),
+        prog_words,
+        io: options.io,
+        sentinel: options.sentinel,
+        grid: grid.size(),
+    }
+    .to_args(abi)?;
+    Ok(Derived {
+        source_sha256: sha256_hex(normalize_source(source).as_bytes()),
+        program_root: merkle::program_root_hex(&grid.programs)?,
+        program_id: ProgramId::of_programs(&grid.programs)?,
+        args_sha256: sha256_hex(serde_json::to_string(&args)?.as_bytes()),
+    })
+}
+
+impl Metadata {
+    /// Metadata for assembling `source` with `options` into args of `abi`.
+    pub fn generate(source: &str, source_file: &str, abi: u32, options: AssembleOptions) -> Result<Self> {
+        let derived = derive(source, &options, abi)?;
+        Ok(Metadata {
+            tool: built_info::PKG_NAME.to_string(),
+            version: built_info::PKG_VERSION.to_string(),
+            git_hash: built_info::GIT_COMMIT_HASH_SHORT.map(str::to_string),
+            source_file: source_file.to_string(),
+            created_unix: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            encoding_version: ENCODING_VERSION,
+            commitment_version: COMMITMENT_VERSION,
+            abi_version: abi,
+            hash_function: merkle::HASH_FUNCTION,
+            schedule: sim::SCHEDULE,
+            options,
+            source_sha256: derived.source_sha256,
+            program_root: derived.program_root,
+            program_id: Some(derived.program_id),
+            args_sha256: derived.args_sha256,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub recorded: String,
+    pub actual: String,
+}
+
+impl Check {
+    pub fn is_ok(&self) -> bool {
+        self.recorded == self.actual
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Attestation {
+    /// Options or versions this build cannot reproduce; when non-empty the
+    /// pipeline was not run and `checks` is empty.
+    pub unsupported: Vec<String>,
+    pub checks: Vec<Check>,
+    /// Set when the source no longer assembles.
+    pub error: Option<String>,
+}
+
+impl Attestation {
+    pub fn is_ok(&self) -> bool {
+        self.unsupported.is_empty() && self.error.is_none() && self.checks.iter().all(Check::is_ok)
+    }
+}
+
+impl fmt::Display for Attestation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for reason in &self.unsupported {
+            writeln!(f, "UNSUPPORTED  {}", reason)?;
+        }
+        if let Some(error) = &self.error {
+            writeln!(f, "FAIL         source does not assemble: {}", error)?;
+        }
+        for check in &self.checks {
+            if check.is_ok() {
+                writeln!(f, "ok           {}: {}", check.name, check.actual)?;
+            } else {
+                writeln!(f, "MISMATCH     {}: recorded {}, reproduced {}", check.name, check.recorded, check.actual)?;
+            }
+        }
+        write!(f, "{}", if self.is_ok() { "attestation passed" } else { "attestation FAILED" })
+    }
+}
+
+/// Re-run the pipeline on `source` with the options recorded in `metadata`.
+pub fn attest(source: &str, metadata: &Metadata) -> Attestation {
+    let mut attestation = Attestation::default();
+    for (name, recorded, oldest, newest) in [
+        ("encoding_version", metadata.encoding_version, ENCODING_VERSION, ENCODING_VERSION),
+        ("commitment_version", metadata.commitment_version, COMMITMENT_VERSION, COMMITMENT_VERSION),
+        ("abi_version", metadata.abi_version, cairo_abi::LEGACY_ABI, ABI_VERSION),
+    ] {
+        if !(oldest..=newest).contains(&recorded) {
+            let supported =
+                if oldest == newest { newest.to_string() } else { format!("{} to {}", oldest, newest) };
+            attestation
+                .unsupported
+                .push(format!("{} {} (this build implements {})", name, recorded, supported));
+        }
+    }
+    if let Some(name) = metadata.options.profile.as_ref().filter(|name| capabilities::profile(name).is_err()) {
+        attestation.unsupported.push(format!("profile '{}' is not known to this build", name));
+    }
+    for key in metadata.options.unknown.keys() {
+        attestation.unsupported.push(format!("assemble option '{}' is not known to this build", key));
+    }
+    if !attestation.unsupported.is_empty() {
+        return attestation;
+    }
+
+    match derive(source, &metadata.options, metadata.abi_version) {
+        Ok(derived) => {
+            attestation.checks = vec![
+                Check { name: "source_sha256", recorded: metadata.source_sha256.clone(), actual: derived.source_sha256 },
+                Check { name: "program_root", recorded: metadata.program_root.clone(), actual: derived.program_root },
+                Check { name: "args_sha256", recorded: metadata.args_sha256.clone(), actual: derived.args_sha256 },
+            ];
+            if let Some(recorded) = metadata.program_id {
+                let actual = derived.program_id.to_string();
+                attestation.checks.push(Check { name: "program_id", recorded: recorded.to_string(), actual });
+            }
+        }
+        Err(e) => attestation.error = Some(e.to_string()),
+    }
+    attestation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_source() {
+        assert_eq!(normalize_source("NODE (0,0)  \r\nNOP\r\n\r\n\n"), "NODE (0,0)\nNOP\n");
+        assert_eq!(normalize_source("NOP"), normalize_source("NOP\n"));
+    }
+
+    #[test]
+    fn test_unknown_option_is_kept() {
+        let options: AssembleOptions =
+            serde_json::from_str(r#"{"inputs":[1],"expected":[],"optimize":true}"#).unwrap();
+        assert_eq!(options.inputs, [1]);
+        assert_eq!(options.unknown.keys().collect::<Vec<_>>(), ["optimize"]);
+    }
+}
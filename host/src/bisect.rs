@@ -0,0 +1,218 @@
+//! `zk100 bisect-encoding`: which instruction words moved between the
+//! `prog_words` of an old args file and the current assembler's.
+//!
+//! Both sides are split into per-node programs by their length prefixes and
+//! aligned word by word with a longest common subsequence, so one inserted
+//! instruction shows up as one insertion rather than as a change at every
+//! later pc. Between two matched words, removed and inserted words are paired
+//! in order as changes; whatever is left over is a removal or an insertion.
+
+use crate::assembler::GRID_COLS;
+use crate::cairo_abi;
+use crate::instruction::Inst;
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    Changed,
+    Inserted,
+    Removed,
+}
+
+/// One side of a differing word: its pc, hex and disassembly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WordSide {
+    pub pc: usize,
+    pub word: String,
+    pub asm: String,
+}
+
+impl WordSide {
+    fn new(pc: usize, word: u32) -> Self {
+        WordSide { pc, word: format!("0x{:08x}", word), asm: disassemble(word) }
+    }
+}
+
+impl fmt::Display for WordSide {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pc {} {} {}", self.pc, self.word, self.asm)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WordDiff {
+    pub node: (usize, usize),
+    pub kind: DiffKind,
+    pub old: Option<WordSide>,
+    pub new: Option<WordSide>,
+}
+
+/// A node whose program length changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LengthChange {
+    pub node: (usize, usize),
+    pub old: usize,
+    pub new: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Identical,
+    /// Same words in the same places, some with different values.
+    Encoding,
+    /// Words were inserted or removed.
+    Structural,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EncodingDiff {
+    pub verdict: Verdict,
+    pub lengths: Vec<LengthChange>,
+    pub diffs: Vec<WordDiff>,
+}
+
+/// Disassembly of one word, or why it does not decode.
+pub fn disassemble(word: u32) -> String {
+    match Inst::decode(word) {
+        Ok(inst) => inst.to_string(),
+        Err(e) => format!("<{}>", e),
+    }
+}
+
+/// Align `old` and `new` length-prefixed `prog_words` node by node.
+pub fn diff_prog_words(old: &[u32], new: &[u32]) -> Result<EncodingDiff> {
+    let old_nodes = cairo_abi::node_programs(old)?;
+    let new_nodes = cairo_abi::node_programs(new)?;
+    let mut lengths = Vec::new();
+    let mut diffs = Vec::new();
+    for (i, (old, new)) in old_nodes.iter().zip(&new_nodes).enumerate() {
+        let node = (i / GRID_COLS, i % GRID_COLS);
+        if old.len() != new.len() {
+            lengths.push(LengthChange { node, old: old.len(), new: new.len() });
+        }
+        for (old_pc, new_pc) in align(old, new) {
+            let kind = match (old_pc, new_pc) {
+                (Some(_), Some(_)) => DiffKind::Changed,
+                (None, _) => DiffKind::Inserted,
+                (_, None) => DiffKind::Removed,
+            };
+            diffs.push(WordDiff {
+                node,
+                kind,
+                old: old_pc.map(|pc| WordSide::new(pc, old[pc])),
+                new: new_pc.map(|pc| WordSide::new(pc, new[pc])),
+            });
+        }
+    }
+    let verdict = if diffs.iter().any(|d| d.kind != DiffKind::Changed) {
+        Verdict::Structural
+    } else if diffs.is_empty() {
+        Verdict::Identical
+    } else {
+        Verdict::Encoding
+    };
+    Ok(EncodingDiff { verdict, lengths, diffs })
+}
+
+/// The unmatched words of an LCS alignment as `(old_pc, new_pc)` pairs:
+/// both set for a change, one side `None` for an insertion or removal.
+fn align(old: &[u32], new: &[u32]) -> Vec<(Option<usize>, Option<usize>)> {
+    // lcs[i][j]: LCS length of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let mut out = Vec::new();
+    let (mut removed, mut inserted) = (Vec::new(), Vec::new());
+    let mut flush = |removed: &mut Vec<usize>, inserted: &mut Vec<usize>| {
+        let paired = removed.len().max(inserted.len());
+        for k in 0..paired {
+            out.push((removed.get(k).copied(), inserted.get(k).copied()));
+        }
+        removed.clear();
+        inserted.clear();
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            flush(&mut removed, &mut inserted);
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            removed.push(i);
+            i += 1;
+        } else {
+            inserted.push(j);
+            j += 1;
+        }
+    }
+    flush(&mut removed, &mut inserted);
+    out
+}
+
+impl fmt::Display for EncodingDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for diff in &self.diffs {
+            let (r, c) = diff.node;
+            match (&diff.old, &diff.new) {
+                (Some(old), Some(new)) => writeln!(f, "changed   node ({},{}) {}  ->  {}", r, c, old, new)?,
+                (None, Some(new)) => writeln!(f, "inserted  node ({},{}) {}", r, c, new)?,
+                (Some(old), None) => writeln!(f, "removed   node ({},{}) {}", r, c, old)?,
+                (None, None) => {}
+            }
+        }
+        for change in &self.lengths {
+            let (r, c) = change.node;
+            writeln!(f, "length    node ({},{}) {} -> {} words", r, c, change.old, change.new)?;
+        }
+        let count = |n: usize, what: &str| format!("{} {}{}", n, what, if n == 1 { "" } else { "s" });
+        match self.verdict {
+            Verdict::Identical => write!(f, "identical: prog_words match"),
+            Verdict::Encoding => {
+                write!(f, "encoding: {} changed value, no lengths changed", count(self.diffs.len(), "word"))
+            }
+            Verdict::Structural => write!(
+                f,
+                "structural: {} reported, {} changed length",
+                count(self.diffs.len(), "difference"),
+                count(self.lengths.len(), "node")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_align_pairs_changes_and_keeps_insertions_local() {
+        // One insertion after pc 1: nothing after it is reported.
+        assert_eq!(align(&[1, 2, 3, 4], &[1, 2, 9, 3, 4]), [(None, Some(2))]);
+        // A value change in place is a pair.
+        assert_eq!(align(&[1, 2, 3], &[1, 7, 3]), [(Some(1), Some(1))]);
+        // Two removed against one inserted: one change, one removal.
+        assert_eq!(align(&[1, 5, 6, 4], &[1, 8, 4]), [(Some(1), Some(1)), (Some(2), None)]);
+        assert_eq!(align(&[], &[1]), [(None, Some(0))]);
+        assert!(align(&[1, 2], &[1, 2]).is_empty());
+    }
+
+    #[test]
+    fn test_verdicts() {
+        let same = [1, 10, 0, 0, 0];
+        assert_eq!(diff_prog_words(&same, &same).unwrap().verdict, Verdict::Identical);
+        let diff = diff_prog_words(&same, &[1, 11, 0, 0, 0]).unwrap();
+        assert_eq!(diff.verdict, Verdict::Encoding);
+        assert!(diff.lengths.is_empty());
+        let diff = diff_prog_words(&same, &[1, 10, 0, 1, 10, 0]).unwrap();
+        assert_eq!(diff.verdict, Verdict::Structural);
+        assert_eq!(diff.lengths, [LengthChange { node: (1, 0), old: 0, new: 1 }]);
+        assert!(diff_prog_words(&[1, 10], &same).is_err());
+    }
+}
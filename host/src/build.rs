@@ -0,0 +1,501 @@
+//! Incremental workspace builds behind `zk100 build`.
+//!
+//! Every puzzle manifest under the workspace (a `.toml` file with a
+//! `[puzzle]` section, see `report`) contributes to one dependency graph:
+//!
+//! ```text
+//! program.asm ─┐
+//! INCLUDEd .asm├─> program ──> manifest case 0, case 1, ..
+//! zk100.toml  ─┘
+//! ```
+//!
+//! File nodes are only inputs. A program node writes its grid JSON to
+//! `.zk100/build/<program>.json`; a case node runs its case in the simulator,
+//! fails the build if the outputs miss `expected`, and writes the case's args
+//! to `.zk100/build/<manifest>/case-<n>.args.json`. A program shared by
+//! several manifests is one node.
+//!
+//! Each node's key hashes its own inputs (a file's bytes, a case's inputs,
+//! expected outputs and settings) with the keys of its dependencies, so a
+//! change anywhere reaches exactly the nodes downstream of it. The keys of
+//! the last successful build live in `.zk100/build-state.json`; a node is
+//! rebuilt when its key differs from the recorded one or one of its outputs
+//! is gone.
+
+use crate::assembler::ParseOptions;
+use crate::cairo_abi::{CairoArgs, IoNodes};
+use crate::dialect::Dialect;
+use crate::expected::ExpectedSpec;
+use crate::grid::{self, ProgramGrid};
+use crate::limits::Limits;
+use crate::report::PuzzleManifest;
+use crate::scarb::strip_comment;
+use crate::sim::Simulator;
+use crate::stats::STATS_DIR;
+use crate::{assembler, config, exec, source};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Keys of the last build, under `.zk100/`.
+pub const STATE_FILE: &str = "build-state.json";
+/// Build outputs, under `.zk100/`.
+pub const OUT_DIR: &str = "build";
+/// Bumped whenever what a node produces changes, so old outputs rebuild.
+pub const STATE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A source, include or settings file: never built itself.
+    File,
+    Program,
+    Case,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub kind: NodeKind,
+    /// Workspace-relative name: the path for files and programs,
+    /// `<manifest> [case <n>]` for cases.
+    pub id: String,
+    /// Hash of the node's own inputs.
+    pub digest: String,
+    /// Indices of the nodes this one is built from, all lower than its own.
+    pub deps: Vec<usize>,
+    pub outputs: Vec<PathBuf>,
+}
+
+/// Nodes in dependency order: every node comes after its dependencies.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dirty {
+    /// No key recorded: never built, or failed last time.
+    New,
+    /// The node's inputs or one of its dependencies changed.
+    Changed,
+    /// An output file is gone.
+    MissingOutput,
+    /// `--force`.
+    Forced,
+}
+
+impl fmt::Display for Dirty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Dirty::New => "not built yet",
+            Dirty::Changed => "inputs changed",
+            Dirty::MissingOutput => "output missing",
+            Dirty::Forced => "forced",
+        })
+    }
+}
+
+/// What a build does with one buildable node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub node: usize,
+    pub key: String,
+    /// Why the node has to be rebuilt; `None` when it is fresh.
+    pub dirty: Option<Dirty>,
+}
+
+impl Graph {
+    /// Add a node, or return the index of the node of that kind and id
+    /// already there.
+    pub fn add(&mut self, kind: NodeKind, id: &str, digest: String, deps: Vec<usize>, outputs: Vec<PathBuf>) -> usize {
+        if let Some(idx) = self.find(kind, id) {
+            return idx;
+        }
+        let idx = self.nodes.len();
+        assert!(deps.iter().all(|&dep| dep < idx), "dependencies of {} must be added first", id);
+        self.nodes.push(Node { kind, id: id.to_string(), digest, deps, outputs });
+        idx
+    }
+
+    pub fn find(&self, kind: NodeKind, id: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.kind == kind && n.id == id)
+    }
+
+    /// Every node's key: its digest hashed with its dependencies' keys.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let mut hasher = Sha256::new();
+            hasher.update(format!("{}\n{:?}\n{}\n{}\n", STATE_VERSION, node.kind, node.id, node.digest));
+            for &dep in &node.deps {
+                hasher.update(&keys[dep]);
+            }
+            keys.push(hex::encode(hasher.finalize()));
+        }
+        keys
+    }
+
+    /// One step per buildable node, in build order. `exists` says whether an
+    /// output file is present.
+    pub fn plan(&self, state: &State, force: bool, exists: impl Fn(&Path) -> bool) -> Vec<Step> {
+        self.keys()
+            .into_iter()
+            .enumerate()
+            .filter(|&(idx, _)| self.nodes[idx].kind != NodeKind::File)
+            .map(|(idx, key)| {
+                let node = &self.nodes[idx];
+                let dirty = match state.nodes.get(&node.id) {
+                    _ if force => Some(Dirty::Forced),
+                    None => Some(Dirty::New),
+                    Some(recorded) if *recorded != key => Some(Dirty::Changed),
+                    Some(_) if !node.outputs.iter().all(|p| exists(p)) => Some(Dirty::MissingOutput),
+                    Some(_) => None,
+                };
+                Step { node: idx, key, dirty }
+            })
+            .collect()
+    }
+}
+
+/// `.zk100/build-state.json`: the key each node was last built with.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    pub version: u32,
+    pub nodes: BTreeMap<String, String>,
+}
+
+impl State {
+    /// The state of the workspace at `root`; none yet, or one written by
+    /// another `STATE_VERSION`, is empty.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(STATS_DIR).join(STATE_FILE);
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Ok(State::default());
+        };
+        let state: State = serde_json::from_str(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Ok(if state.version == STATE_VERSION { state } else { State::default() })
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let state = State { version: STATE_VERSION, nodes: self.nodes.clone() };
+        write(&root.join(STATS_DIR).join(STATE_FILE), serde_json::to_string_pretty(&state)? + "\n")
+    }
+}
+
+/// How building one step went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Fresh,
+    Built,
+    Failed(String),
+    /// A dependency failed.
+    Skipped,
+}
+
+/// What a buildable node does.
+#[derive(Debug, Clone)]
+enum Job {
+    Program { source: String },
+    Case { program: usize, inputs: Vec<u32>, expected: ExpectedSpec, io: IoNodes, max_cycles: u64 },
+}
+
+/// The graph of a workspace and what each buildable node does.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub root: PathBuf,
+    pub graph: Graph,
+    dialect: Dialect,
+    limits: Limits,
+    jobs: BTreeMap<usize, Job>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub built: usize,
+    pub fresh: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} built, {} fresh", self.built, self.fresh)?;
+        if self.failed > 0 || self.skipped > 0 {
+            write!(f, ", {} failed, {} skipped", self.failed, self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+/// `exec::write_atomic`, creating the parent directories first.
+fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| anyhow!("cannot create {}: {}", dir.display(), e))?;
+    }
+    exec::write_atomic(path, contents)
+}
+
+fn sha256_hex(bytes: impl AsRef<[u8]>) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// `path` with `.` and `name/..` pairs removed, without touching the disk.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(out.components().next_back(), Some(Component::Normal(_))) => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Puzzle manifests under `dir`, sorted, skipping hidden directories and
+/// `target`.
+fn find_manifests(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(|e| anyhow!("cannot read {}: {}", dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = entries.map(|e| e.map(|e| e.path())).collect::<std::io::Result<_>>()?;
+    paths.sort();
+    for path in paths {
+        let name = path.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                find_manifests(&path, found)?;
+            }
+        } else if name.ends_with(".toml") {
+            let text = fs::read_to_string(&path).unwrap_or_default();
+            if text.lines().any(|line| strip_comment(line).trim() == "[puzzle]") {
+                found.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Workspace {
+    /// Find the manifests under `root`, read their programs and includes,
+    /// and build the graph. Unreadable files are errors here; programs that
+    /// do not assemble and cases that fail only fail their own nodes.
+    pub fn discover(root: &Path) -> Result<Self> {
+        let settings = root.join(config::PROJECT_CONFIG);
+        let mut ws = Workspace {
+            root: root.to_path_buf(),
+            graph: Graph::default(),
+            dialect: Dialect::load(&settings)?,
+            limits: Limits::load(&settings)?,
+            jobs: BTreeMap::new(),
+        };
+        let mut manifests = Vec::new();
+        find_manifests(root, &mut manifests)?;
+        for manifest_path in manifests {
+            let manifest = PuzzleManifest::load(&manifest_path)?;
+            let base = manifest_path.parent().unwrap_or(root);
+            let program = ws.add_program(&normalize(&base.join(&manifest.program)), &settings)?;
+            let manifest_id = ws.id(&manifest_path);
+            for (n, case) in manifest.cases.iter().enumerate() {
+                let inputs = case.inputs.expand(base).map_err(|e| anyhow!("{} case {}: {}", manifest_id, n, e))?;
+                let max_cycles = manifest.max_cycles.unwrap_or(ws.limits.cycles.value);
+                let digest = sha256_hex(format!("{:?}\n{}\n{:?}\n{}", inputs, case.expected, manifest.io, max_cycles));
+                let output = ws.out_dir().join(&manifest_id).join(format!("case-{}.args.json", n));
+                let id = format!("{} [case {}]", manifest_id, n);
+                let idx = ws.graph.add(NodeKind::Case, &id, digest, vec![program], vec![output]);
+                let job = Job::Case { program, inputs, expected: case.expected.clone(), io: manifest.io, max_cycles };
+                ws.jobs.insert(idx, job);
+            }
+        }
+        Ok(ws)
+    }
+
+    fn out_dir(&self) -> PathBuf {
+        self.root.join(STATS_DIR).join(OUT_DIR)
+    }
+
+    /// `path` relative to the workspace, with `/` separators.
+    fn id(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/")
+    }
+
+    fn add_file(&mut self, path: &Path) -> Result<usize> {
+        let id = self.id(path);
+        if let Some(idx) = self.graph.find(NodeKind::File, &id) {
+            return Ok(idx);
+        }
+        let bytes = fs::read(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+        Ok(self.graph.add(NodeKind::File, &id, sha256_hex(bytes), Vec::new(), Vec::new()))
+    }
+
+    fn add_program(&mut self, path: &Path, settings: &Path) -> Result<usize> {
+        let id = self.id(path);
+        if let Some(idx) = self.graph.find(NodeKind::Program, &id) {
+            return Ok(idx);
+        }
+        let (source, includes) = source::read_with_includes(path)?;
+        let mut deps = vec![self.add_file(path)?];
+        for include in includes {
+            deps.push(self.add_file(&normalize(&include))?);
+        }
+        if settings.exists() {
+            deps.push(self.add_file(settings)?);
+        }
+        let output = self.out_dir().join(format!("{}.json", id));
+        let idx = self.graph.add(NodeKind::Program, &id, String::new(), deps, vec![output]);
+        self.jobs.insert(idx, Job::Program { source });
+        Ok(idx)
+    }
+
+    /// The steps of a build against the recorded state.
+    pub fn plan(&self, force: bool) -> Result<Vec<Step>> {
+        Ok(self.graph.plan(&State::load(&self.root)?, force, Path::exists))
+    }
+
+    /// Run the dirty steps of `plan` in order, calling `on_step` as each one
+    /// finishes, then record the keys of everything that is now up to date.
+    pub fn build(&self, plan: &[Step], on_step: &mut dyn FnMut(&Step, &Outcome)) -> Result<Summary> {
+        let mut summary = Summary::default();
+        let mut state = State::default();
+        let mut ok = vec![true; self.graph.nodes.len()];
+        let mut parsed = BTreeMap::new();
+        for step in plan {
+            let node = &self.graph.nodes[step.node];
+            let outcome = if node.deps.iter().any(|&dep| !ok[dep]) {
+                Outcome::Skipped
+            } else if step.dirty.is_none() {
+                Outcome::Fresh
+            } else {
+                match self.run(step.node, &mut parsed) {
+                    Ok(()) => Outcome::Built,
+                    Err(e) => Outcome::Failed(format!("{:#}", e)),
+                }
+            };
+            match outcome {
+                Outcome::Fresh => summary.fresh += 1,
+                Outcome::Built => summary.built += 1,
+                Outcome::Failed(_) => summary.failed += 1,
+                Outcome::Skipped => summary.skipped += 1,
+            }
+            if matches!(outcome, Outcome::Fresh | Outcome::Built) {
+                state.nodes.insert(node.id.clone(), step.key.clone());
+            } else {
+                ok[step.node] = false;
+            }
+            on_step(step, &outcome);
+        }
+        state.save(&self.root)?;
+        Ok(summary)
+    }
+
+    fn run(&self, idx: usize, parsed: &mut BTreeMap<usize, assembler::ParseResult>) -> Result<()> {
+        let node = &self.graph.nodes[idx];
+        match &self.jobs[&idx] {
+            Job::Program { source } => {
+                let result = self.parse(source)?;
+                write(&node.outputs[0], grid::to_json(&ProgramGrid::new(result.grid.programs.clone()))? + "\n")?;
+                parsed.insert(idx, result);
+            }
+            Job::Case { program, inputs, expected, io, max_cycles } => {
+                if !parsed.contains_key(program) {
+                    let Job::Program { source } = &self.jobs[program] else {
+                        unreachable!("a case depends on a program");
+                    };
+                    parsed.insert(*program, self.parse(source)?);
+                }
+                let result = &parsed[program];
+                let programs = &result.grid.programs;
+                let run = Simulator::with_io(programs, inputs, *io)?
+                    .with_limits(self.limits)?
+                    .with_asserts(result.asserts.clone())
+                    .run_to_result(*max_cycles)?;
+                if let Some(mismatch) = expected.check(&run.outputs).first() {
+                    return Err(anyhow!("{}", mismatch));
+                }
+                let args = CairoArgs {
+                    inputs: inputs.clone(),
+                    // A pattern is written with the values the simulator produced.
+                    expected: expected.exact().unwrap_or(run.outputs),
+                    prog_words: assembler::encode_programs_with(programs, &self.limits)?,
+                    io: *io,
+                    ..Default::default()
+                };
+                write(&node.outputs[0], serde_json::to_string(&args.to_args_with(io.single_case_abi(), &self.limits)?)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse(&self, source: &str) -> Result<assembler::ParseResult> {
+        assembler::parse_with_options(source, &self.dialect, ParseOptions { limits: self.limits, ..Default::default() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// lib.asm <- a.asm, b.asm; programs a and b; a case on each.
+    fn graph(lib: &str) -> Graph {
+        let mut g = Graph::default();
+        let lib = g.add(NodeKind::File, "lib.asm", lib.to_string(), vec![], vec![]);
+        let a_src = g.add(NodeKind::File, "a.asm", "a".to_string(), vec![], vec![]);
+        let b_src = g.add(NodeKind::File, "b.asm", "b".to_string(), vec![], vec![]);
+        let a = g.add(NodeKind::Program, "a.asm", String::new(), vec![a_src, lib], vec!["out/a".into()]);
+        let b = g.add(NodeKind::Program, "b.asm", String::new(), vec![b_src], vec!["out/b".into()]);
+        g.add(NodeKind::Case, "a.toml [case 0]", "1,2".to_string(), vec![a], vec!["out/a0".into()]);
+        g.add(NodeKind::Case, "b.toml [case 0]", "3".to_string(), vec![b], vec!["out/b0".into()]);
+        g
+    }
+
+    fn recorded(g: &Graph) -> State {
+        let steps = g.plan(&State::default(), false, |_| true);
+        State { version: STATE_VERSION, nodes: steps.iter().map(|s| (g.nodes[s.node].id.clone(), s.key.clone())).collect() }
+    }
+
+    fn dirty(g: &Graph, state: &State, exists: impl Fn(&Path) -> bool) -> Vec<(String, Dirty)> {
+        g.plan(state, false, exists).into_iter().filter_map(|s| Some((g.nodes[s.node].id.clone(), s.dirty?))).collect()
+    }
+
+    #[test]
+    fn test_plan() {
+        let g = graph("v1");
+        let steps = g.plan(&State::default(), false, |_| true);
+        assert_eq!(steps.len(), 4, "file nodes are not built");
+        assert!(steps.iter().all(|s| s.dirty == Some(Dirty::New)));
+
+        let state = recorded(&g);
+        assert_eq!(dirty(&g, &state, |_| true), []);
+        assert!(g.plan(&state, true, |_| true).iter().all(|s| s.dirty == Some(Dirty::Forced)));
+        assert_eq!(dirty(&g, &state, |p| p != Path::new("out/b0")), [("b.toml [case 0]".to_string(), Dirty::MissingOutput)]);
+
+        // Changing the shared file reaches a and its case, not b.
+        let changed = graph("v2");
+        assert_eq!(dirty(&changed, &state, |_| true), [
+            ("a.asm".to_string(), Dirty::Changed),
+            ("a.toml [case 0]".to_string(), Dirty::Changed),
+        ]);
+    }
+
+    #[test]
+    fn test_add_dedupes_and_keys_are_stable() {
+        let mut g = graph("v1");
+        let len = g.nodes.len();
+        assert_eq!(g.add(NodeKind::File, "lib.asm", "other".to_string(), vec![], vec![]), 0);
+        assert_eq!(g.nodes.len(), len);
+        assert_eq!(g.find(NodeKind::Program, "a.asm"), Some(3));
+        assert_eq!(g.keys(), graph("v1").keys());
+        // A file and a program may share a path; their keys differ.
+        assert_ne!(g.keys()[1], g.keys()[3]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("ws/programs/../lib/./x.asm")), Path::new("ws/lib/x.asm"));
+        assert_eq!(normalize(Path::new("../x.asm")), Path::new("../x.asm"));
+    }
+}
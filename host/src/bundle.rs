@@ -0,0 +1,256 @@
+//! `.zk100` bundles: one file holding a program, its args, its proof and
+//! enough metadata to check that they belong together.
+//!
+//! A bundle is a plain ustar archive (written and read with the `tar` crate,
+//! and readable with `tar -xf`) with these
+//! members, in order:
+//!
+//! - `program.asm` – the assembly source
+//! - `args.json` – the Cairo arguments the proof was generated from
+//! - `proof.json` – the proof as written by `cairo-prove`
+//! - `metadata.json` – the reproduction metadata (`attest::Metadata`)
+//! - `manifest.json` – SHA-256 of every other member plus the program root
+//!
+//! `Bundle::verify` rejects a bundle when a member's hash differs from the
+//! manifest, when a member is missing or unexpected, when the source no longer
+//! assembles to the manifest's program root, or when `args.json` is not what
+//! the source plus its inputs and expected outputs would generate. Timestamps
+//! in the archive headers are zeroed so packing is reproducible.
+//! `Bundle::extract` runs the same checks before writing any member.
+
+use crate::attest::{AssembleOptions, Metadata};
+use crate::{assembler, cairo_abi, exec, merkle};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+pub const SOURCE: &str = "program.asm";
+pub const ARGS: &str = "args.json";
+pub const PROOF: &str = "proof.json";
+pub const METADATA: &str = "metadata.json";
+pub const MANIFEST: &str = "manifest.json";
+
+/// Members covered by the manifest, in archive order.
+pub const MEMBERS: [&str; 4] = [SOURCE, ARGS, PROOF, METADATA];
+
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    /// `0x`-prefixed hex of the SHA-256 program Merkle root.
+    pub program_root: String,
+    /// Member name to hex SHA-256 of its bytes.
+    pub members: BTreeMap<String, String>,
+}
+
+/// The members of a bundle, read into memory.
+#[derive(Debug, Clone, Default)]
+pub struct Bundle {
+    pub members: BTreeMap<String, Vec<u8>>,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn program_root(source: &str) -> Result<String> {
+    let programs = assembler::parse_assembly(source)?;
+    merkle::program_root_hex(&programs)
+}
+
+/// Check that `args_json` is exactly what `source` generates, in the layout of
+/// `abi`, for the inputs and expected outputs it carries.
+fn check_args(source: &str, args_json: &str, abi: u32) -> Result<()> {
+    let args = cairo_abi::parse_args_abi(args_json, abi).map_err(|e| anyhow!("{}: {}", ARGS, e))?;
+    let programs = assembler::parse_assembly(source).map_err(|e| anyhow!("{}: {}", SOURCE, e))?;
+    let words = assembler::encode_programs(&programs)?;
+    if args.prog_words != words {
+        return Err(anyhow!("{} does not encode the program in {}", ARGS, SOURCE));
+    }
+    let regenerated = args.to_args(abi)?;
+    let bundled: serde_json::Value = serde_json::from_str(args_json)?;
+    if bundled != serde_json::Value::Array(regenerated) {
+        return Err(anyhow!("{} differs from the args regenerated from {}", ARGS, SOURCE));
+    }
+    Ok(())
+}
+
+/// Build a bundle from args in the layout of `abi`. Fails if `args_json` does
+/// not belong to `source`.
+pub fn pack(source: &str, source_file: &str, args_json: &str, abi: u32, proof: &[u8]) -> Result<Vec<u8>> {
+    check_args(source, args_json, abi)?;
+    let args = cairo_abi::parse_args_abi(args_json, abi)?;
+    let options = AssembleOptions {
+        inputs: args.inputs,
+        expected: args.expected,
+        node_expected: args.node_expected,
+        ..Default::default()
+    };
+    let metadata = Metadata::generate(source, source_file, abi, options)?;
+    let contents: [Vec<u8>; 4] = [
+        source.as_bytes().to_vec(),
+        args_json.as_bytes().to_vec(),
+        proof.to_vec(),
+        serde_json::to_vec_pretty(&metadata)?,
+    ];
+    let manifest = Manifest {
+        format: FORMAT_VERSION,
+        program_root: program_root(source)?,
+        members: MEMBERS.iter().zip(&contents).map(|(name, bytes)| (name.to_string(), sha256_hex(bytes))).collect(),
+    };
+
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+    let mut members: Vec<(&str, &[u8])> = MEMBERS.iter().copied().zip(contents.iter().map(Vec::as_slice)).collect();
+    members.push((MANIFEST, &manifest));
+    archive(&members)
+}
+
+impl Bundle {
+    pub fn read(bytes: &[u8]) -> Result<Self> {
+        let mut bundle = Bundle::default();
+        for (name, data) in tar_members(bytes)? {
+            if bundle.members.insert(name.clone(), data).is_some() {
+                return Err(anyhow!("bundle contains {} twice", name));
+            }
+        }
+        Ok(bundle)
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+        Bundle::read(&bytes).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn member(&self, name: &str) -> Result<&[u8]> {
+        self.members
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| anyhow!("bundle has no {}", name))
+    }
+
+    fn text(&self, name: &str) -> Result<&str> {
+        std::str::from_utf8(self.member(name)?).map_err(|_| anyhow!("{} is not UTF-8", name))
+    }
+
+    pub fn manifest(&self) -> Result<Manifest> {
+        serde_json::from_slice(self.member(MANIFEST)?).map_err(|e| anyhow!("{} is malformed: {}", MANIFEST, e))
+    }
+
+    /// Check hashes, the program root and the args; returns the manifest.
+    pub fn verify(&self) -> Result<Manifest> {
+        let manifest = self.manifest()?;
+        if manifest.format != FORMAT_VERSION {
+            return Err(anyhow!("unsupported bundle format {}", manifest.format));
+        }
+        for name in self.members.keys() {
+            if name != MANIFEST && !manifest.members.contains_key(name) {
+                return Err(anyhow!("{} is not listed in the manifest", name));
+            }
+        }
+        for name in MEMBERS {
+            let recorded = manifest
+                .members
+                .get(name)
+                .ok_or_else(|| anyhow!("manifest does not list {}", name))?;
+            let actual = sha256_hex(self.member(name)?);
+            if &actual != recorded {
+                return Err(anyhow!("{} was modified: sha256 {}, manifest records {}", name, actual, recorded));
+            }
+        }
+
+        let source = self.text(SOURCE)?;
+        let root = program_root(source).map_err(|e| anyhow!("{}: {}", SOURCE, e))?;
+        if root != manifest.program_root {
+            return Err(anyhow!(
+                "program root of {} is {}, manifest records {}",
+                SOURCE, root, manifest.program_root
+            ));
+        }
+        let metadata: Metadata = serde_json::from_slice(self.member(METADATA)?)
+            .map_err(|e| anyhow!("{} is malformed: {}", METADATA, e))?;
+        check_args(source, self.text(ARGS)?, metadata.abi_version)?;
+        if metadata.program_root != manifest.program_root {
+            return Err(anyhow!(
+                "{} records program root {}, manifest records {}",
+                METADATA, metadata.program_root, manifest.program_root
+            ));
+        }
+        Ok(manifest)
+    }
+
+    /// Write every member into `dir`, creating it if needed. The bundle is
+    /// verified first, so a tampered bundle writes nothing.
+    pub fn extract(&self, dir: &Path) -> Result<()> {
+        self.verify()?;
+        fs::create_dir_all(dir)?;
+        for (name, bytes) in &self.members {
+            exec::write_atomic(&dir.join(name), bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// A ustar archive of regular files with zeroed timestamps and owners.
+fn archive(members: &[(&str, &[u8])]) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, data) in members {
+        let mut header = tar::Header::new_ustar();
+        header.set_path(name)?;
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_cksum();
+        builder.append(&header, *data)?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+fn tar_members(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut members = Vec::new();
+    let mut archive = tar::Archive::new(bytes);
+    for entry in archive.entries().map_err(|e| anyhow!("corrupt tar archive: {}", e))? {
+        let mut entry = entry.map_err(|e| anyhow!("corrupt tar archive: {}", e))?;
+        let path = entry.path()?;
+        let name = path
+            .to_str()
+            .filter(|name| !name.contains(['/', '\\']) && !name.starts_with('.'))
+            .ok_or_else(|| anyhow!("refusing tar member '{}'", path.display()))?
+            .to_string();
+        if !entry.header().entry_type().is_file() {
+            return Err(anyhow!("tar member {} is not a regular file", name));
+        }
+        let mut data = Vec::new();
+        entry
+            .read_to_end(&mut data)
+            .map_err(|e| anyhow!("tar member {} is truncated: {}", name, e))?;
+        members.push((name, data));
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tar_roundtrip() {
+        let mut out = archive(&[("a.txt", b"hello"), ("empty", b"")]).unwrap();
+        assert_eq!(out.len() % 512, 0);
+        let members = tar_members(&out).unwrap();
+        assert_eq!(members, [("a.txt".to_string(), b"hello".to_vec()), ("empty".to_string(), vec![])]);
+
+        out[0] = b'b';
+        assert!(tar_members(&out).unwrap_err().to_string().contains("corrupt tar archive"));
+    }
+
+    #[test]
+    fn test_nested_members_are_refused() {
+        let out = archive(&[("dir/a.txt", b"hello")]).unwrap();
+        assert_eq!(tar_members(&out).unwrap_err().to_string(), "refusing tar member 'dir/a.txt'");
+    }
+}
@@ -1,5 +1,286 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use crate::assembler::{GRID_COLS, GRID_ROWS, GridSize};
+use crate::limits::{Limit, Limits};
+use crate::trace;
+use serde::Serialize;
 use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub use crate::abi::*;
+
+/// The arrays carried by a single-case args.json file. `expected` is used by
+/// `LEGACY_ABI` (and `BATCH_ABI`, as a batch of one) and `node_expected` only
+/// by `NODE_ABI`. `io` is only carried by `IO_ABI`, `SENTINEL_ABI` and
+/// `GRID_ABI`, `sentinel` only by the last two, and `grid` only by `GRID_ABI`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CairoArgs {
+    pub inputs: Vec<u32>,
+    pub expected: Vec<u32>,
+    pub node_expected: NodeOutputs,
+    pub prog_words: Vec<u32>,
+    pub io: IoNodes,
+    pub sentinel: Option<InputSentinel>,
+    pub grid: GridSize,
+}
+
+impl CairoArgs {
+    /// Serialize in the layout of `abi`.
+    pub fn to_args(&self, abi: u32) -> Result<Vec<Value>> {
+        self.to_args_with(abi, &Limits::default())
+    }
+
+    /// `to_args`, failing if the inputs, the expected outputs or the args
+    /// array exceed `limits`.
+    pub fn to_args_with(&self, abi: u32, limits: &Limits) -> Result<Vec<Value>> {
+        let inputs = self.inputs.len() as u64;
+        limits.check(Limit::Inputs, inputs, || format!("the input stream has {} values", inputs))?;
+        let outputs = (self.expected.len() + self.node_expected.values().map(Vec::len).sum::<usize>()) as u64;
+        limits.check(Limit::Outputs, outputs, || format!("the expected stream has {} values", outputs))?;
+        let args = self.layout(abi)?;
+        let elements = args.len() as u64;
+        limits.check(Limit::ArgsElements, elements, || format!("the args array has {} elements", elements))?;
+        Ok(args)
+    }
+
+    fn layout(&self, abi: u32) -> Result<Vec<Value>> {
+        if abi != GRID_ABI && !self.grid.is_default() {
+            return Err(anyhow!("a {} grid needs ABI {}", self.grid, GRID_ABI));
+        }
+        if abi != IO_ABI && abi != SENTINEL_ABI && abi != GRID_ABI && !self.io.is_default() {
+            return Err(anyhow!("IN and OUT nodes other than (0,0) and (1,1) need ABI {}", IO_ABI));
+        }
+        if abi != SENTINEL_ABI && abi != GRID_ABI && self.sentinel.is_some() {
+            return Err(anyhow!("an input sentinel needs ABI {}", SENTINEL_ABI));
+        }
+        match abi {
+            LEGACY_ABI if !self.node_expected.is_empty() => Err(anyhow!(
+                "node-scoped expected outputs need ABI {}; ABI {} carries one flat stream",
+                NODE_ABI,
+                LEGACY_ABI
+            )),
+            LEGACY_ABI => generate_args(&self.inputs, &self.expected, &self.prog_words),
+            NODE_ABI if !self.expected.is_empty() => Err(anyhow!(
+                "ABI {} attributes expected outputs to nodes; give them as row,col=values",
+                NODE_ABI
+            )),
+            NODE_ABI => generate_node_args(&self.inputs, &self.node_expected, &self.prog_words),
+            BATCH_ABI => Ok(Batch::merge(std::slice::from_ref(self))?.to_args()),
+            DEDUP_ABI if !self.node_expected.is_empty() => Err(anyhow!(
+                "node-scoped expected outputs need ABI {}; ABI {} carries one flat stream",
+                NODE_ABI,
+                DEDUP_ABI
+            )),
+            DEDUP_ABI => generate_dedup_args(&self.inputs, &self.expected, &self.prog_words),
+            IO_ABI | SENTINEL_ABI | GRID_ABI if !self.node_expected.is_empty() => Err(anyhow!(
+                "node-scoped expected outputs need ABI {}; ABI {} carries one flat stream",
+                NODE_ABI,
+                abi
+            )),
+            IO_ABI | SENTINEL_ABI | GRID_ABI => {
+                self.io.validate_in(self.grid)?;
+                let mut args = Vec::new();
+                if abi == GRID_ABI {
+                    node_programs_in(&self.prog_words, self.grid.nodes())?;
+                    args.extend([self.grid.rows, self.grid.cols].map(|v| json_value_from_u32(v as u32)));
+                }
+                if abi == SENTINEL_ABI || abi == GRID_ABI {
+                    let (mode, value) = self.sentinel.map_or((0, 0), |s| (s.behavior.mode(), s.value));
+                    args.extend([mode, value].map(json_value_from_u32));
+                }
+                let (in_node, out_node) = (self.io.in_node, self.io.out_node);
+                args.extend([in_node.0, in_node.1, out_node.0, out_node.1].map(|v| json_value_from_u32(v as u32)));
+                args.extend(generate_args(&self.inputs, &self.expected, &self.prog_words)?);
+                Ok(args)
+            }
+            other => Err(unknown_abi(other)),
+        }
+    }
+}
+
+fn unknown_abi(abi: u32) -> anyhow::Error {
+    anyhow!("unknown args ABI {} (this build implements {} to {})", abi, LEGACY_ABI, GRID_ABI)
+}
+
+/// Split length-prefixed `prog_words` into one program per node of the
+/// default grid, in row-major order.
+pub fn node_programs(prog_words: &[u32]) -> Result<Vec<Vec<u32>>> {
+    node_programs_in(prog_words, GRID_ROWS * GRID_COLS)
+}
+
+/// `node_programs` for a grid of `nodes` nodes.
+pub fn node_programs_in(prog_words: &[u32], nodes: usize) -> Result<Vec<Vec<u32>>> {
+    let mut words = Words::new(prog_words.iter().copied().map(Ok), &[Section::ProgWords]);
+    let programs = (0..nodes)
+        .map(|node| {
+            words.array(&format!("node {} program", node), Section::ProgWords).map_err(|e| anyhow!("prog_words: {}", e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if words.position() != prog_words.len() {
+        return Err(anyhow!("prog_words: {} trailing values after the last node", prog_words.len() - words.position()));
+    }
+    Ok(programs)
+}
+
+/// The node programs of `prog_words` with duplicates folded, as `DEDUP_ABI`
+/// carries them.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DedupPrograms {
+    /// Instruction words of each distinct program, in order of first use.
+    pub programs: Vec<Vec<u32>>,
+    /// Per node, row-major: the index of its program in `programs`.
+    pub index: Vec<u32>,
+}
+
+impl DedupPrograms {
+    /// Fold length-prefixed `prog_words` (one program per grid node).
+    pub fn from_prog_words(prog_words: &[u32]) -> Result<Self> {
+        let mut dedup = DedupPrograms::default();
+        for program in node_programs(prog_words)? {
+            let slot = match dedup.programs.iter().position(|p| *p == program) {
+                Some(slot) => slot,
+                None => {
+                    dedup.programs.push(program);
+                    dedup.programs.len() - 1
+                }
+            };
+            dedup.index.push(slot as u32);
+        }
+        Ok(dedup)
+    }
+
+    /// Expand back into length-prefixed `prog_words`.
+    pub fn to_prog_words(&self) -> Result<Vec<u32>> {
+        if self.index.len() != GRID_ROWS * GRID_COLS {
+            return Err(anyhow!("node index has {} entries, expected {}", self.index.len(), GRID_ROWS * GRID_COLS));
+        }
+        let mut words = Vec::new();
+        for (node, &slot) in self.index.iter().enumerate() {
+            let program = self.programs.get(slot as usize).ok_or_else(|| {
+                anyhow!("node {} uses program {}, but only {} are defined", node, slot, self.programs.len())
+            })?;
+            words.push(program.len() as u32);
+            words.extend(program);
+        }
+        Ok(words)
+    }
+}
+
+/// Inputs and expected outputs of one case in a `Batch`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BatchCase {
+    pub inputs: Vec<u32>,
+    pub expected: Vec<u32>,
+}
+
+/// A `BATCH_ABI` args file: cases in order, sharing one program.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Batch {
+    pub cases: Vec<BatchCase>,
+    pub prog_words: Vec<u32>,
+}
+
+/// `Batch::merge` was given a case whose `prog_words` differ from the first
+/// case's. `diff` lists the differing words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgWordsMismatch {
+    pub case: usize,
+    pub diff: Vec<String>,
+}
+
+impl std::fmt::Display for ProgWordsMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "case {} has different prog_words from case 0:", self.case)?;
+        for line in &self.diff {
+            write!(f, "\n  {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProgWordsMismatch {}
+
+/// Word-by-word differences between two `prog_words` arrays.
+pub fn word_diff(a: &[u32], b: &[u32]) -> Vec<String> {
+    let mut diff = Vec::new();
+    if a.len() != b.len() {
+        diff.push(format!("length {} != {}", a.len(), b.len()));
+    }
+    for i in 0..a.len().max(b.len()) {
+        match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) if x == y => {}
+            (x, y) => {
+                let show = |w: Option<&u32>| w.map_or_else(|| "-".to_string(), |w| format!("0x{:08x}", w));
+                diff.push(format!("[{}] {} != {}", i, show(x), show(y)));
+            }
+        }
+    }
+    diff
+}
+
+impl Batch {
+    /// Batch single-case `LEGACY_ABI` args, keeping their order. Every case
+    /// must carry the same `prog_words`; otherwise the error is a
+    /// `ProgWordsMismatch` naming the first case that differs.
+    pub fn merge(singles: &[CairoArgs]) -> Result<Batch> {
+        let first = singles.first().ok_or_else(|| anyhow!("nothing to batch"))?;
+        let mut cases = Vec::with_capacity(singles.len());
+        for (i, single) in singles.iter().enumerate() {
+            if !single.node_expected.is_empty() {
+                return Err(anyhow!("case {} has node-scoped expected outputs; batches hold ABI {} cases", i, LEGACY_ABI));
+            }
+            if !single.io.is_default() {
+                return Err(anyhow!("case {} designates its own IN and OUT nodes; batches hold ABI {} cases", i, LEGACY_ABI));
+            }
+            if single.sentinel.is_some() {
+                return Err(anyhow!("case {} has an input sentinel; batches hold ABI {} cases", i, LEGACY_ABI));
+            }
+            if single.prog_words != first.prog_words {
+                return Err(ProgWordsMismatch { case: i, diff: word_diff(&first.prog_words, &single.prog_words) }.into());
+            }
+            cases.push(BatchCase { inputs: single.inputs.clone(), expected: single.expected.clone() });
+        }
+        Ok(Batch { cases, prog_words: first.prog_words.clone() })
+    }
+
+    /// One `LEGACY_ABI` args per case, in order.
+    pub fn split(&self) -> Vec<CairoArgs> {
+        self.cases
+            .iter()
+            .map(|case| CairoArgs {
+                inputs: case.inputs.clone(),
+                expected: case.expected.clone(),
+                node_expected: NodeOutputs::new(),
+                prog_words: self.prog_words.clone(),
+                io: IoNodes::default(),
+                sentinel: None,
+                grid: GridSize::default(),
+            })
+            .collect()
+    }
+
+    pub fn to_args(&self) -> Vec<Value> {
+        let span = trace::span!("generate_args", values);
+        let mut args = vec![json_value_from_u32(self.cases.len() as u32)];
+        for case in &self.cases {
+            for array in [&case.inputs, &case.expected] {
+                args.push(json_value_from_u32(array.len() as u32));
+                args.extend(array.iter().map(|&v| json_value_from_u32(v)));
+            }
+        }
+        args.push(json_value_from_u32(self.prog_words.len() as u32));
+        args.extend(self.prog_words.iter().map(|&v| json_value_from_u32(v)));
+        span.record("values", args.len() as u64);
+        args
+    }
+}
+
+/// Parse a `BATCH_ABI` args.json document.
+pub fn parse_batch_args(json: &str) -> Result<Batch> {
+    let mut words = Words::new(parse_words(json)?.into_iter().map(Ok), &Section::ALL);
+    let batch = read_batch(&mut words)?;
+    words.finish(BATCH_ABI)?;
+    Ok(batch)
+}
 
 /// Generate args.json in the format expected by cairo-prove
 /// Format: [inputs_len, ...inputs, expected_len, ...expected, prog_words_len, ...prog_words]
@@ -8,6 +289,7 @@ pub fn generate_args(
     expected: &[u32],
     prog_words: &[u32],
 ) -> Result<Vec<Value>> {
+    let span = trace::span!("generate_args", values);
     let mut args = Vec::new();
     
     // Add inputs array
@@ -28,9 +310,296 @@ pub fn generate_args(
         args.push(json_value_from_u32(word));
     }
     
+    span.record("values", args.len() as u64);
     Ok(args)
 }
 
+/// Generate args.json for `NODE_ABI`.
+pub fn generate_node_args(
+    inputs: &[u32],
+    node_expected: &NodeOutputs,
+    prog_words: &[u32],
+) -> Result<Vec<Value>> {
+    let span = trace::span!("generate_args", values);
+    let mut args = Vec::new();
+
+    args.push(json_value_from_u32(inputs.len() as u32));
+    args.extend(inputs.iter().map(|&v| json_value_from_u32(v)));
+
+    args.push(json_value_from_u32(node_expected.len() as u32));
+    for (&(r, c), values) in node_expected {
+        if r >= GRID_ROWS || c >= GRID_COLS {
+            return Err(anyhow!("expected outputs for node ({},{}) outside the {}x{} grid", r, c, GRID_ROWS, GRID_COLS));
+        }
+        args.push(json_value_from_u32(r as u32));
+        args.push(json_value_from_u32(c as u32));
+        args.push(json_value_from_u32(values.len() as u32));
+        args.extend(values.iter().map(|&v| json_value_from_u32(v)));
+    }
+
+    args.push(json_value_from_u32(prog_words.len() as u32));
+    args.extend(prog_words.iter().map(|&v| json_value_from_u32(v)));
+
+    span.record("values", args.len() as u64);
+    Ok(args)
+}
+
+/// Generate args.json for `DEDUP_ABI`.
+pub fn generate_dedup_args(inputs: &[u32], expected: &[u32], prog_words: &[u32]) -> Result<Vec<Value>> {
+    let dedup = DedupPrograms::from_prog_words(prog_words)?;
+    let span = trace::span!("generate_args", values);
+    let mut args = Vec::new();
+    for array in [inputs, expected] {
+        args.push(json_value_from_u32(array.len() as u32));
+        args.extend(array.iter().map(|&v| json_value_from_u32(v)));
+    }
+    args.push(json_value_from_u32(dedup.programs.len() as u32));
+    for program in &dedup.programs {
+        args.push(json_value_from_u32(program.len() as u32));
+        args.extend(program.iter().map(|&v| json_value_from_u32(v)));
+    }
+    args.push(json_value_from_u32(dedup.index.len() as u32));
+    args.extend(dedup.index.iter().map(|&v| json_value_from_u32(v)));
+    span.record("values", args.len() as u64);
+    Ok(args)
+}
+
+/// Parse a `LEGACY_ABI` args.json document produced by `generate_args`.
+///
+/// Values may be hex (`"0x2a"`) or decimal strings, or JSON integers; each must
+/// fit in a u32. Every length prefix must match the data that follows and no
+/// trailing values are allowed.
+pub fn parse_args(json: &str) -> Result<CairoArgs> {
+    parse_args_abi(json, LEGACY_ABI)
+}
+
+/// Parse an args.json document in the layout of `abi`. For `NODE_ABI`, nodes
+/// must lie in the grid and appear once each, in row-major order. A
+/// `BATCH_ABI` document must hold exactly one case; see `parse_batch_args`.
+pub fn parse_args_abi(json: &str, abi: u32) -> Result<CairoArgs> {
+    check_abi(abi)?;
+    read_single_case(Words::new(parse_words(json)?.into_iter().map(Ok), &Section::ALL), abi)
+}
+
+pub(crate) fn check_abi(abi: u32) -> Result<()> {
+    match abi {
+        LEGACY_ABI | NODE_ABI | BATCH_ABI | DEDUP_ABI | IO_ABI | SENTINEL_ABI | GRID_ABI => Ok(()),
+        other => Err(unknown_abi(other)),
+    }
+}
+
+/// `parse_args_abi` over any source of words, eager or streamed.
+pub(crate) fn read_single_case<I: Iterator<Item = Result<u32>>>(mut words: Words<I>, abi: u32) -> Result<CairoArgs> {
+    if abi == BATCH_ABI {
+        let mut singles = read_batch(&mut words)?.split();
+        words.finish(abi)?;
+        if singles.len() != 1 {
+            return Err(anyhow!("batch holds {} cases, expected exactly one", singles.len()));
+        }
+        return Ok(singles.remove(0));
+    }
+    let args = read_case(&mut words, abi)?;
+    words.finish(abi)?;
+    Ok(args)
+}
+
+/// Sections of an args file, as `Words` keeps or skips them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    Inputs,
+    Expected,
+    /// `prog_words`, or under `DEDUP_ABI` the program table and node index.
+    ProgWords,
+}
+
+impl Section {
+    pub const ALL: [Section; 3] = [Section::Inputs, Section::Expected, Section::ProgWords];
+}
+
+/// Where a section lies in the args array, length prefixes included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SectionSpan {
+    /// Values in the section's arrays, prefixes excluded.
+    pub values: usize,
+    /// Index of the first length prefix.
+    pub start: usize,
+    /// One past the last value.
+    pub end: usize,
+}
+
+/// The words of an args file, taken in layout order. Arrays of a section
+/// that is not kept are counted and dropped instead of collected, so a
+/// streamed file never holds more than the kept sections in memory.
+pub(crate) struct Words<I> {
+    iter: I,
+    cursor: usize,
+    keep: Vec<Section>,
+    pub(crate) spans: BTreeMap<Section, SectionSpan>,
+}
+
+impl<I: Iterator<Item = Result<u32>>> Words<I> {
+    pub(crate) fn new(iter: I, keep: &[Section]) -> Self {
+        Words { iter, cursor: 0, keep: keep.to_vec(), spans: BTreeMap::new() }
+    }
+
+    fn keeps(&self, section: Section) -> bool {
+        self.keep.contains(&section)
+    }
+
+    /// Values read so far.
+    pub(crate) fn position(&self) -> usize {
+        self.cursor
+    }
+
+    fn word(&mut self, name: &str) -> Result<u32> {
+        let word = self.iter.next().transpose()?.ok_or_else(|| anyhow!("missing {} at args[{}]", name, self.cursor))?;
+        self.cursor += 1;
+        Ok(word)
+    }
+
+    /// A length-prefixed array; empty when `section` is not kept.
+    fn array(&mut self, name: &str, section: Section) -> Result<Vec<u32>> {
+        let len = self.word(&format!("{} length", name))? as usize;
+        let start = self.cursor;
+        let keep = self.keeps(section);
+        // The length is untrusted, so the body grows as values arrive.
+        let mut body = Vec::new();
+        for taken in 0..len {
+            let word = self.iter.next().transpose()?.ok_or_else(|| {
+                anyhow!("{} declares {} values at args[{}] but only {} remain", name, len, start - 1, taken)
+            })?;
+            if keep {
+                body.push(word);
+            }
+            self.cursor += 1;
+        }
+        let span = self.spans.entry(section).or_insert(SectionSpan { start: start - 1, ..Default::default() });
+        span.values += len;
+        span.end = self.cursor;
+        Ok(body)
+    }
+
+    /// Reject values after the last section of `abi`.
+    pub(crate) fn finish(&mut self, abi: u32) -> Result<()> {
+        let mut trailing = 0;
+        while self.iter.next().transpose()?.is_some() {
+            trailing += 1;
+        }
+        if trailing > 0 {
+            let last = if abi == DEDUP_ABI { "node index" } else { "prog_words" };
+            return Err(anyhow!("{} trailing values after {}", trailing, last));
+        }
+        Ok(())
+    }
+}
+
+/// One case in the layout of `abi`, anything but `BATCH_ABI`.
+pub(crate) fn read_case<I: Iterator<Item = Result<u32>>>(words: &mut Words<I>, abi: u32) -> Result<CairoArgs> {
+    let mut args = CairoArgs::default();
+    if abi == GRID_ABI {
+        let at = words.cursor;
+        let rows = words.word("grid rows")? as usize;
+        let cols = words.word("grid columns")? as usize;
+        args.grid = GridSize::new(rows, cols).map_err(|e| anyhow!("grid size at args[{}]: {}", at, e))?;
+    }
+    if abi == SENTINEL_ABI || abi == GRID_ABI {
+        let at = words.cursor;
+        let mode = words.word("sentinel mode")?;
+        let value = words.word("sentinel value")?;
+        args.sentinel = match (mode, SentinelBehavior::ALL.into_iter().find(|b| b.mode() == mode)) {
+            (0, _) if value == 0 => None,
+            (0, _) => return Err(anyhow!("sentinel value {} at args[{}] without a sentinel mode", value, at + 1)),
+            (_, Some(behavior)) => Some(InputSentinel { value, behavior }),
+            (_, None) => return Err(anyhow!("unknown sentinel mode {} at args[{}]", mode, at)),
+        };
+    }
+    if abi == IO_ABI || abi == SENTINEL_ABI || abi == GRID_ABI {
+        let mut node = |name: &str| -> Result<(usize, usize)> {
+            let r = words.word(&format!("{} node row", name))? as usize;
+            Ok((r, words.word(&format!("{} node column", name))? as usize))
+        };
+        args.io = IoNodes { in_node: node("IN")?, out_node: node("OUT")? };
+        args.io.validate_in(args.grid)?;
+    }
+    args.inputs = words.array("inputs", Section::Inputs)?;
+    if abi != NODE_ABI {
+        args.expected = words.array("expected", Section::Expected)?;
+    } else {
+        let nodes = words.word("node count")?;
+        for i in 0..nodes {
+            let at = words.cursor;
+            let r = words.word("node row")? as usize;
+            let c = words.word("node column")? as usize;
+            if r >= GRID_ROWS || c >= GRID_COLS {
+                return Err(anyhow!("node {} at args[{}] is ({},{}), outside the {}x{} grid", i, at, r, c, GRID_ROWS, GRID_COLS));
+            }
+            if args.node_expected.keys().next_back().is_some_and(|&last| last >= (r, c)) {
+                return Err(anyhow!("node ({},{}) at args[{}] is repeated or out of row-major order", r, c, at));
+            }
+            let values = words.array(&format!("expected for node ({},{})", r, c), Section::Expected)?;
+            args.node_expected.insert((r, c), values);
+        }
+    }
+    if abi == DEDUP_ABI {
+        let mut dedup = DedupPrograms::default();
+        let unique = words.word("unique program count")?;
+        for i in 0..unique {
+            dedup.programs.push(words.array(&format!("unique program {}", i), Section::ProgWords)?);
+        }
+        dedup.index = words.array("node index", Section::ProgWords)?;
+        if words.keeps(Section::ProgWords) {
+            args.prog_words = dedup.to_prog_words()?;
+        }
+    } else {
+        args.prog_words = words.array("prog_words", Section::ProgWords)?;
+        if abi == GRID_ABI && words.keeps(Section::ProgWords) {
+            node_programs_in(&args.prog_words, args.grid.nodes())?;
+        }
+    }
+    Ok(args)
+}
+
+pub(crate) fn read_batch<I: Iterator<Item = Result<u32>>>(words: &mut Words<I>) -> Result<Batch> {
+    let count = words.word("case count")?;
+    let mut batch = Batch::default();
+    for i in 0..count {
+        let inputs = words.array(&format!("case {} inputs", i), Section::Inputs)?;
+        let expected = words.array(&format!("case {} expected", i), Section::Expected)?;
+        batch.cases.push(BatchCase { inputs, expected });
+    }
+    batch.prog_words = words.array("prog_words", Section::ProgWords)?;
+    Ok(batch)
+}
+
+/// A JSON array of words, each a hex string, a decimal string or a number.
+pub fn parse_words(json: &str) -> Result<Vec<u32>> {
+    let values: Vec<Value> = serde_json::from_str(json)
+        .map_err(|e| anyhow!("args must be a JSON array: {}", e))?;
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| u32_from_json_value(v).map_err(|e| anyhow!("args[{}]: {}", i, e)))
+        .collect()
+}
+
+pub(crate) fn u32_from_json_value(value: &Value) -> Result<u32> {
+    match value {
+        Value::String(s) => {
+            let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => s.parse::<u32>(),
+            };
+            parsed.map_err(|_| anyhow!("'{}' is not a u32 value", s))
+        }
+        Value::Number(n) => n
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| anyhow!("{} is not a u32 value", n)),
+        other => Err(anyhow!("expected a string or number, found {}", other)),
+    }
+}
+
 /// Convert u32 to JSON value (as hex string for Cairo compatibility)
 fn json_value_from_u32(val: u32) -> Value {
     Value::String(format!("0x{:x}", val))
@@ -78,4 +647,200 @@ mod tests {
         assert_eq!(args[10], Value::String("0x12c".to_string()));
         assert_eq!(args[11], Value::String("0x190".to_string()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_args_roundtrip() {
+        let args = generate_args(&[1, 2, 3], &[10, 20], &[100, 200, 300, 400]).unwrap();
+        let json = serde_json::to_string(&args).unwrap();
+        let parsed = parse_args(&json).unwrap();
+        assert_eq!(parsed.inputs, vec![1, 2, 3]);
+        assert_eq!(parsed.expected, vec![10, 20]);
+        assert_eq!(parsed.prog_words, vec![100, 200, 300, 400]);
+    }
+
+    #[test]
+    fn test_parse_args_accepts_decimal_and_numbers() {
+        let parsed = parse_args(r#"["1", 7, "0x0", 0]"#).unwrap();
+        assert_eq!(parsed.inputs, vec![7]);
+        assert!(parsed.expected.is_empty());
+        assert!(parsed.prog_words.is_empty());
+    }
+
+    #[test]
+    fn test_parse_args_rejects_malformed() {
+        assert!(parse_args("{}").is_err());
+        assert!(parse_args(r#"["0x0", "0x0"]"#).is_err());
+        assert!(parse_args(r#"["0x5", "0x1", "0x0", "0x0"]"#).is_err());
+        assert!(parse_args(r#"["0xffffffff", "0x0", "0x0"]"#).is_err());
+        assert!(parse_args(r#"["0x0", "0x0", "0x0", "0x1"]"#).is_err());
+        assert!(parse_args(r#"["0x100000000", "0x0", "0x0"]"#).is_err());
+        assert!(parse_args(r#"[-1, "0x0", "0x0"]"#).is_err());
+        assert!(parse_args(r#"[null, "0x0", "0x0"]"#).is_err());
+    }
+
+    fn hex(values: &[u32]) -> Vec<Value> {
+        values.iter().map(|&v| json_value_from_u32(v)).collect()
+    }
+
+    #[test]
+    fn test_node_abi_layout() {
+        let node_expected = NodeOutputs::from([((1, 1), vec![5, 10]), ((0, 1), vec![0])]);
+        let args = generate_node_args(&[7], &node_expected, &[100]).unwrap();
+        // inputs | 2 nodes, (0,1) before (1,1) | prog_words
+        assert_eq!(args, hex(&[1, 7, 2, 0, 1, 1, 0, 1, 1, 2, 5, 10, 1, 100]));
+
+        let parsed = parse_args_abi(&serde_json::to_string(&args).unwrap(), NODE_ABI).unwrap();
+        assert_eq!(parsed.node_expected, node_expected);
+        assert!(parsed.expected.is_empty());
+        assert_eq!(parsed.to_args(NODE_ABI).unwrap(), args);
+    }
+
+    #[test]
+    fn test_node_abi_rejects_bad_nodes() {
+        let err = generate_node_args(&[], &NodeOutputs::from([((2, 0), vec![])]), &[]).unwrap_err();
+        assert!(err.to_string().contains("outside the 2x2 grid"), "{}", err);
+        let err = parse_args_abi(r#"[0, 2, 1, 1, 0, 0, 1, 0, 0]"#, NODE_ABI).unwrap_err();
+        assert!(err.to_string().contains("out of row-major order"), "{}", err);
+        assert!(parse_args_abi(r#"[0, 1, 0, 2, 0, 0]"#, NODE_ABI).is_err());
+        assert!(parse_args_abi("[0, 0, 0]", 3).is_err());
+    }
+
+    #[test]
+    fn test_abi_and_expectation_kind_must_agree() {
+        let mut args = CairoArgs { node_expected: NodeOutputs::from([((1, 1), vec![1])]), ..Default::default() };
+        assert!(args.to_args(LEGACY_ABI).unwrap_err().to_string().contains("need ABI 2"));
+        args.node_expected.clear();
+        args.expected = vec![1];
+        assert!(args.to_args(NODE_ABI).is_err());
+        assert_eq!(args.to_args(LEGACY_ABI).unwrap(), hex(&[0, 1, 1, 0]));
+    }
+
+    #[test]
+    fn test_batch_layout_round_trip() {
+        let singles = [
+            CairoArgs { inputs: vec![1], expected: vec![2], prog_words: vec![9, 8], ..Default::default() },
+            CairoArgs { inputs: vec![], expected: vec![3, 4], prog_words: vec![9, 8], ..Default::default() },
+        ];
+        let batch = Batch::merge(&singles).unwrap();
+        let args = batch.to_args();
+        assert_eq!(args, hex(&[2, 1, 1, 1, 2, 0, 2, 3, 4, 2, 9, 8]));
+        let parsed = parse_batch_args(&serde_json::to_string(&args).unwrap()).unwrap();
+        assert_eq!(parsed, batch);
+        assert_eq!(parsed.split(), singles);
+
+        // A single case is a batch of one under BATCH_ABI.
+        let one = singles[0].to_args(BATCH_ABI).unwrap();
+        assert_eq!(parse_args_abi(&serde_json::to_string(&one).unwrap(), BATCH_ABI).unwrap(), singles[0]);
+        assert!(parse_args_abi(&serde_json::to_string(&args).unwrap(), BATCH_ABI).is_err());
+    }
+
+    #[test]
+    fn test_batch_merge_reports_word_diff() {
+        let a = CairoArgs { prog_words: vec![1, 2, 3], ..Default::default() };
+        let b = CairoArgs { prog_words: vec![1, 5], ..Default::default() };
+        let err = Batch::merge(&[a.clone(), a, b]).unwrap_err();
+        let mismatch = err.downcast_ref::<ProgWordsMismatch>().unwrap();
+        assert_eq!(mismatch.case, 2);
+        assert_eq!(mismatch.diff, ["length 3 != 2", "[1] 0x00000002 != 0x00000005", "[2] 0x00000003 != -"]);
+        assert!(Batch::merge(&[]).is_err());
+    }
+
+    #[test]
+    fn test_dedup_layout_round_trip() {
+        // (0,0) and (1,1) run [7, 8]; (0,1) and (1,0) are empty.
+        let args = CairoArgs { inputs: vec![5], expected: vec![6], prog_words: vec![2, 7, 8, 0, 0, 2, 7, 8], ..Default::default() };
+        let dedup = args.to_args(DEDUP_ABI).unwrap();
+        assert_eq!(dedup, hex(&[1, 5, 1, 6, 2, 2, 7, 8, 0, 4, 0, 1, 1, 0]));
+        assert_eq!(parse_args_abi(&serde_json::to_string(&dedup).unwrap(), DEDUP_ABI).unwrap(), args);
+    }
+
+    #[test]
+    fn test_dedup_rejects_bad_tables() {
+        assert!(DedupPrograms::from_prog_words(&[1, 7, 0, 0]).unwrap_err().to_string().contains("node 3 program"));
+        assert!(DedupPrograms::from_prog_words(&[0, 0, 0, 0, 9]).unwrap_err().to_string().contains("trailing"));
+        let err = parse_args(r#"[0, 0, 1, 0, 4, 0, 0, 0, 1]"#).unwrap_err().to_string();
+        assert!(err.contains("trailing"), "{}", err);
+        let err = parse_args_abi(r#"[0, 0, 1, 0, 4, 0, 0, 0, 1]"#, DEDUP_ABI).unwrap_err().to_string();
+        assert_eq!(err, "node 3 uses program 1, but only 1 are defined");
+        let err = parse_args_abi(r#"[0, 0, 1, 0, 3, 0, 0, 0]"#, DEDUP_ABI).unwrap_err().to_string();
+        assert_eq!(err, "node index has 3 entries, expected 4");
+    }
+
+    #[test]
+    fn test_io_abi_carries_the_nodes() {
+        let io = IoNodes { in_node: (0, 1), out_node: (1, 0) };
+        let args = CairoArgs { inputs: vec![7], expected: vec![14], prog_words: vec![100], io, ..Default::default() };
+        assert_eq!(args.to_args(IO_ABI).unwrap(), hex(&[0, 1, 1, 0, 1, 7, 1, 14, 1, 100]));
+        assert_eq!(parse_args_abi(&serde_json::to_string(&args.to_args(IO_ABI).unwrap()).unwrap(), IO_ABI).unwrap(), args);
+
+        let err = args.to_args(LEGACY_ABI).unwrap_err().to_string();
+        assert!(err.contains("need ABI 5"), "{}", err);
+        assert!(Batch::merge(&[args]).is_err());
+        let err = parse_args_abi("[2, 0, 1, 1, 0, 0, 0]", IO_ABI).unwrap_err().to_string();
+        assert!(err.contains("IN node (2,0) is outside the 2x2 grid"), "{}", err);
+        // The default nodes under IO_ABI are LEGACY_ABI with a prefix.
+        let plain = CairoArgs { inputs: vec![7], ..Default::default() };
+        assert_eq!(plain.to_args(IO_ABI).unwrap()[4..], plain.to_args(LEGACY_ABI).unwrap()[..]);
+    }
+
+    #[test]
+    fn test_sentinel_abi_carries_the_sentinel() {
+        let sentinel = InputSentinel { value: 0, behavior: SentinelBehavior::EndOfStream };
+        let args = CairoArgs { inputs: vec![3, 0], sentinel: Some(sentinel), ..Default::default() };
+        assert_eq!(args.to_args(SENTINEL_ABI).unwrap()[..4], hex(&[2, 0, 0, 0])[..]);
+        let json = serde_json::to_string(&args.to_args(SENTINEL_ABI).unwrap()).unwrap();
+        assert_eq!(parse_args_abi(&json, SENTINEL_ABI).unwrap(), args);
+        // Without a sentinel the prefix is zeros and IO_ABI follows.
+        let plain = CairoArgs { inputs: vec![7], ..Default::default() };
+        assert_eq!(plain.to_args(SENTINEL_ABI).unwrap()[..2], hex(&[0, 0])[..]);
+        assert_eq!(plain.to_args(SENTINEL_ABI).unwrap()[2..], plain.to_args(IO_ABI).unwrap()[..]);
+        assert_eq!(sentinel.early_position(&[3, 0]), None);
+        assert_eq!(sentinel.early_position(&[0, 3]), Some(0));
+
+        let err = args.to_args(IO_ABI).unwrap_err().to_string();
+        assert_eq!(err, "an input sentinel needs ABI 6");
+        assert!(Batch::merge(&[args]).is_err());
+        let err = parse_args_abi("[3, 0, 0, 0, 1, 1, 0, 0, 0]", SENTINEL_ABI).unwrap_err().to_string();
+        assert!(err.contains("unknown sentinel mode 3"), "{}", err);
+        let err = parse_args_abi("[0, 9, 0, 0, 1, 1, 0, 0, 0]", SENTINEL_ABI).unwrap_err().to_string();
+        assert!(err.contains("without a sentinel mode"), "{}", err);
+    }
+
+    #[test]
+    fn test_grid_abi_carries_the_size() {
+        let grid = GridSize::new(1, 3).unwrap();
+        let io = IoNodes { in_node: (0, 0), out_node: (0, 2) };
+        let args = CairoArgs { inputs: vec![7], prog_words: vec![0, 1, 100, 0], io, grid, ..Default::default() };
+        assert_eq!(args.to_args(GRID_ABI).unwrap()[..6], hex(&[1, 3, 0, 0, 0, 0])[..]);
+        let json = serde_json::to_string(&args.to_args(GRID_ABI).unwrap()).unwrap();
+        assert_eq!(parse_args_abi(&json, GRID_ABI).unwrap(), args);
+        // The default grid under GRID_ABI is SENTINEL_ABI with a prefix.
+        let plain = CairoArgs { inputs: vec![7], prog_words: vec![0; 4], ..Default::default() };
+        assert_eq!(plain.to_args(GRID_ABI).unwrap()[2..], plain.to_args(SENTINEL_ABI).unwrap()[..]);
+
+        let err = args.to_args(SENTINEL_ABI).unwrap_err().to_string();
+        assert_eq!(err, "a 1x3 grid needs ABI 7");
+        let err = parse_args_abi("[0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0]", GRID_ABI).unwrap_err().to_string();
+        assert!(err.contains("grid size at args[0]"), "{}", err);
+        let err = parse_args_abi("[1, 3, 0, 0, 0, 0, 1, 0, 0, 0, 0]", GRID_ABI).unwrap_err().to_string();
+        assert!(err.contains("OUT node (1,0) is outside the 1x3 grid"), "{}", err);
+    }
+
+    #[test]
+    fn test_limits() {
+        use crate::limits::{Limit, Limits, Origin};
+        let args = CairoArgs { inputs: vec![1, 2, 3], expected: vec![2, 4], prog_words: vec![0; 4], ..Default::default() };
+        let lowered = |limit: Limit, value: u64| {
+            let mut limits = Limits::default();
+            limits.set(limit, value, Origin::Config);
+            limits
+        };
+        assert_eq!(args.to_args_with(LEGACY_ABI, &Limits::default()).unwrap(), args.to_args(LEGACY_ABI).unwrap());
+        let err = args.to_args_with(LEGACY_ABI, &lowered(Limit::Inputs, 2)).unwrap_err().to_string();
+        assert_eq!(err, "the input stream has 3 values, over the inputs limit of 2 (from zk100.toml)");
+        let err = args.to_args_with(LEGACY_ABI, &lowered(Limit::Outputs, 1)).unwrap_err().to_string();
+        assert_eq!(err, "the expected stream has 2 values, over the outputs limit of 1 (from zk100.toml)");
+        let err = args.to_args_with(LEGACY_ABI, &lowered(Limit::ArgsElements, 10)).unwrap_err().to_string();
+        assert_eq!(err, "the args array has 12 elements, over the args_elements limit of 10 (from zk100.toml)");
+    }
+}
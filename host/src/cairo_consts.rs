@@ -0,0 +1,62 @@
+//! Cairo constant definitions generated from the Rust instruction tables.
+//!
+//! The guest decodes words with its own copies of the opcode numbers, operand
+//! codes and field layout. `generate` renders those from `Op::ALL`,
+//! `Src::KINDS`, `Dst::KINDS`, `PortTag::ALL` and the codec's `FIELDS`, so the
+//! emitted module is correct by construction; `zk100 gen-cairo-consts` writes
+//! it out for the guest repo.
+
+use crate::instruction::{Dst, FIELDS, Op, PortTag, Src};
+
+/// Render the Cairo module.
+pub fn generate() -> String {
+    let mut out = String::new();
+    out.push_str("// Generated by `zk100 gen-cairo-consts` from the host instruction tables.\n");
+    out.push_str("// Do not edit; regenerate instead.\n");
+
+    out.push_str("\n// Instruction word layout, most significant field first.\n");
+    out.push_str("// Cairo decodes by dividing by FIELD_DIVISOR and masking with FIELD_MASK.\n");
+    for (name, field) in FIELDS {
+        out.push_str(&format!("pub const {}_SHIFT: u32 = {};\n", name, field.shift));
+        out.push_str(&format!("pub const {}_DIVISOR: u32 = 0x{:x};\n", name, 1u64 << field.shift));
+        out.push_str(&format!("pub const {}_MASK: u32 = 0x{:x};\n", name, field.mask));
+    }
+
+    out.push_str("\n// Opcodes (OP field).\n");
+    for op in Op::ALL {
+        out.push_str(&format!("pub const OP_{}: felt252 = {};\n", op.mnemonic(), op as u32));
+    }
+
+    out.push_str("\n// Source operand codes (SRC field).\n");
+    for src in Src::KINDS {
+        out.push_str(&format!("pub const SRC_{}: felt252 = {};\n", src.kind_name(), src.to_code()));
+    }
+
+    out.push_str("\n// Destination operand codes (DST field).\n");
+    for dst in Dst::KINDS {
+        out.push_str(&format!("pub const DST_{}: felt252 = {};\n", dst.kind_name(), dst.to_code()));
+    }
+
+    out.push_str("\n// Port tags (SRC_PORT / DST_PORT fields).\n");
+    for port in PortTag::ALL {
+        out.push_str(&format!("pub const PORT_{}: felt252 = {};\n", port, port as u32));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constants_match_codec() {
+        let consts = generate();
+        assert!(consts.contains("pub const OP_MOV: felt252 = 1;\n"));
+        assert!(consts.contains("pub const OP_HLT: felt252 = 13;\n"));
+        assert!(consts.contains("pub const LIT_DIVISOR: u32 = 0x1000000;\n"));
+        assert!(consts.contains("pub const SRC_PORT_MASK: u32 = 0x3;\n"));
+        assert!(consts.contains("pub const DST_LAST: felt252 = 4;\n"));
+        assert!(consts.contains("pub const PORT_RIGHT: felt252 = 3;\n"));
+    }
+}
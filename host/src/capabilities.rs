@@ -0,0 +1,322 @@
+//! What this build supports, behind `zk100 capabilities`, the daemon's
+//! `capabilities` method and `zk100_capabilities` in the C ABI.
+//!
+//! Everything here is read from the constants and enums the implementation
+//! itself uses, so the report cannot drift from the code. Each version is
+//! reported as the one this build writes plus the oldest it still reads.
+//!
+//! `PROFILES` names the settings each guest build expects, so `--profile`
+//! sets all of them at once instead of one flag at a time.
+
+use crate::instruction::{Dst, LIT_FIELD, Op, PortTag, Src};
+use crate::assembler::{Constants, GridSize};
+use crate::dialect::Dialect;
+use crate::limits::Limits;
+use crate::merkle::{self, HashFunction};
+use crate::sim::{self, Schedule};
+use crate::{assembler, attest, bundle, cairo_abi, grid};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct VersionRange {
+    /// Version this build writes.
+    pub current: u64,
+    /// Oldest version this build reads.
+    pub min_readable: u64,
+}
+
+impl VersionRange {
+    fn only(version: u32) -> Self {
+        VersionRange { current: version.into(), min_readable: version.into() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Range {
+    pub min: usize,
+    pub max: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    pub tool: &'static str,
+    pub version: &'static str,
+    pub encoding_version: VersionRange,
+    pub commitment_version: VersionRange,
+    pub abi_version: VersionRange,
+    pub program_schema: VersionRange,
+    pub bundle_format: VersionRange,
+    /// Hash used by the current commitment version.
+    pub hash_function: HashFunction,
+    pub hash_functions: Vec<HashFunction>,
+    pub grid_rows: Range,
+    pub grid_cols: Range,
+    pub max_literal: u32,
+    pub opcodes: Vec<&'static str>,
+    pub sources: Vec<&'static str>,
+    pub destinations: Vec<&'static str>,
+    pub ports: Vec<String>,
+    pub profiles: Vec<&'static str>,
+    /// Active mnemonic aliases; the built-in ones unless a project adds more.
+    pub aliases: BTreeMap<String, &'static str>,
+    /// Active limits and where each was set; the defaults unless a project
+    /// or a flag changes them.
+    pub limits: Limits,
+}
+
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        tool: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        encoding_version: VersionRange::only(attest::ENCODING_VERSION),
+        commitment_version: VersionRange::only(attest::COMMITMENT_VERSION),
+        abi_version: VersionRange { current: attest::ABI_VERSION.into(), min_readable: cairo_abi::LEGACY_ABI.into() },
+        program_schema: VersionRange { current: grid::CURRENT_SCHEMA, min_readable: grid::OLDEST_SCHEMA },
+        bundle_format: VersionRange::only(bundle::FORMAT_VERSION),
+        hash_function: merkle::HASH_FUNCTION,
+        hash_functions: HashFunction::ALL.to_vec(),
+        grid_rows: Range { min: 1, max: assembler::MAX_GRID_SIDE },
+        grid_cols: Range { min: 1, max: assembler::MAX_GRID_SIDE },
+        max_literal: LIT_FIELD.mask,
+        opcodes: Op::ALL.iter().map(|op| op.mnemonic()).collect(),
+        sources: Src::KINDS.iter().map(|s| s.kind_name()).collect(),
+        destinations: Dst::KINDS.iter().map(|d| d.kind_name()).collect(),
+        ports: PortTag::ALL.iter().map(|p| p.to_string()).collect(),
+        profiles: PROFILES.iter().map(|p| p.name).collect(),
+        aliases: Dialect::default().table(),
+        limits: Limits::default(),
+    }
+}
+
+/// Everything a guest build must agree with the host on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    pub encoding_version: u32,
+    pub commitment_version: u32,
+    pub abi_version: u32,
+    pub hash_function: HashFunction,
+    pub schedule: Schedule,
+}
+
+impl Default for Settings {
+    /// What `assemble` uses with no profile and no flags.
+    fn default() -> Self {
+        Settings {
+            encoding_version: attest::ENCODING_VERSION,
+            commitment_version: attest::COMMITMENT_VERSION,
+            abi_version: cairo_abi::LEGACY_ABI,
+            hash_function: merkle::HASH_FUNCTION,
+            schedule: sim::SCHEDULE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub settings: Settings,
+}
+
+const fn settings(abi_version: u32) -> Settings {
+    Settings {
+        encoding_version: attest::ENCODING_VERSION,
+        commitment_version: attest::COMMITMENT_VERSION,
+        abi_version,
+        hash_function: merkle::HASH_FUNCTION,
+        schedule: sim::SCHEDULE,
+    }
+}
+
+pub const PROFILES: [Profile; 3] = [
+    Profile {
+        name: "guest-v1",
+        description: "released guest: flat expected stream, IN at (0,0), OUT at (1,1)",
+        settings: settings(cairo_abi::LEGACY_ABI),
+    },
+    Profile {
+        name: "guest-v2",
+        description: "guest with shared node programs sent once",
+        settings: settings(cairo_abi::DEDUP_ABI),
+    },
+    Profile {
+        name: "experimental",
+        description: "next guest revision with designated IN/OUT nodes",
+        settings: settings(cairo_abi::IO_ABI),
+    },
+];
+
+pub fn profile(name: &str) -> Result<&'static Profile> {
+    PROFILES.iter().find(|p| p.name == name).ok_or_else(|| {
+        let names: Vec<&str> = PROFILES.iter().map(|p| p.name).collect();
+        anyhow!("unknown profile '{}'; valid profiles: {}", name, names.join(", "))
+    })
+}
+
+/// Settings after applying a profile and then explicit flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolved {
+    pub profile: Option<&'static str>,
+    pub settings: Settings,
+    /// One line per flag that overrode a profile value.
+    pub warnings: Vec<String>,
+    /// Limits to assemble under, from `zk100.toml` and `--limit`.
+    pub limits: Limits,
+    /// Build wide literals over several instructions (`--synthesize-literals`).
+    pub synthesize_literals: bool,
+    /// Ignore extra operands with a warning (`--lenient`).
+    pub lenient: bool,
+    /// Fail on ports that point off the grid (`--strict-ports`).
+    pub strict_ports: bool,
+    /// Labels differing only in case are distinct (`--case-sensitive-labels`).
+    pub case_sensitive_labels: bool,
+    /// Constants from `--define`.
+    pub defines: Constants,
+    /// Node length to pad to (`--pad-to`).
+    pub pad_to: Option<usize>,
+    /// Hold the source to TIS-100 (`--tis100`).
+    pub tis100: bool,
+    /// Grid size from `--rows`/`--cols`.
+    pub grid: Option<GridSize>,
+}
+
+/// Resolve `profile` (if any) with an explicit `--abi` on top.
+pub fn resolve(profile_name: Option<&str>, abi: Option<u32>) -> Result<Resolved> {
+    let mut resolved = Resolved::default();
+    if let Some(name) = profile_name {
+        let profile = profile(name)?;
+        resolved.profile = Some(profile.name);
+        resolved.settings = profile.settings;
+    }
+    if let Some(abi) = abi {
+        if let Some(name) = resolved.profile.filter(|_| abi != resolved.settings.abi_version) {
+            resolved.warnings.push(format!(
+                "--abi {} overrides abi_version {} of profile {}",
+                abi, resolved.settings.abi_version, name
+            ));
+        }
+        resolved.settings.abi_version = abi;
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each helper matches exhaustively, so a new variant fails to compile here
+    // until it is listed, and the test then fails until it is advertised.
+
+    fn hash_name(h: HashFunction) -> &'static str {
+        match h {
+            HashFunction::Sha256 => h.name(),
+        }
+    }
+
+    fn op_name(op: Op) -> &'static str {
+        match op {
+            Op::Mov | Op::Add | Op::Sub | Op::Neg | Op::Sav | Op::Swp | Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz
+            | Op::Jlz | Op::Nop | Op::Hlt => op.mnemonic(),
+        }
+    }
+
+    fn src_name(src: Src) -> &'static str {
+        match src {
+            Src::Lit(_) | Src::Acc | Src::Nil | Src::In | Src::P(_) | Src::Last => src.kind_name(),
+        }
+    }
+
+    fn dst_name(dst: Dst) -> &'static str {
+        match dst {
+            Dst::Acc | Dst::Nil | Dst::Out | Dst::P(_) | Dst::Last => dst.kind_name(),
+        }
+    }
+
+    fn port_name(port: PortTag) -> &'static str {
+        match port {
+            PortTag::Up => "UP",
+            PortTag::Down => "DOWN",
+            PortTag::Left => "LEFT",
+            PortTag::Right => "RIGHT",
+        }
+    }
+
+    #[test]
+    fn test_every_variant_is_advertised() {
+        let caps = serde_json::to_value(capabilities()).unwrap();
+        let listed = |key: &str| -> Vec<String> {
+            caps[key].as_array().unwrap().iter().map(|v| v.as_str().unwrap().to_string()).collect()
+        };
+        let all = [
+            ("hash_functions", vec![hash_name(HashFunction::Sha256)]),
+            (
+                "opcodes",
+                [Op::Mov, Op::Add, Op::Sub, Op::Neg, Op::Sav, Op::Swp, Op::Jmp, Op::Jz, Op::Jnz, Op::Jgz, Op::Jlz, Op::Nop, Op::Hlt]
+                    .map(op_name)
+                    .to_vec(),
+            ),
+            (
+                "sources",
+                [Src::Lit(0), Src::Acc, Src::Nil, Src::In, Src::P(PortTag::Up), Src::Last].map(src_name).to_vec(),
+            ),
+            (
+                "destinations",
+                [Dst::Acc, Dst::Nil, Dst::Out, Dst::P(PortTag::Up), Dst::Last].map(dst_name).to_vec(),
+            ),
+            ("ports", [PortTag::Up, PortTag::Down, PortTag::Left, PortTag::Right].map(port_name).to_vec()),
+        ];
+        for (key, names) in all {
+            let listed = listed(key);
+            for name in names {
+                assert!(listed.iter().any(|l| l == name), "{} is missing {}", key, name);
+            }
+        }
+        assert_eq!(caps["hash_function"], "sha256");
+    }
+
+    #[test]
+    fn test_versions_match_implementation() {
+        let caps = capabilities();
+        assert_eq!(caps.commitment_version.current, u64::from(attest::COMMITMENT_VERSION));
+        assert_eq!(caps.program_schema.current, grid::CURRENT_SCHEMA);
+        assert!(caps.program_schema.min_readable <= caps.program_schema.current);
+        assert_eq!(caps.max_literal, 255);
+    }
+
+    #[test]
+    fn test_profiles_resolve_to_supported_settings() {
+        let abi = |name| resolve(Some(name), None).unwrap().settings.abi_version;
+        assert_eq!(abi("guest-v1"), cairo_abi::LEGACY_ABI);
+        assert_eq!(abi("guest-v2"), cairo_abi::DEDUP_ABI);
+        assert_eq!(abi("experimental"), cairo_abi::IO_ABI);
+        let caps = capabilities();
+        for profile in &PROFILES {
+            let s = profile.settings;
+            assert_eq!(u64::from(s.encoding_version), caps.encoding_version.current, "{}", profile.name);
+            assert_eq!(u64::from(s.commitment_version), caps.commitment_version.current, "{}", profile.name);
+            assert!((caps.abi_version.min_readable..=caps.abi_version.current).contains(&s.abi_version.into()));
+            assert!(HashFunction::ALL.contains(&s.hash_function) && Schedule::ALL.contains(&s.schedule));
+        }
+        assert_eq!(caps.profiles, ["guest-v1", "guest-v2", "experimental"]);
+    }
+
+    #[test]
+    fn test_flags_override_profiles_with_a_warning() {
+        let resolved = resolve(Some("guest-v2"), Some(cairo_abi::IO_ABI)).unwrap();
+        assert_eq!(resolved.profile, Some("guest-v2"));
+        assert_eq!(resolved.settings.abi_version, cairo_abi::IO_ABI);
+        assert_eq!(resolved.warnings, ["--abi 5 overrides abi_version 4 of profile guest-v2"]);
+        // Restating the profile's value, or having no profile, is not an override.
+        assert!(resolve(Some("guest-v2"), Some(cairo_abi::DEDUP_ABI)).unwrap().warnings.is_empty());
+        assert_eq!(resolve(None, Some(2)).unwrap(), Resolved { settings: settings(2), ..Default::default() });
+        assert_eq!(resolve(None, None).unwrap().settings, Settings::default());
+    }
+
+    #[test]
+    fn test_unknown_profile_lists_valid_names() {
+        let err = resolve(Some("guest-v3"), None).unwrap_err().to_string();
+        assert_eq!(err, "unknown profile 'guest-v3'; valid profiles: guest-v1, guest-v2, experimental");
+    }
+}
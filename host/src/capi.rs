@@ -0,0 +1,401 @@
+//! C ABI for embedding the assembler in non-Rust services.
+//!
+//! Every function returns a `Zk100Status` code and, on failure, fills the
+//! caller-provided `Zk100Error` with the same code plus a UTF-8 message.
+//! Buffers handed out by this module are owned by the caller and must be
+//! released with `zk100_free_buffer`. Panics never cross the boundary: they
+//! are caught and reported as `ZK100_STATUS_PANIC`. The `zk100-capi` crate
+//! builds this module as `libzk100`; its header `capi/include/zk100.h` is
+//! generated by cbindgen (see `capi/cbindgen.toml`).
+
+use crate::{assembler, cairo_abi, capabilities, felt_bytes, merkle};
+use anyhow::Result;
+use std::ffi::{CStr, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// Status codes returned by every `zk100_*` function.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zk100Status {
+    Ok = 0,
+    /// A required pointer argument was NULL.
+    NullArgument = 1,
+    /// The source text was not valid UTF-8.
+    InvalidUtf8 = 2,
+    /// The source failed to assemble; see the error message.
+    Assembly = 3,
+    /// Serialization or root computation failed.
+    Internal = 4,
+    /// A Rust panic was caught at the boundary.
+    Panic = 5,
+}
+
+/// A byte buffer allocated by the library.
+///
+/// `data` is followed by a NUL byte that is not counted in `len`, so text
+/// buffers can be used directly as C strings. An empty buffer has a NULL
+/// `data` pointer.
+#[repr(C)]
+pub struct Zk100Buffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// Error details filled in when a call fails.
+#[repr(C)]
+pub struct Zk100Error {
+    pub code: i32,
+    pub message: Zk100Buffer,
+}
+
+impl Zk100Buffer {
+    fn empty() -> Self {
+        Zk100Buffer {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        if bytes.is_empty() {
+            return Zk100Buffer::empty();
+        }
+        let mut owned = Vec::with_capacity(bytes.len() + 1);
+        owned.extend_from_slice(bytes);
+        owned.push(0);
+        let len = bytes.len();
+        let data = Box::into_raw(owned.into_boxed_slice()) as *mut u8;
+        Zk100Buffer { data, len }
+    }
+}
+
+struct CallError {
+    status: Zk100Status,
+    message: String,
+}
+
+impl CallError {
+    fn new(status: Zk100Status, message: impl Into<String>) -> Self {
+        CallError {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// Run `body` with panics caught, translating the outcome into a status code
+/// and (when `err` is non-NULL) an error record.
+fn guarded<F>(err: *mut Zk100Error, body: F) -> i32
+where
+    F: FnOnce() -> std::result::Result<(), CallError>,
+{
+    if !err.is_null() {
+        // SAFETY: the caller guarantees `err` points to writable storage.
+        unsafe {
+            (*err).code = Zk100Status::Ok as i32;
+            (*err).message = Zk100Buffer::empty();
+        }
+    }
+
+    let outcome = match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Err(CallError::new(Zk100Status::Panic, format!("panic: {}", message)))
+        }
+    };
+
+    match outcome {
+        Ok(()) => Zk100Status::Ok as i32,
+        Err(e) => {
+            if !err.is_null() {
+                // SAFETY: checked non-NULL above; the caller owns the storage.
+                unsafe {
+                    (*err).code = e.status as i32;
+                    (*err).message = Zk100Buffer::from_bytes(e.message.as_bytes());
+                }
+            }
+            e.status as i32
+        }
+    }
+}
+
+/// Borrow a NUL-terminated C string as `&str`.
+///
+/// # Safety
+/// `src` must be NULL or point to a NUL-terminated string that outlives the call.
+unsafe fn source_arg<'a>(src: *const c_char) -> std::result::Result<&'a str, CallError> {
+    if src.is_null() {
+        return Err(CallError::new(Zk100Status::NullArgument, "src is NULL"));
+    }
+    // SAFETY: forwarded from the caller's contract.
+    let bytes = unsafe { CStr::from_ptr(src) };
+    bytes
+        .to_str()
+        .map_err(|e| CallError::new(Zk100Status::InvalidUtf8, format!("src is not UTF-8: {}", e)))
+}
+
+/// Borrow a `(ptr, len)` pair of `uint32_t` as a slice; NULL is only valid with len 0.
+///
+/// # Safety
+/// `data` must point to `len` readable `u32` values when non-NULL.
+unsafe fn words_arg<'a>(
+    data: *const u32,
+    len: usize,
+    name: &str,
+) -> std::result::Result<&'a [u32], CallError> {
+    if data.is_null() {
+        if len == 0 {
+            return Ok(&[]);
+        }
+        return Err(CallError::new(Zk100Status::NullArgument, format!("{} is NULL", name)));
+    }
+    // SAFETY: forwarded from the caller's contract.
+    Ok(unsafe { std::slice::from_raw_parts(data, len) })
+}
+
+fn assemble_to_json(src: &str, inputs: &[u32], expected: &[u32]) -> std::result::Result<String, CallError> {
+    let programs = assembler::parse_assembly(src)
+        .map_err(|e| CallError::new(Zk100Status::Assembly, e.to_string()))?;
+    let json = (|| -> Result<String> {
+        let prog_words = assembler::encode_programs(&programs)?;
+        let args = cairo_abi::generate_args(inputs, expected, &prog_words)?;
+        Ok(serde_json::to_string(&args)?)
+    })();
+    json.map_err(|e| CallError::new(Zk100Status::Internal, e.to_string()))
+}
+
+fn program_root(src: &str) -> std::result::Result<[u8; 32], CallError> {
+    let programs = assembler::parse_assembly(src)
+        .map_err(|e| CallError::new(Zk100Status::Assembly, e.to_string()))?;
+    let root = (|| -> Result<[u8; 32]> {
+        felt_bytes::bytes32(&merkle::compute_program_merkle_root(&programs)?)
+    })();
+    root.map_err(|e| CallError::new(Zk100Status::Internal, e.to_string()))
+}
+
+/// Assemble `src` into a cairo-prove args.json document with no inputs and no
+/// expected outputs.
+///
+/// # Safety
+/// See `zk100_assemble_with_io`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk100_assemble(
+    src: *const c_char,
+    out_args_json: *mut Zk100Buffer,
+    err: *mut Zk100Error,
+) -> i32 {
+    // SAFETY: same contract as the callee.
+    unsafe { zk100_assemble_with_io(src, ptr::null(), 0, ptr::null(), 0, out_args_json, err) }
+}
+
+/// Assemble `src` into a cairo-prove args.json document.
+///
+/// # Safety
+/// `src` must be a NUL-terminated string. `inputs`/`expected` must point to
+/// `inputs_len`/`expected_len` values or be NULL with a zero length.
+/// `out_args_json` must point to writable storage; on success it receives a
+/// buffer the caller must release with `zk100_free_buffer`. `err` may be NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk100_assemble_with_io(
+    src: *const c_char,
+    inputs: *const u32,
+    inputs_len: usize,
+    expected: *const u32,
+    expected_len: usize,
+    out_args_json: *mut Zk100Buffer,
+    err: *mut Zk100Error,
+) -> i32 {
+    guarded(err, || {
+        if out_args_json.is_null() {
+            return Err(CallError::new(Zk100Status::NullArgument, "out_args_json is NULL"));
+        }
+        // SAFETY: argument contracts are documented on this function.
+        let (src, inputs, expected) = unsafe {
+            (
+                source_arg(src)?,
+                words_arg(inputs, inputs_len, "inputs")?,
+                words_arg(expected, expected_len, "expected")?,
+            )
+        };
+        let json = assemble_to_json(src, inputs, expected)?;
+        // SAFETY: checked non-NULL above.
+        unsafe { *out_args_json = Zk100Buffer::from_bytes(json.as_bytes()) };
+        Ok(())
+    })
+}
+
+/// Compute the 32-byte program merkle root of `src`.
+///
+/// # Safety
+/// `src` must be a NUL-terminated string and `out_root` must point to 32
+/// writable bytes. `err` may be NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk100_program_root(
+    src: *const c_char,
+    out_root: *mut u8,
+    err: *mut Zk100Error,
+) -> i32 {
+    guarded(err, || {
+        if out_root.is_null() {
+            return Err(CallError::new(Zk100Status::NullArgument, "out_root is NULL"));
+        }
+        // SAFETY: argument contract is documented on this function.
+        let src = unsafe { source_arg(src)? };
+        let root = program_root(src)?;
+        // SAFETY: caller guarantees 32 writable bytes at `out_root`.
+        unsafe { ptr::copy_nonoverlapping(root.as_ptr(), out_root, root.len()) };
+        Ok(())
+    })
+}
+
+/// Describe what this build supports as a JSON document (see
+/// `capabilities::Capabilities`).
+///
+/// # Safety
+/// `out_json` must point to writable storage; on success it receives a buffer
+/// the caller must release with `zk100_free_buffer`. `err` may be NULL.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk100_capabilities(out_json: *mut Zk100Buffer, err: *mut Zk100Error) -> i32 {
+    guarded(err, || {
+        if out_json.is_null() {
+            return Err(CallError::new(Zk100Status::NullArgument, "out_json is NULL"));
+        }
+        let json = serde_json::to_string(&capabilities::capabilities())
+            .map_err(|e| CallError::new(Zk100Status::Internal, e.to_string()))?;
+        // SAFETY: checked non-NULL above.
+        unsafe { *out_json = Zk100Buffer::from_bytes(json.as_bytes()) };
+        Ok(())
+    })
+}
+
+/// Release a buffer previously returned by this library and reset it to empty.
+///
+/// # Safety
+/// `buf` must be NULL or point to a buffer filled in by a `zk100_*` function
+/// that has not already been freed. Freeing an empty buffer is a no-op.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zk100_free_buffer(buf: *mut Zk100Buffer) {
+    if buf.is_null() {
+        return;
+    }
+    // SAFETY: the caller guarantees `buf` came from `Zk100Buffer::from_bytes`,
+    // which allocated `len + 1` bytes as a boxed slice.
+    unsafe {
+        let b = &mut *buf;
+        if !b.data.is_null() {
+            let slice = ptr::slice_from_raw_parts_mut(b.data, b.len + 1);
+            drop(Box::from_raw(slice));
+        }
+        *b = Zk100Buffer::empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn empty_error() -> Zk100Error {
+        Zk100Error {
+            code: -1,
+            message: Zk100Buffer::empty(),
+        }
+    }
+
+    fn message(err: &Zk100Error) -> String {
+        let bytes = unsafe { std::slice::from_raw_parts(err.message.data, err.message.len) };
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_assemble_roundtrip() {
+        let src = CString::new("NODE (0,0)\nMOV IN, ACC\nHLT\n").unwrap();
+        let inputs = [7u32];
+        let mut out = Zk100Buffer::empty();
+        let mut err = empty_error();
+        let status = unsafe {
+            zk100_assemble_with_io(src.as_ptr(), inputs.as_ptr(), 1, ptr::null(), 0, &mut out, &mut err)
+        };
+        assert_eq!(status, Zk100Status::Ok as i32);
+        assert_eq!(err.code, 0);
+        let json = unsafe { std::slice::from_raw_parts(out.data, out.len) };
+        let args: Vec<String> = serde_json::from_slice(json).unwrap();
+        assert_eq!(&args[..2], &["0x1".to_string(), "0x7".to_string()]);
+        // NUL terminator follows the payload.
+        assert_eq!(unsafe { *out.data.add(out.len) }, 0);
+        unsafe { zk100_free_buffer(&mut out) };
+        assert!(out.data.is_null());
+    }
+
+    #[test]
+    fn test_empty_buffer_has_null_data() {
+        let mut empty = Zk100Buffer::from_bytes(b"");
+        assert!(empty.data.is_null());
+        assert_eq!(empty.len, 0);
+        // Freeing it is a no-op, like freeing any empty buffer.
+        unsafe { zk100_free_buffer(&mut empty) };
+        assert!(empty.data.is_null());
+    }
+
+    #[test]
+    fn test_assembly_error_reported() {
+        let src = CString::new("NODE (0,0)\nMVO 1, ACC\n").unwrap();
+        let mut out = Zk100Buffer::empty();
+        let mut err = empty_error();
+        let status = unsafe { zk100_assemble(src.as_ptr(), &mut out, &mut err) };
+        assert_eq!(status, Zk100Status::Assembly as i32);
+        assert_eq!(err.code, status);
+        assert!(message(&err).contains("MVO"));
+        assert!(out.data.is_null());
+        unsafe { zk100_free_buffer(&mut err.message) };
+    }
+
+    #[test]
+    fn test_null_arguments() {
+        let mut root = [0u8; 32];
+        let mut err = empty_error();
+        let status = unsafe { zk100_program_root(ptr::null(), root.as_mut_ptr(), &mut err) };
+        assert_eq!(status, Zk100Status::NullArgument as i32);
+        unsafe { zk100_free_buffer(&mut err.message) };
+        // A NULL error pointer is allowed.
+        let status = unsafe { zk100_program_root(ptr::null(), root.as_mut_ptr(), ptr::null_mut()) };
+        assert_eq!(status, Zk100Status::NullArgument as i32);
+    }
+
+    #[test]
+    fn test_program_root_matches_library() {
+        let code = "NODE (0,0)\nNOP\nHLT\n";
+        let src = CString::new(code).unwrap();
+        let mut root = [0u8; 32];
+        let status = unsafe { zk100_program_root(src.as_ptr(), root.as_mut_ptr(), ptr::null_mut()) };
+        assert_eq!(status, Zk100Status::Ok as i32);
+        let programs = assembler::parse_assembly(code).unwrap();
+        assert_eq!(root.to_vec(), merkle::compute_program_merkle_root(&programs).unwrap());
+    }
+
+    #[test]
+    fn test_capabilities_json() {
+        let mut out = Zk100Buffer::empty();
+        let status = unsafe { zk100_capabilities(&mut out, ptr::null_mut()) };
+        assert_eq!(status, Zk100Status::Ok as i32);
+        let json: serde_json::Value =
+            serde_json::from_slice(unsafe { std::slice::from_raw_parts(out.data, out.len) }).unwrap();
+        assert_eq!(json, serde_json::to_value(capabilities::capabilities()).unwrap());
+        unsafe { zk100_free_buffer(&mut out) };
+    }
+
+    #[test]
+    fn test_panic_is_caught() {
+        let mut err = empty_error();
+        let status = guarded(&mut err, || panic!("boom"));
+        assert_eq!(status, Zk100Status::Panic as i32);
+        assert_eq!(message(&err), "panic: boom");
+        unsafe { zk100_free_buffer(&mut err.message) };
+    }
+}
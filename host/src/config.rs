@@ -0,0 +1,101 @@
+//! Project settings files: `key = value` lines with `#` comments, the subset
+//! of TOML `zk100.toml` and `.zk100/config.toml` use.
+
+use crate::exec;
+use crate::scarb::strip_comment;
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::Path;
+
+/// Project settings, in the directory zk100 is run from.
+pub const PROJECT_CONFIG: &str = "zk100.toml";
+
+/// `key = value` lines of `text` under `[section]`, or before any section
+/// header for `None`, with their 0-based line index.
+fn entries<'a>(text: &'a str, section: Option<&'a str>) -> impl Iterator<Item = (usize, &'a str, &'a str)> + 'a {
+    let mut current: Option<&str> = None;
+    text.lines().enumerate().filter_map(move |(idx, raw)| {
+        let line = strip_comment(raw).trim();
+        if line.starts_with('[') {
+            current = Some(line.trim_matches(|c| c == '[' || c == ']').trim());
+            return None;
+        }
+        let (key, value) = line.split_once('=')?;
+        (current == section).then(|| (idx, key.trim(), value.trim()))
+    })
+}
+
+fn unquote(path: &Path, idx: usize, key: &str, value: &str) -> Result<String> {
+    if !value.starts_with('"') {
+        return Ok(value.to_string());
+    }
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("{}:{}: unterminated string for {}", path.display(), idx + 1, key))
+}
+
+/// The value of the first top-level `key = ...` line in `path`, unquoted if
+/// it is a string. A missing file has no keys.
+pub fn read_key(path: &Path, key: &str) -> Result<Option<String>> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    exec::note_read(path);
+    let found = entries(&text, None).find(|(_, k, _)| *k == key);
+    found.map(|(idx, k, value)| unquote(path, idx, k, value)).transpose()
+}
+
+/// Every `key = value` of the `[section]` table in `path`, in file order,
+/// values unquoted. A missing file or table is empty.
+pub fn read_table(path: &Path, section: &str) -> Result<Vec<(String, String)>> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Ok(Vec::new());
+    };
+    exec::note_read(path);
+    entries(&text, Some(section))
+        .map(|(idx, key, value)| Ok((key.to_string(), unquote(path, idx, key, value)?)))
+        .collect()
+}
+
+/// `read_key` for a `true`/`false` key.
+pub fn read_bool(path: &Path, key: &str) -> Result<Option<bool>> {
+    match read_key(path, key)?.as_deref() {
+        None => Ok(None),
+        Some("true") => Ok(Some(true)),
+        Some("false") => Ok(Some(false)),
+        Some(other) => Err(anyhow!("{}: {} must be true or false, found {}", path.display(), key, other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_key() {
+        let path = std::env::temp_dir().join(format!("zk100-config-{}.toml", std::process::id()));
+        fs::write(&path, "# settings\nprofile = \"guest-v2\" # pinned\nstats_log=true\nbroken = \"x\n").unwrap();
+        assert_eq!(read_key(&path, "profile").unwrap().as_deref(), Some("guest-v2"));
+        assert_eq!(read_bool(&path, "stats_log").unwrap(), Some(true));
+        assert_eq!(read_key(&path, "missing").unwrap(), None);
+        assert!(read_key(&path, "broken").is_err());
+        assert!(read_bool(&path, "profile").is_err());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read_key(&path, "profile").unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_table() {
+        let path = std::env::temp_dir().join(format!("zk100-config-table-{}.toml", std::process::id()));
+        fs::write(&path, "profile = \"guest-v1\"\n[aliases]\nJEZ = \"JZ\" # from gen\nHALT=\"HLT\"\n[other]\nx = 1\n").unwrap();
+        let table = read_table(&path, "aliases").unwrap();
+        assert_eq!(table, [("JEZ".to_string(), "JZ".to_string()), ("HALT".to_string(), "HLT".to_string())]);
+        assert!(read_table(&path, "missing").unwrap().is_empty());
+        // Keys inside a table are not top-level keys.
+        assert_eq!(read_key(&path, "JEZ").unwrap(), None);
+        assert_eq!(read_key(&path, "profile").unwrap().as_deref(), Some("guest-v1"));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,398 @@
+//! Differential testing of the host simulator against the Cairo guest,
+//! behind `zk100 conformance` and the `guest-compare` tests.
+//!
+//! A case is a program and its inputs. Cases come from the corpus embedded
+//! from `src/conformance/` (programs that exercise a guest quirk or once
+//! diverged, each with `# inputs: ..` lines naming its cases), from every
+//! example case, and from seeded `program_gen` programs. Each case is
+//! simulated on the host, then its args (legacy ABI, with what the host
+//! wrote to OUT as the expected outputs) are run through the guest.
+//!
+//! The guest only returns its public outputs, so it agrees with the host
+//! when it is solved (its OUT stream is exactly the host's) and reports the
+//! host's cycles, messages and nodes used. A host error (an overflow) must
+//! be a guest failure (its panic), and the other way round. Halting and
+//! deadlock look the same from outside the guest; running out of cycles
+//! does not, and is compared too.
+//!
+//! A divergence keeps the case's `NNN.asm` and `NNN.args.json` in the work
+//! directory so it can be replayed by hand.
+
+use crate::cairo_abi::{CairoArgs, LEGACY_ABI};
+use crate::examples::EXAMPLES;
+use crate::inputs::splitmix64;
+use crate::limits::DEFAULT_CYCLES;
+use crate::program_gen::{self, GenConfig, PortDensity};
+use crate::proof_reader::{self, ProofPublicData};
+use crate::sim::{self, SimResult, Termination};
+use crate::{assembler, exec, queue};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Comment line naming one case's inputs in a corpus program.
+pub const INPUTS_PREFIX: &str = "# inputs:";
+
+const CORPUS: &[(&str, &str)] = &[
+    ("port_relay", include_str!("conformance/port_relay.asm")),
+    ("literal_max", include_str!("conformance/literal_max.asm")),
+    ("neg_wraps", include_str!("conformance/neg_wraps.asm")),
+    ("overflow", include_str!("conformance/overflow.asm")),
+    ("cycle_limit", include_str!("conformance/cycle_limit.asm")),
+    ("last_blocks", include_str!("conformance/last_blocks.asm")),
+    ("pc_wraps", include_str!("conformance/pc_wraps.asm")),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Case {
+    pub name: String,
+    pub source: String,
+    pub inputs: Vec<u32>,
+}
+
+/// The embedded corpus followed by every example case.
+pub fn corpus() -> Result<Vec<Case>> {
+    let mut cases = Vec::new();
+    for (name, source) in CORPUS {
+        cases.extend(cases_of(&format!("corpus/{}", name), source)?);
+    }
+    for example in EXAMPLES {
+        for (n, case) in example.cases.iter().enumerate() {
+            cases.push(Case {
+                name: format!("example/{} [case {}]", example.name, n),
+                source: example.source.to_string(),
+                inputs: case.inputs.to_vec(),
+            });
+        }
+    }
+    Ok(cases)
+}
+
+/// The cases of every `.asm` file in `dir`, in name order.
+pub fn load_dir(dir: &Path) -> Result<Vec<Case>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| anyhow!("{}: {}", dir.display(), e))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "asm"))
+        .collect();
+    paths.sort();
+    let mut cases = Vec::new();
+    for path in paths {
+        let source = crate::source::read(&path)?;
+        cases.extend(cases_of(&path.display().to_string(), &source)?);
+    }
+    Ok(cases)
+}
+
+/// One case per `# inputs:` line of `source`, or a single case without
+/// inputs if it has none.
+fn cases_of(name: &str, source: &str) -> Result<Vec<Case>> {
+    let inputs: Vec<Vec<u32>> = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(INPUTS_PREFIX))
+        .map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(|value| value.parse().map_err(|_| anyhow!("{}: `{}` is not a u32 input", name, value)))
+                .collect()
+        })
+        .collect::<Result<_>>()?;
+    if inputs.is_empty() {
+        return Ok(vec![Case { name: name.to_string(), source: source.to_string(), inputs: Vec::new() }]);
+    }
+    Ok(inputs
+        .into_iter()
+        .enumerate()
+        .map(|(n, inputs)| Case { name: format!("{} [case {}]", name, n), source: source.to_string(), inputs })
+        .collect())
+}
+
+/// `count` generated programs, each with its own configuration drawn from
+/// `seed`; `random/<seed>.<n>` is the n-th of them.
+pub fn random(seed: u64, count: usize) -> Result<Vec<Case>> {
+    let mut state = seed;
+    let mut draw = |lo: u64, hi: u64| lo + splitmix64(&mut state) % (hi - lo + 1);
+    (0..count)
+        .map(|n| {
+            let loops = draw(0, 1) as usize;
+            let config = GenConfig {
+                nodes: draw(3, 4) as usize,
+                // Enough for a loop and the heaviest handshakes in every node.
+                instructions: 8 + 8 * loops + draw(0, 8) as usize,
+                loops,
+                ports: if draw(0, 1) == 0 { PortDensity::Light } else { PortDensity::Heavy },
+                inputs: draw(1, 5) as usize,
+                seed: draw(0, u64::MAX - 1),
+            };
+            let generated = program_gen::generate(&config)?;
+            Ok(Case { name: format!("random/{}.{}", seed, n), source: generated.source, inputs: generated.inputs })
+        })
+        .collect()
+}
+
+/// How the guest is run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runner {
+    /// `cairo-prove prove`, reading the public output from the proof.
+    Prove,
+    /// `scarb execute --print-program-output` in the artifact's workspace,
+    /// which skips proving.
+    Execute,
+}
+
+impl Runner {
+    pub fn tool(self) -> &'static str {
+        match self {
+            Runner::Prove => "cairo-prove",
+            Runner::Execute => "scarb",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Guest {
+    /// `<workspace>/target/<profile>/<package>.executable.json`.
+    pub artifact: PathBuf,
+    pub runner: Runner,
+}
+
+impl Guest {
+    /// Why the guest cannot run here, if it cannot; the harness then skips
+    /// instead of failing.
+    pub fn unavailable(&self) -> Option<String> {
+        let tool = self.runner.tool();
+        match exec::output(Command::new(tool).arg("--version")) {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => return Some(format!("`{} --version` exited with {}", tool, output.status)),
+            Err(e) => return Some(format!("{} is not runnable ({})", tool, e)),
+        }
+        if !self.artifact.is_file() {
+            return Some(format!("guest artifact {} does not exist", self.artifact.display()));
+        }
+        None
+    }
+
+    /// Run the guest on `args`; `Prove` writes its proof to `proof`.
+    pub fn run(&self, args: &Path, proof: &Path) -> Result<ProofPublicData> {
+        let mut cmd = match self.runner {
+            Runner::Prove => queue::prove_command(&self.artifact, proof, args),
+            Runner::Execute => self.execute_command(args)?,
+        };
+        let output = exec::output(&mut cmd)?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last = stderr.lines().rfind(|line| !line.trim().is_empty()).unwrap_or("").trim();
+            return Err(anyhow!("{} failed ({}): {}", self.runner.tool(), output.status, last));
+        }
+        match self.runner {
+            Runner::Prove => proof_reader::read(&fs::read_to_string(proof)?),
+            Runner::Execute => proof_reader::from_program_output(&String::from_utf8_lossy(&output.stdout)),
+        }
+    }
+
+    fn execute_command(&self, args: &Path) -> Result<Command> {
+        let file = self.artifact.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        let package = file.strip_suffix(".executable.json");
+        let profile_dir = self.artifact.parent();
+        let root = profile_dir.and_then(Path::parent).and_then(Path::parent);
+        let (Some(package), Some(profile), Some(root)) =
+            (package, profile_dir.and_then(Path::file_name), root)
+        else {
+            return Err(anyhow!(
+                "{} is not a Scarb artifact (<workspace>/target/<profile>/<package>.executable.json)",
+                self.artifact.display()
+            ));
+        };
+        let mut cmd = Command::new("scarb");
+        cmd.current_dir(root)
+            .arg("--profile")
+            .arg(profile)
+            .args(["execute", "--no-build", "--print-program-output", "--package", package, "--arguments-file"])
+            .arg(std::path::absolute(args)?);
+        Ok(cmd)
+    }
+}
+
+/// What the host did with a case: its run, or the error it stopped with.
+pub type HostRun = std::result::Result<SimResult, String>;
+
+/// What the guest did with a case: its public outputs, or how it failed.
+pub type GuestRun = std::result::Result<ProofPublicData, String>;
+
+/// The differences between a host and a guest run; empty when they agree.
+pub fn compare(host: &HostRun, guest: &GuestRun) -> Vec<String> {
+    let (host, guest) = match (host, guest) {
+        (Ok(host), Ok(guest)) => (host, guest),
+        (Err(_), Err(_)) => return Vec::new(),
+        (Err(e), Ok(_)) => return vec![format!("the host failed ({}) but the guest ran", e)],
+        (Ok(_), Err(e)) => return vec![format!("the guest failed ({}) but the host ran", e)],
+    };
+    let mut differences = Vec::new();
+    if !guest.solved {
+        differences.push(format!("outputs: the guest's OUT stream is not the host's {:?}", host.outputs));
+    }
+    let host_limit = host.termination == Termination::CycleLimit;
+    let guest_limit = guest.cycles >= DEFAULT_CYCLES;
+    if host_limit != guest_limit {
+        let ended = |limit| if limit { "ran out of cycles" } else { "stopped before the cycle limit" };
+        differences.push(format!("termination: the host {}, the guest {}", ended(host_limit), ended(guest_limit)));
+    }
+    for (what, host, guest) in [
+        ("cycles", host.cycles, guest.cycles),
+        ("messages", host.msgs, guest.msgs),
+        ("nodes used", u64::from(host.nodes_used), guest.nodes_used),
+    ] {
+        if host != guest {
+            differences.push(format!("{}: host {}, guest {}", what, host, guest));
+        }
+    }
+    differences
+}
+
+/// A case the guest and the host disagree on.
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub case: Case,
+    pub differences: Vec<String>,
+    /// The source and args kept for replaying the case.
+    pub source_path: PathBuf,
+    pub args_path: PathBuf,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} diverges:", self.case.name)?;
+        for difference in &self.differences {
+            writeln!(f, "  - {}", difference)?;
+        }
+        let inputs: Vec<String> = self.case.inputs.iter().map(u32::to_string).collect();
+        writeln!(f, "  inputs: [{}]", inputs.join(", "))?;
+        writeln!(f, "  args: {}", self.args_path.display())?;
+        writeln!(f, "  source ({}):", self.source_path.display())?;
+        for line in self.case.source.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Summary {
+    pub agreed: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+/// Run every case on the host and through `guest`, with scratch files in
+/// `work`; `on_case` sees each case and its differences as it finishes.
+pub fn run(
+    cases: &[Case],
+    guest: &Guest,
+    work: &Path,
+    mut on_case: impl FnMut(&Case, &[String]),
+) -> Result<Summary> {
+    fs::create_dir_all(work).map_err(|e| anyhow!("{}: {}", work.display(), e))?;
+    let mut summary = Summary::default();
+    for (n, case) in cases.iter().enumerate() {
+        let programs = assembler::parse(&case.source).map_err(|e| anyhow!("{}: {}", case.name, e))?.grid.programs;
+        let host: HostRun = sim::simulate(&programs, &case.inputs, DEFAULT_CYCLES).map_err(|e| e.to_string());
+        let args = CairoArgs {
+            inputs: case.inputs.clone(),
+            expected: host.as_ref().map(|run| run.outputs.clone()).unwrap_or_default(),
+            prog_words: assembler::encode_programs(&programs)?,
+            ..Default::default()
+        };
+        let args_path = work.join(format!("{:03}.args.json", n));
+        let proof_path = work.join(format!("{:03}.proof.json", n));
+        exec::write_atomic(&args_path, serde_json::to_string(&args.to_args(LEGACY_ABI)?)?)?;
+        let guest_run: GuestRun = guest.run(&args_path, &proof_path).map_err(|e| e.to_string());
+        let differences = compare(&host, &guest_run);
+        on_case(case, &differences);
+        fs::remove_file(&proof_path).ok();
+        if differences.is_empty() {
+            fs::remove_file(&args_path)?;
+            summary.agreed += 1;
+        } else {
+            let source_path = work.join(format!("{:03}.asm", n));
+            exec::write_atomic(&source_path, &case.source)?;
+            summary.divergences.push(Divergence { case: case.clone(), differences, source_path, args_path });
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof_reader::Schema;
+
+    fn public(outputs: &SimResult, solved: bool) -> ProofPublicData {
+        ProofPublicData {
+            schema: Schema::ProgramOutput,
+            program_root: "0x1".into(),
+            inputs: "0x2".into(),
+            outputs: "0x3".into(),
+            cycles: outputs.cycles,
+            msgs: outputs.msgs,
+            nodes_used: u64::from(outputs.nodes_used),
+            solved,
+            raw: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn test_corpus_runs_on_the_host() {
+        let cases = corpus().unwrap();
+        assert!(cases.iter().any(|case| case.name == "corpus/port_relay [case 1]" && case.inputs.is_empty()));
+        assert!(cases.iter().any(|case| case.name == "example/doubler [case 0]"));
+        let run = |name: &str| {
+            let case = cases.iter().find(|case| case.name == name).unwrap();
+            sim::simulate(&assembler::parse(&case.source).unwrap().grid.programs, &case.inputs, DEFAULT_CYCLES)
+        };
+        assert!(run("corpus/port_relay [case 0]").unwrap().outputs.is_empty());
+        assert_eq!(run("corpus/literal_max").unwrap().outputs, [255]);
+        assert_eq!(run("corpus/neg_wraps [case 0]").unwrap().outputs, [0, 4294967291]);
+        assert!(run("corpus/overflow [case 0]").is_ok());
+        assert!(run("corpus/overflow [case 1]").is_err());
+        assert_eq!(run("corpus/cycle_limit").unwrap().termination, Termination::CycleLimit);
+        assert_eq!(run("corpus/last_blocks [case 0]").unwrap().outputs, [5]);
+        assert_eq!(run("corpus/pc_wraps [case 0]").unwrap().outputs, [11, 12, 13]);
+    }
+
+    #[test]
+    fn test_random_is_seeded() {
+        let cases = random(7, 3).unwrap();
+        assert_eq!(cases, random(7, 3).unwrap());
+        assert_eq!(cases[2].name, "random/7.2");
+        assert_ne!(cases[0].source, random(8, 1).unwrap()[0].source);
+    }
+
+    #[test]
+    fn test_compare() {
+        let host = sim::simulate(&assembler::parse_assembly("NODE (1,1)\nMOV 3, OUT\nMOV P:UP, ACC\n").unwrap(), &[], 10).unwrap();
+        assert!(compare(&Ok(host.clone()), &Ok(public(&host, true))).is_empty());
+        assert!(compare(&Err("overflow".into()), &Err("panicked".into())).is_empty());
+
+        let mut guest = public(&host, false);
+        guest.cycles = DEFAULT_CYCLES;
+        guest.msgs += 1;
+        assert_eq!(
+            compare(&Ok(host.clone()), &Ok(guest)),
+            [
+                "outputs: the guest's OUT stream is not the host's [3]",
+                "termination: the host stopped before the cycle limit, the guest ran out of cycles",
+                "cycles: host 2, guest 10000",
+                "messages: host 1, guest 2",
+            ]
+        );
+        assert_eq!(
+            compare(&Ok(host), &Err("cairo-prove failed".into())),
+            ["the guest failed (cairo-prove failed) but the host ran"]
+        );
+    }
+}
@@ -0,0 +1,11 @@
+# Counts down from 3 forever: the run ends at the cycle limit with the
+# same outputs, cycles and messages on both sides.
+
+NODE (1,1)
+top:
+MOV 3, ACC
+loop:
+MOV ACC, OUT
+SUB 1
+JNZ loop
+JMP top
@@ -0,0 +1,15 @@
+# The guest does not implement LAST, so a read from it blocks and the
+# grid deadlocks after (1,0) has sent its first value.
+# inputs: 5, 6
+
+NODE (0,0)
+MOV IN, ACC
+MOV ACC, P:DOWN
+
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+MOV LAST, ACC
+
+NODE (1,1)
+MOV P:LEFT, OUT
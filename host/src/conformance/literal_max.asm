@@ -0,0 +1,8 @@
+# 255 is the widest literal the instruction word has room for and reaches
+# OUT whole; 256 does not assemble. The read from UP never completes, so the
+# grid deadlocks instead of wrapping around.
+
+NODE (1,1)
+MOV 255, ACC
+MOV ACC, OUT
+MOV P:UP, ACC
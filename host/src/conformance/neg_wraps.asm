@@ -0,0 +1,14 @@
+# NEG wraps around u32 instead of failing: 5 reaches OUT as 4294967291.
+# inputs: 0, 5
+
+NODE (0,0)
+MOV IN, ACC
+NEG
+MOV ACC, P:DOWN
+
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+
+NODE (1,1)
+MOV P:LEFT, OUT
@@ -0,0 +1,16 @@
+# SUB below zero panics in the guest; the host reports an error for the
+# same cycle. Both sides failing is agreement.
+# inputs: 3
+# inputs: 0
+
+NODE (0,0)
+MOV IN, ACC
+SUB 1
+MOV ACC, P:DOWN
+
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+
+NODE (1,1)
+MOV P:LEFT, OUT
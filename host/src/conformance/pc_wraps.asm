@@ -0,0 +1,16 @@
+# HLT does nothing and the pc wraps past the last instruction, so (0,0)
+# keeps reading inputs after its HLT.
+# inputs: 1, 2, 3
+
+NODE (0,0)
+MOV IN, ACC
+MOV ACC, P:RIGHT
+HLT
+
+NODE (0,1)
+MOV P:LEFT, ACC
+ADD 10
+MOV ACC, P:DOWN
+
+NODE (1,1)
+MOV P:UP, OUT
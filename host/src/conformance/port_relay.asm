@@ -0,0 +1,22 @@
+# Relays every input from (0,0) around the grid with port-to-port MOVs.
+# Diverged once: a simulator that completes both halves of
+# `MOV P:.., P:..` delivers the inputs, but the guest matches the read
+# half as the writer, so nothing reaches OUT and the grid deadlocks.
+# inputs: 42, 7
+# inputs:
+
+NODE (0,0)
+MOV IN, P:RIGHT
+HLT
+
+NODE (0,1)
+MOV P:LEFT, P:DOWN
+HLT
+
+NODE (1,0)
+MOV P:UP, P:RIGHT
+HLT
+
+NODE (1,1)
+MOV P:LEFT, OUT
+HLT
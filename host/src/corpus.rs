@@ -0,0 +1,216 @@
+//! Bulk import of TIS-100 solutions behind `zk100 import-corpus`.
+//!
+//! The corpus directory holds save files named `<puzzle>.<slot>.txt` (in any
+//! subdirectory) and a `puzzles.json` spec keyed by puzzle id:
+//!
+//! ```json
+//! { "00150": { "name": "SELF-TEST DIAGNOSTIC", "rows": 2, "cols": 2,
+//!              "damaged": [[0, 1]],
+//!              "inputs":  [{ "col": 0, "values": [1, 2] }],
+//!              "outputs": [{ "col": 1, "values": [1, 2] }] } }
+//! ```
+//!
+//! Each save is converted with `tis100::convert`, assembled, and written to
+//! `<out-dir>/<save stem>/program.asm` next to a `manifest.json`. Every save
+//! ends up imported, skipped (with the unsupported feature) or failed (with
+//! the error); one bad file never stops the run.
+
+use crate::tis100::{self, Layout, Unsupported};
+use crate::{assembler, exec, merkle};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SPEC_FILE: &str = "puzzles.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stream {
+    pub col: usize,
+    pub values: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PuzzleSpec {
+    pub name: String,
+    pub rows: usize,
+    pub cols: usize,
+    #[serde(default)]
+    pub damaged: Vec<(usize, usize)>,
+    pub inputs: Vec<Stream>,
+    pub outputs: Vec<Stream>,
+}
+
+impl PuzzleSpec {
+    fn layout(&self) -> Layout {
+        Layout {
+            rows: self.rows,
+            cols: self.cols,
+            damaged: self.damaged.clone(),
+            input_cols: self.inputs.iter().map(|s| s.col).collect(),
+            output_cols: self.outputs.iter().map(|s| s.col).collect(),
+        }
+    }
+}
+
+/// Written next to every imported program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportManifest {
+    /// Save file, relative to the corpus directory.
+    pub source: String,
+    pub puzzle: String,
+    pub name: String,
+    pub inputs: Vec<u32>,
+    pub expected: Vec<u32>,
+    pub program_root: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Imported { out_dir: PathBuf },
+    Skipped { reason: String },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+}
+
+impl fmt::Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.outcome {
+            Outcome::Imported { out_dir } => write!(f, "imported  {} -> {}", self.path.display(), out_dir.display()),
+            Outcome::Skipped { reason } => write!(f, "skipped   {}: {}", self.path.display(), reason),
+            Outcome::Failed { error } => write!(f, "FAILED    {}: {}", self.path.display(), error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub files: Vec<FileReport>,
+}
+
+impl Summary {
+    pub fn count(&self, pred: fn(&Outcome) -> bool) -> usize {
+        self.files.iter().filter(|f| pred(&f.outcome)).count()
+    }
+
+    pub fn imported(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Imported { .. }))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Skipped { .. }))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Failed { .. }))
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for file in &self.files {
+            writeln!(f, "{}", file)?;
+        }
+        write!(
+            f,
+            "{} saves: {} imported, {} skipped, {} failed",
+            self.files.len(),
+            self.imported(),
+            self.skipped(),
+            self.failed()
+        )
+    }
+}
+
+pub fn load_spec(path: &Path) -> Result<BTreeMap<String, PuzzleSpec>> {
+    let text = fs::read_to_string(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+}
+
+fn stream_values(streams: &[Stream], what: &str) -> Result<Vec<u32>> {
+    let values = streams.first().map_or(&[][..], |s| &s.values);
+    values
+        .iter()
+        .map(|&v| {
+            u32::try_from(v).map_err(|_| Unsupported(format!("negative {} value {}", what, v)).into())
+        })
+        .collect()
+}
+
+fn import_save(
+    text: &str,
+    source: &str,
+    puzzle: &str,
+    spec: &PuzzleSpec,
+    out_dir: &Path,
+) -> Result<ImportManifest> {
+    let asm = tis100::convert(text, &spec.layout())?;
+    let inputs = stream_values(&spec.inputs, "input")?;
+    let expected = stream_values(&spec.outputs, "output")?;
+    let programs = assembler::parse_assembly(&asm).map_err(|e| anyhow!("converted program does not assemble: {}", e))?;
+    assembler::encode_programs(&programs)?;
+    let manifest = ImportManifest {
+        source: source.to_string(),
+        puzzle: puzzle.to_string(),
+        name: spec.name.clone(),
+        inputs,
+        expected,
+        program_root: merkle::program_root_hex(&programs)?,
+    };
+    fs::create_dir_all(out_dir)?;
+    exec::write_atomic(&out_dir.join("program.asm"), format!("# {} ({})\n{}", spec.name, source, asm))?;
+    exec::write_atomic(&out_dir.join("manifest.json"), serde_json::to_string_pretty(&manifest)? + "\n")?;
+    Ok(manifest)
+}
+
+fn collect_saves(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).map_err(|e| anyhow!("cannot read {}: {}", dir.display(), e))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_saves(&path, out)?;
+        } else if path.extension().is_some_and(|e| e == "txt") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Import every save under `dir`. Errors only for an unreadable corpus or spec.
+pub fn import_dir(dir: &Path, spec_path: &Path, out_dir: &Path) -> Result<Summary> {
+    let specs = load_spec(spec_path)?;
+    let mut saves = Vec::new();
+    collect_saves(dir, &mut saves)?;
+    saves.sort();
+
+    let mut summary = Summary::default();
+    for path in saves {
+        let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+        let stem = path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let puzzle = stem.split('.').next().unwrap_or("").to_string();
+        let target = out_dir.join(&stem);
+
+        let outcome = match (fs::read(&path), specs.get(&puzzle)) {
+            (Err(e), _) => Outcome::Failed { error: format!("cannot read: {}", e) },
+            (Ok(_), None) => Outcome::Skipped { reason: format!("no spec for puzzle '{}' in {}", puzzle, SPEC_FILE) },
+            (Ok(bytes), Some(spec)) => match String::from_utf8(bytes) {
+                Err(_) => Outcome::Failed { error: "not UTF-8 text".to_string() },
+                Ok(text) => match import_save(&text, &relative.to_string_lossy(), &puzzle, spec, &target) {
+                    Ok(_) => Outcome::Imported { out_dir: target },
+                    Err(e) => match e.downcast_ref::<Unsupported>() {
+                        Some(Unsupported(reason)) => Outcome::Skipped { reason: reason.clone() },
+                        None => Outcome::Failed { error: e.to_string() },
+                    },
+                },
+            },
+        };
+        summary.files.push(FileReport { path: relative, outcome });
+    }
+    Ok(summary)
+}
@@ -0,0 +1,551 @@
+//! `zk100 serve`: line-delimited JSON-RPC 2.0 over TCP.
+//!
+//! Each line a client sends is one request object and gets exactly one
+//! response line back. Methods map onto the library:
+//!
+//! | method        | params                                           | library call                 |
+//! |---------------|--------------------------------------------------|------------------------------|
+//! | `assemble`    | `source`, `inputs?`, exact `expected?` or `node_expected?`, `abi?`, `sentinel?` | `parse_assembly` + `CairoArgs::to_args` |
+//! | `simulate`    | `source` or `prog_words`, `inputs?`, `sentinel?`, `max_cycles?`, `ignore_asserts?` | `sim::Simulator` with the source's asserts |
+//! | `verify`      | as `simulate`, plus `expected` (values or a pattern) or `node_expected` | `sim::Simulator` + `ExpectedSpec::check` / `node_mismatches` |
+//! | `hash`        | `source` or `prog_words`                         | `merkle::program_root_hex`            |
+//! | `disassemble` | `prog_words`, `grid?`                            | `decode_grid` + `ProgramGrid::to_assembly` |
+//! | `capabilities` | none                                            | `capabilities::capabilities` |
+//!
+//! Proving is deliberately not offered; it stays a spawned `cairo-prove`.
+//!
+//! At most `max_concurrency` requests run at once across all connections, and
+//! each must answer within `timeout`. A request that times out gets an error
+//! response immediately; its worker keeps its concurrency slot until it
+//! actually finishes, so runaway work cannot pile up.
+
+use crate::assembler::{self, Asserts, GridSize, Programs};
+use crate::expected::{ExpectedSpec, Mismatch};
+use crate::grid::ProgramGrid;
+use crate::cairo_abi::{self, CairoArgs, InputSentinel, IoNodes, NodeOutputs};
+use crate::sim::{self, Simulator};
+use crate::{capabilities, merkle};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The method ran and failed (bad assembly, guest panic, ...).
+pub const EXECUTION_ERROR: i64 = -32000;
+pub const TIMEOUT: i64 = -32001;
+pub const BUSY: i64 = -32002;
+
+pub const METHODS: &[&str] = &["assemble", "simulate", "verify", "hash", "disassemble", "capabilities"];
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub max_concurrency: usize,
+    pub timeout: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            max_concurrency: 4,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// One entry of an error's diagnostic list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: String,
+    pub message: String,
+}
+
+/// JSON-RPC error object; `data.diagnostics` lists the whole error chain,
+/// each error of a failed parse on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default)]
+    pub data: RpcErrorData,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcErrorData {
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        let message = message.into();
+        RpcError {
+            code,
+            data: RpcErrorData {
+                diagnostics: vec![Diagnostic {
+                    severity: "error".to_string(),
+                    message: message.clone(),
+                }],
+            },
+            message,
+        }
+    }
+
+    fn from_anyhow(code: i64, err: &anyhow::Error) -> Self {
+        RpcError {
+            code,
+            message: err.to_string(),
+            data: RpcErrorData {
+                diagnostics: err
+                    .chain()
+                    .flat_map(|cause| match cause.downcast_ref::<assembler::AssemblyErrors>() {
+                        Some(errors) => errors.errors.iter().map(|e| e.message.clone()).collect(),
+                        None => vec![cause.to_string()],
+                    })
+                    .map(|message| Diagnostic {
+                        severity: "error".to_string(),
+                        message,
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)?;
+        for diagnostic in self.data.diagnostics.iter().skip(1) {
+            write!(f, "\n  {}: {}", diagnostic.severity, diagnostic.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// A program given either as assembly or as encoded `prog_words`, which
+/// decode into a grid of `grid` (2x2 unless given).
+#[derive(Debug, Default, Deserialize)]
+struct ProgramParams {
+    source: Option<String>,
+    prog_words: Option<Vec<u32>>,
+    #[serde(default)]
+    grid: GridSize,
+}
+
+impl ProgramParams {
+    fn programs(&self) -> Result<Programs> {
+        match (&self.source, &self.prog_words) {
+            (Some(source), None) => assembler::parse_assembly(source),
+            (None, Some(words)) => assembler::decode_grid(words, self.grid),
+            _ => Err(anyhow!("give exactly one of \"source\" or \"prog_words\"")),
+        }
+    }
+
+    /// The programs and, for `source`, its `ASSERT`s unless `ignore` is set.
+    fn simulator(&self, inputs: &[u32], io: IoNodes, ignore: bool) -> Result<Simulator> {
+        let (programs, asserts) = match (&self.source, ignore) {
+            (Some(source), false) if self.prog_words.is_none() => {
+                let parsed = assembler::parse(source)?;
+                (parsed.grid.programs, parsed.asserts)
+            }
+            _ => (self.programs()?, Asserts::new()),
+        };
+        Ok(Simulator::with_io(&programs, inputs, io)?.with_asserts(asserts))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AssembleParams {
+    source: String,
+    #[serde(default)]
+    inputs: Vec<u32>,
+    #[serde(default)]
+    expected: ExpectedSpec,
+    #[serde(default, with = "cairo_abi::node_keys")]
+    node_expected: NodeOutputs,
+    #[serde(default = "legacy_abi")]
+    abi: u32,
+    #[serde(default)]
+    io: IoNodes,
+    #[serde(default)]
+    sentinel: Option<InputSentinel>,
+}
+
+fn legacy_abi() -> u32 {
+    cairo_abi::LEGACY_ABI
+}
+
+#[derive(Debug, Deserialize)]
+struct SimulateParams {
+    #[serde(flatten)]
+    program: ProgramParams,
+    #[serde(default)]
+    inputs: Vec<u32>,
+    max_cycles: Option<u64>,
+    #[serde(default)]
+    io: IoNodes,
+    #[serde(default)]
+    sentinel: Option<InputSentinel>,
+    #[serde(default)]
+    ignore_asserts: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct VerifyParams {
+    #[serde(flatten)]
+    program: ProgramParams,
+    #[serde(default)]
+    inputs: Vec<u32>,
+    #[serde(default)]
+    expected: ExpectedSpec,
+    /// When given, outputs are checked per node instead of against `expected`.
+    #[serde(default, with = "cairo_abi::node_keys")]
+    node_expected: NodeOutputs,
+    max_cycles: Option<u64>,
+    #[serde(default)]
+    ignore_asserts: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DisassembleParams {
+    prog_words: Vec<u32>,
+    #[serde(default)]
+    grid: GridSize,
+}
+
+fn params<T: for<'de> Deserialize<'de>>(value: Value) -> std::result::Result<T, RpcError> {
+    serde_json::from_value(value).map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid params: {}", e)))
+}
+
+fn run<T: Serialize>(f: impl FnOnce() -> Result<T>) -> std::result::Result<Value, RpcError> {
+    let value = f().map_err(|e| RpcError::from_anyhow(EXECUTION_ERROR, &e))?;
+    serde_json::to_value(value).map_err(|e| RpcError::new(EXECUTION_ERROR, e.to_string()))
+}
+
+/// Run one method synchronously, without the timeout or concurrency limit.
+pub fn dispatch(method: &str, params_value: Value) -> std::result::Result<Value, RpcError> {
+    match method {
+        "assemble" => {
+            let p: AssembleParams = params(params_value)?;
+            run(|| {
+                let grid = assembler::parse(&p.source)?.grid;
+                let prog_words = assembler::encode_programs(&grid.programs)?;
+                let args = CairoArgs {
+                    inputs: p.inputs,
+                    expected: p.expected.require_exact()?,
+                    node_expected: p.node_expected,
+                    prog_words: prog_words.clone(),
+                    io: p.io,
+                    sentinel: p.sentinel,
+                    grid: grid.size(),
+                }
+                .to_args(p.abi)?;
+                Ok(json!({ "prog_words": prog_words, "args": args, "grid": grid.size() }))
+            })
+        }
+        "simulate" => {
+            let p: SimulateParams = params(params_value)?;
+            run(|| {
+                p.io.validate()?;
+                let max_cycles = p.max_cycles.unwrap_or(sim::DEFAULT_MAX_CYCLES);
+                p.program.simulator(&p.inputs, p.io, p.ignore_asserts)?.with_sentinel(p.sentinel).run_to_result(max_cycles)
+            })
+        }
+        "verify" => {
+            let p: VerifyParams = params(params_value)?;
+            run(|| {
+                let result = p
+                    .program
+                    .simulator(&p.inputs, IoNodes::default(), p.ignore_asserts)?
+                    .run_to_result(p.max_cycles.unwrap_or(sim::DEFAULT_MAX_CYCLES))?;
+                if !p.node_expected.is_empty() {
+                    let mismatches = result.node_mismatches(&p.node_expected);
+                    return Ok(json!({
+                        "passed": mismatches.is_empty(),
+                        "failed_nodes": mismatches.iter().map(|(r, c)| format!("{},{}", r, c)).collect::<Vec<_>>(),
+                        "node_outputs": serde_json::to_value(&result)?["node_outputs"],
+                        "cycles": result.cycles,
+                        "termination": result.termination,
+                    }));
+                }
+                let failures: Vec<String> = p.expected.check(&result.outputs).iter().map(Mismatch::to_string).collect();
+                Ok(json!({
+                    "passed": p.expected.matches(&result.outputs),
+                    "outputs": result.outputs,
+                    "expected": p.expected,
+                    "failures": failures,
+                    "cycles": result.cycles,
+                    "termination": result.termination,
+                }))
+            })
+        }
+        "hash" => {
+            let p: ProgramParams = params(params_value)?;
+            run(|| {
+                Ok(json!({ "program_root": merkle::program_root_hex(&p.programs()?)? }))
+            })
+        }
+        "disassemble" => {
+            let p: DisassembleParams = params(params_value)?;
+            run(|| {
+                let grid = ProgramGrid::new(assembler::decode_grid(&p.prog_words, p.grid)?);
+                Ok(json!({ "assembly": grid.to_assembly() }))
+            })
+        }
+        "capabilities" => run(|| Ok(capabilities::capabilities())),
+        other => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", other))),
+    }
+}
+
+/// Counting semaphore bounding the number of in-flight requests.
+struct Limiter {
+    active: Mutex<usize>,
+    freed: Condvar,
+    max: usize,
+}
+
+struct Permit(Arc<Limiter>);
+
+impl Limiter {
+    fn acquire(self: &Arc<Self>, deadline: Instant) -> Option<Permit> {
+        let mut active = self.active.lock().unwrap();
+        while *active >= self.max {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            active = self.freed.wait_timeout(active, deadline - now).unwrap().0;
+        }
+        *active += 1;
+        Some(Permit(self.clone()))
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *self.0.active.lock().unwrap() -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: Option<String>,
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+fn response(id: Value, outcome: std::result::Result<Value, RpcError>) -> Value {
+    match outcome {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
+
+/// The daemon: shared limits plus the request handler.
+pub struct Server {
+    config: ServerConfig,
+    limiter: Arc<Limiter>,
+}
+
+impl Server {
+    pub fn new(config: ServerConfig) -> Self {
+        let limiter = Arc::new(Limiter {
+            active: Mutex::new(0),
+            freed: Condvar::new(),
+            max: config.max_concurrency.max(1),
+        });
+        Server { config, limiter }
+    }
+
+    /// Answer one request line with one response object.
+    pub fn handle_line(&self, line: &str) -> Value {
+        let request: Request = match serde_json::from_str::<Value>(line) {
+            Err(e) => return response(Value::Null, Err(RpcError::new(PARSE_ERROR, format!("parse error: {}", e)))),
+            Ok(value) => match serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(e) => {
+                    return response(
+                        Value::Null,
+                        Err(RpcError::new(INVALID_REQUEST, format!("invalid request: {}", e))),
+                    );
+                }
+            },
+        };
+        if request.jsonrpc.as_deref() != Some("2.0") {
+            return response(request.id, Err(RpcError::new(INVALID_REQUEST, "\"jsonrpc\" must be \"2.0\"")));
+        }
+        let outcome = self.call_with_limits(request.method, request.params);
+        response(request.id, outcome)
+    }
+
+    fn call_with_limits(&self, method: String, params: Value) -> std::result::Result<Value, RpcError> {
+        let timeout = self.config.timeout;
+        let deadline = Instant::now() + timeout;
+        let permit = self.limiter.acquire(deadline).ok_or_else(|| {
+            RpcError::new(
+                BUSY,
+                format!("server busy: {} requests already running", self.limiter.max),
+            )
+        })?;
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _permit = permit;
+            let _ = tx.send(dispatch(&method, params));
+        });
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(outcome) => outcome,
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(RpcError::new(
+                TIMEOUT,
+                format!("request timed out after {} ms", timeout.as_millis()),
+            )),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(RpcError::new(EXECUTION_ERROR, "request handler panicked"))
+            }
+        }
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> Result<()> {
+        let reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = self.handle_line(&line);
+            writeln!(writer, "{}", reply)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Accept connections forever, one thread per connection.
+    pub fn serve(self, listener: TcpListener) -> Result<()> {
+        let server = Arc::new(self);
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("accept failed: {}", e);
+                    continue;
+                }
+            };
+            let server = server.clone();
+            thread::spawn(move || {
+                if let Err(e) = server.handle_connection(stream) {
+                    eprintln!("connection error: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Send one request to a running daemon and return its `result`.
+///
+/// A JSON-RPC error response comes back as an `RpcError` inside the
+/// `anyhow::Error`, so callers can downcast to inspect the code.
+pub fn call(addr: impl ToSocketAddrs, method: &str, params: Value) -> Result<Value> {
+    let stream = TcpStream::connect(addr)?;
+    let mut writer = stream.try_clone()?;
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    writeln!(writer, "{}", request)?;
+    writer.flush()?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let mut reply: Value = serde_json::from_str(&line)
+        .map_err(|e| anyhow!("malformed response from daemon: {}", e))?;
+    if let Some(error) = reply.get_mut("error") {
+        let error: RpcError = serde_json::from_value(error.take())?;
+        return Err(error.into());
+    }
+    reply
+        .get_mut("result")
+        .map(Value::take)
+        .ok_or_else(|| anyhow!("response has neither \"result\" nor \"error\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(max_concurrency: usize, timeout: Duration) -> Server {
+        Server::new(ServerConfig { max_concurrency, timeout })
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let s = server(1, Duration::from_secs(5));
+        assert_eq!(s.handle_line("{not json")["error"]["code"], PARSE_ERROR);
+        assert_eq!(s.handle_line(r#"{"id": 1}"#)["error"]["code"], INVALID_REQUEST);
+        let reply = s.handle_line(r#"{"jsonrpc": "2.0", "id": 7, "method": "prove"}"#);
+        assert_eq!(reply["id"], 7);
+        assert_eq!(reply["error"]["code"], METHOD_NOT_FOUND);
+        let reply = s.handle_line(r#"{"jsonrpc": "2.0", "id": 8, "method": "hash", "params": {"source": 3}}"#);
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_execution_error_carries_diagnostics() {
+        let err = dispatch("hash", json!({ "source": "NODE (0,0)\nFOO 1\n" })).unwrap_err();
+        assert_eq!(err.code, EXECUTION_ERROR);
+        assert_eq!(err.data.diagnostics[0].severity, "error");
+        assert!(err.data.diagnostics[0].message.contains("Unknown operation: FOO"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_every_assembly_error_is_a_diagnostic() {
+        let err = dispatch("hash", json!({ "source": "NODE (0,0)\nFOO 1\nNODE (1,1)\nJMP nowhere\n" })).unwrap_err();
+        let messages: Vec<&str> = err.data.diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert!(messages[0].starts_with("line 2, col 1, node (0,0): Unknown operation: FOO"), "{:?}", messages);
+        assert!(messages[1].starts_with("line 4, col 5, node (1,1): undefined label 'nowhere'"), "{:?}", messages);
+    }
+
+    #[test]
+    fn test_verify_per_node() {
+        let source = "NODE (0,1)\nMOV 0, OUT\nMOV P:DOWN, ACC\nNODE (1,1)\nMOV 5, OUT\nMOV 10, OUT\nMOV P:UP, ACC\n";
+        let pass = dispatch("verify", json!({ "source": source, "node_expected": { "1,1": [5, 10], "0,1": [0] } }))
+            .unwrap();
+        assert_eq!(pass["passed"], true);
+        assert_eq!(pass["node_outputs"]["0,1"], json!([0]));
+        let fail = dispatch("verify", json!({ "source": source, "node_expected": { "1,1": [5, 10] } })).unwrap();
+        assert_eq!(fail["passed"], false);
+        assert_eq!(fail["failed_nodes"], json!(["0,1"]));
+        let err = dispatch("verify", json!({ "source": source, "node_expected": { "2,0": [] } })).unwrap_err();
+        assert_eq!(err.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_capabilities_method() {
+        let caps = dispatch("capabilities", Value::Null).unwrap();
+        assert_eq!(caps, serde_json::to_value(capabilities::capabilities()).unwrap());
+    }
+
+    #[test]
+    fn test_busy_when_limit_reached() {
+        let s = server(1, Duration::from_millis(20));
+        let _held = s.limiter.acquire(Instant::now() + Duration::from_secs(1)).unwrap();
+        let reply = s.handle_line(r#"{"jsonrpc": "2.0", "id": 1, "method": "hash", "params": {"source": ""}}"#);
+        assert_eq!(reply["error"]["code"], BUSY);
+    }
+}
@@ -0,0 +1,103 @@
+//! Mnemonic dialects: other names for the canonical opcodes, so programs
+//! emitted by tools that spell `JZ` as `JEZ` assemble without being patched.
+//!
+//! `BUILTIN_ALIASES` is always active; a project adds its own in an
+//! `[aliases]` table of `zk100.toml` (`JEZ = "JZ"`). Aliases only change what
+//! the assembler accepts: instructions carry an `Op`, so everything that
+//! prints a program (`convert --to asm`, `disassemble`, `bisect-encoding`)
+//! writes the canonical names and a round trip normalizes the dialect.
+
+use crate::config;
+use crate::instruction::Op;
+use anyhow::{Result, anyhow};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Spellings other toolchains use, accepted everywhere.
+pub const BUILTIN_ALIASES: &[(&str, Op)] = &[("HALT", Op::Hlt), ("JEZ", Op::Jz), ("NOOP", Op::Nop)];
+
+/// Table of `zk100.toml` the project's aliases live in.
+pub const ALIASES_TABLE: &str = "aliases";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialect {
+    /// Upper-case alias to the opcode it stands for.
+    aliases: BTreeMap<String, Op>,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect { aliases: BUILTIN_ALIASES.iter().map(|&(alias, op)| (alias.to_string(), op)).collect() }
+    }
+}
+
+impl Dialect {
+    /// The built-in aliases plus `extra` (alias, canonical mnemonic) pairs.
+    /// An alias may not spell a real mnemonic or repeat another alias.
+    pub fn new<'a>(extra: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Self> {
+        let mut dialect = Dialect::default();
+        for (alias, target) in extra {
+            let name = alias.to_uppercase();
+            if let Ok(op) = Op::from_str(&name) {
+                return Err(anyhow!("alias '{}' collides with the {} instruction", alias, op));
+            }
+            if dialect.aliases.contains_key(&name) {
+                return Err(anyhow!("alias '{}' is defined more than once", alias));
+            }
+            let op = Op::from_str(target)
+                .map_err(|_| anyhow!("alias '{}' names '{}', which is not an instruction", alias, target))?;
+            dialect.aliases.insert(name, op);
+        }
+        Ok(dialect)
+    }
+
+    /// The dialect of the `[aliases]` table in `path`; a missing file or
+    /// table gives the built-in one.
+    pub fn load(path: &Path) -> Result<Self> {
+        let table = config::read_table(path, ALIASES_TABLE)?;
+        Dialect::new(table.iter().map(|(alias, target)| (alias.as_str(), target.as_str())))
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// The opcode `mnemonic` names, canonically or through an alias.
+    pub fn op(&self, mnemonic: &str) -> Result<Op> {
+        match self.aliases.get(&mnemonic.to_uppercase()) {
+            Some(&op) => Ok(op),
+            None => Ok(Op::from_str(mnemonic)?),
+        }
+    }
+
+    /// Every active alias and the canonical mnemonic it stands for.
+    pub fn table(&self) -> BTreeMap<String, &'static str> {
+        self.aliases.iter().map(|(alias, op)| (alias.clone(), op.mnemonic())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_aliases() {
+        let dialect = Dialect::default();
+        assert_eq!(dialect.op("jez").unwrap(), Op::Jz);
+        assert_eq!(dialect.op("HALT").unwrap(), Op::Hlt);
+        assert_eq!(dialect.op("NOOP").unwrap(), Op::Nop);
+        assert_eq!(dialect.op("mov").unwrap(), Op::Mov);
+        assert!(dialect.op("FROB").is_err());
+        assert_eq!(dialect.table()["JEZ"], "JZ");
+    }
+
+    #[test]
+    fn test_collisions_are_rejected() {
+        let dialect = Dialect::new([("jnez", "jnz")]).unwrap();
+        assert_eq!(dialect.op("JNEZ").unwrap(), Op::Jnz);
+
+        let err = |extra: &[(&str, &str)]| Dialect::new(extra.iter().copied()).unwrap_err().to_string();
+        assert_eq!(err(&[("MOV", "NOP")]), "alias 'MOV' collides with the MOV instruction");
+        assert_eq!(err(&[("halt", "HLT")]), "alias 'halt' is defined more than once");
+        assert_eq!(err(&[("GO", "JMP"), ("go", "JMP")]), "alias 'go' is defined more than once");
+        assert_eq!(err(&[("GO", "JUMP")]), "alias 'GO' names 'JUMP', which is not an instruction");
+    }
+}
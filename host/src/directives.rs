@@ -0,0 +1,376 @@
+//! Source directives the parser applies to a node's lines before it collects
+//! labels, so pcs count the code they produce.
+//!
+//! `.rept N` ... `.endr` repeats the lines between them `N` times, for the
+//! unrolled loops tiny nodes need. `N` is a literal, a constant or a
+//! constant expression, from 0 to `MAX_REPT`; repeats nest up to
+//! `MAX_REPT_DEPTH` deep. A label inside a repeat would be defined once per
+//! copy, so it is an error, as is a `NODE` header: a repeat stays in its
+//! block. Repeated lines keep their own line numbers, so diagnostics point
+//! at the source.
+//!
+//! `.if COND` ... `.else` ... `.endif` keeps the lines of one branch and
+//! drops the other, for variants of one solution (`.if DEBUG`). `COND` is
+//! an expression over the constants, from `DEFINE`s or `--define`; it holds
+//! when it is not zero. A dropped branch is gone before labels are
+//! collected, so pcs count the selected code only, and its `DEFINE`s define
+//! nothing. Conditions nest; an `.if` and its `.endif` stay in one `NODE`
+//! block and on one side of any `.rept`.
+//!
+//! `.pad N` is a setting of the whole file, like `GRID`: every node with code
+//! is padded with `NOP`s to `N` instructions once it is parsed
+//! (`assembler::resolve_pad`). Directives match in any case.
+
+use crate::assembler::{Constants, Line, classify, constant_value, operand_text};
+use crate::expr;
+use crate::lexer::{self, Token};
+use anyhow::{Result, anyhow};
+
+/// The most times one `.rept` repeats its lines.
+pub const MAX_REPT: u32 = 256;
+/// How deep `.rept` blocks nest.
+pub const MAX_REPT_DEPTH: usize = 8;
+/// The most lines repeats may expand a file to.
+pub const MAX_EXPANDED_LINES: usize = 1 << 16;
+
+/// Every directive, as written in errors.
+const DIRECTIVES: [&str; 6] = [".rept", ".endr", ".if", ".else", ".endif", ".pad"];
+
+/// Why `name` is no directive, if it is not.
+pub(crate) fn unknown_directive(name: &Token) -> Option<String> {
+    if DIRECTIVES.iter().any(|d| d.eq_ignore_ascii_case(name.text)) {
+        return None;
+    }
+    Some(format!("unknown directive '{}'; the directives are {}", name.text, DIRECTIVES.join(", ")))
+}
+
+/// The lines of `code`, 1-based line number first, with every directive
+/// applied and the directive lines themselves dropped.
+pub(crate) fn expand<'a>(code: &'a str, constants: &Constants) -> Result<Vec<(usize, &'a str)>> {
+    let lines: Vec<(usize, &str)> = code.lines().enumerate().map(|(idx, line)| (idx + 1, line)).collect();
+    let mut expander = Expander { lines: &lines, next: 0, constants, conditions: Conditions::default() };
+    let mut out = Vec::new();
+    expander.block(None, 0, &mut out)?;
+    Ok(out)
+}
+
+struct Expander<'a, 'c> {
+    lines: &'c [(usize, &'a str)],
+    next: usize,
+    constants: &'c Constants,
+    conditions: Conditions,
+}
+
+impl<'a> Expander<'a, '_> {
+    /// Copies the selected lines to `out` until the `.endr` closing the
+    /// `.rept` on line `open`, or the end of the file when `open` is `None`.
+    fn block(&mut self, open: Option<usize>, depth: usize, out: &mut Vec<(usize, &'a str)>) -> Result<()> {
+        // The `.if` blocks open around the `.rept`, which it must not close.
+        let outside = self.conditions.depth();
+        while let Some(&(line_no, text)) = self.lines.get(self.next) {
+            self.next += 1;
+            let tokens = lexer::lex_line(text, line_no);
+            let line = classify(&tokens);
+            if let (Some(rept), Some(name)) = (open, self.conditions.closes(&line)) {
+                if outside > 0 && self.conditions.depth() == outside {
+                    return Err(anyhow!(
+                        "line {}: {} for the .if on line {} outside the .rept on line {}; close the .rept with .endr first",
+                        line_no,
+                        name,
+                        self.conditions.innermost().unwrap_or_default(),
+                        rept
+                    ));
+                }
+            }
+            if self.conditions.apply(&line, self.constants).map_err(|e| anyhow!("line {}: {}", line_no, e))? {
+                continue;
+            }
+            match line {
+                Line::Directive(name, args) if name.text.eq_ignore_ascii_case(".rept") => {
+                    if depth == MAX_REPT_DEPTH {
+                        return Err(anyhow!("line {}: .rept nested more than {} deep", line_no, MAX_REPT_DEPTH));
+                    }
+                    // A `.rept` in a dropped branch still needs its `.endr`,
+                    // but its count may use constants that variant lacks.
+                    let count = match self.conditions.selected() {
+                        true => self.count(&args).map_err(|e| anyhow!("line {}: {}", line_no, e))?,
+                        false => 0,
+                    };
+                    let mut body = Vec::new();
+                    self.block(Some(line_no), depth + 1, &mut body)?;
+                    for _ in 0..count {
+                        out.extend_from_slice(&body);
+                        if out.len() > MAX_EXPANDED_LINES {
+                            return Err(anyhow!(
+                                "line {}: .rept {} expands the source past {} lines",
+                                line_no,
+                                count,
+                                MAX_EXPANDED_LINES
+                            ));
+                        }
+                    }
+                }
+                Line::Directive(name, args) if name.text.eq_ignore_ascii_case(".endr") => {
+                    if open.is_none() {
+                        return Err(anyhow!("line {}: .endr without a .rept", line_no));
+                    }
+                    if !args.is_empty() {
+                        return Err(anyhow!("line {}: .endr takes no operands", line_no));
+                    }
+                    if self.conditions.depth() > outside {
+                        return Err(anyhow!(
+                            "line {}: .endr inside the .if on line {}; close it with .endif first",
+                            line_no,
+                            self.conditions.innermost().unwrap_or_default()
+                        ));
+                    }
+                    return Ok(());
+                }
+                Line::Label(names, _) if open.is_some() => {
+                    return Err(anyhow!(
+                        "line {}: label '{}' inside the .rept on line {} would be defined once per copy; move it before the .rept",
+                        line_no,
+                        names[0].text,
+                        open.unwrap_or_default()
+                    ));
+                }
+                Line::Node(..) if open.is_some() => {
+                    return Err(anyhow!(
+                        "line {}: NODE inside the .rept on line {}; close it with .endr first",
+                        line_no,
+                        open.unwrap_or_default()
+                    ));
+                }
+                Line::Node(..) if self.conditions.depth() > 0 => {
+                    return Err(anyhow!(
+                        "line {}: NODE inside the .if on line {}; close it with .endif first",
+                        line_no,
+                        self.conditions.innermost().unwrap_or_default()
+                    ));
+                }
+                _ if self.conditions.selected() => out.push((line_no, text)),
+                _ => {}
+            }
+        }
+        if self.conditions.depth() > outside {
+            return Err(unclosed(self.conditions.innermost().unwrap_or_default()));
+        }
+        match open {
+            Some(line_no) => Err(anyhow!("line {}: .rept has no .endr", line_no)),
+            None => Ok(()),
+        }
+    }
+
+    /// How many times a `.rept` with operand `tokens` repeats.
+    fn count(&self, tokens: &[Token]) -> Result<u32> {
+        if tokens.is_empty() {
+            return Err(anyhow!("expected .rept <count>"));
+        }
+        let text = operand_text(tokens);
+        let count = expr::eval_i64(&text, &|word| constant_value(word, self.constants))?;
+        match u32::try_from(count) {
+            Ok(count) if count <= MAX_REPT => Ok(count),
+            _ => Err(anyhow!(".rept repeats 0 to {} times, not {}", MAX_REPT, count)),
+        }
+    }
+}
+
+/// The `.pad` line of `code` outside any dropped branch, and the node length
+/// it pads to. There may be one, before the first `NODE`.
+pub(crate) fn declared_pad(code: &str, constants: &Constants) -> Result<Option<(usize, usize)>> {
+    let mut declared: Option<(usize, usize)> = None;
+    let mut first_node = None;
+    let mut conditions = Conditions::default();
+    for (idx, text) in code.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = classify(&lexer::lex_line(text, line_no));
+        let at = |e: anyhow::Error| anyhow!("line {}: {}", line_no, e);
+        if conditions.apply(&line, constants).map_err(at)? || !conditions.selected() {
+            continue;
+        }
+        match line {
+            Line::Node(..) => {
+                first_node.get_or_insert(line_no);
+            }
+            Line::Directive(name, args) if name.text.eq_ignore_ascii_case(".pad") => {
+                if let Some((first, _)) = declared {
+                    return Err(anyhow!("line {}: .pad is declared twice (first on line {})", line_no, first));
+                }
+                if let Some(node) = first_node {
+                    return Err(anyhow!("line {}: .pad must come before the first NODE (line {})", line_no, node));
+                }
+                if args.is_empty() {
+                    return Err(anyhow!("line {}: expected .pad <instructions>", line_no));
+                }
+                let target = expr::eval_i64(&operand_text(&args), &|word| constant_value(word, constants)).map_err(at)?;
+                let target = usize::try_from(target).map_err(|_| anyhow!("line {}: .pad {} is not a node length", line_no, target))?;
+                declared = Some((line_no, target));
+            }
+            _ => {}
+        }
+    }
+    Ok(declared)
+}
+
+/// The `.if` blocks open at a point in the source, innermost last.
+#[derive(Debug, Default)]
+pub(crate) struct Conditions {
+    open: Vec<Branch>,
+}
+
+#[derive(Debug)]
+struct Branch {
+    /// Line of the `.if`.
+    line: usize,
+    /// Line of its `.else`, once past it.
+    other: Option<usize>,
+    /// Whether the condition held.
+    held: bool,
+    /// Whether the lines around the `.if` are selected.
+    outer: bool,
+}
+
+impl Conditions {
+    /// Whether the lines here are kept.
+    pub(crate) fn selected(&self) -> bool {
+        self.open.last().is_none_or(|b| b.outer && b.held == b.other.is_none())
+    }
+
+    /// How many `.if` blocks are open.
+    pub(crate) fn depth(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Line of the innermost open `.if`.
+    pub(crate) fn innermost(&self) -> Option<usize> {
+        self.open.last().map(|b| b.line)
+    }
+
+    /// The directive `line` is, if it is `.else` or `.endif`.
+    fn closes(&self, line: &Line) -> Option<&'static str> {
+        match line {
+            Line::Directive(name, _) if name.text.eq_ignore_ascii_case(".else") => Some(".else"),
+            Line::Directive(name, _) if name.text.eq_ignore_ascii_case(".endif") => Some(".endif"),
+            _ => None,
+        }
+    }
+
+    /// Steps past `line` if it is `.if`, `.else` or `.endif`, returning
+    /// whether it was. A condition inside a dropped branch is not evaluated.
+    pub(crate) fn apply(&mut self, line: &Line, constants: &Constants) -> Result<bool> {
+        let Line::Directive(name, args) = line else {
+            return Ok(false);
+        };
+        let line_no = name.line;
+        if name.text.eq_ignore_ascii_case(".if") {
+            let outer = self.selected();
+            let held = outer && condition(args, constants)?;
+            self.open.push(Branch { line: line_no, other: None, held, outer });
+            return Ok(true);
+        }
+        let Some(closes) = self.closes(line) else {
+            return Ok(false);
+        };
+        if !args.is_empty() {
+            return Err(anyhow!("{} takes no operands", closes));
+        }
+        let Some(branch) = self.open.last_mut() else {
+            return Err(anyhow!("{} without an .if", closes));
+        };
+        match (closes, branch.other) {
+            (".else", Some(first)) => {
+                return Err(anyhow!("second .else for the .if on line {}, after the one on line {}", branch.line, first));
+            }
+            (".else", None) => branch.other = Some(line_no),
+            _ => {
+                self.open.pop();
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// The error for the `.if` on line `line_no` the source leaves open.
+pub(crate) fn unclosed(line_no: usize) -> anyhow::Error {
+    anyhow!("line {}: .if has no .endif", line_no)
+}
+
+/// Whether an `.if` with operand `tokens` holds.
+fn condition(tokens: &[Token], constants: &Constants) -> Result<bool> {
+    if tokens.is_empty() {
+        return Err(anyhow!("expected .if <condition>"));
+    }
+    let value = expr::eval_i64(&operand_text(tokens), &|word| constant_value(word, constants))?;
+    Ok(value != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(code: &str) -> Vec<usize> {
+        expand(code, &Constants::from([("N".to_string(), 2)])).unwrap().into_iter().map(|(line, _)| line).collect()
+    }
+
+    #[test]
+    fn test_rept() {
+        assert_eq!(lines("NODE (0,0)\n.rept 3\nADD 1\n.endr\nNOP\n"), [1, 3, 3, 3, 5]);
+        assert_eq!(lines(".REPT N*2\nA\n.rept N\nB\n.endr\n.Endr\n"), [2, 4, 4, 2, 4, 4, 2, 4, 4, 2, 4, 4]);
+        assert_eq!(lines(".rept 0\nA\n.endr\n"), [] as [usize; 0]);
+
+        let err = |code: &str| expand(code, &Constants::new()).unwrap_err().to_string();
+        assert_eq!(err("NOP\n.rept 2\nNOP\n"), "line 2: .rept has no .endr");
+        assert_eq!(err(".endr\n"), "line 1: .endr without a .rept");
+        assert_eq!(
+            err(".rept 2\nloop: ADD 1\n.endr\n"),
+            "line 2: label 'loop' inside the .rept on line 1 would be defined once per copy; move it before the .rept"
+        );
+        assert_eq!(err(".rept 2\nNODE (0,0)\n.endr\n"), "line 2: NODE inside the .rept on line 1; close it with .endr first");
+        assert_eq!(err(".rept\n.endr\n"), "line 1: expected .rept <count>");
+        assert_eq!(err(".rept 1-2\n.endr\n"), "line 1: .rept repeats 0 to 256 times, not -1");
+        assert!(err(".rept M\n.endr\n").contains("undefined constant 'M'"));
+        let deep = ".rept 1\n".repeat(MAX_REPT_DEPTH + 1) + &".endr\n".repeat(MAX_REPT_DEPTH + 1);
+        assert_eq!(err(&deep), "line 9: .rept nested more than 8 deep");
+        assert!(err(".rept 256\n.rept 256\nNOP\nNOP\n.endr\n.endr\n").ends_with("expands the source past 65536 lines"));
+    }
+
+    #[test]
+    fn test_declared_pad() {
+        let pad = |code: &str| declared_pad(code, &Constants::from([("N".to_string(), 8)]));
+        assert_eq!(pad("GRID 2 2\n.PAD N*2\nNODE (0,0)\n").unwrap(), Some((2, 16)));
+        assert_eq!(pad(".if 0\n.pad 4\n.else\n.pad 6\n.endif\n").unwrap(), Some((4, 6)));
+        assert_eq!(pad("NODE (0,0)\nNOP\n").unwrap(), None);
+
+        let err = |code: &str| pad(code).unwrap_err().to_string();
+        assert_eq!(err(".pad 4\n.pad 4\n"), "line 2: .pad is declared twice (first on line 1)");
+        assert_eq!(err("NODE (0,0)\n.pad 4\n"), "line 2: .pad must come before the first NODE (line 1)");
+        assert_eq!(err(".pad\n"), "line 1: expected .pad <instructions>");
+        assert_eq!(err(".pad 1-2\n"), "line 1: .pad -1 is not a node length");
+    }
+
+    #[test]
+    fn test_if() {
+        assert_eq!(lines(".if N\nA\n.else\nB\n.endif\nC\n"), [2, 6]);
+        assert_eq!(lines(".IF N-2\nA\n.Else\nB\n.ENDIF\n"), [4]);
+        assert_eq!(lines(".if 1\n.if 0\nA\n.else\nB\n.endif\n.else\n.if 1\nC\n.endif\n.endif\n"), [5]);
+        // A dropped branch is not evaluated, repeats in it included.
+        assert_eq!(lines(".if 0\n.if MISSING\n.rept MISSING\nA\n.endr\n.endif\n.endif\nB\n"), [8]);
+        assert_eq!(lines(".rept N\n.if 1\nA\n.endif\n.endr\n"), [3, 3]);
+
+        let err = |code: &str| expand(code, &Constants::new()).unwrap_err().to_string();
+        assert_eq!(err("A\n.if 1\n.if 0\n.endif\n"), "line 2: .if has no .endif");
+        assert_eq!(err(".if 1\n.else\n.if 0\n"), "line 3: .if has no .endif");
+        assert_eq!(err(".else\n"), "line 1: .else without an .if");
+        assert_eq!(err(".if 1\n.endif\n.endif\n"), "line 3: .endif without an .if");
+        assert_eq!(err(".if 1\n.else\n.else\n.endif\n"), "line 3: second .else for the .if on line 1, after the one on line 2");
+        assert_eq!(err(".if\n.endif\n"), "line 1: expected .if <condition>");
+        assert_eq!(err(".if 1\n.endif 1\n"), "line 2: .endif takes no operands");
+        assert_eq!(err(".if 1\nNODE (0,0)\n.endif\n"), "line 2: NODE inside the .if on line 1; close it with .endif first");
+        assert_eq!(err(".rept 2\n.if 1\n.endr\n.endif\n"), "line 3: .endr inside the .if on line 2; close it with .endif first");
+        assert_eq!(
+            err(".if 1\n.rept 2\n.endif\n.endr\n"),
+            "line 3: .endif for the .if on line 1 outside the .rept on line 2; close the .rept with .endr first"
+        );
+        assert_eq!(err(".rept 2\n.if 1\nA\n"), "line 2: .if has no .endif");
+    }
+}
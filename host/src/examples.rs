@@ -0,0 +1,143 @@
+//! Example programs embedded in the binary, behind `zk100 example`.
+//!
+//! Each example carries the cases it is known to pass. `Example::run`
+//! assembles, hashes and simulates it against every case, which is also how
+//! the test suite keeps the examples from rotting.
+
+use crate::sim::{self, DEFAULT_MAX_CYCLES};
+use crate::{assembler, cairo_abi, exec, merkle};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Case {
+    pub inputs: &'static [u32],
+    pub expected: &'static [u32],
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Example {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub source: &'static str,
+    pub cases: &'static [Case],
+}
+
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        name: "passthrough",
+        summary: "copy every input to the output",
+        source: include_str!("examples/passthrough.asm"),
+        cases: &[
+            Case { inputs: &[1, 2, 3], expected: &[1, 2, 3] },
+            Case { inputs: &[42], expected: &[42] },
+        ],
+    },
+    Example {
+        name: "adder",
+        summary: "sum inputs in pairs",
+        source: include_str!("examples/adder.asm"),
+        cases: &[
+            Case { inputs: &[1, 2, 3, 4], expected: &[3, 7] },
+            Case { inputs: &[10, 20], expected: &[30] },
+        ],
+    },
+    Example {
+        name: "doubler",
+        summary: "double each input in a middle node",
+        source: include_str!("examples/doubler.asm"),
+        cases: &[Case { inputs: &[1, 2, 3], expected: &[2, 4, 6] }],
+    },
+    Example {
+        name: "ping_pong",
+        summary: "bounce each input between two nodes, adding one",
+        source: include_str!("examples/ping_pong.asm"),
+        cases: &[Case { inputs: &[1, 5, 9], expected: &[2, 6, 10] }],
+    },
+    Example {
+        name: "sort_pair",
+        summary: "compare-exchange element of a sorting network",
+        source: include_str!("examples/sort_pair.asm"),
+        cases: &[
+            Case { inputs: &[3, 1, 2, 5], expected: &[1, 3, 2, 5] },
+            Case { inputs: &[7, 7], expected: &[7, 7] },
+        ],
+    },
+];
+
+pub fn find(name: &str) -> Result<&'static Example> {
+    EXAMPLES.iter().find(|e| e.name == name).ok_or_else(|| {
+        let names: Vec<&str> = EXAMPLES.iter().map(|e| e.name).collect();
+        anyhow!("no example named '{}' (available: {})", name, names.join(", "))
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub case: Case,
+    pub outputs: Vec<u32>,
+    pub cycles: u64,
+    pub passed: bool,
+}
+
+/// Outcome of running an example through the whole host pipeline.
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub program_root: String,
+    pub words: usize,
+    pub cases: Vec<CaseResult>,
+}
+
+impl Run {
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(|c| c.passed)
+    }
+}
+
+impl Example {
+    /// Assemble, hash, generate args for and simulate every case.
+    pub fn run(&self) -> Result<Run> {
+        let programs = assembler::parse_assembly(self.source)?;
+        let words = assembler::encode_programs(&programs)?;
+        let mut cases = Vec::new();
+        for case in self.cases {
+            cairo_abi::generate_args(case.inputs, case.expected, &words)?;
+            let result = sim::simulate(&programs, case.inputs, DEFAULT_MAX_CYCLES)?;
+            cases.push(CaseResult {
+                case: *case,
+                passed: result.matches(case.expected),
+                outputs: result.outputs,
+                cycles: result.cycles,
+            });
+        }
+        Ok(Run {
+            program_root: merkle::program_root_hex(&programs)?,
+            words: words.len(),
+            cases,
+        })
+    }
+
+    /// Write `<name>.asm` and `cases.json` into `dir`.
+    pub fn export(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        fs::create_dir_all(dir)?;
+        let source = dir.join(format!("{}.asm", self.name));
+        let cases = dir.join("cases.json");
+        exec::write_atomic(&source, self.source)?;
+        exec::write_atomic(&cases, serde_json::to_string_pretty(self.cases)? + "\n")?;
+        Ok(vec![source, cases])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("adder").unwrap().name, "adder");
+        let err = find("nope").unwrap_err().to_string();
+        assert!(err.contains("available: passthrough, adder"), "{}", err);
+    }
+}
@@ -0,0 +1,13 @@
+# Adder: read inputs in pairs and output each pair's sum.
+
+NODE (0,0)
+MOV IN, ACC
+ADD IN
+MOV ACC, P:RIGHT
+
+NODE (0,1)
+MOV P:LEFT, ACC
+MOV ACC, P:DOWN
+
+NODE (1,1)
+MOV P:UP, OUT
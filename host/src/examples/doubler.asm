@@ -0,0 +1,14 @@
+# Signal doubler: (0,0) forwards each input, (0,1) doubles it,
+# (1,1) writes the result.
+
+NODE (0,0)
+MOV IN, ACC
+MOV ACC, P:RIGHT
+
+NODE (0,1)
+MOV P:LEFT, ACC
+ADD ACC
+MOV ACC, P:DOWN
+
+NODE (1,1)
+MOV P:UP, OUT
@@ -0,0 +1,13 @@
+# Passthrough: copy every input to the output.
+# (0,0) reads IN, (1,0) relays, (1,1) writes OUT.
+
+NODE (0,0)
+MOV IN, ACC
+MOV ACC, P:DOWN
+
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+
+NODE (1,1)
+MOV P:LEFT, OUT
@@ -0,0 +1,20 @@
+# Port ping-pong: (0,0) sends each input to (0,1), which adds one and
+# sends it back; (0,0) then forwards the reply towards the output.
+
+NODE (0,0)
+MOV IN, ACC
+MOV ACC, P:RIGHT
+MOV P:RIGHT, ACC
+MOV ACC, P:DOWN
+
+NODE (0,1)
+MOV P:LEFT, ACC
+ADD 1
+MOV ACC, P:LEFT
+
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+
+NODE (1,1)
+MOV P:LEFT, OUT
@@ -0,0 +1,46 @@
+# Sorting network skeleton: one compare-exchange element.
+# Inputs are read in pairs (a, b) and written back as (min, max).
+# (0,0) streams a, b, a, b: (0,1) counts the first copies down to find
+# the smaller one (SUB would panic below zero), then forwards the second
+# copies in order.
+
+NODE (0,0)
+MOV IN, ACC
+MOV ACC, P:RIGHT
+SAV
+MOV IN, ACC
+MOV ACC, P:RIGHT
+SWP
+MOV ACC, P:RIGHT
+SWP
+MOV ACC, P:RIGHT
+
+NODE (0,1)
+top:
+MOV P:LEFT, ACC
+SAV
+MOV P:LEFT, ACC
+count:
+SWP
+JZ in_order
+SUB 1
+SWP
+JZ swapped
+SUB 1
+JMP count
+in_order:
+MOV P:LEFT, ACC
+MOV ACC, P:DOWN
+MOV P:LEFT, ACC
+MOV ACC, P:DOWN
+JMP top
+swapped:
+MOV P:LEFT, ACC
+SAV
+MOV P:LEFT, ACC
+MOV ACC, P:DOWN
+SWP
+MOV ACC, P:DOWN
+
+NODE (1,1)
+MOV P:UP, OUT
@@ -0,0 +1,585 @@
+//! External processes, progress output and interruption.
+//!
+//! Every external tool (`cairo-prove`, `scarb`) is started through `Child`,
+//! which puts it in its own process group and kills that group when the
+//! wrapper is dropped, so neither an error nor Ctrl-C leaves an orphaned
+//! prover behind. `install_interrupt_handler` (the `ctrlc` crate) turns
+//! SIGINT/SIGTERM into a flag that `Child::wait` polls; the command then fails
+//! with `Interrupted` and `main` exits with `INTERRUPTED_EXIT_CODE`. While no
+//! child is running the handler exits straight away, first removing the
+//! temporary file of every `PendingFile` not yet committed.
+//!
+//! Output files are never written in place: `write_atomic` and `PendingFile`
+//! write to a temporary sibling, fsync it and rename it over the destination
+//! only once it is complete, and remove the temporary file otherwise. Unless
+//! `set_verify_writes(false)` (`--no-verify-write`) turned it off, the
+//! destination is then read back and its SHA-256 compared with what was
+//! written; a mismatch removes it. Either failure is a `WriteError` naming
+//! its `WriteStage`.
+//!
+//! Between `start_file_log` and `take_file_log`, every committed output and
+//! every input passed to `note_read` is logged, for `--record-session`.
+
+use anyhow::{Result, anyhow};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::{Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Exit code after SIGINT, as shells report it (128 + 2).
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+const POLL: Duration = Duration::from_millis(50);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static RUNNING_CHILDREN: AtomicUsize = AtomicUsize::new(0);
+static VERIFY_WRITES: AtomicBool = AtomicBool::new(true);
+static FILE_LOG: Mutex<Option<FileLog>> = Mutex::new(None);
+/// The temporary files of the live `PendingFile`s, for the interrupt
+/// handler to remove.
+static PENDING: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// The command was stopped by SIGINT/SIGTERM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Route SIGINT and SIGTERM through `interrupted()`. `ctrlc` runs the
+/// handler on a thread of its own, not in the signal handler, so it may
+/// lock `PENDING` and remove files before exiting.
+#[cfg(feature = "cli")]
+pub fn install_interrupt_handler() {
+    let installed = ctrlc::set_handler(|| {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        if RUNNING_CHILDREN.load(Ordering::SeqCst) == 0 {
+            let pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+            for tmp in pending.iter() {
+                fs::remove_file(tmp).ok();
+            }
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    });
+    if let Err(e) = installed {
+        eprintln!("warning: Ctrl-C will not clean up: {}", e);
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// A running external process, killed (with its process group) on drop.
+pub struct Child {
+    inner: Option<std::process::Child>,
+    program: String,
+}
+
+impl Child {
+    pub fn spawn(cmd: &mut Command) -> Result<Self> {
+        let program = cmd.get_program().to_string_lossy().into_owned();
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(cmd, 0);
+        let inner = cmd
+            .spawn()
+            .map_err(|e| anyhow!("failed to run {} (is it on PATH?): {}", program, e))?;
+        RUNNING_CHILDREN.fetch_add(1, Ordering::SeqCst);
+        Ok(Child { inner: Some(inner), program })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.inner.as_ref().map_or(0, std::process::Child::id)
+    }
+
+    /// Wait for exit, ticking `progress`. Fails with `Interrupted` (after
+    /// killing the process group) if a signal arrives first.
+    pub fn wait(self, progress: &mut Progress) -> Result<ExitStatus> {
+        Ok(self.wait_until(progress, None)?.expect("no deadline"))
+    }
+
+    /// Wait like `wait`, but once `deadline` passes kill the process group
+    /// and return `None`.
+    pub fn wait_until(mut self, progress: &mut Progress, deadline: Option<Instant>) -> Result<Option<ExitStatus>> {
+        loop {
+            if interrupted() {
+                self.kill();
+                return Err(Interrupted.into());
+            }
+            let child = self.inner.as_mut().expect("child is present until reaped");
+            if let Some(status) = child.try_wait()? {
+                self.reaped();
+                return Ok(Some(status));
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                self.kill();
+                return Ok(None);
+            }
+            progress.tick();
+            std::thread::sleep(POLL);
+        }
+    }
+
+    /// Wait like `wait`, collecting stdout and stderr.
+    pub fn wait_with_output(mut self, progress: &mut Progress) -> Result<Output> {
+        let child = self.inner.as_mut().expect("child is present until reaped");
+        let stdout = child.stdout.take().map(drain);
+        let stderr = child.stderr.take().map(drain);
+        let status = self.wait(progress)?;
+        let join = |handle: Option<std::thread::JoinHandle<Vec<u8>>>| {
+            handle.map_or_else(Vec::new, |h| h.join().unwrap_or_default())
+        };
+        Ok(Output { status, stdout: join(stdout), stderr: join(stderr) })
+    }
+
+    fn reaped(&mut self) {
+        if self.inner.take().is_some() {
+            RUNNING_CHILDREN.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    fn kill(&mut self) {
+        let Some(child) = self.inner.as_mut() else {
+            return;
+        };
+        // The group created in `spawn` only holds this child and its
+        // descendants.
+        #[cfg(unix)]
+        nix::sys::signal::killpg(nix::unistd::Pid::from_raw(child.id() as i32), nix::sys::signal::Signal::SIGKILL).ok();
+        child.kill().ok();
+        child.wait().ok();
+        self.reaped();
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+impl fmt::Debug for Child {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Child({} pid {})", self.program, self.id())
+    }
+}
+
+fn drain(mut reader: impl Read + Send + 'static) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).ok();
+        buf
+    })
+}
+
+/// Whether a process with this pid exists. Off Unix every pid is treated as
+/// gone.
+pub fn process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let Ok(pid) = i32::try_from(pid) else {
+            return false;
+        };
+        // No signal only checks that the pid exists and may be signalled;
+        // EPERM means it exists but belongs to someone else.
+        matches!(nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), None), Ok(()) | Err(nix::errno::Errno::EPERM))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+/// Run `cmd` to completion with inherited stdio.
+pub fn status(cmd: &mut Command, progress: &mut Progress) -> Result<ExitStatus> {
+    Child::spawn(cmd)?.wait(progress)
+}
+
+/// Run `cmd` to completion, capturing its output.
+pub fn output(cmd: &mut Command) -> Result<Output> {
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    Child::spawn(cmd)?.wait_with_output(&mut Progress::hidden())
+}
+
+/// Spinner (or counter, with a total) on stderr.
+#[derive(Debug)]
+pub struct Progress {
+    enabled: bool,
+    label: String,
+    started: Instant,
+    frame: usize,
+    done: u64,
+    total: Option<u64>,
+}
+
+const FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+impl Progress {
+    pub fn hidden() -> Self {
+        Progress::spinner("", false)
+    }
+
+    pub fn spinner(label: &str, enabled: bool) -> Self {
+        Progress {
+            enabled,
+            label: label.to_string(),
+            started: Instant::now(),
+            frame: 0,
+            done: 0,
+            total: None,
+        }
+    }
+
+    pub fn counter(label: &str, total: u64, enabled: bool) -> Self {
+        Progress { total: Some(total), ..Progress::spinner(label, enabled) }
+    }
+
+    /// Show progress only on an interactive terminal and without `--quiet`.
+    pub fn enabled(quiet: bool) -> bool {
+        !quiet && std::io::stdout().is_terminal() && std::io::stderr().is_terminal()
+    }
+
+    pub fn tick(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        self.frame = (self.frame + 1) % FRAMES.len();
+        let count = self.total.map_or_else(String::new, |t| format!(" {}/{}", self.done, t));
+        eprint!(
+            "\r\x1b[2K{} {}{} ({}s)",
+            FRAMES[self.frame],
+            self.label,
+            count,
+            self.started.elapsed().as_secs()
+        );
+        std::io::stderr().flush().ok();
+    }
+
+    pub fn inc(&mut self) {
+        self.done += 1;
+        self.tick();
+    }
+
+    /// Clear the progress line.
+    pub fn finish(&mut self) {
+        if self.enabled {
+            eprint!("\r\x1b[2K");
+            std::io::stderr().flush().ok();
+        }
+    }
+}
+
+/// Whether committed files are read back and checked (on by default).
+pub fn set_verify_writes(verify: bool) {
+    VERIFY_WRITES.store(verify, Ordering::SeqCst);
+}
+
+pub fn verify_writes() -> bool {
+    VERIFY_WRITES.load(Ordering::SeqCst)
+}
+
+/// Files one command read and wrote, each once, in the order first seen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileLog {
+    pub reads: Vec<PathBuf>,
+    pub writes: Vec<PathBuf>,
+}
+
+pub fn start_file_log() {
+    *FILE_LOG.lock().unwrap_or_else(|e| e.into_inner()) = Some(FileLog::default());
+}
+
+/// What was logged since `start_file_log`, stopping the log.
+pub fn take_file_log() -> FileLog {
+    FILE_LOG.lock().unwrap_or_else(|e| e.into_inner()).take().unwrap_or_default()
+}
+
+/// Log `path` as an input, if the log is on.
+pub fn note_read(path: &Path) {
+    log_file(path, |log| &mut log.reads);
+}
+
+fn log_file(path: &Path, list: impl FnOnce(&mut FileLog) -> &mut Vec<PathBuf>) {
+    if let Some(log) = FILE_LOG.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        let list = list(log);
+        if !list.iter().any(|p| p == path) {
+            list.push(path.to_path_buf());
+        }
+    }
+}
+
+/// Where writing an output file failed: putting the bytes down (temporary
+/// file, fsync, rename) or reading them back afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteStage {
+    Write,
+    Verify,
+}
+
+#[derive(Debug)]
+pub struct WriteError {
+    pub path: PathBuf,
+    pub stage: WriteStage,
+    pub reason: String,
+}
+
+impl WriteError {
+    fn new(path: &Path, stage: WriteStage, reason: impl fmt::Display) -> Self {
+        WriteError { path: path.to_path_buf(), stage, reason: reason.to_string() }
+    }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.stage {
+            WriteStage::Write => write!(f, "cannot write {}: {}", self.path.display(), self.reason),
+            WriteStage::Verify => write!(f, "{} failed verification after writing: {}", self.path.display(), self.reason),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// An output file that only appears at `dest` once `commit` is called. Until
+/// then its contents live at `path()`; dropping without committing, or an
+/// interrupt, deletes them.
+#[derive(Debug)]
+pub struct PendingFile {
+    dest: PathBuf,
+    tmp: PathBuf,
+    committed: bool,
+}
+
+impl PendingFile {
+    pub fn new(dest: &Path) -> Self {
+        let name = dest.file_name().map_or_else(|| "out".into(), |n| n.to_string_lossy().into_owned());
+        let tmp = dest.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+        PENDING.lock().unwrap_or_else(PoisonError::into_inner).push(tmp.clone());
+        PendingFile { dest: dest.to_path_buf(), tmp, committed: false }
+    }
+
+    /// Where the contents should be written.
+    pub fn path(&self) -> &Path {
+        &self.tmp
+    }
+
+    /// Fsync the contents, move them to `dest` and, if verification is on,
+    /// check `dest` reads back the same.
+    pub fn commit(self) -> Result<()> {
+        let digest = if verify_writes() {
+            Some(sha256(&fs::read(&self.tmp).map_err(|e| self.error(WriteStage::Write, e))?))
+        } else {
+            None
+        };
+        self.commit_expecting(digest)
+    }
+
+    fn commit_expecting(mut self, digest: Option<[u8; 32]>) -> Result<()> {
+        fs::File::open(&self.tmp).and_then(|file| file.sync_all()).map_err(|e| self.error(WriteStage::Write, e))?;
+        fs::rename(&self.tmp, &self.dest)
+            .map_err(|e| self.error(WriteStage::Write, format!("cannot move it into place: {}", e)))?;
+        self.committed = true;
+        log_file(&self.dest, |log| &mut log.writes);
+        let Some(digest) = digest else { return Ok(()) };
+        let reason = match fs::read(&self.dest) {
+            Ok(bytes) if sha256(&bytes) == digest => return Ok(()),
+            Ok(bytes) => format!("read back {} bytes with a different SHA-256", bytes.len()),
+            Err(e) => e.to_string(),
+        };
+        fs::remove_file(&self.dest).ok();
+        Err(self.error(WriteStage::Verify, reason).into())
+    }
+
+    fn error(&self, stage: WriteStage, reason: impl fmt::Display) -> WriteError {
+        WriteError::new(&self.dest, stage, reason)
+    }
+}
+
+impl Drop for PendingFile {
+    fn drop(&mut self) {
+        let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+        if !self.committed {
+            fs::remove_file(&self.tmp).ok();
+        }
+        if let Some(i) = pending.iter().position(|tmp| *tmp == self.tmp) {
+            pending.swap_remove(i);
+        }
+    }
+}
+
+/// Replace `path` with `contents` without ever exposing a partial file.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    write_atomic_with(path, contents.as_ref(), |file, bytes| file.write_all(bytes))
+}
+
+/// `write_atomic` with the bytes put into the temporary file by `write`, for
+/// callers that stream them or tests that inject failures. Verification
+/// compares against `contents`, not what `write` did.
+pub fn write_atomic_with(
+    path: &Path,
+    contents: &[u8],
+    write: impl FnOnce(&mut fs::File, &[u8]) -> std::io::Result<()>,
+) -> Result<()> {
+    let pending = PendingFile::new(path);
+    fs::File::create(pending.path())
+        .and_then(|mut file| write(&mut file, contents))
+        .map_err(|e| pending.error(WriteStage::Write, e))?;
+    pending.commit_expecting(verify_writes().then(|| sha256(contents)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("zk100-exec-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_without_leftovers() {
+        let dir = scratch("atomic");
+        let path = dir.join("args.json");
+        write_atomic(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(entries(&dir), ["args.json"]);
+
+        // An uncommitted file never reaches the destination.
+        let pending = PendingFile::new(&path);
+        fs::write(pending.path(), "partial").unwrap();
+        assert_eq!(entries(&dir).len(), 2);
+        drop(pending);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(entries(&dir), ["args.json"]);
+
+        let err = write_atomic(&dir.join("missing/args.json"), "x").unwrap_err();
+        assert_eq!(err.downcast_ref::<WriteError>().unwrap().stage, WriteStage::Write);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Ctrl-C with no child running, after a `PendingFile` was written but
+    /// before it was renamed into place. The test runs itself again as the
+    /// process to interrupt, which stops at that point.
+    #[cfg(all(unix, feature = "cli"))]
+    #[test]
+    fn test_interrupt_removes_pending_files() {
+        const DIR_ENV: &str = "ZK100_TEST_INTERRUPT_DIR";
+        if let Some(dir) = std::env::var_os(DIR_ENV).map(PathBuf::from) {
+            install_interrupt_handler();
+            let pending = PendingFile::new(&dir.join("proof.json"));
+            fs::write(pending.path(), "{\"partial").unwrap();
+            fs::write(dir.join("ready"), "").unwrap();
+            std::thread::sleep(Duration::from_secs(30));
+            pending.commit().unwrap();
+            return;
+        }
+        let dir = scratch("interrupt");
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "exec::tests::test_interrupt_removes_pending_files"])
+            .env(DIR_ENV, &dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let started = Instant::now();
+        while !dir.join("ready").exists() {
+            assert!(started.elapsed() < Duration::from_secs(20), "the interrupted run never got ready");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(entries(&dir), [format!(".proof.json.{}.tmp", child.id()), "ready".to_string()]);
+        let pid = nix::unistd::Pid::from_raw(child.id() as i32);
+        nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGINT).unwrap();
+        let status = child.wait().unwrap();
+        let left = entries(&dir);
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(status.code(), Some(INTERRUPTED_EXIT_CODE));
+        assert_eq!(left, ["ready"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_drop_kills_process_group() {
+        // The stub starts a grandchild sleeper and reports its pid; dropping
+        // the wrapper must take down both.
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 30 & echo $!; wait"]).stdout(Stdio::piped());
+        let mut child = Child::spawn(&mut cmd).unwrap();
+        let mut line = String::new();
+        let stdout = child.inner.as_mut().unwrap().stdout.take().unwrap();
+        std::io::BufRead::read_line(&mut std::io::BufReader::new(stdout), &mut line).unwrap();
+        let sleeper: u32 = line.trim().parse().unwrap();
+        let shell = child.id();
+
+        let started = Instant::now();
+        drop(child);
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let alive = |pid: u32| {
+            fs::read_to_string(format!("/proc/{}/stat", pid))
+                .is_ok_and(|stat| !stat.rsplit(')').next().unwrap_or("").trim_start().starts_with('Z'))
+        };
+        if Path::new("/proc/self/stat").exists() {
+            for _ in 0..100 {
+                if !alive(sleeper) {
+                    break;
+                }
+                std::thread::sleep(POLL);
+            }
+            assert!(!alive(shell), "shell {} survived", shell);
+            assert!(!alive(sleeper), "sleeper {} survived", sleeper);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_captures_stdout() {
+        let out = output(Command::new("sh").args(["-c", "echo hi; echo err >&2"])).unwrap();
+        assert!(out.status.success());
+        assert_eq!(out.stdout, b"hi\n");
+        assert_eq!(out.stderr, b"err\n");
+        assert!(output(&mut Command::new("zk100-no-such-tool")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_until_deadline_kills() {
+        let child = Child::spawn(Command::new("sleep").arg("30")).unwrap();
+        let pid = child.id();
+        assert!(process_alive(pid));
+        let started = Instant::now();
+        let status = child.wait_until(&mut Progress::hidden(), Some(started + Duration::from_millis(200))).unwrap();
+        assert!(status.is_none());
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(!process_alive(pid));
+
+        let child = Child::spawn(&mut Command::new("true")).unwrap();
+        let status = child.wait_until(&mut Progress::hidden(), Some(Instant::now() + Duration::from_secs(30))).unwrap();
+        assert!(status.unwrap().success());
+    }
+}
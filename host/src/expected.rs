@@ -0,0 +1,322 @@
+//! Expected-output specs: exact lists, or patterns for puzzles that accept
+//! a range of answers.
+//!
+//! A spec is a list of per-position entries, plus optionally a rule on the
+//! stream length:
+//!
+//! | entry       | JSON / TOML            | CLI (`-e`) | matches                        |
+//! |-------------|------------------------|------------|--------------------------------|
+//! | exact value | `42`                   | `42`       | that value                     |
+//! | range       | `{ min = 1, max = 9 }` | `1..=9`    | `min..=max`                    |
+//! | wildcard    | `"*"`                  | `*`        | any value                      |
+//! | length      | `"len >= 4"`           | `len>=4`   | at least 4 outputs in all      |
+//!
+//! Without a length rule the output must have exactly one value per entry;
+//! with one, the entries constrain a prefix. `check` lists every failed
+//! position, so a failure names what went wrong rather than only that the
+//! lists differ.
+//!
+//! Only an exact list can be proven: args carry concrete values, and the
+//! guest compares them with `==`. `exact` is that fast path, and the only
+//! thing `CairoArgs` is built from; a pattern has to be made concrete first,
+//! with `assemble --expected-from-simulation`.
+
+use crate::instruction;
+use crate::inputs::parse_inline;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    Exact(u32),
+    Range { min: u32, max: u32 },
+    Any,
+}
+
+impl Entry {
+    pub fn matches(&self, value: u32) -> bool {
+        match *self {
+            Entry::Exact(expected) => value == expected,
+            Entry::Range { min, max } => (min..=max).contains(&value),
+            Entry::Any => true,
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Entry::Exact(value) => write!(f, "{}", value),
+            Entry::Range { min, max } => write!(f, "{}..={}", min, max),
+            Entry::Any => write!(f, "*"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Value", into = "Value")]
+pub struct ExpectedSpec {
+    pub entries: Vec<Entry>,
+    /// `len >= N`: the output may be longer than `entries`.
+    pub min_len: Option<usize>,
+}
+
+/// One way an output failed its spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The output position, or `None` for the length.
+    pub position: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some(i) => write!(f, "outputs[{}]: {}", i, self.message),
+            None => write!(f, "length: {}", self.message),
+        }
+    }
+}
+
+impl From<Vec<u32>> for ExpectedSpec {
+    fn from(values: Vec<u32>) -> Self {
+        ExpectedSpec { entries: values.into_iter().map(Entry::Exact).collect(), min_len: None }
+    }
+}
+
+impl ExpectedSpec {
+    /// The values, if this is a plain list.
+    pub fn exact(&self) -> Option<Vec<u32>> {
+        if self.min_len.is_some() {
+            return None;
+        }
+        self.entries.iter().map(|entry| if let Entry::Exact(v) = *entry { Some(v) } else { None }).collect()
+    }
+
+    /// Like `exact`, but an error telling the user how to get concrete
+    /// values, for the places that encode args.
+    pub fn require_exact(&self) -> Result<Vec<u32>> {
+        self.exact().ok_or_else(|| {
+            anyhow!(
+                "expected outputs {} are a pattern, and only exact values can be encoded into args; \
+                 use --expected-from-simulation to take them from a simulated run",
+                self
+            )
+        })
+    }
+
+    pub fn matches(&self, outputs: &[u32]) -> bool {
+        match self.exact() {
+            Some(values) => outputs == values,
+            None => self.check(outputs).is_empty(),
+        }
+    }
+
+    /// Every position and length rule `outputs` breaks, in order.
+    pub fn check(&self, outputs: &[u32]) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        for (i, entry) in self.entries.iter().enumerate() {
+            let message = match outputs.get(i) {
+                Some(&value) if entry.matches(value) => continue,
+                Some(&value) => format!("expected {}, got {}", entry, value),
+                None => format!("expected {}, but there are only {} outputs", entry, outputs.len()),
+            };
+            mismatches.push(Mismatch { position: Some(i), message });
+        }
+        match self.min_len {
+            Some(n) if outputs.len() < n => mismatches.push(Mismatch {
+                position: None,
+                message: format!("expected at least {} outputs, got {}", n, outputs.len()),
+            }),
+            None if outputs.len() > self.entries.len() => mismatches.push(Mismatch {
+                position: None,
+                message: format!("expected {} outputs, got {}", self.entries.len(), outputs.len()),
+            }),
+            _ => {}
+        }
+        mismatches
+    }
+
+    /// The CLI form: comma-separated `42`, `1..=9`, `*` and `len>=4`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut spec = ExpectedSpec::default();
+        for item in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            spec.push_item(item)?;
+        }
+        Ok(spec)
+    }
+
+    /// The TOML array form report manifests use.
+    pub fn parse_toml(text: &str) -> Result<Self> {
+        ExpectedSpec::try_from(parse_inline(text, "the expected outputs")?)
+    }
+
+    /// Add one CLI or string item.
+    fn push_item(&mut self, item: &str) -> Result<()> {
+        let compact: String = item.chars().filter(|c| !c.is_whitespace()).collect();
+        let number = |text: &str| -> Result<u32> {
+            // Negative values wrap, as `-e -1` always has.
+            instruction::parse_literal(text)
+                .ok_or_else(|| anyhow!("'{}' is not a value, a range min..=max, * or len>=N", item))
+        };
+        if compact == "*" {
+            self.entries.push(Entry::Any);
+        } else if let Some(n) = compact.strip_prefix("len>=") {
+            if self.min_len.is_some() {
+                return Err(anyhow!("the length rule is given twice"));
+            }
+            self.min_len = Some(n.parse().map_err(|_| anyhow!("'{}': the length must be a count", item))?);
+        } else if let Some((min, max)) = compact.split_once("..=") {
+            self.push_range(number(min)?, number(max)?)?;
+        } else {
+            self.entries.push(Entry::Exact(number(&compact)?));
+        }
+        Ok(())
+    }
+
+    fn push_range(&mut self, min: u32, max: u32) -> Result<()> {
+        if min > max {
+            return Err(anyhow!("range {}..={} is empty", min, max));
+        }
+        self.entries.push(Entry::Range { min, max });
+        Ok(())
+    }
+}
+
+/// The CLI form `parse` reads.
+impl fmt::Display for ExpectedSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut items: Vec<String> = self.entries.iter().map(Entry::to_string).collect();
+        if let Some(n) = self.min_len {
+            items.push(format!("len>={}", n));
+        }
+        write!(f, "{}", items.join(","))
+    }
+}
+
+impl TryFrom<Value> for ExpectedSpec {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> Result<Self> {
+        let items = value.as_array().ok_or_else(|| anyhow!("expected outputs are an array, found {}", value))?;
+        let mut spec = ExpectedSpec::default();
+        for item in items {
+            match item {
+                Value::Number(n) => {
+                    let value = n.as_u64().and_then(|n| u32::try_from(n).ok());
+                    spec.entries.push(Entry::Exact(value.ok_or_else(|| anyhow!("{} is not a u32 value", n))?));
+                }
+                Value::String(text) => spec.push_item(text)?,
+                Value::Object(table) => {
+                    if let Some(extra) = table.keys().find(|k| !["min", "max"].contains(&k.as_str())) {
+                        return Err(anyhow!("unexpected key {} in a range; write {{ min, max }}", extra));
+                    }
+                    let bound = |key: &str| {
+                        table
+                            .get(key)
+                            .and_then(Value::as_u64)
+                            .and_then(|n| u32::try_from(n).ok())
+                            .ok_or_else(|| anyhow!("a range needs {} as a u32 value", key))
+                    };
+                    spec.push_range(bound("min")?, bound("max")?)?;
+                }
+                other => return Err(anyhow!("{} is not a value, a range, \"*\" or \"len >= N\"", other)),
+            }
+        }
+        Ok(spec)
+    }
+}
+
+impl From<ExpectedSpec> for Value {
+    fn from(spec: ExpectedSpec) -> Value {
+        let mut items: Vec<Value> = spec
+            .entries
+            .iter()
+            .map(|entry| match *entry {
+                Entry::Exact(value) => json!(value),
+                Entry::Range { min, max } => json!({ "min": min, "max": max }),
+                Entry::Any => json!("*"),
+            })
+            .collect();
+        if let Some(n) = spec.min_len {
+            items.push(json!(format!("len >= {}", n)));
+        }
+        Value::Array(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(spec: &str, outputs: &[u32]) -> Vec<String> {
+        ExpectedSpec::parse(spec).unwrap().check(outputs).iter().map(Mismatch::to_string).collect()
+    }
+
+    #[test]
+    fn test_exact_lists_keep_the_fast_path() {
+        let spec = ExpectedSpec::parse("2,4,6").unwrap();
+        assert_eq!(spec, ExpectedSpec::from(vec![2, 4, 6]));
+        assert_eq!(spec.exact(), Some(vec![2, 4, 6]));
+        assert!(spec.matches(&[2, 4, 6]));
+        assert!(!spec.matches(&[2, 4]));
+        assert_eq!(messages("2,4,6", &[2, 5, 6, 8]), ["outputs[1]: expected 4, got 5", "length: expected 3 outputs, got 4"]);
+        assert_eq!(ExpectedSpec::parse("-1").unwrap().exact(), Some(vec![u32::MAX]));
+        assert_eq!(ExpectedSpec::parse("").unwrap().exact(), Some(vec![]));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let spec = ExpectedSpec::parse("1..=9, 5").unwrap();
+        assert_eq!(spec.exact(), None);
+        assert!(spec.matches(&[1, 5]) && spec.matches(&[9, 5]));
+        assert_eq!(messages("1..=9,5", &[10, 5]), ["outputs[0]: expected 1..=9, got 10"]);
+        assert_eq!(ExpectedSpec::parse("9..=1").unwrap_err().to_string(), "range 9..=1 is empty");
+        assert!(ExpectedSpec::parse("0x1..=0b1001").unwrap().matches(&[9]));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(ExpectedSpec::parse("*,*,3").unwrap().matches(&[u32::MAX, 0, 3]));
+        assert_eq!(messages("*,*", &[7]), ["outputs[1]: expected *, but there are only 1 outputs"]);
+    }
+
+    #[test]
+    fn test_length_rules() {
+        // Entries constrain a prefix; the rule bounds the whole stream.
+        let spec = ExpectedSpec::parse("1, len>=3").unwrap();
+        assert!(spec.matches(&[1, 2, 3]) && spec.matches(&[1, 2, 3, 4, 5]));
+        assert_eq!(messages("1,len>=3", &[1, 2]), ["length: expected at least 3 outputs, got 2"]);
+        assert_eq!(messages("1,len>=3", &[2]), ["outputs[0]: expected 1, got 2", "length: expected at least 3 outputs, got 1"]);
+        assert!(ExpectedSpec::parse("len>=0").unwrap().matches(&[]));
+        assert!(ExpectedSpec::parse("len>=1,len>=2").is_err());
+        assert!(ExpectedSpec::parse("len>=x").is_err());
+    }
+
+    #[test]
+    fn test_serde_and_toml_forms() {
+        let text = "[3, { min = 1, max = 9 }, \"*\", \"len >= 4\"]";
+        let spec = ExpectedSpec::parse_toml(text).unwrap();
+        assert_eq!(spec, ExpectedSpec::parse("3,1..=9,*,len>=4").unwrap());
+        assert_eq!(spec.to_string(), "3,1..=9,*,len>=4");
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(json, r#"[3,{"max":9,"min":1},"*","len >= 4"]"#);
+        assert_eq!(serde_json::from_str::<ExpectedSpec>(&json).unwrap(), spec);
+        // Exact lists serialize as they always have.
+        assert_eq!(serde_json::to_string(&ExpectedSpec::from(vec![1, 2])).unwrap(), "[1,2]");
+
+        let err = |json: &str| serde_json::from_str::<ExpectedSpec>(json).unwrap_err().to_string();
+        assert!(err(r#"[{"min": 1}]"#).contains("a range needs max"));
+        assert!(err(r#"[{"min": 1, "max": 2, "step": 1}]"#).contains("unexpected key step"));
+        assert!(err(r#"[true]"#).contains("is not a value"));
+    }
+
+    #[test]
+    fn test_require_exact_points_at_simulation() {
+        let err = ExpectedSpec::parse("*").unwrap().require_exact().unwrap_err().to_string();
+        assert!(err.contains("--expected-from-simulation"), "{}", err);
+        assert_eq!(ExpectedSpec::from(vec![1]).require_exact().unwrap(), [1]);
+    }
+}
@@ -0,0 +1,213 @@
+//! Constant expressions in literal operands.
+//!
+//! Wherever a literal goes, an operand may be an expression over literals
+//! and `DEFINE` constants: `MOV WIDTH*2+1, ACC`, `ADD (LIMIT-1)`. `+`, `-`,
+//! `*`, `/` (truncating), parentheses and unary minus work as usual, on
+//! `i64`; a literal in it may be anything `i64` holds, and a constant is the
+//! value it was defined as. Division by zero and overflow are errors quoting
+//! the expression. The result stays an `i64`: an operand is checked against
+//! the instruction's literal field afterwards, as a written `-5` or `300`
+//! is, and a constant must fit in 32 bits (`eval`).
+//!
+//! What a name means is up to the caller: `eval` asks `name` for the value
+//! of every identifier, so the assembler decides which names are constants
+//! and which are errors (a label, say).
+
+use anyhow::{Result, anyhow};
+use std::num::IntErrorKind;
+
+/// True if `s` is written as an expression rather than a single literal or
+/// name: it has an operator or a parenthesis past a leading sign.
+pub fn is_expression(s: &str) -> bool {
+    let body = s.strip_prefix(['-', '+']).unwrap_or(s);
+    body.contains(['+', '-', '*', '/', '(', ')']) || (body.len() < s.len() && !body.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// The value of the expression `s`, which must fit in 32 bits signed or
+/// unsigned (`i32::MIN..=u32::MAX`), with `name` giving the value of each
+/// identifier in it.
+pub fn eval(s: &str, name: &dyn Fn(&str) -> Result<i64>) -> Result<i64> {
+    let value = eval_i64(s, name)?;
+    if value < i64::from(i32::MIN) || value > i64::from(u32::MAX) {
+        return Err(anyhow!("`{}` is {}, which does not fit in 32 bits", s, value));
+    }
+    Ok(value)
+}
+
+/// The value of the expression `s`, unchecked against any range.
+pub fn eval_i64(s: &str, name: &dyn Fn(&str) -> Result<i64>) -> Result<i64> {
+    let mut parser = Parser { text: s, pos: 0, name };
+    let value = parser.sum()?;
+    parser.skip_spaces();
+    match parser.peek() {
+        None => Ok(value),
+        Some(')') => Err(anyhow!("unbalanced ')' in `{}`", s)),
+        Some(c) => Err(anyhow!("unexpected '{}' in `{}`", c, s)),
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    name: &'a dyn Fn(&str) -> Result<i64>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += self.peek().map_or(0, char::len_utf8);
+        }
+    }
+
+    /// The next character, if it is one of `ops`, consumed.
+    fn operator(&mut self, ops: &[char]) -> Option<char> {
+        self.skip_spaces();
+        let c = self.peek().filter(|c| ops.contains(c))?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn overflow(&self) -> anyhow::Error {
+        anyhow!("overflow in `{}`", self.text)
+    }
+
+    fn sum(&mut self) -> Result<i64> {
+        let mut value = self.product()?;
+        while let Some(op) = self.operator(&['+', '-']) {
+            let rhs = self.product()?;
+            value = match op {
+                '+' => value.checked_add(rhs),
+                _ => value.checked_sub(rhs),
+            }
+            .ok_or_else(|| self.overflow())?;
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<i64> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.operator(&['*', '/']) {
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value.checked_mul(rhs).ok_or_else(|| self.overflow())?,
+                _ if rhs == 0 => return Err(anyhow!("division by zero in `{}`", self.text)),
+                _ => value.checked_div(rhs).ok_or_else(|| self.overflow())?,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64> {
+        match self.operator(&['-', '+']) {
+            Some('-') => self.unary()?.checked_neg().ok_or_else(|| self.overflow()),
+            Some(_) => self.unary(),
+            None => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<i64> {
+        if self.operator(&['(']).is_some() {
+            let value = self.sum()?;
+            if self.operator(&[')']).is_none() {
+                return Err(anyhow!("missing ')' in `{}`", self.text));
+            }
+            return Ok(value);
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+            self.pos += 1;
+        }
+        let word = &self.text[start..self.pos];
+        match word.chars().next() {
+            None => match self.peek() {
+                Some(c) => Err(anyhow!("unexpected '{}' in `{}`", c, self.text)),
+                None => Err(anyhow!("expected a value at the end of `{}`", self.text)),
+            },
+            Some(c) if c.is_ascii_digit() => self.literal(word),
+            Some(_) => (self.name)(word),
+        }
+    }
+
+    /// The value of the unsigned literal `word`, decimal, `0x` hex or `0b`
+    /// binary, as wide as `i64` goes; the range of the whole expression is
+    /// checked once it is evaluated.
+    fn literal(&self, word: &str) -> Result<i64> {
+        let (radix, digits) = match word.get(..2) {
+            Some("0x" | "0X") => (16, &word[2..]),
+            Some("0b" | "0B") => (2, &word[2..]),
+            _ => (10, word),
+        };
+        if !digits.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return Err(anyhow!("'{}' is not a literal in `{}`", word, self.text));
+        }
+        i64::from_str_radix(digits, radix).map_err(|e| match e.kind() {
+            IntErrorKind::PosOverflow => self.overflow(),
+            _ => anyhow!("'{}' is not a literal in `{}`", word, self.text),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constants(name: &str) -> Result<i64> {
+        match name {
+            "WIDTH" => Ok(4),
+            "LIMIT" => Ok(10),
+            "STEP" => Ok(-2),
+            _ => Err(anyhow!("undefined constant '{}'", name)),
+        }
+    }
+
+    fn value(s: &str) -> Result<i64> {
+        eval(s, &constants)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(value("WIDTH*2+1").unwrap(), 9);
+        assert_eq!(value("(LIMIT-1)").unwrap(), 9);
+        assert_eq!(value("2+3*4").unwrap(), 14);
+        assert_eq!(value("(2+3)*4").unwrap(), 20);
+        assert_eq!(value("-(WIDTH) * STEP").unwrap(), 8);
+        assert_eq!(value("7/2 - -1").unwrap(), 4);
+        assert_eq!(value("0x10 + 0b1").unwrap(), 17);
+        assert_eq!(value("1-3").unwrap(), -2);
+        // Past i32::MAX the value stays what it is rather than wrapping.
+        assert_eq!(value("2147483647*2").unwrap(), 4_294_967_294);
+    }
+
+    #[test]
+    fn test_errors_quote_the_expression() {
+        let err = |s: &str| value(s).unwrap_err().to_string();
+        assert_eq!(err("LIMIT/(WIDTH-4)"), "division by zero in `LIMIT/(WIDTH-4)`");
+        assert_eq!(err("65536*65536*65536*65536"), "overflow in `65536*65536*65536*65536`");
+        assert_eq!(err("65536*65536"), "`65536*65536` is 4294967296, which does not fit in 32 bits");
+        assert_eq!(err("(1+2"), "missing ')' in `(1+2`");
+        assert_eq!(err("1+2)"), "unbalanced ')' in `1+2)`");
+        assert_eq!(err("1+"), "expected a value at the end of `1+`");
+        assert_eq!(err("2*MISSING"), "undefined constant 'MISSING'");
+        assert_eq!(err("0b2+1"), "'0b2' is not a literal in `0b2+1`");
+        // Literals past u32 reach the checks on the expression.
+        assert_eq!(err("9223372036854775807*2"), "overflow in `9223372036854775807*2`");
+        assert_eq!(err("99999999999999999999+1"), "overflow in `99999999999999999999+1`");
+        assert_eq!(err("5000000000-1"), "`5000000000-1` is 4999999999, which does not fit in 32 bits");
+        assert_eq!(value("5000000000-4999999999").unwrap(), 1);
+        assert_eq!(value("0x100000000/0x10000").unwrap(), 0x10000);
+    }
+
+    #[test]
+    fn test_is_expression() {
+        for s in ["1+2", "WIDTH*2", "(3)", "-x", "LIMIT-1"] {
+            assert!(is_expression(s), "{}", s);
+        }
+        for s in ["42", "-5", "+0x10", "WIDTH", "P:UP"] {
+            assert!(!is_expression(s), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! 32-byte big-endian words and felts, in one place.
+//!
+//! Program roots are SHA-256 digests carried as 32 big-endian bytes, and the
+//! guest's public output is felts. Everything that moves between the two
+//! goes through here: `to_be_bytes32` refuses values that need more than 256
+//! bits instead of truncating them, `check_felt` separately refuses values
+//! the Stark field cannot hold, and the hex forms are `0x`-prefixed,
+//! lowercase and either minimal (`to_hex`) or zero-padded to 64 digits
+//! (`hex32`).
+
+use anyhow::{Result, anyhow};
+use num_bigint::BigUint;
+
+/// 2^251 + 17·2^192 + 1, the modulus of Cairo's `felt252`.
+pub fn stark_prime() -> BigUint {
+    (BigUint::from(1u32) << 251) + (BigUint::from(17u32) << 192) + 1u32
+}
+
+/// `n` as 32 big-endian bytes, left-padded with zeros.
+pub fn to_be_bytes32(n: &BigUint) -> Result<[u8; 32]> {
+    if n.bits() > 256 {
+        return Err(anyhow!("{} does not fit in 32 bytes", to_hex(n)));
+    }
+    let bytes = n.to_bytes_be();
+    let mut out = [0u8; 32];
+    out[32 - bytes.len()..].copy_from_slice(&bytes);
+    Ok(out)
+}
+
+pub fn from_be_bytes32(bytes: &[u8; 32]) -> BigUint {
+    BigUint::from_bytes_be(bytes)
+}
+
+/// Any big-endian byte string as 32 bytes: shorter ones are padded, longer
+/// ones only fit if their extra leading bytes are zero.
+pub fn bytes32(bytes: &[u8]) -> Result<[u8; 32]> {
+    to_be_bytes32(&BigUint::from_bytes_be(bytes))
+}
+
+/// Whether `n` is a canonical felt, i.e. below the Stark prime.
+pub fn check_felt(n: &BigUint) -> Result<()> {
+    if *n >= stark_prime() {
+        return Err(anyhow!("{} is not below the Stark prime", to_hex(n)));
+    }
+    Ok(())
+}
+
+/// `0x` and the lowercase hex digits of `n`, without leading zeros.
+pub fn to_hex(n: &BigUint) -> String {
+    format!("0x{}", n.to_str_radix(16))
+}
+
+/// `0x` and all 64 lowercase hex digits of `bytes`.
+pub fn hex32(bytes: &[u8; 32]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// A `0x`-prefixed hex value of up to 32 bytes, in any case and with or
+/// without leading zeros, as 32 bytes.
+pub fn from_hex32(text: &str) -> Result<[u8; 32]> {
+    let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+    let n = digits
+        .filter(|d| !d.is_empty())
+        .and_then(|d| BigUint::parse_bytes(d.as_bytes(), 16))
+        .ok_or_else(|| anyhow!("'{}' is not 0x-prefixed hex", text))?;
+    to_be_bytes32(&n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    /// The `i`-th of a fixed sequence of values of 0 to 300 bits.
+    fn value(i: u32) -> BigUint {
+        let mut bytes = Vec::new();
+        for block in 0..2u32 {
+            bytes.extend(Sha256::new().chain_update(i.to_be_bytes()).chain_update(block.to_be_bytes()).finalize());
+        }
+        let n = BigUint::from_bytes_be(&bytes) >> (512 - 300);
+        n >> (bytes[0] as usize % 301)
+    }
+
+    #[test]
+    fn test_round_trips_and_rejects_oversized() {
+        let limit = BigUint::from(1u32) << 256;
+        let (mut fits, mut oversized) = (0, 0);
+        for i in 0..2000 {
+            let n = value(i);
+            match to_be_bytes32(&n) {
+                Ok(bytes) => {
+                    assert!(n < limit, "{}", to_hex(&n));
+                    assert_eq!(from_be_bytes32(&bytes), n);
+                    assert_eq!(bytes32(&n.to_bytes_be()).unwrap(), bytes);
+                    assert_eq!(BigUint::parse_bytes(&hex32(&bytes).as_bytes()[2..], 16).unwrap(), n);
+                    fits += 1;
+                }
+                Err(e) => {
+                    assert!(n >= limit, "{}", to_hex(&n));
+                    assert!(e.to_string().ends_with("does not fit in 32 bytes"));
+                    assert!(bytes32(&n.to_bytes_be()).is_err());
+                    oversized += 1;
+                }
+            }
+        }
+        assert!(fits > 100 && oversized > 100, "{} fit, {} oversized", fits, oversized);
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let zero = BigUint::from(0u32);
+        assert_eq!(to_be_bytes32(&zero).unwrap(), [0; 32]);
+        assert_eq!((to_hex(&zero), hex32(&[0; 32]).len()), ("0x0".to_string(), 66));
+        check_felt(&zero).unwrap();
+
+        let max = (BigUint::from(1u32) << 256) - 1u32;
+        assert_eq!(to_be_bytes32(&max).unwrap(), [0xff; 32]);
+        assert!(to_be_bytes32(&(max + 1u32)).is_err());
+        assert_eq!(bytes32(&[0, 0, 1]).unwrap()[31], 1);
+        assert_eq!(bytes32(&[[0u8; 8].as_slice(), &[0xff; 32]].concat()).unwrap(), [0xff; 32]);
+
+        assert_eq!(from_hex32("0x00FF").unwrap(), bytes32(&[0xff]).unwrap());
+        assert_eq!(from_hex32(&hex32(&[0xab; 32])).unwrap(), [0xab; 32]);
+        for bad in ["ff", "0x", "0xfg", &format!("0x1{}", "0".repeat(64))] {
+            assert!(from_hex32(bad).is_err(), "{}", bad);
+        }
+
+        let p = stark_prime();
+        assert_eq!(to_hex(&p), "0x800000000000011000000000000000000000000000000000000000000000001");
+        check_felt(&(p.clone() - 1u32)).unwrap();
+        assert!(check_felt(&p).unwrap_err().to_string().ends_with("is not below the Stark prime"));
+        // Above the prime, but still 32 bytes.
+        assert_eq!(from_be_bytes32(&to_be_bytes32(&(p.clone() + 1u32)).unwrap()), p + 1u32);
+    }
+}
@@ -0,0 +1,334 @@
+//! `ProgramGrid`: the assembled grid of node programs and its versioned JSON
+//! form.
+//!
+//! Serialized grids carry a `"schema"` number. Deserialization reads that
+//! number first and dispatches to the matching `GridVn` struct, which is then
+//! converted to the current in-memory type, so files written by older
+//! releases keep loading. Serialization always writes `CURRENT_SCHEMA`.
+//!
+//! To add schema N+1: add a `GridVn` struct, a `VersionedGrid` variant, its
+//! conversion in `into_current`, bump `CURRENT_SCHEMA`, and check in a
+//! fixture under `tests/fixtures/schema/`.
+//!
+//! Reading and writing the JSON form needs the `abi-json` feature.
+
+use crate::assembler::{GridSize, Labels, Programs};
+use crate::instruction::{Inst, Op, Src};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+#[cfg(feature = "abi-json")]
+use {
+    crate::instruction::Dst,
+    anyhow::{Result, anyhow},
+    serde::Deserializer,
+    serde::de::Error as _,
+    serde_json::Value,
+    std::str::FromStr,
+};
+
+/// Schema number written by this build.
+pub const CURRENT_SCHEMA: u64 = 1;
+/// Oldest schema `from_json` still reads.
+pub const OLDEST_SCHEMA: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramGrid {
+    pub programs: Programs,
+}
+
+impl ProgramGrid {
+    pub fn new(programs: Programs) -> Self {
+        ProgramGrid { programs }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.programs.first().map_or(0, Vec::len)
+    }
+
+    pub fn size(&self) -> GridSize {
+        GridSize { rows: self.rows(), cols: self.cols() }
+    }
+
+    /// Render the grid as assembly source, one `NODE` block per non-empty
+    /// node, led by a `GRID` line when the grid is not 2x2.
+    pub fn to_assembly(&self) -> String {
+        self.to_assembly_with_labels(&Labels::new())
+    }
+
+    /// Like `to_assembly`, but with the label names from a `ParseResult`:
+    /// each label is written before the instruction it marks, and literal
+    /// jump targets that land on a label use its name (the first in name
+    /// order when several labels share a pc).
+    pub fn to_assembly_with_labels(&self, labels: &Labels) -> String {
+        let no_labels = BTreeMap::new();
+        let mut out = String::new();
+        if !self.size().is_default() {
+            out.push_str(&format!("GRID {} {}\n", self.rows(), self.cols()));
+        }
+        for (r, row) in self.programs.iter().enumerate() {
+            for (c, program) in row.iter().enumerate() {
+                if program.is_empty() {
+                    continue;
+                }
+                if !out.is_empty() {
+                    out.push('\n');
+                }
+                out.push_str(&format!("NODE ({},{})\n", r, c));
+                let node_labels = labels.get(&(r, c)).unwrap_or(&no_labels);
+                let name_at = |pc: usize| node_labels.iter().find(|(_, &at)| at == pc).map(|(name, _)| name);
+                for pc in 0..=program.len() {
+                    for (name, _) in node_labels.iter().filter(|(_, &at)| at == pc) {
+                        out.push_str(&format!("{}:\n", name));
+                    }
+                    let Some(inst) = program.get(pc) else {
+                        break;
+                    };
+                    match (inst.op, inst.src) {
+                        (Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz, Src::Lit(target))
+                            if name_at(target as usize).is_some() =>
+                        {
+                            out.push_str(&format!("{} {}\n", inst.op, name_at(target as usize).unwrap()));
+                        }
+                        _ => out.push_str(&format!("{}\n", inst)),
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl From<Programs> for ProgramGrid {
+    fn from(programs: Programs) -> Self {
+        ProgramGrid::new(programs)
+    }
+}
+
+/// An assembled grid of node programs. Schema 1 stores each instruction as
+/// its canonical assembly text.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+struct GridV1 {
+    /// Version of this layout.
+    #[cfg_attr(feature = "schema", schemars(extend("const" = CURRENT_SCHEMA)))]
+    schema: u64,
+    rows: usize,
+    cols: usize,
+    /// Every node of the grid, empty programs included, in row-major order.
+    nodes: Vec<NodeV1>,
+}
+
+/// One node's program.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+struct NodeV1 {
+    row: usize,
+    col: usize,
+    /// Canonical assembly text of each instruction, in pc order.
+    program: Vec<String>,
+}
+
+#[cfg(feature = "abi-json")]
+enum VersionedGrid {
+    V1(GridV1),
+}
+
+#[cfg(feature = "abi-json")]
+impl VersionedGrid {
+    fn into_current(self) -> Result<ProgramGrid> {
+        match self {
+            VersionedGrid::V1(v1) => {
+                let mut programs: Programs = vec![vec![vec![]; v1.cols]; v1.rows];
+                for node in v1.nodes {
+                    if node.row >= v1.rows || node.col >= v1.cols {
+                        return Err(anyhow!(
+                            "node ({},{}) is outside the {}x{} grid",
+                            node.row, node.col, v1.rows, v1.cols
+                        ));
+                    }
+                    let program = node
+                        .program
+                        .iter()
+                        .enumerate()
+                        .map(|(pc, text)| {
+                            parse_canonical(text).map_err(|e| {
+                                anyhow!("node ({},{}) pc {}: {}", node.row, node.col, pc, e)
+                            })
+                        })
+                        .collect::<Result<Vec<Inst>>>()?;
+                    programs[node.row][node.col] = program;
+                }
+                Ok(ProgramGrid::new(programs))
+            }
+        }
+    }
+}
+
+/// Parse one instruction in the canonical form produced by `Inst`'s `Display`.
+#[cfg(feature = "abi-json")]
+fn parse_canonical(text: &str) -> Result<Inst> {
+    let (mnemonic, operands) = text.trim().split_once(' ').unwrap_or((text.trim(), ""));
+    let op = Op::from_str(mnemonic)?;
+    let operands: Vec<&str> = operands
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    let expected = match op {
+        Op::Nop | Op::Hlt | Op::Neg | Op::Sav | Op::Swp => 0,
+        Op::Mov => 2,
+        _ => 1,
+    };
+    if operands.len() != expected {
+        return Err(anyhow!("'{}' needs {} operand(s)", text, expected));
+    }
+    let src = match operands.first() {
+        Some(s) => Src::from_str(s)?,
+        None => Src::Nil,
+    };
+    let dst = match operands.get(1) {
+        Some(d) => Dst::from_str(d)?,
+        None => Dst::Nil,
+    };
+    Ok(Inst { op, src, dst })
+}
+
+impl Serialize for ProgramGrid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut nodes = Vec::new();
+        for (row, programs) in self.programs.iter().enumerate() {
+            for (col, program) in programs.iter().enumerate() {
+                nodes.push(NodeV1 {
+                    row,
+                    col,
+                    program: program.iter().map(Inst::to_string).collect(),
+                });
+            }
+        }
+        GridV1 {
+            schema: CURRENT_SCHEMA,
+            rows: self.rows(),
+            cols: self.cols(),
+            nodes,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "abi-json")]
+impl<'de> Deserialize<'de> for ProgramGrid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let schema = match value.get("schema") {
+            Some(Value::Number(n)) => n
+                .as_u64()
+                .ok_or_else(|| D::Error::custom(format!("invalid schema number {}", n)))?,
+            Some(other) => {
+                return Err(D::Error::custom(format!("\"schema\" must be a number, found {}", other)));
+            }
+            None => return Err(D::Error::custom("not a zk100 program file: missing \"schema\" field")),
+        };
+
+        let versioned = match schema {
+            1 => VersionedGrid::V1(serde_json::from_value(value).map_err(D::Error::custom)?),
+            n if n > CURRENT_SCHEMA => {
+                return Err(D::Error::custom(format!(
+                    "program file uses schema {}, which was produced by a newer zk100 \
+                     (this build reads schemas up to {}); upgrade zk100 to load it",
+                    n, CURRENT_SCHEMA
+                )));
+            }
+            n => return Err(D::Error::custom(format!("unknown program schema {}", n))),
+        };
+        versioned.into_current().map_err(D::Error::custom)
+    }
+}
+
+/// The grid's JSON form is `GridV1`'s.
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for ProgramGrid {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "ProgramGrid".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        <GridV1 as schemars::JsonSchema>::json_schema(generator)
+    }
+}
+
+/// Load a grid from its JSON form.
+#[cfg(feature = "abi-json")]
+pub fn from_json(json: &str) -> Result<ProgramGrid> {
+    serde_json::from_str(json).map_err(|e| anyhow!("{}", e))
+}
+
+/// Serialize a grid as pretty-printed JSON using the current schema.
+#[cfg(feature = "abi-json")]
+pub fn to_json(grid: &ProgramGrid) -> Result<String> {
+    Ok(serde_json::to_string_pretty(grid)?)
+}
+
+#[cfg(all(test, feature = "abi-json"))]
+mod tests {
+    use super::*;
+    use crate::assembler::parse_assembly;
+
+    const SAMPLE: &str = "NODE (0,0)\nloop:\nMOV IN, ACC\nJZ loop\nMOV ACC, P:RIGHT\nHLT\n\nNODE (0,1)\nMOV P:LEFT, OUT\n";
+
+    #[test]
+    fn test_json_roundtrip() {
+        let grid = ProgramGrid::new(parse_assembly(SAMPLE).unwrap());
+        let json = to_json(&grid).unwrap();
+        assert!(json.contains("\"schema\": 1"));
+        assert_eq!(from_json(&json).unwrap(), grid);
+    }
+
+    #[test]
+    fn test_assembly_roundtrip() {
+        let grid = ProgramGrid::new(parse_assembly(SAMPLE).unwrap());
+        let reparsed = ProgramGrid::new(parse_assembly(&grid.to_assembly()).unwrap());
+        assert_eq!(reparsed, grid);
+
+        let wide = ProgramGrid::new(parse_assembly("GRID 1 3\nNODE (0,2)\nMOV IN, OUT\n").unwrap());
+        assert!(wide.to_assembly().starts_with("GRID 1 3\n"));
+        assert_eq!(ProgramGrid::new(parse_assembly(&wide.to_assembly()).unwrap()), wide);
+    }
+
+    #[test]
+    fn test_assembly_with_labels() {
+        let result = crate::assembler::parse(SAMPLE).unwrap();
+        let text = result.grid.to_assembly_with_labels(&result.labels);
+        assert!(text.starts_with("NODE (0,0)\nloop:\nMOV IN, ACC\nJZ loop\n"), "{}", text);
+        let reparsed = crate::assembler::parse(&text).unwrap();
+        assert_eq!(reparsed.grid, result.grid);
+        assert_eq!(reparsed.labels, result.labels);
+        // Without labels the target stays numeric.
+        assert!(result.grid.to_assembly().contains("JZ 0\n"));
+    }
+
+    #[test]
+    fn test_newer_schema_rejected() {
+        let err = from_json(r#"{"schema": 7, "whatever": true}"#).unwrap_err().to_string();
+        assert!(err.contains("produced by a newer zk100"), "{}", err);
+    }
+
+    #[test]
+    fn test_missing_schema_rejected() {
+        let err = from_json(r#"{"rows": 2, "cols": 2, "nodes": []}"#).unwrap_err().to_string();
+        assert!(err.contains("missing \"schema\""), "{}", err);
+    }
+
+    #[test]
+    fn test_bad_instruction_located() {
+        let json = r#"{"schema": 1, "rows": 2, "cols": 2,
+            "nodes": [{"row": 1, "col": 0, "program": ["NOP", "MOV 1"]}]}"#;
+        let err = from_json(json).unwrap_err().to_string();
+        assert!(err.contains("node (1,0) pc 1"), "{}", err);
+    }
+}
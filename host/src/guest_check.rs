@@ -0,0 +1,354 @@
+//! Drift detector between the host instruction tables and the Cairo guest.
+//!
+//! `extract` scans every `.cairo` file under a directory line by line (after
+//! removing whitespace, so formatting does not matter) and recognizes:
+//!
+//! - constants as written by `gen-cairo-consts`: `pub const OP_MOV: felt252 = 1;`
+//! - field extraction in a decoder: `let op_val = (val / 0x10000) & 0xf;`
+//! - decoder if-chains: `else if val == 7 { Op::Jmp }`, also when the
+//!   variant is on the line after the `{`
+//! - encoder match arms `Op::Jmp => 7,` and packing terms `op_val * 0x10000 +`
+//! - a trailing `else { Op::Hlt }`, recorded as a fallthrough: the guest maps
+//!   every unlisted code to it, which is reported as a warning
+//!
+//! `check` compares what it found with the names and values `cairo_consts`
+//! generates. A differing value or a constant that cannot be found fails.
+
+use crate::instruction::{Dst, FIELDS, Op, PortTag, Src};
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A value the guest defines, with where it was found. `value` is `None` for
+/// a fallthrough `else` branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestConstant {
+    pub name: String,
+    pub value: Option<u64>,
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    Match { name: String, value: u64, at: String },
+    Mismatch { name: String, host: u64, guest: u64, at: String },
+    Missing { name: String, host: u64 },
+    Fallthrough { name: String, host: u64, at: String },
+}
+
+impl Finding {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Finding::Mismatch { .. } | Finding::Missing { .. })
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::Match { name, value, at } => write!(f, "ok        {} = {} ({})", name, value, at),
+            Finding::Mismatch { name, host, guest, at } => {
+                write!(f, "MISMATCH  {}: host {} (0x{:x}), guest {} (0x{:x}) at {}", name, host, host, guest, guest, at)
+            }
+            Finding::Missing { name, host } => write!(f, "MISSING   {}: host {}, not found in guest", name, host),
+            Finding::Fallthrough { name, host, at } => write!(
+                f,
+                "warning   {}: host {}, guest decodes it as the fallthrough branch at {}, so unassigned codes also map to it",
+                name, host, at
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        !self.findings.iter().any(Finding::is_failure)
+    }
+
+    pub fn count(&self, pred: fn(&Finding) -> bool) -> usize {
+        self.findings.iter().filter(|f| pred(f)).count()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for finding in &self.findings {
+            if !matches!(finding, Finding::Match { .. }) {
+                writeln!(f, "{}", finding)?;
+            }
+        }
+        write!(
+            f,
+            "{} constants checked: {} match, {} mismatch, {} missing, {} warnings",
+            self.findings.len(),
+            self.count(|f| matches!(f, Finding::Match { .. })),
+            self.count(|f| matches!(f, Finding::Mismatch { .. })),
+            self.count(|f| matches!(f, Finding::Missing { .. })),
+            self.count(|f| matches!(f, Finding::Fallthrough { .. })),
+        )
+    }
+}
+
+/// Every constant the guest should agree on, with the host's value and
+/// whether it must be present. `*_SHIFT` is optional because a decoder that
+/// divides only needs `*_DIVISOR`.
+pub fn host_constants() -> Vec<(String, u64, bool)> {
+    let mut out = Vec::new();
+    for (name, field) in FIELDS {
+        out.push((format!("{}_SHIFT", name), field.shift as u64, false));
+        out.push((format!("{}_DIVISOR", name), 1u64 << field.shift, true));
+        out.push((format!("{}_MASK", name), field.mask as u64, true));
+    }
+    out.extend(Op::ALL.iter().map(|op| (format!("OP_{}", op.mnemonic()), *op as u64, true)));
+    out.extend(Src::KINDS.iter().map(|s| (format!("SRC_{}", s.kind_name()), s.to_code() as u64, true)));
+    out.extend(Dst::KINDS.iter().map(|d| (format!("DST_{}", d.kind_name()), d.to_code() as u64, true)));
+    out.extend(PortTag::ALL.iter().map(|p| (format!("PORT_{}", p), *p as u64, true)));
+    out
+}
+
+fn parse_number(s: &str) -> Option<u64> {
+    let s = s.trim_matches(|c| c == '(' || c == ')');
+    // Drop a type suffix such as `_u32`.
+    let s = match s.rfind("_u").or_else(|| s.rfind("_felt")) {
+        Some(idx) => &s[..idx],
+        None => s,
+    };
+    let s = s.replace('_', "");
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// `Op::Jnz` → `OP_JNZ`, `Src::Lit(..)` → `SRC_LIT`, `PortTag::Up` → `PORT_UP`.
+fn variant_constant(code: &str) -> Option<String> {
+    let prefixes = [("Op::", "OP_"), ("Src::", "SRC_"), ("Dst::", "DST_"), ("PortTag::", "PORT_")];
+    let (path, prefix) = prefixes.iter().find(|(path, _)| code.starts_with(path))?;
+    let variant: String = code[path.len()..].chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+    (!variant.is_empty()).then(|| format!("{}{}", prefix, variant.to_uppercase()))
+}
+
+/// Constants found in one Cairo source file.
+pub fn extract_source(text: &str, file: &Path) -> Vec<GuestConstant> {
+    let mut found = Vec::new();
+    let mut push = |name: String, value: Option<u64>, line: usize| {
+        found.push(GuestConstant { name, value, file: file.to_path_buf(), line });
+    };
+    // Code from an `== N {` whose variant is expected on a later line.
+    let mut pending: Option<u64> = None;
+
+    for (idx, raw) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let code = raw.split("//").next().unwrap_or("");
+        let compact: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+        if compact.is_empty() {
+            continue;
+        }
+
+        if let Some(value) = pending.take() {
+            if let Some(name) = variant_constant(&compact) {
+                push(name, Some(value), line_no);
+                continue;
+            }
+        }
+
+        // pub const NAME: type = value;
+        if let Some(rest) = compact.strip_prefix("pub").unwrap_or(&compact).strip_prefix("const") {
+            if let Some((name, value)) = rest.split_once(':').and_then(|(name, rest)| {
+                let value = rest.split_once('=')?.1.trim_end_matches(';');
+                Some((name, parse_number(value)?))
+            }) {
+                push(name.to_string(), Some(value), line_no);
+            }
+            continue;
+        }
+
+        // let op_val = (val / 0x10000) & 0xf;
+        if let Some((var, expr)) = compact.strip_prefix("let").and_then(|rest| rest.split_once("_val=")) {
+            let field = var.to_uppercase();
+            if FIELDS.iter().any(|(name, _)| *name == field) && expr.contains(['/', '&']) {
+                let expr = expr.trim_end_matches(';').replace(['(', ')'], "");
+                let (shifted, mask) = match expr.split_once('&') {
+                    Some((shifted, mask)) => (shifted.to_string(), parse_number(mask)),
+                    None => (expr.clone(), None),
+                };
+                let divisor = match shifted.split_once('/') {
+                    Some((_, divisor)) => parse_number(divisor),
+                    None => Some(1),
+                };
+                if let Some(divisor) = divisor.filter(|d| *d > 0) {
+                    push(format!("{}_DIVISOR", field), Some(divisor), line_no);
+                    // Without a mask the field is everything above the divisor.
+                    let mask = mask.unwrap_or(u32::MAX as u64 / divisor);
+                    push(format!("{}_MASK", field), Some(mask), line_no);
+                }
+                continue;
+            }
+        }
+
+        // Op::Jmp => 7,   (encoder match arms)
+        if let Some((variant, value)) = compact.split_once("=>") {
+            if let (Some(name), Some(value)) =
+                (variant_constant(variant), parse_number(value.trim_end_matches(',')))
+            {
+                push(name, Some(value), line_no);
+                continue;
+            }
+        }
+
+        // op_val * 0x10000 +   (encoder packing)
+        if let Some((var, rest)) = compact.split_once('*') {
+            let field = var.trim_start_matches('(').trim_end_matches("_val").to_uppercase();
+            let multiplier = rest.trim_end_matches(['+', ')', ';']);
+            if let (true, Some(value)) =
+                (FIELDS.iter().any(|(name, _)| *name == field), parse_number(multiplier))
+            {
+                push(format!("{}_DIVISOR", field), Some(value), line_no);
+                continue;
+            }
+        }
+
+        // if val == 7 { Op::Jmp }  /  else if src_val == 4 {
+        if let Some((_, after)) = compact.split_once("==") {
+            if let Some((number, body)) = after.split_once('{') {
+                if let Some(value) = parse_number(number) {
+                    match variant_constant(body) {
+                        Some(name) => push(name, Some(value), line_no),
+                        None if body.is_empty() => pending = Some(value),
+                        None => {}
+                    }
+                }
+            }
+            continue;
+        }
+
+        // else { Op::Hlt }
+        if let Some(body) = compact.strip_prefix("else{").or_else(|| compact.strip_prefix("}else{")) {
+            if let Some(name) = variant_constant(body) {
+                push(name, None, line_no);
+            }
+        }
+    }
+    found
+}
+
+fn cairo_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| anyhow!("cannot read guest source {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if path.file_name().is_some_and(|n| n == "target") {
+                continue;
+            }
+            cairo_files(&path, out)?;
+        } else if path.extension().is_some_and(|e| e == "cairo") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Constants found in every `.cairo` file under `dir`; paths are relative to it.
+pub fn extract(dir: &Path) -> Result<Vec<GuestConstant>> {
+    let mut files = Vec::new();
+    cairo_files(dir, &mut files)?;
+    let mut found = Vec::new();
+    for path in files {
+        let text = fs::read_to_string(&path)?;
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        found.extend(extract_source(&text, relative));
+    }
+    Ok(found)
+}
+
+/// Compare `found` against the host tables.
+pub fn compare(found: &[GuestConstant]) -> Report {
+    let mut findings = Vec::new();
+    for (name, host, required) in host_constants() {
+        let definitions: Vec<&GuestConstant> = found.iter().filter(|c| c.name == name).collect();
+        if definitions.is_empty() {
+            if required {
+                findings.push(Finding::Missing { name, host });
+            }
+            continue;
+        }
+        for def in definitions {
+            let at = format!("{}:{}", def.file.display(), def.line);
+            findings.push(match def.value {
+                Some(guest) if guest == host => Finding::Match { name: name.clone(), value: host, at },
+                Some(guest) => Finding::Mismatch { name: name.clone(), host, guest, at },
+                None => Finding::Fallthrough { name: name.clone(), host, at },
+            });
+        }
+    }
+    Report { findings }
+}
+
+pub fn check(guest_src: &Path) -> Result<Report> {
+    Ok(compare(&extract(guest_src)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(text: &str) -> Vec<(String, Option<u64>, usize)> {
+        extract_source(text, Path::new("x.cairo"))
+            .into_iter()
+            .map(|c| (c.name, c.value, c.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_const_forms() {
+        let found = names("pub const OP_MOV: felt252 = 1;\nconst  OP_JZ :u32= 0x8_u32 ;\n// const OP_NOP: felt252 = 3;\n");
+        assert_eq!(found, [("OP_MOV".into(), Some(1), 1), ("OP_JZ".into(), Some(8), 2)]);
+    }
+
+    #[test]
+    fn test_extract_decoder_forms() {
+        let src = "\
+let lit_val = val / 0x1000000;
+let op_val = (val / 0x10000) & 0xf; // bits 16-19
+let dst_val = val & 0xff;
+let src = if src_val == 0 { Src::Lit(lit_val) }
+else if src_val == 4 {
+    // port
+    Src::P(decode_port_tag(src_port_val))
+}
+else { Src::Last };
+";
+        let found = names(src);
+        assert_eq!(
+            found,
+            [
+                ("LIT_DIVISOR".into(), Some(0x1000000), 1),
+                ("LIT_MASK".into(), Some(0xff), 1),
+                ("OP_DIVISOR".into(), Some(0x10000), 2),
+                ("OP_MASK".into(), Some(0xf), 2),
+                ("DST_DIVISOR".into(), Some(1), 3),
+                ("DST_MASK".into(), Some(0xff), 3),
+                ("SRC_LIT".into(), Some(0), 4),
+                ("SRC_P".into(), Some(4), 7),
+                ("SRC_LAST".into(), None, 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generated_consts_pass() {
+        let found = extract_source(&crate::cairo_consts::generate(), Path::new("consts.cairo"));
+        let report = compare(&found);
+        assert!(report.is_ok(), "{}", report);
+        assert_eq!(report.count(|f| matches!(f, Finding::Match { .. })), host_constants().len());
+    }
+}
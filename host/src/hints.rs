@@ -0,0 +1,206 @@
+//! Private hint files for guests that take witness data besides the args.
+//!
+//! A `HintGenerator` looks at one simulated run of the program, an
+//! `Execution` per instruction a node completed, and returns `Hint`s: the
+//! values the prover should supply at that node, pc and cycle. `Registry`
+//! runs every registered generator and collects their hints into a
+//! `HintsFile`, the `hints.json` that `assemble --hints` writes and
+//! `prove --hints` hands to the prover with `PROVER_FLAG`.
+//!
+//! Library users add generators with `Registry::register`. The built-in
+//! registry is empty for now: the ISA has no instruction whose result the
+//! guest needs precomputed, so an assemble with hints writes a valid file
+//! with no entries.
+
+use crate::assembler::Programs;
+use crate::cairo_abi::{InputSentinel, IoNodes};
+use crate::instruction::Inst;
+use crate::limits::Limits;
+use crate::sim::{Simulator, StepResult};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// Layout version of `hints.json`.
+pub const HINTS_VERSION: u32 = 1;
+
+/// The `cairo-prove prove` argument that names the hints file.
+pub const PROVER_FLAG: &str = "--hints-file";
+
+/// One instruction a node completed; the registers are as it found them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Execution {
+    pub node: (usize, usize),
+    pub pc: usize,
+    pub cycle: u64,
+    pub inst: Inst,
+    pub acc: u32,
+    pub bak: u32,
+}
+
+/// What generators see: the program, its inputs and the run.
+#[derive(Debug, Clone)]
+pub struct HintContext<'a> {
+    pub programs: &'a Programs,
+    pub inputs: &'a [u32],
+    /// In cycle order, and in node order within a cycle.
+    pub executions: Vec<Execution>,
+}
+
+impl<'a> HintContext<'a> {
+    /// Simulate `programs` on `inputs` as `assemble` would, recording every
+    /// completed instruction, until the run stops or reaches the cycle limit.
+    pub fn simulate(
+        programs: &'a Programs,
+        inputs: &'a [u32],
+        io: IoNodes,
+        sentinel: Option<InputSentinel>,
+        limits: Limits,
+    ) -> Result<Self> {
+        let mut sim = Simulator::with_io(programs, inputs, io)?.with_limits(limits)?.with_sentinel(sentinel);
+        let mut executions = Vec::new();
+        while sim.cycles() < limits.cycles.value {
+            let before: Vec<Vec<_>> = sim.nodes().to_vec();
+            let insts: Vec<Vec<Option<Inst>>> = (0..before.len())
+                .map(|r| (0..before[r].len()).map(|c| sim.current_inst(r, c)).collect())
+                .collect();
+            let cycle = sim.cycles();
+            let step = sim.step()?;
+            for (r, row) in before.iter().enumerate() {
+                for (c, node) in row.iter().enumerate() {
+                    let (Some(inst), false) = (insts[r][c], sim.nodes()[r][c].blocked) else {
+                        continue;
+                    };
+                    let pc = node.pc as usize % programs[r][c].len();
+                    executions.push(Execution { node: (r, c), pc, cycle, inst, acc: node.acc, bak: node.bak });
+                }
+            }
+            if step != StepResult::Continue {
+                break;
+            }
+        }
+        Ok(HintContext { programs, inputs, executions })
+    }
+}
+
+/// Values the prover supplies when `node` runs `pc` in `cycle`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hint {
+    pub node: (usize, usize),
+    pub pc: usize,
+    pub cycle: u64,
+    pub values: Vec<u32>,
+}
+
+/// A `Hint` tagged with the generator that produced it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HintEntry {
+    pub generator: String,
+    #[serde(flatten)]
+    pub hint: Hint,
+}
+
+/// `hints.json`: every entry in cycle order, then node and pc order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HintsFile {
+    pub version: u32,
+    pub hints: Vec<HintEntry>,
+}
+
+pub trait HintGenerator: Send + Sync {
+    /// Recorded with each hint; unique within a registry.
+    fn name(&self) -> &str;
+    fn generate(&self, context: &HintContext) -> Result<Vec<Hint>>;
+}
+
+#[derive(Default)]
+pub struct Registry {
+    generators: Vec<Box<dyn HintGenerator>>,
+}
+
+impl Registry {
+    /// The generators `assemble` runs. None yet; see the module docs.
+    pub fn builtin() -> Self {
+        Registry::default()
+    }
+
+    pub fn register(&mut self, generator: impl HintGenerator + 'static) -> Result<()> {
+        if self.names().contains(&generator.name()) {
+            return Err(anyhow!("a hint generator named {} is already registered", generator.name()));
+        }
+        self.generators.push(Box::new(generator));
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.generators.iter().map(|g| g.name()).collect()
+    }
+
+    pub fn generate(&self, context: &HintContext) -> Result<HintsFile> {
+        let mut hints = Vec::new();
+        for generator in &self.generators {
+            let generated = generator.generate(context).map_err(|e| anyhow!("hint generator {}: {}", generator.name(), e))?;
+            hints.extend(generated.into_iter().map(|hint| HintEntry { generator: generator.name().to_string(), hint }));
+        }
+        hints.sort_by_key(|entry| (entry.hint.cycle, entry.hint.node, entry.hint.pc));
+        Ok(HintsFile { version: HINTS_VERSION, hints })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+    use crate::instruction::{Op, Src};
+
+    /// What a DIV generator would do, on `SUB` with a literal for lack of a
+    /// DIV instruction: the quotient and remainder of ACC by the operand.
+    struct Quotients;
+
+    impl HintGenerator for Quotients {
+        fn name(&self) -> &str {
+            "quotients"
+        }
+
+        fn generate(&self, context: &HintContext) -> Result<Vec<Hint>> {
+            let hints = context.executions.iter().filter_map(|e| match (e.inst.op, e.inst.src) {
+                (Op::Sub, Src::Lit(divisor)) if divisor > 0 => Some(Hint {
+                    node: e.node,
+                    pc: e.pc,
+                    cycle: e.cycle,
+                    values: vec![e.acc / divisor, e.acc % divisor],
+                }),
+                _ => None,
+            });
+            Ok(hints.collect())
+        }
+    }
+
+    fn hints(code: &str, inputs: &[u32]) -> HintsFile {
+        let programs = assembler::parse_assembly(code).unwrap();
+        let context = HintContext::simulate(&programs, inputs, IoNodes::default(), None, Limits::default()).unwrap();
+        let mut registry = Registry::builtin();
+        registry.register(Quotients).unwrap();
+        registry.generate(&context).unwrap()
+    }
+
+    #[test]
+    fn test_generators_see_each_execution() {
+        let code = "NODE (0,0)\nMOV IN, ACC\nSUB 3\nSUB 2\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+        let file = hints(code, &[17]);
+        let found: Vec<(usize, u64, Vec<u32>)> =
+            file.hints.iter().map(|h| (h.hint.pc, h.hint.cycle, h.hint.values.clone())).collect();
+        // 17 / 3 = 5 r 2, then (17 - 3) / 2 = 7 r 0.
+        assert_eq!(found, [(1, 1, vec![5, 2]), (2, 2, vec![7, 0])]);
+        assert_eq!(
+            serde_json::to_value(&file.hints[0]).unwrap(),
+            serde_json::json!({"generator": "quotients", "node": [0, 0], "pc": 1, "cycle": 1, "values": [5, 2]})
+        );
+
+        let none = hints("NODE (0,0)\nMOV IN, ACC\nMOV ACC, P:DOWN\n", &[1]);
+        assert_eq!(serde_json::to_string(&none).unwrap(), r#"{"version":1,"hints":[]}"#);
+
+        let mut registry = Registry::builtin();
+        registry.register(Quotients).unwrap();
+        assert!(registry.register(Quotients).is_err());
+    }
+}
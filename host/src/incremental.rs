@@ -0,0 +1,213 @@
+//! Node-granular rebuilds for the LSP and other long-lived callers.
+//!
+//! `IncrementalAssembler::update` splits the source into `NODE` blocks with
+//! `assembler::node_ranges` and keys each node by a hash of its block text.
+//! A node whose text is unchanged since the last update reuses its parsed
+//! program, encoded words and Merkle leaf; only edited nodes are parsed again,
+//! and only the root and the `prog_words` around them are recomputed. Labels
+//! are local to a node, so a node's block and the file's `DEFINE` constants
+//! are all it takes to assemble it.
+//!
+//! The one-shot CLI path does not use this: it has nothing to reuse.
+
+use crate::assembler::{self, Constants, FileScope, GridSize, ParseOptions, Programs};
+use crate::instruction::Inst;
+use crate::{felt_bytes, merkle};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+/// One node, assembled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeArtifact {
+    pub program: Vec<Inst>,
+    pub words: Vec<u32>,
+    pub leaf: Vec<u8>,
+}
+
+impl NodeArtifact {
+    fn new(program: Vec<Inst>) -> Self {
+        let words: Vec<u32> = program.iter().map(Inst::encode).collect();
+        let leaf = merkle::leaf_hash(&words);
+        NodeArtifact { program, words, leaf }
+    }
+}
+
+/// What an update produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Build {
+    /// Every node that assembles; a node that does not is left empty.
+    pub programs: Programs,
+    /// Why each failing node does not assemble.
+    pub errors: BTreeMap<(usize, usize), String>,
+    /// Length-prefixed words of every node, when no node failed.
+    pub prog_words: Option<Vec<u32>>,
+    /// `0x`-hex program root, when no node failed.
+    pub root: Option<String>,
+}
+
+impl Build {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// How much work the updates so far have done.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters {
+    pub updates: usize,
+    /// Nodes parsed and encoded.
+    pub nodes_built: usize,
+    /// Nodes taken from the cache.
+    pub nodes_reused: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct IncrementalAssembler {
+    /// Keyed by the SHA-256 of a node's block text; holds the entries the
+    /// last update used.
+    cache: HashMap<Vec<u8>, Result<NodeArtifact, String>>,
+    counters: Counters,
+}
+
+impl IncrementalAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
+
+    /// Assemble `source`, reusing every node whose block text is unchanged.
+    /// Errors only when a `NODE` header or a constant itself is malformed.
+    pub fn update(&mut self, source: &str) -> Result<Build> {
+        let size = assembler::grid_size(source)?;
+        let file = assembler::file_scope(source, &Constants::new())?;
+        let pad_to = assembler::resolve_pad(source, None, &file.constants)?;
+        let ranges = assembler::node_ranges(source)?;
+        let lines: Vec<&str> = source.lines().collect();
+        let mut cache = HashMap::with_capacity(ranges.len());
+        // Cache key of each node with a block, row-major.
+        let mut keys: Vec<Option<Vec<u8>>> = vec![None; size.nodes()];
+
+        for (&(r, c), node_ranges) in &ranges {
+            let text: String = node_ranges
+                .iter()
+                .flat_map(|range| &lines[range.start - 1..range.end - 1])
+                .map(|line| format!("{}\n", line))
+                .collect();
+            // The grid size, the padding and the file scope are part of the
+            // key: they decide which coordinates parse, how long a node is,
+            // what the operands mean and which names a node may define.
+            let mut hasher = Sha256::new().chain_update(format!("{} {:?};", size, pad_to));
+            for (name, value) in &file.constants {
+                hasher.update(format!("{}={};", name, value));
+            }
+            for (name, line) in &file.globals {
+                hasher.update(format!("GLOBAL {}@{};", name, line));
+            }
+            let key = hasher.chain_update(text.as_bytes()).finalize().to_vec();
+            let entry = match self.cache.remove(&key) {
+                Some(entry) => {
+                    self.counters.nodes_reused += 1;
+                    entry
+                }
+                None => {
+                    self.counters.nodes_built += 1;
+                    assemble_node(&text, (r, c), size, pad_to, &file)
+                }
+            };
+            cache.insert(key.clone(), entry);
+            keys[r * size.cols + c] = Some(key);
+        }
+        self.cache = cache;
+        self.counters.updates += 1;
+
+        let mut build = Build {
+            programs: size.empty_programs(),
+            errors: BTreeMap::new(),
+            prog_words: None,
+            root: None,
+        };
+        let mut prog_words = Vec::new();
+        let mut leaves = Vec::with_capacity(keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            let (r, c) = (i / size.cols, i % size.cols);
+            match key.as_ref().map(|key| &self.cache[key]) {
+                None => {
+                    prog_words.push(0);
+                    leaves.push(merkle::leaf_hash(&[]));
+                }
+                Some(Ok(artifact)) => {
+                    build.programs[r][c] = artifact.program.clone();
+                    prog_words.push(artifact.words.len() as u32);
+                    prog_words.extend_from_slice(&artifact.words);
+                    leaves.push(artifact.leaf.clone());
+                }
+                Some(Err(message)) => {
+                    build.errors.insert((r, c), message.clone());
+                }
+            }
+        }
+        if build.is_ok() {
+            build.prog_words = Some(prog_words);
+            build.root = Some(felt_bytes::hex32(&felt_bytes::bytes32(&merkle::root_from_leaves(&leaves))?));
+        }
+        Ok(build)
+    }
+}
+
+fn assemble_node(
+    text: &str,
+    (r, c): (usize, usize),
+    size: GridSize,
+    pad_to: Option<usize>,
+    file: &FileScope,
+) -> Result<NodeArtifact, String> {
+    let options = ParseOptions { grid: Some(size), pad_to, ..Default::default() };
+    match assembler::parse_with_constants(text, &Default::default(), options, file.clone()) {
+        Ok(parsed) => Ok(NodeArtifact::new(parsed.grid.programs[r][c].clone())),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reopened_node_is_one_entry() {
+        let source = "NODE (0,0)\nx:\nNOP\nNODE (1,1)\nNOP\nNODE (0,0) CONTINUE\nJMP x\n";
+        let mut assembler = IncrementalAssembler::new();
+        let build = assembler.update(source).unwrap();
+        assert_eq!(build.programs, assembler::parse_assembly(source).unwrap());
+        assert_eq!(assembler.counters().nodes_built, 2);
+    }
+
+    #[test]
+    fn test_broken_node_keeps_the_rest() {
+        let mut assembler = IncrementalAssembler::new();
+        let build = assembler.update("NODE (0,0)\nNOP\nNODE (1,0)\nFROB\n").unwrap();
+        assert_eq!(build.errors.keys().collect::<Vec<_>>(), [&(1, 0)]);
+        assert_eq!(build.programs[0][0].len(), 1);
+        assert!(build.root.is_none() && build.prog_words.is_none());
+        assert!(assembler.update("NODE (5,0)\n").is_err());
+    }
+
+    #[test]
+    fn test_node_constants_stay_in_their_node() {
+        let source = "GLOBAL LIMIT 3\nNODE (0,0)\nDEFINE N 1\nADD N\nADD LIMIT\nNODE (1,1)\nDEFINE LIMIT 4\n";
+        let build = IncrementalAssembler::new().update(source).unwrap();
+        assert_eq!(build.programs[0][0], assembler::parse_assembly(&source.replace("DEFINE LIMIT 4\n", "")).unwrap()[0][0]);
+        assert!(build.errors[&(1, 1)].contains("cannot define 'LIMIT'; it is GLOBAL (line 1)"), "{:?}", build.errors);
+    }
+
+    #[test]
+    fn test_padding_matches_a_full_parse() {
+        let source = ".pad 4\nNODE (0,0)\nNOP\nNODE (1,1)\nMOV IN, ACC\nMOV ACC, OUT\n";
+        let build = IncrementalAssembler::new().update(source).unwrap();
+        assert_eq!(build.programs, assembler::parse_assembly(source).unwrap());
+        assert_eq!(build.programs[1][1].len(), 4);
+    }
+}
@@ -0,0 +1,441 @@
+//! Generated input streams: `InputSpec` and the syntax `--inputs-gen`,
+//! report manifests and queue jobs share.
+//!
+//! A spec is written as a TOML inline table (or, in JSON documents, the
+//! equivalent object) with one generator key:
+//!
+//! - `{ range = [1, 100] }` – 1, 2, .., 100 (inclusive);
+//! - `{ repeat = [7, 25] }` – the value 7, 25 times;
+//! - `{ random = { len = 64, min = 0, max = 999, seed = 3 } }` – `len` values
+//!   in `min..=max` from a seeded SplitMix64 stream, identical on every run;
+//! - `{ file = "data.csv", column = 2 }` – the 1-based `column` of a CSV file
+//!   (relative to the manifest, or the working directory on the CLI); a
+//!   first row that is not numeric is taken as a header and skipped.
+//!
+//! `expand` turns a spec into concrete values. Metadata records the spec next
+//! to the values it expanded to.
+
+use crate::{exec, instruction};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Longest stream a generator may produce.
+pub const MAX_GENERATED: u64 = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Value", into = "Value")]
+pub enum InputSpec {
+    Range { first: u32, last: u32 },
+    Repeat { value: u32, count: u64 },
+    Random { len: u64, min: u32, max: u32, seed: u64 },
+    File { path: PathBuf, column: usize },
+}
+
+impl InputSpec {
+    /// Parse the TOML inline-table form, e.g. `{ range = [1, 100] }`.
+    pub fn parse(text: &str) -> Result<Self> {
+        InputSpec::try_from(parse_inline(text, "the input spec")?)
+    }
+
+    /// The concrete input values. `base` resolves `file` paths.
+    pub fn expand(&self, base: &Path) -> Result<Vec<u32>> {
+        match *self {
+            InputSpec::Range { first, last } => Ok((first..=last).collect()),
+            InputSpec::Repeat { value, count } => Ok(vec![value; count as usize]),
+            InputSpec::Random { len, min, max, seed } => {
+                let span = u64::from(max - min) + 1;
+                let mut state = seed;
+                Ok((0..len).map(|_| min + (splitmix64(&mut state) % span) as u32).collect())
+            }
+            InputSpec::File { ref path, column } => read_csv_column(&base.join(path), column),
+        }
+    }
+}
+
+/// The `-i` form of a stream: comma-separated literals, read as in source
+/// (hex and binary too, negative values wrapping to `u32`).
+pub fn parse_values(text: &str) -> Result<Vec<u32>> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| instruction::parse_literal(v).ok_or_else(|| anyhow!("'{}' is not a value", v)))
+        .collect()
+}
+
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn read_csv_column(path: &Path, column: usize) -> Result<Vec<u32>> {
+    let text = fs::read_to_string(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    exec::note_read(path);
+    let mut values = Vec::new();
+    let mut first = true;
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let at = format!("{}:{}", path.display(), idx + 1);
+        let cell = line
+            .split(',')
+            .nth(column - 1)
+            .map(|c| c.trim().trim_matches('"').trim())
+            .ok_or_else(|| anyhow!("{}: no column {}", at, column))?;
+        match cell.parse::<u32>() {
+            Ok(value) => values.push(value),
+            Err(_) if first => {}
+            Err(_) => return Err(anyhow!("{}: '{}' in column {} is not a u32 value", at, cell, column)),
+        }
+        first = false;
+    }
+    Ok(values)
+}
+
+fn field<'a>(table: &'a Map<String, Value>, key: &str, generator: &str) -> Result<&'a Value> {
+    table.get(key).ok_or_else(|| anyhow!("{} needs {}", generator, key))
+}
+
+fn integer(value: &Value, what: &str) -> Result<u64> {
+    value.as_u64().ok_or_else(|| anyhow!("{} must be a non-negative integer, found {}", what, value))
+}
+
+fn to_u32(n: u64, what: &str) -> Result<u32> {
+    u32::try_from(n).map_err(|_| anyhow!("{} must fit in a u32, found {}", what, n))
+}
+
+fn small(value: &Value, what: &str) -> Result<u32> {
+    to_u32(integer(value, what)?, what)
+}
+
+fn pair(value: &Value, what: &str) -> Result<(u64, u64)> {
+    match value.as_array().map(Vec::as_slice) {
+        Some([a, b]) => Ok((integer(a, what)?, integer(b, what)?)),
+        _ => Err(anyhow!("{} must be a two-element array, found {}", what, value)),
+    }
+}
+
+fn check_len(len: u64) -> Result<u64> {
+    if len > MAX_GENERATED {
+        return Err(anyhow!("generator would produce {} values, more than the limit of {}", len, MAX_GENERATED));
+    }
+    Ok(len)
+}
+
+impl TryFrom<Value> for InputSpec {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> Result<Self> {
+        let table = value.as_object().ok_or_else(|| anyhow!("an input spec is a table, found {}", value))?;
+        let generators: Vec<&str> =
+            ["range", "repeat", "random", "file"].into_iter().filter(|k| table.contains_key(*k)).collect();
+        let allowed: &[&str] = match generators.as_slice() {
+            [] => return Err(anyhow!("an input spec needs one of range, repeat, random or file")),
+            ["file"] => &["file", "column"],
+            [_] => &generators,
+            _ => return Err(anyhow!("an input spec has one generator, found {}", generators.join(", "))),
+        };
+        if table.keys().any(|k| !allowed.contains(&k.as_str())) {
+            let keys: Vec<&str> = table.keys().map(String::as_str).collect();
+            return Err(anyhow!("unexpected keys in input spec: {}", keys.join(", ")));
+        }
+        if let Some(range) = table.get("range") {
+            let (first, last) = pair(range, "range")?;
+            let (first, last) = (to_u32(first, "range start")?, to_u32(last, "range end")?);
+            if first > last {
+                return Err(anyhow!("range [{}, {}] is empty", first, last));
+            }
+            check_len(u64::from(last - first) + 1)?;
+            Ok(InputSpec::Range { first, last })
+        } else if let Some(repeat) = table.get("repeat") {
+            let (value, count) = pair(repeat, "repeat")?;
+            Ok(InputSpec::Repeat { value: to_u32(value, "repeated value")?, count: check_len(count)? })
+        } else if let Some(random) = table.get("random") {
+            let random = random.as_object().ok_or_else(|| anyhow!("random takes a table of len, min, max and seed"))?;
+            if let Some(extra) = random.keys().find(|k| !["len", "min", "max", "seed"].contains(&k.as_str())) {
+                return Err(anyhow!("unexpected key {} in random", extra));
+            }
+            let len = check_len(integer(field(random, "len", "random")?, "len")?)?;
+            let min = small(field(random, "min", "random")?, "min")?;
+            let max = small(field(random, "max", "random")?, "max")?;
+            let seed = integer(field(random, "seed", "random")?, "seed")?;
+            if min > max {
+                return Err(anyhow!("random min {} is above max {}", min, max));
+            }
+            Ok(InputSpec::Random { len, min, max, seed })
+        } else {
+            let path = table["file"].as_str().ok_or_else(|| anyhow!("file must be a string path"))?;
+            let column = match table.get("column") {
+                Some(column) => integer(column, "column")? as usize,
+                None => 1,
+            };
+            if column == 0 {
+                return Err(anyhow!("column is 1-based"));
+            }
+            Ok(InputSpec::File { path: path.into(), column })
+        }
+    }
+}
+
+impl From<InputSpec> for Value {
+    fn from(spec: InputSpec) -> Value {
+        match spec {
+            InputSpec::Range { first, last } => json!({ "range": [first, last] }),
+            InputSpec::Repeat { value, count } => json!({ "repeat": [value, count] }),
+            InputSpec::Random { len, min, max, seed } => {
+                json!({ "random": { "len": len, "min": min, "max": max, "seed": seed } })
+            }
+            InputSpec::File { path, column } => json!({ "file": path.to_string_lossy(), "column": column }),
+        }
+    }
+}
+
+/// The JSON form above is a one-key object per generator, except `file`,
+/// which sits beside its `column`.
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for InputSpec {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "InputSpec".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        let pair = |description: &str| json!({ "type": "array", "items": { "type": "integer", "minimum": 0 }, "minItems": 2, "maxItems": 2, "description": description });
+        let only = |properties: Value| {
+            let required: Vec<&String> = properties.as_object().map(|p| p.keys().collect()).unwrap_or_default();
+            json!({ "type": "object", "properties": properties, "required": required, "additionalProperties": false })
+        };
+        let count = json!({ "type": "integer", "minimum": 0 });
+        schemars::json_schema!({
+            "description": "The generator the inputs were expanded from.",
+            "oneOf": [
+                only(json!({ "range": pair("First and last value, inclusive.") })),
+                only(json!({ "repeat": pair("The value and how many times it repeats.") })),
+                only(json!({ "random": only(json!({ "len": count, "min": count, "max": count, "seed": count })) })),
+                only(json!({
+                    "file": { "type": "string", "description": "CSV file, relative to the manifest." },
+                    "column": { "type": "integer", "minimum": 1, "description": "1-based column." },
+                })),
+            ],
+        })
+    }
+}
+
+/// The TOML inline-table form `parse` reads.
+impl fmt::Display for InputSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputSpec::Range { first, last } => write!(f, "{{ range = [{}, {}] }}", first, last),
+            InputSpec::Repeat { value, count } => write!(f, "{{ repeat = [{}, {}] }}", value, count),
+            InputSpec::Random { len, min, max, seed } => {
+                write!(f, "{{ random = {{ len = {}, min = {}, max = {}, seed = {} }} }}", len, min, max, seed)
+            }
+            InputSpec::File { path, column } => write!(f, "{{ file = {:?}, column = {} }}", path.display().to_string(), column),
+        }
+    }
+}
+
+/// Inputs given either as values or as a generator, as queue job manifests
+/// accept them: `"inputs": [1, 2]` or `"inputs": {"range": [1, 100]}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Inputs {
+    Values(Vec<u32>),
+    Spec(InputSpec),
+}
+
+impl Default for Inputs {
+    fn default() -> Self {
+        Inputs::Values(Vec::new())
+    }
+}
+
+impl Inputs {
+    pub fn expand(&self, base: &Path) -> Result<Vec<u32>> {
+        match self {
+            Inputs::Values(values) => Ok(values.clone()),
+            Inputs::Spec(spec) => spec.expand(base),
+        }
+    }
+
+    pub fn spec(&self) -> Option<&InputSpec> {
+        match self {
+            Inputs::Values(_) => None,
+            Inputs::Spec(spec) => Some(spec),
+        }
+    }
+}
+
+/// One TOML inline value filling all of `text`; `what` names it in errors.
+pub(crate) fn parse_inline(text: &str, what: &str) -> Result<Value> {
+    let mut parser = Parser { text, pos: 0 };
+    let value = parser.value()?;
+    parser.skip_space();
+    if parser.pos != text.len() {
+        return Err(anyhow!("unexpected '{}' after {}", &text[parser.pos..], what));
+    }
+    Ok(value)
+}
+
+/// Just enough TOML for inline tables: integers, basic strings, arrays and
+/// nested inline tables.
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        while self.text[self.pos..].starts_with(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        if self.text[self.pos..].starts_with(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(anyhow!("expected '{}' at column {} of '{}'", c, self.pos + 1, self.text))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip_space();
+        let rest = &self.text[self.pos..];
+        if self.eat('{') {
+            let mut table = Map::new();
+            if self.eat('}') {
+                return Ok(Value::Object(table));
+            }
+            loop {
+                self.skip_space();
+                let key_len = self.text[self.pos..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(self.text.len() - self.pos);
+                if key_len == 0 {
+                    return Err(anyhow!("expected a key at column {} of '{}'", self.pos + 1, self.text));
+                }
+                let key = self.text[self.pos..self.pos + key_len].to_string();
+                self.pos += key_len;
+                self.expect('=')?;
+                let value = self.value()?;
+                if table.insert(key.clone(), value).is_some() {
+                    return Err(anyhow!("key {} given twice", key));
+                }
+                if self.eat('}') {
+                    return Ok(Value::Object(table));
+                }
+                self.expect(',')?;
+            }
+        } else if self.eat('[') {
+            let mut items = Vec::new();
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            loop {
+                items.push(self.value()?);
+                if self.eat(']') {
+                    return Ok(Value::Array(items));
+                }
+                self.expect(',')?;
+            }
+        } else if let Some(body) = rest.strip_prefix('"') {
+            let end = body.find('"').ok_or_else(|| anyhow!("unterminated string in '{}'", self.text))?;
+            self.pos += end + 2;
+            Ok(Value::String(body[..end].to_string()))
+        } else {
+            let len = rest.find(|c: char| !(c.is_ascii_digit() || c == '_')).unwrap_or(rest.len());
+            let digits = rest[..len].replace('_', "");
+            let number: u64 = digits
+                .parse()
+                .map_err(|_| anyhow!("expected a value at column {} of '{}'", self.pos + 1, self.text))?;
+            self.pos += len;
+            Ok(Value::from(number))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(text: &str) -> Vec<u32> {
+        InputSpec::parse(text).unwrap().expand(Path::new(".")).unwrap()
+    }
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(parse_values(" 1, 0x10,-1 ,").unwrap(), [1, 16, u32::MAX]);
+        assert_eq!(parse_values("").unwrap(), [] as [u32; 0]);
+        assert_eq!(parse_values("1, two, 3").unwrap_err().to_string(), "'two' is not a value");
+    }
+
+    #[test]
+    fn test_range_and_repeat() {
+        assert_eq!(expand("{ range = [3, 7] }"), [3, 4, 5, 6, 7]);
+        assert_eq!(expand("{repeat=[7,3]}"), [7, 7, 7]);
+        assert!(InputSpec::parse("{ range = [7, 3] }").is_err());
+    }
+
+    #[test]
+    fn test_random_is_seeded() {
+        let spec = "{ random = { len = 64, min = 10, max = 20, seed = 3 } }";
+        let a = expand(spec);
+        assert_eq!(a.len(), 64);
+        assert!(a.iter().all(|v| (10..=20).contains(v)));
+        assert_eq!(a, expand(spec));
+        assert_ne!(a, expand("{ random = { len = 64, min = 10, max = 20, seed = 4 } }"));
+        assert_eq!(expand("{ random = { len = 3, min = 5, max = 5, seed = 0 } }"), [5, 5, 5]);
+    }
+
+    #[test]
+    fn test_round_trips_through_display_and_json() {
+        for text in [
+            "{ range = [1, 100] }",
+            "{ repeat = [7, 25] }",
+            "{ random = { len = 64, min = 0, max = 999, seed = 3 } }",
+            "{ file = \"data.csv\", column = 2 }",
+        ] {
+            let spec = InputSpec::parse(text).unwrap();
+            assert_eq!(spec.to_string(), text);
+            let json = serde_json::to_string(&spec).unwrap();
+            assert_eq!(serde_json::from_str::<InputSpec>(&json).unwrap(), spec);
+        }
+    }
+
+    #[test]
+    fn test_malformed_specs() {
+        let err = |text: &str| InputSpec::parse(text).unwrap_err().to_string();
+        assert_eq!(err("{ ranges = [1, 2] }"), "an input spec needs one of range, repeat, random or file");
+        assert_eq!(err("{ range = [1, 2], seed = 3 }"), "unexpected keys in input spec: range, seed");
+        assert_eq!(err("{ random = { len = 2, min = 0, max = 9 } }"), "random needs seed");
+        assert!(err("{ range = [1, 2] } x").contains("after the input spec"));
+        assert!(err("{ repeat = [1, 99999999] }").contains("limit"));
+        assert_eq!(err("{ file = \"a.csv\", column = 0 }"), "column is 1-based");
+    }
+
+    #[test]
+    fn test_inputs_accept_values_or_spec() {
+        let values: Inputs = serde_json::from_str("[1, 2]").unwrap();
+        let spec: Inputs = serde_json::from_str(r#"{"range": [1, 3]}"#).unwrap();
+        assert_eq!(values.expand(Path::new(".")).unwrap(), [1, 2]);
+        assert_eq!(spec.expand(Path::new(".")).unwrap(), [1, 2, 3]);
+        assert!(serde_json::from_str::<Inputs>(r#"{"range": [3, 1]}"#).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Tokenizer for zk-100 assembly.
+//!
+//! This is the only tokenization of the language: `parse_assembly`, the LSP
+//! and any formatter or highlighter all consume `lex`/`lex_line`. Comments are
+//! kept as tokens so tools that rewrite source can preserve them; the parser
+//! skips them.
+//!
+//! Lines are 1-based. Columns count characters, are 1-based, and `col_end` is
+//! exclusive, so `col_end - col_start` is the token's length in characters.
+//!
+//! Source that did not come through `source::decode` is read as it is: any
+//! Unicode whitespace separates tokens (tabs, a non-breaking space, a `\r`
+//! left from CRLF), as does a byte order mark, wherever it is. A word may hold
+//! non-ASCII letters so that it stays one token; the assembler rejects it
+//! whole (`assembler::non_ascii`).
+
+use crate::instruction::Op;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// `NODE`
+    NodeKeyword,
+    /// An opcode name, in any case (`MOV`, `jnz`).
+    Mnemonic,
+    /// Register names, port names and labels.
+    Ident,
+    /// A literal, optionally negative (`42`, `-1`).
+    Number,
+    /// `P:` introducing a port name.
+    PortPrefix,
+    Comma,
+    Colon,
+    LParen,
+    RParen,
+    /// `# ...`, `// ...` or `; ...` (TIS-100 style) up to the end of the line.
+    Comment,
+    /// Any character the language does not use.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub line: usize,
+    pub col_start: usize,
+    pub col_end: usize,
+}
+
+impl Token<'_> {
+    /// True if `next` starts exactly where this token ends.
+    pub fn touches(&self, next: &Token) -> bool {
+        self.line == next.line && self.col_end == next.col_start
+    }
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_' || ch == '.'
+}
+
+/// True for the characters that separate tokens: Unicode whitespace and the
+/// byte order mark (U+FEFF).
+pub fn is_space(ch: char) -> bool {
+    ch.is_whitespace() || ch == '\u{feff}'
+}
+
+/// Tokenize a whole source file.
+pub fn lex(source: &str) -> Vec<Token<'_>> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(idx, line)| lex_line(line, idx + 1))
+        .collect()
+}
+
+/// Tokenize one line; `line_no` is recorded on every token.
+pub fn lex_line(line: &str, line_no: usize) -> Vec<Token<'_>> {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let byte_at = |i: usize| chars.get(i).map_or(line.len(), |&(b, _)| b);
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i].1;
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        let start = i;
+
+        let kind = if is_space(ch) {
+            i += 1;
+            continue;
+        } else if ch == '#' || ch == ';' || (ch == '/' && next == Some('/')) {
+            i = chars.len();
+            TokenKind::Comment
+        } else if ch == ',' {
+            i += 1;
+            TokenKind::Comma
+        } else if ch == ':' {
+            i += 1;
+            TokenKind::Colon
+        } else if ch == '(' {
+            i += 1;
+            TokenKind::LParen
+        } else if ch == ')' {
+            i += 1;
+            TokenKind::RParen
+        } else if ch.is_ascii_digit() || (ch == '-' && next.is_some_and(|c| c.is_ascii_digit())) {
+            i += 1;
+            while i < chars.len() && is_word_char(chars[i].1) {
+                i += 1;
+            }
+            TokenKind::Number
+        } else if is_word_char(ch) {
+            while i < chars.len() && is_word_char(chars[i].1) {
+                i += 1;
+            }
+            let word = &line[byte_at(start)..byte_at(i)];
+            let after = chars.get(i + 1).map(|&(_, c)| c);
+            if word.eq_ignore_ascii_case("P")
+                && chars.get(i).map(|&(_, c)| c) == Some(':')
+                && after.is_some_and(|c| c.is_ascii_alphabetic())
+            {
+                i += 1;
+                TokenKind::PortPrefix
+            } else if word == "NODE" {
+                TokenKind::NodeKeyword
+            } else if Op::from_str(word).is_ok() {
+                TokenKind::Mnemonic
+            } else {
+                TokenKind::Ident
+            }
+        } else {
+            i += 1;
+            TokenKind::Unknown
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &line[byte_at(start)..byte_at(i)],
+            line: line_no,
+            col_start: start + 1,
+            col_end: i + 1,
+        });
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenKind::*;
+
+    fn spans(line: &str) -> Vec<(TokenKind, &str, usize, usize)> {
+        lex_line(line, 1).iter().map(|t| (t.kind, t.text, t.col_start, t.col_end)).collect()
+    }
+
+    #[test]
+    fn test_instruction_line() {
+        assert_eq!(
+            spans("    MOV P:LEFT, ACC  # forward"),
+            [
+                (Mnemonic, "MOV", 5, 8),
+                (PortPrefix, "P:", 9, 11),
+                (Ident, "LEFT", 11, 15),
+                (Comma, ",", 15, 16),
+                (Ident, "ACC", 17, 20),
+                (Comment, "# forward", 22, 31),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_node_and_label_lines() {
+        assert_eq!(
+            spans("NODE (0, 1)"),
+            [
+                (NodeKeyword, "NODE", 1, 5),
+                (LParen, "(", 6, 7),
+                (Number, "0", 7, 8),
+                (Comma, ",", 8, 9),
+                (Number, "1", 10, 11),
+                (RParen, ")", 11, 12),
+            ]
+        );
+        assert_eq!(spans("loop:"), [(Ident, "loop", 1, 5), (Colon, ":", 5, 6)]);
+        // A label that happens to spell a port prefix is still a label.
+        assert_eq!(spans("p:"), [(Ident, "p", 1, 2), (Colon, ":", 2, 3)]);
+    }
+
+    #[test]
+    fn test_numbers_comments_and_unknown() {
+        assert_eq!(
+            spans("add -1 // x"),
+            [(Mnemonic, "add", 1, 4), (Number, "-1", 5, 7), (Comment, "// x", 8, 12)]
+        );
+        assert_eq!(spans("NEG;flip"), [(Mnemonic, "NEG", 1, 4), (Comment, ";flip", 4, 9)]);
+        assert_eq!(spans("MOV 1,ACC!"), [
+            (Mnemonic, "MOV", 1, 4),
+            (Number, "1", 5, 6),
+            (Comma, ",", 6, 7),
+            (Ident, "ACC", 7, 10),
+            (Unknown, "!", 10, 11),
+        ]);
+        // Columns count characters, not bytes.
+        assert_eq!(spans("é JMP"), [(Ident, "é", 1, 2), (Mnemonic, "JMP", 3, 6)]);
+        assert_eq!(spans("→"), [(Unknown, "→", 1, 2)]);
+    }
+
+    #[test]
+    fn test_lex_numbers_lines() {
+        let tokens = lex("NODE (0,0)\n\n# c\nHLT\n");
+        let lines: Vec<usize> = tokens.iter().map(|t| t.line).collect();
+        assert_eq!(lines, [1, 1, 1, 1, 1, 1, 3, 4]);
+    }
+}
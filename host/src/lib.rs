@@ -0,0 +1,115 @@
+//! ZK-100 host library: assembly parsing, instruction encoding, program
+//! commitments and Cairo ABI generation.
+//!
+//! The assembler and encoder always build. The rest is split into Cargo
+//! features (all on by default): `merkle` for program roots, `abi-json` for
+//! args.json and the other JSON formats, `sim` for the host simulator, and
+//! `cli` for the binary. Modules that need several of them say so in their
+//! `cfg`; the feature matrix in `tests/feature_matrix.rs` builds every
+//! combination.
+
+pub mod abi;
+pub mod assembler;
+pub mod cairo_consts;
+pub mod config;
+pub mod dialect;
+pub mod directives;
+pub mod exec;
+pub mod expr;
+pub mod grid;
+pub mod guest_check;
+pub mod lexer;
+pub mod limits;
+pub mod listing;
+pub mod literals;
+pub mod scarb;
+pub mod source;
+pub mod tis100;
+pub mod trace;
+pub mod word_list;
+
+/// Package and git facts `build.rs` records with `built`.
+pub mod built_info {
+    include!(concat!(env!("OUT_DIR"), "/built.rs"));
+}
+
+#[cfg(feature = "abi-json")]
+pub mod args_stream;
+#[cfg(feature = "abi-json")]
+pub mod bisect;
+#[cfg(feature = "abi-json")]
+pub mod cairo_abi;
+#[cfg(feature = "abi-json")]
+pub mod expected;
+#[cfg(feature = "abi-json")]
+pub mod inputs;
+#[cfg(feature = "abi-json")]
+pub mod source_streams;
+#[cfg(feature = "abi-json")]
+pub mod stats;
+
+#[cfg(feature = "merkle")]
+pub mod felt_bytes;
+#[cfg(feature = "merkle")]
+pub mod incremental;
+#[cfg(feature = "merkle")]
+pub mod merkle;
+#[cfg(feature = "merkle")]
+pub mod migrate;
+#[cfg(feature = "merkle")]
+pub mod program_id;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+#[cfg(all(feature = "merkle", feature = "abi-json"))]
+pub mod proof_reader;
+
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(all(feature = "abi-json", feature = "sim"))]
+pub mod program_gen;
+
+// The pipeline: everything below assembles, commits, writes args and runs
+// the simulator.
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod artifacts;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod attest;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod build;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod bundle;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod capabilities;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod conformance;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod corpus;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod daemon;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod examples;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod hints;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod minimize;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod queue;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod report;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod seal;
+#[cfg(all(feature = "merkle", feature = "abi-json", feature = "sim"))]
+pub mod session;
+#[cfg(feature = "schema")]
+pub mod schema;
+
+/// Instruction types and the word codec, provided by `zk100-core`.
+pub use zk100_core::instruction;
+pub use zk100_core::CoreError;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+#[cfg(feature = "tui")]
+pub mod tui;
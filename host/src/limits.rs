@@ -0,0 +1,242 @@
+//! Resource limits shared by the assembler, the encoder, args generation and
+//! the simulator.
+//!
+//! Each limit has a default, may be set in a `[limits]` table of
+//! `zk100.toml` (`node_instructions = 64`) and overridden for one command
+//! with `--limit NAME=VALUE`. A `Limits` remembers where each value came
+//! from, and every violation is a `LimitError` naming the limit, its value
+//! and that origin, so a lowered limit in some config file is easy to find.
+//!
+//! The guest itself only bounds cycles. The other defaults are what the
+//! encoding can address (a jump target is an 8-bit literal, so a node's last
+//! reachable pc is 255) or generous sanity bounds.
+
+use crate::assembler::{GRID_COLS, GRID_ROWS};
+use crate::config;
+use crate::instruction::LIT_FIELD;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+/// Table of `zk100.toml` the project's limits live in.
+pub const LIMITS_TABLE: &str = "limits";
+
+/// Cycles the guest runs before stopping (`MAX_CYCLES` in exec/src/main.cairo).
+pub const DEFAULT_CYCLES: u64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Limit {
+    /// Instructions in one node's program.
+    NodeInstructions,
+    /// Words of the encoded grid, length prefixes included.
+    ProgWords,
+    /// Values on the input stream.
+    Inputs,
+    /// Expected or simulated output values.
+    Outputs,
+    /// Cycles a simulation may run.
+    Cycles,
+    /// Elements of an args.json array.
+    ArgsElements,
+}
+
+impl Limit {
+    pub const ALL: [Limit; 6] =
+        [Limit::NodeInstructions, Limit::ProgWords, Limit::Inputs, Limit::Outputs, Limit::Cycles, Limit::ArgsElements];
+
+    /// The key in `[limits]` and `--limit`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Limit::NodeInstructions => "node_instructions",
+            Limit::ProgWords => "prog_words",
+            Limit::Inputs => "inputs",
+            Limit::Outputs => "outputs",
+            Limit::Cycles => "cycles",
+            Limit::ArgsElements => "args_elements",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        Limit::ALL.into_iter().find(|l| l.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = Limit::ALL.iter().map(|l| l.name()).collect();
+            anyhow!("unknown limit {}; expected one of {}", name, names.join(", "))
+        })
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Where a limit's value was set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    #[default]
+    Default,
+    /// The `[limits]` table of `zk100.toml`.
+    Config,
+    /// `--limit` on the command line.
+    Flag,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Origin::Default => "default",
+            Origin::Config => config::PROJECT_CONFIG,
+            Origin::Flag => "--limit",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Setting {
+    pub value: u64,
+    pub origin: Origin,
+}
+
+impl Setting {
+    const fn default(value: u64) -> Self {
+        Setting { value, origin: Origin::Default }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Limits {
+    pub node_instructions: Setting,
+    pub prog_words: Setting,
+    pub inputs: Setting,
+    pub outputs: Setting,
+    pub cycles: Setting,
+    pub args_elements: Setting,
+}
+
+const NODE_INSTRUCTIONS: u64 = LIT_FIELD.mask as u64 + 1;
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            node_instructions: Setting::default(NODE_INSTRUCTIONS),
+            prog_words: Setting::default((GRID_ROWS * GRID_COLS) as u64 * (1 + NODE_INSTRUCTIONS)),
+            inputs: Setting::default(1 << 20),
+            outputs: Setting::default(1 << 20),
+            cycles: Setting::default(DEFAULT_CYCLES),
+            args_elements: Setting::default(1 << 22),
+        }
+    }
+}
+
+/// A limit that was exceeded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitError {
+    pub limit: Limit,
+    pub setting: Setting,
+    /// What went over, with its size: "node (0,0) has 300 instructions".
+    pub what: String,
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, over the {} limit of {} (from {})", self.what, self.limit, self.setting.value, self.setting.origin)
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+impl Limits {
+    pub fn get(&self, limit: Limit) -> Setting {
+        match limit {
+            Limit::NodeInstructions => self.node_instructions,
+            Limit::ProgWords => self.prog_words,
+            Limit::Inputs => self.inputs,
+            Limit::Outputs => self.outputs,
+            Limit::Cycles => self.cycles,
+            Limit::ArgsElements => self.args_elements,
+        }
+    }
+
+    pub fn set(&mut self, limit: Limit, value: u64, origin: Origin) {
+        let setting = match limit {
+            Limit::NodeInstructions => &mut self.node_instructions,
+            Limit::ProgWords => &mut self.prog_words,
+            Limit::Inputs => &mut self.inputs,
+            Limit::Outputs => &mut self.outputs,
+            Limit::Cycles => &mut self.cycles,
+            Limit::ArgsElements => &mut self.args_elements,
+        };
+        *setting = Setting { value, origin };
+    }
+
+    /// The defaults with the `[limits]` table of `path` on top; a missing
+    /// file or table changes nothing.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut limits = Limits::default();
+        for (key, value) in config::read_table(path, LIMITS_TABLE)? {
+            let at = |e: anyhow::Error| anyhow!("{}: [{}] {}", path.display(), LIMITS_TABLE, e);
+            let limit = Limit::from_name(&key).map_err(at)?;
+            limits.set(limit, parse_value(&value).map_err(at)?, Origin::Config);
+        }
+        Ok(limits)
+    }
+
+    /// `load`, then each `--limit` override.
+    pub fn load_with(path: &Path, overrides: &[(Limit, u64)]) -> Result<Self> {
+        let mut limits = Limits::load(path)?;
+        for &(limit, value) in overrides {
+            limits.set(limit, value, Origin::Flag);
+        }
+        Ok(limits)
+    }
+
+    /// Fails with a `LimitError` if `actual` is over `limit`; `what` says
+    /// what was counted.
+    pub fn check(&self, limit: Limit, actual: u64, what: impl FnOnce() -> String) -> Result<()> {
+        let setting = self.get(limit);
+        if actual > setting.value {
+            return Err(LimitError { limit, setting, what: what() }.into());
+        }
+        Ok(())
+    }
+}
+
+fn parse_value(value: &str) -> Result<u64> {
+    value.replace('_', "").parse().map_err(|_| anyhow!("expected a non-negative integer, found {}", value))
+}
+
+/// Parse a `--limit NAME=VALUE` argument.
+pub fn parse_override(arg: &str) -> Result<(Limit, u64)> {
+    let (name, value) = arg.split_once('=').ok_or_else(|| anyhow!("expected NAME=VALUE, found {}", arg))?;
+    Ok((Limit::from_name(name.trim())?, parse_value(value.trim())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_load_and_override() {
+        let path = std::env::temp_dir().join(format!("zk100-limits-{}.toml", std::process::id()));
+        fs::write(&path, "[limits]\nnode_instructions = 15\ncycles = 1_000\n").unwrap();
+        let limits = Limits::load_with(&path, &[parse_override("cycles=50").unwrap()]).unwrap();
+        assert_eq!(limits.node_instructions, Setting { value: 15, origin: Origin::Config });
+        assert_eq!(limits.cycles, Setting { value: 50, origin: Origin::Flag });
+        assert_eq!(limits.inputs, Limits::default().inputs);
+
+        let err = limits.check(Limit::NodeInstructions, 16, || "node (0,0) has 16 instructions".into()).unwrap_err();
+        assert_eq!(err.to_string(), "node (0,0) has 16 instructions, over the node_instructions limit of 15 (from zk100.toml)");
+        assert_eq!(err.downcast::<LimitError>().unwrap().limit, Limit::NodeInstructions);
+        assert!(limits.check(Limit::NodeInstructions, 15, || unreachable!()).is_ok());
+
+        fs::write(&path, "[limits]\nstack = 3\n").unwrap();
+        assert!(Limits::load(&path).unwrap_err().to_string().ends_with("[limits] unknown limit stack; expected one of node_instructions, prog_words, inputs, outputs, cycles, args_elements"));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Limits::load(&path).unwrap(), Limits::default());
+        assert!(parse_override("cycles").is_err());
+        assert!(parse_override("cycles=-1").is_err());
+    }
+}
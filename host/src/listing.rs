@@ -0,0 +1,372 @@
+//! Aligned program listings, behind `disassemble` and its listing file.
+//!
+//! Each node is a `NODE` header followed by one line per instruction: the
+//! pc, the instruction word, the label defined there (when the program came
+//! from assembly) and the instruction, each in a column as wide as the
+//! widest entry in the grid. Jump targets that land on a label are written
+//! with its name, as `convert --to asm` does.
+//!
+//! `source_listing` is the listing file of `assemble --listing`: the words
+//! as encoded, each with its index in `prog_words` and the source line it
+//! came from, or `(padding)` for the `NOP`s `.pad` appended.
+//!
+//! `Style::Plain` output is a scripting contract and stays byte-stable;
+//! `Style::Color` only adds ANSI escapes around tokens, so stripping them
+//! gives the plain listing back. `width` wraps an instruction whose line
+//! is too long after an operand's comma, never inside a token.
+
+use crate::assembler::{self, Labels, ParseResult, Programs};
+use crate::instruction::{Dst, Inst, Op, Src};
+use std::collections::BTreeMap;
+
+/// What a token is, for coloring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    /// Pcs and instruction words.
+    Address,
+    Mnemonic,
+    /// `IN`, `OUT`, `LAST` and `P:` ports.
+    Port,
+    Literal,
+    Label,
+    /// `ACC` and `NIL`, left uncolored.
+    Register,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Style {
+    #[default]
+    Plain,
+    Color,
+}
+
+impl Style {
+    /// The style for `--style auto`: color when writing to a terminal,
+    /// unless `NO_COLOR` is set to anything non-empty.
+    pub fn auto(terminal: bool) -> Style {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        if terminal && !no_color { Style::Color } else { Style::Plain }
+    }
+
+    /// `text` as it prints in this style.
+    pub fn paint(self, class: Class, text: &str) -> String {
+        let code = match (self, class) {
+            _ if text.is_empty() => return String::new(),
+            (Style::Plain, _) | (_, Class::Register) => return text.to_string(),
+            (Style::Color, Class::Address) => "2",
+            (Style::Color, Class::Mnemonic) => "1;34",
+            (Style::Color, Class::Port) => "36",
+            (Style::Color, Class::Literal) => "35",
+            (Style::Color, Class::Label) => "32",
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Options {
+    pub style: Style,
+    /// Wrap instructions whose line would be longer than this.
+    pub width: Option<usize>,
+}
+
+/// One instruction line before alignment.
+struct Row {
+    pc: Option<usize>,
+    word: Option<u32>,
+    label: Option<String>,
+    inst: Option<(Op, Vec<(Class, String)>)>,
+}
+
+/// The listing of every non-empty node of `programs`, with `labels` from
+/// the `ParseResult` they were assembled from (empty for decoded words).
+pub fn render(programs: &Programs, labels: &Labels, options: Options) -> String {
+    let no_labels = BTreeMap::new();
+    let mut nodes = Vec::new();
+    for (r, row) in programs.iter().enumerate() {
+        for (c, program) in row.iter().enumerate() {
+            if !program.is_empty() {
+                nodes.push(((r, c), node_rows(program, labels.get(&(r, c)).unwrap_or(&no_labels))));
+            }
+        }
+    }
+
+    let rows = || nodes.iter().flat_map(|(_, rows)| rows);
+    let pc_width = rows().filter_map(|row| row.pc).max().unwrap_or(0).to_string().len();
+    let label_width = rows().filter_map(|row| row.label.as_ref()).map(|name| name.len() + 1).max();
+    let mnemonic_width = rows().filter_map(|row| row.inst.as_ref()).map(|(op, _)| op.mnemonic().len()).max().unwrap_or(0);
+    // pc, two spaces, 0x and eight digits, two spaces, then the label column.
+    let inst_column = pc_width + 14 + label_width.map_or(0, |w| w + 2);
+
+    let style = options.style;
+    let mut out = String::new();
+    for (idx, ((r, c), rows)) in nodes.iter().enumerate() {
+        if idx > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("NODE ({},{})\n", r, c));
+        for row in rows {
+            let mut line = String::new();
+            let cell = |line: &mut String, class: Class, text: &str, width: usize| {
+                line.push_str(&style.paint(class, text));
+                line.push_str(&" ".repeat(width.saturating_sub(text.len())));
+            };
+            let pc = row.pc.map(|pc| format!("{:>width$}", pc, width = pc_width)).unwrap_or_default();
+            cell(&mut line, Class::Address, &pc, pc_width + 2);
+            let word = row.word.map(|word| format!("0x{:08x}", word)).unwrap_or_default();
+            cell(&mut line, Class::Address, &word, 12);
+            if let Some(width) = label_width {
+                let label = row.label.as_ref().map(|name| format!("{}:", name)).unwrap_or_default();
+                cell(&mut line, Class::Label, &label, width + 2);
+            }
+            match &row.inst {
+                Some((op, operands)) => {
+                    let mnemonic = op.mnemonic();
+                    let padding = if operands.is_empty() { mnemonic.len() } else { mnemonic_width + 1 };
+                    cell(&mut line, Class::Mnemonic, mnemonic, padding);
+                    let operand_column = inst_column + mnemonic_width + 1;
+                    let mut column = operand_column;
+                    for (i, (class, text)) in operands.iter().enumerate() {
+                        let last = i + 1 == operands.len();
+                        let len = text.len() + usize::from(!last);
+                        if i > 0 && options.width.is_some_and(|w| column + len > w) {
+                            line.push('\n');
+                            line.push_str(&" ".repeat(operand_column));
+                            column = operand_column;
+                        } else if i > 0 {
+                            line.push(' ');
+                            column += 1;
+                        }
+                        line.push_str(&style.paint(*class, text));
+                        if !last {
+                            line.push(',');
+                        }
+                        column += len;
+                    }
+                }
+                None => line.truncate(line.trim_end().len()),
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Every node of `parsed` in `prog_words` order, empty ones included: its
+/// length word, then per instruction the word's index in `prog_words`, the
+/// pc, the word and the line of `source` it was assembled from, with each
+/// label on a line of its own at its pc.
+pub fn source_listing(parsed: &ParseResult, source: &str, prog_words: &[u32]) -> String {
+    let programs = &parsed.grid.programs;
+    let source_lines: Vec<&str> = source.lines().collect();
+    let index_width = prog_words.len().saturating_sub(1).to_string().len();
+    let pc_width = programs.iter().flatten().map(Vec::len).max().unwrap_or(0).to_string().len();
+    let line_width = source_lines.len().to_string().len();
+    let no_labels = BTreeMap::new();
+
+    let mut out = String::new();
+    for ((r, c), offset) in assembler::word_offsets(programs) {
+        let program = &programs[r][c];
+        let labels = parsed.labels.get(&(r, c)).unwrap_or(&no_labels);
+        let lines = parsed.lines.get(&(r, c));
+        let padded_from = program.len() - parsed.padding.get(&(r, c)).copied().unwrap_or(0);
+        if offset > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("NODE {}\n", parsed.node_label((r, c))));
+        out.push_str(&format!(
+            "[{:>iw$}]  {:pw$}  0x{:08x}  length {}\n",
+            offset,
+            "",
+            prog_words[offset],
+            program.len(),
+            iw = index_width,
+            pw = pc_width
+        ));
+        for pc in 0..=program.len() {
+            for (name, _) in labels.iter().filter(|(_, &at)| at == pc) {
+                out.push_str(&format!("{:iw$}  {:>pw$}  {}:\n", "", pc, name, iw = index_width + 2, pw = pc_width));
+            }
+            if pc == program.len() {
+                break;
+            }
+            let index = offset + 1 + pc;
+            if pc >= padded_from {
+                out.push_str(&format!(
+                    "[{:>iw$}]  {:>pw$}  0x{:08x}  {:>lw$}  NOP (padding)\n",
+                    index,
+                    pc,
+                    prog_words[index],
+                    "",
+                    iw = index_width,
+                    pw = pc_width,
+                    lw = line_width
+                ));
+                continue;
+            }
+            let line = lines.and_then(|lines| lines.get(pc)).copied().unwrap_or_default();
+            let text = line.checked_sub(1).and_then(|i| source_lines.get(i)).map_or("", |text| text.trim());
+            out.push_str(&format!(
+                "[{:>iw$}]  {:>pw$}  0x{:08x}  {:>lw$}: {}\n",
+                index,
+                pc,
+                prog_words[index],
+                line,
+                text,
+                iw = index_width,
+                pw = pc_width,
+                lw = line_width
+            ));
+        }
+    }
+    out
+}
+
+/// A node's rows: one per instruction, carrying the last label (in name
+/// order) at its pc, and a label-only row for each other label there and
+/// for each label past the last instruction.
+fn node_rows(program: &[Inst], labels: &BTreeMap<String, usize>) -> Vec<Row> {
+    let name_at = |pc: usize| labels.iter().find(|(_, &at)| at == pc).map(|(name, _)| name.clone());
+    let mut rows = Vec::new();
+    for pc in 0..=program.len() {
+        let mut names: Vec<String> = labels.iter().filter(|(_, &at)| at == pc).map(|(name, _)| name.clone()).collect();
+        let Some(inst) = program.get(pc) else {
+            rows.extend(names.into_iter().map(|name| Row { pc: None, word: None, label: Some(name), inst: None }));
+            break;
+        };
+        let label = names.pop();
+        rows.extend(names.into_iter().map(|name| Row { pc: None, word: None, label: Some(name), inst: None }));
+        let operands = match inst.op {
+            Op::Nop | Op::Hlt | Op::Neg | Op::Sav | Op::Swp => vec![],
+            Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz => match inst.src {
+                Src::Lit(target) => match name_at(target as usize) {
+                    Some(name) => vec![(Class::Label, name)],
+                    None => vec![src(inst.src)],
+                },
+                other => vec![src(other)],
+            },
+            Op::Add | Op::Sub => vec![src(inst.src)],
+            Op::Mov => vec![src(inst.src), dst(inst.dst)],
+        };
+        rows.push(Row { pc: Some(pc), word: Some(inst.encode()), label, inst: Some((inst.op, operands)) });
+    }
+    rows
+}
+
+fn src(src: Src) -> (Class, String) {
+    let class = match src {
+        Src::Lit(_) => Class::Literal,
+        Src::Acc | Src::Nil => Class::Register,
+        Src::In | Src::P(_) | Src::Last => Class::Port,
+    };
+    (class, src.to_string())
+}
+
+fn dst(dst: Dst) -> (Class, String) {
+    let class = match dst {
+        Dst::Acc | Dst::Nil => Class::Register,
+        Dst::Out | Dst::P(_) | Dst::Last => Class::Port,
+    };
+    (class, dst.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+
+    const LOOP: &str = "NODE (0,0)\nstart:\nMOV IN, ACC\nJZ start\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+    fn strip_ansi(text: &str) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                out.push(ch);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_color_only_adds_escapes() {
+        let parsed = assembler::parse(LOOP).unwrap();
+        let plain = render(&parsed.grid.programs, &parsed.labels, Options::default());
+        assert!(!plain.contains('\x1b'));
+        assert!(plain.starts_with("NODE (0,0)\n0  0x00010300  start:  MOV IN, ACC\n1  "), "{}", plain);
+        let color = render(&parsed.grid.programs, &parsed.labels, Options { style: Style::Color, width: None });
+        assert!(color.contains("\x1b[1;34mMOV\x1b[0m \x1b[36mIN\x1b[0m, ACC"), "{}", color);
+        assert_eq!(strip_ansi(&color), plain);
+
+        // Decoded words have no labels, so no label column and numeric targets.
+        let bare = render(&parsed.grid.programs, &Labels::new(), Options { style: Style::Plain, width: Some(26) });
+        assert!(bare.starts_with("NODE (0,0)\n0  0x00010300  MOV IN, ACC\n1  0x00080001  JZ  0\n"), "{}", bare);
+        assert!(bare.contains("\n2  0x00110103  MOV ACC,\n                   P:DOWN\n"), "{}", bare);
+    }
+
+    #[test]
+    fn test_source_listing() {
+        let parsed = assembler::parse(LOOP).unwrap();
+        let words = assembler::encode_programs(&parsed.grid.programs).unwrap();
+        let listing = source_listing(&parsed, LOOP, &words);
+        assert!(
+            listing.starts_with(
+                "NODE (0,0)\n\
+                 [0]     0x00000003  length 3\n\
+                 \x20    0  start:\n\
+                 [1]  0  0x00010300  3: MOV IN, ACC\n\
+                 [2]  1  0x00080001  4: JZ start\n\
+                 [3]  2  0x00110103  5: MOV ACC, P:DOWN\n\
+                 \n\
+                 NODE (0,1)\n\
+                 [4]     0x00000000  length 0\n"
+            ),
+            "{}",
+            listing
+        );
+        assert!(listing.contains("\nNODE (1,1)\n[7]     0x00000001  length 1\n[8]  0  0x"), "{}", listing);
+        assert!(listing.ends_with("  9: MOV P:LEFT, OUT\n"), "{}", listing);
+    }
+
+    #[test]
+    fn test_source_listing_marks_padding() {
+        let code = ".pad 3\nNODE (0,0)\nMOV IN, ACC\n";
+        let parsed = assembler::parse(code).unwrap();
+        let words = assembler::encode_programs(&parsed.grid.programs).unwrap();
+        let listing = source_listing(&parsed, code, &words);
+        assert!(
+            listing.starts_with(
+                "NODE (0,0)\n\
+                 [0]     0x00000003  length 3\n\
+                 [1]  0  0x00010300  3: MOV IN, ACC\n\
+                 [2]  1  0x000c0201     NOP (padding)\n\
+                 [3]  2  0x000c0201     NOP (padding)\n"
+            ),
+            "{}",
+            listing
+        );
+    }
+
+    #[test]
+    fn test_labels_sharing_a_pc() {
+        let code = "NODE (0,0)\nstart:\nretry: MOV IN, ACC\nJZ retry\n";
+        let parsed = assembler::parse(code).unwrap();
+        let listing = render(&parsed.grid.programs, &parsed.labels, Options::default());
+        assert!(listing.starts_with("NODE (0,0)\n               retry:\n0  0x00010300  start:  MOV IN, ACC\n"), "{}", listing);
+        let words = assembler::encode_programs(&parsed.grid.programs).unwrap();
+        let listing = source_listing(&parsed, code, &words);
+        assert!(listing.contains("\n\x20    0  retry:\n\x20    0  start:\n[1]  0  0x00010300  3: retry: MOV IN, ACC\n"), "{}", listing);
+    }
+
+    #[test]
+    fn test_source_listing_names_nodes() {
+        let code = "NODE TOP_RIGHT\nNOP\n";
+        let parsed = assembler::parse(code).unwrap();
+        let words = assembler::encode_programs(&parsed.grid.programs).unwrap();
+        let listing = source_listing(&parsed, code, &words);
+        assert!(listing.contains("\nNODE TOP_RIGHT (0,1)\n[1]     0x00000001  length 1\n"), "{}", listing);
+    }
+}
@@ -0,0 +1,308 @@
+//! Literals as the source writes them, fitted to the instruction word.
+//!
+//! An operand's literal stays an `i64` (`Operand::Lit`) from the parse until
+//! `lower` turns its instruction into the ones the guest runs, so `-1` and
+//! `4294967295` never meet as the same `u32`. The field holds
+//! `0..=MAX_LITERAL` and has no sign: `ADD -k` becomes `SUB k`, `SUB -k`
+//! becomes `ADD k`, `MOV -k, ACC` becomes `MOV k, ACC; NEG` and `MOV -k, NIL`
+//! is just `MOV k, NIL`. `MOV -k` into a port, `OUT` or `LAST` is an error
+//! naming the `MOV k, ACC; NEG; MOV ACC, ...` it would take, since that
+//! leaves `-k` in ACC and only the author knows whether ACC is free.
+//!
+//! Wider constants are built in ACC over several instructions only with
+//! `--synthesize-literals`; otherwise `MOV 1000, ACC` does not assemble. `plan` finds a shortest sequence
+//! of `MOV c, ACC`, `ADD c` and `ADD ACC` (doubling) that leaves the value in
+//! ACC, never passing through anything larger, and a negative one is its
+//! magnitude's plan followed by `NEG`. Once a node's instructions are
+//! lowered, `retarget` moves every pc that refers into the node along with
+//! them. Only ACC can be built this way: a port or `OUT` would see each
+//! partial value.
+
+use crate::assembler::Assert;
+use crate::instruction::{Dst, Inst, LIT_FIELD, Op, Src};
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, HashMap};
+
+/// The largest literal an instruction word holds.
+pub const MAX_LITERAL: u32 = LIT_FIELD.mask;
+
+/// A source operand as written. A literal keeps its sign and full width
+/// until `lower` fits it to the instruction word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operand {
+    Src(Src),
+    Lit(i64),
+}
+
+/// The instructions one source instruction lowers to, and the warning a
+/// synthesized constant comes with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Lowered {
+    pub steps: Vec<Inst>,
+    pub warning: Option<String>,
+}
+
+impl Lowered {
+    fn one(inst: Inst) -> Self {
+        Lowered { steps: vec![inst], warning: None }
+    }
+}
+
+/// `op src, dst` as the guest runs it: one instruction when the literal
+/// fits the field, the rewrites above for a negative one, and with
+/// `synthesize` a `plan` for a wide `MOV` into ACC. Anything else is an
+/// error.
+pub(crate) fn lower(op: Op, src: Operand, dst: Dst, synthesize: bool) -> Result<Lowered> {
+    let value = match src {
+        Operand::Src(src) => return Ok(Lowered::one(Inst { op, src, dst })),
+        Operand::Lit(value) => value,
+    };
+    let magnitude = u32::try_from(value.unsigned_abs()).ok().filter(|&k| k <= MAX_LITERAL);
+    match (magnitude, op, dst) {
+        (Some(k), _, _) if value >= 0 => Ok(Lowered::one(Inst { op, src: Src::Lit(k), dst })),
+        (Some(k), Op::Add | Op::Sub, _) => {
+            let op = if op == Op::Add { Op::Sub } else { Op::Add };
+            Ok(Lowered::one(Inst { op, src: Src::Lit(k), dst }))
+        }
+        // Nothing reads what NIL is given.
+        (Some(k), Op::Mov, Dst::Nil) => Ok(Lowered::one(Inst { op, src: Src::Lit(k), dst })),
+        (Some(k), Op::Mov, Dst::Acc) => {
+            let steps = vec![Inst { op, src: Src::Lit(k), dst }, Inst { op: Op::Neg, src: Src::Nil, dst: Dst::Nil }];
+            Ok(Lowered { steps, warning: None })
+        }
+        (Some(k), Op::Mov, _) => Err(anyhow!(
+            "the literal field has no sign, so MOV {} only goes into ACC or NIL; MOV {}, ACC; NEG; MOV ACC, {} sends it but leaves {} in ACC",
+            value, k, dst, value
+        )),
+        (None, Op::Mov, Dst::Acc) if synthesize => synthesized(value),
+        (None, Op::Mov, _) if synthesize => Err(anyhow!(
+            "cannot synthesize {} into {}: only ACC can be built over several instructions; MOV it into ACC first",
+            value, dst
+        )),
+        _ => Err(anyhow!(too_wide(op, dst, value))),
+    }
+}
+
+/// Why the literal `value` of `op ..., dst` has no room in the instruction
+/// word.
+pub(crate) fn too_wide(op: Op, dst: Dst, value: i64) -> String {
+    let what = match op {
+        Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz => "jump target",
+        _ => "literal",
+    };
+    let hint = match (op, dst) {
+        (Op::Mov, Dst::Acc) => "; --synthesize-literals builds it in ACC",
+        _ => "",
+    };
+    format!(
+        "{} {} does not fit the {}-bit literal field (0..={}){}",
+        what,
+        value,
+        MAX_LITERAL.count_ones(),
+        MAX_LITERAL,
+        hint
+    )
+}
+
+/// The `plan` that leaves `value` in ACC, negated for a negative one, with
+/// a warning listing it. ACC holds 32 bits, signed for a negative value.
+fn synthesized(value: i64) -> Result<Lowered> {
+    let steps = match value {
+        0.. => u32::try_from(value).ok().map(plan),
+        _ => u32::try_from(value.unsigned_abs()).ok().filter(|&k| k <= 1 << 31).map(|k| {
+            let mut steps = plan(k);
+            steps.push(Inst { op: Op::Neg, src: Src::Nil, dst: Dst::Nil });
+            steps
+        }),
+    };
+    let Some(steps) = steps else {
+        return Err(anyhow!("cannot synthesize {} in ACC: it does not fit in 32 bits", value));
+    };
+    let listed: Vec<String> = steps.iter().map(Inst::to_string).collect();
+    let warning = format!("synthesized {} in ACC with {} instructions: {}", value, steps.len(), listed.join("; "));
+    Ok(Lowered { steps, warning: Some(warning) })
+}
+
+/// The instructions that set ACC to `value`: just `MOV value, ACC` when it
+/// fits, else the shortest sequence found, preferring plain additions over
+/// doubling on a tie.
+pub fn plan(value: u32) -> Vec<Inst> {
+    let mut memo = HashMap::new();
+    best(value, &mut memo);
+    let mut steps = Vec::new();
+    let mut at = value;
+    loop {
+        match memo.get(&at).map_or(Step::Set, |&(_, step)| step) {
+            Step::Set => {
+                steps.push(Inst { op: Op::Mov, src: Src::Lit(at), dst: Dst::Acc });
+                break;
+            }
+            Step::Chain => {
+                steps.extend(chain(at).into_iter().rev());
+                break;
+            }
+            Step::Double(add) => {
+                if add > 0 {
+                    steps.push(add_lit(add));
+                }
+                steps.push(Inst { op: Op::Add, src: Src::Acc, dst: Dst::Nil });
+                at = (at - add) / 2;
+            }
+        }
+    }
+    steps.reverse();
+    steps
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    /// `MOV value, ACC`.
+    Set,
+    /// `MOV MAX_LITERAL, ACC`, then `ADD`s of at most `MAX_LITERAL`.
+    Chain,
+    /// Build `(value - add) / 2`, double it, then add `add` (if any).
+    Double(u32),
+}
+
+/// The length of the shortest sequence for `value`, memoized with its first
+/// step back from `value`.
+fn best(value: u32, memo: &mut HashMap<u32, (usize, Step)>) -> usize {
+    if value <= MAX_LITERAL {
+        return 1;
+    }
+    if let Some(&(cost, _)) = memo.get(&value) {
+        return cost;
+    }
+    let mut choice = (value.div_ceil(MAX_LITERAL) as usize, Step::Chain);
+    for add in (value % 2..=MAX_LITERAL).step_by(2) {
+        let half = (value - add) / 2;
+        let cost = best(half, memo) + 1 + usize::from(add > 0);
+        if cost < choice.0 {
+            choice = (cost, Step::Double(add));
+        }
+    }
+    memo.insert(value, choice);
+    choice.0
+}
+
+fn chain(value: u32) -> Vec<Inst> {
+    let mut steps = vec![Inst { op: Op::Mov, src: Src::Lit(MAX_LITERAL), dst: Dst::Acc }];
+    let mut left = value - MAX_LITERAL;
+    while left > 0 {
+        let add = left.min(MAX_LITERAL);
+        steps.push(add_lit(add));
+        left -= add;
+    }
+    steps
+}
+
+fn add_lit(value: u32) -> Inst {
+    Inst { op: Op::Add, src: Src::Lit(value), dst: Dst::Nil }
+}
+
+/// Point what refers to a node's source pcs at its lowered `program`, in
+/// which source pc `pc` starts at `starts[pc]` (with one past its last
+/// instruction at the end): every literal jump target, `labels` and
+/// `asserts`. A target past the node's end stays as far past it, for the
+/// jump check to report.
+pub(crate) fn retarget(
+    program: &mut [Inst],
+    starts: &[usize],
+    labels: Option<&mut BTreeMap<String, usize>>,
+    asserts: Option<&mut BTreeMap<usize, Vec<Assert>>>,
+) {
+    let source_len = starts.len().saturating_sub(1);
+    if source_len == program.len() {
+        return;
+    }
+    let grown = program.len() - source_len;
+    let moved = |pc: usize| starts.get(pc).copied().unwrap_or(pc + grown);
+    for inst in program.iter_mut() {
+        if let (Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz, Src::Lit(target)) = (inst.op, inst.src) {
+            inst.src = Src::Lit(moved(target as usize) as u32);
+        }
+    }
+    if let Some(labels) = labels {
+        labels.values_mut().for_each(|pc| *pc = moved(*pc));
+    }
+    if let Some(asserts) = asserts {
+        *asserts = std::mem::take(asserts).into_iter().map(|(pc, a)| (moved(pc), a)).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed(value: u32) -> Vec<String> {
+        plan(value).iter().map(Inst::to_string).collect()
+    }
+
+    #[test]
+    fn test_plans() {
+        assert_eq!(listed(200), ["MOV 200, ACC"]);
+        assert_eq!(listed(256), ["MOV 255, ACC", "ADD 1"]);
+        assert_eq!(listed(1000), ["MOV 255, ACC", "ADD 245", "ADD ACC"]);
+        // As short as doubling, so plain additions win.
+        assert_eq!(listed(1001), ["MOV 255, ACC", "ADD 255", "ADD 255", "ADD 236"]);
+        assert_eq!(listed(65_536), ["MOV 255, ACC", "ADD 255", "ADD 2", "ADD ACC", "ADD ACC", "ADD ACC", "ADD ACC", "ADD ACC", "ADD ACC", "ADD ACC"]);
+        assert_eq!(plan(u32::MAX).len(), best(u32::MAX, &mut HashMap::new()));
+        // Every plan builds its value without overflowing on the way.
+        for value in [256, 511, 1000, 65_537, 1_000_000, u32::MAX - 1, u32::MAX] {
+            let mut acc: u32 = 0;
+            for inst in plan(value) {
+                acc = match (inst.op, inst.src) {
+                    (Op::Mov, Src::Lit(v)) => v,
+                    (Op::Add, Src::Lit(v)) => acc.checked_add(v).unwrap(),
+                    (Op::Add, Src::Acc) => acc.checked_add(acc).unwrap(),
+                    _ => unreachable!("{}", inst),
+                };
+                if let Src::Lit(v) = inst.src {
+                    assert!(v <= MAX_LITERAL, "{}", inst);
+                }
+            }
+            assert_eq!(acc, value);
+        }
+    }
+
+    fn lowered(op: Op, value: i64, dst: Dst, synthesize: bool) -> Result<Vec<String>> {
+        Ok(lower(op, Operand::Lit(value), dst, synthesize)?.steps.iter().map(Inst::to_string).collect())
+    }
+
+    #[test]
+    fn test_lower_keeps_the_literal_as_written() {
+        assert_eq!(lowered(Op::Add, -1, Dst::Nil, false).unwrap(), ["SUB 1"]);
+        assert_eq!(lowered(Op::Sub, -255, Dst::Nil, false).unwrap(), ["ADD 255"]);
+        assert_eq!(lowered(Op::Mov, -7, Dst::Acc, false).unwrap(), ["MOV 7, ACC", "NEG"]);
+        assert_eq!(lowered(Op::Mov, -7, Dst::Nil, false).unwrap(), ["MOV 7, NIL"]);
+        assert_eq!(lowered(Op::Mov, -1000, Dst::Acc, true).unwrap(), ["MOV 255, ACC", "ADD 245", "ADD ACC", "NEG"]);
+
+        // Past i32::MAX a literal is a wide unsigned value, never a negative
+        // one read back from its two's complement.
+        let err = |op, value, dst| lowered(op, value, dst, false).unwrap_err().to_string();
+        assert_eq!(err(Op::Mov, 4_294_967_295, Dst::Acc), "literal 4294967295 does not fit the 8-bit literal field (0..=255); --synthesize-literals builds it in ACC");
+        assert_eq!(err(Op::Add, 4_294_967_295, Dst::Nil), "literal 4294967295 does not fit the 8-bit literal field (0..=255)");
+        assert_eq!(err(Op::Mov, 2_147_483_648, Dst::Acc), "literal 2147483648 does not fit the 8-bit literal field (0..=255); --synthesize-literals builds it in ACC");
+        assert_eq!(lowered(Op::Mov, 4_294_967_295, Dst::Acc, true).unwrap().last().unwrap(), "ADD 255");
+        assert_eq!(err(Op::Jmp, -1, Dst::Nil), "jump target -1 does not fit the 8-bit literal field (0..=255)");
+
+        // A port sees whatever ACC would be left holding, so that is the
+        // author's call.
+        assert_eq!(
+            err(Op::Mov, -7, Dst::P(crate::instruction::PortTag::Down)),
+            "the literal field has no sign, so MOV -7 only goes into ACC or NIL; MOV 7, ACC; NEG; MOV ACC, P:DOWN sends it but leaves -7 in ACC"
+        );
+        assert_eq!(
+            lowered(Op::Mov, 1000, Dst::Out, true).unwrap_err().to_string(),
+            "cannot synthesize 1000 into OUT: only ACC can be built over several instructions; MOV it into ACC first"
+        );
+        assert_eq!(
+            lowered(Op::Mov, 4_294_967_296, Dst::Acc, true).unwrap_err().to_string(),
+            "cannot synthesize 4294967296 in ACC: it does not fit in 32 bits"
+        );
+        assert_eq!(
+            lower(Op::Mov, Operand::Lit(1000), Dst::Acc, true).unwrap().warning.unwrap(),
+            "synthesized 1000 in ACC with 3 instructions: MOV 255, ACC; ADD 245; ADD ACC"
+        );
+    }
+}
@@ -0,0 +1,848 @@
+//! `zk100 lsp`: a Language Server Protocol server over stdio.
+//!
+//! `lsp-server` owns the framing, the initialize handshake and shutdown;
+//! `LanguageServer` answers the requests and notifications in between.
+//! Supports full-document sync, diagnostics on open/change, go-to-definition
+//! for labels, hover (encoded word plus a plain-English reading), document
+//! symbols (NODE blocks with their labels) and completion for mnemonics,
+//! operands and the labels of the enclosing node. The `zk100/programRoot`
+//! request returns the document's program root and `prog_words`, rebuilt
+//! with an `IncrementalAssembler` per document so only edited nodes are
+//! assembled again.
+//!
+//! The assembler does not yet report positions, so `analyze` lexes and
+//! classifies lines with the assembler's own functions and runs the per-line
+//! parsers itself. That also lets the editor show every bad line instead of
+//! only the first.
+//! Positions are zero-based lines and UTF-16 columns, as LSP requires.
+
+use crate::assembler::{
+    self, ConstantScope, Constants, EMPTY_LABEL, EMPTY_LOCAL_LABEL, Labels, Line, ParseOptions, before_first_node, classify,
+    define_directive, duplicate_label, grid_directive, is_local, label_key, node_name, non_ascii, parse_assert,
+    parse_instruction_at, parse_node_line, reopen_error, reserved_label,
+};
+use crate::dialect::Dialect;
+use crate::directives::{Conditions, unknown_directive};
+use crate::incremental::IncrementalAssembler;
+use crate::lexer::{self, Token, TokenKind};
+use crate::instruction::{Dst, Inst, Op, Src};
+use anyhow::Result;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Response};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+
+const OPERANDS: [&str; 9] = ["ACC", "NIL", "IN", "OUT", "LAST", "P:UP", "P:DOWN", "P:LEFT", "P:RIGHT"];
+
+const SEVERITY_ERROR: u8 = 1;
+
+/// A span on one line, in UTF-16 columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LineRange {
+    fn to_json(self) -> Value {
+        json!({
+            "start": { "line": self.line, "character": self.start },
+            "end": { "line": self.line, "character": self.end },
+        })
+    }
+
+    fn contains(&self, line: usize, character: usize) -> bool {
+        self.line == line && self.start <= character && character <= self.end
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeBlock {
+    pub coords: (usize, usize),
+    /// The name the header gave the node instead of coordinates, if any.
+    pub name: Option<String>,
+    pub header: LineRange,
+    pub last_line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelDef {
+    pub name: String,
+    pub node: (usize, usize),
+    pub pc: usize,
+    pub range: LineRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct InstLine {
+    pub node: (usize, usize),
+    pub pc: usize,
+    pub range: LineRange,
+    pub inst: Option<Inst>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub range: LineRange,
+    pub severity: u8,
+    pub message: String,
+}
+
+/// Everything the handlers need to know about one document.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub nodes: Vec<NodeBlock>,
+    pub labels: Vec<LabelDef>,
+    pub instructions: Vec<InstLine>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Node in effect on each line.
+    line_nodes: Vec<Option<(usize, usize)>>,
+    /// The label owning `.local` labels on each line.
+    line_scopes: Vec<Option<String>>,
+}
+
+/// UTF-16 range of a run of tokens from `line`.
+fn token_range(line_no: usize, line: &str, first: &Token, last: &Token) -> LineRange {
+    let utf16_col = |col: usize| line.chars().take(col - 1).map(char::len_utf16).sum::<usize>();
+    LineRange { line: line_no, start: utf16_col(first.col_start), end: utf16_col(last.col_end) }
+}
+
+/// Classify lines with the assembler's own classifier and record positions.
+pub fn analyze(text: &str) -> Analysis {
+    let mut analysis = Analysis::default();
+    let mut current: Option<(usize, usize)> = None;
+    let mut pcs: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut inst_tokens: Vec<(Vec<Token>, Option<String>)> = Vec::new();
+    let mut scope: Option<String> = None;
+    // The file's constants and each node's, as the assembler scopes them.
+    let mut constants = Constants::new();
+    let mut node_constants: HashMap<(usize, usize), Constants> = HashMap::new();
+    let mut globals: HashMap<String, usize> = HashMap::new();
+    let mut constant_lines: HashMap<(Option<(usize, usize)>, String), usize> = HashMap::new();
+    let mut assert_tokens: Vec<((usize, usize), Vec<Token>, LineRange)> = Vec::new();
+    let no_constants = Constants::new();
+    let mut conditions = Conditions::default();
+    let mut if_ranges: HashMap<usize, LineRange> = HashMap::new();
+
+    let size = assembler::grid_size(text).unwrap_or_default();
+    for (line_no, raw) in text.lines().enumerate() {
+        let tokens = lexer::lex_line(raw, line_no + 1);
+        let code: Vec<&Token> = tokens.iter().filter(|t| t.kind != TokenKind::Comment).collect();
+        analysis.line_nodes.push(current);
+        analysis.line_scopes.push(scope.clone());
+        let (Some(first), Some(last)) = (code.first(), code.last()) else {
+            continue;
+        };
+        let range = token_range(line_no, raw, first, last);
+
+        // Lines of a branch an `.if` drops are not read at all.
+        let line = classify(&tokens);
+        match conditions.apply(&line, &constants) {
+            Ok(true) => {
+                if_ranges.entry(line_no + 1).or_insert(range);
+                continue;
+            }
+            Ok(false) if !conditions.selected() => continue,
+            Ok(false) => {}
+            Err(e) => {
+                analysis.diagnostics.push(Diagnostic { range, severity: SEVERITY_ERROR, message: e.to_string() });
+                continue;
+            }
+        }
+        if let Some((col, message)) = non_ascii(&tokens).filter(|_| !matches!(line, Line::Node(..))) {
+            let start = raw.chars().take(col - 1).map(char::len_utf16).sum::<usize>();
+            let end = start + raw.chars().nth(col - 1).map_or(0, char::len_utf16);
+            let range = LineRange { line: line_no, start, end };
+            analysis.diagnostics.push(Diagnostic { range, severity: SEVERITY_ERROR, message });
+            continue;
+        }
+        match line {
+            Line::Blank => {}
+            Line::Grid(tokens) => {
+                if let Err(e) = grid_directive(&tokens) {
+                    analysis.diagnostics.push(Diagnostic { range, severity: SEVERITY_ERROR, message: e.to_string() });
+                }
+            }
+            Line::Define(name, tokens, global) => {
+                let key = (current, name.text.to_string());
+                let value = if let (Some(_), true) = (current, global) {
+                    let first = analysis.nodes[0].header.line + 1;
+                    Err(format!("GLOBAL {} must come before the first NODE (line {})", name.text, first))
+                } else if let (Some(node), Some(line)) = (current, globals.get(name.text)) {
+                    Err(format!("node ({},{}) cannot define '{}'; it is GLOBAL (line {})", node.0, node.1, name.text, line))
+                } else if let Some(first) = constant_lines.get(&key) {
+                    let place = current.map(|(r, c)| format!(" in node ({},{})", r, c)).unwrap_or_default();
+                    Err(format!("constant '{}' is defined twice{}, on lines {} and {}", name.text, place, first, line_no + 1))
+                } else {
+                    let node = current.map(|node| (node, node_constants.get(&node).unwrap_or(&no_constants)));
+                    define_directive(&name, &tokens, &ConstantScope { file: &constants, node }).map_err(|e| e.to_string())
+                };
+                match value {
+                    Ok(value) => {
+                        constant_lines.insert(key, line_no + 1);
+                        if global {
+                            globals.insert(name.text.to_string(), line_no + 1);
+                        }
+                        match current {
+                            Some(node) => node_constants.entry(node).or_default().insert(name.text.to_string(), value),
+                            None => constants.insert(name.text.to_string(), value),
+                        };
+                    }
+                    Err(message) => analysis.diagnostics.push(Diagnostic { range, severity: SEVERITY_ERROR, message }),
+                }
+            }
+            Line::Directive(name, _) => {
+                if let Some(message) = unknown_directive(&name) {
+                    analysis.diagnostics.push(Diagnostic { range, severity: SEVERITY_ERROR, message });
+                }
+            }
+            Line::Node(text, continued) => match parse_node_line(&text, size) {
+                Ok(coords) => {
+                    let first = analysis.nodes.iter().find(|b| b.coords == coords).map(|b| b.header.line + 1);
+                    if let Some(message) = reopen_error(coords, continued, first, line_no + 1) {
+                        analysis.diagnostics.push(Diagnostic { range, severity: SEVERITY_ERROR, message });
+                    }
+                    if let Some(block) = analysis.nodes.last_mut() {
+                        block.last_line = line_no.saturating_sub(1);
+                    }
+                    current = Some(coords);
+                    scope = None;
+                    analysis.line_nodes[line_no] = current;
+                    analysis.line_scopes[line_no] = None;
+                    pcs.entry(coords).or_insert(0);
+                    analysis.nodes.push(NodeBlock { coords, name: node_name(&text), header: range, last_line: line_no });
+                }
+                Err(e) => analysis.diagnostics.push(Diagnostic {
+                    range,
+                    severity: SEVERITY_ERROR,
+                    message: e.to_string(),
+                }),
+            },
+            Line::Label(names, instruction) => {
+                for name in &names {
+                    let name_range = token_range(line_no, raw, name, name);
+                    let Some(node) = current else {
+                        let message = match name.text {
+                            "" => EMPTY_LABEL.to_string(),
+                            text => before_first_node(&format!("label '{}'", text)),
+                        };
+                        analysis.diagnostics.push(Diagnostic { range: name_range, severity: SEVERITY_ERROR, message });
+                        break;
+                    };
+                    let key = label_key(name.text, scope.as_deref());
+                    if name.text.is_empty() || name.text == "." {
+                        analysis.diagnostics.push(Diagnostic {
+                            range: name_range,
+                            severity: SEVERITY_ERROR,
+                            message: if name.text.is_empty() { EMPTY_LABEL } else { EMPTY_LOCAL_LABEL }.to_string(),
+                        });
+                    } else if let Some(message) = reserved_label(name, &Dialect::default()) {
+                        analysis.diagnostics.push(Diagnostic { range: name_range, severity: SEVERITY_ERROR, message });
+                    } else if let Some(earlier) = analysis.label(node, &key) {
+                        let message = duplicate_label(&key, &earlier.name, node, (earlier.range.line + 1, line_no + 1));
+                        analysis.diagnostics.push(Diagnostic { range: name_range, severity: SEVERITY_ERROR, message });
+                    } else {
+                        analysis.labels.push(LabelDef { name: key, node, pc: pcs[&node], range: name_range });
+                    }
+                    if !name.text.is_empty() && !is_local(name.text) {
+                        scope = Some(name.text.to_string());
+                    }
+                }
+                if let (Some(node), Some(start)) = (current, instruction.first()) {
+                    let range = token_range(line_no, raw, start, last);
+                    let pc = pcs.get_mut(&node).unwrap();
+                    analysis.instructions.push(InstLine { node, pc: *pc, range, inst: None });
+                    inst_tokens.push((instruction, scope.clone()));
+                    *pc += 1;
+                }
+            }
+            Line::Assert(tokens) => match current {
+                Some(node) => assert_tokens.push((node, tokens, range)),
+                None => analysis.diagnostics.push(outside_node(range, "ASSERT")),
+            },
+            Line::Instruction(tokens) => match current {
+                Some(node) => {
+                    let pc = pcs.get_mut(&node).unwrap();
+                    analysis.instructions.push(InstLine { node, pc: *pc, range, inst: None });
+                    inst_tokens.push((tokens, scope.clone()));
+                    *pc += 1;
+                }
+                None => analysis.diagnostics.push(outside_node(range, "instruction")),
+            },
+        }
+    }
+    if let Some(block) = analysis.nodes.last_mut() {
+        block.last_line = analysis.line_nodes.len().saturating_sub(1);
+    }
+    if let Some(open) = conditions.innermost() {
+        let message = ".if has no .endif".to_string();
+        analysis.diagnostics.push(Diagnostic { range: if_ranges[&open], severity: SEVERITY_ERROR, message });
+    }
+
+    // Second pass, once every label of every node is known.
+    let mut node_labels: Labels = Labels::new();
+    for label in &analysis.labels {
+        node_labels.entry(label.node).or_default().insert(label.name.clone(), label.pc);
+    }
+    let no_labels = BTreeMap::new();
+    // A node's constants count in all of its lines, above their `DEFINE` too.
+    let constant_scope =
+        |node| ConstantScope { file: &constants, node: Some((node, node_constants.get(&node).unwrap_or(&no_constants))) };
+    for (node, tokens, range) in &assert_tokens {
+        if let Err(e) = parse_assert(tokens, range.line + 1, &constant_scope(*node)) {
+            analysis.diagnostics.push(Diagnostic { range: *range, severity: SEVERITY_ERROR, message: e.to_string() });
+        }
+    }
+    for (inst_line, (tokens, scope)) in analysis.instructions.iter_mut().zip(&inst_tokens) {
+        let labels = node_labels.get(&inst_line.node).unwrap_or(&no_labels);
+        let constants = constant_scope(inst_line.node);
+        let options = ParseOptions::default();
+        match parse_instruction_at(tokens, inst_line.node, labels, scope.as_deref(), &constants, &Dialect::default(), &options)
+            .map_err(|(_, e)| e)
+        {
+            // A literal lowered to several instructions has no one word to show.
+            Ok(lowered) => inst_line.inst = <[Inst; 1]>::try_from(lowered.steps).ok().map(|[inst]| inst),
+            Err(e) => analysis.diagnostics.push(Diagnostic {
+                range: inst_line.range,
+                severity: SEVERITY_ERROR,
+                message: e.to_string(),
+            }),
+        }
+    }
+    analysis.diagnostics.sort_by_key(|d| (d.range.line, d.range.start));
+    analysis
+}
+
+fn outside_node(range: LineRange, what: &str) -> Diagnostic {
+    Diagnostic { range, severity: SEVERITY_ERROR, message: before_first_node(what) }
+}
+
+impl Analysis {
+    fn node_at(&self, line: usize) -> Option<(usize, usize)> {
+        self.line_nodes.get(line).copied().flatten()
+    }
+
+    fn label(&self, node: (usize, usize), name: &str) -> Option<&LabelDef> {
+        self.labels.iter().find(|l| l.node == node && l.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The token under the cursor (UTF-16 `character`) and its range.
+fn token_at(text: &str, line: usize, character: usize) -> Option<(String, LineRange)> {
+    let raw = text.lines().nth(line)?;
+    lexer::lex_line(raw, line + 1)
+        .iter()
+        .map(|t| (t.text.to_string(), token_range(line, raw, t, t)))
+        .find(|(_, range)| range.contains(line, character))
+}
+
+pub fn diagnostics(text: &str) -> Vec<Value> {
+    analyze(text)
+        .diagnostics
+        .iter()
+        .map(|d| {
+            json!({
+                "range": d.range.to_json(),
+                "severity": d.severity,
+                "source": "zk100",
+                "message": d.message,
+            })
+        })
+        .collect()
+}
+
+/// Location of the label referenced at the cursor, within the same node.
+pub fn definition(text: &str, line: usize, character: usize) -> Option<LineRange> {
+    let analysis = analyze(text);
+    let node = analysis.node_at(line)?;
+    let (word, _) = token_at(text, line, character)?;
+    let scope = analysis.line_scopes.get(line).cloned().flatten();
+    let local = scope.filter(|_| is_local(&word)).and_then(|scope| analysis.label(node, &label_key(&word, Some(&scope))));
+    local.or_else(|| analysis.label(node, &word)).map(|l| l.range)
+}
+
+/// Plain-English reading of an instruction.
+pub fn explain(inst: &Inst) -> String {
+    let src = match inst.src {
+        Src::Lit(v) => v.to_string(),
+        Src::Acc => "ACC".to_string(),
+        Src::Nil => "zero".to_string(),
+        Src::In => "the next input (node (0,0) only)".to_string(),
+        Src::P(port) => format!("the {} neighbour", port.to_string().to_lowercase()),
+        Src::Last => "LAST (always blocks in the guest)".to_string(),
+    };
+    let dst = match inst.dst {
+        Dst::Acc => "ACC".to_string(),
+        Dst::Nil => "nowhere (discarded)".to_string(),
+        Dst::Out => "the output stream (recorded at node (1,1) only)".to_string(),
+        Dst::P(port) => format!("the {} neighbour", port.to_string().to_lowercase()),
+        Dst::Last => "LAST (always blocks in the guest)".to_string(),
+    };
+    match inst.op {
+        Op::Mov => format!("Move {} to {}.", src, dst),
+        Op::Add => format!("Add {} to ACC.", src),
+        Op::Sub => format!("Subtract {} from ACC.", src),
+        Op::Neg => "Negate ACC.".to_string(),
+        Op::Sav => "Copy ACC into BAK.".to_string(),
+        Op::Swp => "Swap ACC and BAK.".to_string(),
+        Op::Jmp => format!("Jump to instruction {}.", src),
+        Op::Jz => format!("Jump to instruction {} if ACC is zero.", src),
+        Op::Jnz => format!("Jump to instruction {} if ACC is not zero.", src),
+        Op::Jgz => format!("Jump to instruction {} if ACC is positive.", src),
+        Op::Jlz => format!("Jump to instruction {} if ACC is negative.", src),
+        Op::Nop => "Do nothing.".to_string(),
+        Op::Hlt => "Do nothing; the guest treats HLT like NOP and wraps around.".to_string(),
+    }
+}
+
+/// Markdown hover for the instruction on `line`.
+pub fn hover(text: &str, line: usize, character: usize) -> Option<(String, LineRange)> {
+    let analysis = analyze(text);
+    if let Some(inst_line) = analysis.instructions.iter().find(|i| i.range.contains(line, character)) {
+        let inst = inst_line.inst.as_ref()?;
+        let markdown = format!(
+            "`{}` → `0x{:08x}`\n\n{}\n\nnode ({},{}) pc {}",
+            inst,
+            inst.encode(),
+            explain(inst),
+            inst_line.node.0,
+            inst_line.node.1,
+            inst_line.pc
+        );
+        return Some((markdown, inst_line.range));
+    }
+    let label = analysis.labels.iter().find(|l| l.range.contains(line, character))?;
+    Some((format!("label `{}` → pc {}", label.name, label.pc), label.range))
+}
+
+pub fn document_symbols(text: &str) -> Vec<Value> {
+    const KIND_MODULE: u8 = 2;
+    const KIND_KEY: u8 = 20;
+    let analysis = analyze(text);
+    analysis
+        .nodes
+        .iter()
+        .map(|block| {
+            let children: Vec<Value> = analysis
+                .labels
+                .iter()
+                .filter(|l| l.range.line > block.header.line && l.range.line <= block.last_line)
+                .map(|l| {
+                    json!({
+                        "name": l.name,
+                        "detail": format!("pc {}", l.pc),
+                        "kind": KIND_KEY,
+                        "range": l.range.to_json(),
+                        "selectionRange": l.range.to_json(),
+                    })
+                })
+                .collect();
+            json!({
+                "name": match &block.name {
+                    Some(name) => format!("NODE {} ({},{})", name, block.coords.0, block.coords.1),
+                    None => format!("NODE ({},{})", block.coords.0, block.coords.1),
+                },
+                "kind": KIND_MODULE,
+                "range": {
+                    "start": { "line": block.header.line, "character": 0 },
+                    "end": { "line": block.last_line, "character": 0 },
+                },
+                "selectionRange": block.header.to_json(),
+                "children": children,
+            })
+        })
+        .collect()
+}
+
+/// Mnemonics for the first word of a line, operands and in-scope labels after.
+pub fn completion(text: &str, line: usize, character: usize) -> Vec<Value> {
+    const KIND_KEYWORD: u8 = 14;
+    const KIND_VARIABLE: u8 = 6;
+    const KIND_REFERENCE: u8 = 18;
+    let analysis = analyze(text);
+    let raw = text.lines().nth(line).unwrap_or("");
+    let before: String = char_prefix(raw, character);
+    if before.split_whitespace().count() <= 1 && !before.ends_with(char::is_whitespace) {
+        let mut items: Vec<Value> = Op::ALL
+            .iter()
+            .map(|op| json!({ "label": op.mnemonic(), "kind": KIND_KEYWORD }))
+            .collect();
+        items.push(json!({ "label": "NODE", "kind": KIND_KEYWORD, "insertText": "NODE (0,0)" }));
+        return items;
+    }
+    let mut items: Vec<Value> = OPERANDS
+        .iter()
+        .map(|name| json!({ "label": name, "kind": KIND_VARIABLE }))
+        .collect();
+    if let Some(node) = analysis.node_at(line) {
+        items.extend(analysis.labels.iter().filter(|l| l.node == node).map(|l| {
+            json!({ "label": l.name, "kind": KIND_REFERENCE, "detail": format!("pc {}", l.pc) })
+        }));
+    }
+    items
+}
+
+/// The part of `line` before UTF-16 column `character`.
+fn char_prefix(line: &str, character: usize) -> String {
+    let mut col = 0;
+    line.chars()
+        .take_while(|ch| {
+            col += ch.len_utf16();
+            col <= character
+        })
+        .collect()
+}
+
+/// What `initialize` answers: the capabilities and who is serving them.
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "definitionProvider": true,
+            "hoverProvider": true,
+            "documentSymbolProvider": true,
+            "completionProvider": { "triggerCharacters": [" ", ":"] },
+        },
+        "serverInfo": { "name": "zk100", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// Open documents plus the request dispatcher.
+#[derive(Default)]
+pub struct LanguageServer {
+    documents: HashMap<String, String>,
+    assemblers: HashMap<String, IncrementalAssembler>,
+}
+
+fn position(params: &Value) -> Option<(String, usize, usize)> {
+    let uri = params["textDocument"]["uri"].as_str()?.to_string();
+    let line = params["position"]["line"].as_u64()? as usize;
+    let character = params["position"]["character"].as_u64()? as usize;
+    Some((uri, line, character))
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn text(&self, uri: &str) -> &str {
+        self.documents.get(uri).map(String::as_str).unwrap_or("")
+    }
+
+    fn publish(&self, uri: &str) -> Notification {
+        Notification::new(
+            "textDocument/publishDiagnostics".to_string(),
+            json!({ "uri": uri, "diagnostics": diagnostics(self.text(uri)) }),
+        )
+    }
+
+    /// Answer a request; `None` for a method the server does not implement.
+    pub fn request(&mut self, method: &str, params: &Value) -> Option<Value> {
+        let result = match method {
+            "textDocument/definition" => match position(params) {
+                Some((uri, line, character)) => match definition(self.text(&uri), line, character) {
+                    Some(range) => json!({ "uri": uri, "range": range.to_json() }),
+                    None => Value::Null,
+                },
+                None => Value::Null,
+            },
+            "textDocument/hover" => match position(params) {
+                Some((uri, line, character)) => match hover(self.text(&uri), line, character) {
+                    Some((markdown, range)) => json!({
+                        "contents": { "kind": "markdown", "value": markdown },
+                        "range": range.to_json(),
+                    }),
+                    None => Value::Null,
+                },
+                None => Value::Null,
+            },
+            "textDocument/documentSymbol" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                json!(document_symbols(self.text(uri)))
+            }
+            "zk100/programRoot" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                let text = self.documents.get(uri).map(String::as_str).unwrap_or("");
+                match self.assemblers.entry(uri.to_string()).or_default().update(text) {
+                    Ok(build) => {
+                        let errors: Vec<Value> = build
+                            .errors
+                            .iter()
+                            .map(|((r, c), message)| json!({ "node": [r, c], "message": message }))
+                            .collect();
+                        json!({ "root": build.root, "progWords": build.prog_words, "errors": errors })
+                    }
+                    Err(e) => json!({ "root": null, "progWords": null, "errors": [{ "node": null, "message": e.to_string() }] }),
+                }
+            }
+            "textDocument/completion" => match position(params) {
+                Some((uri, line, character)) => json!(completion(self.text(&uri), line, character)),
+                None => json!([]),
+            },
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    /// Apply a document notification; returns the diagnostics to publish.
+    /// Unknown notifications are ignored.
+    pub fn notify(&mut self, method: &str, params: &Value) -> Option<Notification> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("").to_string();
+                self.documents.insert(uri.clone(), text);
+            }
+            "textDocument/didChange" => {
+                // Full sync: the last change carries the whole document.
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.assemblers.remove(&uri);
+            }
+            _ => return None,
+        }
+        Some(self.publish(&uri))
+    }
+
+    /// Handle one incoming message; returns the messages to send back.
+    pub fn handle(&mut self, message: Message) -> Vec<Message> {
+        match message {
+            Message::Request(request) => {
+                let response = match self.request(&request.method, &request.params) {
+                    Some(result) => Response::new_ok(request.id, result),
+                    None => Response::new_err(
+                        request.id,
+                        ErrorCode::MethodNotFound as i32,
+                        format!("unknown method '{}'", request.method),
+                    ),
+                };
+                vec![response.into()]
+            }
+            Message::Notification(notification) => {
+                self.notify(&notification.method, &notification.params).into_iter().map(Message::from).collect()
+            }
+            Message::Response(_) => vec![],
+        }
+    }
+}
+
+/// Serve LSP on `connection`: the initialize handshake, then requests and
+/// notifications until the client shuts the server down or hangs up.
+pub fn run(connection: &Connection) -> Result<()> {
+    let (id, _params) = connection.initialize_start()?;
+    connection.initialize_finish(id, initialize_result())?;
+    let mut server = LanguageServer::new();
+    for message in &connection.receiver {
+        if let Message::Request(request) = &message {
+            if connection.handle_shutdown(request)? {
+                break;
+            }
+        }
+        for reply in server.handle(message) {
+            connection.sender.send(reply)?;
+        }
+    }
+    Ok(())
+}
+
+/// Serve LSP over stdin/stdout.
+pub fn serve_stdio() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    run(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_server::Request;
+
+    const DOC: &str = "NODE (0,0)\nloop:\n    MOV IN, ACC\n    JZ loop\n    FOO 1\n\nNODE (1,1)\nend:\n    JMP end\n";
+
+    #[test]
+    fn test_diagnostics_cover_every_bad_line() {
+        let diags = diagnostics("NODE (0,0)\nFOO\nMOV 1\nNODE (3,0)\nNODE (1,1)\nx:\nx:\nNOP\n");
+        let lines: Vec<u64> = diags.iter().map(|d| d["range"]["start"]["line"].as_u64().unwrap()).collect();
+        assert_eq!(lines, [1, 2, 3, 6]);
+        assert_eq!(diags[3]["message"], "label 'x' is defined twice in node (1,1), on lines 6 and 7");
+        assert_eq!(diags[0]["message"], "Unknown operation: FOO");
+        assert_eq!(diags[0]["range"]["end"]["character"], 3);
+
+        let diags = diagnostics("NODE (0,0)\nNOP\nNODE (0,0)\nNOP\nNODE (1,1) CONTINUE\n");
+        let messages: Vec<&str> = diags.iter().map(|d| d["message"].as_str().unwrap()).collect();
+        assert_eq!(
+            messages,
+            [
+                "node (0,0) is declared twice, on lines 1 and 3; write NODE (0,0) CONTINUE to append to it",
+                "NODE (1,1) CONTINUE has no earlier NODE (1,1) block to append to"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inline_labels() {
+        let doc = "NODE (0,0)\nloop: MOV IN, ACC\n    JZ loop\nx: y: NOP\n";
+        assert!(diagnostics(doc).is_empty());
+        assert_eq!(definition(doc, 2, 8), Some(LineRange { line: 1, start: 0, end: 4 }));
+        assert!(hover(doc, 1, 7).unwrap().0.starts_with("`MOV IN, ACC` → `0x"));
+        let analysis = analyze(doc);
+        assert_eq!(analysis.labels.iter().map(|l| (l.name.as_str(), l.pc)).collect::<Vec<_>>(), [("loop", 0), ("x", 2), ("y", 2)]);
+        assert_eq!(analysis.instructions[2].range, LineRange { line: 3, start: 6, end: 9 });
+
+        let diags = diagnostics("NODE (0,0)\nx: acc: NOP\n");
+        assert_eq!(diags.len(), 1);
+        assert!(diags[0]["message"].as_str().unwrap().starts_with("label 'acc' is reserved: ACC is a register"));
+        assert_eq!(diags[0]["range"]["start"]["character"], 3);
+    }
+
+    #[test]
+    fn test_definition_is_scoped_to_node() {
+        assert_eq!(definition(DOC, 3, 8), Some(LineRange { line: 1, start: 0, end: 4 }));
+        assert_eq!(definition(DOC, 8, 9), Some(LineRange { line: 7, start: 0, end: 3 }));
+        assert_eq!(definition(DOC, 2, 8), None);
+    }
+
+    #[test]
+    fn test_local_labels_follow_their_scope() {
+        let doc = "NODE (0,0)\na:\n.loop: NOP\nJMP .loop\nb:\n.loop: NOP\nJMP .loop\n";
+        assert!(diagnostics(doc).is_empty());
+        assert_eq!(analyze(doc).labels.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(), ["a", "a.loop", "b", "b.loop"]);
+        assert_eq!(definition(doc, 3, 6).map(|r| r.line), Some(2));
+        assert_eq!(definition(doc, 6, 6).map(|r| r.line), Some(5));
+    }
+
+    #[test]
+    fn test_dropped_branches_are_not_read() {
+        let doc = "DEFINE DEBUG 0\nNODE (0,0)\n.if DEBUG\nnext: MOV ACC, P:RIGHT\n.else\nnext: ADD 1\n.endif\nJMP next\n";
+        assert!(diagnostics(doc).is_empty());
+        assert_eq!(analyze(doc).labels.iter().map(|l| l.range.line).collect::<Vec<_>>(), [5]);
+        let open = analyze("NODE (0,0)\n.if 1\nNOP\n").diagnostics;
+        assert_eq!(open.iter().map(|d| (d.range.line, d.message.as_str())).collect::<Vec<_>>(), [(1, ".if has no .endif")]);
+    }
+
+    #[test]
+    fn test_constants_are_scoped_like_the_assembler() {
+        let doc = "GLOBAL LIMIT 1\nNODE (0,0)\nASSERT ACC < N\nADD N\nDEFINE N 2\nDEFINE LIMIT 3\nNODE (1,1)\nADD N\n";
+        let messages: Vec<(usize, String)> = analyze(doc).diagnostics.into_iter().map(|d| (d.range.line, d.message)).collect();
+        assert_eq!(
+            messages,
+            [
+                (5, "node (0,0) cannot define 'LIMIT'; it is GLOBAL (line 1)".to_string()),
+                (7, "undefined constant 'N' (searched node (1,1): none; file: LIMIT)".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_is_flagged_where_it_is() {
+        let analysis = analyze("NODE (0,0)\nMOV IN, \u{410}CC ; ok: é\n\u{feff}NOP\n");
+        let flagged: Vec<_> = analysis.diagnostics.iter().map(|d| (d.range, d.message.as_str())).collect();
+        assert_eq!(
+            flagged,
+            [(
+                LineRange { line: 1, start: 8, end: 9 },
+                "'АCC' has the non-ASCII character 'А' (U+0410); outside comments, source is ASCII"
+            )]
+        );
+        assert_eq!(analysis.instructions.len(), 1);
+    }
+
+    #[test]
+    fn test_hover_shows_word_and_explanation() {
+        let (markdown, range) = hover(DOC, 2, 6).unwrap();
+        assert!(markdown.starts_with("`MOV IN, ACC` → `0x"), "{}", markdown);
+        assert!(markdown.contains("Move the next input"), "{}", markdown);
+        assert_eq!(range, LineRange { line: 2, start: 4, end: 15 });
+        assert!(hover(DOC, 1, 1).unwrap().0.contains("pc 0"));
+        assert!(hover(DOC, 4, 5).is_none());
+    }
+
+    #[test]
+    fn test_document_symbols_nest_labels() {
+        let symbols = document_symbols(DOC);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0]["name"], "NODE (0,0)");
+        assert_eq!(symbols[0]["children"][0]["name"], "loop");
+        assert_eq!(symbols[1]["children"][0]["name"], "end");
+        assert_eq!(document_symbols("NODE bottom_left\nNOP\n")[0]["name"], "NODE BOTTOM_LEFT (1,0)");
+    }
+
+    #[test]
+    fn test_completion_by_position() {
+        let items = completion(DOC, 3, 5);
+        assert!(items.iter().any(|i| i["label"] == "JNZ"));
+        let items = completion(DOC, 3, 7);
+        assert!(items.iter().any(|i| i["label"] == "P:LEFT"));
+        assert!(items.iter().any(|i| i["label"] == "loop"));
+        assert!(!items.iter().any(|i| i["label"] == "end"));
+    }
+
+    #[test]
+    fn test_session() {
+        let (server, client) = Connection::memory();
+        let messages: [Message; 5] = [
+            Request::new(1.into(), "initialize".to_string(), json!({})).into(),
+            Notification::new("initialized".to_string(), json!({})).into(),
+            Notification::new(
+                "textDocument/didOpen".to_string(),
+                json!({"textDocument": {"uri": "file:///a.asm", "text": "NODE (0,0)\nBAD\n"}}),
+            )
+            .into(),
+            Request::new(2.into(), "shutdown".to_string(), Value::Null).into(),
+            Notification::new("exit".to_string(), Value::Null).into(),
+        ];
+        for message in messages {
+            client.sender.send(message).unwrap();
+        }
+        run(&server).unwrap();
+
+        let Ok(Message::Response(init)) = client.receiver.try_recv() else { panic!("expected the initialize response") };
+        assert_eq!(init.result.unwrap()["capabilities"]["hoverProvider"], true);
+        let Ok(Message::Notification(published)) = client.receiver.try_recv() else { panic!("expected diagnostics") };
+        assert_eq!(published.method, "textDocument/publishDiagnostics");
+        assert_eq!(published.params["diagnostics"][0]["message"], "Unknown operation: BAD");
+        let Ok(Message::Response(shutdown)) = client.receiver.try_recv() else { panic!("expected the shutdown response") };
+        assert_eq!(shutdown.id, 2.into());
+        assert!(client.receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_unknown_request_is_an_error() {
+        let mut server = LanguageServer::new();
+        let replies = server.handle(Request::new(7.into(), "zk100/nope".to_string(), Value::Null).into());
+        let [Message::Response(response)] = replies.as_slice() else { panic!("expected one response") };
+        assert_eq!(response.error.as_ref().unwrap().code, ErrorCode::MethodNotFound as i32);
+        assert!(server.handle(Notification::new("$/cancelRequest".to_string(), Value::Null).into()).is_empty());
+    }
+
+    #[test]
+    fn test_program_root_request() {
+        let mut server = LanguageServer::new();
+        let uri = json!({"textDocument": {"uri": "file:///a.asm"}});
+        let change = |text: &str| json!({"textDocument": {"uri": "file:///a.asm"}, "contentChanges": [{"text": text}]});
+        server.notify("textDocument/didChange", &change(DOC));
+        let result = server.request("zk100/programRoot", &uri).unwrap();
+        assert_eq!(result["errors"][0]["node"], json!([0, 0]));
+        assert!(result["root"].is_null());
+
+        let fixed = DOC.replace("    FOO 1\n", "");
+        server.notify("textDocument/didChange", &change(&fixed));
+        let result = server.request("zk100/programRoot", &uri).unwrap();
+        let root = crate::merkle::program_root_hex(&crate::assembler::parse_assembly(&fixed).unwrap()).unwrap();
+        assert_eq!(result["root"], root);
+        assert!(result["errors"].as_array().unwrap().is_empty());
+        // Node (1,1) did not change between the two requests.
+        assert_eq!(server.assemblers["file:///a.asm"].counters().nodes_reused, 1);
+    }
+}
@@ -1,98 +1,2240 @@
-mod assembler;
-mod instruction;
-// mod merkle;  // No longer needed - Cairo computes merkle roots
-mod cairo_abi;
-
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use clap::{Parser, Subcommand};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{BufRead, IsTerminal as _, Read as _, Write as _};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use zk100_host::trace::{self, TimingAggregator};
+use zk100_host::assembler::{Diagnostic, GridSize, Labels, ParseOptions};
+use zk100_host::limits::{self, Limit, Limits};
+use zk100_host::grid::{self, ProgramGrid};
+use zk100_host::attest::{self, AssembleOptions, Metadata};
+use zk100_host::bundle::{self, Bundle};
+use zk100_host::cairo_abi::{InputSentinel, IoNodes, NodeOutputs, Section, SentinelBehavior};
+use zk100_host::exec::{self, PendingFile, Progress};
+use zk100_host::expected::ExpectedSpec;
+use zk100_host::inputs::{self, InputSpec};
+use zk100_host::source::Origins;
+use zk100_host::source_streams::{self, Streams};
+use zk100_host::sim::{LastBehavior, Simulator};
+use zk100_host::args_stream::{self, ArgsInspection};
+use zk100_host::artifacts::{self, ArtifactSink, Assembled, Emit, FsSink};
+use zk100_host::stats::{self, Facts, StatsRecord};
+use zk100_host::capabilities::Resolved;
+use zk100_host::dialect::{self, Dialect};
+use zk100_host::program_id::ProgramId;
+use zk100_host::word_list::{self, WordFormat};
+use zk100_host::{assembler, bisect, build, cairo_abi, cairo_consts, capabilities, config, conformance, corpus, daemon, examples, guest_check, hints, instruction, listing, lsp, merkle, migrate, minimize, program_gen, proof_reader, queue, report, scarb, schema, seal, session, source};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "ZK-100 Host - Assembly to Proof Pipeline")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Print a per-phase timing table to stderr on exit
+    #[arg(long, global = true)]
+    timings: bool,
+    /// Write the per-phase timing summary as JSON to this file
+    #[arg(long, global = true, value_name = "PATH")]
+    timings_json: Option<PathBuf>,
+    /// Never show progress spinners
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Append a record of this assemble, prove or verify-proof run to .zk100/stats.jsonl
+    #[arg(long, global = true)]
+    stats_log: bool,
+    /// Print only the stable, scriptable records (or `--json` output) on stdout; see README
+    #[arg(long, global = true)]
+    porcelain: bool,
+    /// Do not read output files back to check them after writing (for slow network filesystems)
+    #[arg(long, global = true)]
+    no_verify_write: bool,
+    /// Record this run (arguments, inputs, outputs) to DIR for `replay`; defaults to `record_session` in zk100.toml
+    #[arg(long, global = true, value_name = "DIR")]
+    record_session: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Assemble a program from assembly to Cairo format
     Assemble {
-        /// Input assembly file
+        /// Input assembly file (`-` reads stdin)
         input: PathBuf,
-        /// Output args.json file
+        /// Output args.json file (`-` writes it to stdout)
         #[arg(short, long, default_value = "args.json")]
         output: PathBuf,
-        /// Input values (comma-separated)
-        #[arg(short = 'i', long)]
-        inputs: Option<String>,
-        /// Expected output values (comma-separated)
-        #[arg(short = 'e', long)]
-        expected: Option<String>,
+        #[command(flatten)]
+        inputs: InputArgs,
+        #[command(flatten)]
+        expected: ExpectedArgs,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        io: IoArgs,
+        /// Also write the reproduction metadata sidecar here
+        #[arg(long)]
+        metadata: Option<PathBuf>,
+        /// Also write labels, their references and jump provenance here as JSON
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        /// Also write the private hints file for the prover here
+        #[arg(long)]
+        hints: Option<PathBuf>,
+        /// Also write a listing here: per instruction its prog_words index, pc, word and source line
+        #[arg(long)]
+        listing: Option<PathBuf>,
+        /// Fail without writing anything when assembling warns
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+    /// Re-run the pipeline with the options in a metadata sidecar and compare
+    Attest {
+        /// Assembly source
+        program: PathBuf,
+        /// metadata.json written by `assemble --metadata` or taken from a bundle
+        #[arg(long)]
+        against: PathBuf,
+    },
+    /// Publish a program's root and shape, optionally with the source encrypted
+    Seal {
+        /// Assembly source
+        program: PathBuf,
+        /// Sealed file to write
+        #[arg(short, long, default_value = "sealed.json")]
+        output: PathBuf,
+        /// Encrypt the source into the sealed file under this passphrase
+        #[arg(long)]
+        passphrase: Option<String>,
+    },
+    /// Recover or check the source behind a sealed file against its root
+    Unseal {
+        /// Sealed file written by `seal`
+        sealed: PathBuf,
+        /// Decrypt the source embedded in the sealed file
+        #[arg(long, required_unless_present = "source", conflicts_with = "source")]
+        passphrase: Option<String>,
+        /// Check a source revealed separately instead of decrypting one
+        #[arg(long)]
+        source: Option<PathBuf>,
+        /// Where to write the decrypted source (defaults to stdout)
+        #[arg(short, long, requires = "passphrase")]
+        output: Option<PathBuf>,
+    },
+    /// Convert a program between assembly and the versioned JSON format
+    Convert {
+        /// Input program (.json is read as JSON, anything else as assembly unless --from says; `-` reads stdin)
+        input: PathBuf,
+        /// Input format, when the file name does not say
+        #[arg(long, value_enum)]
+        from: Option<ConvertFormat>,
+        /// Output format; JSON is always written with the latest schema
+        #[arg(long, value_enum)]
+        to: ConvertFormat,
+        /// Word list layout for `words` (defaults by extension: .csv, .rs, else text)
+        #[arg(long, value_enum)]
+        word_format: Option<WordFormatArg>,
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print an aligned listing of a program: pc, word, label, instruction
+    Disassemble {
+        /// Assembly, grid JSON, or a JSON array of instruction words (`-` reads stdin)
+        program: PathBuf,
+        #[command(flatten)]
+        style: StyleArgs,
+        /// Write the listing here instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Re-run a session recorded with --record-session and compare its outputs
+    Replay {
+        /// The session directory
+        dir: PathBuf,
+    },
+    /// Assemble a program and prove its execution with cairo-prove
+    Prove {
+        /// Input assembly file
+        input: PathBuf,
+        #[command(flatten)]
+        inputs: InputArgs,
+        #[command(flatten)]
+        expected: ExpectedArgs,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        io: IoArgs,
+        /// Where to write the generated args.json
+        #[arg(long, default_value = "args.json")]
+        args: PathBuf,
+        /// Where cairo-prove writes the proof
+        #[arg(long, default_value = "proof.json")]
+        proof: PathBuf,
+        /// Write the private hints file here and pass it to cairo-prove
+        #[arg(long)]
+        hints: Option<PathBuf>,
+        #[command(flatten)]
+        guest: GuestArgs,
+    },
+    /// Check a proof with `cairo-prove verify` and print its public output
+    VerifyProof {
+        proof: PathBuf,
+        /// Print the public output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check that a proof commits to the root of a program
+    CheckRoot {
+        proof: PathBuf,
+        program: PathBuf,
+    },
+    /// Print the registry ID of a program's commitment
+    Id {
+        /// Assembly, grid JSON, or a JSON array of instruction words (`-` reads stdin)
+        program: PathBuf,
+    },
+    /// Find the instruction words whose encoding changed since an old args file
+    BisectEncoding {
+        /// args.json written by the older assembler (`-` reads stdin)
+        old_args: PathBuf,
+        /// The program it was assembled from
+        program: PathBuf,
+        /// Layout of the old args file
+        #[arg(long, default_value_t = cairo_abi::LEGACY_ABI, value_parser = clap::value_parser!(u32).range(1..=7))]
+        abi: u32,
+        /// Print the differences as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Report where an args file's sections lie, reading it as a stream
+    InspectArgs {
+        /// args.json to read (`-` reads stdin)
+        args: PathBuf,
+        /// Layout of the args file
+        #[arg(long, default_value_t = cairo_abi::LEGACY_ABI, value_parser = clap::value_parser!(u32).range(1..=7))]
+        abi: u32,
+        /// Print only this section's values, one per line
+        #[arg(long, value_enum)]
+        only: Option<ArgsSection>,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+        /// Color the report: always, never, or on a terminal without NO_COLOR
+        #[arg(long, value_enum, default_value = "auto")]
+        style: StyleArg,
+    },
+    /// Package a program, its args and its proof into one .zk100 file
+    Bundle(BundleCmd),
+    /// Merge single-case args files into one batched args file, or split one
+    Batch {
+        #[command(subcommand)]
+        action: BatchAction,
+    },
+    /// Validate and prove a directory of job manifests
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+    /// List, show, run or export the built-in example programs
+    Example {
+        #[command(subcommand)]
+        action: ExampleAction,
+    },
+    /// Generate a synthetic program of a given size, with a case it passes, for load testing
+    GenProgram {
+        /// Nodes the program uses: 3 is a pipeline from IN to OUT, 4 adds a co-processor at (1,0)
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(3..=4))]
+        nodes: u32,
+        /// Instructions in every node
+        #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u32).range(1..=program_gen::MAX_INSTRUCTIONS as i64))]
+        instructions: u32,
+        /// Counted loops in every node
+        #[arg(long, default_value_t = 1)]
+        loops: u32,
+        /// Port handshakes around each transfer
+        #[arg(long, value_enum, default_value_t = PortsArg::Light)]
+        ports: PortsArg,
+        /// Number of inputs in the generated case
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+        input_count: u32,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Assembly file to write, with the case next to it as `<stem>.cases.json` (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Convert a directory of TIS-100 saves into zk-100 programs
+    ImportCorpus {
+        /// Directory of `<puzzle>.<slot>.txt` saves
+        dir: PathBuf,
+        /// Puzzle spec (defaults to DIR/puzzles.json)
+        #[arg(long)]
+        spec: Option<PathBuf>,
+        /// Where to write one directory per imported save
+        #[arg(long, default_value = "corpus")]
+        out_dir: PathBuf,
+    },
+    /// Recompute registry roots under a new commitment version, verifying each old root first
+    MigrateRoots {
+        /// CSV of program_id,path,old_root rows
+        #[arg(long)]
+        input: PathBuf,
+        /// Commitment version of the old roots, e.g. v1
+        #[arg(long, value_parser = migrate::parse_version)]
+        from: u32,
+        /// Commitment version to compute the new roots under
+        #[arg(long, value_parser = migrate::parse_version)]
+        to: u32,
+        /// Directory the rows' paths are relative to
+        #[arg(long, default_value = ".")]
+        sources_dir: PathBuf,
+        /// CSV to write, with new_root, status and detail columns (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// How many rows to migrate at once
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: u16,
+    },
+    /// Write a self-contained HTML report for a solved puzzle
+    Report {
+        /// Puzzle manifest naming the program, cases and optional proof
+        #[arg(long)]
+        manifest: PathBuf,
+        /// HTML file to write
+        #[arg(short, long, default_value = "report.html")]
+        output: PathBuf,
+        /// Also write the report data as JSON next to the HTML
+        #[arg(long)]
+        json: bool,
+        /// Do not check the program's ASSERT lines while simulating
+        #[arg(long)]
+        ignore_asserts: bool,
+    },
+    /// Rebuild the out-of-date programs and cases of every puzzle manifest in the workspace
+    Build {
+        /// Workspace directory to scan for puzzle manifests; state goes to its .zk100/
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+        /// Rebuild everything, fresh or not
+        #[arg(long)]
+        force: bool,
+        /// Print what would be rebuilt and why, without building
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Shrink a working solution: drop and merge instructions while every case still passes
+    Minimize {
+        /// Assembly file to shrink
+        input: PathBuf,
+        /// Puzzle manifest whose cases the result must pass (its `program` is not used)
+        #[arg(long)]
+        manifest: PathBuf,
+        /// How long to search: `30s`, `500ms`, `2m`, or plain seconds
+        #[arg(long, default_value = "30s", value_parser = parse_duration)]
+        budget: Duration,
+        /// Random input streams on which the result must match the original's outputs
+        #[arg(long, default_value_t = 16)]
+        random_inputs: usize,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Assembly file to write (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the JSON Schema of a JSON artifact
+    Schema {
+        #[arg(value_enum)]
+        artifact: ArtifactArg,
+        #[arg(long, value_enum, default_value_t = SchemaFormatArg::JsonSchema)]
+        format: SchemaFormatArg,
+        /// File to write (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Report the versions, hash, grid size and instruction set this build supports
+    Capabilities {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+    /// Check that the external tools and the guest artifact are available
+    Doctor {
+        #[command(flatten)]
+        guest: GuestArgs,
+    },
+    /// Run the conformance corpus and seeded random programs on the host and through the guest, reporting every divergence
+    Conformance {
+        #[command(flatten)]
+        guest: GuestArgs,
+        /// How to run the guest: `prove` with cairo-prove, or `execute` with scarb (faster, no proof)
+        #[arg(long, value_enum, default_value_t = RunnerArg::Prove)]
+        runner: RunnerArg,
+        /// Also run the `.asm` programs in this directory, one case per `# inputs:` line (repeatable)
+        #[arg(long)]
+        corpus: Vec<PathBuf>,
+        /// How many random programs to generate
+        #[arg(long, default_value_t = 20)]
+        random: usize,
+        /// Seed for the random programs
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Only run the cases whose name contains this
+        #[arg(long)]
+        only: Option<String>,
+        /// Where case args go while they run, and the source and args of divergent cases stay
+        #[arg(long, default_value = ".zk100/conformance")]
+        work_dir: PathBuf,
+    },
+    /// Write Cairo constants (opcodes, operand codes, field layout) for the guest
+    GenCairoConsts {
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Compare the Cairo guest's opcode/operand codes and word layout with the host's
+    CheckGuest {
+        /// Directory holding the guest's Cairo sources
+        #[arg(long)]
+        guest_src: PathBuf,
+        /// Also list constants that match
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Run a JSON-RPC daemon (assemble, simulate, verify, hash, disassemble)
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7070")]
+        listen: String,
+        /// Maximum number of requests running at once
+        #[arg(long, default_value_t = 4)]
+        max_concurrency: usize,
+        /// Per-request timeout in milliseconds
+        #[arg(long, default_value_t = 10_000)]
+        timeout_ms: u64,
+    },
+    /// Run the language server on stdin/stdout
+    Lsp,
+    /// Step through a program's simulation in an interactive terminal dashboard
+    #[cfg(feature = "tui")]
+    Tui {
+        /// Input assembly file
+        input: PathBuf,
+        #[command(flatten)]
+        inputs: InputArgs,
+    },
+    /// Send one request to a running daemon and print the result
+    Client {
+        /// Method name, e.g. simulate
+        method: String,
+        /// JSON file holding the params object
+        params: PathBuf,
+        /// Daemon address
+        #[arg(long, default_value = "127.0.0.1:7070")]
+        connect: String,
+    },
+    /// Aggregate the local usage log written with --stats-log
+    StatsLog {
+        #[command(subcommand)]
+        action: StatsLogAction,
+    },
+}
+
+impl Commands {
+    /// The name a run of this command is logged under, if it is logged.
+    fn stats_name(&self) -> Option<&'static str> {
+        match self {
+            Commands::Assemble { .. } => Some("assemble"),
+            Commands::Prove { .. } => Some("prove"),
+            Commands::VerifyProof { .. } => Some("verify-proof"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum StatsLogAction {
+    /// Print runs and failures per day, average times and the failure rate
+    Summarize {
+        /// Project directory holding .zk100/stats.jsonl
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct BundleCmd {
+    #[command(subcommand)]
+    action: Option<BundleAction>,
+    /// Assembly source
+    #[arg(required = true)]
+    program: Option<PathBuf>,
+    /// args.json the proof was generated from
+    #[arg(required = true)]
+    args: Option<PathBuf>,
+    /// Proof written by cairo-prove
+    #[arg(required = true)]
+    proof: Option<PathBuf>,
+    /// Bundle to write
+    #[arg(short, long, default_value = "bundle.zk100")]
+    output: PathBuf,
+    /// Layout the args were written in (`assemble --abi`)
+    #[arg(long, default_value_t = cairo_abi::LEGACY_ABI, value_parser = parse_abi)]
+    abi: u32,
+}
+
+#[derive(Subcommand, Debug)]
+enum BundleAction {
+    /// Check member hashes, the program root and the args of a bundle
+    Verify {
+        bundle: PathBuf,
+        /// Also check the proof with `cairo-prove verify`
+        #[arg(long)]
+        run_verifier: bool,
+    },
+    /// Unpack a bundle into a directory
+    Extract {
+        bundle: PathBuf,
+        /// Destination directory (defaults to the bundle name without extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BatchAction {
+    /// Batch args files that share prog_words, in the order given
+    Merge {
+        #[arg(required = true)]
+        args: Vec<PathBuf>,
+        /// Batched args file to write
+        #[arg(short, long, default_value = "batch.json")]
+        output: PathBuf,
+    },
+    /// Write one single-case args file per case of a batch
+    Split {
+        batch: PathBuf,
+        /// Directory for the `case-NNN.json` files
+        #[arg(long, default_value = "singles")]
+        out_dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum QueueAction {
+    /// Prove every pending job in DIR; manifests move to done/ or failed/
+    Run {
+        dir: PathBuf,
+        /// How many cairo-prove processes run at once
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+        /// Kill a prover that runs longer than this (e.g. 90s, 30m, 2h)
+        #[arg(long, value_parser = queue::parse_duration)]
+        job_timeout: Option<Duration>,
+        /// Give up on a job after this many interrupted proving attempts
+        #[arg(long, default_value_t = 3)]
+        max_attempts: u32,
+        #[command(flatten)]
+        guest: GuestArgs,
     },
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+#[derive(Subcommand, Debug)]
+enum ExampleAction {
+    /// List the examples
+    List,
+    /// Print an example's source and cases
+    Show { name: String },
+    /// Assemble and simulate an example against its cases
+    Run { name: String },
+    /// Write an example's source and cases into a directory
+    Export { name: String, dir: PathBuf },
+}
+
+/// How to find the Cairo guest executable.
+#[derive(clap::Args, Debug)]
+struct GuestArgs {
+    /// Path to the guest executable (.executable.json)
+    #[arg(long, conflicts_with = "scarb_package")]
+    guest: Option<PathBuf>,
+    /// Scarb package whose executable artifact is the guest (e.g. zk100_exec)
+    #[arg(long)]
+    scarb_package: Option<String>,
+    /// Directory to start searching for the Scarb workspace from
+    #[arg(long, default_value = ".")]
+    scarb_dir: PathBuf,
+    /// Scarb profile the artifact is built with
+    #[arg(long, default_value = scarb::DEFAULT_PROFILE)]
+    scarb_profile: String,
+    /// Run `scarb build` for the package before using its artifact
+    #[arg(long, requires = "scarb_package")]
+    build_guest: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct InputArgs {
+    /// Input values (comma-separated)
+    #[arg(short = 'i', long)]
+    inputs: Option<String>,
+    /// Generate the inputs instead, e.g. `{ range = [1, 100] }` or `{ file = "data.csv", column = 2 }`
+    #[arg(long, conflicts_with = "inputs", value_parser = InputSpec::parse)]
+    inputs_gen: Option<InputSpec>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ExpectedArgs {
+    /// Expected output values: `a,b,..`, or `row,col=a,b,..` per node with `--abi 2` (repeatable). Patterns (`1..=9`, `*`, `len>=N`) need --expected-from-simulation
+    #[arg(short = 'e', long)]
+    expected: Vec<String>,
+    /// Simulate the program and use its outputs as the expected values, after checking them against --expected if given
+    #[arg(long)]
+    expected_from_simulation: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct LayoutArgs {
+    /// args.json layout: 1 = one flat expected stream, 2 = expected outputs per node, 4 = shared node programs sent once, 5 = designated IN/OUT nodes, 6 = input sentinel, 7 = grid size [default: 1, or the profile's]
+    #[arg(long, value_parser = parse_abi)]
+    abi: Option<u32>,
+    /// Guest compatibility profile setting the encoding, commitment, ABI, hash and schedule together (guest-v1, guest-v2, experimental); defaults to `profile` in zk100.toml
+    #[arg(long)]
+    profile: Option<String>,
+    #[command(flatten)]
+    limits: LimitArgs,
+    /// Build MOVs of literals over 255 into ACC from several instructions instead of rejecting literals above 255
+    #[arg(long)]
+    synthesize_literals: bool,
+    /// Ignore extra operands, such as the `, ACC` of `ADD 1, ACC`, with a warning instead of failing
+    #[arg(long)]
+    lenient: bool,
+    /// Fail on port operands that point off the edge of the grid instead of warning
+    #[arg(long)]
+    strict_ports: bool,
+    /// Treat `Loop` and `LOOP` as different labels; by default labels match in any case
+    #[arg(long)]
+    case_sensitive_labels: bool,
+    /// Define a constant for `.if` and operands, over a `DEFINE` of the same name, e.g. `DEBUG=1`; `DEBUG` alone is 1 (repeatable)
+    #[arg(long = "define", value_name = "NAME=VALUE", value_parser = assembler::parse_define)]
+    defines: Vec<(String, i64)>,
+    /// Pad every node with code to N instructions with NOPs, for a trace of the same shape on every run (a `.pad N` line in the source also sets it)
+    #[arg(long, value_name = "N")]
+    pad_to: Option<usize>,
+    /// Accept only what TIS-100 runs, reporting every violation by node: its mnemonics and bare ports, UP/DOWN as the streams of the I/O nodes, literals in -999..=999, at most 15 instructions a node and no zk-100 directives
+    #[arg(long)]
+    tis100: bool,
+    /// Grid rows, for guests with a grid other than 2x2 (needs `--abi 7`; a `GRID` line in the source also sets it)
+    #[arg(long)]
+    rows: Option<usize>,
+    /// Grid columns; see --rows
+    #[arg(long)]
+    cols: Option<usize>,
+}
+
+#[derive(clap::Args, Debug)]
+struct LimitArgs {
+    /// Override a limit for this run, e.g. `node_instructions=15` (repeatable; see `capabilities`)
+    #[arg(long = "limit", value_name = "NAME=VALUE", value_parser = limits::parse_override)]
+    overrides: Vec<(Limit, u64)>,
+    /// Most instructions in one node; the same as `--limit node_instructions=N`
+    #[arg(long, value_name = "N")]
+    max_instructions: Option<u64>,
+}
+
+impl LimitArgs {
+    /// The project's limits with the overrides on top.
+    fn resolve(&self) -> Result<Limits> {
+        let max_instructions = self.max_instructions.map(|n| (Limit::NodeInstructions, n));
+        let overrides: Vec<(Limit, u64)> = self.overrides.iter().copied().chain(max_instructions).collect();
+        Limits::load_with(Path::new(config::PROJECT_CONFIG), &overrides)
+    }
+}
+
+impl LayoutArgs {
+    /// The settings to assemble with, warning about flags that override the profile.
+    fn resolve(&self) -> Result<Resolved> {
+        let profile = match &self.profile {
+            Some(name) => Some(name.clone()),
+            None => config::read_key(Path::new(config::PROJECT_CONFIG), "profile")?,
+        };
+        let mut resolved = capabilities::resolve(profile.as_deref(), self.abi)?;
+        resolved.limits = self.limits.resolve()?;
+        resolved.synthesize_literals = self.synthesize_literals;
+        resolved.lenient = self.lenient;
+        resolved.strict_ports = self.strict_ports;
+        resolved.case_sensitive_labels = self.case_sensitive_labels;
+        resolved.defines = self.defines.iter().cloned().collect();
+        resolved.pad_to = self.pad_to;
+        resolved.tis100 = self.tis100;
+        if self.rows.is_some() || self.cols.is_some() {
+            let default = GridSize::default();
+            resolved.grid = Some(GridSize::new(self.rows.unwrap_or(default.rows), self.cols.unwrap_or(default.cols))?);
+        }
+        for warning in &resolved.warnings {
+            eprintln!("warning: {}", warning);
+        }
+        Ok(resolved)
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct IoArgs {
+    /// Node that reads the input stream with IN; anything but 0,0 needs `--abi 5`
+    #[arg(long, value_name = "ROW,COL", default_value = "0,0", value_parser = parse_any_node_coords)]
+    in_node: (usize, usize),
+    /// Node whose OUT writes are checked; anything but 1,1 needs `--abi 5`
+    #[arg(long, value_name = "ROW,COL", default_value = "1,1", value_parser = parse_any_node_coords)]
+    out_node: (usize, usize),
+    /// Input value that marks the end of the stream; needs `--abi 6`
+    #[arg(long, value_name = "VALUE")]
+    input_sentinel: Option<u32>,
+    /// What reading the sentinel from IN does
+    #[arg(long, value_enum, default_value = "pass-through", requires = "input_sentinel")]
+    sentinel_behavior: SentinelArg,
+}
+
+#[derive(clap::Args, Debug)]
+struct StyleArgs {
+    /// Color the listing: always, never, or on a terminal without NO_COLOR
+    #[arg(long, value_enum, default_value = "auto")]
+    style: StyleArg,
+    /// Wrap instructions at this many columns
+    #[arg(long, value_name = "COLUMNS")]
+    width: Option<usize>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum StyleArg {
+    Plain,
+    Color,
+    Auto,
+}
+
+impl StyleArg {
+    /// The style for output that goes to a terminal or not.
+    fn resolve(self, terminal: bool) -> listing::Style {
+        match self {
+            StyleArg::Plain => listing::Style::Plain,
+            StyleArg::Color => listing::Style::Color,
+            StyleArg::Auto => listing::Style::auto(terminal),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum SentinelArg {
+    PassThrough,
+    EndOfStream,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum PortsArg {
+    Light,
+    Heavy,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum RunnerArg {
+    Prove,
+    Execute,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ArtifactArg {
+    Program,
+    Metadata,
+    Symbols,
+    Timings,
+    StatsRecord,
+    StatsSummary,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum SchemaFormatArg {
+    JsonSchema,
+}
+
+impl IoArgs {
+    fn sentinel(&self) -> Option<InputSentinel> {
+        let behavior = match self.sentinel_behavior {
+            SentinelArg::PassThrough => SentinelBehavior::PassThrough,
+            SentinelArg::EndOfStream => SentinelBehavior::EndOfStream,
+        };
+        self.input_sentinel.map(|value| InputSentinel { value, behavior })
+    }
+
+    /// The IN and OUT nodes, which must lie in a grid of `size`.
+    fn nodes(&self, size: GridSize) -> Result<IoNodes> {
+        let io = IoNodes { in_node: self.in_node, out_node: self.out_node };
+        io.validate_in(size)?;
+        Ok(io)
+    }
+}
+
+/// Where `assemble` writes args.json (`-` for stdout) and its optional sidecars
+/// and hints.
+struct OutputPaths {
+    args: PathBuf,
+    metadata: Option<PathBuf>,
+    symbols: Option<PathBuf>,
+    hints: Option<PathBuf>,
+    listing: Option<PathBuf>,
+    /// Write nothing, and fail, if there are warnings.
+    deny_warnings: bool,
+}
+
+impl InputArgs {
+    /// The input values, and the generator they came from if any. Without
+    /// either flag the source's `## IN:` lines give them.
+    fn resolve(&self, streams: &Streams, out: &Output) -> Result<(Vec<u32>, Option<InputSpec>)> {
+        let flag = match (&self.inputs, &self.inputs_gen) {
+            (_, Some(_)) => "--inputs-gen",
+            (Some(_), None) => "--inputs",
+            (None, None) => return Ok((streams.inputs.clone().unwrap_or_default(), None)),
+        };
+        if let Some(line) = streams.inputs_line {
+            out.note(format!("notice: {} overrides the ## IN: lines of the source (from line {})", flag, line));
+        }
+        match &self.inputs_gen {
+            Some(spec) => Ok((spec.expand(Path::new("."))?, Some(spec.clone()))),
+            None => {
+                let values = self.inputs.as_deref().unwrap_or_default();
+                Ok((inputs::parse_values(values).map_err(|e| anyhow!("--inputs: {}", e))?, None))
+            }
+        }
+    }
+}
+
+impl ExpectedArgs {
+    /// The `--expected` arguments, or without any the source's `## OUT:` lines.
+    fn specs<'a>(&'a self, streams: &'a Streams, out: &Output) -> &'a [String] {
+        match (self.expected.is_empty(), streams.expected_line) {
+            (true, _) => &streams.expected,
+            (false, Some(line)) => {
+                out.note(format!("notice: --expected overrides the ## OUT: lines of the source (from line {})", line));
+                &self.expected
+            }
+            (false, None) => &self.expected,
+        }
+    }
+}
+
+impl GuestArgs {
+    fn resolve(&self) -> Result<PathBuf> {
+        match (&self.guest, &self.scarb_package) {
+            (Some(path), _) => {
+                if !path.is_file() {
+                    return Err(anyhow!("guest artifact {} does not exist", path.display()));
+                }
+                Ok(path.clone())
+            }
+            (None, Some(package)) => {
+                scarb::locate_guest(&self.scarb_dir, package, &self.scarb_profile, self.build_guest)
+            }
+            (None, None) => Err(anyhow!("specify the guest with --guest <path> or --scarb-package <name>")),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ArgsSection {
+    Inputs,
+    Expected,
+    ProgWords,
+}
+
+impl From<ArgsSection> for Section {
+    fn from(section: ArgsSection) -> Self {
+        match section {
+            ArgsSection::Inputs => Section::Inputs,
+            ArgsSection::Expected => Section::Expected,
+            ArgsSection::ProgWords => Section::ProgWords,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum ConvertFormat {
+    Json,
+    Asm,
+    /// A list of instruction words (see --word-format)
+    Words,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum WordFormatArg {
+    /// One word per line, `#` comments
+    Text,
+    /// A `const PROG: [u32; N]` array
+    Array,
+    /// `node,pc,word` rows
+    Csv,
+}
+
+impl WordFormatArg {
+    fn resolve(arg: Option<Self>, path: &Path) -> WordFormat {
+        match arg {
+            Some(WordFormatArg::Text) => WordFormat::Text,
+            Some(WordFormatArg::Array) => WordFormat::Array,
+            Some(WordFormatArg::Csv) => WordFormat::Csv,
+            None => WordFormat::from_path(path),
+        }
+    }
+}
+
+/// Where a command's output goes. Notices (progress, files written) always go
+/// to stderr. Results go to stdout as text, or with `--porcelain` as
+/// `key<TAB>field..` records while the text moves to stderr.
+#[derive(Debug, Clone, Copy)]
+struct Output {
+    porcelain: bool,
+}
+
+impl Output {
+    fn note(&self, text: impl fmt::Display) {
+        eprintln!("{}", text);
+    }
+
+    /// The human-readable result.
+    fn text(&self, text: impl fmt::Display) {
+        if self.porcelain {
+            eprintln!("{}", text);
+        } else {
+            println!("{}", text);
+        }
+    }
+
+    /// One porcelain record. Tabs and newlines in fields print as spaces.
+    fn record(&self, key: &str, fields: &[&dyn fmt::Display]) {
+        if self.porcelain {
+            let fields: Vec<String> = fields.iter().map(|f| f.to_string().replace(['\t', '\n', '\r'], " ")).collect();
+            println!("{}\t{}", key, fields.join("\t"));
+        }
+    }
+
+    /// A command with `--json` prints that JSON under `--porcelain` too.
+    fn json(&self, json: bool) -> bool {
+        json || self.porcelain
+    }
+}
+
+/// Contents of `path`, or of stdin when it is `-`, decoded as source (see
+/// `source::decode`), with the file and line each line of the
+/// `INCLUDE`-expanded text came from, to report errors by. Source on stdin
+/// cannot `INCLUDE`.
+fn read_input(path: &Path) -> Result<(String, Origins)> {
+    if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        return Ok((source::decode_standalone(&bytes, "<stdin>")?, Origins::default()));
+    }
+    source::read_with_origins(path)
+}
+
+/// The assembly in `path` (`read_input`), parsed with the project dialect,
+/// its errors naming the file an included line came from.
+fn parse_program(path: &Path) -> Result<assembler::ParseResult> {
+    let (text, origins) = read_input(path)?;
+    assembler::parse_with(&text, &project_dialect()?).map_err(|e| origins.relocate(e))
+}
+
+/// `read_input` as a stream, for files too large to read whole.
+fn open_input(path: &Path) -> Result<Box<dyn BufRead>> {
+    if path == Path::new("-") {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    let file = fs::File::open(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    exec::note_read(path);
+    Ok(Box::new(std::io::BufReader::new(file)))
+}
+
+/// `fs::read_to_string`, logged for `--record-session`.
+fn read_file(path: &Path) -> Result<String> {
+    let text = fs::read_to_string(path)?;
+    exec::note_read(path);
+    Ok(text)
+}
+
+/// Writes `-` to stdout and everything else through `FsSink`.
+struct CliSink {
+    fs: FsSink,
+}
+
+impl ArtifactSink for CliSink {
+    fn write_artifact(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+        if name == "-" {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(bytes)?;
+            stdout.write_all(b"\n")?;
+            return Ok(stdout.flush()?);
+        }
+        self.fs.write_artifact(name, bytes)
+    }
+}
+
+fn main() {
+    exec::install_interrupt_handler();
+    if let Err(e) = run(Cli::parse()) {
+        if e.is::<exec::Interrupted>() {
+            eprintln!("interrupted");
+            std::process::exit(exec::INTERRUPTED_EXIT_CODE);
+        }
+        // One line with the whole context chain, never a backtrace, so the
+        // message is the same whatever RUST_BACKTRACE says.
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let show_progress = Progress::enabled(cli.quiet);
+    exec::set_verify_writes(!cli.no_verify_write);
+    
+    let timings = if cli.timings || cli.timings_json.is_some() {
+        let aggregator = TimingAggregator::default();
+        trace::set_global_aggregator(&aggregator);
+        Some(aggregator)
+    } else {
+        None
+    };
     
-    match cli.command {
-        Commands::Assemble { input, output, inputs, expected } => {
-            assemble_program(input, output, inputs, expected)?;
+    let project = Path::new(".");
+    let stats_name = match cli.command.stats_name() {
+        Some(name) if cli.stats_log || stats::enabled_in(project)? => Some(name),
+        _ => None,
+    };
+    let started = SystemTime::now();
+    let mut facts = Facts::default();
+    let out = Output { porcelain: cli.porcelain };
+    let session_dir = match (&cli.command, cli.record_session) {
+        (Commands::Replay { .. }, _) => None,
+        (_, Some(dir)) => Some(dir),
+        (_, None) => config::read_key(Path::new(config::PROJECT_CONFIG), session::CONFIG_KEY)?.map(PathBuf::from),
+    };
+    let resolved = format!("{:#?}", cli.command);
+    if session_dir.is_some() {
+        exec::start_file_log();
+        if Path::new(config::PROJECT_CONFIG).is_file() {
+            exec::note_read(Path::new(config::PROJECT_CONFIG));
+        }
+    }
+    let result = execute(cli.command, show_progress, out, &mut facts);
+    if let Some(dir) = session_dir {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let error = result.as_ref().err().map(|e| format!("{:#}", e));
+        session::record(&dir, &std::env::current_dir()?, &args, resolved, &exec::take_file_log(), error)?;
+        out.note(format!("Recorded session to {}", dir.display()));
+        out.record("session", &[&dir.display()]);
+    }
+    if let Some(name) = stats_name {
+        let started_unix = started.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let duration_ms = started.elapsed().map_or(0, |d| d.as_millis() as u64);
+        let record = StatsRecord::new(name, started_unix, duration_ms, result.is_ok(), facts);
+        if let Err(e) = stats::append(project, &record) {
+            eprintln!("warning: {:#}", e);
         }
     }
+    result?;
+    
+    if let Some(aggregator) = timings {
+        report_timings(&aggregator, cli.timings, cli.timings_json.as_deref())?;
+    }
     
     Ok(())
 }
 
+/// Run one command, noting what `--stats-log` records about it in `facts`.
+fn execute(command: Commands, show_progress: bool, out: Output, facts: &mut Facts) -> Result<()> {
+    match command {
+        Commands::Assemble { input, output, inputs, expected, layout, io, metadata, symbols, hints, listing, deny_warnings } => {
+            let paths = OutputPaths { args: output, metadata, symbols, hints, listing, deny_warnings };
+            let assembled = assemble_program(input, inputs, &expected, &layout.resolve()?, &io, paths, out)?;
+            record_assembled(facts, &assembled)?;
+        }
+        Commands::Attest { program, against } => {
+            let source = source::read(&program)?;
+            let metadata: Metadata = serde_json::from_str(&read_file(&against)?)
+                .map_err(|e| anyhow!("{}: {}", against.display(), e))?;
+            let attestation = attest::attest(&source, &metadata);
+            out.text(&attestation);
+            for reason in &attestation.unsupported {
+                out.record("unsupported", &[reason]);
+            }
+            for check in &attestation.checks {
+                out.record("check", &[&check.name, &if check.is_ok() { "ok" } else { "mismatch" }]);
+            }
+            out.record("result", &[&if attestation.is_ok() { "passed" } else { "failed" }]);
+            if !attestation.is_ok() {
+                return Err(anyhow!("{} does not reproduce {}", program.display(), against.display()));
+            }
+        }
+        Commands::Seal { program, output, passphrase } => {
+            let sealed = seal::seal(&source::read(&program)?, passphrase.as_deref())?;
+            exec::write_atomic(&output, serde_json::to_string_pretty(&sealed)? + "\n")?;
+            out.note(format!("sealed {} (root {}) to {}", program.display(), sealed.root, output.display()));
+            out.record("root", &[&sealed.root]);
+            out.record("sealed", &[&output.display()]);
+        }
+        Commands::Unseal { sealed: path, passphrase, source, output } => {
+            let sealed: seal::Sealed = serde_json::from_str(&read_file(&path)?)
+                .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+            match (passphrase, source) {
+                (Some(passphrase), _) => {
+                    let source = seal::unseal(&sealed, &passphrase)?;
+                    match output {
+                        Some(out) => exec::write_atomic(&out, source)?,
+                        None => print!("{}", source),
+                    }
+                }
+                (None, Some(source)) => seal::verify_source(&sealed, &source::read(&source)?)?,
+                (None, None) => unreachable!("clap requires --passphrase or --source"),
+            }
+            out.note(format!("source matches sealed root {}", sealed.root));
+        }
+        Commands::Convert { input, from, to, word_format, output } => {
+            convert_program(input, from, to, word_format, output)?;
+        }
+        Commands::Replay { dir } => {
+            let replay = session::replay(&dir, &std::env::current_exe()?)?;
+            for (path, outcome) in &replay.outputs {
+                out.note(format!("{:8} {}", outcome.name(), path));
+                out.record("output", &[path, &outcome.name()]);
+            }
+            if replay.error != replay.recorded_error {
+                let describe = |error: &Option<String>| error.clone().unwrap_or_else(|| "success".to_string());
+                out.note(format!("recorded: {}", describe(&replay.recorded_error)));
+                out.note(format!("replayed: {}", describe(&replay.error)));
+            }
+            out.record("result", &[&if replay.matches() { "match" } else { "mismatch" }]);
+            if !replay.matches() {
+                let differ = replay.outputs.iter().filter(|(_, o)| *o != session::Outcome::Same).count();
+                return Err(anyhow!("replay of {} does not match: {} of {} outputs differ", dir.display(), differ, replay.outputs.len()));
+            }
+            out.note(format!("Replay of {} matches", dir.display()));
+        }
+        Commands::Disassemble { program, style, output } => {
+            let (programs, labels) = read_programs_with_labels(&program)?;
+            let terminal = output.is_none() && std::io::stdout().is_terminal();
+            let options = listing::Options { style: style.style.resolve(terminal), width: style.width };
+            let rendered = listing::render(&programs, &labels, options);
+            match output {
+                Some(path) => {
+                    exec::write_atomic(&path, rendered)?;
+                    out.note(format!("Wrote {}", path.display()));
+                    out.record("wrote", &[&path.display()]);
+                }
+                None => print!("{}", rendered),
+            }
+        }
+        Commands::Prove { input, inputs, expected, layout, io, args, proof, hints, guest } => {
+            let guest_path = guest.resolve()?;
+            let layout = layout.resolve()?;
+            let paths = OutputPaths { args: args.clone(), metadata: None, symbols: None, hints: hints.clone(), listing: None, deny_warnings: false };
+            let assembled = assemble_program(input, inputs, &expected, &layout, &io, paths, out)?;
+            record_assembled(facts, &assembled)?;
+            if let Some(public) = prove(&guest_path, &args, hints.as_deref(), &proof, show_progress, out)? {
+                record_proof(facts, &public);
+            }
+        }
+        Commands::VerifyProof { proof, json } => {
+            let public = verify_proof(&proof, show_progress)?;
+            record_proof(facts, &public);
+            out.note(format!("Proof verified: {}", proof.display()));
+            if out.json(json) {
+                println!("{}", serde_json::to_string_pretty(&public)?);
+            } else {
+                out.text(&public);
+            }
+        }
+        Commands::CheckRoot { proof, program } => {
+            let public = read_proof(&proof)?;
+            let parsed = parse_program(&program)?;
+            let root = merkle::program_root_hex(&parsed.grid.programs)?;
+            if !public.commits_to(&root)? {
+                return Err(anyhow!(
+                    "{} commits to program root {}, but {} hashes to {}",
+                    proof.display(),
+                    public.program_root,
+                    program.display(),
+                    root
+                ));
+            }
+            out.text(format!("{} commits to {} (root {})", proof.display(), program.display(), public.program_root));
+            out.record("root", &[&public.program_root]);
+        }
+        Commands::Id { program } => {
+            let id = ProgramId::of_programs(&read_programs(&program)?)?;
+            out.record("id", &[&id]);
+            out.text(id);
+        }
+        Commands::BisectEncoding { old_args, program, abi, json } => {
+            let old = args_stream::inspect_args(open_input(&old_args)?, abi, Some(Section::ProgWords))?.only.unwrap_or_default();
+            let parsed = parse_program(&program)?;
+            let new = assembler::encode_programs(&parsed.grid.programs)?;
+            let diff = bisect::diff_prog_words(&old, &new).map_err(|e| anyhow!("{}: {}", old_args.display(), e))?;
+            if out.json(json) {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else {
+                println!("{}", diff);
+            }
+        }
+        Commands::InspectArgs { args, abi, only, json, style } => {
+            let inspection = args_stream::inspect_args(open_input(&args)?, abi, only.map(Section::from))?;
+            if let (Some(values), false) = (&inspection.only, json) {
+                let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+                for value in values {
+                    writeln!(stdout, "{}", value)?;
+                }
+                stdout.flush()?;
+            } else if out.json(json) {
+                println!("{}", serde_json::to_string_pretty(&inspection)?);
+            } else {
+                print_inspection(&inspection, style.resolve(std::io::stdout().is_terminal()));
+            }
+        }
+        Commands::Bundle(cmd) => {
+            run_bundle(cmd, show_progress, out)?;
+        }
+        Commands::Batch { action } => {
+            run_batch(action, out)?;
+        }
+        Commands::Queue { action: QueueAction::Run { dir, concurrency, job_timeout, max_attempts, guest } } => {
+            let config = queue::QueueConfig {
+                guest: guest.resolve()?,
+                concurrency: concurrency.into(),
+                job_timeout,
+                max_attempts,
+            };
+            let summary = queue::run(&dir, &config, &|result| {
+                out.text(result);
+                let status = serde_json::to_value(result.status).unwrap_or_default();
+                out.record("job", &[&result.job, &status.as_str().unwrap_or_default()]);
+            })?;
+            out.text(&summary);
+            if summary.failed() > 0 {
+                return Err(anyhow!("{} of {} jobs did not succeed", summary.failed(), summary.results.len()));
+            }
+        }
+        Commands::Example { action } => {
+            run_example(action, out)?;
+        }
+        Commands::GenProgram { nodes, instructions, loops, ports, input_count, seed, output } => {
+            let config = program_gen::GenConfig {
+                nodes: nodes as usize,
+                instructions: instructions as usize,
+                loops: loops as usize,
+                ports: match ports {
+                    PortsArg::Light => program_gen::PortDensity::Light,
+                    PortsArg::Heavy => program_gen::PortDensity::Heavy,
+                },
+                inputs: input_count as usize,
+                seed,
+            };
+            let generated = program_gen::generate(&config)?;
+            match output {
+                Some(path) => {
+                    let cases = path.with_extension("cases.json");
+                    exec::write_atomic(&path, &generated.source)?;
+                    exec::write_atomic(&cases, generated.cases_json()?)?;
+                    for path in [&path, &cases] {
+                        out.note(format!("Wrote {}", path.display()));
+                        out.record("wrote", &[&path.display()]);
+                    }
+                }
+                None => print!("{}", generated.source),
+            }
+            let join = |values: &[u32]| values.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+            out.note(format!("  Inputs:   {}", join(&generated.inputs)));
+            out.note(format!("  Expected: {}", join(&generated.expected)));
+            out.note(format!("  Cycles:   {}", generated.cycles));
+            out.record("case", &[&join(&generated.inputs), &join(&generated.expected), &generated.cycles]);
+        }
+        Commands::ImportCorpus { dir, spec, out_dir } => {
+            let spec = spec.unwrap_or_else(|| dir.join(corpus::SPEC_FILE));
+            let summary = corpus::import_dir(&dir, &spec, &out_dir)?;
+            out.text(&summary);
+            for file in &summary.files {
+                let outcome = match file.outcome {
+                    corpus::Outcome::Imported { .. } => "imported",
+                    corpus::Outcome::Skipped { .. } => "skipped",
+                    corpus::Outcome::Failed { .. } => "failed",
+                };
+                out.record("save", &[&file.path.display(), &outcome]);
+            }
+        }
+        Commands::MigrateRoots { input, from, to, sources_dir, output, jobs } => {
+            let rows = migrate::read_rows(&input)?;
+            let migrated = migrate::migrate(&rows, &sources_dir, &project_dialect()?, from, to, usize::from(jobs))?;
+            for m in &migrated {
+                if m.status != migrate::Status::Ok {
+                    out.note(format!("{:>12} {} ({}): {}", "Failed", m.row.program_id, m.status, m.detail));
+                }
+                out.record("migrated", &[&m.row.program_id, &m.status]);
+            }
+            let csv = migrate::to_csv(&migrated);
+            match output {
+                Some(path) => exec::write_atomic(&path, csv)?,
+                None => out.text(csv.trim_end()),
+            }
+            let failed = migrated.iter().filter(|m| m.status != migrate::Status::Ok).count();
+            out.note(format!("{:>12} {} roots, {} ok, {} failed", "Finished", migrated.len(), migrated.len() - failed, failed));
+            if failed > 0 {
+                return Err(anyhow!("{} of {} roots did not migrate", failed, migrated.len()));
+            }
+        }
+        Commands::Build { dir, force, dry_run } => {
+            let started = Instant::now();
+            let workspace = build::Workspace::discover(&dir)?;
+            let plan = workspace.plan(force)?;
+            let id = |step: &build::Step| workspace.graph.nodes[step.node].id.clone();
+            if dry_run {
+                for step in &plan {
+                    match step.dirty {
+                        Some(dirty) => {
+                            out.text(format!("{:>12} {} ({})", "Dirty", id(step), dirty));
+                            out.record("dirty", &[&id(step), &dirty]);
+                        }
+                        None => {
+                            out.text(format!("{:>12} {}", "Fresh", id(step)));
+                            out.record("fresh", &[&id(step)]);
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            let summary = workspace.build(&plan, &mut |step, outcome| match outcome {
+                build::Outcome::Built => {
+                    out.note(format!("{:>12} {}", "Compiling", id(step)));
+                    out.record("compiled", &[&id(step)]);
+                }
+                build::Outcome::Fresh => {
+                    out.note(format!("{:>12} {}", "Fresh", id(step)));
+                    out.record("fresh", &[&id(step)]);
+                }
+                build::Outcome::Failed(reason) => {
+                    out.note(format!("{:>12} {}: {}", "Failed", id(step), reason));
+                    out.record("failed", &[&id(step), reason]);
+                }
+                build::Outcome::Skipped => {
+                    out.note(format!("{:>12} {} (a dependency failed)", "Skipped", id(step)));
+                    out.record("skipped", &[&id(step)]);
+                }
+            })?;
+            out.note(format!("{:>12} {} in {:.2}s", "Finished", summary, started.elapsed().as_secs_f64()));
+            if summary.failed > 0 {
+                return Err(anyhow!("could not build {} of {} targets", summary.failed + summary.skipped, plan.len()));
+            }
+        }
+        Commands::Minimize { input, manifest, budget, random_inputs, seed, output } => {
+            let parsed = parse_program(&input)?;
+            let base = manifest.parent().unwrap_or(Path::new(""));
+            let config = minimize::MinimizeConfig { random_inputs, seed, budget };
+            let minimized = minimize::minimize(&parsed, &report::PuzzleManifest::load(&manifest)?, base, &config)?;
+            match output {
+                Some(path) => {
+                    exec::write_atomic(&path, &minimized.source)?;
+                    out.note(format!("Wrote {}", path.display()));
+                    out.record("wrote", &[&path.display()]);
+                }
+                None => print!("{}", minimized.source),
+            }
+            for line in minimized.to_string().lines() {
+                out.note(format!("  {}", line));
+            }
+            out.record("instructions", &[&minimized.before.instructions, &minimized.after.instructions]);
+            out.record("cycles", &[&minimized.before.cycles, &minimized.after.cycles]);
+        }
+        Commands::Report { manifest, output, json, ignore_asserts } => {
+            let report = report::build(&manifest, ignore_asserts)?;
+            exec::write_atomic(&output, report::render_html(&report))?;
+            out.note(format!("Wrote {} ({} of {} cases passed)", output.display(), report.passed, report.cases.len()));
+            out.record("html", &[&output.display()]);
+            out.record("passed", &[&report.passed, &report.cases.len()]);
+            if json {
+                let path = output.with_extension("json");
+                exec::write_atomic(&path, serde_json::to_string_pretty(&report)? + "\n")?;
+                out.note(format!("Wrote {}", path.display()));
+                out.record("json", &[&path.display()]);
+            }
+        }
+        Commands::Schema { artifact, format: SchemaFormatArg::JsonSchema, output } => {
+            let artifact = match artifact {
+                ArtifactArg::Program => schema::Artifact::Program,
+                ArtifactArg::Metadata => schema::Artifact::Metadata,
+                ArtifactArg::Symbols => schema::Artifact::Symbols,
+                ArtifactArg::Timings => schema::Artifact::Timings,
+                ArtifactArg::StatsRecord => schema::Artifact::StatsRecord,
+                ArtifactArg::StatsSummary => schema::Artifact::StatsSummary,
+            };
+            let document = serde_json::to_string_pretty(&artifact.schema())? + "\n";
+            match output {
+                Some(path) => {
+                    exec::write_atomic(&path, document)?;
+                    out.note(format!("Wrote {}", path.display()));
+                    out.record("wrote", &[&path.display()]);
+                }
+                None => print!("{}", document),
+            }
+        }
+        Commands::Capabilities { json, limits } => {
+            let mut caps = capabilities::capabilities();
+            caps.aliases = project_dialect()?.table();
+            caps.limits = limits.resolve()?;
+            if out.json(json) {
+                println!("{}", serde_json::to_string_pretty(&caps)?);
+            } else {
+                print_capabilities(&caps);
+            }
+        }
+        Commands::Doctor { guest } => {
+            doctor(&guest, out)?;
+        }
+        Commands::Conformance { guest, runner, corpus, random, seed, only, work_dir } => {
+            let artifact = match guest.resolve() {
+                Ok(artifact) => artifact,
+                // A guest that was named but is not built is skipped like a missing tool.
+                Err(e) if guest.guest.is_some() || guest.scarb_package.is_some() => {
+                    return skip_conformance(&e.to_string(), out);
+                }
+                Err(e) => return Err(e),
+            };
+            let runner = match runner {
+                RunnerArg::Prove => conformance::Runner::Prove,
+                RunnerArg::Execute => conformance::Runner::Execute,
+            };
+            let guest = conformance::Guest { artifact, runner };
+            if let Some(reason) = guest.unavailable() {
+                return skip_conformance(&reason, out);
+            }
+            let mut cases = conformance::corpus()?;
+            for dir in &corpus {
+                cases.extend(conformance::load_dir(dir)?);
+            }
+            cases.extend(conformance::random(seed, random)?);
+            if let Some(only) = &only {
+                cases.retain(|case| case.name.contains(only.as_str()));
+            }
+            let summary = conformance::run(&cases, &guest, &work_dir, |case, differences| {
+                if differences.is_empty() {
+                    out.note(format!("{:>12} {}", "Agree", case.name));
+                    out.record("agree", &[&case.name]);
+                } else {
+                    out.note(format!("{:>12} {}", "Diverge", case.name));
+                    for difference in differences {
+                        out.record("diverge", &[&case.name, difference]);
+                    }
+                }
+            })?;
+            for divergence in &summary.divergences {
+                out.text(divergence.to_string().trim_end());
+            }
+            out.note(format!(
+                "{:>12} {} cases, {} agree, {} diverge",
+                "Finished",
+                cases.len(),
+                summary.agreed,
+                summary.divergences.len()
+            ));
+            if !summary.divergences.is_empty() {
+                return Err(anyhow!("{} of {} cases diverge from the guest", summary.divergences.len(), cases.len()));
+            }
+        }
+        Commands::GenCairoConsts { output } => {
+            let consts = cairo_consts::generate();
+            match output {
+                Some(path) => exec::write_atomic(&path, consts)?,
+                None => print!("{}", consts),
+            }
+        }
+        Commands::CheckGuest { guest_src, verbose } => {
+            let report = guest_check::check(&guest_src)?;
+            for finding in &report.findings {
+                if verbose {
+                    out.text(finding);
+                }
+                let (name, status) = match finding {
+                    guest_check::Finding::Match { name, .. } => (name, "ok"),
+                    guest_check::Finding::Mismatch { name, .. } => (name, "mismatch"),
+                    guest_check::Finding::Missing { name, .. } => (name, "missing"),
+                    guest_check::Finding::Fallthrough { name, .. } => (name, "warning"),
+                };
+                out.record("constant", &[name, &status]);
+            }
+            out.text(&report);
+            if !report.is_ok() {
+                return Err(anyhow!("guest constants differ from the host tables"));
+            }
+        }
+        Commands::Serve { listen, max_concurrency, timeout_ms } => {
+            let listener = TcpListener::bind(&listen)
+                .map_err(|e| anyhow!("cannot listen on {}: {}", listen, e))?;
+            out.note(format!("zk100 daemon listening on {}", listener.local_addr()?));
+            let config = daemon::ServerConfig {
+                max_concurrency,
+                timeout: Duration::from_millis(timeout_ms),
+            };
+            daemon::Server::new(config).serve(listener)?;
+        }
+        Commands::Lsp => {
+            lsp::serve_stdio()?;
+        }
+        #[cfg(feature = "tui")]
+        Commands::Tui { input, inputs } => {
+            let (source, origins) = source::read_with_origins(&input)?;
+            let parsed = assembler::parse_with(&source, &project_dialect()?).map_err(|e| origins.relocate(e))?;
+            let (inputs, _) = inputs.resolve(&source_streams::scan(&source).map_err(|e| origins.relocate(e))?, &out)?;
+            let last = LastBehavior::load(Path::new(config::PROJECT_CONFIG))?;
+            zk100_host::tui::run(&source, &parsed, &inputs, IoNodes::default(), last)?;
+        }
+        Commands::Client { method, params, connect } => {
+            let params: serde_json::Value = serde_json::from_str(&read_file(&params)?)?;
+            let result = daemon::call(connect.as_str(), &method, params)?;
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+        Commands::StatsLog { action: StatsLogAction::Summarize { dir, json } } => {
+            let summary = stats::summarize(&stats::read(&stats::log_path(&dir))?);
+            if out.json(json) {
+                println!("{}", serde_json::to_string_pretty(&summary)?);
+            } else {
+                out.text(&summary);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn record_assembled(facts: &mut Facts, assembled: &Assembled) -> Result<()> {
+    let programs = &assembled.parsed.grid.programs;
+    facts.instructions = Some(programs.iter().flatten().map(Vec::len).sum());
+    facts.program_root = Some(merkle::program_root_hex(programs)?);
+    Ok(())
+}
+
+fn record_proof(facts: &mut Facts, public: &proof_reader::ProofPublicData) {
+    facts.program_root = Some(public.program_root.clone());
+    facts.cases_passed = Some(u32::from(public.solved));
+    facts.cases_failed = Some(u32::from(!public.solved));
+}
+
+fn report_timings(
+    aggregator: &TimingAggregator,
+    print_table: bool,
+    json_path: Option<&std::path::Path>,
+) -> Result<()> {
+    if !cfg!(feature = "tracing") {
+        eprintln!("warning: built without the `tracing` feature; no timings were recorded");
+    }
+    if print_table {
+        eprint!("{}", aggregator.render_table());
+    }
+    if let Some(path) = json_path {
+        let summary = trace::Timings { phases: aggregator.summary() };
+        exec::write_atomic(path, serde_json::to_string_pretty(&summary)?)?;
+    }
+    Ok(())
+}
+
 fn assemble_program(
     input_path: PathBuf,
-    output_path: PathBuf,
-    inputs_str: Option<String>,
-    expected_str: Option<String>,
-) -> Result<()> {
-    println!("Assembling program from: {}", input_path.display());
+    input_args: InputArgs,
+    expected_args: &ExpectedArgs,
+    layout: &Resolved,
+    io_args: &IoArgs,
+    paths: OutputPaths,
+    out: Output,
+) -> Result<Assembled> {
+    out.note(format!("Assembling program from: {}", input_path.display()));
+    let abi = layout.settings.abi_version;
     
     // Read assembly file
-    let assembly_code = fs::read_to_string(&input_path)?;
+    let (assembly_code, origins) = read_input(&input_path)?;
+    let located = |e: anyhow::Error| origins.relocate(e);
     
-    // Parse assembly into programs for 2x2 grid
-    let programs = assembler::parse_assembly(&assembly_code)?;
+    // Parse inputs and expected values
+    let streams = source_streams::scan(&assembly_code).map_err(located)?;
+    let (inputs, inputs_gen) = input_args.resolve(&streams, &out)?;
+    let (spec, mut node_expected) = parse_expected(expected_args.specs(&streams, &out), abi)?;
+    let size = assembler::resolve_grid(&assembly_code, layout.grid).map_err(located)?;
+    let (io, sentinel) = (io_args.nodes(size)?, io_args.sentinel());
+    let expected = if expected_args.expected_from_simulation {
+        let parse_options =
+            ParseOptions {
+                limits: layout.limits,
+                synthesize_literals: layout.synthesize_literals,
+                grid: layout.grid,
+                lenient: layout.lenient,
+                case_sensitive_labels: layout.case_sensitive_labels,
+                defines: layout.defines.clone(),
+                pad_to: layout.pad_to,
+                tis100: layout.tis100.then_some(io),
+                ..Default::default()
+            };
+        let parsed = assembler::parse_with_options(&assembly_code, &project_dialect()?, parse_options).map_err(located)?;
+        let run = Simulator::with_io(&parsed.grid.programs, &inputs, io)?
+            .with_limits(layout.limits)?
+            .with_sentinel(sentinel)
+            .with_asserts(parsed.asserts)
+            .with_last(LastBehavior::load(Path::new(config::PROJECT_CONFIG))?)
+            .run_to_result(layout.limits.cycles.value)?;
+        let failures = spec.check(&run.outputs);
+        if !spec.entries.is_empty() && !failures.is_empty() {
+            let failures: Vec<String> = failures.iter().map(|f| format!("  {}", f)).collect();
+            return Err(anyhow!(
+                "the simulated outputs {:?} do not match --expected {}:\n{}",
+                run.outputs,
+                spec,
+                failures.join("\n")
+            ));
+        }
+        for (&(r, c), values) in &node_expected {
+            let simulated = run.node_outputs.get(&(r, c)).map_or(&[][..], Vec::as_slice);
+            if simulated != values.as_slice() {
+                return Err(anyhow!(
+                    "node ({},{}) simulated outputs {:?} do not match --expected {:?}",
+                    r, c, simulated, values
+                ));
+            }
+        }
+        out.note(format!("  Expected from simulation ({} cycles, {:?})", run.cycles, run.termination));
+        if abi == cairo_abi::NODE_ABI {
+            node_expected = run.node_outputs;
+            Vec::new()
+        } else {
+            run.outputs
+        }
+    } else {
+        spec.require_exact()?
+    };
+    let profile = layout.profile.map(str::to_string);
+    let aliases = project_aliases()?;
+    let options = AssembleOptions {
+        inputs,
+        expected,
+        node_expected,
+        inputs_gen,
+        io,
+        sentinel,
+        profile,
+        aliases,
+        limits: layout.limits,
+        synthesize_literals: layout.synthesize_literals,
+        grid: layout.grid,
+        lenient: layout.lenient,
+        case_sensitive_labels: layout.case_sensitive_labels,
+        defines: layout.defines.clone(),
+        pad_to: layout.pad_to,
+        tis100: layout.tis100,
+        ..Default::default()
+    };
     
-    // Encode programs to prog_words
-    let prog_words = assembler::encode_programs(&programs)?;
+    // Every artifact is complete before the first one is written
+    let name = |path: &Path| path.to_string_lossy().into_owned();
+    let emit = Emit {
+        args: name(&paths.args),
+        metadata: paths.metadata.as_deref().map(name),
+        symbols: paths.symbols.as_deref().map(name),
+        hints: paths.hints.as_deref().map(name),
+        listing: paths.listing.as_deref().map(name),
+    };
+    let source_file = input_path.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    let sentinel_warning = sentinel.and_then(|s| Some((s, s.early_position(&options.inputs)?))).map(|(sentinel, i)| {
+        format!(
+            "inputs[{}] is the sentinel {} but {} more inputs follow it",
+            i,
+            sentinel.value,
+            options.inputs.len() - i - 1
+        )
+    });
+    if layout.strict_ports {
+        let parsed = assembler::parse_with_options(&assembly_code, &options.dialect()?, options.parse_options()).map_err(located)?;
+        let ports = parsed.port_warnings();
+        if !ports.is_empty() {
+            for diagnostic in &ports {
+                eprintln!("error: {}", described(&input_path, &origins, diagnostic));
+            }
+            return Err(anyhow!("{} port(s) point off the grid and --strict-ports is set; nothing was written", ports.len()));
+        }
+    }
+    if paths.deny_warnings {
+        let parsed = assembler::parse_with_options(&assembly_code, &options.dialect()?, options.parse_options()).map_err(located)?;
+        let warnings = assembly_warnings(&parsed, &io);
+        let count = warnings.len() + usize::from(sentinel_warning.is_some());
+        if count > 0 {
+            report_diagnostics(&input_path, &origins, &warnings);
+            if let Some(warning) = &sentinel_warning {
+                eprintln!("warning: {}", warning);
+            }
+            return Err(anyhow!("{} warning(s) and --deny-warnings is set; nothing was written", count));
+        }
+    }
+    let mut sink = CliSink { fs: FsSink::default() };
+    let assembled = artifacts::assemble(&assembly_code, &source_file, abi, &options, &emit, &mut sink).map_err(located)?;
+    let parsed = &assembled.parsed;
+    report_diagnostics(&input_path, &origins, &assembly_warnings(parsed, &io));
+    if let Some(warning) = &sentinel_warning {
+        eprintln!("warning: {}", warning);
+    }
     
-    println!("Encoded prog_words:");
-    for (i, word) in prog_words.iter().enumerate() {
-        println!("  [{}] = {}", i, word);
+    out.note("Encoded prog_words:");
+    for (i, word) in assembled.prog_words.iter().enumerate() {
+        out.note(format!("  [{}] = {}", i, word));
     }
     
-    // Parse inputs and expected values
-    let inputs = parse_u32_array(&inputs_str.unwrap_or_default());
-    let expected = parse_u32_array(&expected_str.unwrap_or_default());
+    out.note(format!("Generated args file: {}", emit.args));
+    if let Some(profile) = layout.profile {
+        out.note(format!("  Profile: {} (ABI {})", profile, abi));
+    }
+    if let Some(path) = &emit.metadata {
+        out.note(format!("Generated metadata file: {}", path));
+    }
+    if let Some(path) = &emit.symbols {
+        out.note(format!("Generated symbols file: {}", path));
+    }
+    if let Some(path) = &emit.hints {
+        out.note(format!("Generated hints file: {}", path));
+    }
+    if let Some(path) = &emit.listing {
+        out.note(format!("Generated listing file: {}", path));
+    }
+    match &options.inputs_gen {
+        Some(spec) => out.note(format!("  Inputs: {:?} (from {})", options.inputs, spec)),
+        None => out.note(format!("  Inputs: {:?}", options.inputs)),
+    }
+    if let Some(sentinel) = sentinel {
+        out.note(format!("  Sentinel: {}", sentinel));
+    }
+    if abi != cairo_abi::NODE_ABI {
+        out.note(format!("  Expected: {:?}", options.expected));
+    } else {
+        for ((r, c), values) in &options.node_expected {
+            out.note(format!("  Expected from ({},{}): {:?}", r, c, values));
+        }
+    }
+    out.note(format!("  Programs: {} words", assembled.prog_words.len()));
+    let limit = options.limits.get(Limit::NodeInstructions).value;
+    for (r, row) in parsed.grid.programs.iter().enumerate() {
+        for (c, program) in row.iter().enumerate() {
+            out.note(format!("  Node ({},{}): {} of {} instructions", r, c, program.len(), limit));
+        }
+    }
     
-    // Generate Cairo ABI format args (Cairo will compute merkle root)
-    let args = cairo_abi::generate_args(&inputs, &expected, &prog_words)?;
+    // With args on stdout, stdout is the args JSON and nothing else.
+    if emit.args != "-" {
+        out.record("args", &[&emit.args]);
+        for (key, path) in [("metadata", &emit.metadata), ("symbols", &emit.symbols), ("hints", &emit.hints), ("listing", &emit.listing)] {
+            if let Some(path) = path {
+                out.record(key, &[path]);
+            }
+        }
+        out.record("words", &[&assembled.prog_words.len()]);
+        if out.porcelain {
+            out.record("root", &[&merkle::program_root_hex(&parsed.grid.programs)?]);
+        }
+    }
     
-    // Write to output file
-    fs::write(&output_path, serde_json::to_string(&args)?)?;
+    Ok(assembled)
+}
+
+fn print_capabilities(caps: &capabilities::Capabilities) {
+    println!("{} {}", caps.tool, caps.version);
+    for (name, range) in [
+        ("encoding version", caps.encoding_version),
+        ("commitment version", caps.commitment_version),
+        ("ABI version", caps.abi_version),
+        ("program schema", caps.program_schema),
+        ("bundle format", caps.bundle_format),
+    ] {
+        println!("{:<20}{} (reads {}..={})", name, range.current, range.min_readable, range.current);
+    }
+    println!("{:<20}{}", "hash function", caps.hash_function.name());
+    println!("{:<20}{}..={} x {}..={}", "grid", caps.grid_rows.min, caps.grid_rows.max, caps.grid_cols.min, caps.grid_cols.max);
+    println!("{:<20}0..={}", "literals", caps.max_literal);
+    println!("{:<20}{}", "opcodes", caps.opcodes.join(" "));
+    println!("{:<20}{}", "sources", caps.sources.join(" "));
+    println!("{:<20}{}", "destinations", caps.destinations.join(" "));
+    println!("{:<20}{}", "ports", caps.ports.join(" "));
+    println!("{:<20}{}", "profiles", caps.profiles.join(" "));
+    let aliases: Vec<String> = caps.aliases.iter().map(|(alias, op)| format!("{}={}", alias, op)).collect();
+    println!("{:<20}{}", "aliases", aliases.join(" "));
+    for limit in Limit::ALL {
+        let setting = caps.limits.get(limit);
+        println!("{:<20}{} = {} ({})", "limit", limit, setting.value, setting.origin);
+    }
+}
+
+/// The dialect of the project's `zk100.toml`.
+fn project_dialect() -> Result<Dialect> {
+    Dialect::load(Path::new(config::PROJECT_CONFIG))
+}
+
+/// The `[aliases]` the project adds to the built-in ones, checked for
+/// collisions first (a repeated key would otherwise just overwrite).
+fn project_aliases() -> Result<BTreeMap<String, String>> {
+    project_dialect()?;
+    Ok(config::read_table(Path::new(config::PROJECT_CONFIG), dialect::ALIASES_TABLE)?.into_iter().collect())
+}
+
+fn print_inspection(inspection: &ArgsInspection, style: listing::Style) {
+    use listing::Class;
+    match inspection.cases {
+        Some(cases) => println!("ABI {}: {} values, {} cases", inspection.abi, inspection.values, cases),
+        None => println!("ABI {}: {} values", inspection.abi, inspection.values),
+    }
+    for (section, span) in &inspection.sections {
+        let name = serde_json::to_value(section).unwrap_or_default();
+        println!(
+            "{}{} values  {}",
+            style.paint(Class::Label, &format!("{:<12}", name.as_str().unwrap_or_default())),
+            style.paint(Class::Literal, &format!("{:>10}", span.values)),
+            style.paint(Class::Address, &format!("args[{}..{}]", span.start, span.end))
+        );
+    }
+    if let Some(sentinel) = inspection.sentinel {
+        println!("{}{}", style.paint(Class::Label, &format!("{:<12}", "sentinel")), style.paint(Class::Literal, &sentinel.to_string()));
+    }
+    if let Some(grid) = inspection.grid {
+        println!("{}{}", style.paint(Class::Label, &format!("{:<12}", "grid")), style.paint(Class::Literal, &grid.to_string()));
+    }
+}
+
+/// Every warning about `parsed` that `assemble` prints, in print order.
+fn assembly_warnings(parsed: &assembler::ParseResult, io: &IoNodes) -> Vec<Diagnostic> {
+    [
+        parsed.diagnostics.clone(),
+        parsed.unused_labels(),
+        parsed.io_warnings(io),
+        parsed.last_warnings(),
+        parsed.unreachable_warnings(),
+        parsed.port_warnings(),
+    ]
+    .concat()
+}
+
+fn report_diagnostics(path: &Path, origins: &Origins, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("warning: {}", described(path, origins, diagnostic));
+    }
+}
+
+/// `diagnostic` of the source in `path`, after the name of the file its
+/// line came from: `path: line N: ...`, or `relay.inc:3: ...` for an
+/// included line.
+fn described(path: &Path, origins: &Origins, diagnostic: &Diagnostic) -> String {
+    match origins.locate(diagnostic.line) {
+        Some(origin) => format!("{}: {}", origin, diagnostic.message),
+        None => format!("{}: {}", path.display(), diagnostic),
+    }
+}
+
+/// The grid in `path`, which holds assembly, grid JSON (`convert --to json`)
+/// or a JSON array of 2x2 `prog_words`.
+fn read_programs(path: &Path) -> Result<assembler::Programs> {
+    Ok(read_programs_with_labels(path)?.0)
+}
+
+/// `read_programs`, with the labels when `path` holds assembly.
+fn read_programs_with_labels(path: &Path) -> Result<(assembler::Programs, Labels)> {
+    let (text, origins) = read_input(path)?;
+    match text.trim_start().chars().next() {
+        Some('{') => Ok((grid::from_json(&text)?.programs, Labels::new())),
+        Some('[') => Ok((assembler::decode_grid(&cairo_abi::parse_words(&text)?, GridSize::default())?, Labels::new())),
+        _ => assembler::parse_with(&text, &project_dialect()?)
+            .map(|parsed| (parsed.grid.programs, parsed.labels))
+            .map_err(|e| origins.relocate(e)),
+    }
+    .map_err(|e| anyhow!("{}: {}", path.display(), e))
+}
+
+fn convert_program(
+    input_path: PathBuf,
+    from: Option<ConvertFormat>,
+    to: ConvertFormat,
+    word_format: Option<WordFormatArg>,
+    output_path: Option<PathBuf>,
+) -> Result<()> {
+    let (text, origins) = read_input(&input_path)?;
+    // On stdin there is no extension to go by.
+    let from = from.unwrap_or_else(|| match input_path.extension() {
+        Some(ext) if ext == "json" => ConvertFormat::Json,
+        Some(_) => ConvertFormat::Asm,
+        None if input_path == Path::new("-") && text.trim_start().starts_with('{') => ConvertFormat::Json,
+        None => ConvertFormat::Asm,
+    });
+    let (program_grid, labels) = match from {
+        ConvertFormat::Json => (grid::from_json(&text)?, Labels::new()),
+        ConvertFormat::Words => {
+            let format = WordFormatArg::resolve(word_format, &input_path);
+            let programs = word_list::read(&text, format).map_err(|e| anyhow!("{}: {}", input_path.display(), e))?;
+            (ProgramGrid::new(programs), Labels::new())
+        }
+        ConvertFormat::Asm => {
+            let parsed = assembler::parse_with(&text, &project_dialect()?).map_err(|e| origins.relocate(e))?;
+            report_diagnostics(&input_path, &origins, &parsed.diagnostics);
+            (parsed.grid, parsed.labels)
+        }
+    };
     
-    println!("Generated args file: {}", output_path.display());
-    println!("  Inputs: {:?}", inputs);
-    println!("  Expected: {:?}", expected);
-    println!("  Programs: {} words", prog_words.len());
+    let rendered = match to {
+        ConvertFormat::Json => grid::to_json(&program_grid)? + "\n",
+        ConvertFormat::Asm => program_grid.to_assembly_with_labels(&labels),
+        ConvertFormat::Words => {
+            let format = WordFormatArg::resolve(word_format, output_path.as_deref().unwrap_or(Path::new("-")));
+            word_list::write(&program_grid.programs, format)
+        }
+    };
+    
+    match output_path {
+        Some(path) => exec::write_atomic(&path, rendered)?,
+        None => print!("{}", rendered),
+    }
     
     Ok(())
 }
 
+/// Prove `args_path` (with the hints file, if any), returning the proof's
+/// public data when it is readable.
+fn prove(
+    guest: &Path,
+    args_path: &Path,
+    hints: Option<&Path>,
+    proof_path: &Path,
+    show_progress: bool,
+    out: Output,
+) -> Result<Option<proof_reader::ProofPublicData>> {
+    out.note(format!("Proving with guest: {}", guest.display()));
+    
+    // cairo-prove writes to a temporary file so an interrupted or failed run
+    // never leaves a truncated proof behind.
+    let proof_file = PendingFile::new(proof_path);
+    let mut progress = Progress::spinner("proving", show_progress);
+    let span = trace::span!("cairo-prove", exit_code);
+    let mut command = queue::prove_command(guest, proof_file.path(), args_path);
+    if let Some(hints) = hints {
+        command.arg(hints::PROVER_FLAG).arg(hints);
+    }
+    let status = exec::status(&mut command, &mut progress);
+    progress.finish();
+    let status = status?;
+    span.record("exit_code", status.code().unwrap_or(-1) as u64);
+    drop(span);
+    
+    if !status.success() {
+        return Err(anyhow!("cairo-prove failed ({})", status));
+    }
+    proof_file.commit()?;
+    
+    out.note(format!("Proof written to: {}", proof_path.display()));
+    out.record("proof", &[&proof_path.display()]);
+    match read_proof(proof_path) {
+        Ok(public) => {
+            out.text(&public);
+            out.record("program_root", &[&public.program_root]);
+            out.record("solved", &[&public.solved]);
+            out.record("cycles", &[&public.cycles]);
+            Ok(Some(public))
+        }
+        Err(e) => {
+            eprintln!("warning: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+fn read_proof(path: &Path) -> Result<proof_reader::ProofPublicData> {
+    proof_reader::read(&read_file(path)?).map_err(|e| anyhow!("{}: {}", path.display(), e))
+}
+
+/// Run `cairo-prove verify` on `path`, then read its public output.
+fn verify_proof(path: &Path, show_progress: bool) -> Result<proof_reader::ProofPublicData> {
+    let mut progress = Progress::spinner("verifying proof", show_progress);
+    let status = exec::status(Command::new("cairo-prove").arg("verify").arg(path), &mut progress);
+    progress.finish();
+    let status = status?;
+    if !status.success() {
+        return Err(anyhow!("proof verification failed ({})", status));
+    }
+    read_proof(path)
+}
+
+fn run_bundle(cmd: BundleCmd, show_progress: bool, out: Output) -> Result<()> {
+    match cmd.action {
+        Some(BundleAction::Verify { bundle, run_verifier }) => {
+            let contents = Bundle::open(&bundle)?;
+            let manifest = contents
+                .verify()
+                .map_err(|e| anyhow!("{}: {}", bundle.display(), e))?;
+            out.text(format!("ok    members: {}", bundle::MEMBERS.join(", ")));
+            out.text(format!("ok    program root: {}", manifest.program_root));
+            out.text(format!("ok    {} matches {}", bundle::ARGS, bundle::SOURCE));
+            out.record("program_root", &[&manifest.program_root]);
+            if run_verifier {
+                let dir = std::env::temp_dir().join(format!("zk100-bundle-{}", std::process::id()));
+                contents.extract(&dir)?;
+                let public = verify_proof(&dir.join(bundle::PROOF), show_progress);
+                fs::remove_dir_all(&dir).ok();
+                let public = public?;
+                out.text("ok    proof verified");
+                if !public.commits_to(&manifest.program_root)? {
+                    return Err(anyhow!(
+                        "proof commits to program root {}, not the bundled {}",
+                        public.program_root,
+                        manifest.program_root
+                    ));
+                }
+                out.text("ok    proof commits to the program root");
+                out.record("proof", &[&"verified"]);
+            }
+        }
+        Some(BundleAction::Extract { bundle, output }) => {
+            let dir = output.unwrap_or_else(|| bundle.with_extension(""));
+            Bundle::open(&bundle)?.extract(&dir)?;
+            out.note(format!("Extracted {} into {}", bundle.display(), dir.display()));
+            out.record("dir", &[&dir.display()]);
+        }
+        None => {
+            // clap enforces the three paths when no subcommand is given.
+            let (Some(program), Some(args), Some(proof)) = (cmd.program, cmd.args, cmd.proof) else {
+                return Err(anyhow!("bundle needs PROGRAM ARGS PROOF"));
+            };
+            let source = source::read(&program)?;
+            let source_file = program.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+            let packed = bundle::pack(&source, &source_file, &read_file(&args)?, cmd.abi, &fs::read(&proof)?)?;
+            exec::write_atomic(&cmd.output, packed)?;
+            out.note(format!("Bundle written to: {}", cmd.output.display()));
+            out.record("bundle", &[&cmd.output.display()]);
+        }
+    }
+    Ok(())
+}
+
+fn run_batch(action: BatchAction, out: Output) -> Result<()> {
+    match action {
+        BatchAction::Merge { args, output } => {
+            let singles = args
+                .iter()
+                .map(|path| {
+                    cairo_abi::parse_args(&read_file(path)?).map_err(|e| anyhow!("{}: {}", path.display(), e))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let batch = cairo_abi::Batch::merge(&singles).map_err(|e| {
+                match e.downcast_ref::<cairo_abi::ProgWordsMismatch>() {
+                    Some(m) => anyhow!(
+                        "{} does not share prog_words with {}:\n  {}",
+                        args[m.case].display(),
+                        args[0].display(),
+                        m.diff.join("\n  ")
+                    ),
+                    None => e,
+                }
+            })?;
+            exec::write_atomic(&output, serde_json::to_string(&batch.to_args())?)?;
+            out.note(format!("batched {} cases into {}", batch.cases.len(), output.display()));
+            out.record("batch", &[&output.display(), &batch.cases.len()]);
+        }
+        BatchAction::Split { batch, out_dir } => {
+            let parsed = cairo_abi::parse_batch_args(&read_file(&batch)?)
+                .map_err(|e| anyhow!("{}: {}", batch.display(), e))?;
+            fs::create_dir_all(&out_dir)?;
+            let singles = parsed.split();
+            let width = singles.len().saturating_sub(1).to_string().len().max(3);
+            for (i, single) in singles.iter().enumerate() {
+                let path = out_dir.join(format!("case-{:0width$}.json", i, width = width));
+                exec::write_atomic(&path, serde_json::to_string(&single.to_args(cairo_abi::LEGACY_ABI)?)?)?;
+                out.record("case", &[&path.display()]);
+            }
+            out.note(format!("wrote {} cases to {}", singles.len(), out_dir.display()));
+        }
+    }
+    Ok(())
+}
+
+fn run_example(action: ExampleAction, out: Output) -> Result<()> {
+    match action {
+        ExampleAction::List => {
+            for example in examples::EXAMPLES {
+                out.text(format!("{:<12} {}", example.name, example.summary));
+                out.record("example", &[&example.name]);
+            }
+        }
+        ExampleAction::Show { name } => {
+            let example = examples::find(&name)?;
+            print!("{}", example.source);
+            println!();
+            for case in example.cases {
+                println!("# case: inputs {:?} -> expected {:?}", case.inputs, case.expected);
+            }
+        }
+        ExampleAction::Run { name } => {
+            let example = examples::find(&name)?;
+            let run = example.run()?;
+            out.text(format!("{}: {} words, program root {}", example.name, run.words, run.program_root));
+            out.record("program_root", &[&run.program_root]);
+            out.record("words", &[&run.words]);
+            for (i, case) in run.cases.iter().enumerate() {
+                let status = if case.passed { "ok  " } else { "FAIL" };
+                out.text(format!(
+                    "{}  case {}: inputs {:?} -> {:?} (expected {:?}, {} cycles)",
+                    status, i + 1, case.case.inputs, case.outputs, case.case.expected, case.cycles
+                ));
+                out.record("case", &[&(i + 1), &if case.passed { "passed" } else { "failed" }, &case.cycles]);
+            }
+            if !run.passed() {
+                return Err(anyhow!("example {} failed", example.name));
+            }
+        }
+        ExampleAction::Export { name, dir } => {
+            for path in examples::find(&name)?.export(&dir)? {
+                out.note(format!("Wrote {}", path.display()));
+                out.record("wrote", &[&path.display()]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The guest toolchain is not there: say so and succeed, so CI without it
+/// stays green.
+fn skip_conformance(reason: &str, out: Output) -> Result<()> {
+    out.note(format!("notice: skipping the guest comparison: {}", reason));
+    out.record("skipped", &[&reason]);
+    Ok(())
+}
+
+fn doctor(guest: &GuestArgs, out: Output) -> Result<()> {
+    let mut healthy = true;
+    
+    for tool in ["scarb", "cairo-prove"] {
+        match exec::output(Command::new(tool).arg("--version")) {
+            Ok(output) if output.status.success() => {
+                let version = String::from_utf8_lossy(&output.stdout);
+                let version = version.lines().next().unwrap_or("").trim();
+                out.text(format!("ok    {}: {}", tool, version));
+                out.record("tool", &[&tool, &"ok", &version]);
+            }
+            Ok(output) => {
+                healthy = false;
+                out.text(format!("FAIL  {}: `{} --version` exited with {}", tool, tool, output.status));
+                out.record("tool", &[&tool, &"fail"]);
+            }
+            Err(e) => {
+                healthy = false;
+                out.text(format!("FAIL  {}: not runnable ({})", tool, e));
+                out.record("tool", &[&tool, &"fail"]);
+            }
+        }
+    }
+    
+    match guest.resolve() {
+        Ok(path) => {
+            out.text(format!("ok    guest: {}", path.display()));
+            out.record("guest", &[&"ok", &path.display()]);
+        }
+        Err(e) => {
+            healthy = false;
+            out.text(format!("FAIL  guest: {}", e));
+            out.record("guest", &[&"fail"]);
+        }
+    }
+    
+    if healthy {
+        Ok(())
+    } else {
+        Err(anyhow!("doctor found problems"))
+    }
+}
+
+/// Split `--expected` flags into the flat stream (`a,b,..`, legacy ABI) and
+/// node-scoped lists (`row,col=a,b,..`, node ABI).
+fn parse_expected(specs: &[String], abi: u32) -> Result<(ExpectedSpec, NodeOutputs)> {
+    let mut flat = None;
+    let mut nodes = NodeOutputs::new();
+    for spec in specs {
+        // `len>=N` and `a..=b` also hold an `=`; only a coordinate before it scopes to a node
+        let scoped = spec.split_once('=').and_then(|(node, values)| Some((assembler::parse_node_coords(node).ok()?, values)));
+        match scoped {
+            Some((node, values)) => {
+                if abi != cairo_abi::NODE_ABI {
+                    return Err(anyhow!(
+                        "--expected {}: node-scoped expectations need --abi {}; ABI {} carries one flat stream",
+                        spec, cairo_abi::NODE_ABI, abi
+                    ));
+                }
+                if nodes.insert(node, parse_u32_array(values)).is_some() {
+                    return Err(anyhow!("--expected given twice for node ({},{})", node.0, node.1));
+                }
+            }
+            None if abi == cairo_abi::NODE_ABI => {
+                return Err(anyhow!(
+                    "--expected {}: ABI {} attributes outputs to nodes; write row,col=values",
+                    spec, cairo_abi::NODE_ABI
+                ));
+            }
+            None if flat.is_some() => return Err(anyhow!("--expected given twice")),
+            None => flat = Some(ExpectedSpec::parse(spec).map_err(|e| anyhow!("--expected {}: {}", spec, e))?),
+        }
+    }
+    Ok((flat.unwrap_or_default(), nodes))
+}
+
+/// `--in-node`/`--out-node`: coordinates in the largest grid; the args
+/// layout checks them against the program's.
+fn parse_any_node_coords(s: &str) -> Result<(usize, usize)> {
+    assembler::parse_node_coords_in(s, GridSize::MAX)
+}
+
+/// `--abi` for single-case args; batches (ABI 3) come from `batch merge`.
+fn parse_abi(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(
+            abi @ (cairo_abi::LEGACY_ABI
+            | cairo_abi::NODE_ABI
+            | cairo_abi::DEDUP_ABI
+            | cairo_abi::IO_ABI
+            | cairo_abi::SENTINEL_ABI
+            | cairo_abi::GRID_ABI),
+        ) => Ok(abi),
+        Ok(cairo_abi::BATCH_ABI) => Err("ABI 3 batches are written by `batch merge`".to_string()),
+        _ => Err(format!(
+            "expected {}, {}, {}, {}, {} or {}",
+            cairo_abi::LEGACY_ABI,
+            cairo_abi::NODE_ABI,
+            cairo_abi::DEDUP_ABI,
+            cairo_abi::IO_ABI,
+            cairo_abi::SENTINEL_ABI,
+            cairo_abi::GRID_ABI
+        )),
+    }
+}
+
+/// `--budget`: a number with an optional `ms`, `s` or `m` suffix (seconds by default).
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let text = s.trim();
+    let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len()));
+    let value: u64 = number.parse().map_err(|_| format!("expected a duration such as 30s, found {}", s))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "" | "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        _ => Err(format!("unknown unit {} in {}; use ms, s or m", unit, s)),
+    }
+}
+
 fn parse_u32_array(s: &str) -> Vec<u32> {
     if s.is_empty() {
         return vec![];
     }
     s.split(',')
         .filter_map(|v| {
-            // Parse as i32 first to handle negative numbers, then convert to u32
-            v.trim().parse::<i32>().ok().map(|n| n as u32)
+            // Negative values wrap to u32; hex and binary read as in source.
+            instruction::parse_literal(v.trim())
         })
         .collect()
 }
@@ -101,11 +2243,22 @@ fn parse_u32_array(s: &str) -> Vec<u32> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("7"), Ok(Duration::from_secs(7)));
+        assert!(parse_duration("1h").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+
     #[test]
     fn test_parse_u32_array() {
         assert_eq!(parse_u32_array(""), Vec::<u32>::new());
         assert_eq!(parse_u32_array("42"), vec![42u32]);
         assert_eq!(parse_u32_array("1,2,3"), vec![1u32, 2, 3]);
         assert_eq!(parse_u32_array("10, 20, 30"), vec![10u32, 20, 30]);
+        assert_eq!(parse_u32_array("0x2a, 0b11, -1"), vec![42u32, 3, u32::MAX]);
     }
 }
\ No newline at end of file
@@ -1,19 +1,65 @@
+use crate::assembler;
+use crate::felt_bytes;
 use crate::instruction::Inst;
-use anyhow::Result;
+use crate::trace;
+use anyhow::{Result, anyhow};
 use sha2::{Sha256, Digest};
 use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
+/// Hash functions commitments are built with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum HashFunction {
+    #[default]
+    Sha256,
+}
+
+impl HashFunction {
+    /// Every hash function this build implements.
+    pub const ALL: [HashFunction; 1] = [HashFunction::Sha256];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashFunction::Sha256 => "sha256",
+        }
+    }
+}
+
+/// SHA-256 leaves per node, SHA-256 Merkle tree over the grid.
+pub const COMMITMENT_VERSION: u32 = 1;
+
+/// Hash used for leaves and inner nodes of the program tree.
+pub const HASH_FUNCTION: HashFunction = HashFunction::Sha256;
+
+/// Nodes with the same instruction words share a leaf, so each distinct
+/// program is hashed once; the `leaf_hashes` span field counts the hashes.
 pub fn compute_program_merkle_root(programs: &[Vec<Vec<Inst>>]) -> Result<Vec<u8>> {
+    assembler::check_complete(programs)?;
+    let span = trace::span!("merkle::compute", leaves, leaf_hashes);
     let mut leaves = Vec::new();
+    let mut memo: HashMap<Vec<u32>, Vec<u8>> = HashMap::new();
     
     // For each program, compute its hash
     for row in programs {
         for program in row {
-            let leaf_hash = hash_program(program)?;
+            let words = program_words(program);
+            let leaf_hash = match memo.get(&words) {
+                Some(hash) => hash.clone(),
+                None => {
+                    let hash = hash_words(&words);
+                    memo.insert(words, hash.clone());
+                    hash
+                }
+            };
             leaves.push(leaf_hash);
         }
     }
-    
+    span.record("leaves", leaves.len() as u64);
+    span.record("leaf_hashes", memo.len() as u64);
+
     // Compute merkle root from leaves
     let root = merkle_root(&leaves);
     
@@ -21,21 +67,56 @@ pub fn compute_program_merkle_root(programs: &[Vec<Vec<Inst>>]) -> Result<Vec<u8
     Ok(root)
 }
 
-fn hash_program(program: &[Inst]) -> Result<Vec<u8>> {
-    // Encode all instructions
-    let mut encoded_data = Vec::new();
-    for inst in program {
-        let encoded = inst.encode();
-        // Convert u32 to bytes (big-endian to match Cairo)
-        encoded_data.extend_from_slice(&encoded.to_be_bytes());
+/// The program root as `0x`-prefixed hex, the form `hash`, bundles, seals and
+/// metadata all use.
+pub fn program_root_hex(programs: &[Vec<Vec<Inst>>]) -> Result<String> {
+    Ok(felt_bytes::hex32(&felt_bytes::bytes32(&compute_program_merkle_root(programs)?)?))
+}
+
+/// `program_root_hex` under commitment `version`. This build implements
+/// only `COMMITMENT_VERSION`; any other version is an error.
+pub fn program_root_hex_for(version: u32, programs: &[Vec<Vec<Inst>>]) -> Result<String> {
+    check_commitment_version(version)?;
+    program_root_hex(programs)
+}
+
+pub fn check_commitment_version(version: u32) -> Result<()> {
+    if version != COMMITMENT_VERSION {
+        return Err(anyhow!(
+            "commitment version {} is not implemented; this build only has version {}",
+            version,
+            COMMITMENT_VERSION
+        ));
     }
-    
-    // Hash the encoded program
+    Ok(())
+}
+
+fn program_words(program: &[Inst]) -> Vec<u32> {
+    program.iter().map(Inst::encode).collect()
+}
+
+/// The leaf of one node's encoded program.
+pub fn leaf_hash(words: &[u32]) -> Vec<u8> {
+    hash_words(words)
+}
+
+/// The root over leaves already hashed with `leaf_hash`, in row-major order.
+pub fn root_from_leaves(leaves: &[Vec<u8>]) -> Vec<u8> {
+    merkle_root(leaves)
+}
+
+fn hash_words(words: &[u32]) -> Vec<u8> {
+    // Convert u32 to bytes (big-endian to match Cairo)
     let mut hasher = Sha256::new();
-    hasher.update(&encoded_data);
-    let hash = hasher.finalize();
-    
-    Ok(hash.to_vec())
+    for word in words {
+        hasher.update(word.to_be_bytes());
+    }
+    hasher.finalize().to_vec()
+}
+
+#[cfg(test)]
+fn hash_program(program: &[Inst]) -> Result<Vec<u8>> {
+    Ok(hash_words(&program_words(program)))
 }
 
 fn merkle_root(leaves: &[Vec<u8>]) -> Vec<u8> {
@@ -75,8 +156,7 @@ fn merkle_root(leaves: &[Vec<u8>]) -> Vec<u8> {
 
 // Convert bytes to felt252 representation (for Cairo compatibility)
 pub fn bytes_to_felt252(bytes: &[u8]) -> String {
-    let big_int = BigUint::from_bytes_be(bytes);
-    format!("0x{}", big_int.to_str_radix(16))
+    felt_bytes::to_hex(&BigUint::from_bytes_be(bytes))
 }
 
 #[cfg(test)]
@@ -137,6 +217,16 @@ mod tests {
         assert_eq!(root.len(), 32);
     }
 
+    #[test]
+    fn test_shared_programs_hash_like_distinct_ones() {
+        let programs = crate::assembler::parse_assembly(
+            "NODE (0,0)\nMOV IN, P:RIGHT\nNODE (0,1)\nMOV P:LEFT, P:DOWN\nNODE (1,0)\nMOV P:LEFT, P:DOWN\nNODE (1,1)\nMOV P:LEFT, P:DOWN\n",
+        )
+        .unwrap();
+        let leaves: Vec<Vec<u8>> = programs.iter().flatten().map(|p| hash_program(p).unwrap()).collect();
+        assert_eq!(compute_program_merkle_root(&programs).unwrap(), merkle_root(&leaves));
+    }
+
     #[test]
     fn test_bytes_to_felt252() {
         let bytes = vec![0x12, 0x34, 0x56, 0x78];
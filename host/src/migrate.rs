@@ -0,0 +1,194 @@
+//! Registry root migration across commitment versions, behind
+//! `migrate-roots`.
+//!
+//! The input is a CSV of `program_id,path,old_root` rows (a header row is
+//! optional), with each `path` relative to the sources directory. Every row
+//! is checked on its own. The source is read and assembled, its root under
+//! the old version must equal `old_root`, and only then is its root under
+//! the new version computed. A row that fails a step gets that step's
+//! status and no new root. The output CSV repeats each row with
+//! `new_root`, `status` and a `detail` that says what went wrong.
+
+use crate::dialect::Dialect;
+use crate::{assembler, felt_bytes, merkle, source};
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub const OUTPUT_HEADER: &str = "program_id,path,old_root,new_root,status,detail";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub program_id: String,
+    pub path: String,
+    pub old_root: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// No file at `path`.
+    SourceMissing,
+    /// The file is there but does not read or assemble.
+    SourceInvalid,
+    /// The source assembles to another root under the old version, or the
+    /// recorded root is not a hex root at all.
+    OldRootMismatch,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::Ok => "ok",
+            Status::SourceMissing => "source-missing",
+            Status::SourceInvalid => "source-invalid",
+            Status::OldRootMismatch => "old-root-mismatch",
+        })
+    }
+}
+
+/// One row's outcome; `new_root` is only set when `status` is `Ok`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migrated {
+    pub row: Row,
+    pub new_root: Option<String>,
+    pub status: Status,
+    pub detail: String,
+}
+
+/// Parse `v1` or `1` as a commitment version.
+pub fn parse_version(text: &str) -> Result<u32> {
+    let digits = text.strip_prefix('v').or_else(|| text.strip_prefix('V')).unwrap_or(text);
+    digits.parse().map_err(|_| anyhow!("expected a commitment version like v1, found {}", text))
+}
+
+/// The rows of the CSV at `path`. A row without exactly three cells is an
+/// error for the whole file, naming its line.
+pub fn read_rows(path: &Path) -> Result<Vec<Row>> {
+    let text = fs::read_to_string(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    let mut rows = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cells: Vec<&str> = line.split(',').map(|c| c.trim().trim_matches('"').trim()).collect();
+        let [program_id, path_cell, old_root] = cells[..] else {
+            return Err(anyhow!(
+                "{}:{}: expected program_id,path,old_root, found {} cells",
+                path.display(),
+                idx + 1,
+                cells.len()
+            ));
+        };
+        if rows.is_empty() && program_id == "program_id" {
+            continue;
+        }
+        rows.push(Row { program_id: program_id.into(), path: path_cell.into(), old_root: old_root.into() });
+    }
+    Ok(rows)
+}
+
+/// Migrate every row from commitment version `from` to `to` on up to `jobs`
+/// threads, keeping the input order. Only an unimplemented version fails
+/// the whole run; everything else is a row's status.
+pub fn migrate(rows: &[Row], sources: &Path, dialect: &Dialect, from: u32, to: u32, jobs: usize) -> Result<Vec<Migrated>> {
+    merkle::check_commitment_version(from)?;
+    merkle::check_commitment_version(to)?;
+    let next = AtomicUsize::new(0);
+    let done = Mutex::new(Vec::with_capacity(rows.len()));
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, rows.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(row) = rows.get(idx) else {
+                        return;
+                    };
+                    let migrated = migrate_row(row, sources, dialect, from, to);
+                    done.lock().unwrap_or_else(|e| e.into_inner()).push((idx, migrated));
+                }
+            });
+        }
+    });
+    let mut done = done.into_inner().unwrap_or_else(|e| e.into_inner());
+    done.sort_by_key(|(idx, _)| *idx);
+    Ok(done.into_iter().map(|(_, migrated)| migrated).collect())
+}
+
+fn migrate_row(row: &Row, sources: &Path, dialect: &Dialect, from: u32, to: u32) -> Migrated {
+    let failed = |status: Status, detail: String| Migrated { row: row.clone(), new_root: None, status, detail };
+    let path = sources.join(&row.path);
+    if !path.is_file() {
+        return failed(Status::SourceMissing, format!("no file at {}", path.display()));
+    }
+    let programs = match source::read(&path).and_then(|text| Ok(assembler::parse_with(&text, dialect)?.grid.programs)) {
+        Ok(programs) => programs,
+        Err(e) => return failed(Status::SourceInvalid, e.to_string()),
+    };
+    let recorded = match felt_bytes::from_hex32(&row.old_root) {
+        Ok(root) => root,
+        Err(e) => return failed(Status::OldRootMismatch, format!("the old root {}", e)),
+    };
+    let old = match root(from, &programs) {
+        Ok(old) => old,
+        Err(e) => return failed(Status::SourceInvalid, e.to_string()),
+    };
+    if felt_bytes::from_hex32(&old).ok() != Some(recorded) {
+        return failed(Status::OldRootMismatch, format!("the source has v{} root {}", from, old));
+    }
+    match root(to, &programs) {
+        Ok(new_root) => Migrated { row: row.clone(), new_root: Some(new_root), status: Status::Ok, detail: String::new() },
+        Err(e) => failed(Status::SourceInvalid, e.to_string()),
+    }
+}
+
+fn root(version: u32, programs: &assembler::Programs) -> Result<String> {
+    merkle::program_root_hex_for(version, programs)
+}
+
+/// The output CSV, header first, one line per row.
+pub fn to_csv(migrated: &[Migrated]) -> String {
+    let mut csv = format!("{}\n", OUTPUT_HEADER);
+    for m in migrated {
+        let new_root = m.new_root.as_deref().unwrap_or("");
+        let cells = [&m.row.program_id, &m.row.path, &m.row.old_root, new_root, &m.status.to_string(), &m.detail];
+        let cells: Vec<String> = cells.iter().map(|cell| quote(cell)).collect();
+        csv.push_str(&cells.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn quote(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\"").replace('\n', " "))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_and_csv() {
+        let dir = std::env::temp_dir().join(format!("zk100-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("roots.csv");
+        fs::write(&input, "program_id,path,old_root\nzk1a,\"a.asm\",0x01\n\nzk1b, b.asm ,0x02\n").unwrap();
+        let rows = read_rows(&input).unwrap();
+        assert_eq!(rows[1], Row { program_id: "zk1b".into(), path: "b.asm".into(), old_root: "0x02".into() });
+        fs::write(&input, "zk1a,a.asm\n").unwrap();
+        assert!(read_rows(&input).unwrap_err().to_string().ends_with("roots.csv:1: expected program_id,path,old_root, found 2 cells"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let migrated = Migrated { row: rows[0].clone(), new_root: None, status: Status::SourceInvalid, detail: "line 2: bad, \"x\"".into() };
+        assert_eq!(to_csv(&[migrated]), format!("{}\nzk1a,a.asm,0x01,,source-invalid,\"line 2: bad, \"\"x\"\"\"\n", OUTPUT_HEADER));
+        assert_eq!((parse_version("v1").unwrap(), parse_version("2").unwrap()), (1, 2));
+        assert!(parse_version("one").is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! Shrinking a working solution behind `minimize`.
+//!
+//! `minimize` proposes programs one instruction shorter than the current one
+//! and keeps the first that still passes, until no proposal passes or the
+//! time budget runs out. A proposal, in each node and at each pc:
+//!
+//! - merges a literal chain into one instruction: `MOV a, ACC` + `ADD b`
+//!   becomes `MOV a+b, ACC`, and two `ADD`/`SUB` literals become their sum,
+//!   as long as the result still fits the 8-bit literal field;
+//! - merges a `MOV x, ACC` with the `MOV ACC, y` after it into `MOV x, y`;
+//! - drops the instruction.
+//!
+//! Removing an instruction moves every later jump target and label of its
+//! node down by one; a jump to the removed instruction lands on the one
+//! after it. Merges are skipped when something jumps to their second half.
+//!
+//! None of this is guaranteed to keep the program's meaning, so every
+//! proposal is run in the simulator: it must pass every manifest case, and
+//! on `random_inputs` extra input streams, drawn like the cases' inputs, it
+//! must produce what the original program produced. `ASSERT` lines are not
+//! checked and do not survive, since their pcs would move.
+
+use crate::assembler::{self, Labels, ParseResult, Programs};
+use crate::cairo_abi::IoNodes;
+use crate::expected::ExpectedSpec;
+use crate::grid::ProgramGrid;
+use crate::inputs::splitmix64;
+use crate::instruction::{Dst, Inst, LIT_FIELD, Op, Src};
+use crate::report::PuzzleManifest;
+use crate::sim::{DEFAULT_MAX_CYCLES, Simulator, Termination};
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinimizeConfig {
+    /// Extra input streams checked against the original program's outputs.
+    pub random_inputs: usize,
+    pub seed: u64,
+    pub budget: Duration,
+}
+
+impl Default for MinimizeConfig {
+    fn default() -> Self {
+        MinimizeConfig { random_inputs: 16, seed: 0, budget: Duration::from_secs(30) }
+    }
+}
+
+/// Instruction count over all nodes, and cycles summed over the cases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub instructions: usize,
+    pub cycles: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Minimized {
+    pub source: String,
+    pub before: Size,
+    pub after: Size,
+    /// Proposals run in the simulator, and how many of them were kept.
+    pub candidates: usize,
+    pub accepted: usize,
+    /// Whether the budget ran out before every proposal failed.
+    pub out_of_time: bool,
+}
+
+impl fmt::Display for Minimized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let delta = |before: u64, after: u64| after as i64 - before as i64;
+        writeln!(
+            f,
+            "{} -> {} instructions ({:+}), {} -> {} cycles ({:+})",
+            self.before.instructions,
+            self.after.instructions,
+            delta(self.before.instructions as u64, self.after.instructions as u64),
+            self.before.cycles,
+            self.after.cycles,
+            delta(self.before.cycles, self.after.cycles)
+        )?;
+        write!(f, "{} candidates tried, {} kept", self.candidates, self.accepted)?;
+        if self.out_of_time {
+            write!(f, " (budget ran out)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Shrink `parsed` against `manifest`'s cases, whose input files are
+/// relative to `base`.
+pub fn minimize(parsed: &ParseResult, manifest: &PuzzleManifest, base: &Path, config: &MinimizeConfig) -> Result<Minimized> {
+    let deadline = Instant::now() + config.budget;
+    let mut checker = Checker {
+        cases: Vec::new(),
+        oracle: Vec::new(),
+        io: manifest.io,
+        max_cycles: manifest.max_cycles.unwrap_or(DEFAULT_MAX_CYCLES),
+    };
+    for case in &manifest.cases {
+        checker.cases.push((case.inputs.expand(base)?, case.expected.clone()));
+    }
+    let programs = parsed.grid.programs.clone();
+    for (i, (inputs, expected)) in checker.cases.iter().enumerate() {
+        let outputs = checker.run(&programs, inputs)?.0;
+        let failures = expected.check(&outputs);
+        if let Some(failure) = failures.first() {
+            return Err(anyhow!("the program fails case {} before minimizing: {}", i + 1, failure));
+        }
+    }
+    checker.oracle = oracle_inputs(&checker, &programs, config);
+
+    let cycles = checker.cycles(&programs).expect("the original passes its own checks");
+    let before = Size { instructions: count(&programs), cycles };
+    let (mut current, mut labels, mut after) = (programs, parsed.labels.clone(), before);
+    let (mut candidates, mut accepted, mut out_of_time) = (0, 0, false);
+    'search: loop {
+        for (node, pc, reduction) in proposals(&current) {
+            if Instant::now() >= deadline {
+                out_of_time = true;
+                break 'search;
+            }
+            let Some(candidate) = apply(&current, node, pc, reduction) else {
+                continue;
+            };
+            candidates += 1;
+            if let Some(cycles) = checker.cycles(&candidate) {
+                current = candidate;
+                shift_labels(&mut labels, node, pc + reduction.removes());
+                after = Size { instructions: count(&current), cycles };
+                accepted += 1;
+                continue 'search;
+            }
+        }
+        break;
+    }
+    let source = ProgramGrid::new(current).to_assembly_with_labels(&labels);
+    Ok(Minimized { source, before, after, candidates, accepted, out_of_time })
+}
+
+struct Checker {
+    cases: Vec<(Vec<u32>, ExpectedSpec)>,
+    /// Input streams and the original program's outputs on them.
+    oracle: Vec<(Vec<u32>, Vec<u32>)>,
+    io: IoNodes,
+    max_cycles: u64,
+}
+
+impl Checker {
+    /// Outputs and cycles of a run that terminates without error.
+    fn run(&self, programs: &Programs, inputs: &[u32]) -> Result<(Vec<u32>, u64)> {
+        let result = Simulator::with_io(programs, inputs, self.io)?.run_to_result(self.max_cycles)?;
+        match result.termination {
+            Termination::CycleLimit => Err(anyhow!("no termination within {} cycles", self.max_cycles)),
+            _ => Ok((result.outputs, result.cycles)),
+        }
+    }
+
+    /// Total cycles over the cases, if `programs` passes every check.
+    fn cycles(&self, programs: &Programs) -> Option<u64> {
+        assembler::encode_programs(programs).ok()?;
+        let mut total = 0;
+        for (inputs, expected) in &self.cases {
+            let (outputs, cycles) = self.run(programs, inputs).ok()?;
+            if !expected.matches(&outputs) {
+                return None;
+            }
+            total += cycles;
+        }
+        for (inputs, wanted) in &self.oracle {
+            if self.run(programs, inputs).ok()?.0 != *wanted {
+                return None;
+            }
+        }
+        Some(total)
+    }
+}
+
+/// Random input streams as long as the cases' and within their value range,
+/// with the original's outputs. Streams the original fails on are dropped.
+fn oracle_inputs(checker: &Checker, programs: &Programs, config: &MinimizeConfig) -> Vec<(Vec<u32>, Vec<u32>)> {
+    let values = checker.cases.iter().flat_map(|(inputs, _)| inputs.iter().copied());
+    let (lo, hi) = values.fold((u32::MAX, 0), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    if lo > hi {
+        return Vec::new();
+    }
+    let mut state = config.seed;
+    let mut draw = |n: u64| (splitmix64(&mut state) % n) as usize;
+    let mut oracle = Vec::new();
+    for _ in 0..config.random_inputs {
+        let len = checker.cases[draw(checker.cases.len() as u64)].0.len();
+        let inputs: Vec<u32> = (0..len).map(|_| lo + draw(u64::from(hi - lo) + 1) as u32).collect();
+        if let Ok((outputs, _)) = checker.run(programs, &inputs) {
+            oracle.push((inputs, outputs));
+        }
+    }
+    oracle
+}
+
+fn count(programs: &Programs) -> usize {
+    programs.iter().flatten().map(Vec::len).sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reduction {
+    LiteralChain,
+    MergeMov,
+    Drop,
+}
+
+impl Reduction {
+    /// The pc offset of the instruction that disappears.
+    fn removes(self) -> usize {
+        match self {
+            Reduction::LiteralChain | Reduction::MergeMov => 1,
+            Reduction::Drop => 0,
+        }
+    }
+}
+
+fn proposals(programs: &Programs) -> Vec<((usize, usize), usize, Reduction)> {
+    let mut out = Vec::new();
+    for (r, row) in programs.iter().enumerate() {
+        for (c, program) in row.iter().enumerate() {
+            for pc in 0..program.len() {
+                for reduction in [Reduction::LiteralChain, Reduction::MergeMov, Reduction::Drop] {
+                    out.push(((r, c), pc, reduction));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// `programs` with `reduction` applied at `node`'s `pc`, if it applies there.
+fn apply(programs: &Programs, (r, c): (usize, usize), pc: usize, reduction: Reduction) -> Option<Programs> {
+    let program = &programs[r][c];
+    let merged = match reduction {
+        Reduction::Drop => None,
+        Reduction::LiteralChain => Some(literal_chain(program[pc], *program.get(pc + 1)?)?),
+        Reduction::MergeMov => Some(merge_mov(program[pc], *program.get(pc + 1)?)?),
+    };
+    let mut program = program.clone();
+    if let Some(merged) = merged {
+        if jump_targets(&program).any(|target| target == pc + 1) {
+            return None;
+        }
+        program[pc] = merged;
+    }
+    let removed = pc + reduction.removes();
+    let len = program.len();
+    program.remove(removed);
+    for inst in &mut program {
+        if let (true, Src::Lit(target)) = (is_jump(inst.op), inst.src) {
+            let target = target as usize % len;
+            inst.src = Src::Lit(if target > removed { target - 1 } else { target } as u32);
+        }
+    }
+    let mut programs = programs.clone();
+    programs[r][c] = program;
+    Some(programs)
+}
+
+fn literal_chain(first: Inst, second: Inst) -> Option<Inst> {
+    let fits = |value: i64| (0..=i64::from(LIT_FIELD.mask)).contains(&value).then_some(value as u32);
+    let signed = |inst: Inst| match (inst.op, inst.src) {
+        (Op::Add, Src::Lit(value)) => Some(i64::from(value)),
+        (Op::Sub, Src::Lit(value)) => Some(-i64::from(value)),
+        _ => None,
+    };
+    match (first.op, first.src, first.dst) {
+        (Op::Mov, Src::Lit(value), Dst::Acc) => {
+            let value = fits(i64::from(value) + signed(second)?)?;
+            Some(Inst { src: Src::Lit(value), ..first })
+        }
+        _ => {
+            let net = signed(first)? + signed(second)?;
+            let op = if net < 0 { Op::Sub } else { Op::Add };
+            Some(Inst { op, src: Src::Lit(fits(net.abs())?), dst: first.dst })
+        }
+    }
+}
+
+fn merge_mov(first: Inst, second: Inst) -> Option<Inst> {
+    match (first, second) {
+        (Inst { op: Op::Mov, src, dst: Dst::Acc }, Inst { op: Op::Mov, src: Src::Acc, dst })
+            if src != Src::Acc && dst != Dst::Acc =>
+        {
+            Some(Inst { op: Op::Mov, src, dst })
+        }
+        _ => None,
+    }
+}
+
+fn is_jump(op: Op) -> bool {
+    matches!(op, Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz)
+}
+
+fn jump_targets(program: &[Inst]) -> impl Iterator<Item = usize> + '_ {
+    program.iter().filter_map(move |inst| match (is_jump(inst.op), inst.src) {
+        (true, Src::Lit(target)) => Some(target as usize % program.len()),
+        _ => None,
+    })
+}
+
+fn shift_labels(labels: &mut Labels, node: (usize, usize), removed: usize) {
+    for at in labels.entry(node).or_default().values_mut() {
+        if *at > removed {
+            *at -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::parse_assembly;
+
+    #[test]
+    fn test_dropping_retargets_jumps() {
+        let programs = parse_assembly("NODE (0,0)\nNOP\nL:\nMOV IN, ACC\nNOP\nJMP L\nJMP 2\n").unwrap();
+        let dropped = apply(&programs, (0, 0), 0, Reduction::Drop).unwrap();
+        let node = &dropped[0][0];
+        assert_eq!(node[0], Inst { op: Op::Mov, src: Src::In, dst: Dst::Acc });
+        assert_eq!((node[2].src, node[3].src), (Src::Lit(0), Src::Lit(1)));
+
+        // A jump to the dropped NOP now lands on the JMP after it.
+        let dropped = apply(&programs, (0, 0), 2, Reduction::Drop).unwrap();
+        assert_eq!((dropped[0][0][2].src, dropped[0][0][3].src), (Src::Lit(1), Src::Lit(2)));
+    }
+
+    #[test]
+    fn test_merges() {
+        let lit = |op, value| Inst { op, src: Src::Lit(value), dst: Dst::Nil };
+        let mov = |src, dst| Inst { op: Op::Mov, src, dst };
+        let load = mov(Src::Lit(200), Dst::Acc);
+        assert_eq!(literal_chain(load, lit(Op::Add, 55)), Some(mov(Src::Lit(255), Dst::Acc)));
+        assert_eq!(literal_chain(load, lit(Op::Add, 56)), None);
+        assert_eq!(literal_chain(load, lit(Op::Sub, 201)), None);
+        assert_eq!(literal_chain(lit(Op::Add, 3), lit(Op::Sub, 5)), Some(lit(Op::Sub, 2)));
+        assert_eq!(literal_chain(lit(Op::Add, 3), Inst { src: Src::Acc, ..lit(Op::Add, 0) }), None);
+
+        assert_eq!(merge_mov(mov(Src::In, Dst::Acc), mov(Src::Acc, Dst::Out)), Some(mov(Src::In, Dst::Out)));
+        assert_eq!(merge_mov(mov(Src::Acc, Dst::Acc), mov(Src::Acc, Dst::Out)), None);
+
+        // Nothing may jump between the merged pair.
+        let programs = parse_assembly("NODE (0,0)\nMOV IN, ACC\nL:\nMOV ACC, OUT\nJMP L\n").unwrap();
+        assert_eq!(apply(&programs, (0, 0), 0, Reduction::MergeMov), None);
+    }
+}
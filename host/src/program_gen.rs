@@ -0,0 +1,422 @@
+//! Synthetic programs of a chosen size, behind `gen-program`, for load
+//! testing the prover.
+//!
+//! A generated program is a pipeline between the legacy IN and OUT nodes:
+//! (0,0) reads, (0,1) passes the value on and (1,1) writes it. With four
+//! nodes (0,0) also sends every value down to (1,0) and waits for it to come
+//! back. Each node receives a value, works on it and sends it on, padded to
+//! exactly the requested number of instructions. The work is:
+//!
+//! - loops, each counting a literal down to zero in `BAK` while adding to
+//!   `ACC`, so every loop terminates. With only `ACC` and `BAK` there is no
+//!   register left for a second counter, so loops follow each other rather
+//!   than nest;
+//! - filler that keeps the value or adds or subtracts a literal, never going
+//!   below zero: `ADD`, `SUB`, `NEG` and `SWP` pairs, `SAV`, `NOP`, and
+//!   conditional jumps to the next instruction;
+//! - with `PortDensity::Heavy`, handshakes: the sender writes one to three
+//!   literals before each value and the receiver reads them into `NIL`.
+//!
+//! Every value leaves the pipeline as its input plus a fixed offset, which
+//! gives the expected outputs. The program then goes through the checks a
+//! hand-written one would: it must assemble without diagnostics, survive the
+//! guest's word codec, and in the simulator consume every input, produce the
+//! expected outputs and deadlock within the guest's cycle limit. A program
+//! that fails them is generated again from the next draws, at most
+//! `MAX_ATTEMPTS` times; by construction that should not happen.
+
+use crate::assembler;
+use crate::inputs::splitmix64;
+use crate::sim::{self, DEFAULT_MAX_CYCLES, Termination};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+
+/// Jump targets are 8-bit literals, so labels past this are unreachable.
+pub const MAX_INSTRUCTIONS: usize = 256;
+/// Generations tried before `generate` gives up.
+pub const MAX_ATTEMPTS: u32 = 8;
+/// Inputs are drawn from `0..=MAX_INPUT`.
+pub const MAX_INPUT: u32 = 99;
+/// Instructions in one counted loop.
+const LOOP_LEN: usize = 8;
+
+/// How many port transfers a program makes per value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortDensity {
+    /// One transfer per value and link.
+    Light,
+    /// One to three handshakes before each value.
+    Heavy,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenConfig {
+    /// 3 or 4: the IN and OUT nodes are diagonal, so a pipeline needs one
+    /// of the other two, and the fourth node works as a co-processor.
+    pub nodes: usize,
+    /// Instructions in every node's program.
+    pub instructions: usize,
+    /// Counted loops per node.
+    pub loops: usize,
+    pub ports: PortDensity,
+    /// How many inputs to generate.
+    pub inputs: usize,
+    pub seed: u64,
+}
+
+/// A generated program and a case it passes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generated {
+    pub source: String,
+    pub inputs: Vec<u32>,
+    pub expected: Vec<u32>,
+    /// Cycles the simulator took for `inputs`.
+    pub cycles: u64,
+    /// 1 unless a generated program failed its checks.
+    pub attempts: u32,
+}
+
+impl Generated {
+    /// The case as `cases.json`, in the format `example export` writes.
+    pub fn cases_json(&self) -> Result<String> {
+        #[derive(Serialize)]
+        struct Case<'a> {
+            inputs: &'a [u32],
+            expected: &'a [u32],
+        }
+        Ok(serde_json::to_string_pretty(&[Case { inputs: &self.inputs, expected: &self.expected }])? + "\n")
+    }
+}
+
+struct Rng(u64);
+
+impl Rng {
+    /// Uniform in `lo..=hi`.
+    fn range(&mut self, lo: u32, hi: u32) -> u32 {
+        lo + (splitmix64(&mut self.0) % (u64::from(hi - lo) + 1)) as u32
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.range(0, items.len() as u32 - 1) as usize]
+    }
+}
+
+/// One part of a node's program, in order.
+#[derive(Debug, Clone, Copy)]
+enum Piece {
+    Line(&'static str),
+    /// Handshakes and the value over a link, as sender or receiver.
+    Send(usize),
+    Recv(usize),
+    /// Generated work; gaps are numbered in the order the value visits them.
+    Gap(usize),
+}
+
+/// A link's port as seen from the sender and from the receiver.
+struct Link {
+    send: &'static str,
+    recv: &'static str,
+}
+
+type Layout = (Vec<((usize, usize), Vec<Piece>)>, Vec<Link>, usize);
+
+/// The nodes with their pieces, the links and the number of gaps.
+fn layout(nodes: usize) -> Layout {
+    use Piece::*;
+    const DOWN: Link = Link { send: "DOWN", recv: "UP" };
+    const UP: Link = Link { send: "UP", recv: "DOWN" };
+    const RIGHT: Link = Link { send: "RIGHT", recv: "LEFT" };
+    if nodes == 3 {
+        let grid = vec![
+            ((0, 0), vec![Line("MOV IN, ACC"), Gap(0), Send(0)]),
+            ((0, 1), vec![Recv(0), Gap(1), Send(1)]),
+            ((1, 1), vec![Recv(1), Gap(2), Line("MOV ACC, OUT")]),
+        ];
+        return (grid, vec![RIGHT, DOWN], 3);
+    }
+    let grid = vec![
+        ((0, 0), vec![Line("MOV IN, ACC"), Gap(0), Send(0), Recv(1), Gap(2), Send(2)]),
+        ((0, 1), vec![Recv(2), Gap(3), Send(3)]),
+        ((1, 0), vec![Recv(0), Gap(1), Send(1)]),
+        ((1, 1), vec![Recv(3), Gap(4), Line("MOV ACC, OUT")]),
+    ];
+    (grid, vec![DOWN, UP, RIGHT, DOWN], 5)
+}
+
+/// Instructions a node spends outside its gaps.
+fn fixed_len(pieces: &[Piece], handshakes: &[usize]) -> usize {
+    pieces
+        .iter()
+        .map(|piece| match *piece {
+            Piece::Line(_) => 1,
+            Piece::Send(link) | Piece::Recv(link) => 1 + handshakes[link],
+            Piece::Gap(_) => 0,
+        })
+        .sum()
+}
+
+/// Generate a program for `config` that passes every check, retrying with
+/// the next draws of the same seed if one does not.
+pub fn generate(config: &GenConfig) -> Result<Generated> {
+    if !(3..=4).contains(&config.nodes) {
+        return Err(anyhow!("--nodes must be 3 or 4, not {}", config.nodes));
+    }
+    if config.instructions > MAX_INSTRUCTIONS {
+        return Err(anyhow!(
+            "--instructions {} is more than a node can jump across ({})",
+            config.instructions,
+            MAX_INSTRUCTIONS
+        ));
+    }
+    if config.inputs == 0 {
+        return Err(anyhow!("need at least one input"));
+    }
+    let (grid, links, _) = layout(config.nodes);
+    let least = usize::from(config.ports == PortDensity::Heavy);
+    for (node, pieces) in &grid {
+        let needed = fixed_len(pieces, &vec![least; links.len()]) + config.loops * LOOP_LEN;
+        if needed > config.instructions {
+            return Err(anyhow!(
+                "--instructions {} is too few: node ({},{}) needs at least {} for {} loops",
+                config.instructions,
+                node.0,
+                node.1,
+                needed,
+                config.loops
+            ));
+        }
+    }
+
+    let mut rng = Rng(config.seed);
+    let mut failures = Vec::new();
+    for attempt in 1..=MAX_ATTEMPTS {
+        let (source, inputs, expected) = draw(config, &mut rng);
+        match check(&source, &inputs, &expected) {
+            Ok(cycles) => return Ok(Generated { source, inputs, expected, cycles, attempts: attempt }),
+            Err(e) => failures.push(format!("attempt {}: {}", attempt, e)),
+        }
+    }
+    Err(anyhow!("no valid program in {} attempts:\n{}", MAX_ATTEMPTS, failures.join("\n")))
+}
+
+/// The checks a generated program must pass; the cycles it took.
+pub fn check(source: &str, inputs: &[u32], expected: &[u32]) -> Result<u64> {
+    let parsed = assembler::parse(source)?;
+    if let Some(diagnostic) = parsed.diagnostics.first() {
+        return Err(anyhow!("assembles with a diagnostic: {}", diagnostic));
+    }
+    let size = parsed.grid.size();
+    let programs = parsed.grid.programs;
+    if assembler::decode_grid(&assembler::encode_programs(&programs)?, size)? != programs {
+        return Err(anyhow!("the encoded words decode to a different program"));
+    }
+    let result = sim::simulate(&programs, inputs, DEFAULT_MAX_CYCLES)?;
+    if result.termination != Termination::Deadlock {
+        return Err(anyhow!("ended in {:?} after {} cycles", result.termination, result.cycles));
+    }
+    if result.inputs_consumed != inputs.len() {
+        return Err(anyhow!("consumed {} of {} inputs", result.inputs_consumed, inputs.len()));
+    }
+    if !result.matches(expected) {
+        return Err(anyhow!("output {:?}, expected {:?}", result.outputs, expected));
+    }
+    Ok(result.cycles)
+}
+
+/// One candidate program with its inputs and expected outputs.
+fn draw(config: &GenConfig, rng: &mut Rng) -> (String, Vec<u32>, Vec<u32>) {
+    let (grid, links, gap_count) = layout(config.nodes);
+
+    let mut handshakes = vec![0; links.len()];
+    if config.ports == PortDensity::Heavy {
+        for count in &mut handshakes {
+            *count = rng.range(1, 3) as usize;
+        }
+        // Give back handshakes on the busiest links until every node fits.
+        for (_, pieces) in &grid {
+            while fixed_len(pieces, &handshakes) + config.loops * LOOP_LEN > config.instructions {
+                let link = pieces
+                    .iter()
+                    .filter_map(|piece| match *piece {
+                        Piece::Send(link) | Piece::Recv(link) => Some(link),
+                        _ => None,
+                    })
+                    .max_by_key(|&link| handshakes[link])
+                    .expect("every node has a link");
+                handshakes[link] -= 1;
+            }
+        }
+    }
+
+    // Split each node's free instructions and loops between its gaps.
+    let mut budgets = vec![(0, 0); gap_count];
+    for (_, pieces) in &grid {
+        let gaps: Vec<usize> =
+            pieces.iter().filter_map(|piece| if let Piece::Gap(gap) = *piece { Some(gap) } else { None }).collect();
+        for _ in 0..config.loops {
+            budgets[rng.pick(&gaps)].1 += 1;
+        }
+        let mut free = config.instructions - fixed_len(pieces, &handshakes) - config.loops * LOOP_LEN;
+        for (i, &gap) in gaps.iter().enumerate() {
+            let share = if i + 1 == gaps.len() { free } else { rng.range(0, free as u32) as usize };
+            budgets[gap].0 += share;
+            free -= share;
+        }
+    }
+
+    // Fill the gaps in the order the value passes them, so `offset` is
+    // what every value has gained by then.
+    let mut offset = 0;
+    let mut labels = 0;
+    let bodies: Vec<Vec<String>> =
+        budgets.iter().map(|&(filler, loops)| gap_body(rng, filler, loops, &mut offset, &mut labels)).collect();
+
+    let mut source = format!(
+        "# gen-program --nodes {} --instructions {} --loops {} --ports {} --input-count {} --seed {}\n",
+        config.nodes,
+        config.instructions,
+        config.loops,
+        if config.ports == PortDensity::Heavy { "heavy" } else { "light" },
+        config.inputs,
+        config.seed
+    );
+    for (node, pieces) in &grid {
+        source.push_str(&format!("\nNODE ({},{})\n", node.0, node.1));
+        for piece in pieces {
+            match *piece {
+                Piece::Line(line) => source.push_str(&format!("{}\n", line)),
+                Piece::Send(link) => {
+                    for _ in 0..handshakes[link] {
+                        source.push_str(&format!("MOV {}, P:{}\n", rng.range(0, 255), links[link].send));
+                    }
+                    source.push_str(&format!("MOV ACC, P:{}\n", links[link].send));
+                }
+                Piece::Recv(link) => {
+                    for _ in 0..handshakes[link] {
+                        source.push_str(&format!("MOV P:{}, NIL\n", links[link].recv));
+                    }
+                    source.push_str(&format!("MOV P:{}, ACC\n", links[link].recv));
+                }
+                Piece::Gap(gap) => {
+                    for line in &bodies[gap] {
+                        source.push_str(&format!("{}\n", line));
+                    }
+                }
+            }
+        }
+    }
+
+    let inputs: Vec<u32> = (0..config.inputs).map(|_| rng.range(0, MAX_INPUT)).collect();
+    let expected = inputs.iter().map(|input| input + offset).collect();
+    (source, inputs, expected)
+}
+
+/// `filler` single instructions and `loops` loops in a random order. Labels
+/// are numbered across the whole program, so they never collide in a node.
+fn gap_body(rng: &mut Rng, filler: usize, loops: usize, offset: &mut u32, labels: &mut usize) -> Vec<String> {
+    let mut slots = vec![false; filler];
+    for _ in 0..loops {
+        let at = rng.range(0, slots.len() as u32) as usize;
+        slots.insert(at, true);
+    }
+    let mut label = || {
+        *labels += 1;
+        format!("l{}", *labels)
+    };
+    let mut lines = Vec::new();
+    let mut slots = slots.into_iter().peekable();
+    while let Some(is_loop) = slots.next() {
+        if is_loop {
+            let (count, step) = (rng.range(1, 4), rng.range(1, 9));
+            let top = label();
+            lines.extend([
+                "SWP".to_string(),
+                format!("MOV {}, ACC", count),
+                format!("{}:", top),
+                "SWP".to_string(),
+                format!("ADD {}", step),
+                "SWP".to_string(),
+                "SUB 1".to_string(),
+                format!("JNZ {}", top),
+                "SWP".to_string(),
+            ]);
+            *offset += count * step;
+            continue;
+        }
+        // Pairs take the next filler slot too.
+        let pair = rng.range(0, 2) == 0 && slots.next_if_eq(&false).is_some();
+        if pair {
+            match rng.range(0, 2) {
+                0 => lines.extend(["NEG".to_string(), "NEG".to_string()]),
+                1 => lines.extend(["SWP".to_string(), "SWP".to_string()]),
+                _ => {
+                    let k = rng.range(1, 9);
+                    lines.extend([format!("ADD {}", k), format!("SUB {}", k)]);
+                }
+            }
+            continue;
+        }
+        match rng.range(0, 4) {
+            0 => {
+                let k = rng.range(0, 9);
+                lines.push(format!("ADD {}", k));
+                *offset += k;
+            }
+            1 if *offset > 0 => {
+                let k = rng.range(1, (*offset).min(9));
+                lines.push(format!("SUB {}", k));
+                *offset -= k;
+            }
+            2 => lines.push("SAV".to_string()),
+            3 => {
+                let next = label();
+                lines.push(format!("{} {}", rng.pick(&["JZ", "JNZ", "JGZ", "JLZ"]), next));
+                lines.push(format!("{}:", next));
+            }
+            _ => lines.push("NOP".to_string()),
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(nodes: usize, ports: PortDensity, seed: u64) -> GenConfig {
+        GenConfig { nodes, instructions: 40, loops: 2, ports, inputs: 4, seed }
+    }
+
+    #[test]
+    fn test_generated_programs_pass_their_checks() {
+        for nodes in 3..=4 {
+            for ports in [PortDensity::Light, PortDensity::Heavy] {
+                for seed in 0..20 {
+                    let generated = generate(&config(nodes, ports, seed)).unwrap();
+                    assert_eq!(generated.attempts, 1, "nodes {} {:?} seed {}", nodes, ports, seed);
+                    let programs = assembler::parse_assembly(&generated.source).unwrap();
+                    let lengths: Vec<usize> = programs.iter().flatten().map(Vec::len).filter(|&len| len > 0).collect();
+                    assert_eq!(lengths, vec![40; nodes]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generation_is_seed_deterministic() {
+        let config = config(4, PortDensity::Heavy, 9);
+        assert_eq!(generate(&config).unwrap(), generate(&config).unwrap());
+        let other = GenConfig { seed: 10, ..config.clone() };
+        assert_ne!(generate(&config).unwrap().source, generate(&other).unwrap().source);
+    }
+
+    #[test]
+    fn test_limits() {
+        let small = GenConfig { instructions: 10, ..config(4, PortDensity::Light, 0) };
+        let err = generate(&small).unwrap_err().to_string();
+        assert!(err.contains("node (0,0) needs at least 20 for 2 loops"), "{}", err);
+        let wide = GenConfig { instructions: 257, ..config(3, PortDensity::Light, 0) };
+        assert!(generate(&wide).is_err());
+        assert!(generate(&config(2, PortDensity::Light, 0)).is_err());
+    }
+}
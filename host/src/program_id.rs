@@ -0,0 +1,261 @@
+//! `ProgramId`: a short, checksummed name for a program commitment, for
+//! registries and for pasting into chat.
+//!
+//! The raw root only means something together with the commitment version
+//! and the grid it was computed over, so the ID hashes all three:
+//! SHA-256 of `"zk100-program-id" || root || commitment_version || rows ||
+//! cols` (integers as big-endian u32), truncated to 160 bits. That is written
+//! in the bech32 alphabet after a `zk100-` prefix and followed by a six
+//! character bech32 checksum over the `zk100` prefix and the data, so
+//! `zk100-` plus 38 characters in all. The checksum catches any single
+//! mistyped character and most transpositions; `parse` reports a failed
+//! checksum separately from text that is not an ID at all.
+//!
+//! Because the ID is derived from the root, it is the same for a program
+//! written as assembly, as grid JSON or as encoded words.
+
+use crate::assembler::Programs;
+use crate::merkle;
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+/// What every ID starts with.
+pub const PREFIX: &str = "zk100-";
+/// Human-readable part the checksum covers (`PREFIX` without the dash).
+const HRP: &str = "zk100";
+/// Domain separator of the digest.
+const DOMAIN: &[u8] = b"zk100-program-id";
+/// Digest bytes kept in an ID.
+pub const DIGEST_BYTES: usize = 20;
+/// Data characters: 160 bits at 5 bits each.
+const DATA_CHARS: usize = DIGEST_BYTES * 8 / 5;
+const CHECKSUM_CHARS: usize = 6;
+
+/// The bech32 alphabet: no `1`, `b`, `i` or `o`.
+pub const ALPHABET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProgramId([u8; DIGEST_BYTES]);
+
+/// Why `ProgramId::parse` rejected a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramIdError {
+    /// Not shaped like an ID: wrong prefix, length or characters.
+    Format(String),
+    /// Shaped like an ID, but a character was changed.
+    Checksum,
+}
+
+impl fmt::Display for ProgramIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgramIdError::Format(reason) => write!(f, "malformed program ID: {}", reason),
+            ProgramIdError::Checksum => write!(f, "program ID checksum does not match; it was probably mistyped"),
+        }
+    }
+}
+
+impl std::error::Error for ProgramIdError {}
+
+impl ProgramId {
+    /// The ID of `root` under `commitment_version` on a `rows`x`cols` grid.
+    pub fn new(root: &[u8], commitment_version: u32, rows: usize, cols: usize) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(DOMAIN);
+        hasher.update(root);
+        for n in [commitment_version, rows as u32, cols as u32] {
+            hasher.update(n.to_be_bytes());
+        }
+        let mut digest = [0; DIGEST_BYTES];
+        digest.copy_from_slice(&hasher.finalize()[..DIGEST_BYTES]);
+        ProgramId(digest)
+    }
+
+    /// The ID of an assembled grid under this build's commitment.
+    pub fn of_programs(programs: &Programs) -> Result<Self> {
+        let root = merkle::compute_program_merkle_root(programs)?;
+        let cols = programs.first().map_or(0, Vec::len);
+        Ok(ProgramId::new(&root, merkle::COMMITMENT_VERSION, programs.len(), cols))
+    }
+
+    pub fn digest(&self) -> &[u8; DIGEST_BYTES] {
+        &self.0
+    }
+
+    /// Read an ID in either all-lower or all-upper case.
+    pub fn parse(text: &str) -> Result<Self, ProgramIdError> {
+        let malformed = ProgramIdError::Format;
+        if text.chars().any(char::is_lowercase) && text.chars().any(char::is_uppercase) {
+            return Err(malformed("mixes upper and lower case".to_string()));
+        }
+        let text = text.to_lowercase();
+        let body = text.strip_prefix(PREFIX).ok_or_else(|| malformed(format!("does not start with '{}'", PREFIX)))?;
+        if body.len() != DATA_CHARS + CHECKSUM_CHARS {
+            return Err(malformed(format!(
+                "expected {} characters after '{}', found {}",
+                DATA_CHARS + CHECKSUM_CHARS,
+                PREFIX,
+                body.chars().count()
+            )));
+        }
+        let values = body
+            .chars()
+            .map(|c| {
+                ALPHABET
+                    .iter()
+                    .position(|&a| char::from(a) == c)
+                    .map(|v| v as u8)
+                    .ok_or_else(|| malformed(format!("'{}' is not in the ID alphabet", c)))
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        if !verify_checksum(HRP, &values) {
+            return Err(ProgramIdError::Checksum);
+        }
+        let bytes = regroup(&values[..DATA_CHARS], 5, 8);
+        let mut digest = [0; DIGEST_BYTES];
+        digest.copy_from_slice(&bytes[..DIGEST_BYTES]);
+        Ok(ProgramId(digest))
+    }
+}
+
+impl fmt::Display for ProgramId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let data = regroup(&self.0, 8, 5);
+        let checksum = create_checksum(HRP, &data);
+        let encoded: String = data.iter().chain(&checksum).map(|&v| char::from(ALPHABET[v as usize])).collect();
+        write!(f, "{}{}", PREFIX, encoded)
+    }
+}
+
+impl FromStr for ProgramId {
+    type Err = ProgramIdError;
+
+    fn from_str(text: &str) -> Result<Self, ProgramIdError> {
+        ProgramId::parse(text)
+    }
+}
+
+impl Serialize for ProgramId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ProgramId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        ProgramId::parse(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Repack `from`-bit groups into `to`-bit groups, zero-padding the last one.
+fn regroup(values: &[u8], from: u32, to: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0u32);
+    for &value in values {
+        acc = (acc << from) | u32::from(value);
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & ((1 << to) - 1)) as u8);
+        }
+    }
+    if bits > 0 {
+        out.push(((acc << (to - bits)) & ((1 << to) - 1)) as u8);
+    }
+    out
+}
+
+/// The BCH code of BIP-173.
+fn polymod(values: impl IntoIterator<Item = u8>) -> u32 {
+    const GEN: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk = 1u32;
+    for value in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ u32::from(value);
+        for (i, g) in GEN.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let bytes = hrp.as_bytes();
+    bytes.iter().map(|b| b >> 5).chain([0]).chain(bytes.iter().map(|b| b & 31)).collect()
+}
+
+fn create_checksum(hrp: &str, data: &[u8]) -> [u8; CHECKSUM_CHARS] {
+    let values = hrp_expand(hrp).into_iter().chain(data.iter().copied()).chain([0; CHECKSUM_CHARS]);
+    let modulus = polymod(values) ^ 1;
+    std::array::from_fn(|i| ((modulus >> (5 * (5 - i))) & 31) as u8)
+}
+
+fn verify_checksum(hrp: &str, values: &[u8]) -> bool {
+    polymod(hrp_expand(hrp).into_iter().chain(values.iter().copied())) == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(text: &str) -> Vec<u8> {
+        text.bytes().map(|c| ALPHABET.iter().position(|&a| a == c).unwrap() as u8).collect()
+    }
+
+    #[test]
+    fn test_bip173_vectors() {
+        // "a12uel5l": prefix "a", no data.
+        assert!(verify_checksum("a", &decode("2uel5l")));
+        assert_eq!(create_checksum("a", &[]), decode("2uel5l")[..]);
+        // The whole alphabet as data, in order.
+        let data = decode("qpzry9x8gf2tvdw0s3jn54khce6mua7l");
+        assert_eq!(data, (0..32).collect::<Vec<u8>>());
+        assert_eq!(create_checksum("abcdef", &data), decode("mqqqxw")[..]);
+        assert!(!verify_checksum("abcdef", &decode("qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxq")));
+    }
+
+    #[test]
+    fn test_id_vector_and_round_trip() {
+        let zero = ProgramId::new(&[0; 32], 1, 2, 2);
+        let text = zero.to_string();
+        assert_eq!(text, "zk100-8qhnzc2ls526pkch42kp4e3sxhnr5nkea0agd7");
+        assert_eq!(text.len(), PREFIX.len() + 38);
+        assert_eq!(ProgramId::parse(&text).unwrap(), zero);
+        assert_eq!(ProgramId::parse(&text.to_uppercase()).unwrap(), zero);
+        assert_eq!(serde_json::to_string(&zero).unwrap(), format!("\"{}\"", text));
+        assert_eq!(serde_json::from_str::<ProgramId>(&format!("\"{}\"", text)).unwrap(), zero);
+
+        // Every input of the digest changes the ID.
+        assert_ne!(ProgramId::new(&[0; 32], 2, 2, 2), zero);
+        assert_ne!(ProgramId::new(&[0; 32], 1, 3, 2), zero);
+        assert_ne!(ProgramId::new(&[1; 32], 1, 2, 2), zero);
+    }
+
+    #[test]
+    fn test_checksum_and_format_errors_differ() {
+        let text = ProgramId::new(&[7; 32], 1, 2, 2).to_string();
+        // Any one changed character fails the checksum, not the format.
+        for i in PREFIX.len()..text.len() {
+            let mut typo = text.clone().into_bytes();
+            typo[i] = if typo[i] == b'q' { b'p' } else { b'q' };
+            let typo = String::from_utf8(typo).unwrap();
+            assert_eq!(ProgramId::parse(&typo), Err(ProgramIdError::Checksum), "{}", typo);
+        }
+        let malformed = |text: &str| match ProgramId::parse(text) {
+            Err(ProgramIdError::Format(reason)) => reason,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(malformed(&text[1..]), "does not start with 'zk100-'");
+        assert_eq!(malformed(&text[..text.len() - 1]), "expected 38 characters after 'zk100-', found 37");
+        let mut foreign = text.clone();
+        foreign.replace_range(10..11, "b");
+        assert_eq!(malformed(&foreign), "'b' is not in the ID alphabet");
+        assert_eq!(malformed(&text.replacen("zk100-", "ZK100-", 1)), "mixes upper and lower case");
+    }
+}
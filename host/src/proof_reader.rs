@@ -0,0 +1,239 @@
+//! The guest's public output, read out of a `cairo-prove` proof.
+//!
+//! Where the proof JSON keeps that output has moved between the cairo-prove
+//! versions we use:
+//!
+//! - `public_input.output` – felts at the top level;
+//! - `claim.public_input.output` – the same, nested under `claim`;
+//! - `claim.public_data.public_memory.output` – `[id, [limb; 8]]` pairs, each
+//!   felt as eight little-endian `u32` limbs.
+//!
+//! Felts come as hex strings, decimal strings or JSON numbers. `read` sniffs
+//! the layout, so no command looks at the proof schema itself.
+//!
+//! `from_program_output` reads the same felts from what `scarb execute
+//! --print-program-output` prints, for runs that execute the guest without
+//! proving it.
+
+use crate::felt_bytes;
+use anyhow::{Result, anyhow};
+use num_bigint::BigUint;
+use num_traits::{ToPrimitive, Zero};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// Felts in the guest's serialized `PublicOutputs`.
+pub const PUBLIC_OUTPUT_LEN: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schema {
+    TopLevel,
+    Claim,
+    PublicMemory,
+    /// Printed by `scarb execute`, not read from a proof.
+    ProgramOutput,
+}
+
+impl Schema {
+    pub fn path(self) -> &'static str {
+        match self {
+            Schema::TopLevel => "public_input.output",
+            Schema::Claim => "claim.public_input.output",
+            Schema::PublicMemory => "claim.public_data.public_memory.output",
+            Schema::ProgramOutput => "Program output",
+        }
+    }
+}
+
+/// The guest's `PublicOutputs`; commitments are `0x`-hex without leading zeros.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProofPublicData {
+    pub schema: Schema,
+    /// `program_commit`, the Merkle root of the proved programs.
+    pub program_root: String,
+    /// `challenge_commit` over the inputs and expected outputs.
+    pub inputs: String,
+    /// `output_commit` over what (1,1) wrote to OUT.
+    pub outputs: String,
+    pub cycles: u64,
+    pub msgs: u64,
+    pub nodes_used: u64,
+    pub solved: bool,
+    /// The proof as read.
+    #[serde(skip)]
+    pub raw: Value,
+}
+
+impl ProofPublicData {
+    /// Whether the proof commits to `root` (any `0x`-hex form).
+    pub fn commits_to(&self, root: &str) -> Result<bool> {
+        Ok(parse_felt(&Value::String(root.to_string()))? == parse_felt(&Value::String(self.program_root.clone()))?)
+    }
+}
+
+impl fmt::Display for ProofPublicData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Program root: {}", self.program_root)?;
+        writeln!(f, "Challenge commitment: {}", self.inputs)?;
+        writeln!(f, "Output commitment: {}", self.outputs)?;
+        writeln!(f, "Score: {} cycles, {} messages, {} nodes", self.cycles, self.msgs, self.nodes_used)?;
+        write!(f, "Solved: {}", if self.solved { "yes" } else { "no" })
+    }
+}
+
+/// Parse the public output of a proof given as JSON text.
+pub fn read(proof_json: &str) -> Result<ProofPublicData> {
+    let raw: Value = serde_json::from_str(proof_json).map_err(|e| anyhow!("proof is not JSON: {}", e))?;
+    from_value(raw)
+}
+
+pub fn from_value(raw: Value) -> Result<ProofPublicData> {
+    let (schema, felts) = sniff(&raw)?;
+    decode(schema, felts, raw)
+}
+
+/// Parse the felts printed after `Program output:`, one per line; `raw`
+/// holds them as strings.
+pub fn from_program_output(stdout: &str) -> Result<ProofPublicData> {
+    let header = format!("{}:", Schema::ProgramOutput.path());
+    let mut lines = stdout.lines().map(str::trim).skip_while(|line| *line != header);
+    if lines.next().is_none() {
+        return Err(anyhow!("no `{}` line in the guest's output", header));
+    }
+    let printed: Vec<Value> =
+        lines.take_while(|line| parse_felt(&Value::from(*line)).is_ok()).map(Value::from).collect();
+    let felts = printed.iter().map(parse_felt).collect::<Result<_>>()?;
+    decode(Schema::ProgramOutput, felts, Value::Array(printed))
+}
+
+fn decode(schema: Schema, felts: Vec<BigUint>, raw: Value) -> Result<ProofPublicData> {
+    let felts = strip_framing(felts)?;
+    let hex = felt_bytes::to_hex;
+    let small = |i: usize, name: &str| {
+        felts[i].to_u64().ok_or_else(|| anyhow!("public output {} ({}) does not fit in 64 bits", i, name))
+    };
+    let solved = match small(6, "solved")? {
+        0 => false,
+        1 => true,
+        other => return Err(anyhow!("public output 6 (solved) is {}, expected 0 or 1", other)),
+    };
+    Ok(ProofPublicData {
+        schema,
+        inputs: hex(&felts[0]),
+        program_root: hex(&felts[1]),
+        outputs: hex(&felts[2]),
+        cycles: small(3, "cycles")?,
+        msgs: small(4, "msgs")?,
+        nodes_used: small(5, "nodes_used")?,
+        solved,
+        raw,
+    })
+}
+
+fn sniff(raw: &Value) -> Result<(Schema, Vec<BigUint>)> {
+    let at = |path: &[&str]| path.iter().try_fold(raw, |v, key| v.get(key));
+    let schema = if at(&["public_input", "output"]).is_some() {
+        Schema::TopLevel
+    } else if at(&["claim", "public_input", "output"]).is_some() {
+        Schema::Claim
+    } else if at(&["claim", "public_data", "public_memory", "output"]).is_some() {
+        Schema::PublicMemory
+    } else {
+        let keys: Vec<&str> = raw.as_object().map_or(Vec::new(), |o| o.keys().map(String::as_str).collect());
+        return Err(anyhow!("unrecognized proof schema; found top-level keys: [{}]", keys.join(", ")));
+    };
+    let path: Vec<&str> = schema.path().split('.').collect();
+    let entries = at(&path)
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("{} is not an array", schema.path()))?;
+    let felts = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let felt = match schema {
+                Schema::PublicMemory => parse_limbs(entry),
+                Schema::TopLevel | Schema::Claim | Schema::ProgramOutput => parse_felt(entry),
+            };
+            felt.map_err(|e| anyhow!("{}[{}]: {}", schema.path(), i, e))
+        })
+        .collect::<Result<_>>()?;
+    Ok((schema, felts))
+}
+
+/// Drop the executable's framing around the returned array: a length
+/// prefix, and before that the panic flag.
+fn strip_framing(felts: Vec<BigUint>) -> Result<Vec<BigUint>> {
+    let len = BigUint::from(PUBLIC_OUTPUT_LEN);
+    let skip = match felts.len() {
+        n if n == PUBLIC_OUTPUT_LEN => 0,
+        n if n == PUBLIC_OUTPUT_LEN + 1 && felts[0] == len => 1,
+        n if n == PUBLIC_OUTPUT_LEN + 2 && felts[0].is_zero() && felts[1] == len => 2,
+        n => return Err(anyhow!("public output has {} felts, expected the guest's {}", n, PUBLIC_OUTPUT_LEN)),
+    };
+    Ok(felts.into_iter().skip(skip).collect())
+}
+
+fn parse_felt(value: &Value) -> Result<BigUint> {
+    let parsed = match value {
+        Value::Number(n) => n.as_u64().map(BigUint::from),
+        Value::String(s) => match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(digits) => BigUint::parse_bytes(digits.as_bytes(), 16),
+            None => BigUint::parse_bytes(s.as_bytes(), 10),
+        },
+        _ => None,
+    };
+    parsed.ok_or_else(|| anyhow!("{} is not a felt", value))
+}
+
+fn parse_limbs(entry: &Value) -> Result<BigUint> {
+    let limbs = entry
+        .get(1)
+        .and_then(Value::as_array)
+        .filter(|limbs| limbs.len() == 8)
+        .ok_or_else(|| anyhow!("expected [id, [limb; 8]], found {}", entry))?;
+    let limbs = limbs
+        .iter()
+        .map(|l| l.as_u64().and_then(|l| u32::try_from(l).ok()).ok_or_else(|| anyhow!("limb {} is not a u32", l)))
+        .collect::<Result<Vec<u32>>>()?;
+    Ok(BigUint::from_slice(&limbs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_felt_forms() {
+        let expected = BigUint::from(255u32);
+        for value in [Value::from(255), Value::from("255"), Value::from("0xff"), Value::from("0x00FF")] {
+            assert_eq!(parse_felt(&value).unwrap(), expected, "{}", value);
+        }
+        assert!(parse_felt(&Value::from("ff")).is_err());
+        assert!(parse_felt(&Value::from(-1)).is_err());
+    }
+
+    #[test]
+    fn test_limbs_are_little_endian() {
+        let entry = serde_json::json!([3, [1, 2, 0, 0, 0, 0, 0, 0]]);
+        assert_eq!(parse_limbs(&entry).unwrap(), (BigUint::from(2u32) << 32) + 1u32);
+        assert!(parse_limbs(&serde_json::json!([3, [1, 2]])).is_err());
+    }
+
+    #[test]
+    fn test_unrecognized_schema_lists_keys() {
+        let err = read(r#"{"stark_proof": {}, "version": 4}"#).unwrap_err().to_string();
+        assert_eq!(err, "unrecognized proof schema; found top-level keys: [stark_proof, version]");
+    }
+
+    #[test]
+    fn test_program_output() {
+        let stdout = "   Executing zk100_exec\nProgram output:\n0\n7\n1\n2\n3\n12\n3\n3\n1\nSaving output to: target/execute\n";
+        let public = from_program_output(stdout).unwrap();
+        assert_eq!((public.schema, public.program_root.as_str(), public.cycles, public.solved), (Schema::ProgramOutput, "0x2", 12, true));
+        assert_eq!(public.raw.as_array().unwrap().len(), 9);
+        let err = from_program_output("error: package not found\n").unwrap_err().to_string();
+        assert_eq!(err, "no `Program output:` line in the guest's output");
+    }
+}
@@ -0,0 +1,480 @@
+//! Proving queue behind `zk100 queue run`.
+//!
+//! A jobs directory holds one manifest per job, `<name>.json`:
+//!
+//! ```json
+//! { "program": "adder.asm",
+//!   "cases": [{ "inputs": [1, 2], "expected": [3] }],
+//!   "options": { "simulate": true, "max_cycles": 10000 } }
+//! ```
+//!
+//! `program` is relative to the jobs directory. A single case is proved with
+//! `LEGACY_ABI` args (`IO_ABI` when `options.io` moves the IN or OUT node),
+//! several cases as one `BATCH_ABI` batch.
+//!
+//! A run validates every pending job first (assemble, generate args and,
+//! unless `simulate` is off, check each case in the simulator), so cheap
+//! failures never wait behind a prover. Valid jobs are then proved with at
+//! most `concurrency` `cairo-prove` processes, each bounded by the job
+//! timeout. Each job leaves `results/<name>.json` (a `JobResult`), its args,
+//! proof and prover log in `results/`, and its manifest moves to `done/` or
+//! `failed/`.
+//!
+//! Before proving, `state/<name>.json` records the runner's pid and the
+//! attempt number; it is removed once the job is finished. A state file left
+//! by a runner that is gone means the job was interrupted mid-prove: it is
+//! retried, or failed once `max_attempts` is used up. A state file whose
+//! runner is still alive means another runner has the job, and it is skipped.
+
+use crate::cairo_abi::{Batch, CairoArgs, IoNodes};
+use crate::exec::{self, Child, PendingFile, Progress};
+use crate::inputs::Inputs;
+use crate::sim::{self, DEFAULT_MAX_CYCLES};
+use crate::{assembler, merkle, source};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const DONE_DIR: &str = "done";
+pub const FAILED_DIR: &str = "failed";
+pub const RESULTS_DIR: &str = "results";
+pub const STATE_DIR: &str = "state";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobManifest {
+    pub program: PathBuf,
+    pub cases: Vec<JobCase>,
+    #[serde(default)]
+    pub options: JobOptions,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobCase {
+    /// Values, or a generator such as `{"range": [1, 100]}`.
+    #[serde(default)]
+    pub inputs: Inputs,
+    #[serde(default)]
+    pub expected: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobOptions {
+    /// Check every case in the simulator before proving.
+    pub simulate: bool,
+    pub max_cycles: Option<u64>,
+    #[serde(skip_serializing_if = "IoNodes::is_default")]
+    pub io: IoNodes,
+}
+
+impl Default for JobOptions {
+    fn default() -> Self {
+        JobOptions { simulate: true, max_cycles: None, io: IoNodes::default() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Succeeded,
+    /// Rejected during validation; the prover never ran.
+    Invalid,
+    Failed,
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timings {
+    pub validate_ms: u64,
+    pub prove_ms: Option<u64>,
+}
+
+/// Written to `results/<name>.json`. Paths are relative to the jobs directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobResult {
+    pub job: String,
+    pub status: JobStatus,
+    pub attempt: u32,
+    pub program_root: Option<String>,
+    pub args: Option<PathBuf>,
+    pub proof: Option<PathBuf>,
+    pub log: Option<PathBuf>,
+    pub failure: Option<String>,
+    pub timings: Timings,
+    pub finished_unix: u64,
+}
+
+impl fmt::Display for JobResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let failure = self.failure.as_deref().unwrap_or("");
+        match self.status {
+            JobStatus::Succeeded => {
+                let proof = self.proof.as_deref().unwrap_or(Path::new(""));
+                write!(f, "ok        {} -> {}", self.job, proof.display())
+            }
+            JobStatus::Invalid => write!(f, "INVALID   {}: {}", self.job, failure),
+            JobStatus::Failed => write!(f, "FAILED    {}: {}", self.job, failure),
+            JobStatus::TimedOut => write!(f, "TIMEOUT   {}: {}", self.job, failure),
+        }
+    }
+}
+
+/// Written to `state/<name>.json` while the job is being proved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct JobState {
+    pid: u32,
+    attempt: u32,
+    started_unix: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    pub guest: PathBuf,
+    pub concurrency: usize,
+    pub job_timeout: Option<Duration>,
+    /// Attempts a job gets before an interrupted prove marks it failed.
+    pub max_attempts: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub results: Vec<JobResult>,
+    /// Jobs held by another live runner, with the reason.
+    pub skipped: Vec<(String, String)>,
+}
+
+impl Summary {
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|r| r.status == JobStatus::Succeeded).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (job, reason) in &self.skipped {
+            writeln!(f, "skipped   {}: {}", job, reason)?;
+        }
+        write!(
+            f,
+            "{} jobs: {} succeeded, {} failed, {} skipped",
+            self.results.len() + self.skipped.len(),
+            self.succeeded(),
+            self.failed(),
+            self.skipped.len()
+        )
+    }
+}
+
+/// Parse `500ms`, `90s`, `30m` or `2h`; a bare number is seconds.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let n: u64 = number.parse().map_err(|_| anyhow!("invalid duration '{}'", s))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(n)),
+        "" | "s" => Ok(Duration::from_secs(n)),
+        "m" => Ok(Duration::from_secs(n * 60)),
+        "h" => Ok(Duration::from_secs(n * 3600)),
+        _ => Err(anyhow!("invalid duration '{}' (use ms, s, m or h)", s)),
+    }
+}
+
+/// The `cairo-prove` invocation shared by `zk100 prove` and the queue.
+pub fn prove_command(guest: &Path, proof: &Path, args: &Path) -> Command {
+    let mut cmd = Command::new("cairo-prove");
+    cmd.arg("prove").arg(guest).arg(proof).arg("--arguments-file").arg(args);
+    cmd
+}
+
+fn now_unix() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn millis(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+struct Job {
+    name: String,
+    manifest: PathBuf,
+    attempt: u32,
+}
+
+impl Job {
+    fn result(&self, status: JobStatus, failure: Option<String>) -> JobResult {
+        JobResult {
+            job: self.name.clone(),
+            status,
+            attempt: self.attempt,
+            program_root: None,
+            args: None,
+            proof: None,
+            log: None,
+            failure,
+            timings: Timings::default(),
+            finished_unix: 0,
+        }
+    }
+}
+
+struct Queue<'a> {
+    dir: &'a Path,
+    config: &'a QueueConfig,
+}
+
+impl Queue<'_> {
+    fn path(&self, sub: &str, name: &str, suffix: &str) -> PathBuf {
+        self.dir.join(sub).join(format!("{}{}", name, suffix))
+    }
+
+    fn relative(&self, sub: &str, name: &str, suffix: &str) -> PathBuf {
+        Path::new(sub).join(format!("{}{}", name, suffix))
+    }
+
+    /// Assemble the job and write its args; returns the result to fill in.
+    fn validate(&self, job: &Job) -> Result<JobResult> {
+        let text = fs::read_to_string(&job.manifest)?;
+        let manifest: JobManifest = serde_json::from_str(&text).map_err(|e| anyhow!("malformed manifest: {}", e))?;
+        let program = self.dir.join(&manifest.program);
+        let source = source::read(&program)?;
+        let programs = assembler::parse_assembly(&source)?;
+        let prog_words = assembler::encode_programs(&programs)?;
+        let io = manifest.options.io;
+        io.validate()?;
+
+        if manifest.options.simulate {
+            let max_cycles = manifest.options.max_cycles.unwrap_or(DEFAULT_MAX_CYCLES);
+            for (i, case) in manifest.cases.iter().enumerate() {
+                let run = sim::simulate_io(&programs, &case.inputs.expand(self.dir)?, io, max_cycles)?;
+                if !run.matches(&case.expected) {
+                    return Err(anyhow!(
+                        "case {}: simulator outputs {:?}, expected {:?}",
+                        i, run.outputs, case.expected
+                    ));
+                }
+            }
+        }
+
+        let singles = manifest
+            .cases
+            .iter()
+            .map(|case| {
+                Ok(CairoArgs {
+                    inputs: case.inputs.expand(self.dir)?,
+                    expected: case.expected.clone(),
+                    prog_words: prog_words.clone(),
+                    io,
+                    ..Default::default()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let args = match singles.as_slice() {
+            [] => return Err(anyhow!("job has no cases")),
+            [single] => single.to_args(io.single_case_abi())?,
+            _ => Batch::merge(&singles)?.to_args(),
+        };
+        exec::write_atomic(&self.path(RESULTS_DIR, &job.name, ".args.json"), serde_json::to_string(&args)?)?;
+
+        let mut result = job.result(JobStatus::Succeeded, None);
+        result.program_root = Some(merkle::program_root_hex(&programs)?);
+        result.args = Some(self.relative(RESULTS_DIR, &job.name, ".args.json"));
+        Ok(result)
+    }
+
+    /// Run the prover for a validated job. Only `Interrupted` is an error;
+    /// every other outcome is recorded in `result`.
+    fn prove(&self, job: &Job, result: &mut JobResult) -> Result<()> {
+        let state = JobState { pid: std::process::id(), attempt: job.attempt, started_unix: now_unix() };
+        exec::write_atomic(&self.path(STATE_DIR, &job.name, ".json"), serde_json::to_string(&state)?)?;
+
+        let started = Instant::now();
+        let proof = PendingFile::new(&self.path(RESULTS_DIR, &job.name, ".proof.json"));
+        let log_path = self.path(RESULTS_DIR, &job.name, ".prove.log");
+        result.log = Some(self.relative(RESULTS_DIR, &job.name, ".prove.log"));
+        let outcome = (|| -> Result<Option<std::process::ExitStatus>> {
+            let log = File::create(&log_path)?;
+            let mut cmd = prove_command(&self.config.guest, proof.path(), &self.path(RESULTS_DIR, &job.name, ".args.json"));
+            cmd.stdin(Stdio::null()).stdout(log.try_clone()?).stderr(log);
+            let deadline = self.config.job_timeout.map(|t| Instant::now() + t);
+            Child::spawn(&mut cmd)?.wait_until(&mut Progress::hidden(), deadline)
+        })();
+        result.timings.prove_ms = Some(millis(started));
+
+        match outcome {
+            Err(e) if e.is::<exec::Interrupted>() => return Err(e),
+            Err(e) => {
+                result.status = JobStatus::Failed;
+                result.failure = Some(e.to_string());
+            }
+            Ok(None) => {
+                result.status = JobStatus::TimedOut;
+                let timeout = self.config.job_timeout.unwrap_or_default();
+                result.failure = Some(format!("cairo-prove still running after {:?}", timeout));
+            }
+            Ok(Some(status)) if !status.success() => {
+                result.status = JobStatus::Failed;
+                let log = fs::read_to_string(&log_path).unwrap_or_default();
+                let last = log.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("no output");
+                result.failure = Some(format!("cairo-prove failed ({}): {}", status, last.trim()));
+            }
+            Ok(Some(_)) => match proof.commit() {
+                Ok(()) => result.proof = Some(self.relative(RESULTS_DIR, &job.name, ".proof.json")),
+                Err(e) => {
+                    result.status = JobStatus::Failed;
+                    result.failure = Some(format!("cairo-prove wrote no proof: {}", e));
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Record the result, move the manifest and drop the state file, in that
+    /// order, so a crash in between only ever causes a retry.
+    fn finish(&self, job: &Job, mut result: JobResult) -> Result<JobResult> {
+        result.finished_unix = now_unix();
+        exec::write_atomic(
+            &self.path(RESULTS_DIR, &job.name, ".json"),
+            serde_json::to_string_pretty(&result)? + "\n",
+        )?;
+        let dest = if result.status == JobStatus::Succeeded { DONE_DIR } else { FAILED_DIR };
+        fs::rename(&job.manifest, self.path(dest, &job.name, ".json"))
+            .map_err(|e| anyhow!("cannot move {}: {}", job.manifest.display(), e))?;
+        let state = self.path(STATE_DIR, &job.name, ".json");
+        if state.exists() {
+            fs::remove_file(state)?;
+        }
+        Ok(result)
+    }
+}
+
+/// Process every manifest in `dir`. `on_finished` is called as each job
+/// completes. Fails with `exec::Interrupted` on SIGINT/SIGTERM, leaving the
+/// state files of jobs that were mid-prove for the next run.
+pub fn run(dir: &Path, config: &QueueConfig, on_finished: &(dyn Fn(&JobResult) + Sync)) -> Result<Summary> {
+    for sub in [DONE_DIR, FAILED_DIR, RESULTS_DIR, STATE_DIR] {
+        fs::create_dir_all(dir.join(sub))?;
+    }
+    let queue = Queue { dir, config };
+    let mut manifests: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| anyhow!("cannot read {}: {}", dir.display(), e))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    manifests.retain(|p| p.is_file() && p.extension().is_some_and(|e| e == "json"));
+    manifests.sort();
+
+    let mut summary = Summary::default();
+    let finished = |result: JobResult, summary: &mut Summary| {
+        on_finished(&result);
+        summary.results.push(result);
+    };
+
+    // Recover state and validate everything before the first prover starts.
+    let mut ready = VecDeque::new();
+    for manifest in manifests {
+        let name = manifest.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let mut job = Job { name, manifest, attempt: 1 };
+        let state_path = queue.path(STATE_DIR, &job.name, ".json");
+        if let Ok(text) = fs::read_to_string(&state_path) {
+            let state: Option<JobState> = serde_json::from_str(&text).ok();
+            if let Some(state) = &state {
+                if state.pid != std::process::id() && exec::process_alive(state.pid) {
+                    summary.skipped.push((job.name, format!("being proved by runner pid {}", state.pid)));
+                    continue;
+                }
+            }
+            let previous = state.map_or(1, |s| s.attempt);
+            job.attempt = previous + 1;
+            if previous >= config.max_attempts {
+                job.attempt = previous;
+                let failure = format!("interrupted while proving on {} of {} attempts", previous, config.max_attempts);
+                finished(queue.finish(&job, job.result(JobStatus::Failed, Some(failure)))?, &mut summary);
+                continue;
+            }
+        }
+
+        let started = Instant::now();
+        match queue.validate(&job) {
+            Ok(mut result) => {
+                result.timings.validate_ms = millis(started);
+                ready.push_back((job, result));
+            }
+            Err(e) => {
+                let mut result = job.result(JobStatus::Invalid, Some(e.to_string()));
+                result.timings.validate_ms = millis(started);
+                finished(queue.finish(&job, result)?, &mut summary);
+            }
+        }
+    }
+
+    let ready = Mutex::new(ready);
+    let done = Mutex::new(Vec::new());
+    let workers = config.concurrency.max(1);
+    let errors: Vec<anyhow::Error> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    loop {
+                        let next = ready.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+                        let Some((job, mut result)) = next else {
+                            return Ok(());
+                        };
+                        queue.prove(&job, &mut result)?;
+                        let result = queue.finish(&job, result)?;
+                        on_finished(&result);
+                        done.lock().unwrap_or_else(|e| e.into_inner()).push(result);
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|h| h.join().unwrap_or_else(|_| Err(anyhow!("queue worker panicked"))).err())
+            .collect()
+    });
+
+    let mut proved = done.into_inner().unwrap_or_else(|e| e.into_inner());
+    proved.sort_by(|a, b| a.job.cmp(&b.job));
+    summary.results.extend(proved);
+    // An interruption outranks any other worker error.
+    let mut errors = errors.into_iter();
+    match errors.next() {
+        None => Ok(summary),
+        Some(first) => Err(errors.find(|e| e.is::<exec::Interrupted>()).unwrap_or(first)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("5d").is_err());
+    }
+
+    #[test]
+    fn test_manifest_defaults() {
+        let manifest: JobManifest = serde_json::from_str(r#"{"program": "a.asm", "cases": [{"inputs": [1]}]}"#).unwrap();
+        assert!(manifest.options.simulate);
+        assert_eq!(manifest.cases[0].expected, Vec::<u32>::new());
+        assert!(serde_json::from_str::<JobManifest>(r#"{"program": "a.asm", "cases": [], "retries": 2}"#).is_err());
+    }
+}
@@ -0,0 +1,528 @@
+//! Self-contained HTML (and JSON) reports behind `zk100 report`.
+//!
+//! A report is described by a small TOML manifest:
+//!
+//! ```toml
+//! [puzzle]
+//! name = "doubler"
+//! program = "doubler.asm"
+//! proof = "proof.json"   # optional
+//! prove_ms = 5120        # optional, how long proving took
+//! in_node = "0,0"        # optional, the node reading IN (default 0,0)
+//! out_node = "1,1"       # optional, the node whose OUT is checked (default 1,1)
+//!
+//! [[case]]
+//! inputs = [1, 2, 3]            # or a generator: { range = [1, 3] }
+//! expected = [2, 4, 6]         # or a pattern: [2, { min = 3, max = 5 }, "*", "len >= 3"]
+//! ```
+//!
+//! Paths are relative to the manifest. `build` gathers everything into a
+//! `Report` (the JSON form) and `render_html` renders that same data into one
+//! HTML page with inline styles and no external assets. Like `scarb`, the
+//! manifest is read with a line-based parser covering only the keys above.
+
+use crate::cairo_abi::{self, Batch, CairoArgs, IoNodes};
+use crate::expected::{ExpectedSpec, Mismatch};
+use crate::inputs::{InputSpec, Inputs};
+use crate::program_id::ProgramId;
+use crate::proof_reader::{self, ProofPublicData};
+use crate::scarb::{parse_string, strip_comment};
+use crate::sim::{DEFAULT_MAX_CYCLES, Simulator, Termination};
+use crate::{assembler, attest, exec, merkle, source};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReportCase {
+    pub inputs: Inputs,
+    pub expected: ExpectedSpec,
+}
+
+/// The fields of a report manifest this module understands.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PuzzleManifest {
+    pub name: String,
+    pub program: PathBuf,
+    pub proof: Option<PathBuf>,
+    pub prove_ms: Option<u64>,
+    pub max_cycles: Option<u64>,
+    pub io: IoNodes,
+    pub cases: Vec<ReportCase>,
+}
+
+impl PuzzleManifest {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut manifest = PuzzleManifest::default();
+        let mut section = String::new();
+        for (idx, raw) in text.lines().enumerate() {
+            let line = strip_comment(raw).trim();
+            let at = |e: anyhow::Error| anyhow!("line {}: {}", idx + 1, e);
+            if line.is_empty() {
+                continue;
+            }
+            if line == "[[case]]" {
+                section = "case".to_string();
+                manifest.cases.push(ReportCase::default());
+                continue;
+            }
+            if line.starts_with('[') {
+                section = line.trim_matches(|c| c == '[' || c == ']').trim().to_string();
+                if section != "puzzle" {
+                    return Err(at(anyhow!("unknown section [{}]", section)));
+                }
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| at(anyhow!("expected key = value, found {}", line)))?;
+            match (section.as_str(), key) {
+                ("puzzle", "name") => manifest.name = parse_string(value).map_err(at)?,
+                ("puzzle", "program") => manifest.program = parse_string(value).map_err(at)?.into(),
+                ("puzzle", "proof") => manifest.proof = Some(parse_string(value).map_err(at)?.into()),
+                ("puzzle", "prove_ms") => manifest.prove_ms = Some(parse_integer(value).map_err(at)?),
+                ("puzzle", "max_cycles") => manifest.max_cycles = Some(parse_integer(value).map_err(at)?),
+                ("puzzle", "in_node") => manifest.io.in_node = parse_node(value).map_err(at)?,
+                ("puzzle", "out_node") => manifest.io.out_node = parse_node(value).map_err(at)?,
+                ("case", "inputs") => {
+                    let inputs = if value.starts_with('{') {
+                        Inputs::Spec(InputSpec::parse(value).map_err(at)?)
+                    } else {
+                        Inputs::Values(parse_u32_array(value).map_err(at)?)
+                    };
+                    manifest.cases.last_mut().expect("case section has a case").inputs = inputs;
+                }
+                ("case", "expected") => {
+                    let spec = ExpectedSpec::parse_toml(value).map_err(at)?;
+                    manifest.cases.last_mut().expect("case section has a case").expected = spec;
+                }
+                ("", _) => return Err(at(anyhow!("{} outside any section", key))),
+                _ => return Err(at(anyhow!("unknown key {}.{}", section, key))),
+            }
+        }
+        if manifest.program.as_os_str().is_empty() {
+            return Err(anyhow!("[puzzle] program is required"));
+        }
+        if manifest.cases.is_empty() {
+            return Err(anyhow!("at least one [[case]] is required"));
+        }
+        manifest.io.validate()?;
+        Ok(manifest)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).map_err(|e| anyhow!("failed to read {}: {}", path.display(), e))?;
+        exec::note_read(path);
+        PuzzleManifest::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+}
+
+fn parse_integer(value: &str) -> Result<u64> {
+    value.replace('_', "").parse().map_err(|_| anyhow!("expected an integer, found {}", value))
+}
+
+fn parse_node(value: &str) -> Result<(usize, usize)> {
+    assembler::parse_node_coords(&parse_string(value)?)
+}
+
+fn parse_u32_array(value: &str) -> Result<Vec<u32>> {
+    let inner = value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or_else(|| anyhow!("expected an array on one line, found {}", value))?;
+    inner
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|_| anyhow!("{} is not a u32 value", s)))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceLine {
+    pub line: usize,
+    pub text: String,
+    /// Node, pc and instruction word, for lines holding an instruction. An
+    /// `ASSERT` line has the pc it guards and no word.
+    pub node: Option<(usize, usize)>,
+    pub pc: Option<usize>,
+    pub word: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NodeSummary {
+    pub row: usize,
+    pub col: usize,
+    pub instructions: usize,
+    pub words: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CaseReport {
+    pub inputs: Vec<u32>,
+    /// The generator `inputs` were expanded from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs_gen: Option<InputSpec>,
+    pub expected: ExpectedSpec,
+    pub outputs: Vec<u32>,
+    pub passed: bool,
+    /// Why the outputs failed `expected`, position by position.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<String>,
+    pub cycles: u64,
+    pub msgs: u64,
+    pub nodes_used: u32,
+    pub termination: Termination,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Commitments {
+    pub program_root: String,
+    pub program_id: ProgramId,
+    /// SHA-256 of the source after `attest::normalize_source`.
+    pub source_sha256: String,
+    pub encoding_version: u32,
+    pub commitment_version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArgsSummary {
+    /// `LEGACY_ABI` (or `IO_ABI` with designated nodes) for one case,
+    /// `BATCH_ABI` for several.
+    pub abi: u32,
+    pub values: usize,
+    pub prog_words: usize,
+    pub bytes: usize,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProofSummary {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub prove_ms: Option<u64>,
+    pub public: Option<ProofPublicData>,
+    /// Why `public` is missing, when the proof could not be read.
+    pub public_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub name: String,
+    pub tool: String,
+    pub version: String,
+    pub source_file: String,
+    pub source: Vec<SourceLine>,
+    pub grid: Vec<NodeSummary>,
+    pub cases: Vec<CaseReport>,
+    pub passed: usize,
+    pub commitments: Commitments,
+    pub args: ArgsSummary,
+    pub proof: Option<ProofSummary>,
+}
+
+/// Assemble, simulate and summarize the puzzle described by `manifest_path`.
+/// The program's `ASSERT`s are checked unless `ignore_asserts` is set.
+pub fn build(manifest_path: &Path, ignore_asserts: bool) -> Result<Report> {
+    let manifest = PuzzleManifest::load(manifest_path)?;
+    let base = manifest_path.parent().unwrap_or(Path::new(""));
+    let program_path = base.join(&manifest.program);
+    let source = source::read(&program_path)?;
+    let parsed = assembler::parse(&source)?;
+    let programs = &parsed.grid.programs;
+    let prog_words = assembler::encode_programs(programs)?;
+
+    let mut at_line = BTreeMap::new();
+    for (&node, lines) in &parsed.lines {
+        for (pc, &line) in lines.iter().enumerate() {
+            at_line.insert(line, (node, pc, Some(programs[node.0][node.1][pc].encode())));
+        }
+    }
+    for (&node, asserts) in &parsed.asserts {
+        for (&pc, asserts) in asserts {
+            for assert in asserts {
+                at_line.insert(assert.line, (node, pc, None));
+            }
+        }
+    }
+    let source_lines = source
+        .lines()
+        .enumerate()
+        .map(|(idx, text)| {
+            let found = at_line.get(&(idx + 1));
+            SourceLine {
+                line: idx + 1,
+                text: text.to_string(),
+                node: found.map(|&(node, _, _)| node),
+                pc: found.map(|&(_, pc, _)| pc),
+                word: found.and_then(|&(_, _, word)| word).map(|word| format!("0x{:08x}", word)),
+            }
+        })
+        .collect();
+    let grid = programs
+        .iter()
+        .enumerate()
+        .flat_map(|(row, nodes)| {
+            nodes.iter().enumerate().map(move |(col, program)| NodeSummary {
+                row,
+                col,
+                instructions: program.len(),
+                words: program.iter().map(|inst| format!("0x{:08x}", inst.encode())).collect(),
+            })
+        })
+        .collect();
+
+    let max_cycles = manifest.max_cycles.unwrap_or(DEFAULT_MAX_CYCLES);
+    let mut cases = Vec::new();
+    for case in &manifest.cases {
+        let inputs = case.inputs.expand(base)?;
+        let asserts = if ignore_asserts { Default::default() } else { parsed.asserts.clone() };
+        let run = Simulator::with_io(programs, &inputs, manifest.io)?.with_asserts(asserts).run_to_result(max_cycles)?;
+        cases.push(CaseReport {
+            inputs,
+            inputs_gen: case.inputs.spec().cloned(),
+            expected: case.expected.clone(),
+            passed: case.expected.matches(&run.outputs),
+            failures: case.expected.check(&run.outputs).iter().map(Mismatch::to_string).collect(),
+            outputs: run.outputs,
+            cycles: run.cycles,
+            msgs: run.msgs,
+            nodes_used: run.nodes_used,
+            termination: run.termination,
+        });
+    }
+
+    let singles: Vec<CairoArgs> = cases
+        .iter()
+        .map(|case| CairoArgs {
+            inputs: case.inputs.clone(),
+            // A pattern is summarized with the values the simulator produced.
+            expected: case.expected.exact().unwrap_or_else(|| case.outputs.clone()),
+            prog_words: prog_words.clone(),
+            io: manifest.io,
+            ..Default::default()
+        })
+        .collect();
+    let (abi, args) = match singles.as_slice() {
+        [single] => (manifest.io.single_case_abi(), single.to_args(manifest.io.single_case_abi())?),
+        _ => (cairo_abi::BATCH_ABI, Batch::merge(&singles)?.to_args()),
+    };
+    let args_json = serde_json::to_string(&args)?;
+
+    let proof = match &manifest.proof {
+        None => None,
+        Some(path) => {
+            let full = base.join(path);
+            let text = fs::read_to_string(&full).map_err(|e| anyhow!("cannot read {}: {}", full.display(), e))?;
+            let (public, public_error) = match proof_reader::read(&text) {
+                Ok(public) => (Some(public), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            Some(ProofSummary { path: path.clone(), bytes: text.len() as u64, prove_ms: manifest.prove_ms, public, public_error })
+        }
+    };
+
+    let source_file = manifest.program.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    Ok(Report {
+        name: if manifest.name.is_empty() { source_file.clone() } else { manifest.name },
+        tool: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        source_file,
+        source: source_lines,
+        grid,
+        passed: cases.iter().filter(|c| c.passed).count(),
+        cases,
+        commitments: Commitments {
+            program_root: merkle::program_root_hex(programs)?,
+            program_id: ProgramId::of_programs(programs)?,
+            source_sha256: hex::encode(Sha256::digest(attest::normalize_source(&source))),
+            encoding_version: attest::ENCODING_VERSION,
+            commitment_version: attest::COMMITMENT_VERSION,
+        },
+        args: ArgsSummary {
+            abi,
+            values: args.len(),
+            prog_words: prog_words.len(),
+            bytes: args_json.len(),
+            sha256: hex::encode(Sha256::digest(&args_json)),
+        },
+        proof,
+    })
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn list(values: &[u32]) -> String {
+    values.iter().map(u32::to_string).collect::<Vec<_>>().join(", ")
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#222}\
+table{border-collapse:collapse;margin-bottom:1.5rem}\
+td,th{border:1px solid #ccc;padding:.25rem .6rem;text-align:left;vertical-align:top}\
+code,pre,.mono{font-family:ui-monospace,monospace}\
+.pass{color:#17803d}.fail{color:#b42318}.muted{color:#888}\
+.grid td{width:16rem}.grid pre{margin:.25rem 0 0}";
+
+/// Render `report` as one self-contained HTML page.
+pub fn render_html(report: &Report) -> String {
+    let mut h = String::new();
+    let _ = write!(
+        h,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{name} – zk-100 report</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{name}</h1>\n<p>{passed} of {total} cases passed · root <code>{root}</code> · {tool} {version}</p>\n",
+        name = escape(&report.name),
+        passed = report.passed,
+        total = report.cases.len(),
+        root = report.commitments.program_root,
+        tool = escape(&report.tool),
+        version = escape(&report.version),
+    );
+
+    let _ = write!(h, "<h2>Grid</h2>\n<table class=\"grid\">\n");
+    let rows = report.grid.iter().map(|n| n.row).max().map_or(0, |r| r + 1);
+    for row in 0..rows {
+        h.push_str("<tr>");
+        for node in report.grid.iter().filter(|n| n.row == row) {
+            let _ = write!(
+                h,
+                "<td><strong>({},{})</strong> {} instructions<pre>{}</pre></td>",
+                node.row,
+                node.col,
+                node.instructions,
+                node.words.join("\n")
+            );
+        }
+        h.push_str("</tr>\n");
+    }
+    h.push_str("</table>\n");
+
+    let _ = write!(h, "<h2>Source</h2>\n<p class=\"mono\">{}</p>\n<table>\n<tr><th>Line</th><th>Node</th><th>PC</th><th>Source</th><th>Word</th></tr>\n", escape(&report.source_file));
+    for line in &report.source {
+        let node = line.node.map_or_else(String::new, |(r, c)| format!("({},{})", r, c));
+        let pc = line.pc.map_or_else(String::new, |pc| pc.to_string());
+        let _ = writeln!(
+            h,
+            "<tr><td class=\"muted\">{}</td><td>{}</td><td>{}</td><td><code>{}</code></td><td><code>{}</code></td></tr>",
+            line.line,
+            node,
+            pc,
+            escape(&line.text),
+            line.word.as_deref().unwrap_or("")
+        );
+    }
+    h.push_str("</table>\n");
+
+    let _ = write!(h, "<h2>Cases</h2>\n<table>\n<tr><th>#</th><th>Inputs</th><th>Expected</th><th>Outputs</th><th>Result</th><th>Cycles</th><th>Messages</th><th>Nodes</th><th>Ended</th></tr>\n");
+    for (i, case) in report.cases.iter().enumerate() {
+        let (class, verdict) = if case.passed { ("pass", "pass") } else { ("fail", "FAIL") };
+        let failures: String =
+            case.failures.iter().map(|f| format!("<div class=\"muted\">{}</div>", escape(f))).collect();
+        let _ = writeln!(
+            h,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"{}\">{}{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:?}</td></tr>",
+            i,
+            list(&case.inputs),
+            escape(&case.expected.to_string().replace(',', ", ")),
+            list(&case.outputs),
+            class,
+            verdict,
+            failures,
+            case.cycles,
+            case.msgs,
+            case.nodes_used,
+            case.termination
+        );
+    }
+    h.push_str("</table>\n");
+
+    let c = &report.commitments;
+    let a = &report.args;
+    let _ = write!(
+        h,
+        "<h2>Commitments</h2>\n<table>\n<tr><th>Program root</th><td><code>{}</code></td></tr>\n<tr><th>Program ID</th><td><code>{}</code></td></tr>\n<tr><th>Source SHA-256</th><td><code>{}</code></td></tr>\n<tr><th>Encoding / commitment version</th><td>{} / {}</td></tr>\n</table>\n",
+        c.program_root, c.program_id, c.source_sha256, c.encoding_version, c.commitment_version
+    );
+    let _ = write!(
+        h,
+        "<h2>Args</h2>\n<table>\n<tr><th>ABI</th><td>{}</td></tr>\n<tr><th>Values</th><td>{} ({} program words)</td></tr>\n<tr><th>Size</th><td>{} bytes</td></tr>\n<tr><th>SHA-256</th><td><code>{}</code></td></tr>\n</table>\n",
+        a.abi, a.values, a.prog_words, a.bytes, a.sha256
+    );
+
+    if let Some(proof) = &report.proof {
+        let _ = write!(
+            h,
+            "<h2>Proof</h2>\n<table>\n<tr><th>File</th><td class=\"mono\">{}</td></tr>\n<tr><th>Size</th><td>{} bytes</td></tr>\n",
+            escape(&proof.path.display().to_string()),
+            proof.bytes
+        );
+        if let Some(ms) = proof.prove_ms {
+            let _ = writeln!(h, "<tr><th>Proving time</th><td>{:.1} s</td></tr>", ms as f64 / 1000.0);
+        }
+        match (&proof.public, &proof.public_error) {
+            (Some(public), _) => {
+                let _ = write!(
+                    h,
+                    "<tr><th>Committed root</th><td><code>{}</code></td></tr>\n<tr><th>Solved</th><td>{}</td></tr>\n",
+                    public.program_root,
+                    if public.solved { "yes" } else { "no" }
+                );
+            }
+            (None, Some(error)) => {
+                let _ = writeln!(h, "<tr><th>Public output</th><td class=\"fail\">{}</td></tr>", escape(error));
+            }
+            (None, None) => {}
+        }
+        h.push_str("</table>\n");
+    }
+    h.push_str("</body>\n</html>\n");
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_parse() {
+        let manifest = PuzzleManifest::parse(
+            "# demo\n[puzzle]\nname = \"demo\"\nprogram = \"p.asm\"\nprove_ms = 1_500\n\n[[case]]\ninputs = [1, 2]\nexpected = [3]\n[[case]]\ninputs = []\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.name, "demo");
+        assert_eq!(manifest.prove_ms, Some(1500));
+        assert_eq!(manifest.cases, [
+            ReportCase { inputs: Inputs::Values(vec![1, 2]), expected: vec![3].into() },
+            ReportCase { inputs: Inputs::Values(vec![]), expected: ExpectedSpec::default() },
+        ]);
+    }
+
+    #[test]
+    fn test_manifest_errors_name_the_line() {
+        let err = PuzzleManifest::parse("[puzzle]\nprogram = \"p.asm\"\ncolour = \"red\"\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: unknown key puzzle.colour");
+        assert!(PuzzleManifest::parse("[puzzle]\nprogram = \"p.asm\"\n").is_err());
+        assert!(PuzzleManifest::parse("[[case]]\ninputs = [x]\n").unwrap_err().to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("MOV <a> & \"b\""), "MOV &lt;a&gt; &amp; &quot;b&quot;");
+    }
+}
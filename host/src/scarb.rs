@@ -0,0 +1,360 @@
+//! Locating the Cairo guest artifact inside a Scarb workspace.
+//!
+//! Instead of a hand-written `--guest target/dev/zk100_exec.executable.json`
+//! path, commands can name the Scarb package. We walk up from a starting
+//! directory to the nearest `Scarb.toml`, continue up to the workspace root
+//! that lists it as a member (if any), confirm the package exists and derive
+//! `<root>/target/<profile>/<package>.executable.json`.
+//!
+//! Only the handful of manifest keys we need are read (`[package] name` and
+//! `[workspace] members`), with a line-based parser rather than a TOML crate.
+
+use crate::exec::{self, Progress};
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const MANIFEST_NAME: &str = "Scarb.toml";
+pub const DEFAULT_PROFILE: &str = "dev";
+
+/// The fields of a `Scarb.toml` this module understands.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub package_name: Option<String>,
+    pub workspace_members: Option<Vec<String>>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut manifest = Manifest::default();
+        let mut section = String::new();
+        let mut lines = text.lines();
+
+        while let Some(raw) = lines.next() {
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                section = line.trim_matches(|c| c == '[' || c == ']').trim().to_string();
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match (section.as_str(), key.trim()) {
+                ("package", "name") => {
+                    manifest.package_name = Some(parse_string(value.trim())?);
+                }
+                ("workspace", "members") => {
+                    // Arrays may span several lines.
+                    let mut array = value.trim().to_string();
+                    while !array.contains(']') {
+                        let next = lines
+                            .next()
+                            .ok_or_else(|| anyhow!("unterminated workspace.members array"))?;
+                        array.push_str(strip_comment(next));
+                    }
+                    manifest.workspace_members = Some(parse_string_array(&array)?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read {}: {}", path.display(), e))?;
+        Manifest::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+}
+
+pub(crate) fn strip_comment(line: &str) -> &str {
+    // Good enough for manifests: '#' inside quoted strings is not expected
+    // in package names or member paths.
+    line.split('#').next().unwrap_or("")
+}
+
+pub(crate) fn parse_string(value: &str) -> Result<String> {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("expected a quoted string, found {}", value))
+}
+
+fn parse_string_array(value: &str) -> Result<Vec<String>> {
+    let inner = value
+        .trim()
+        .strip_prefix('[')
+        .and_then(|v| v.trim_end().strip_suffix(']'))
+        .ok_or_else(|| anyhow!("expected an array, found {}", value))?;
+    inner
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(parse_string)
+        .collect()
+}
+
+/// A Scarb workspace (or single-package project) on disk.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub root: PathBuf,
+    /// (package name, package directory) for every package in the workspace.
+    pub packages: Vec<(String, PathBuf)>,
+}
+
+impl Workspace {
+    /// Find the workspace enclosing `start` by walking up for `Scarb.toml`.
+    pub fn discover(start: &Path) -> Result<Self> {
+        let start = start
+            .canonicalize()
+            .map_err(|e| anyhow!("cannot resolve {}: {}", start.display(), e))?;
+        let nearest = start
+            .ancestors()
+            .find(|dir| dir.join(MANIFEST_NAME).is_file())
+            .ok_or_else(|| {
+                anyhow!(
+                    "no {} found in {} or any parent directory; pass --scarb-dir pointing into the guest project",
+                    MANIFEST_NAME,
+                    start.display()
+                )
+            })?;
+
+        // A package may belong to a workspace declared further up.
+        for dir in nearest.ancestors() {
+            let path = dir.join(MANIFEST_NAME);
+            if !path.is_file() {
+                continue;
+            }
+            let manifest = Manifest::load(&path)?;
+            if manifest.workspace_members.is_some() {
+                let workspace = Workspace::from_root(dir, &manifest)?;
+                if dir == nearest || workspace.packages.iter().any(|(_, p)| p == nearest) {
+                    return Ok(workspace);
+                }
+            }
+        }
+
+        Workspace::from_root(nearest, &Manifest::load(&nearest.join(MANIFEST_NAME))?)
+    }
+
+    fn from_root(root: &Path, manifest: &Manifest) -> Result<Self> {
+        let mut packages = Vec::new();
+        if let Some(name) = &manifest.package_name {
+            packages.push((name.clone(), root.to_path_buf()));
+        }
+        for member in manifest.workspace_members.iter().flatten() {
+            for dir in expand_member(root, member)? {
+                let path = dir.join(MANIFEST_NAME);
+                if let Some(name) = Manifest::load(&path)?.package_name {
+                    packages.push((name, dir));
+                }
+            }
+        }
+        Ok(Workspace {
+            root: root.to_path_buf(),
+            packages,
+        })
+    }
+
+    pub fn package_dir(&self, name: &str) -> Option<&Path> {
+        self.packages
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, dir)| dir.as_path())
+    }
+
+    /// Target directory, honoring `SCARB_TARGET_DIR` like scarb does.
+    pub fn target_dir(&self) -> PathBuf {
+        match std::env::var_os("SCARB_TARGET_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => self.root.join("target"),
+        }
+    }
+
+    /// Path of the executable artifact scarb builds for `package`.
+    pub fn artifact_path(&self, package: &str, profile: &str) -> Result<PathBuf> {
+        if self.package_dir(package).is_none() {
+            let known: Vec<&str> = self.packages.iter().map(|(n, _)| n.as_str()).collect();
+            return Err(anyhow!(
+                "package '{}' not found in Scarb workspace {} (packages: {})",
+                package,
+                self.root.display(),
+                known.join(", ")
+            ));
+        }
+        Ok(self
+            .target_dir()
+            .join(profile)
+            .join(format!("{}.executable.json", package)))
+    }
+
+    /// The exact command that builds `package` for `profile`.
+    pub fn build_command(&self, package: &str, profile: &str) -> Command {
+        let mut cmd = Command::new("scarb");
+        cmd.current_dir(&self.root)
+            .args(["--profile", profile, "build", "--package", package]);
+        cmd
+    }
+
+    /// Human-readable form of `build_command` for error messages.
+    pub fn build_command_line(&self, package: &str, profile: &str) -> String {
+        format!(
+            "cd {} && scarb --profile {} build --package {}",
+            self.root.display(),
+            profile,
+            package
+        )
+    }
+}
+
+/// Expand a `members` entry: plain paths or a trailing `/*` glob.
+fn expand_member(root: &Path, member: &str) -> Result<Vec<PathBuf>> {
+    if let Some(parent) = member.strip_suffix("/*") {
+        let dir = root.join(parent);
+        let mut dirs: Vec<PathBuf> = fs::read_dir(&dir)
+            .map_err(|e| anyhow!("workspace member glob {}: {}", dir.display(), e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.join(MANIFEST_NAME).is_file())
+            .collect();
+        dirs.sort();
+        Ok(dirs)
+    } else {
+        let dir = root.join(member);
+        if dir.join(MANIFEST_NAME).is_file() {
+            Ok(vec![dir])
+        } else {
+            Err(anyhow!("workspace member {} has no {}", dir.display(), MANIFEST_NAME))
+        }
+    }
+}
+
+/// Resolve the artifact for `package`, optionally building it first.
+pub fn locate_guest(start: &Path, package: &str, profile: &str, build: bool) -> Result<PathBuf> {
+    let workspace = Workspace::discover(start)?;
+    let artifact = workspace.artifact_path(package, profile)?;
+
+    if build {
+        let status = exec::status(&mut workspace.build_command(package, profile), &mut Progress::hidden())?;
+        if !status.success() {
+            return Err(anyhow!(
+                "`{}` failed ({})",
+                workspace.build_command_line(package, profile),
+                status
+            ));
+        }
+    }
+
+    if !artifact.is_file() {
+        return Err(anyhow!(
+            "guest artifact {} does not exist; build it with:\n    {}\nor rerun with --build-guest",
+            artifact.display(),
+            workspace.build_command_line(package, profile)
+        ));
+    }
+
+    Ok(artifact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scarb_workspace")
+    }
+
+    /// Copy the fixture workspace into a fresh temp dir so tests can add artifacts.
+    fn scratch_copy() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "zk100-scarb-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        copy_dir(&fixture(), &dir);
+        dir
+    }
+
+    fn copy_dir(from: &Path, to: &Path) {
+        fs::create_dir_all(to).unwrap();
+        for entry in fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            let target = to.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &target);
+            } else {
+                fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(
+            "[workspace]\nmembers = [\n  \"crates/*\", # all crates\n  \"tools/gen\",\n]\n\n[package]\nname = \"root_pkg\"\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.package_name.as_deref(), Some("root_pkg"));
+        assert_eq!(
+            manifest.workspace_members,
+            Some(vec!["crates/*".to_string(), "tools/gen".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_discover_from_member_directory() {
+        let ws = Workspace::discover(&fixture().join("crates/zk100_guest/src")).unwrap();
+        assert_eq!(ws.root, fixture().canonicalize().unwrap());
+        assert!(ws.package_dir("zk100_guest").is_some());
+        assert!(ws.package_dir("zk100_vm").is_some());
+    }
+
+    #[test]
+    fn test_artifact_path_and_unknown_package() {
+        let ws = Workspace::discover(&fixture()).unwrap();
+        let path = ws.artifact_path("zk100_guest", "release").unwrap();
+        assert!(path.ends_with("target/release/zk100_guest.executable.json"));
+        let err = ws.artifact_path("nope", "dev").unwrap_err().to_string();
+        assert!(err.contains("package 'nope' not found"), "{}", err);
+        assert!(err.contains("zk100_guest"), "{}", err);
+    }
+
+    #[test]
+    fn test_missing_artifact_names_scarb_command() {
+        let dir = scratch_copy();
+        let err = locate_guest(&dir, "zk100_guest", DEFAULT_PROFILE, false)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("scarb --profile dev build --package zk100_guest"), "{}", err);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_existing_artifact_is_found() {
+        let dir = scratch_copy();
+        let target = dir.join("target/dev");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("zk100_guest.executable.json"), "{}").unwrap();
+
+        let found = locate_guest(&dir.join("crates/zk100_guest"), "zk100_guest", "dev", false).unwrap();
+        assert_eq!(found, dir.canonicalize().unwrap().join("target/dev/zk100_guest.executable.json"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_no_manifest_anywhere() {
+        let dir = std::env::temp_dir();
+        if dir.ancestors().any(|d| d.join(MANIFEST_NAME).is_file()) {
+            return;
+        }
+        let err = Workspace::discover(&dir).unwrap_err().to_string();
+        assert!(err.contains("--scarb-dir"), "{}", err);
+    }
+}
@@ -0,0 +1,117 @@
+//! JSON Schema documents for the JSON artifacts, behind `zk100 schema`.
+//!
+//! The schemas are derived with `schemars` from the artifact types, so they
+//! follow the serde attributes that shape each document, and the types' doc
+//! comments become the `description`s integrators read. `tests/schema.rs` runs
+//! the crate's own output through them so they cannot drift from what is
+//! written.
+
+use crate::assembler::NodeSymbols;
+use crate::attest::Metadata;
+use crate::grid::ProgramGrid;
+use crate::stats::{StatsRecord, Summary};
+use crate::trace::Timings;
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, Schema};
+use serde_json::{Value, json};
+
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The documents `zk100 schema` describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Artifact {
+    /// Grid JSON from `convert --to json`.
+    Program,
+    /// The `--metadata` sidecar.
+    Metadata,
+    /// The `--symbols` file.
+    Symbols,
+    /// The `--timings-json` summary.
+    Timings,
+    /// One line of `.zk100/stats.jsonl`.
+    StatsRecord,
+    /// `stats-log summarize --json`.
+    StatsSummary,
+}
+
+impl Artifact {
+    pub const ALL: [Artifact; 6] = [
+        Artifact::Program,
+        Artifact::Metadata,
+        Artifact::Symbols,
+        Artifact::Timings,
+        Artifact::StatsRecord,
+        Artifact::StatsSummary,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Artifact::Program => "program",
+            Artifact::Metadata => "metadata",
+            Artifact::Symbols => "symbols",
+            Artifact::Timings => "timings",
+            Artifact::StatsRecord => "stats-record",
+            Artifact::StatsSummary => "stats-summary",
+        }
+    }
+
+    /// The complete schema document, with `$schema` and `title`.
+    pub fn schema(self) -> Value {
+        let schema = match self {
+            Artifact::Program => written::<ProgramGrid>(),
+            Artifact::Metadata => written::<Metadata>(),
+            Artifact::Symbols => written::<Vec<NodeSymbols>>(),
+            Artifact::Timings => written::<Timings>(),
+            Artifact::StatsRecord => written::<StatsRecord>(),
+            Artifact::StatsSummary => written::<Summary>(),
+        };
+        let mut schema = schema.to_value();
+        schema["title"] = json!(format!("zk-100 {}", self.name()));
+        schema
+    }
+}
+
+/// The schema of `T` as this build serializes it: a field skipped when
+/// empty is optional even where reading it back would default it.
+fn written<T: JsonSchema>() -> Schema {
+    SchemaSettings::draft2020_12().for_serialize().into_generator().into_root_schema_for::<T>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inputs::InputSpec;
+
+    fn errors(schema: &Value, document: &Value) -> Vec<String> {
+        let validator = jsonschema::validator_for(schema).unwrap();
+        validator.iter_errors(document).map(|e| format!("{}: {}", e.instance_path(), e)).collect()
+    }
+
+    #[test]
+    fn test_timings_schema_is_closed() {
+        let schema = Artifact::Timings.schema();
+        assert_eq!(schema["$schema"], DIALECT);
+        let ok = json!({ "phases": [{ "name": "parse", "calls": 1, "total_us": 5, "mean_us": 5, "fields": { "bytes": 3 } }] });
+        assert_eq!(errors(&schema, &ok), Vec::<String>::new());
+
+        let bad = json!({ "phases": [{ "name": 7, "calls": 1, "total_us": 5, "fields": {} }], "extra": true });
+        let found = errors(&schema, &bad);
+        assert_eq!(found.len(), 3, "{:?}", found);
+        assert!(found.iter().any(|e| e.contains("\"mean_us\" is a required property")), "{:?}", found);
+        assert!(found.iter().any(|e| e.starts_with("/phases/0/name: 7 is not of type")), "{:?}", found);
+    }
+
+    #[test]
+    fn test_input_specs_match_one_alternative() {
+        let schema = written::<InputSpec>().to_value();
+        for spec in [
+            "{ range = [1, 3] }",
+            "{ repeat = [7, 2] }",
+            "{ random = { len = 2, min = 0, max = 9, seed = 1 } }",
+        ] {
+            let value = serde_json::to_value(InputSpec::parse(spec).unwrap()).unwrap();
+            assert_eq!(errors(&schema, &value), Vec::<String>::new(), "{}", spec);
+        }
+        assert_eq!(errors(&schema, &json!({ "range": [1] })).len(), 1);
+    }
+}
@@ -0,0 +1,229 @@
+//! Root-only solution sharing behind `zk100 seal` / `zk100 unseal`.
+//!
+//! A sealed file discloses the program root, the grid shape and how many
+//! instructions each node holds, never the source itself. The source can ride
+//! along encrypted under a player-chosen passphrase; `unseal` decrypts it and
+//! recomputes the root with `merkle::program_root_hex` (the same call the
+//! daemon's `hash` method makes), so a source swapped in after sealing is
+//! rejected even when it decrypts cleanly.
+//!
+//! The source is encrypted with ChaCha20-Poly1305 under a key Argon2id
+//! derives from the passphrase and a random salt; the seal format version is
+//! the associated data, so a file cannot be replayed under another version.
+
+use crate::{assembler, attest, merkle};
+use anyhow::{Result, anyhow};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{AeadCore, AeadInPlace, KeyInit, OsRng, rand_core::RngCore};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, Tag};
+use serde::{Deserialize, Serialize};
+
+/// Version of the sealed file format; version 1 used the retired
+/// SHA-256 keystream and is rejected with a hint to re-seal.
+pub const SEAL_VERSION: u32 = 2;
+pub const CIPHER: &str = "chacha20poly1305";
+pub const KDF: &str = "argon2id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GridShape {
+    pub rows: usize,
+    pub cols: usize,
+}
+
+/// The encrypted source; every binary field is hex.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedSource {
+    pub cipher: String,
+    pub kdf: String,
+    /// Argon2id memory cost in KiB; absent from version 1 files.
+    #[serde(default)]
+    pub memory_kib: u32,
+    pub iterations: u32,
+    #[serde(default)]
+    pub lanes: u32,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+    pub tag: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sealed {
+    #[serde(default = "legacy_version")]
+    pub seal_version: u32,
+    /// `0x`-prefixed hex of the program Merkle root.
+    pub root: String,
+    pub commitment_version: u32,
+    pub grid: GridShape,
+    /// Instructions per node, `[row][col]`.
+    pub instruction_counts: Vec<Vec<usize>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_source: Option<EncryptedSource>,
+}
+
+/// Files written before the field existed are version 1.
+fn legacy_version() -> u32 {
+    1
+}
+
+/// What `unseal` discloses about a source: root, shape and node sizes.
+fn disclose(source: &str) -> Result<Sealed> {
+    let programs = assembler::parse_assembly(source)?;
+    assembler::encode_programs(&programs)?;
+    Ok(Sealed {
+        seal_version: SEAL_VERSION,
+        root: merkle::program_root_hex(&programs)?,
+        commitment_version: attest::COMMITMENT_VERSION,
+        grid: GridShape { rows: programs.len(), cols: programs.first().map_or(0, Vec::len) },
+        instruction_counts: programs.iter().map(|row| row.iter().map(Vec::len).collect()).collect(),
+        encrypted_source: None,
+    })
+}
+
+/// Seal `source`, encrypting it under `passphrase` when one is given.
+pub fn seal(source: &str, passphrase: Option<&str>) -> Result<Sealed> {
+    let mut sealed = disclose(source)?;
+    if let Some(passphrase) = passphrase {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        sealed.encrypted_source = Some(encrypt(source.as_bytes(), passphrase, &salt, Params::default())?);
+    }
+    Ok(sealed)
+}
+
+/// Decrypt the sealed source and check it still hashes to the sealed root.
+pub fn unseal(sealed: &Sealed, passphrase: &str) -> Result<String> {
+    let encrypted = sealed
+        .encrypted_source
+        .as_ref()
+        .ok_or_else(|| anyhow!("sealed file carries no encrypted source; reveal it with --source"))?;
+    if sealed.seal_version != SEAL_VERSION {
+        return Err(anyhow!(
+            "sealed with format version {}, this build implements {}; re-seal the source",
+            sealed.seal_version,
+            SEAL_VERSION
+        ));
+    }
+    let plaintext = decrypt(encrypted, passphrase)?;
+    let source = String::from_utf8(plaintext).map_err(|_| anyhow!("decrypted source is not UTF-8 text"))?;
+    verify_source(sealed, &source)?;
+    Ok(source)
+}
+
+/// Check that a revealed `source` is the one that was sealed.
+pub fn verify_source(sealed: &Sealed, source: &str) -> Result<()> {
+    if sealed.commitment_version != attest::COMMITMENT_VERSION {
+        return Err(anyhow!(
+            "sealed with commitment version {}, this build implements {}",
+            sealed.commitment_version,
+            attest::COMMITMENT_VERSION
+        ));
+    }
+    let actual = disclose(source).map_err(|e| anyhow!("revealed source does not assemble: {}", e))?;
+    if actual.root != sealed.root {
+        return Err(anyhow!(
+            "revealed source hashes to {}, but the sealed root is {}; the source was swapped after sealing",
+            actual.root,
+            sealed.root
+        ));
+    }
+    if actual.grid != sealed.grid || actual.instruction_counts != sealed.instruction_counts {
+        return Err(anyhow!("grid shape or instruction counts do not match the revealed source"));
+    }
+    Ok(())
+}
+
+fn cipher(passphrase: &str, salt: &[u8], params: Params) -> Result<ChaCha20Poly1305> {
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("cannot derive the key: {}", e))?;
+    Ok(ChaCha20Poly1305::new(&key))
+}
+
+fn associated_data() -> [u8; 4] {
+    SEAL_VERSION.to_be_bytes()
+}
+
+fn encrypt(plaintext: &[u8], passphrase: &str, salt: &[u8], params: Params) -> Result<EncryptedSource> {
+    let (memory_kib, iterations, lanes) = (params.m_cost(), params.t_cost(), params.p_cost());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let mut ciphertext = plaintext.to_vec();
+    let tag = cipher(passphrase, salt, params)?
+        .encrypt_in_place_detached(&nonce, &associated_data(), &mut ciphertext)
+        .map_err(|_| anyhow!("source is too large to encrypt"))?;
+    Ok(EncryptedSource {
+        cipher: CIPHER.to_string(),
+        kdf: KDF.to_string(),
+        memory_kib,
+        iterations,
+        lanes,
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+        tag: hex::encode(tag),
+    })
+}
+
+fn decrypt(encrypted: &EncryptedSource, passphrase: &str) -> Result<Vec<u8>> {
+    if encrypted.cipher != CIPHER || encrypted.kdf != KDF {
+        return Err(anyhow!(
+            "unsupported encryption {} / {} (this build implements {} / {})",
+            encrypted.cipher,
+            encrypted.kdf,
+            CIPHER,
+            KDF
+        ));
+    }
+    let field = |name: &str, value: &str| hex::decode(value).map_err(|e| anyhow!("encrypted_source.{}: {}", name, e));
+    let salt = field("salt", &encrypted.salt)?;
+    let nonce = field("nonce", &encrypted.nonce)?;
+    let mut data = field("ciphertext", &encrypted.ciphertext)?;
+    let tag = field("tag", &encrypted.tag)?;
+    if nonce.len() != 12 || tag.len() != 16 {
+        return Err(anyhow!("encrypted_source: nonce must be 12 bytes and tag 16 bytes"));
+    }
+
+    let params = Params::new(encrypted.memory_kib, encrypted.iterations, encrypted.lanes, None)
+        .map_err(|e| anyhow!("encrypted_source: {}", e))?;
+    cipher(passphrase, &salt, params)?
+        .decrypt_in_place_detached(Nonce::from_slice(&nonce), &associated_data(), &mut data, Tag::from_slice(&tag))
+        .map_err(|_| anyhow!("wrong passphrase, or the encrypted source was corrupted"))?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cheap() -> Params {
+        Params::new(Params::MIN_M_COST, 1, 1, None).unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let text = b"NODE (0,0)\nMOV IN, P:RIGHT\n".repeat(5);
+        let sealed = encrypt(&text, "pw", b"saltsaltsaltsalt", cheap()).unwrap();
+        assert_ne!(sealed.ciphertext, hex::encode(&text));
+        assert_eq!(decrypt(&sealed, "pw").unwrap(), text);
+        assert!(decrypt(&sealed, "pW").is_err());
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_is_rejected() {
+        let mut sealed = encrypt(b"MOV 1, ACC\n", "pw", b"saltsaltsaltsalt", cheap()).unwrap();
+        let mut bytes = hex::decode(&sealed.ciphertext).unwrap();
+        bytes[0] ^= 1;
+        sealed.ciphertext = hex::encode(bytes);
+        let err = decrypt(&sealed, "pw").unwrap_err().to_string();
+        assert!(err.contains("wrong passphrase"), "{}", err);
+    }
+
+    #[test]
+    fn test_legacy_version_is_rejected() {
+        let mut sealed = seal("NODE (0,0)\nMOV IN, OUT\n", Some("pw")).unwrap();
+        sealed.seal_version = 1;
+        let err = unseal(&sealed, "pw").unwrap_err().to_string();
+        assert!(err.contains("format version 1"), "{}", err);
+    }
+}
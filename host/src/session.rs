@@ -0,0 +1,275 @@
+//! Recorded sessions for bug reports: `--record-session DIR` and
+//! `replay DIR`.
+//!
+//! A recording is a directory holding:
+//!
+//! - `replay.json`, the `Manifest`: the command line, the parsed command
+//!   with every default filled in, the files it read and wrote, and the
+//!   error it failed with, if any;
+//! - `inputs/`, a copy of every file the command read (sources, includes,
+//!   input files, manifests, `zk100.toml`) laid out as the command saw them
+//!   from its working directory;
+//! - `outputs/`, a copy of every file it wrote, laid out the same way;
+//! - `capabilities.json`, what this build supports.
+//!
+//! Paths inside the working directory keep their relative form. Anything
+//! else is stored under `_root/` with its absolute path, and the command
+//! line is rewritten to match, so a session replays from any directory.
+//! `replay` copies `inputs/` into a scratch directory, runs the recorded
+//! command line there and compares each output with the recorded one.
+
+use crate::capabilities;
+use crate::exec::{self, FileLog};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+pub const MANIFEST: &str = "replay.json";
+pub const INPUTS_DIR: &str = "inputs";
+pub const OUTPUTS_DIR: &str = "outputs";
+pub const CAPABILITIES: &str = "capabilities.json";
+/// Layout version of `replay.json`.
+pub const SESSION_VERSION: u32 = 1;
+/// Top-level `zk100.toml` key naming the directory to record every run to.
+pub const CONFIG_KEY: &str = "record_session";
+/// The flag that turns recording on; dropped from the recorded command line.
+pub const FLAG: &str = "--record-session";
+/// Top-level metadata key whose value is the time of writing, not a result.
+const VOLATILE_KEY: &str = "created_unix";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// Version of the tool that recorded the session.
+    pub tool_version: String,
+    /// Arguments after the program name, paths relativized.
+    pub args: Vec<String>,
+    /// The parsed command, every default and override resolved.
+    pub resolved: String,
+    /// Paths under `inputs/`.
+    pub inputs: Vec<String>,
+    /// Paths under `outputs/`.
+    pub outputs: Vec<String>,
+    /// The error the command failed with, as `main` printed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// `path` as seen from `cwd`, without `.` or `..`: relative if it lies under
+/// `cwd`, else under `_root/`.
+pub fn relativize(cwd: &Path, path: &Path) -> PathBuf {
+    let absolute = normalize(&cwd.join(path));
+    match absolute.strip_prefix(normalize(cwd)) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => Path::new("_root").join(absolute.components().filter(|c| matches!(c, Component::Normal(_))).collect::<PathBuf>()),
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// `args` without `FLAG` and its value.
+pub fn strip_flag(args: &[String]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == FLAG {
+            args.next();
+        } else if !arg.starts_with(&format!("{}=", FLAG)) {
+            kept.push(arg.clone());
+        }
+    }
+    kept
+}
+
+/// Write a session of the command `args` (after the program name), run
+/// from `cwd`, to `dir`, replacing any earlier one there. `files` is what
+/// the command logged; files inside `dir` itself are left out.
+pub fn record(dir: &Path, cwd: &Path, args: &[String], resolved: String, files: &FileLog, error: Option<String>) -> Result<Manifest> {
+    for sub in [INPUTS_DIR, OUTPUTS_DIR] {
+        if dir.join(sub).exists() {
+            fs::remove_dir_all(dir.join(sub)).map_err(|e| anyhow!("cannot clear {}: {}", dir.join(sub).display(), e))?;
+        }
+    }
+    let own = relativize(cwd, dir);
+    let mut renamed = Vec::new();
+    let mut copy_all = |paths: &[PathBuf], sub: &str| -> Result<Vec<String>> {
+        let mut copied = Vec::new();
+        for path in paths {
+            let relative = relativize(cwd, path);
+            if relative.starts_with(&own) || !path.is_file() {
+                continue;
+            }
+            let dest = dir.join(sub).join(&relative);
+            fs::create_dir_all(dest.parent().unwrap_or(dir))?;
+            fs::copy(path, &dest).map_err(|e| anyhow!("cannot copy {} into the session: {}", path.display(), e))?;
+            if relative != *path {
+                renamed.push((path.display().to_string(), relative.display().to_string()));
+            }
+            copied.push(relative.display().to_string());
+        }
+        Ok(copied)
+    };
+    let inputs = copy_all(&files.reads, INPUTS_DIR)?;
+    let outputs = copy_all(&files.writes, OUTPUTS_DIR)?;
+
+    let args = strip_flag(args)
+        .into_iter()
+        .map(|arg| {
+            for (from, to) in &renamed {
+                if arg == *from {
+                    return to.clone();
+                }
+                if let Some((flag, value)) = arg.split_once('=') {
+                    if value == from {
+                        return format!("{}={}", flag, to);
+                    }
+                }
+            }
+            arg
+        })
+        .collect();
+    let manifest = Manifest {
+        version: SESSION_VERSION,
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        args,
+        resolved,
+        inputs,
+        outputs,
+        error,
+    };
+    exec::write_atomic(&dir.join(CAPABILITIES), serde_json::to_string_pretty(&capabilities::capabilities())? + "\n")?;
+    exec::write_atomic(&dir.join(MANIFEST), serde_json::to_string_pretty(&manifest)? + "\n")?;
+    Ok(manifest)
+}
+
+pub fn read_manifest(dir: &Path) -> Result<Manifest> {
+    let path = dir.join(MANIFEST);
+    let text = fs::read_to_string(&path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    let manifest: Manifest = serde_json::from_str(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+    if manifest.version != SESSION_VERSION {
+        return Err(anyhow!("{}: session version {} is not supported (expected {})", path.display(), manifest.version, SESSION_VERSION));
+    }
+    Ok(manifest)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Same,
+    Differs,
+    /// The replay did not write it.
+    Missing,
+}
+
+impl Outcome {
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::Same => "same",
+            Outcome::Differs => "differs",
+            Outcome::Missing => "missing",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub outputs: Vec<(String, Outcome)>,
+    pub recorded_error: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Replay {
+    pub fn matches(&self) -> bool {
+        self.recorded_error == self.error && self.outputs.iter().all(|(_, outcome)| *outcome == Outcome::Same)
+    }
+}
+
+/// Run the session in `dir` again with `program` (this binary) in a scratch
+/// copy of its inputs, and compare. Metadata's `created_unix` is the one
+/// thing expected to change and is not compared.
+pub fn replay(dir: &Path, program: &Path) -> Result<Replay> {
+    let manifest = read_manifest(dir)?;
+    let work = std::env::temp_dir().join(format!("zk100-replay-{}", std::process::id()));
+    if work.exists() {
+        fs::remove_dir_all(&work)?;
+    }
+    fs::create_dir_all(&work)?;
+    let result = replay_in(dir, &manifest, program, &work);
+    fs::remove_dir_all(&work).ok();
+    result
+}
+
+fn replay_in(dir: &Path, manifest: &Manifest, program: &Path, work: &Path) -> Result<Replay> {
+    for input in &manifest.inputs {
+        let dest = work.join(input);
+        fs::create_dir_all(dest.parent().unwrap_or(work))?;
+        fs::copy(dir.join(INPUTS_DIR).join(input), &dest)
+            .map_err(|e| anyhow!("cannot copy recorded input {}: {}", input, e))?;
+    }
+    let output = exec::output(Command::new(program).args(&manifest.args).arg("--quiet").current_dir(work))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let error = match output.status.success() {
+        true => None,
+        false => Some(stderr.lines().rev().find_map(|l| l.strip_prefix("error: ")).unwrap_or("").to_string()),
+    };
+    let outputs = manifest
+        .outputs
+        .iter()
+        .map(|path| {
+            let outcome = match fs::read(work.join(path)) {
+                Err(_) => Outcome::Missing,
+                Ok(bytes) => match fs::read(dir.join(OUTPUTS_DIR).join(path)) {
+                    Ok(recorded) if same(&recorded, &bytes) => Outcome::Same,
+                    _ => Outcome::Differs,
+                },
+            };
+            (path.clone(), outcome)
+        })
+        .collect();
+    Ok(Replay { outputs, recorded_error: manifest.error.clone(), error })
+}
+
+/// Byte-equal, or JSON objects equal once `VOLATILE_KEY` is dropped.
+fn same(recorded: &[u8], replayed: &[u8]) -> bool {
+    if recorded == replayed {
+        return true;
+    }
+    let parse = |bytes: &[u8]| {
+        let mut value: serde_json::Value = serde_json::from_slice(bytes).ok()?;
+        value.as_object_mut()?.remove(VOLATILE_KEY)?;
+        Some(value)
+    };
+    matches!((parse(recorded), parse(replayed)), (Some(a), Some(b)) if a == b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relativize_and_flags() {
+        let cwd = Path::new("/work/project");
+        assert_eq!(relativize(cwd, Path::new("src/./a.asm")), Path::new("src/a.asm"));
+        assert_eq!(relativize(cwd, Path::new("/work/project/b.asm")), Path::new("b.asm"));
+        assert_eq!(relativize(cwd, Path::new("../shared/c.asm")), Path::new("_root/work/shared/c.asm"));
+        let args: Vec<String> = ["assemble", "--record-session", "s", "a.asm", "--record-session=t"].map(String::from).to_vec();
+        assert_eq!(strip_flag(&args), ["assemble", "a.asm"]);
+
+        assert!(same(br#"{"created_unix": 1, "root": "0x1"}"#, br#"{"created_unix": 2, "root": "0x1"}"#));
+        assert!(!same(br#"{"created_unix": 1, "root": "0x1"}"#, br#"{"created_unix": 1, "root": "0x2"}"#));
+        assert!(!same(b"[1]", b"[2]"));
+    }
+}
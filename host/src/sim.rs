@@ -0,0 +1,929 @@
+//! Host-side simulator mirroring the Cairo guest (`crates/vm/src/step.cairo`).
+//!
+//! The guest semantics are reproduced deliberately, quirks included, so that a
+//! host run predicts what the proof will attest to:
+//! - Each cycle first collects port intents from every running node, then
+//!   executes every node against that snapshot.
+//! - The PC wraps modulo the program length; `HLT` behaves like `NOP`. Only
+//!   nodes with empty programs halt, so most runs end in deadlock (every
+//!   running node blocked) or at the cycle limit.
+//! - Only (0,0) reads `IN` and only (1,1)'s `OUT` writes are recorded; `OUT`
+//!   elsewhere is dropped. A blocked `MOV IN, P:..` still consumes the input.
+//!   `simulate_io` moves both roles to other nodes, as `abi::IO_ABI`
+//!   lets the next guest revision do.
+//!   With an input sentinel (`abi::SENTINEL_ABI`), `EndOfStream`
+//!   makes `IN` block at the sentinel as if the inputs had run out;
+//!   `PassThrough` delivers it like any value.
+//!   `node_outputs` additionally keeps every node's `OUT` writes, as the
+//!   per-node guest variant (`abi::NODE_ABI`) checks them.
+//! - `ADD`/`SUB` use checked u32 arithmetic: the guest panics on overflow, so
+//!   the simulator reports an error. `NEG` wraps.
+//! - `LAST` names the port of the last `ANY` transfer. There is no `ANY` port,
+//!   so nothing ever sets it and the guest blocks on every use; that is
+//!   `LastBehavior::Block`, the default. `with_last(LastBehavior::Nil)` reads
+//!   it as 0 and drops writes to it instead, for trying out that semantics
+//!   before the guest settles on one.
+//! - Literals go through the instruction codec, so only the encoded 8 bits
+//!   are visible, exactly as in the guest. The assembler rejects wider ones
+//!   and lowers negative ones, so only a grid built by hand has any.
+//! - `ASSERT` lines are host-only: given `with_asserts`, the simulator
+//!   checks them before a node executes the pc they guard and stops with an
+//!   error naming the node, pc, line, cycle and `ACC` when one fails.
+
+use crate::assembler::{Asserts, Programs};
+use crate::abi::{self, InputSentinel, IoNodes, NodeOutputs, SentinelBehavior};
+use crate::instruction::{Dst, Inst, Op, PortTag, Src};
+use crate::config;
+use crate::limits::{self, Limit, Limits};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Orders in which a cycle visits the nodes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Schedule {
+    /// Row by row, left to right, as the guest does.
+    #[default]
+    RowMajor,
+}
+
+impl Schedule {
+    /// Every order this build implements.
+    pub const ALL: [Schedule; 1] = [Schedule::RowMajor];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Schedule::RowMajor => "row-major",
+        }
+    }
+}
+
+/// Order `Simulator::step` visits nodes in.
+pub const SCHEDULE: Schedule = Schedule::RowMajor;
+
+/// Cycle budget used by the guest, the default `cycles` limit.
+pub const DEFAULT_MAX_CYCLES: u64 = limits::DEFAULT_CYCLES;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    /// Every node halted. The guest only halts empty nodes, and halting is not
+    /// progress, so in practice runs end in `Deadlock` first.
+    Halted,
+    /// No running node made progress in the last cycle.
+    Deadlock,
+    /// The cycle budget ran out.
+    CycleLimit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    Continue,
+    Halted,
+    Deadlock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct NodeState {
+    pub acc: u32,
+    pub bak: u32,
+    pub pc: u32,
+    pub z: bool,
+    pub n: bool,
+    pub halted: bool,
+    pub blocked: bool,
+}
+
+impl Default for NodeState {
+    fn default() -> Self {
+        NodeState {
+            acc: 0,
+            bak: 0,
+            pc: 0,
+            z: true,
+            n: false,
+            halted: false,
+            blocked: false,
+        }
+    }
+}
+
+impl NodeState {
+    fn set_acc(&mut self, value: u32) {
+        self.acc = value;
+        self.z = value == 0;
+        self.n = value >> 31 == 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PortIntent {
+    r: usize,
+    c: usize,
+    port: PortTag,
+    value: u32,
+    is_read: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimResult {
+    pub outputs: Vec<u32>,
+    /// `OUT` writes of every node that wrote any.
+    #[serde(serialize_with = "abi::node_keys::serialize")]
+    pub node_outputs: NodeOutputs,
+    pub cycles: u64,
+    pub msgs: u64,
+    pub nodes_used: u32,
+    pub termination: Termination,
+    pub inputs_consumed: usize,
+}
+
+impl SimResult {
+    /// The guest's `check_target`: outputs must equal `expected` exactly.
+    pub fn matches(&self, expected: &[u32]) -> bool {
+        self.outputs == expected
+    }
+
+    /// The per-node check: every node's `OUT` writes must equal its expected
+    /// list exactly, and nodes without one must write nothing.
+    pub fn node_matches(&self, expected: &NodeOutputs) -> bool {
+        self.node_mismatches(expected).is_empty()
+    }
+
+    /// Nodes whose `OUT` writes differ from `expected`, in row-major order.
+    pub fn node_mismatches(&self, expected: &NodeOutputs) -> Vec<(usize, usize)> {
+        let mut nodes: Vec<(usize, usize)> = expected.keys().chain(self.node_outputs.keys()).copied().collect();
+        nodes.sort();
+        nodes.dedup();
+        nodes
+            .into_iter()
+            .filter(|node| {
+                let actual = self.node_outputs.get(node).map_or(&[][..], Vec::as_slice);
+                let wanted = expected.get(node).map_or(&[][..], Vec::as_slice);
+                actual != wanted
+            })
+            .collect()
+    }
+}
+
+/// A copy of what a front end shows of a simulation between two cycles,
+/// from `Simulator::snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimSnapshot {
+    pub cycles: u64,
+    pub msgs: u64,
+    /// Indexed `[row][col]`, like the programs.
+    pub nodes: Vec<Vec<NodeSnapshot>>,
+    pub inputs: Vec<u32>,
+    /// How many inputs `IN` has consumed.
+    pub in_cursor: usize,
+    pub outputs: Vec<u32>,
+    pub io: IoNodes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeSnapshot {
+    pub state: NodeState,
+    pub program: Vec<Inst>,
+    /// The instruction that runs next, with the guest's wrap-around; `None`
+    /// for an empty node.
+    pub pc: Option<usize>,
+}
+
+/// What an instruction using `LAST` does while nothing has set it, which in
+/// this ISA is always.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LastBehavior {
+    /// Block forever, as the guest does.
+    #[default]
+    Block,
+    /// Read 0 and discard writes, like `NIL`.
+    Nil,
+}
+
+impl LastBehavior {
+    /// The `zk100.toml` key that picks one.
+    pub const KEY: &'static str = "last";
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LastBehavior::Block => "block",
+            LastBehavior::Nil => "nil",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "block" => Ok(LastBehavior::Block),
+            "nil" => Ok(LastBehavior::Nil),
+            other => Err(anyhow!("{} must be block or nil, found {}", LastBehavior::KEY, other)),
+        }
+    }
+
+    /// `last` in the settings file at `path`, or `Block` without one.
+    pub fn load(path: &Path) -> Result<Self> {
+        match config::read_key(path, LastBehavior::KEY)? {
+            Some(name) => LastBehavior::from_name(&name).map_err(|e| anyhow!("{}: {}", path.display(), e)),
+            None => Ok(LastBehavior::Block),
+        }
+    }
+}
+
+pub struct Simulator {
+    programs: Programs,
+    nodes: Vec<Vec<NodeState>>,
+    inputs: Vec<u32>,
+    in_cursor: usize,
+    io: IoNodes,
+    sentinel: Option<InputSentinel>,
+    asserts: Asserts,
+    outputs: Vec<u32>,
+    node_outputs: NodeOutputs,
+    cycles: u64,
+    msgs: u64,
+    limits: Limits,
+    last: LastBehavior,
+}
+
+impl Simulator {
+    pub fn new(programs: &Programs, inputs: &[u32]) -> Result<Self> {
+        Simulator::with_io(programs, inputs, IoNodes::default())
+    }
+
+    /// A simulator whose input and output streams belong to `io`'s nodes.
+    pub fn with_io(programs: &Programs, inputs: &[u32], io: IoNodes) -> Result<Self> {
+        let limits = Limits::default();
+        check_inputs(&limits, inputs)?;
+        // Round-trip through the codec so literals are what the guest decodes.
+        let programs = programs
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|program| {
+                        program
+                            .iter()
+                            .map(|inst| Inst::decode(inst.encode()).map_err(|e| anyhow!("{}", e)))
+                            .collect::<Result<Vec<Inst>>>()
+                    })
+                    .collect::<Result<Vec<Vec<Inst>>>>()
+            })
+            .collect::<Result<Programs>>()?;
+        let nodes = programs
+            .iter()
+            .map(|row| vec![NodeState::default(); row.len()])
+            .collect();
+        Ok(Simulator {
+            programs,
+            nodes,
+            inputs: inputs.to_vec(),
+            in_cursor: 0,
+            io,
+            sentinel: None,
+            asserts: Asserts::new(),
+            outputs: Vec::new(),
+            node_outputs: NodeOutputs::new(),
+            cycles: 0,
+            msgs: 0,
+            limits,
+            last: LastBehavior::Block,
+        })
+    }
+
+    /// Enforce `limits` instead of the defaults: on the inputs now, on the
+    /// outputs as they are produced and on the cycles a run asks for.
+    pub fn with_limits(mut self, limits: Limits) -> Result<Self> {
+        check_inputs(&limits, &self.inputs)?;
+        self.limits = limits;
+        Ok(self)
+    }
+
+    /// Honor `sentinel` when nodes read `IN`.
+    pub fn with_sentinel(mut self, sentinel: Option<InputSentinel>) -> Self {
+        self.sentinel = sentinel;
+        self
+    }
+
+    /// Treat `LAST` as `last` says.
+    pub fn with_last(mut self, last: LastBehavior) -> Self {
+        self.last = last;
+        self
+    }
+
+    /// Check `asserts` (from `ParseResult::asserts`) as nodes reach them.
+    pub fn with_asserts(mut self, asserts: Asserts) -> Self {
+        self.asserts = asserts;
+        self
+    }
+
+    pub fn nodes(&self) -> &[Vec<NodeState>] {
+        &self.nodes
+    }
+
+    pub fn outputs(&self) -> &[u32] {
+        &self.outputs
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn in_cursor(&self) -> usize {
+        self.in_cursor
+    }
+
+    pub fn snapshot(&self) -> SimSnapshot {
+        let nodes = (0..self.rows())
+            .map(|r| {
+                (0..self.cols())
+                    .map(|c| {
+                        let program = self.programs[r][c].clone();
+                        let pc = (!program.is_empty()).then(|| self.nodes[r][c].pc as usize % program.len());
+                        NodeSnapshot { state: self.nodes[r][c], program, pc }
+                    })
+                    .collect()
+            })
+            .collect();
+        SimSnapshot {
+            cycles: self.cycles,
+            msgs: self.msgs,
+            nodes,
+            inputs: self.inputs.clone(),
+            in_cursor: self.in_cursor,
+            outputs: self.outputs.clone(),
+            io: self.io,
+        }
+    }
+
+    fn rows(&self) -> usize {
+        self.programs.len()
+    }
+
+    fn cols(&self) -> usize {
+        self.programs.first().map_or(0, Vec::len)
+    }
+
+    /// Current instruction of a node, with the guest's PC wrap-around.
+    pub fn current_inst(&self, r: usize, c: usize) -> Option<Inst> {
+        let program = &self.programs[r][c];
+        if program.is_empty() {
+            return None;
+        }
+        let pc = self.nodes[r][c].pc as usize % program.len();
+        Some(program[pc])
+    }
+
+    fn neighbor(&self, r: usize, c: usize, port: PortTag) -> Option<(usize, usize)> {
+        match port {
+            PortTag::Up => r.checked_sub(1).map(|r| (r, c)),
+            PortTag::Down => (r + 1 < self.rows()).then_some((r + 1, c)),
+            PortTag::Left => c.checked_sub(1).map(|c| (r, c)),
+            PortTag::Right => (c + 1 < self.cols()).then_some((r, c + 1)),
+        }
+    }
+
+    /// Non-port source read used for intents and plain execution.
+    fn read_source(&self, r: usize, c: usize, src: Src) -> Option<(u32, bool)> {
+        let node = &self.nodes[r][c];
+        match src {
+            Src::Lit(v) => Some((v, false)),
+            Src::Acc => Some((node.acc, false)),
+            Src::Nil => Some((0, false)),
+            Src::In => {
+                if (r, c) == self.io.in_node {
+                    let value = *self.inputs.get(self.in_cursor)?;
+                    match self.sentinel {
+                        Some(s) if s.behavior == SentinelBehavior::EndOfStream && s.value == value => None,
+                        _ => Some((value, true)),
+                    }
+                } else {
+                    None
+                }
+            }
+            Src::Last => (self.last == LastBehavior::Nil).then_some((0, false)),
+            Src::P(_) => None,
+        }
+    }
+
+    fn port_intent(&self, r: usize, c: usize) -> Option<PortIntent> {
+        let inst = self.current_inst(r, c)?;
+        match inst.op {
+            Op::Mov => match (inst.src, inst.dst) {
+                (Src::P(port), _) => Some(PortIntent { r, c, port, value: 0, is_read: true }),
+                (src, Dst::P(port)) => {
+                    let (value, _) = self.read_source(r, c, src)?;
+                    Some(PortIntent { r, c, port, value, is_read: false })
+                }
+                _ => None,
+            },
+            Op::Add | Op::Sub | Op::Jmp | Op::Jz | Op::Jnz | Op::Jgz | Op::Jlz => match inst.src {
+                Src::P(port) => Some(PortIntent { r, c, port, value: 0, is_read: true }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn ports_match(&self, a: &PortIntent, b: &PortIntent) -> bool {
+        a.is_read != b.is_read
+            && self.neighbor(a.r, a.c, a.port) == Some((b.r, b.c))
+            && opposite(a.port) == b.port
+    }
+
+    /// Advance every node by one cycle.
+    pub fn step(&mut self) -> Result<StepResult> {
+        let mut all_halted = true;
+        let mut any_progress = false;
+
+        let mut intents = Vec::new();
+        for r in 0..self.rows() {
+            for c in 0..self.cols() {
+                if !self.nodes[r][c].halted {
+                    all_halted = false;
+                    if let Some(intent) = self.port_intent(r, c) {
+                        intents.push(intent);
+                    }
+                }
+            }
+        }
+
+        let mut next = self.nodes.clone();
+        for (r, row) in next.iter_mut().enumerate() {
+            for (c, slot) in row.iter_mut().enumerate() {
+                if slot.halted {
+                    continue;
+                }
+                let Some(inst) = self.current_inst(r, c) else {
+                    slot.halted = true;
+                    continue;
+                };
+                self.check_asserts(r, c)?;
+                let port_match = self
+                    .port_intent(r, c)
+                    .and_then(|own| intents.iter().find(|other| self.ports_match(&own, other)).copied());
+
+                let (node, blocked, output, consumed) = self.execute(r, c, inst, port_match)?;
+                if !blocked {
+                    any_progress = true;
+                }
+                if let Some(value) = output {
+                    self.node_outputs.entry((r, c)).or_default().push(value);
+                }
+                if (r, c) == self.io.out_node {
+                    if let Some(value) = output {
+                        self.outputs.push(value);
+                        self.msgs += 1;
+                        let count = self.outputs.len() as u64;
+                        self.limits.check(Limit::Outputs, count, || format!("OUT has produced {} values", count))?;
+                    }
+                }
+                if (r, c) == self.io.in_node && consumed {
+                    self.in_cursor += 1;
+                }
+                *slot = node;
+            }
+        }
+
+        self.nodes = next;
+        self.cycles += 1;
+
+        Ok(if all_halted {
+            StepResult::Halted
+        } else if !any_progress {
+            StepResult::Deadlock
+        } else {
+            StepResult::Continue
+        })
+    }
+
+    /// Fail on the first assert guarding the pc node (r, c) is about to run
+    /// that does not hold.
+    fn check_asserts(&self, r: usize, c: usize) -> Result<()> {
+        let node = &self.nodes[r][c];
+        let pc = node.pc as usize % self.programs[r][c].len();
+        let guards = self.asserts.get(&(r, c)).and_then(|node| node.get(&pc));
+        match guards.into_iter().flatten().find(|a| !a.holds(node.acc)) {
+            Some(assert) => Err(anyhow!(
+                "assertion failed at node ({},{}) pc {} (line {}) in cycle {}: {}, but ACC is {}",
+                r, c, pc, assert.line, self.cycles, assert, node.acc as i32
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Read a source, taking port values from the matched writer.
+    fn read_with_ports(
+        &self,
+        r: usize,
+        c: usize,
+        src: Src,
+        port_match: Option<PortIntent>,
+    ) -> Option<(u32, bool)> {
+        match src {
+            Src::P(_) => port_match.filter(|m| !m.is_read).map(|m| (m.value, false)),
+            _ => self.read_source(r, c, src),
+        }
+    }
+
+    fn execute(
+        &self,
+        r: usize,
+        c: usize,
+        inst: Inst,
+        port_match: Option<PortIntent>,
+    ) -> Result<(NodeState, bool, Option<u32>, bool)> {
+        let mut node = self.nodes[r][c];
+        let mut blocked = false;
+        let mut output = None;
+        let mut consumed = false;
+
+        let jump_if = |node: &mut NodeState, blocked: &mut bool, consumed: &mut bool, taken: bool| {
+            if taken {
+                match self.read_with_ports(r, c, inst.src, port_match) {
+                    Some((value, used)) => {
+                        *consumed = used;
+                        node.pc = value;
+                    }
+                    None => *blocked = true,
+                }
+            } else {
+                node.pc += 1;
+            }
+        };
+
+        match inst.op {
+            Op::Nop | Op::Hlt => node.pc += 1,
+            Op::Mov => match self.read_with_ports(r, c, inst.src, port_match) {
+                Some((value, used)) => {
+                    consumed = used;
+                    match inst.dst {
+                        Dst::Acc => {
+                            node.set_acc(value);
+                            node.pc += 1;
+                        }
+                        Dst::Nil => node.pc += 1,
+                        Dst::Out => {
+                            output = Some(value);
+                            node.pc += 1;
+                        }
+                        Dst::P(_) => {
+                            if port_match.is_some_and(|m| m.is_read) {
+                                node.pc += 1;
+                            } else {
+                                blocked = true;
+                            }
+                        }
+                        Dst::Last => match self.last {
+                            LastBehavior::Block => blocked = true,
+                            LastBehavior::Nil => node.pc += 1,
+                        },
+                    }
+                }
+                None => blocked = true,
+            },
+            Op::Add | Op::Sub => match self.read_with_ports(r, c, inst.src, port_match) {
+                Some((value, used)) => {
+                    consumed = used;
+                    let result = if inst.op == Op::Add {
+                        node.acc.checked_add(value)
+                    } else {
+                        node.acc.checked_sub(value)
+                    };
+                    let result = result.ok_or_else(|| {
+                        anyhow!(
+                            "u32 {} overflow at node ({},{}) pc {} in cycle {} (the guest panics here)",
+                            inst.op, r, c, node.pc, self.cycles
+                        )
+                    })?;
+                    node.set_acc(result);
+                    node.pc += 1;
+                }
+                None => blocked = true,
+            },
+            Op::Neg => {
+                node.set_acc(0u32.wrapping_sub(node.acc));
+                node.pc += 1;
+            }
+            Op::Sav => {
+                node.bak = node.acc;
+                node.pc += 1;
+            }
+            Op::Swp => {
+                std::mem::swap(&mut node.acc, &mut node.bak);
+                node.set_acc(node.acc);
+                node.pc += 1;
+            }
+            Op::Jmp => jump_if(&mut node, &mut blocked, &mut consumed, true),
+            Op::Jz => {
+                let taken = node.z;
+                jump_if(&mut node, &mut blocked, &mut consumed, taken)
+            }
+            Op::Jnz => {
+                let taken = !node.z;
+                jump_if(&mut node, &mut blocked, &mut consumed, taken)
+            }
+            Op::Jgz => {
+                let taken = !node.z && !node.n;
+                jump_if(&mut node, &mut blocked, &mut consumed, taken)
+            }
+            Op::Jlz => {
+                let taken = node.n;
+                jump_if(&mut node, &mut blocked, &mut consumed, taken)
+            }
+        }
+
+        node.blocked = blocked;
+        Ok((node, blocked, output, consumed))
+    }
+
+    /// Run until halt, deadlock or `max_cycles`.
+    pub fn run(&mut self, max_cycles: u64) -> Result<Termination> {
+        self.limits.check(Limit::Cycles, max_cycles, || format!("a run of up to {} cycles", max_cycles))?;
+        while self.cycles < max_cycles {
+            match self.step()? {
+                StepResult::Continue => {}
+                StepResult::Halted => return Ok(Termination::Halted),
+                StepResult::Deadlock => return Ok(Termination::Deadlock),
+            }
+        }
+        Ok(Termination::CycleLimit)
+    }
+
+    /// `run` to the end and collect the result.
+    pub fn run_to_result(mut self, max_cycles: u64) -> Result<SimResult> {
+        let termination = self.run(max_cycles)?;
+        Ok(self.into_result(termination))
+    }
+
+    fn into_result(self, termination: Termination) -> SimResult {
+        let nodes_used = self.programs.iter().flatten().filter(|p| !p.is_empty()).count() as u32;
+        SimResult {
+            outputs: self.outputs,
+            node_outputs: self.node_outputs,
+            cycles: self.cycles,
+            msgs: self.msgs,
+            nodes_used,
+            termination,
+            inputs_consumed: self.in_cursor,
+        }
+    }
+}
+
+fn check_inputs(limits: &Limits, inputs: &[u32]) -> Result<()> {
+    limits.check(Limit::Inputs, inputs.len() as u64, || format!("the input stream has {} values", inputs.len()))
+}
+
+fn opposite(port: PortTag) -> PortTag {
+    match port {
+        PortTag::Up => PortTag::Down,
+        PortTag::Down => PortTag::Up,
+        PortTag::Left => PortTag::Right,
+        PortTag::Right => PortTag::Left,
+    }
+}
+
+/// Run `programs` on `inputs` to completion.
+pub fn simulate(programs: &Programs, inputs: &[u32], max_cycles: u64) -> Result<SimResult> {
+    simulate_io(programs, inputs, IoNodes::default(), max_cycles)
+}
+
+/// `simulate` with the input and output streams on `io`'s nodes.
+pub fn simulate_io(programs: &Programs, inputs: &[u32], io: IoNodes, max_cycles: u64) -> Result<SimResult> {
+    simulate_stream(programs, inputs, io, None, max_cycles)
+}
+
+/// `simulate_io` honoring an input sentinel.
+pub fn simulate_stream(
+    programs: &Programs,
+    inputs: &[u32],
+    io: IoNodes,
+    sentinel: Option<InputSentinel>,
+    max_cycles: u64,
+) -> Result<SimResult> {
+    Simulator::with_io(programs, inputs, io)?.with_sentinel(sentinel).run_to_result(max_cycles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::parse_assembly;
+
+    fn run(code: &str, inputs: &[u32]) -> SimResult {
+        simulate(&parse_assembly(code).unwrap(), inputs, DEFAULT_MAX_CYCLES).unwrap()
+    }
+
+    #[test]
+    fn test_doubles_inputs_through_the_grid() {
+        let code = r#"
+NODE (0,0)
+MOV IN, ACC
+ADD ACC
+MOV ACC, P:DOWN
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT
+"#;
+        let result = run(code, &[1, 2, 3]);
+        assert_eq!(result.outputs, vec![2, 4, 6]);
+        assert_eq!(result.msgs, 3);
+        assert_eq!(result.inputs_consumed, 3);
+        assert_eq!(result.nodes_used, 3);
+        assert_eq!(result.termination, Termination::Deadlock);
+        assert!(result.matches(&[2, 4, 6]));
+    }
+
+    #[test]
+    fn test_port_to_port_mov_drops_the_value() {
+        // The guest treats a port read's match as the writer, so the write
+        // half of `MOV P:.., P:..` never succeeds: the sample relay program
+        // delivers nothing.
+        let code = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/test_program.asm")).unwrap();
+        let result = run(&code, &[42, 7]);
+        assert!(result.outputs.is_empty());
+        assert_eq!(result.termination, Termination::Deadlock);
+    }
+
+    #[test]
+    fn test_empty_grid_deadlocks_in_first_cycle() {
+        // Empty nodes halt while executing, which is not progress.
+        let result = run("", &[]);
+        assert_eq!(result.termination, Termination::Deadlock);
+        assert_eq!(result.cycles, 1);
+    }
+
+    #[test]
+    fn test_conditional_jumps_and_swap() {
+        // Count down from 3 in (1,1), emitting each value.
+        let code = r#"
+NODE (1,1)
+MOV 3, ACC
+loop:
+MOV ACC, OUT
+SUB 1
+JNZ loop
+SAV
+SWP
+MOV ACC, OUT
+"#;
+        let result = run(code, &[]);
+        assert_eq!(&result.outputs[..4], &[3, 2, 1, 0]);
+        assert_eq!(result.termination, Termination::CycleLimit);
+    }
+
+    #[test]
+    fn test_overflow_is_an_error() {
+        let programs = parse_assembly("NODE (0,0)\nSUB 1\n").unwrap();
+        let err = simulate(&programs, &[], 10).unwrap_err().to_string();
+        assert!(err.contains("overflow at node (0,0) pc 0"), "{}", err);
+    }
+
+    #[test]
+    fn test_literals_follow_the_codec() {
+        // The assembler rejects 300, but a grid built by hand may hold it:
+        // it encodes as 44 in the 8-bit literal field.
+        let mut programs = parse_assembly("NODE (1,1)\n").unwrap();
+        programs[1][1].push(Inst { op: Op::Mov, src: Src::Lit(300), dst: Dst::Out });
+        let result = simulate(&programs, &[], DEFAULT_MAX_CYCLES).unwrap();
+        assert_eq!(result.outputs[0], 44);
+    }
+
+    #[test]
+    fn test_in_only_readable_at_origin() {
+        let result = run("NODE (1,1)\nMOV IN, OUT\n", &[5]);
+        assert!(result.outputs.is_empty());
+        assert_eq!(result.termination, Termination::Deadlock);
+    }
+
+    #[test]
+    fn test_designated_io_nodes() {
+        let code = "NODE (0,1)\nMOV IN, ACC\nADD ACC\nMOV ACC, P:LEFT\nNODE (0,0)\nMOV P:RIGHT, ACC\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, OUT\n";
+        let io = IoNodes { in_node: (0, 1), out_node: (1, 0) };
+        let result = simulate_io(&parse_assembly(code).unwrap(), &[1, 2, 3], io, DEFAULT_MAX_CYCLES).unwrap();
+        assert_eq!(result.outputs, [2, 4, 6]);
+        assert_eq!(result.inputs_consumed, 3);
+        assert_eq!(result.msgs, 3);
+        // The default nodes see nothing: (0,0) never reads IN, (1,1) never writes.
+        let default = run(code, &[1, 2, 3]);
+        assert!(default.outputs.is_empty());
+        assert_eq!(default.inputs_consumed, 0);
+    }
+
+    #[test]
+    fn test_outputs_are_attributed_per_node() {
+        // Both nodes end blocked reading from each other.
+        let result = run(
+            "NODE (0,1)\nMOV 0, OUT\nMOV P:DOWN, ACC\nNODE (1,1)\nMOV 5, OUT\nMOV 10, OUT\nMOV P:UP, ACC\n",
+            &[],
+        );
+        assert_eq!(result.termination, Termination::Deadlock);
+        assert_eq!(result.outputs, [5, 10]);
+        let expected = NodeOutputs::from([((0, 1), vec![0]), ((1, 1), vec![5, 10])]);
+        assert_eq!(result.node_outputs, expected);
+        assert!(result.node_matches(&expected));
+
+        let mut wrong = expected.clone();
+        wrong.insert((1, 1), vec![5, 11]);
+        assert_eq!(result.node_mismatches(&wrong), [(1, 1)]);
+        wrong.remove(&(0, 1));
+        assert_eq!(result.node_mismatches(&wrong), [(0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn test_sentinel_behaviors() {
+        let code = r#"
+NODE (0,0)
+MOV IN, ACC
+MOV ACC, P:DOWN
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT
+"#;
+        let programs = parse_assembly(code).unwrap();
+        let stream = |behavior| {
+            let sentinel = InputSentinel { value: 0, behavior };
+            simulate_stream(&programs, &[4, 0, 5], IoNodes::default(), Some(sentinel), DEFAULT_MAX_CYCLES).unwrap()
+        };
+        // End of stream: `IN` blocks at the sentinel and never reads past it.
+        let result = stream(SentinelBehavior::EndOfStream);
+        assert_eq!(result.outputs, vec![4]);
+        assert_eq!(result.inputs_consumed, 1);
+        assert_eq!(result.termination, Termination::Deadlock);
+        // Pass-through: the sentinel is an ordinary value.
+        let result = stream(SentinelBehavior::PassThrough);
+        assert_eq!(result.outputs, vec![4, 0, 5]);
+        assert_eq!(result.inputs_consumed, 3);
+    }
+
+    #[test]
+    fn test_asserts() {
+        let code = r#"
+NODE (0,0)
+MOV IN, ACC
+ASSERT ACC > 0
+ASSERT ACC != 7
+MOV ACC, P:DOWN
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT
+"#;
+        let parsed = crate::assembler::parse(code).unwrap();
+        let check = |inputs: &[u32]| {
+            Simulator::new(&parsed.grid.programs, inputs)
+                .unwrap()
+                .with_asserts(parsed.asserts.clone())
+                .run_to_result(DEFAULT_MAX_CYCLES)
+        };
+        assert_eq!(check(&[1, 2, 3]).unwrap().outputs, vec![1, 2, 3]);
+        // The third input fails the second assert when (0,0) reaches pc 1.
+        let err = check(&[1, 2, 7]).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "assertion failed at node (0,0) pc 1 (line 5) in cycle 5: ASSERT ACC != 7, but ACC is 7"
+        );
+        // Without asserts the same run passes.
+        assert_eq!(run(code, &[1, 2, 7]).outputs, vec![1, 2, 7]);
+    }
+
+    #[test]
+    fn test_limits() {
+        use crate::limits::Origin;
+        let code = "NODE (0,0)\nMOV IN, ACC\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+        let programs = parse_assembly(code).unwrap();
+        let lowered = |limit: Limit, value: u64| {
+            let mut limits = Limits::default();
+            limits.set(limit, value, Origin::Flag);
+            limits
+        };
+        let err = Simulator::new(&programs, &[1, 2, 3]).unwrap().with_limits(lowered(Limit::Inputs, 2)).err().unwrap();
+        assert_eq!(err.to_string(), "the input stream has 3 values, over the inputs limit of 2 (from --limit)");
+
+        let sim = Simulator::new(&programs, &[1, 2, 3]).unwrap().with_limits(lowered(Limit::Outputs, 2)).unwrap();
+        let err = sim.run_to_result(DEFAULT_MAX_CYCLES).unwrap_err().to_string();
+        assert_eq!(err, "OUT has produced 3 values, over the outputs limit of 2 (from --limit)");
+
+        let sim = Simulator::new(&programs, &[1]).unwrap().with_limits(lowered(Limit::Cycles, 100)).unwrap();
+        let err = sim.run_to_result(DEFAULT_MAX_CYCLES).unwrap_err().to_string();
+        assert_eq!(err, "a run of up to 10000 cycles, over the cycles limit of 100 (from --limit)");
+    }
+
+    #[test]
+    fn test_last_behaviors() {
+        let code = "NODE (1,1)\nMOV 5, ACC\nMOV ACC, LAST\nMOV LAST, ACC\nMOV ACC, OUT\nMOV P:UP, ACC\n";
+        let programs = parse_assembly(code).unwrap();
+        let run = |last| Simulator::new(&programs, &[]).unwrap().with_last(last).run_to_result(100).unwrap();
+        let blocked = run(LastBehavior::default());
+        assert_eq!((blocked.termination, blocked.outputs, blocked.cycles), (Termination::Deadlock, vec![], 2));
+        let nil = run(LastBehavior::Nil);
+        assert_eq!((nil.termination, nil.outputs), (Termination::Deadlock, vec![0]));
+
+        assert_eq!(LastBehavior::from_name("nil").unwrap(), LastBehavior::Nil);
+        assert_eq!(LastBehavior::from_name("any").unwrap_err().to_string(), "last must be block or nil, found any");
+    }
+}
@@ -0,0 +1,281 @@
+//! Loading assembly source files.
+//!
+//! Editors on Windows save with a UTF-8 byte order mark, CRLF line endings
+//! and, now and then, Latin-1 bytes in a comment. `decode` turns such a file
+//! into the text the parser expects: the BOM is dropped (the lexer would
+//! otherwise take it for part of the first `NODE` keyword), `\r\n` becomes
+//! `\n` so line and column numbers match whatever the editor shows, and
+//! invalid UTF-8 after a comment marker is replaced with U+FFFD. Invalid
+//! UTF-8 anywhere else is an error naming the file, the line and the byte
+//! offset, since guessing there could change the program.
+//!
+//! `read` also expands `INCLUDE "fragment.asm"` (or `.include`) lines, paths
+//! relative to the including file, so shared node blocks can live in one
+//! place. Expansion is textual, so the parser counts lines of the expanded
+//! source; `read_with_origins` also returns the file and line each of them
+//! came from, and `Origins::relocate` rewrites an error's `line N` as
+//! `relay.inc:3`. Source with no file behind it, such as stdin, has nothing
+//! to resolve a path against; `decode_standalone` rejects its includes.
+
+use crate::assembler::AssemblyErrors;
+use crate::exec;
+use crate::lexer::{self, TokenKind};
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// The directive pulling another source file in, in any case and with or
+/// without a leading `.`.
+pub const INCLUDE: &str = "INCLUDE";
+
+/// Read and `decode` the source file at `path`, with its includes expanded.
+pub fn read(path: &Path) -> Result<String> {
+    Ok(read_with_includes(path)?.0)
+}
+
+/// `read`, also returning every file it included, directly or not, in the
+/// order they were first reached.
+pub fn read_with_includes(path: &Path) -> Result<(String, Vec<PathBuf>)> {
+    let mut includes = Vec::new();
+    let text = expand(path, &mut vec![path.to_path_buf()], &mut includes, &mut Vec::new())?;
+    Ok((text, includes))
+}
+
+/// `read`, also returning where each line of the expanded text came from.
+pub fn read_with_origins(path: &Path) -> Result<(String, Origins)> {
+    let mut lines = Vec::new();
+    let text = expand(path, &mut vec![path.to_path_buf()], &mut Vec::new(), &mut lines)?;
+    Ok((text, Origins { lines }))
+}
+
+/// The file and 1-based line of each line of an expanded source, in order.
+/// Empty for source read without includes in mind, which is then its own
+/// origin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Origins {
+    lines: Vec<(PathBuf, usize)>,
+}
+
+impl Origins {
+    /// Whether any line came from a file other than the first, so that the
+    /// expanded line numbers are no use to the reader.
+    pub fn is_spliced(&self) -> bool {
+        self.lines.first().is_some_and(|(first, _)| self.lines.iter().any(|(path, _)| path != first))
+    }
+
+    /// Line `line` of the expanded source as `file:line`, if it was spliced.
+    pub fn locate(&self, line: usize) -> Option<String> {
+        let (path, line) = self.lines.get(line.checked_sub(1)?).filter(|_| self.is_spliced())?;
+        Some(format!("{}:{}", path.display(), line))
+    }
+
+    /// `message` with its leading `line N` located, as the assembler words
+    /// its errors (`line 3, col 5, ...` becomes `relay.inc:3, col 5, ...`).
+    pub fn relocate_message(&self, message: &str) -> String {
+        let Some(rest) = message.strip_prefix("line ") else {
+            return message.to_string();
+        };
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        match rest[..digits].parse().ok().and_then(|line| self.locate(line)) {
+            Some(origin) => format!("{}{}", origin, &rest[digits..]),
+            None => message.to_string(),
+        }
+    }
+
+    /// `error` from parsing the expanded source, with every line it names
+    /// located.
+    pub fn relocate(&self, error: anyhow::Error) -> anyhow::Error {
+        if !self.is_spliced() {
+            return error;
+        }
+        if let Some(errors) = error.downcast_ref::<AssemblyErrors>() {
+            let mut errors = errors.clone();
+            errors.errors.iter_mut().for_each(|e| e.message = self.relocate_message(&e.message));
+            return errors.into();
+        }
+        let message = error.to_string();
+        match self.relocate_message(&message) {
+            relocated if relocated != message => anyhow!(relocated),
+            _ => error,
+        }
+    }
+
+}
+
+fn expand(path: &Path, stack: &mut Vec<PathBuf>, includes: &mut Vec<PathBuf>, origins: &mut Vec<(PathBuf, usize)>) -> Result<String> {
+    let bytes = fs::read(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    exec::note_read(path);
+    let name = path.display().to_string();
+    let text = decode(&bytes, &name)?;
+    if !text.lines().any(is_include) {
+        origins.extend((1..=text.lines().count()).map(|line| (path.to_path_buf(), line)));
+        return Ok(text);
+    }
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut out = String::with_capacity(text.len());
+    for (idx, line) in text.split_inclusive('\n').enumerate() {
+        if !is_include(line) {
+            out.push_str(line);
+            origins.push((path.to_path_buf(), idx + 1));
+            continue;
+        }
+        let target = include_target(line).map_err(|e| anyhow!("{}: line {}: {}", name, idx + 1, e))?;
+        let full = base.join(target);
+        if stack.contains(&full) {
+            let chain: Vec<String> = stack.iter().chain([&full]).map(|p| p.display().to_string()).collect();
+            return Err(anyhow!("include cycle: {}", chain.join(" -> ")));
+        }
+        if !includes.contains(&full) {
+            includes.push(full.clone());
+        }
+        stack.push(full.clone());
+        let included = expand(&full, stack, includes, origins)?;
+        stack.pop();
+        out.push_str(&included);
+        if !included.is_empty() && !included.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+fn is_include(line: &str) -> bool {
+    include_operand(line).is_some_and(|rest| rest.starts_with(char::is_whitespace))
+}
+
+/// What follows the `INCLUDE` keyword, if `line` starts with it.
+fn include_operand(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let line = line.strip_prefix('.').unwrap_or(line);
+    line.get(..INCLUDE.len()).filter(|word| word.eq_ignore_ascii_case(INCLUDE)).map(|_| &line[INCLUDE.len()..])
+}
+
+/// The `INCLUDE` keyword of an `INCLUDE` line as it is written there
+/// (`.include`, say), for errors to echo.
+fn include_keyword(line: &str) -> &str {
+    let line = line.trim_start();
+    let operand = include_operand(line).unwrap_or_default();
+    &line[..line.len() - operand.len()]
+}
+
+/// The quoted path of an `INCLUDE` line, which may end in a comment.
+fn include_target(line: &str) -> Result<&str> {
+    let rest = include_operand(line).unwrap_or_default().trim();
+    let expects = || anyhow!("{} expects a quoted path, found {}", include_keyword(line), rest);
+    let (target, after) = rest.strip_prefix('"').and_then(|r| r.split_once('"')).ok_or_else(expects)?;
+    let trailing_code = lexer::lex_line(after, 1).iter().any(|t| t.kind != TokenKind::Comment);
+    if target.is_empty() || trailing_code {
+        return Err(expects());
+    }
+    Ok(target)
+}
+
+/// `decode` for source that is not a file (`name` is `<stdin>`, say), which
+/// may not `INCLUDE` anything.
+pub fn decode_standalone(bytes: &[u8], name: &str) -> Result<String> {
+    let text = decode(bytes, name)?;
+    if let Some((idx, line)) = text.lines().enumerate().find(|(_, line)| is_include(line)) {
+        return Err(anyhow!(
+            "{}: line {}: {} needs a source file to resolve its path against",
+            name,
+            idx + 1,
+            include_keyword(line)
+        ));
+    }
+    Ok(text)
+}
+
+/// `bytes` of the source called `name` as parser-ready text.
+pub fn decode(bytes: &[u8], name: &str) -> Result<String> {
+    let body = bytes.strip_prefix(BOM).unwrap_or(bytes);
+    let mut offset = bytes.len() - body.len();
+    let mut text = String::with_capacity(body.len());
+    for (idx, raw) in body.split_inclusive(|&b| b == b'\n').enumerate() {
+        let line = raw.strip_suffix(b"\n").unwrap_or(raw);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        match std::str::from_utf8(line) {
+            Ok(line) => text.push_str(line),
+            Err(e) => {
+                let valid = std::str::from_utf8(&line[..e.valid_up_to()]).expect("prefix is valid");
+                let in_comment = lexer::lex_line(valid, idx + 1).last().is_some_and(|t| t.kind == TokenKind::Comment);
+                if !in_comment {
+                    return Err(anyhow!(
+                        "{}: line {}, byte {}: invalid UTF-8 outside a comment",
+                        name,
+                        idx + 1,
+                        offset + e.valid_up_to()
+                    ));
+                }
+                text.push_str(&String::from_utf8_lossy(line));
+            }
+        }
+        if raw.ends_with(b"\n") {
+            text.push('\n');
+        }
+        offset += raw.len();
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"\xef\xbb\xbfNODE (0,0)\r\nNOP\r\n", "a.asm").unwrap(), "NODE (0,0)\nNOP\n");
+        assert_eq!(decode(b"NOP # caf\xe9\nNOP // \xff", "a.asm").unwrap(), "NOP # caf\u{fffd}\nNOP // \u{fffd}");
+        // A lone `\r` is not a line ending.
+        assert_eq!(decode(b"NOP\rNOP", "a.asm").unwrap(), "NOP\rNOP");
+
+        let err = decode(b"\xef\xbb\xbfNODE (0,0)\nMOV 1\xe9, ACC # ok\n", "a.asm").unwrap_err();
+        assert_eq!(err.to_string(), "a.asm: line 2, byte 19: invalid UTF-8 outside a comment");
+        let err = decode(b"NOP /\xe9/\n", "a.asm").unwrap_err();
+        assert_eq!(err.to_string(), "a.asm: line 1, byte 5: invalid UTF-8 outside a comment");
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = std::env::temp_dir().join(format!("zk100-source-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("main.asm"), "NODE (0,0)\nMOV IN, ACC\ninclude \"lib/out.asm\" # shared\n").unwrap();
+        fs::write(dir.join("lib/out.asm"), "INCLUDE \"tail.asm\"\nNODE (1,1)\nMOV P:LEFT, OUT").unwrap();
+        fs::write(dir.join("lib/tail.asm"), "NODE (1,0)\nMOV P:UP, ACC\n").unwrap();
+        let (text, includes) = read_with_includes(&dir.join("main.asm")).unwrap();
+        assert_eq!(text, "NODE (0,0)\nMOV IN, ACC\nNODE (1,0)\nMOV P:UP, ACC\nNODE (1,1)\nMOV P:LEFT, OUT\n");
+        assert_eq!(includes, [dir.join("lib/out.asm"), dir.join("lib/tail.asm")]);
+        // Every line knows its file, the unterminated last one of out.asm too.
+        let (_, origins) = read_with_origins(&dir.join("main.asm")).unwrap();
+        let tail = dir.join("lib/tail.asm").display().to_string();
+        assert_eq!(origins.locate(4), Some(format!("{}:2", tail)));
+        assert_eq!(origins.locate(6), Some(format!("{}:3", dir.join("lib/out.asm").display())));
+        assert_eq!(origins.locate(7), None);
+        assert_eq!(origins.relocate_message("line 4, col 5: bad"), format!("{}:2, col 5: bad", tail));
+        assert_eq!(origins.relocate_message("2 errors"), "2 errors");
+        let errors = AssemblyErrors { errors: vec![crate::assembler::AssemblyError { line: 3, node: None, message: "line 3: bad".into() }] };
+        assert_eq!(origins.relocate(errors.into()).to_string(), format!("{}:1: bad", tail));
+        // Without includes the parser's line numbers are right as they are.
+        assert!(!read_with_origins(&dir.join("lib/tail.asm")).unwrap().1.is_spliced());
+
+        fs::write(dir.join("lib/tail.asm"), "INCLUDE \"out.asm\"\n").unwrap();
+        let err = read(&dir.join("main.asm")).unwrap_err().to_string();
+        let out = dir.join("lib/out.asm").display().to_string();
+        assert!(err.starts_with("include cycle: ") && err.ends_with(&format!("tail.asm -> {}", out)), "{}", err);
+        fs::write(dir.join("main.asm"), "INCLUDE lib/out.asm\n").unwrap();
+        assert!(read(&dir.join("main.asm")).unwrap_err().to_string().ends_with("line 1: INCLUDE expects a quoted path, found lib/out.asm"));
+        // A label called INCLUDE is not the directive.
+        assert!(!is_include("INCLUDE:"));
+        assert!(!is_include(".includes \"a.asm\""));
+
+        fs::write(dir.join("main.asm"), ".include \"lib/tail.asm\" ; TIS-100 style\nNODE (0,0)\n").unwrap();
+        fs::write(dir.join("lib/tail.asm"), "NODE (1,0)\n").unwrap();
+        assert_eq!(read(&dir.join("main.asm")).unwrap(), "NODE (1,0)\nNODE (0,0)\n");
+        let err = decode_standalone(b"NODE (0,0)\n  .INCLUDE \"a.asm\"\n", "<stdin>").unwrap_err();
+        assert_eq!(err.to_string(), "<stdin>: line 2: .INCLUDE needs a source file to resolve its path against");
+        let err = decode_standalone(b"include \"a.asm\"\n", "<stdin>").unwrap_err();
+        assert_eq!(err.to_string(), "<stdin>: line 1: include needs a source file to resolve its path against");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
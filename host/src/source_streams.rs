@@ -0,0 +1,97 @@
+//! The test streams a source file carries in its comments, so the values it
+//! is checked against live next to the code instead of in a shell script:
+//!
+//! ```text
+//! ## IN: 1, 2, 3
+//! ## OUT: 2, 4, 6
+//! ```
+//!
+//! `## IN:` lines hold input values and `## OUT:` lines expected outputs,
+//! written as `-i` and `-e` take them; several lines of either concatenate
+//! in order. An `## OUT: row,col=...` line is one node's outputs, as with a
+//! node-scoped `-e`. To the assembler they are comments. `assemble` uses them
+//! when `-i`/`-e` are absent; the flags win over them.
+
+use crate::assembler;
+use crate::expected::ExpectedSpec;
+use crate::inputs;
+use anyhow::{Result, anyhow};
+
+/// The `## IN:` and `## OUT:` lines of a source, checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Streams {
+    /// The input values, if any `## IN:` line gives them.
+    pub inputs: Option<Vec<u32>>,
+    /// The expected outputs as `-e` arguments: the flat `## OUT:` lines
+    /// joined into one, then each node-scoped line.
+    pub expected: Vec<String>,
+    /// Line of the first `## IN:` and `## OUT:`, for notices.
+    pub inputs_line: Option<usize>,
+    pub expected_line: Option<usize>,
+}
+
+/// The streams `code` declares. A value either flag would reject fails with
+/// its line.
+pub fn scan(code: &str) -> Result<Streams> {
+    let mut streams = Streams::default();
+    let mut flat: Vec<&str> = Vec::new();
+    for (idx, line) in code.lines().enumerate() {
+        let line_no = idx + 1;
+        let Some((key, values)) = directive(line) else {
+            continue;
+        };
+        let at = |e: anyhow::Error| anyhow!("line {}: ## {}: {}", line_no, key, e);
+        if key == "IN" {
+            streams.inputs.get_or_insert_with(Vec::new).extend(inputs::parse_values(values).map_err(at)?);
+            streams.inputs_line.get_or_insert(line_no);
+            continue;
+        }
+        streams.expected_line.get_or_insert(line_no);
+        match values.split_once('=').filter(|(node, _)| assembler::parse_node_coords(node).is_ok()) {
+            Some((_, outputs)) => {
+                inputs::parse_values(outputs).map_err(at)?;
+                streams.expected.push(values.to_string());
+            }
+            None => {
+                ExpectedSpec::parse(values).map_err(at)?;
+                flat.push(values);
+            }
+        }
+    }
+    if !flat.is_empty() {
+        streams.expected.insert(0, flat.join(", "));
+    }
+    Ok(streams)
+}
+
+/// The key (`IN` or `OUT`) and values of a `## KEY:` line, keys in any case.
+fn directive(line: &str) -> Option<(&'static str, &str)> {
+    let rest = line.trim_start().strip_prefix("##")?.trim_start();
+    let (key, values) = rest.split_once(':')?;
+    let key = ["IN", "OUT"].into_iter().find(|k| k.eq_ignore_ascii_case(key.trim_end()))?;
+    Some((key, values.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let code = "## IN: 1, 2\n## out: 2, 4\n##IN: 3\n# IN: 9\nNODE (0,0)\n## OUT: 6\n## Note: anything\n";
+        let streams = scan(code).unwrap();
+        assert_eq!(streams.inputs, Some(vec![1, 2, 3]));
+        assert_eq!(streams.expected, ["2, 4, 6"]);
+        assert_eq!((streams.inputs_line, streams.expected_line), (Some(1), Some(2)));
+
+        let nodes = scan("## OUT: 0,1=5\n## OUT: 1,1 = 6, 7\n").unwrap();
+        assert_eq!((nodes.inputs, nodes.expected), (None, vec!["0,1=5".to_string(), "1,1 = 6, 7".to_string()]));
+        assert_eq!(scan("NODE (0,0)\n").unwrap(), Streams::default());
+        assert_eq!(scan("## IN:\n").unwrap().inputs, Some(vec![]));
+
+        let err = |code: &str| scan(code).unwrap_err().to_string();
+        assert_eq!(err("\n## IN: 1, x\n"), "line 2: ## IN: 'x' is not a value");
+        assert!(err("## OUT: 1, y\n").starts_with("line 1: ## OUT: 'y' is not a value"));
+        assert_eq!(err("## OUT: 1,1=z\n"), "line 1: ## OUT: 'z' is not a value");
+    }
+}
@@ -0,0 +1,245 @@
+//! Opt-in local usage statistics: one JSON line per assemble, prove or
+//! verify-proof run in `.zk100/stats.jsonl` of the project directory, and the
+//! `stats-log summarize` table over it. Nothing here touches the network.
+//!
+//! Logging is on with `--stats-log` or with `stats_log = true` in
+//! `.zk100/config.toml`. Each record is appended with a single `write` on a
+//! file opened with `O_APPEND`, so runs in parallel (say, from the batch
+//! runner) never interleave partial lines.
+
+use crate::built_info;
+use crate::config;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+pub const STATS_DIR: &str = ".zk100";
+pub const STATS_FILE: &str = "stats.jsonl";
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// What a command learned about the program it ran on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Facts {
+    pub instructions: Option<usize>,
+    pub program_root: Option<String>,
+    pub cases_passed: Option<u32>,
+    pub cases_failed: Option<u32>,
+}
+
+/// One assemble, prove or verify-proof run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct StatsRecord {
+    pub command: String,
+    /// Seconds since the Unix epoch.
+    pub started_unix: u64,
+    pub duration_ms: u64,
+    pub ok: bool,
+    #[serde(default)]
+    pub instructions: Option<usize>,
+    #[serde(default)]
+    pub program_root: Option<String>,
+    #[serde(default)]
+    pub cases_passed: Option<u32>,
+    #[serde(default)]
+    pub cases_failed: Option<u32>,
+    pub tool: String,
+    pub version: String,
+    #[serde(default)]
+    pub git_hash: Option<String>,
+}
+
+impl StatsRecord {
+    pub fn new(command: &str, started_unix: u64, duration_ms: u64, ok: bool, facts: Facts) -> Self {
+        StatsRecord {
+            command: command.to_string(),
+            started_unix,
+            duration_ms,
+            ok,
+            instructions: facts.instructions,
+            program_root: facts.program_root,
+            cases_passed: facts.cases_passed,
+            cases_failed: facts.cases_failed,
+            tool: built_info::PKG_NAME.to_string(),
+            version: built_info::PKG_VERSION.to_string(),
+            git_hash: built_info::GIT_COMMIT_HASH_SHORT.map(str::to_string),
+        }
+    }
+}
+
+pub fn log_path(project: &Path) -> PathBuf {
+    project.join(STATS_DIR).join(STATS_FILE)
+}
+
+/// Whether `.zk100/config.toml` under `project` sets `stats_log = true`.
+pub fn enabled_in(project: &Path) -> Result<bool> {
+    Ok(config::read_bool(&project.join(STATS_DIR).join(CONFIG_FILE), "stats_log")?.unwrap_or(false))
+}
+
+/// Append `record` to the project's log as one line.
+pub fn append(project: &Path, record: &StatsRecord) -> Result<()> {
+    let path = log_path(project);
+    fs::create_dir_all(path.parent().expect("log path has a parent"))?;
+    let line = serde_json::to_string(record)? + "\n";
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| anyhow!("cannot open {}: {}", path.display(), e))?;
+    // One write of the whole line: with O_APPEND the kernel places it at the
+    // end of the file in one piece.
+    file.write_all(line.as_bytes()).map_err(|e| anyhow!("cannot append to {}: {}", path.display(), e))
+}
+
+/// Records of a log file, in file order.
+pub fn read(path: &Path) -> Result<Vec<StatsRecord>> {
+    let text = fs::read_to_string(path).map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| serde_json::from_str(line).map_err(|e| anyhow!("{}:{}: {}", path.display(), idx + 1, e)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct DaySummary {
+    /// `YYYY-MM-DD`, UTC.
+    pub day: String,
+    pub runs: usize,
+    pub failures: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct CommandSummary {
+    pub command: String,
+    pub runs: usize,
+    pub failures: usize,
+    /// Mean duration of the successful runs.
+    pub avg_ms: Option<f64>,
+}
+
+/// Totals over a stats log.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct Summary {
+    pub runs: usize,
+    pub failures: usize,
+    pub failure_rate: f64,
+    /// Mean duration of successful `prove` runs.
+    pub avg_prove_ms: Option<f64>,
+    pub days: Vec<DaySummary>,
+    pub commands: Vec<CommandSummary>,
+}
+
+pub fn summarize(records: &[StatsRecord]) -> Summary {
+    let mut days: BTreeMap<String, DaySummary> = BTreeMap::new();
+    let mut commands: BTreeMap<&str, (CommandSummary, u64)> = BTreeMap::new();
+    for record in records {
+        let day = utc_date(record.started_unix);
+        let entry = days.entry(day.clone()).or_insert_with(|| DaySummary { day, ..Default::default() });
+        entry.runs += 1;
+        let (summary, total_ms) = commands
+            .entry(&record.command)
+            .or_insert_with(|| (CommandSummary { command: record.command.clone(), ..Default::default() }, 0));
+        summary.runs += 1;
+        if record.ok {
+            *total_ms += record.duration_ms;
+        } else {
+            entry.failures += 1;
+            summary.failures += 1;
+        }
+    }
+    let commands: Vec<CommandSummary> = commands
+        .into_values()
+        .map(|(mut summary, total_ms)| {
+            let ok = summary.runs - summary.failures;
+            summary.avg_ms = (ok > 0).then(|| total_ms as f64 / ok as f64);
+            summary
+        })
+        .collect();
+    let failures = commands.iter().map(|c| c.failures).sum();
+    Summary {
+        runs: records.len(),
+        failures,
+        failure_rate: if records.is_empty() { 0.0 } else { failures as f64 / records.len() as f64 },
+        avg_prove_ms: commands.iter().find(|c| c.command == "prove").and_then(|c| c.avg_ms),
+        days: days.into_values().collect(),
+        commands,
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.0} ms", v));
+        writeln!(f, "{:<12} {:>6} {:>9}", "day", "runs", "failures")?;
+        for day in &self.days {
+            writeln!(f, "{:<12} {:>6} {:>9}", day.day, day.runs, day.failures)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:<14} {:>6} {:>9} {:>12}", "command", "runs", "failures", "avg time")?;
+        for command in &self.commands {
+            writeln!(f, "{:<14} {:>6} {:>9} {:>12}", command.command, command.runs, command.failures, ms(command.avg_ms))?;
+        }
+        writeln!(f)?;
+        writeln!(f, "average prove time: {}", ms(self.avg_prove_ms))?;
+        write!(f, "failure rate: {:.1}% ({} of {} runs)", self.failure_rate * 100.0, self.failures, self.runs)
+    }
+}
+
+/// The UTC calendar date of a unix timestamp (Howard Hinnant's civil_from_days).
+fn utc_date(unix: u64) -> String {
+    let days = (unix / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(command: &str, started_unix: u64, duration_ms: u64, ok: bool) -> StatsRecord {
+        StatsRecord::new(command, started_unix, duration_ms, ok, Facts::default())
+    }
+
+    #[test]
+    fn test_utc_date() {
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(951_782_400), "2000-02-29");
+        assert_eq!(utc_date(1_790_000_000), "2026-09-21");
+    }
+
+    #[test]
+    fn test_summary_math() {
+        let day = 1_790_000_000;
+        let records = [
+            record("prove", day, 1_000, true),
+            record("prove", day + 60, 3_000, true),
+            record("prove", day + 120, 50, false),
+            record("assemble", day + 86_400, 10, true),
+        ];
+        let summary = summarize(&records);
+        assert_eq!((summary.runs, summary.failures), (4, 1));
+        assert_eq!(summary.failure_rate, 0.25);
+        // The failed run's 50 ms does not count.
+        assert_eq!(summary.avg_prove_ms, Some(2_000.0));
+        let days: Vec<(&str, usize, usize)> = summary.days.iter().map(|d| (d.day.as_str(), d.runs, d.failures)).collect();
+        assert_eq!(days, [("2026-09-21", 3, 1), ("2026-09-22", 1, 0)]);
+        assert_eq!(summary.commands[0].command, "assemble");
+        assert_eq!(summary.commands[0].avg_ms, Some(10.0));
+        assert!(summary.to_string().ends_with("failure rate: 25.0% (1 of 4 runs)"));
+        assert_eq!(summarize(&[]).failure_rate, 0.0);
+    }
+}
@@ -0,0 +1,227 @@
+//! Golden-file harness for crates that build on the assembler (`testkit`
+//! feature).
+//!
+//! A case is a directory holding `program.asm`, optional `inputs.txt` and
+//! `expected.txt` (numbers separated by commas or whitespace), and a `golden/`
+//! directory with the checked-in outputs:
+//!
+//! - `golden/prog_words.txt` – one encoded word per line, as `0x%08x`
+//! - `golden/program_root.txt` – the SHA-256 program Merkle root in hex
+//! - `golden/args.json` – the generated Cairo arguments, pretty-printed
+//!
+//! `run_and_compare` regenerates all three and reports a line diff for each
+//! mismatch. With `UPDATE_GOLDENS=1` in the environment it rewrites the
+//! golden files instead, so blessing a change is one test run.
+
+use crate::{assembler, cairo_abi, felt_bytes, merkle};
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Set to a non-empty value other than `0` to rewrite golden files.
+pub const UPDATE_ENV: &str = "UPDATE_GOLDENS";
+
+const GOLDEN_FILES: [&str; 3] = ["prog_words.txt", "program_root.txt", "args.json"];
+
+#[derive(Debug, Clone)]
+pub struct GoldenCase {
+    pub name: String,
+    pub dir: PathBuf,
+    pub source: String,
+    pub inputs: Vec<u32>,
+    pub expected: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub file: String,
+    pub diff: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GoldenReport {
+    pub case: String,
+    pub mismatches: Vec<Mismatch>,
+    /// Golden files rewritten because `UPDATE_GOLDENS` was set.
+    pub updated: Vec<String>,
+}
+
+impl GoldenReport {
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for GoldenReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_ok() {
+            write!(f, "{}: ok", self.case)?;
+            if !self.updated.is_empty() {
+                write!(f, " (updated {})", self.updated.join(", "))?;
+            }
+            return Ok(());
+        }
+        writeln!(f, "{}: {} golden file(s) differ", self.case, self.mismatches.len())?;
+        for mismatch in &self.mismatches {
+            writeln!(f, "--- golden/{}", mismatch.file)?;
+            write!(f, "{}", mismatch.diff)?;
+        }
+        write!(f, "(rerun with {}=1 to accept the new output)", UPDATE_ENV)
+    }
+}
+
+fn read_numbers(path: &Path) -> Result<Vec<u32>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    fs::read_to_string(path)?
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<u32>()
+                .or_else(|_| s.parse::<i32>().map(|n| n as u32))
+                .map_err(|_| anyhow!("{}: '{}' is not a number", path.display(), s))
+        })
+        .collect()
+}
+
+/// Line diff of `expected` vs `actual`, showing only differing lines.
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut out = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        let (e, a) = (expected.get(i), actual.get(i));
+        if e == a {
+            continue;
+        }
+        if let Some(e) = e {
+            out.push_str(&format!("{:>4} - {}\n", i + 1, e));
+        }
+        if let Some(a) = a {
+            out.push_str(&format!("{:>4} + {}\n", i + 1, a));
+        }
+    }
+    out
+}
+
+fn updating() -> bool {
+    std::env::var(UPDATE_ENV).is_ok_and(|v| !v.is_empty() && v != "0")
+}
+
+impl GoldenCase {
+    pub fn from_dir(path: impl AsRef<Path>) -> Result<Self> {
+        let dir = path.as_ref().to_path_buf();
+        let program = dir.join("program.asm");
+        let source = fs::read_to_string(&program)
+            .map_err(|e| anyhow!("cannot read {}: {}", program.display(), e))?;
+        Ok(GoldenCase {
+            name: dir.file_name().map_or_else(|| dir.display().to_string(), |n| n.to_string_lossy().into_owned()),
+            inputs: read_numbers(&dir.join("inputs.txt"))?,
+            expected: read_numbers(&dir.join("expected.txt"))?,
+            source,
+            dir,
+        })
+    }
+
+    /// Regenerate every golden file's contents, keyed by file name.
+    pub fn render(&self) -> Result<Vec<(&'static str, String)>> {
+        let programs = assembler::parse_assembly(&self.source)?;
+        let words = assembler::encode_programs(&programs)?;
+        let root = merkle::compute_program_merkle_root(&programs)?;
+        let args = cairo_abi::generate_args(&self.inputs, &self.expected, &words)?;
+
+        let prog_words: String = words.iter().map(|w| format!("0x{:08x}\n", w)).collect();
+        let rendered = [
+            prog_words,
+            format!("{}\n", felt_bytes::hex32(&felt_bytes::bytes32(&root)?)),
+            serde_json::to_string_pretty(&args)? + "\n",
+        ];
+        Ok(GOLDEN_FILES.into_iter().zip(rendered).collect())
+    }
+
+    /// Compare against `golden/`, or rewrite it when `UPDATE_GOLDENS` is set.
+    pub fn run_and_compare(&self) -> Result<GoldenReport> {
+        let golden_dir = self.dir.join("golden");
+        let update = updating();
+        let mut report = GoldenReport {
+            case: self.name.clone(),
+            ..Default::default()
+        };
+
+        for (file, actual) in self.render()? {
+            let path = golden_dir.join(file);
+            let expected = fs::read_to_string(&path).ok();
+            if expected.as_deref() == Some(actual.as_str()) {
+                continue;
+            }
+            if update {
+                fs::create_dir_all(&golden_dir)?;
+                fs::write(&path, &actual)?;
+                report.updated.push(file.to_string());
+            } else {
+                let diff = match expected {
+                    Some(expected) => line_diff(&expected, &actual),
+                    None => "     missing; the regenerated file would be:\n".to_string() + &line_diff("", &actual),
+                };
+                report.mismatches.push(Mismatch { file: file.to_string(), diff });
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Run every case directory directly under `root`, in name order.
+pub fn run_dir(root: impl AsRef<Path>) -> Result<Vec<GoldenReport>> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(root.as_ref())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.join("program.asm").is_file())
+        .collect();
+    dirs.sort();
+    dirs.iter()
+        .map(|dir| GoldenCase::from_dir(dir)?.run_and_compare())
+        .collect()
+}
+
+/// Panic with the combined report if any case under `root` differs.
+pub fn assert_goldens(root: impl AsRef<Path>) {
+    let reports = run_dir(root.as_ref())
+        .unwrap_or_else(|e| panic!("golden cases under {}: {}", root.as_ref().display(), e));
+    assert!(!reports.is_empty(), "no golden cases under {}", root.as_ref().display());
+    let failures: Vec<String> = reports.iter().filter(|r| !r.is_ok()).map(|r| r.to_string()).collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_diff_shows_only_changes() {
+        let diff = line_diff("a\nb\nc\n", "a\nB\nc\nd\n");
+        assert_eq!(diff, "   2 - b\n   2 + B\n   4 + d\n");
+    }
+
+    #[test]
+    fn test_mismatch_report() {
+        let dir = std::env::temp_dir().join(format!("zk100-golden-{}", std::process::id()));
+        fs::create_dir_all(dir.join("golden")).unwrap();
+        fs::write(dir.join("program.asm"), "NODE (0,0)\nNOP\n").unwrap();
+        fs::write(dir.join("inputs.txt"), "1, 2\n").unwrap();
+        fs::write(dir.join("golden/prog_words.txt"), "0x00000001\n").unwrap();
+
+        let case = GoldenCase::from_dir(&dir).unwrap();
+        assert_eq!(case.inputs, [1, 2]);
+        let report = case.run_and_compare().unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        if updating() {
+            return;
+        }
+        let files: Vec<&str> = report.mismatches.iter().map(|m| m.file.as_str()).collect();
+        assert_eq!(files, GOLDEN_FILES);
+        assert!(report.mismatches[0].diff.contains("   2 + 0x000c0201"), "{}", report);
+        assert!(report.to_string().contains("UPDATE_GOLDENS=1"));
+    }
+}
@@ -0,0 +1,496 @@
+//! Converting TIS-100 save files into zk-100 assembly.
+//!
+//! A save is a list of `@N` sections, one per working (non-damaged) compute
+//! node of the puzzle layout in row-major order, each holding that node's
+//! program. Only the part of TIS-100 that the 2x2 guest can run the same way
+//! is converted:
+//!
+//! - the layout has two rows, the single input stream enters the top of
+//!   (0,0) and the single output stream leaves the bottom of (1,1); `UP`
+//!   reads in (0,0) become `IN` and `DOWN` writes in (1,1) become `OUT`;
+//! - every node with code lies in the top-left 2x2;
+//! - no `ANY`, `LAST`, `JRO` or `HCF`, and literals fit in 0..=255;
+//! - no port-to-port `MOV` and no `MOV UP, <port>` in the input node, both of
+//!   which the guest executes differently (the value is dropped, or input is
+//!   consumed while blocked).
+//!
+//! Anything outside that subset fails with `Unsupported`; a save that is not
+//! valid TIS-100 fails with an ordinary error.
+//!
+//! The same subset, written as zk-100 source, is what `--tis100` holds a
+//! program to (`ParseOptions::tis100`): the assembler reads each instruction
+//! with `op`, `Strict::src` and `Strict::dst` instead of the zk-100 operand
+//! parser, and `report` groups what they reject by node.
+
+use crate::abi::IoNodes;
+use crate::assembler::{self, Diagnostic, NodeRanges};
+use crate::instruction::{Dst, Op, PortTag, Src};
+use crate::literals::Operand;
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// The save uses a feature the 2x2 grid cannot express.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported(pub String);
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unsupported: {}", self.0)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+fn unsupported(msg: impl Into<String>) -> anyhow::Error {
+    Unsupported(msg.into()).into()
+}
+
+/// Where a puzzle's compute nodes and I/O streams are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub rows: usize,
+    pub cols: usize,
+    /// Damaged nodes have no `@N` section.
+    pub damaged: Vec<(usize, usize)>,
+    /// Columns whose top node receives an input stream.
+    pub input_cols: Vec<usize>,
+    /// Columns whose bottom node emits an output stream.
+    pub output_cols: Vec<usize>,
+}
+
+impl Layout {
+    /// Row-major coordinates of the working nodes, i.e. of `@0`, `@1`, ...
+    pub fn working_nodes(&self) -> Vec<(usize, usize)> {
+        (0..self.rows)
+            .flat_map(|r| (0..self.cols).map(move |c| (r, c)))
+            .filter(|node| !self.damaged.contains(node))
+            .collect()
+    }
+
+    fn check_streams(&self) -> Result<()> {
+        if self.input_cols != [0] {
+            return Err(unsupported(format!(
+                "input streams at columns {:?}; only one stream into (0,0) is supported",
+                self.input_cols
+            )));
+        }
+        if self.rows != 2 || self.output_cols != [1] {
+            return Err(unsupported(format!(
+                "output streams leave row {} at columns {:?}; only one stream out of (1,1) is supported",
+                self.rows.saturating_sub(1),
+                self.output_cols
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// One `@N` section: its number and `(line number, text)` pairs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveNode {
+    pub index: usize,
+    pub lines: Vec<(usize, String)>,
+}
+
+/// Split a save into its `@N` sections.
+pub fn parse_save(text: &str) -> Result<Vec<SaveNode>> {
+    let mut nodes: Vec<SaveNode> = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let trimmed = line.trim();
+        if let Some(number) = trimmed.strip_prefix('@') {
+            let index: usize = number
+                .parse()
+                .map_err(|_| anyhow!("line {}: bad node header '{}'", line_no, trimmed))?;
+            if nodes.iter().any(|n| n.index == index) {
+                return Err(anyhow!("line {}: node @{} appears twice", line_no, index));
+            }
+            nodes.push(SaveNode { index, lines: Vec::new() });
+        } else if let Some(node) = nodes.last_mut() {
+            node.lines.push((line_no, line.to_string()));
+        } else if !trimmed.is_empty() {
+            return Err(anyhow!("line {}: code before the first @N node header", line_no));
+        }
+    }
+    if nodes.is_empty() {
+        return Err(anyhow!("no @N node sections; not a TIS-100 save"));
+    }
+    Ok(nodes)
+}
+
+fn is_label_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// One source line split into an optional label and instruction words.
+fn split_line(line: &str) -> (Option<&str>, Vec<&str>) {
+    let code = line.split(['#', ';']).next().unwrap_or("").trim();
+    let code = code.strip_prefix('!').unwrap_or(code).trim();
+    let (label, rest) = match code.split_once(':') {
+        Some((label, rest)) => (Some(label.trim()), rest),
+        None => (None, code),
+    };
+    let words = rest
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .collect();
+    (label, words)
+}
+
+struct NodeContext<'a> {
+    node: (usize, usize),
+    labels: &'a BTreeSet<String>,
+}
+
+impl NodeContext<'_> {
+    fn port(&self, word: &str) -> Option<&'static str> {
+        match word {
+            "UP" => Some("UP"),
+            "DOWN" => Some("DOWN"),
+            "LEFT" => Some("LEFT"),
+            "RIGHT" => Some("RIGHT"),
+            _ => None,
+        }
+    }
+
+    fn src(&self, word: &str) -> Result<String> {
+        let upper = word.to_ascii_uppercase();
+        match upper.as_str() {
+            "ACC" | "NIL" => Ok(upper),
+            "ANY" | "LAST" => Err(unsupported(format!("{} port", upper))),
+            "DOWN" if self.node == (1, 1) => Err(unsupported("reading from the output stream")),
+            "UP" if self.node == (0, 0) => Ok("IN".to_string()),
+            _ => {
+                if let Some(port) = self.port(&upper) {
+                    return Ok(format!("P:{}", port));
+                }
+                let value: i64 = word.parse().map_err(|_| anyhow!("'{}' is not a valid source", word))?;
+                if !(0..=255).contains(&value) {
+                    return Err(unsupported(format!("literal {} outside 0..=255", value)));
+                }
+                Ok(value.to_string())
+            }
+        }
+    }
+
+    fn dst(&self, word: &str) -> Result<String> {
+        let upper = word.to_ascii_uppercase();
+        match upper.as_str() {
+            "ACC" | "NIL" => Ok(upper),
+            "ANY" | "LAST" => Err(unsupported(format!("{} port", upper))),
+            "UP" if self.node == (0, 0) => Err(unsupported("writing to the input stream")),
+            "DOWN" if self.node == (1, 1) => Ok("OUT".to_string()),
+            _ => match self.port(&upper) {
+                Some(port) => Ok(format!("P:{}", port)),
+                None => Err(anyhow!("'{}' is not a valid destination", word)),
+            },
+        }
+    }
+
+    fn target(&self, word: &str) -> Result<String> {
+        if self.labels.contains(word) {
+            Ok(word.to_string())
+        } else {
+            Err(anyhow!("jump to undefined label '{}'", word))
+        }
+    }
+
+    fn instruction(&self, words: &[&str]) -> Result<String> {
+        let (mnemonic, operands) = words.split_first().expect("caller skips empty lines");
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let arity = |n: usize| -> Result<()> {
+            if operands.len() == n {
+                Ok(())
+            } else {
+                Err(anyhow!("{} takes {} operand(s), found {}", mnemonic, n, operands.len()))
+            }
+        };
+        match mnemonic.as_str() {
+            "NOP" | "SWP" | "SAV" | "NEG" => {
+                arity(0)?;
+                Ok(mnemonic)
+            }
+            "ADD" | "SUB" => {
+                arity(1)?;
+                Ok(format!("{} {}", mnemonic, self.src(operands[0])?))
+            }
+            "JMP" | "JEZ" | "JNZ" | "JGZ" | "JLZ" => {
+                arity(1)?;
+                let op = if mnemonic == "JEZ" { "JZ" } else { &mnemonic };
+                Ok(format!("{} {}", op, self.target(operands[0])?))
+            }
+            "MOV" => {
+                arity(2)?;
+                let src = self.src(operands[0])?;
+                let dst = self.dst(operands[1])?;
+                if dst.starts_with("P:") && src.starts_with("P:") {
+                    return Err(unsupported(format!(
+                        "port-to-port MOV {}, {} (the guest drops the value)",
+                        operands[0], operands[1]
+                    )));
+                }
+                if dst.starts_with("P:") && src == "IN" {
+                    return Err(unsupported(format!(
+                        "MOV UP, {} in the input node (the guest consumes input while blocked)",
+                        operands[1]
+                    )));
+                }
+                Ok(format!("MOV {}, {}", src, dst))
+            }
+            "JRO" | "HCF" => Err(unsupported(mnemonic)),
+            _ => Err(anyhow!("unknown instruction '{}'", mnemonic)),
+        }
+    }
+}
+
+/// Convert a save for a puzzle with `layout` into zk-100 assembly.
+pub fn convert(save: &str, layout: &Layout) -> Result<String> {
+    let nodes = parse_save(save)?;
+    let working = layout.working_nodes();
+    layout.check_streams()?;
+
+    let mut out = String::new();
+    for save_node in &nodes {
+        let &node = working.get(save_node.index).ok_or_else(|| {
+            anyhow!("node @{} does not exist; the layout has {} working nodes", save_node.index, working.len())
+        })?;
+        let lines: Vec<(usize, Option<&str>, Vec<&str>)> = save_node
+            .lines
+            .iter()
+            .map(|(line_no, text)| {
+                let (label, words) = split_line(text);
+                (*line_no, label, words)
+            })
+            .collect();
+        if lines.iter().all(|(_, label, words)| label.is_none() && words.is_empty()) {
+            continue;
+        }
+        if node.0 >= 2 || node.1 >= 2 {
+            return Err(unsupported(format!(
+                "node @{} at ({},{}) has code outside the 2x2 grid",
+                save_node.index, node.0, node.1
+            )));
+        }
+
+        let mut labels = BTreeSet::new();
+        for (line_no, label, _) in &lines {
+            if let Some(label) = label {
+                if !is_label_name(label) {
+                    return Err(unsupported(format!("line {}: label name '{}'", line_no, label)));
+                }
+                labels.insert(label.to_string());
+            }
+        }
+        let context = NodeContext { node, labels: &labels };
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("# TIS-100 node @{}\nNODE ({},{})\n", save_node.index, node.0, node.1));
+        for (line_no, label, words) in lines {
+            if let Some(label) = label {
+                out.push_str(&format!("{}:\n", label));
+            }
+            if !words.is_empty() {
+                let inst = context.instruction(&words).map_err(|e| match e.downcast::<Unsupported>() {
+                    Ok(Unsupported(msg)) => unsupported(format!("line {}: {}", line_no, msg)),
+                    Err(e) => anyhow!("line {}: {}", line_no, e),
+                })?;
+                out.push_str(&inst);
+                out.push('\n');
+            }
+        }
+    }
+    if out.is_empty() {
+        return Err(anyhow!("save has no code"));
+    }
+    Ok(out)
+}
+
+/// The most instructions a TIS-100 node holds.
+pub const MAX_NODE_INSTRUCTIONS: usize = 15;
+
+/// The values a TIS-100 literal may take.
+pub const VALUES: RangeInclusive<i64> = -999..=999;
+
+/// The op a TIS-100 mnemonic names, in any case. zk-100's own mnemonics and
+/// the dialect's aliases are extensions; `JRO` and `HCF` are TIS-100 but have
+/// no zk-100 op.
+pub(crate) fn op(mnemonic: &str) -> Result<Op> {
+    let upper = mnemonic.to_ascii_uppercase();
+    Ok(match upper.as_str() {
+        "NOP" => Op::Nop,
+        "SWP" => Op::Swp,
+        "SAV" => Op::Sav,
+        "NEG" => Op::Neg,
+        "ADD" => Op::Add,
+        "SUB" => Op::Sub,
+        "JMP" => Op::Jmp,
+        "JEZ" => Op::Jz,
+        "JNZ" => Op::Jnz,
+        "JGZ" => Op::Jgz,
+        "JLZ" => Op::Jlz,
+        "MOV" => Op::Mov,
+        "JRO" | "HCF" => return Err(anyhow!("{} has no zk-100 op", upper)),
+        "JZ" => return Err(anyhow!("JZ is a zk-100 mnemonic; TIS-100 writes JEZ")),
+        "HLT" => return Err(anyhow!("HLT is a zk-100 extension; TIS-100 nodes never halt")),
+        _ => return Err(anyhow!("'{}' is not a TIS-100 instruction", mnemonic)),
+    })
+}
+
+/// How the operands of one node read under `--tis100`: bare ports, with the
+/// input stream above the input node and the output stream below the output
+/// node, as the converter maps them.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Strict {
+    pub node: (usize, usize),
+    pub io: IoNodes,
+}
+
+impl Strict {
+    /// A source operand. `LAST` reads as `NIL`, which is what it is in
+    /// TIS-100 until an `ANY` transfer sets it, and no program here has one.
+    /// A literal stays as written for `literals::lower`.
+    pub(crate) fn src(&self, word: &str) -> Result<Operand> {
+        let upper = word.to_ascii_uppercase();
+        match upper.as_str() {
+            "ACC" => Ok(Operand::Src(Src::Acc)),
+            "NIL" | "LAST" => Ok(Operand::Src(Src::Nil)),
+            "UP" if self.node == self.io.in_node => Ok(Operand::Src(Src::In)),
+            "DOWN" if self.node == self.io.out_node => Err(anyhow!("DOWN is the output stream in node {}; it cannot be read", self.node_text())),
+            _ => self.port_or_extension(word, &upper).map(|port| Operand::Src(Src::P(port))).or_else(|e| match word.parse::<i64>() {
+                Ok(value) if VALUES.contains(&value) => Ok(Operand::Lit(value)),
+                Ok(value) => Err(anyhow!("{} is outside TIS-100's {}..={}", value, VALUES.start(), VALUES.end())),
+                Err(_) => Err(e),
+            }),
+        }
+    }
+
+    /// A destination operand, with `LAST` as for `src`.
+    pub(crate) fn dst(&self, word: &str) -> Result<Dst> {
+        let upper = word.to_ascii_uppercase();
+        match upper.as_str() {
+            "ACC" => Ok(Dst::Acc),
+            "NIL" | "LAST" => Ok(Dst::Nil),
+            "DOWN" if self.node == self.io.out_node => Ok(Dst::Out),
+            "UP" if self.node == self.io.in_node => Err(anyhow!("UP is the input stream in node {}; it cannot be written", self.node_text())),
+            _ => self.port_or_extension(word, &upper).map(Dst::P),
+        }
+    }
+
+    /// Why `MOV src, dst` would run differently in the guest, as the
+    /// converter refuses it.
+    pub(crate) fn mov(&self, src: Operand, dst: Dst) -> Result<()> {
+        match (src, dst) {
+            (Operand::Src(Src::P(_)), Dst::P(_)) => Err(anyhow!("port-to-port MOV drops the value in the guest; MOV through ACC")),
+            (Operand::Src(Src::In), Dst::P(_)) => Err(anyhow!("MOV UP to a port consumes input while blocked in the guest; MOV through ACC")),
+            _ => Ok(()),
+        }
+    }
+
+    /// The port a bare direction names, or why `word` is not one.
+    fn port_or_extension(&self, word: &str, upper: &str) -> Result<PortTag> {
+        if let Ok(port) = upper.parse() {
+            return Ok(port);
+        }
+        Err(match upper {
+            "ANY" => anyhow!("ANY has no zk-100 port"),
+            "IN" => anyhow!("IN is a zk-100 operand; TIS-100 reads the input stream as UP in node {}", tuple(self.io.in_node)),
+            "OUT" => anyhow!("OUT is a zk-100 operand; TIS-100 writes the output stream as DOWN in node {}", tuple(self.io.out_node)),
+            _ if upper.starts_with("P:") => anyhow!("the P: prefix is zk-100 syntax; TIS-100 writes {}", &word[2..]),
+            _ if word.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') && word.parse::<i64>().is_err() => {
+                anyhow!("'{}' is not a TIS-100 literal; TIS-100 writes plain decimal", word)
+            }
+            _ => anyhow!("'{}' is not a TIS-100 operand (constants and expressions are zk-100 extensions)", word),
+        })
+    }
+
+    fn node_text(&self) -> String {
+        tuple(self.node)
+    }
+}
+
+fn tuple(node: (usize, usize)) -> String {
+    format!("({},{})", node.0, node.1)
+}
+
+/// The errors of a `--tis100` parse as one error, grouped under the node
+/// whose block each line is in. `ranges` are the parse's `node_ranges`.
+pub(crate) fn report(errors: &[Diagnostic], ranges: &NodeRanges) -> anyhow::Error {
+    let mut groups: BTreeMap<Option<(usize, usize)>, Vec<&Diagnostic>> = BTreeMap::new();
+    for error in errors {
+        groups.entry(assembler::node_at(ranges, error.line)).or_default().push(error);
+    }
+    let mut text = format!(
+        "{} {} under --tis100",
+        errors.len(),
+        if errors.len() == 1 { "problem" } else { "problems" }
+    );
+    for (node, errors) in groups {
+        match node {
+            Some(node) => text.push_str(&format!("\nnode {}:", tuple(node))),
+            None => text.push_str("\nbefore the first NODE:"),
+        }
+        for error in errors {
+            text.push_str(&format!("\n  {}", error));
+        }
+    }
+    anyhow!(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> Layout {
+        Layout { rows: 2, cols: 2, damaged: vec![], input_cols: vec![0], output_cols: vec![1] }
+    }
+
+    #[test]
+    fn test_convert_maps_streams_and_labels() {
+        let save = "@0\nSTART: MOV UP ACC # read\nJEZ START\nMOV ACC, RIGHT\n\n@1\nMOV LEFT, DOWN\n\n@2\n\n@3\n!MOV UP, DOWN\n";
+        let err = convert(save, &layout()).unwrap_err();
+        assert!(err.downcast_ref::<Unsupported>().is_some(), "{}", err);
+        assert!(err.to_string().contains("line 7: port-to-port"), "{}", err);
+
+        let save = save.replace("MOV LEFT, DOWN", "MOV LEFT ACC\nMOV ACC DOWN");
+        let asm = convert(&save, &layout()).unwrap();
+        assert_eq!(
+            asm,
+            "# TIS-100 node @0\nNODE (0,0)\nSTART:\nMOV IN, ACC\nJZ START\nMOV ACC, P:RIGHT\n\n\
+             # TIS-100 node @1\nNODE (0,1)\nMOV P:LEFT, ACC\nMOV ACC, P:DOWN\n\n\
+             # TIS-100 node @3\nNODE (1,1)\nMOV P:UP, OUT\n"
+        );
+        crate::assembler::parse_assembly(&asm).unwrap();
+    }
+
+    #[test]
+    fn test_damaged_nodes_shift_numbering() {
+        let layout = Layout { cols: 3, damaged: vec![(0, 1)], ..layout() };
+        assert_eq!(layout.working_nodes(), [(0, 0), (0, 2), (1, 0), (1, 1), (1, 2)]);
+        let err = convert("@1\nNOP\n", &layout).unwrap_err().to_string();
+        assert!(err.contains("@1 at (0,2) has code outside"), "{}", err);
+    }
+
+    #[test]
+    fn test_malformed_saves_are_errors_not_unsupported() {
+        for (save, message) in [
+            ("MOV UP ACC\n@0\n", "before the first @N"),
+            ("@x\n", "bad node header"),
+            ("@0\n@0\n", "appears twice"),
+            ("@0\nFLY ACC\n", "unknown instruction"),
+            ("@0\nJMP NOWHERE\n", "undefined label"),
+            ("@0\nMOV ACC\n", "takes 2 operand(s)"),
+            ("@9\nNOP\n", "does not exist"),
+            ("", "not a TIS-100 save"),
+        ] {
+            let err = convert(save, &layout()).unwrap_err();
+            assert!(err.downcast_ref::<Unsupported>().is_none(), "{:?}: {}", save, err);
+            assert!(err.to_string().contains(message), "{:?}: {}", save, err);
+        }
+        let err = convert("@0\nADD -1\n", &layout()).unwrap_err();
+        assert!(err.downcast_ref::<Unsupported>().is_some());
+    }
+}
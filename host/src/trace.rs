@@ -0,0 +1,275 @@
+//! Timing spans for the assembly pipeline, built on `tracing`.
+//!
+//! Library functions open a span for each phase with `trace::span!`, naming
+//! the numeric fields (instruction counts, word counts) they fill in later.
+//! `TimingAggregator` is a `tracing_subscriber` layer that sums span durations
+//! and fields per phase for `--timings`. With the `tracing` feature disabled
+//! spans are zero-cost no-ops and the aggregator stays empty.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+#[cfg(feature = "tracing")]
+use tracing::field::{Field, Visit};
+#[cfg(feature = "tracing")]
+use tracing::span::{Attributes, Id, Record};
+#[cfg(feature = "tracing")]
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+#[cfg(feature = "tracing")]
+use tracing_subscriber::registry::LookupSpan;
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing;
+
+/// Open a span named after the pipeline phase it measures, declaring the
+/// fields it records: `trace::span!("encode_programs", words)`.
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __trace_span {
+    ($name:literal $(, $field:ident)* $(,)?) => {
+        $crate::trace::Span::new($crate::trace::tracing::info_span!(
+            $name,
+            $($field = $crate::trace::tracing::field::Empty),*
+        ))
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __trace_span {
+    ($name:literal $(, $field:ident)* $(,)?) => {
+        $crate::trace::Span::disabled()
+    };
+}
+
+pub use crate::__trace_span as span;
+
+/// An open timing span, entered until it is dropped.
+pub struct Span {
+    #[cfg(feature = "tracing")]
+    inner: tracing::span::EnteredSpan,
+}
+
+impl Span {
+    #[cfg(feature = "tracing")]
+    #[doc(hidden)]
+    pub fn new(span: tracing::Span) -> Self {
+        Span { inner: span.entered() }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[doc(hidden)]
+    pub fn disabled() -> Self {
+        Span {}
+    }
+
+    /// Fill in a declared field, e.g. `span.record("instructions", 12)`.
+    pub fn record(&self, key: &'static str, value: u64) {
+        #[cfg(feature = "tracing")]
+        self.inner.record(key, value);
+        #[cfg(not(feature = "tracing"))]
+        let _ = (key, value);
+    }
+}
+
+/// Per-phase totals collected by `TimingAggregator`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct PhaseSummary {
+    pub name: String,
+    pub calls: u64,
+    /// Total time in microseconds.
+    pub total_us: u128,
+    pub mean_us: u128,
+    /// Sum of each numeric field over all calls.
+    pub fields: BTreeMap<String, u64>,
+}
+
+/// The `--timings-json` summary of one run.
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema), schemars(deny_unknown_fields))]
+pub struct Timings {
+    /// One entry per phase, in the order each first ran.
+    pub phases: Vec<PhaseSummary>,
+}
+
+/// Layer that sums span durations per phase for `--timings`. Clones share
+/// their totals, so one can be installed while another reads the summary.
+#[derive(Clone, Default)]
+pub struct TimingAggregator {
+    phases: Arc<Mutex<Vec<PhaseSummary>>>,
+}
+
+/// Install `aggregator` as the process-wide subscriber. Returns false if one
+/// was already set, or when spans are compiled out.
+pub fn set_global_aggregator(aggregator: &TimingAggregator) -> bool {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(aggregator.clone())).is_ok()
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = aggregator;
+        false
+    }
+}
+
+/// Run `f` with `aggregator` receiving every span opened on this thread.
+#[cfg(feature = "tracing")]
+pub fn with_aggregator<R>(aggregator: &TimingAggregator, f: impl FnOnce() -> R) -> R {
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(aggregator.clone()), f)
+}
+
+/// Start time and numeric fields of an open span, kept in its extensions.
+#[cfg(feature = "tracing")]
+struct Timing {
+    start: Instant,
+    fields: Vec<(&'static str, u64)>,
+}
+
+#[cfg(feature = "tracing")]
+impl Visit for Timing {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.push((field.name(), value));
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+#[cfg(feature = "tracing")]
+impl<S> Layer<S> for TimingAggregator
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut timing = Timing { start: Instant::now(), fields: Vec::new() };
+        attrs.record(&mut timing);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(timing);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<Timing>() {
+                values.record(timing);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(timing) = span.extensions_mut().remove::<Timing>() else { return };
+        let mut phases = self.phases.lock().unwrap();
+        let idx = match phases.iter().position(|p| p.name == span.name()) {
+            Some(idx) => idx,
+            None => {
+                phases.push(PhaseSummary {
+                    name: span.name().to_string(),
+                    ..Default::default()
+                });
+                phases.len() - 1
+            }
+        };
+        let phase = &mut phases[idx];
+        phase.calls += 1;
+        phase.total_us += timing.start.elapsed().as_micros();
+        phase.mean_us = phase.total_us / phase.calls as u128;
+        for (key, value) in timing.fields {
+            *phase.fields.entry(key.to_string()).or_insert(0) += value;
+        }
+    }
+}
+
+impl TimingAggregator {
+    /// Phases in the order they first closed.
+    pub fn summary(&self) -> Vec<PhaseSummary> {
+        self.phases.lock().unwrap().clone()
+    }
+
+    /// Render the per-phase table printed by `--timings`.
+    pub fn render_table(&self) -> String {
+        let mut out = format!(
+            "{:<18} {:>6} {:>12} {:>12}  fields\n",
+            "phase", "calls", "total ms", "mean ms"
+        );
+        for phase in self.summary() {
+            let fields: Vec<String> = phase
+                .fields
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            out.push_str(&format!(
+                "{:<18} {:>6} {:>12.3} {:>12.3}  {}\n",
+                phase.name,
+                phase.calls,
+                phase.total_us as f64 / 1000.0,
+                phase.mean_us as f64 / 1000.0,
+                fields.join(" ")
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(all(test, feature = "tracing", feature = "merkle", feature = "abi-json"))]
+mod tests {
+    use super::*;
+    use crate::{assembler, cairo_abi, merkle};
+
+    fn phase<'a>(summary: &'a [PhaseSummary], name: &str) -> &'a PhaseSummary {
+        summary.iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_assemble_emits_expected_spans() {
+        let aggregator = TimingAggregator::default();
+        with_aggregator(&aggregator, || {
+            let programs = assembler::parse_assembly("NODE (0,0)\nNOP\nHLT\n").unwrap();
+            let words = assembler::encode_programs(&programs).unwrap();
+            merkle::compute_program_merkle_root(&programs).unwrap();
+            cairo_abi::generate_args(&[1], &[], &words).unwrap();
+        });
+
+        let summary = aggregator.summary();
+        let names: Vec<&str> = summary.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["parse_assembly", "encode_programs", "merkle::compute", "generate_args"]);
+        assert_eq!(summary[0].fields["instructions"], 2);
+        assert_eq!(summary[1].fields["words"], 6);
+        assert_eq!(summary[3].fields["values"], 10);
+    }
+
+    #[test]
+    fn test_merkle_hashes_each_distinct_program_once() {
+        let aggregator = TimingAggregator::default();
+        let source = "NODE (0,0)\nMOV IN, P:RIGHT\nNODE (0,1)\nMOV P:LEFT, P:DOWN\nNODE (1,0)\nMOV P:LEFT, P:DOWN\nNODE (1,1)\nMOV P:LEFT, P:DOWN\n";
+        let programs = assembler::parse_assembly(source).unwrap();
+        with_aggregator(&aggregator, || merkle::compute_program_merkle_root(&programs).unwrap());
+
+        let summary = aggregator.summary();
+        let merkle = phase(&summary, "merkle::compute");
+        assert_eq!(merkle.fields["leaves"], 4);
+        assert_eq!(merkle.fields["leaf_hashes"], 2);
+    }
+
+    #[test]
+    fn test_aggregator_sums_phases() {
+        let aggregator = TimingAggregator::default();
+        with_aggregator(&aggregator, || {
+            for _ in 0..3 {
+                let s = span!("phase", words);
+                s.record("words", 2);
+            }
+        });
+        let summary = aggregator.summary();
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].calls, 3);
+        assert_eq!(summary[0].fields["words"], 6);
+        assert!(aggregator.render_table().contains("phase"));
+    }
+}
@@ -0,0 +1,420 @@
+//! Interactive simulation dashboard behind `zk100 tui` (`tui` feature).
+//!
+//! The dashboard never looks inside the simulator: it drives it with
+//! `Simulator::step` and shows `Simulator::snapshot`s. Turning a snapshot
+//! into what is on screen is a set of pure view-model functions
+//! (`node_pane`, `stream_view`, `status_line`), which is what the tests
+//! cover; `render` only lays their output out as ratatui widgets, drawn
+//! through the crossterm backend. A non-TTY is refused up front.
+//!
+//! Keys: `s` or space steps one cycle, `r` runs, `p` pauses, `0` resets,
+//! `+`/`-` change the run speed and `q` quits.
+
+use crate::abi::IoNodes;
+use crate::assembler::ParseResult;
+use crate::sim::{DEFAULT_MAX_CYCLES, LastBehavior, SimSnapshot, Simulator, StepResult};
+use anyhow::{Result, anyhow};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
+
+/// Run speeds in cycles per second; `+` and `-` move through them.
+pub const SPEEDS: [u32; 7] = [1, 2, 5, 10, 20, 50, 100];
+
+/// The source text shown for each pc of each node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    pub text: BTreeMap<(usize, usize), Vec<String>>,
+}
+
+impl SourceMap {
+    /// Each instruction's source line, trimmed, as the parser recorded it.
+    pub fn new(source: &str, parsed: &ParseResult) -> Self {
+        let lines: Vec<&str> = source.lines().collect();
+        let text = parsed
+            .lines
+            .iter()
+            .map(|(&node, pcs)| {
+                let text = pcs.iter().map(|&line| lines.get(line - 1).map_or("", |l| l.trim()).to_string()).collect();
+                (node, text)
+            })
+            .collect();
+        SourceMap { text }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeStatus {
+    Empty,
+    Running,
+    Blocked,
+    Halted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneLine {
+    pub pc: usize,
+    pub text: String,
+    pub current: bool,
+}
+
+/// One node's pane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pane {
+    /// `NODE (r,c)`, with `IN`/`OUT` when the node has that role.
+    pub title: String,
+    /// At most `height` instructions, scrolled to keep the current one in view.
+    pub lines: Vec<PaneLine>,
+    /// `ACC`, `BAK` and `LAST`; without an `ANY` port nothing sets `LAST`,
+    /// so it always shows `-`.
+    pub registers: String,
+    pub status: NodeStatus,
+}
+
+pub fn node_pane(snapshot: &SimSnapshot, source: &SourceMap, (r, c): (usize, usize), height: usize) -> Pane {
+    let node = &snapshot.nodes[r][c];
+    let mut title = format!("NODE ({},{})", r, c);
+    for (role, at) in [("IN", snapshot.io.in_node), ("OUT", snapshot.io.out_node)] {
+        if at == (r, c) {
+            title.push(' ');
+            title.push_str(role);
+        }
+    }
+    let status = match (node.pc, node.state.halted, node.state.blocked) {
+        (None, _, _) => NodeStatus::Empty,
+        (_, true, _) => NodeStatus::Halted,
+        (_, _, true) => NodeStatus::Blocked,
+        _ => NodeStatus::Running,
+    };
+    let current = node.pc.unwrap_or(0);
+    let len = node.program.len();
+    let start = current.saturating_sub(height / 2).min(len.saturating_sub(height));
+    let text = source.text.get(&(r, c));
+    let lines = (start..len.min(start + height))
+        .map(|pc| PaneLine {
+            pc,
+            text: text.and_then(|t| t.get(pc)).cloned().unwrap_or_else(|| node.program[pc].to_string()),
+            current: node.pc == Some(pc),
+        })
+        .collect();
+    let registers = format!("ACC {}  BAK {}  LAST -", node.state.acc, node.state.bak);
+    Pane { title, lines, registers, status }
+}
+
+/// `label` and `values` on one line of at most `width` characters. With a
+/// `cursor`, the value it points at is bracketed, and values before it are
+/// dropped first when the line is too long; otherwise the oldest ones are.
+pub fn stream_view(label: &str, values: &[u32], cursor: Option<usize>, width: usize) -> String {
+    let items: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| if cursor == Some(i) { format!("[{}]", v) } else { v.to_string() })
+        .collect();
+    let head = format!("{:<4}", label);
+    let end = match cursor {
+        Some(at) if at == values.len() => format!(" [{}]", "end"),
+        _ => String::new(),
+    };
+    let fits = |from: usize| {
+        let prefix = if from > 0 { 2 } else { 0 };
+        let body: usize = items[from..].iter().map(|item| item.chars().count() + 1).sum();
+        head.chars().count() + prefix + body + end.chars().count() <= width
+    };
+    let from = (0..=items.len()).find(|&from| fits(from)).unwrap_or(items.len());
+    let mut line = head;
+    if from > 0 {
+        line.push_str(" …");
+    }
+    for item in &items[from..] {
+        line.push(' ');
+        line.push_str(item);
+    }
+    line.push_str(&end);
+    line
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Paused,
+    Running,
+}
+
+/// What the keys have set, and how the run ended if it has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Controls {
+    pub mode: Mode,
+    /// Index into `SPEEDS`.
+    pub speed: usize,
+    pub ended: Option<String>,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Controls { mode: Mode::Paused, speed: 3, ended: None }
+    }
+}
+
+pub fn status_line(snapshot: &SimSnapshot, controls: &Controls) -> String {
+    let state = match (&controls.ended, controls.mode) {
+        (Some(reason), _) => format!("ended: {}", reason),
+        (None, Mode::Running) => format!("running {}/s", SPEEDS[controls.speed]),
+        (None, Mode::Paused) => "paused".to_string(),
+    };
+    format!("cycle {}  msgs {}  {}", snapshot.cycles, snapshot.msgs, state)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Step,
+    Run,
+    Pause,
+    Reset,
+    Faster,
+    Slower,
+    Quit,
+}
+
+pub fn action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Action::Quit),
+        KeyCode::Char('s' | ' ') => Some(Action::Step),
+        KeyCode::Char('r') => Some(Action::Run),
+        KeyCode::Char('p') => Some(Action::Pause),
+        KeyCode::Char('0') => Some(Action::Reset),
+        KeyCode::Char('+' | '=') => Some(Action::Faster),
+        KeyCode::Char('-') => Some(Action::Slower),
+        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+        _ => None,
+    }
+}
+
+const HELP: &str = "[s]tep [r]un [p]ause [0] reset [+/-] speed [q]uit";
+
+/// Draw the whole screen: a pane per node, then the streams, the status
+/// line and the key help, with the current instruction in reverse video.
+fn render(frame: &mut Frame, snapshot: &SimSnapshot, source: &SourceMap, controls: &Controls) {
+    let [grid, input, output, status, help] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let grid_rows = snapshot.nodes.len().max(1);
+    let grid_cols = snapshot.nodes.first().map_or(0, Vec::len).max(1);
+    let rows = Layout::vertical(vec![Constraint::Ratio(1, grid_rows as u32); grid_rows]).split(grid);
+    for (r, &row) in rows.iter().enumerate().take(snapshot.nodes.len()) {
+        let panes = Layout::horizontal(vec![Constraint::Ratio(1, grid_cols as u32); grid_cols]).split(row);
+        for (c, &area) in panes.iter().enumerate() {
+            // The border takes two rows and the register and status lines two more.
+            let height = usize::from(area.height.saturating_sub(4)).max(1);
+            render_pane(frame, &node_pane(snapshot, source, (r, c), height), area);
+        }
+    }
+    let width = usize::from(input.width);
+    frame.render_widget(Line::raw(stream_view("IN", &snapshot.inputs, Some(snapshot.in_cursor), width)), input);
+    frame.render_widget(Line::raw(stream_view("OUT", &snapshot.outputs, None, width)), output);
+    frame.render_widget(Line::raw(status_line(snapshot, controls)), status);
+    frame.render_widget(Line::raw(HELP), help);
+}
+
+fn render_pane(frame: &mut Frame, pane: &Pane, area: Rect) {
+    let block = Block::bordered().title(Line::raw(pane.title.as_str()).bold());
+    let [code, registers, status] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1), Constraint::Length(1)]).areas(block.inner(area));
+    frame.render_widget(block, area);
+    let lines: Vec<Line> = pane
+        .lines
+        .iter()
+        .map(|line| {
+            let text = format!("{:>3} {}", line.pc, line.text);
+            if line.current { Line::raw(text).reversed() } else { Line::raw(text) }
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), code);
+    frame.render_widget(Line::raw(pane.registers.as_str()), registers);
+    let status_text = match pane.status {
+        NodeStatus::Empty => Line::raw("empty"),
+        NodeStatus::Running => Line::raw("running"),
+        NodeStatus::Blocked => Line::raw("BLOCKED").bold(),
+        NodeStatus::Halted => Line::raw("halted"),
+    };
+    frame.render_widget(status_text, status);
+}
+
+/// The terminal in raw mode on the alternate screen; dropping it restores
+/// the terminal, and ratatui's panic hook does the same on a panic.
+struct Screen {
+    terminal: DefaultTerminal,
+}
+
+impl Screen {
+    fn open() -> Result<Self> {
+        if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+            return Err(anyhow!("tui needs an interactive terminal on stdin and stdout"));
+        }
+        Ok(Screen { terminal: ratatui::try_init()? })
+    }
+
+    /// The next key pressed within 0.1s, if any.
+    fn key(&self) -> Result<Option<KeyEvent>> {
+        if !event::poll(Duration::from_millis(100))? {
+            return Ok(None);
+        }
+        Ok(match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => Some(key),
+            _ => None,
+        })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        ratatui::try_restore().ok();
+    }
+}
+
+/// Run the dashboard for `parsed` on `inputs` until `q`.
+pub fn run(source: &str, parsed: &ParseResult, inputs: &[u32], io: IoNodes, last: LastBehavior) -> Result<()> {
+    let programs = &parsed.grid.programs;
+    let start = || Simulator::with_io(programs, inputs, io).map(|sim| sim.with_last(last));
+    let source = SourceMap::new(source, parsed);
+    let mut sim = start()?;
+    let mut controls = Controls::default();
+    let mut screen = Screen::open()?;
+    let mut last_step = Instant::now();
+    loop {
+        let snapshot = sim.snapshot();
+        screen.terminal.draw(|frame| render(frame, &snapshot, &source, &controls))?;
+        let mut steps = 0;
+        match screen.key()?.and_then(action) {
+            Some(Action::Quit) => return Ok(()),
+            Some(Action::Step) => {
+                controls.mode = Mode::Paused;
+                steps = 1;
+            }
+            Some(Action::Run) => controls.mode = Mode::Running,
+            Some(Action::Pause) => controls.mode = Mode::Paused,
+            Some(Action::Reset) => {
+                sim = start()?;
+                controls = Controls { speed: controls.speed, ..Controls::default() };
+            }
+            Some(Action::Faster) => controls.speed = (controls.speed + 1).min(SPEEDS.len() - 1),
+            Some(Action::Slower) => controls.speed = controls.speed.saturating_sub(1),
+            None => {}
+        }
+        if controls.mode == Mode::Running {
+            let due = (last_step.elapsed().as_secs_f64() * f64::from(SPEEDS[controls.speed])) as u32;
+            if due > 0 {
+                steps = due;
+                last_step = Instant::now();
+            }
+        } else {
+            last_step = Instant::now();
+        }
+        for _ in 0..steps {
+            if controls.ended.is_some() {
+                break;
+            }
+            controls.ended = match sim.step() {
+                Ok(StepResult::Continue) if sim.cycles() >= DEFAULT_MAX_CYCLES => Some("cycle limit".to_string()),
+                Ok(StepResult::Continue) => None,
+                Ok(StepResult::Halted) => Some("every node halted".to_string()),
+                Ok(StepResult::Deadlock) => Some("deadlock".to_string()),
+                Err(e) => Some(e.to_string()),
+            };
+        }
+        if controls.ended.is_some() {
+            controls.mode = Mode::Paused;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+    use ratatui::style::Modifier;
+
+    const RELAY: &str = "NODE (0,0)\nMOV IN, ACC\nADD 1\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+    fn after(cycles: usize) -> (SimSnapshot, SourceMap) {
+        let parsed = assembler::parse(RELAY).unwrap();
+        let mut sim = Simulator::new(&parsed.grid.programs, &[4, 9]).unwrap();
+        for _ in 0..cycles {
+            sim.step().unwrap();
+        }
+        (sim.snapshot(), SourceMap::new(RELAY, &parsed))
+    }
+
+    #[test]
+    fn test_node_panes() {
+        let (snapshot, source) = after(2);
+        let pane = node_pane(&snapshot, &source, (0, 0), 8);
+        assert_eq!(pane.title, "NODE (0,0) IN");
+        assert_eq!(pane.registers, "ACC 5  BAK 0  LAST -");
+        assert_eq!(pane.status, NodeStatus::Running);
+        let lines: Vec<(usize, &str, bool)> = pane.lines.iter().map(|l| (l.pc, l.text.as_str(), l.current)).collect();
+        assert_eq!(lines, [(0, "MOV IN, ACC", false), (1, "ADD 1", false), (2, "MOV ACC, P:DOWN", true)]);
+
+        let pane = node_pane(&snapshot, &source, (1, 0), 8);
+        assert_eq!((pane.title.as_str(), pane.status), ("NODE (1,0)", NodeStatus::Blocked));
+        assert_eq!(node_pane(&snapshot, &source, (0, 1), 8).status, NodeStatus::Empty);
+        assert!(node_pane(&snapshot, &source, (0, 1), 8).lines.is_empty());
+        assert_eq!(node_pane(&snapshot, &source, (1, 1), 8).title, "NODE (1,1) OUT");
+
+        // A short pane scrolls to keep the current instruction in view.
+        let pane = node_pane(&snapshot, &source, (0, 0), 1);
+        assert_eq!((pane.lines.len(), pane.lines[0].pc, pane.lines[0].current), (1, 2, true));
+    }
+
+    #[test]
+    fn test_streams_and_status() {
+        let (snapshot, _) = after(6);
+        assert_eq!(snapshot.outputs, [5]);
+        assert_eq!(stream_view("IN", &snapshot.inputs, Some(snapshot.in_cursor), 40), "IN   4 9 [end]");
+        assert_eq!(stream_view("IN", &[1, 2, 3], Some(1), 40), "IN   1 [2] 3");
+        assert_eq!(stream_view("OUT", &snapshot.outputs, None, 40), "OUT  5");
+        let long: Vec<u32> = (100..120).collect();
+        let view = stream_view("OUT", &long, None, 24);
+        assert_eq!(view, "OUT  … 116 117 118 119");
+        assert!(view.chars().count() <= 24);
+
+        let mut controls = Controls::default();
+        assert_eq!(status_line(&snapshot, &controls), "cycle 6  msgs 1  paused");
+        controls.mode = Mode::Running;
+        assert_eq!(status_line(&snapshot, &controls), "cycle 6  msgs 1  running 10/s");
+        controls.ended = Some("deadlock".into());
+        assert_eq!(status_line(&snapshot, &controls), "cycle 6  msgs 1  ended: deadlock");
+    }
+
+    #[test]
+    fn test_keys_and_render() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert_eq!(action(key(KeyCode::Char(' '))), Some(Action::Step));
+        assert_eq!(action(key(KeyCode::Char('q'))), Some(Action::Quit));
+        assert_eq!(action(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)), Some(Action::Quit));
+        assert_eq!(action(key(KeyCode::Char('x'))), None);
+
+        let (snapshot, source) = after(2);
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        terminal.draw(|frame| render(frame, &snapshot, &source, &Controls::default())).unwrap();
+        let buffer = terminal.backend().buffer();
+        let rows: Vec<String> =
+            (0..24).map(|y| (0..80).map(|x| buffer[(x, y)].symbol()).collect::<String>()).collect();
+        let y = rows.iter().position(|row| row.contains("  2 MOV ACC, P:DOWN")).unwrap();
+        let x = rows[y][..rows[y].find("  2 MOV").unwrap()].chars().count() as u16;
+        assert!(buffer[(x, y as u16)].modifier.contains(Modifier::REVERSED));
+        assert!(rows.iter().any(|row| row.contains("NODE (0,0) IN")));
+        assert_eq!(rows[23].trim_end(), HELP);
+    }
+}
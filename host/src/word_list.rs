@@ -0,0 +1,315 @@
+//! Programs as plain lists of instruction words, for tools that exchange
+//! `prog_words` rather than assembly or grid JSON.
+//!
+//! Three formats are read and written by `convert --from words` / `--to
+//! words`:
+//!
+//! - `Text`: one word per line, `0x` hex or decimal, `#` comments. The
+//!   length-prefixed layout of `encode_programs` is written out as is.
+//! - `Array`: a `const PROG: [u32; N] = [...];` snippet, `//` comments.
+//! - `Csv`: `node,pc,word` rows, `node` being the row-major index. The
+//!   length words are implied by the rows, which must run through each
+//!   node's pcs from 0 with nodes in order.
+//!
+//! The emitters annotate each node and instruction with comments (the CSV has
+//! no comments) and write nothing that depends on the run, so their output can
+//! be committed and diffed. The readers check the words against the layout and
+//! name the line of the word that breaks it.
+
+use crate::assembler::{self, GRID_COLS, GRID_ROWS, Programs};
+use crate::instruction::Inst;
+use anyhow::{Result, anyhow};
+use std::fmt::Write as _;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordFormat {
+    Text,
+    Array,
+    Csv,
+}
+
+/// Header line of the CSV format.
+pub const CSV_HEADER: &str = "node,pc,word";
+
+impl WordFormat {
+    /// The format a file name suggests: `.csv` and `.rs`, else `Text`.
+    pub fn from_path(path: &Path) -> WordFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => WordFormat::Csv,
+            Some("rs") => WordFormat::Array,
+            _ => WordFormat::Text,
+        }
+    }
+}
+
+/// `programs` as a word list in `format`.
+pub fn write(programs: &Programs, format: WordFormat) -> String {
+    let mut out = String::new();
+    match format {
+        WordFormat::Text => {
+            out.push_str("# zk100 prog_words: length-prefixed programs, nodes in row-major order\n");
+            for_each_node(programs, |r, c, program| {
+                let _ = writeln!(out, "# node ({},{})", r, c);
+                let _ = writeln!(out, "0x{:08x}", program.len());
+                for inst in program {
+                    let _ = writeln!(out, "0x{:08x}  # {}", inst.encode(), inst);
+                }
+            });
+        }
+        WordFormat::Array => {
+            let words: usize = programs.iter().flatten().map(|p| p.len() + 1).sum();
+            out.push_str("// zk100 prog_words: length-prefixed programs, nodes in row-major order\n");
+            let _ = writeln!(out, "const PROG: [u32; {}] = [", words);
+            for_each_node(programs, |r, c, program| {
+                let _ = writeln!(out, "    // node ({},{})", r, c);
+                let _ = writeln!(out, "    0x{:08x},", program.len());
+                for inst in program {
+                    let _ = writeln!(out, "    0x{:08x}, // {}", inst.encode(), inst);
+                }
+            });
+            out.push_str("];\n");
+        }
+        WordFormat::Csv => {
+            let _ = writeln!(out, "{}", CSV_HEADER);
+            let mut node = 0;
+            for_each_node(programs, |_, _, program| {
+                for (pc, inst) in program.iter().enumerate() {
+                    let _ = writeln!(out, "{},{},0x{:08x}", node, pc, inst.encode());
+                }
+                node += 1;
+            });
+        }
+    }
+    out
+}
+
+fn for_each_node(programs: &Programs, mut f: impl FnMut(usize, usize, &[Inst])) {
+    for (r, row) in programs.iter().enumerate() {
+        for (c, program) in row.iter().enumerate() {
+            f(r, c, program);
+        }
+    }
+}
+
+/// Read a word list in `format` back into a grid.
+pub fn read(text: &str, format: WordFormat) -> Result<Programs> {
+    match format {
+        WordFormat::Text => {
+            let mut words = Vec::new();
+            for (line, raw) in lines(text) {
+                let token = raw.split('#').next().unwrap_or_default().trim();
+                if !token.is_empty() {
+                    words.push((line, parse_word(token, line)?));
+                }
+            }
+            decode(&words, text.lines().count())
+        }
+        WordFormat::Array => decode(&read_array(text)?, text.lines().count()),
+        WordFormat::Csv => read_csv(text),
+    }
+}
+
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines().enumerate().map(|(i, line)| (i + 1, line))
+}
+
+fn parse_word(token: &str, line: usize) -> Result<u32> {
+    let parsed = match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(&hex.replace('_', ""), 16),
+        None => token.parse::<u32>(),
+    };
+    parsed.map_err(|_| anyhow!("line {}: '{}' is not a u32 word", line, token))
+}
+
+/// `assembler::decode_grid` over words tagged with their line.
+fn decode(words: &[(usize, u32)], last_line: usize) -> Result<Programs> {
+    let mut programs: Programs = vec![vec![vec![]; GRID_COLS]; GRID_ROWS];
+    let mut next = words.iter();
+    for (r, row) in programs.iter_mut().enumerate() {
+        for (c, program) in row.iter_mut().enumerate() {
+            let &(line, len) = next
+                .next()
+                .ok_or_else(|| anyhow!("line {}: the list ends before the length word of node ({},{})", last_line, r, c))?;
+            for pc in 0..len {
+                let &(word_line, word) = next.next().ok_or_else(|| {
+                    anyhow!("line {}: node ({},{}) declares {} instructions but the list ends after {}", line, r, c, len, pc)
+                })?;
+                let inst =
+                    Inst::decode(word).map_err(|e| anyhow!("line {}: node ({},{}) pc {}: {}", word_line, r, c, pc, e))?;
+                program.push(inst);
+            }
+        }
+    }
+    match next.as_slice() {
+        [] => Ok(programs),
+        rest => Err(anyhow!("line {}: {} trailing words after the last program", rest[0].0, rest.len())),
+    }
+}
+
+/// The words of a `const NAME: [u32; N] = [...];` snippet.
+fn read_array(text: &str) -> Result<Vec<(usize, u32)>> {
+    let mut declared: Option<(usize, usize)> = None;
+    let mut words = Vec::new();
+    let mut closed = None;
+    for (line, raw) in lines(text) {
+        let mut code = raw.split("//").next().unwrap_or_default().trim();
+        if code.is_empty() {
+            continue;
+        }
+        if closed.is_some() {
+            return Err(anyhow!("line {}: text after the end of the array", line));
+        }
+        if declared.is_none() {
+            let (head, body) = code
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected `const PROG: [u32; N] = [`", line))?;
+            let len = head
+                .trim()
+                .strip_prefix("const ")
+                .and_then(|rest| rest.split_once(':'))
+                .and_then(|(_, ty)| ty.trim().strip_prefix("[u32;")?.trim().strip_suffix(']')?.trim().parse().ok())
+                .ok_or_else(|| anyhow!("line {}: expected `const PROG: [u32; N] = [`", line))?;
+            code = body
+                .trim()
+                .strip_prefix('[')
+                .ok_or_else(|| anyhow!("line {}: expected `[` after `=`", line))?;
+            declared = Some((line, len));
+        }
+        let (items, end) = match code.split_once(']') {
+            Some((items, end)) => (items, Some(end)),
+            None => (code, None),
+        };
+        for token in items.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let token = token.strip_suffix("u32").unwrap_or(token).trim_end_matches('_');
+            words.push((line, parse_word(token, line)?));
+        }
+        if let Some(end) = end {
+            if end.trim() != ";" {
+                return Err(anyhow!("line {}: expected `];` to end the array", line));
+            }
+            closed = Some(line);
+        }
+    }
+    let (line, len) = declared.ok_or_else(|| anyhow!("no `const PROG: [u32; N]` array found"))?;
+    if closed.is_none() {
+        return Err(anyhow!("line {}: the array is never closed with `];`", text.lines().count()));
+    }
+    if words.len() != len {
+        return Err(anyhow!("line {}: the array declares {} words but lists {}", line, len, words.len()));
+    }
+    Ok(words)
+}
+
+fn read_csv(text: &str) -> Result<Programs> {
+    let mut programs: Programs = vec![vec![vec![]; GRID_COLS]; GRID_ROWS];
+    let mut rows = lines(text).filter(|(_, line)| !line.trim().is_empty());
+    match rows.next() {
+        Some((_, header)) if header.trim().replace(' ', "") == CSV_HEADER => {}
+        Some((line, _)) => return Err(anyhow!("line {}: expected the header `{}`", line, CSV_HEADER)),
+        None => return Err(anyhow!("empty CSV; expected the header `{}`", CSV_HEADER)),
+    }
+    let mut last_node = 0;
+    for (line, row) in rows {
+        let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+        let [node, pc, word] = fields[..] else {
+            return Err(anyhow!("line {}: expected 3 fields (node,pc,word), found {}", line, fields.len()));
+        };
+        let node: usize = node.parse().map_err(|_| anyhow!("line {}: '{}' is not a node index", line, node))?;
+        let pc: usize = pc.parse().map_err(|_| anyhow!("line {}: '{}' is not a pc", line, pc))?;
+        if node >= GRID_ROWS * GRID_COLS {
+            return Err(anyhow!("line {}: node {} is outside the {}x{} grid", line, node, GRID_ROWS, GRID_COLS));
+        }
+        if node < last_node {
+            return Err(anyhow!("line {}: node {} comes after node {}; rows must be in node order", line, node, last_node));
+        }
+        last_node = node;
+        let (r, c) = (node / GRID_COLS, node % GRID_COLS);
+        let program = &mut programs[r][c];
+        if pc != program.len() {
+            return Err(anyhow!("line {}: node {} pc {} where pc {} was expected", line, node, pc, program.len()));
+        }
+        let inst = Inst::decode(parse_word(word, line)?)
+            .map_err(|e| anyhow!("line {}: node ({},{}) pc {}: {}", line, r, c, pc, e))?;
+        program.push(inst);
+    }
+    // The rows imply the length words; the result must encode like any grid.
+    assembler::encode_programs(&programs)?;
+    Ok(programs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::parse_assembly;
+
+    const PROGRAM: &str = "NODE (0,0)\nMOV IN, ACC\nADD ACC\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+    #[test]
+    fn test_round_trips() {
+        let programs = parse_assembly(PROGRAM).unwrap();
+        let words = assembler::encode_programs(&programs).unwrap();
+        for format in [WordFormat::Text, WordFormat::Array, WordFormat::Csv] {
+            let written = write(&programs, format);
+            assert_eq!(read(&written, format).unwrap(), programs, "{:?}:\n{}", format, written);
+            assert_eq!(write(&programs, format), written, "{:?} is not deterministic", format);
+        }
+        let text = write(&programs, WordFormat::Text);
+        assert!(text.contains("0x00000003\n"), "{}", text);
+        let listed: Vec<u32> =
+            read_array(&write(&programs, WordFormat::Array)).unwrap().into_iter().map(|(_, w)| w).collect();
+        assert_eq!(listed, words);
+        // An empty grid is four zero length words, and no CSV rows.
+        let empty = parse_assembly("").unwrap();
+        assert_eq!(write(&empty, WordFormat::Csv), "node,pc,word\n");
+        assert_eq!(read("0\n0\n0\n0\n", WordFormat::Text).unwrap(), empty);
+    }
+
+    #[test]
+    fn test_readers_accept_hand_written_lists() {
+        let programs = parse_assembly("NODE (1,1)\nNOP\n").unwrap();
+        let nop = format!("{:#x}", programs[1][1][0].encode());
+        let text = format!("# three empty nodes\n0\n0\n0x0\n\n1 # (1,1)\n{}\n", nop);
+        assert_eq!(read(&text, WordFormat::Text).unwrap(), programs);
+        let array = format!("const NOPS: [u32; 5] = [0, 0, 0, // empty\n  1u32, {}];\n", nop);
+        assert_eq!(read(&array, WordFormat::Array).unwrap(), programs);
+        let csv = format!("node, pc, word\n3, 0, {}\n", nop);
+        assert_eq!(read(&csv, WordFormat::Csv).unwrap(), programs);
+    }
+
+    #[test]
+    fn test_text_reader_names_the_line() {
+        let err = |text: &str| read(text, WordFormat::Text).unwrap_err().to_string();
+        let nop = parse_assembly("NODE (0,0)\nNOP\n").unwrap()[0][0][0].encode();
+        assert_eq!(err("0\n0\nzz\n0\n"), "line 3: 'zz' is not a u32 word");
+        let short = format!("# a\n0\n0\n5\n{}\n{}\n", nop, nop);
+        assert_eq!(err(&short), "line 4: node (1,0) declares 5 instructions but the list ends after 2");
+        assert_eq!(err("0\n0\n0\n1\n0xffffffff\n"), "line 5: node (1,1) pc 0: Invalid opcode 15 in word 0xffffffff");
+        assert_eq!(err("0\n0\n0\n0\n\n7\n8\n"), "line 6: 2 trailing words after the last program");
+        assert_eq!(err("0\n0\n"), "line 2: the list ends before the length word of node (1,0)");
+    }
+
+    #[test]
+    fn test_array_reader_names_the_line() {
+        let err = |text: &str| read(text, WordFormat::Array).unwrap_err().to_string();
+        assert_eq!(err("const PROG: [u32; 4] = [\n0, 0,\n0, x1,\n];\n"), "line 3: 'x1' is not a u32 word");
+        assert_eq!(err("const PROG: [u32; 5] = [\n0, 0, 0, 0,\n];\n"), "line 1: the array declares 5 words but lists 4");
+        assert_eq!(err("const PROG: [u32; 4] = [0, 0, 0, 0]\n"), "line 1: expected `];` to end the array");
+        assert_eq!(err("const PROG: [u32; 4] = [0, 0,\n0, 0,\n"), "line 2: the array is never closed with `];`");
+        assert_eq!(err("static PROG = [0];\n"), "line 1: expected `const PROG: [u32; N] = [`");
+        assert_eq!(err("const PROG: [u32; 4] = [0, 0, 0, 0];\n0\n"), "line 2: text after the end of the array");
+        assert_eq!(err("const PROG: [u32; 5] = [\n0, 0, 0,\n0, 9];\n"), "line 3: 1 trailing words after the last program");
+    }
+
+    #[test]
+    fn test_csv_reader_names_the_line() {
+        let err = |text: &str| read(text, WordFormat::Csv).unwrap_err().to_string();
+        let nop = format!("{:#x}", parse_assembly("NODE (0,0)\nNOP\n").unwrap()[0][0][0].encode());
+        assert_eq!(err("pc,word\n"), "line 1: expected the header `node,pc,word`");
+        assert_eq!(err(&format!("node,pc,word\n0,0,{}\n0,2,{}\n", nop, nop)), "line 3: node 0 pc 2 where pc 1 was expected");
+        assert_eq!(err(&format!("node,pc,word\n1,0,{}\n0,0,{}\n", nop, nop)), "line 3: node 0 comes after node 1; rows must be in node order");
+        assert_eq!(err(&format!("node,pc,word\n4,0,{}\n", nop)), "line 2: node 4 is outside the 2x2 grid");
+        assert_eq!(err("node,pc,word\n0,0\n"), "line 2: expected 3 fields (node,pc,word), found 2");
+        assert_eq!(err("node,pc,word\n0,0,0xffffffff\n"), "line 2: node (0,0) pc 0: Invalid opcode 15 in word 0xffffffff");
+    }
+}
@@ -0,0 +1,52 @@
+//! `ASSERT` lines: left out of the encoded program and its root, checked by
+//! the simulator behind `report`, and skipped with `--ignore-asserts`.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+use zk100_host::{assembler, cairo_abi, merkle};
+
+const CHECKED: &str = "NODE (0,0)\nMOV IN, ACC\nASSERT ACC < 100\nADD ACC\nASSERT ACC > 0\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+#[test]
+fn asserts_do_not_change_words_or_root() {
+    let plain = assembler::parse_assembly(DOUBLER).unwrap();
+    let checked = assembler::parse(CHECKED).unwrap();
+    assert_eq!(checked.asserts[&(0, 0)].len(), 2);
+    assert_eq!(assembler::encode_programs(&checked.grid.programs).unwrap(), assembler::encode_programs(&plain).unwrap());
+    assert_eq!(merkle::program_root_hex(&checked.grid.programs).unwrap(), merkle::program_root_hex(&plain).unwrap());
+
+    let sandbox = Sandbox::new("asserts-root");
+    sandbox.write("plain.asm", DOUBLER).write("checked.asm", CHECKED);
+    for name in ["plain", "checked"] {
+        sandbox.run(&["assemble", &format!("{}.asm", name), "-o", &format!("{}.json", name)]).success();
+    }
+    let words = |name: &str| cairo_abi::parse_args(&sandbox.read(&format!("{}.json", name))).unwrap().prog_words;
+    assert_eq!(words("plain"), words("checked"));
+    let id = |name: &str| sandbox.run(&["id", &format!("{}.asm", name)]).success().stdout();
+    assert_eq!(id("plain"), id("checked"));
+}
+
+#[test]
+fn report_checks_asserts_unless_ignored() {
+    let sandbox = Sandbox::new("asserts-report");
+    sandbox.write("checked.asm", CHECKED);
+    let manifest = |input: u32| {
+        format!("[puzzle]\nname = \"checked\"\nprogram = \"checked.asm\"\n\n[[case]]\ninputs = [{}]\nexpected = [{}]\n", input, input * 2)
+    };
+    sandbox.write("ok.toml", manifest(3)).write("bad.toml", manifest(0));
+
+    sandbox.run(&["report", "--manifest", "ok.toml", "--json"]).success();
+    let json: serde_json::Value = serde_json::from_str(&sandbox.read("report.json")).unwrap();
+    let assert_line = &json["source"][2];
+    assert_eq!(assert_line["text"], "ASSERT ACC < 100");
+    assert_eq!((assert_line["pc"].as_u64(), assert_line["word"].is_null()), (Some(1), true));
+
+    sandbox
+        .run(&["report", "--manifest", "bad.toml"])
+        .code(1)
+        .stderr_has("assertion failed at node (0,0) pc 2 (line 5) in cycle 2: ASSERT ACC > 0, but ACC is 0");
+    sandbox.run(&["report", "--manifest", "bad.toml", "--ignore-asserts"]).success();
+}
@@ -0,0 +1,88 @@
+//! `assemble --metadata` followed by `attest`, through the CLI.
+
+#![cfg(feature = "cli")]
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn zk100(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_zk100-host")).args(args).output().unwrap()
+}
+
+/// Assemble the passthrough fixture into a scratch dir; returns (dir, source, metadata).
+fn assembled(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("zk100-attest-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("program.asm");
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden/passthrough/program.asm");
+    fs::copy(fixture, &source).unwrap();
+    let metadata = dir.join("metadata.json");
+    let out = zk100(&[
+        Path::new("assemble"),
+        &source,
+        Path::new("--output"),
+        &dir.join("args.json"),
+        Path::new("-i"),
+        Path::new("1,2,3"),
+        Path::new("-e"),
+        Path::new("1,2,3"),
+        Path::new("--metadata"),
+        &metadata,
+    ]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    (dir, source, metadata)
+}
+
+fn attest(source: &Path, metadata: &Path) -> (bool, String) {
+    let out = zk100(&[Path::new("attest"), source, Path::new("--against"), metadata]);
+    (out.status.success(), String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+#[test]
+fn fresh_metadata_attests() {
+    let (dir, source, metadata) = assembled("fresh");
+    let recorded: Value = serde_json::from_str(&fs::read_to_string(&metadata).unwrap()).unwrap();
+    let args_json = fs::read(dir.join("args.json")).unwrap();
+    assert_eq!(recorded["options"]["inputs"], serde_json::json!([1, 2, 3]));
+    assert_eq!(
+        recorded["args_sha256"].as_str().unwrap(),
+        hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&args_json))
+    );
+
+    // Line endings are normalized away.
+    let crlf = fs::read_to_string(&source).unwrap().replace('\n', "\r\n");
+    fs::write(&source, crlf).unwrap();
+    let (ok, report) = attest(&source, &metadata);
+    fs::remove_dir_all(&dir).ok();
+    assert!(ok, "{}", report);
+    assert!(report.contains("ok           program_root"), "{}", report);
+}
+
+#[test]
+fn edited_source_fails() {
+    let (dir, source, metadata) = assembled("edited");
+    let edited = fs::read_to_string(&source).unwrap().replacen("MOV", "NEG\nMOV", 1);
+    fs::write(&source, edited).unwrap();
+    let (ok, report) = attest(&source, &metadata);
+    fs::remove_dir_all(&dir).ok();
+    assert!(!ok);
+    assert!(report.contains("MISMATCH     program_root"), "{}", report);
+    assert!(report.contains("MISMATCH     source_sha256"), "{}", report);
+}
+
+#[test]
+fn unknown_encoding_version_is_named() {
+    let (dir, source, metadata) = assembled("version");
+    let mut recorded: Value = serde_json::from_str(&fs::read_to_string(&metadata).unwrap()).unwrap();
+    recorded["encoding_version"] = 2.into();
+    recorded["options"]["optimize"] = true.into();
+    fs::write(&metadata, recorded.to_string()).unwrap();
+    let (ok, report) = attest(&source, &metadata);
+    fs::remove_dir_all(&dir).ok();
+    assert!(!ok);
+    assert!(report.contains("UNSUPPORTED  encoding_version 2 (this build implements 1)"), "{}", report);
+    assert!(report.contains("assemble option 'optimize'"), "{}", report);
+    assert!(!report.contains("ok "), "pipeline must not run with substituted defaults: {}", report);
+}
@@ -0,0 +1,86 @@
+//! `batch merge` / `batch split` through the CLI.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn zk100(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_zk100-host")).args(args).output().unwrap()
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zk100-batch-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Assemble a golden program with `inputs` (also used as the expected outputs).
+fn single(dir: &Path, program: &str, name: &str, inputs: &str) -> PathBuf {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/fixtures/golden/{}/program.asm", program));
+    let out_path = dir.join(name);
+    let out = zk100(&[
+        Path::new("assemble"),
+        &source,
+        Path::new("-o"),
+        &out_path,
+        Path::new("-i"),
+        Path::new(inputs),
+        Path::new("-e"),
+        Path::new(inputs),
+    ]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    out_path
+}
+
+#[test]
+fn merge_then_split_round_trips() {
+    let dir = scratch("round-trip");
+    let originals = [
+        single(&dir, "passthrough", "a.json", "1,2,3"),
+        single(&dir, "passthrough", "b.json", "42"),
+        single(&dir, "passthrough", "c.json", ""),
+    ];
+    let batch = dir.join("batch.json");
+    let singles = dir.join("singles");
+    let mut merge: Vec<&Path> = vec![Path::new("batch"), Path::new("merge")];
+    merge.extend(originals.iter().map(PathBuf::as_path));
+    merge.extend([Path::new("--output"), &batch]);
+    let merged = zk100(&merge);
+    let split = zk100(&[Path::new("batch"), Path::new("split"), &batch, Path::new("--out-dir"), &singles]);
+
+    let batched: Vec<String> = serde_json::from_str(&fs::read_to_string(&batch).unwrap()).unwrap();
+    let mut written: Vec<PathBuf> = fs::read_dir(&singles).unwrap().map(|e| e.unwrap().path()).collect();
+    written.sort();
+    let contents = |paths: &[PathBuf]| paths.iter().map(|p| fs::read(p).unwrap()).collect::<Vec<_>>();
+    let (expected, actual) = (contents(&originals), contents(&written));
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(merged.status.success(), "{}", String::from_utf8_lossy(&merged.stderr));
+    assert!(split.status.success(), "{}", String::from_utf8_lossy(&split.stderr));
+    assert_eq!(&batched[..5], ["0x3", "0x3", "0x1", "0x2", "0x3"]);
+    assert_eq!(written.iter().map(|p| p.file_name().unwrap().to_str().unwrap()).collect::<Vec<_>>(), [
+        "case-000.json",
+        "case-001.json",
+        "case-002.json"
+    ]);
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn merge_rejects_another_program() {
+    let dir = scratch("incompatible");
+    let a = single(&dir, "passthrough", "a.json", "1");
+    let b = single(&dir, "negate", "b.json", "1");
+    let batch = dir.join("batch.json");
+    let out = zk100(&[Path::new("batch"), Path::new("merge"), &a, &a, &b, Path::new("-o"), &batch]);
+    let written = batch.exists();
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(!out.status.success());
+    assert!(!written);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("b.json does not share prog_words with"), "{}", stderr);
+    assert!(stderr.contains("] 0x"), "{}", stderr);
+}
@@ -0,0 +1,46 @@
+//! `bisect-encoding` over fixtures whose old args came from a different
+//! assembler: a narrower literal field, and a program that gained a `NOP`.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::Sandbox;
+
+#[test]
+fn literal_width_change_is_an_encoding_difference() {
+    let sandbox = Sandbox::with_fixture("bisect/literal_width");
+    let run = sandbox.run(&["bisect-encoding", "old_args.json", "program.asm"]);
+    run.success()
+        .stdout_has("changed   node (0,0) pc 1 0x48020001 ADD 72  ->  pc 1 0xc8020001 ADD 200")
+        .stdout_has("encoding: 1 word changed value, no lengths changed");
+    assert_eq!(run.stdout().lines().count(), 2, "{}", run.stdout());
+}
+
+#[test]
+fn inserted_instruction_is_structural_and_local() {
+    let sandbox = Sandbox::with_fixture("bisect/inserted");
+    let run = sandbox.run(&["bisect-encoding", "old_args.json", "program.asm", "--json"]);
+    run.success();
+    let json: serde_json::Value = serde_json::from_slice(&run.output.stdout).unwrap();
+    assert_eq!(json["verdict"], "structural");
+    assert_eq!(json["lengths"], serde_json::json!([{"node": [0, 0], "old": 3, "new": 4}]));
+    // Only the NOP is reported; the MOV after it moved but did not change.
+    let diffs = json["diffs"].as_array().unwrap();
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0]["kind"], "inserted");
+    assert_eq!(diffs[0]["old"], serde_json::Value::Null);
+    assert_eq!(diffs[0]["new"]["pc"], 2);
+    assert_eq!(diffs[0]["new"]["asm"], "NOP");
+}
+
+#[test]
+fn current_args_are_identical() {
+    let sandbox = Sandbox::with_fixture("bisect/inserted");
+    sandbox.run(&["assemble", "program.asm", "-o", "new_args.json"]).success();
+    sandbox
+        .run(&["bisect-encoding", "new_args.json", "program.asm"])
+        .success()
+        .stdout_has("identical: prog_words match");
+    sandbox.run(&["bisect-encoding", "program.asm", "program.asm"]).code(1).stderr_has("args must be a JSON array");
+}
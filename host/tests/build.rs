@@ -0,0 +1,80 @@
+//! `build`: a workspace of two puzzles, one of them built from a shared
+//! include, rebuilds only what an edit reaches.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+
+const SHARED: &str = "# the output stage both doublers share\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+const USES_SHARED: &str = "NODE (0,0)\nMOV IN, ACC\nADD ACC\nMOV ACC, P:DOWN\nINCLUDE \"../lib/shared.asm\"\n";
+
+fn manifest(program: &str, expected: &str) -> String {
+    format!("[puzzle]\nprogram = \"{}\"\n\n[[case]]\ninputs = [1, 2]\nexpected = {}\n\n[[case]]\ninputs = [5]\nexpected = [10]\n", program, expected)
+}
+
+fn workspace(name: &str) -> Sandbox {
+    let sandbox = Sandbox::new(name);
+    sandbox
+        .write("lib/shared.asm", SHARED)
+        .write("a/a.asm", USES_SHARED)
+        .write("a/a.toml", manifest("a.asm", "[2, 4]"))
+        .write("b/b.asm", DOUBLER)
+        .write("b/b.toml", manifest("b.asm", "[2, \"*\"]"))
+        .write("zk100.toml", "# no settings\n");
+    sandbox
+}
+
+#[test]
+fn editing_an_include_rebuilds_only_its_dependents() {
+    let sandbox = workspace("build-include");
+    let run = sandbox.run(&["build"]);
+    run.success().stderr_has("   Compiling a/a.asm\n").stderr_has("   Compiling b/b.toml [case 1]\n").stderr_has("6 built, 0 fresh");
+    assert!(sandbox.exists(".zk100/build/a/a.asm.json"));
+    assert_eq!(sandbox.read(".zk100/build/b/b.toml/case-0.args.json"), sandbox.read(".zk100/build/a/a.toml/case-0.args.json"));
+
+    sandbox.run(&["build"]).success().stderr_has("       Fresh a/a.toml [case 0]\n").stderr_has("0 built, 6 fresh");
+
+    sandbox.write("lib/shared.asm", SHARED.replace("share\n", "share (edited)\n"));
+    let plan = sandbox.run(&["build", "--dry-run", "--porcelain"]);
+    plan.success();
+    assert_eq!(
+        plan.stdout(),
+        "dirty\ta/a.asm\tinputs changed\ndirty\ta/a.toml [case 0]\tinputs changed\ndirty\ta/a.toml [case 1]\tinputs changed\n\
+fresh\tb/b.asm\nfresh\tb/b.toml [case 0]\nfresh\tb/b.toml [case 1]\n"
+    );
+    let run = sandbox.run(&["build", "--porcelain"]);
+    assert_eq!(
+        run.success().stdout(),
+        "compiled\ta/a.asm\ncompiled\ta/a.toml [case 0]\ncompiled\ta/a.toml [case 1]\n\
+fresh\tb/b.asm\nfresh\tb/b.toml [case 0]\nfresh\tb/b.toml [case 1]\n"
+    );
+
+    // A lost output is rebuilt on its own; --force rebuilds everything.
+    std::fs::remove_file(sandbox.path(".zk100/build/b/b.toml/case-1.args.json")).unwrap();
+    sandbox.run(&["build", "--dry-run"]).success().stdout_has("       Dirty b/b.toml [case 1] (output missing)\n");
+    sandbox.run(&["build"]).success().stderr_has("1 built, 5 fresh");
+    sandbox.run(&["build", "--force"]).success().stderr_has("6 built, 0 fresh");
+
+    // Settings reach every program.
+    sandbox.write("zk100.toml", "[aliases]\nSTOP = \"HLT\"\n");
+    sandbox.run(&["build"]).success().stderr_has("6 built, 0 fresh");
+}
+
+#[test]
+fn failures_are_rebuilt_next_time() {
+    let sandbox = workspace("build-failures");
+    sandbox.write("a/a.toml", manifest("a.asm", "[2, 5]"));
+    let run = sandbox.run(&["build"]);
+    run.code(1)
+        .stderr_has("      Failed a/a.toml [case 0]: outputs[1]: expected 5, got 4\n")
+        .stderr_has("could not build 1 of 6 targets");
+
+    sandbox.write("a/a.asm", "NODE (0,0)\nMOV IN, ACC\nBOGUS\nINCLUDE \"../lib/shared.asm\"\n");
+    let run = sandbox.run(&["build"]);
+    run.code(1).stderr_has("      Failed a/a.asm: ").stderr_has("     Skipped a/a.toml [case 1] (a dependency failed)\n");
+
+    sandbox.write("a/a.asm", USES_SHARED).write("a/a.toml", manifest("a.asm", "[2, 4]"));
+    sandbox.run(&["build"]).success().stderr_has("3 built, 3 fresh");
+}
@@ -0,0 +1,159 @@
+//! Packs a bundle from the passthrough golden fixture through the CLI,
+//! verifies and extracts it, and checks that tampering with any member is
+//! detected and named.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use zk100_host::bundle::{self, Bundle};
+use zk100_host::cairo_abi;
+
+fn fixture(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(path)
+}
+
+fn zk100(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_zk100-host")).args(args).output().unwrap()
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zk100-bundle-test-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn packed() -> Vec<u8> {
+    let case = fixture("golden/passthrough");
+    bundle::pack(
+        &fs::read_to_string(case.join("program.asm")).unwrap(),
+        "program.asm",
+        &fs::read_to_string(case.join("golden/args.json")).unwrap(),
+        cairo_abi::LEGACY_ABI,
+        &fs::read(fixture("bundle/proof.json")).unwrap(),
+    )
+    .unwrap()
+}
+
+/// Flip one byte inside `member`'s data, leaving the tar headers intact.
+fn corrupt(bytes: &mut [u8], member: &[u8]) {
+    let at = bytes.windows(member.len()).position(|w| w == member).unwrap();
+    bytes[at + member.len() / 2] ^= 0x01;
+}
+
+#[test]
+fn bundle_verify_extract_cli() {
+    let dir = scratch("cli");
+    let case = fixture("golden/passthrough");
+    let out = dir.join("puzzle.zk100");
+    let packed = zk100(&[
+        Path::new("bundle"),
+        &case.join("program.asm"),
+        &case.join("golden/args.json"),
+        &fixture("bundle/proof.json"),
+        Path::new("--output"),
+        &out,
+    ]);
+    assert!(packed.status.success(), "{}", String::from_utf8_lossy(&packed.stderr));
+
+    let verified = zk100(&[Path::new("bundle"), Path::new("verify"), &out]);
+    assert!(verified.status.success(), "{}", String::from_utf8_lossy(&verified.stderr));
+    let expected_root = fs::read_to_string(case.join("golden/program_root.txt")).unwrap();
+    assert!(String::from_utf8_lossy(&verified.stdout).contains(expected_root.trim()));
+
+    let extracted = zk100(&[Path::new("bundle"), Path::new("extract"), &out]);
+    assert!(extracted.status.success());
+    assert_eq!(
+        fs::read(dir.join("puzzle").join(bundle::PROOF)).unwrap(),
+        fs::read(fixture("bundle/proof.json")).unwrap()
+    );
+
+    let mut bytes = fs::read(&out).unwrap();
+    corrupt(&mut bytes, b"fixture stand-in");
+    fs::write(&out, bytes).unwrap();
+    let tampered = zk100(&[Path::new("bundle"), Path::new("verify"), &out]);
+    assert!(!tampered.status.success());
+    assert!(String::from_utf8_lossy(&tampered.stderr).contains("proof.json was modified"));
+
+    let target = dir.join("tampered");
+    let refused = zk100(&[Path::new("bundle"), Path::new("extract"), &out, Path::new("--output"), &target]);
+    let wrote = target.exists();
+    fs::remove_dir_all(&dir).ok();
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("proof.json was modified"));
+    assert!(!wrote, "a tampered bundle was extracted");
+}
+
+#[test]
+fn tampering_with_each_member_is_named() {
+    let bytes = packed();
+    let clean = Bundle::read(&bytes).unwrap();
+    clean.verify().unwrap();
+
+    for name in bundle::MEMBERS {
+        let mut tampered = bytes.clone();
+        corrupt(&mut tampered, clean.member(name).unwrap());
+        let err = Bundle::read(&tampered).unwrap().verify().unwrap_err().to_string();
+        assert!(err.contains(&format!("{} was modified", name)), "{}: {}", name, err);
+    }
+}
+
+#[test]
+fn manifest_rewritten_to_match_is_still_caught() {
+    // Edit the source and the manifest hash together: the recorded program
+    // root and the args no longer line up with the source.
+    let mut contents = Bundle::read(&packed()).unwrap();
+    let source = String::from_utf8(contents.members[bundle::SOURCE].clone()).unwrap();
+    let edited = source.replacen("MOV", "NEG\nMOV", 1).into_bytes();
+    let mut manifest = contents.manifest().unwrap();
+    manifest
+        .members
+        .insert(bundle::SOURCE.to_string(), hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&edited)));
+    contents.members.insert(bundle::SOURCE.to_string(), edited);
+    contents
+        .members
+        .insert(bundle::MANIFEST.to_string(), serde_json::to_vec(&manifest).unwrap());
+
+    let err = contents.verify().unwrap_err().to_string();
+    assert!(err.contains("program root of program.asm"), "{}", err);
+}
+
+#[test]
+fn pack_rejects_args_for_another_program() {
+    let other = cairo_abi::generate_args(&[1], &[1], &[0x000c0201]).unwrap();
+    let err = bundle::pack(
+        &fs::read_to_string(fixture("golden/passthrough/program.asm")).unwrap(),
+        "program.asm",
+        &serde_json::to_string(&other).unwrap(),
+        cairo_abi::LEGACY_ABI,
+        b"{}",
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("does not encode the program"), "{}", err);
+}
+
+#[test]
+fn dedup_args_bundle_and_verify() {
+    let dir = scratch("dedup");
+    let program = dir.join("relay.asm");
+    let stub = "MOV P:LEFT, ACC\nADD 1\nSUB 1\nMOV ACC, P:DOWN\n";
+    let source = format!("NODE (0,0)\nMOV IN, P:RIGHT\nNODE (0,1)\n{0}NODE (1,0)\n{0}NODE (1,1)\n{0}", stub);
+    fs::write(&program, source).unwrap();
+    let (flat, dedup) = (dir.join("flat.json"), dir.join("dedup.json"));
+    for (args, abi) in [(&flat, "1"), (&dedup, "4")] {
+        let out = zk100(&[Path::new("assemble"), &program, Path::new("-o"), args, Path::new("-i"), Path::new("3"), Path::new("--abi"), Path::new(abi)]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    }
+    let words = |path: &Path| serde_json::from_str::<Vec<serde_json::Value>>(&fs::read_to_string(path).unwrap()).unwrap().len();
+    assert!(words(&dedup) < words(&flat), "shared node program not folded");
+    let parsed = cairo_abi::parse_args_abi(&fs::read_to_string(&dedup).unwrap(), cairo_abi::DEDUP_ABI).unwrap();
+    assert_eq!(parsed, cairo_abi::parse_args(&fs::read_to_string(&flat).unwrap()).unwrap());
+
+    let out = dir.join("relay.zk100");
+    let packed = zk100(&[Path::new("bundle"), &program, &dedup, &fixture("bundle/proof.json"), Path::new("--output"), &out, Path::new("--abi"), Path::new("4")]);
+    assert!(packed.status.success(), "{}", String::from_utf8_lossy(&packed.stderr));
+    let verified = zk100(&[Path::new("bundle"), Path::new("verify"), &out]);
+    fs::remove_dir_all(&dir).ok();
+    assert!(verified.status.success(), "{}", String::from_utf8_lossy(&verified.stderr));
+}
@@ -0,0 +1,18 @@
+//! Pins the generated Cairo constants. After an intentional codec change,
+//! regenerate with `UPDATE_GOLDENS=1 cargo test --test cairo_consts` and copy
+//! the file into the guest.
+
+use std::fs;
+use std::path::Path;
+use zk100_host::{cairo_consts, testkit};
+
+#[test]
+fn generated_consts_match_snapshot() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/consts.cairo");
+    let generated = cairo_consts::generate();
+    if std::env::var(testkit::UPDATE_ENV).is_ok_and(|v| !v.is_empty() && v != "0") {
+        fs::write(&path, &generated).unwrap();
+    }
+    let snapshot = fs::read_to_string(&path).unwrap();
+    assert_eq!(generated, snapshot, "consts.cairo is stale; rerun with {}=1", testkit::UPDATE_ENV);
+}
@@ -0,0 +1,186 @@
+//! The `zk100-host` binary end to end, in a `Sandbox` with stub `cairo-prove`
+//! and `scarb` on PATH.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+
+#[test]
+fn assemble_writes_args_and_metadata() {
+    let sandbox = Sandbox::new("cli-assemble");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,2", "-e", "2,4", "--metadata", "metadata.json"])
+        .success()
+        .stderr_has("Generated args file: args.json")
+        .stderr_has("Generated metadata file: metadata.json")
+        .stderr_lacks("warning");
+    let args: Vec<String> = serde_json::from_str(&sandbox.read("args.json")).unwrap();
+    assert_eq!(args[..6], ["0x2", "0x1", "0x2", "0x2", "0x2", "0x4"]);
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("metadata.json")).unwrap();
+    assert_eq!(metadata["options"]["inputs"], serde_json::json!([1, 2]));
+}
+
+#[test]
+fn assemble_failure_is_one_stable_line() {
+    let sandbox = Sandbox::new("cli-assemble-fail");
+    sandbox.write("bad.asm", "NODE (0,0)\nFROB ACC\n");
+    let run = sandbox.run_env(&["assemble", "bad.asm"], &[("RUST_BACKTRACE", "1"), ("RUST_LIB_BACKTRACE", "1")]);
+    run.code(1).stderr_has("error: ").stderr_lacks("backtrace").stderr_lacks("Caused by");
+    // Progress notes share stderr; the failure itself is the one last line.
+    assert_eq!(run.stderr().lines().filter(|line| line.starts_with("error")).count(), 1, "{}", run.stderr());
+    assert!(run.stderr().lines().last().unwrap().starts_with("error: "), "{}", run.stderr());
+    assert!(!sandbox.exists("args.json"));
+}
+
+#[test]
+fn assemble_reports_every_error() {
+    let sandbox = Sandbox::new("cli-assemble-errors");
+    sandbox.write("bad.asm", "NODE (0,0)\nFROB ACC\nNODE (0,x)\nNOP\nNODE (1,1)\nJMP nowhere\n");
+    let run = sandbox.run(&["assemble", "bad.asm"]);
+    run.code(1)
+        .stderr_has("error: 3 errors\n")
+        .stderr_has("\n  line 2, col 1, node (0,0): Unknown operation: FROB in `FROB ACC`\n")
+        .stderr_has("\n  line 3, col 6: Invalid node coordinates: (0,x)")
+        .stderr_has("\n  line 6, col 5, node (1,1): undefined label 'nowhere'");
+    assert!(!sandbox.exists("args.json"));
+}
+
+#[test]
+fn extra_operands_fail_unless_lenient() {
+    let sandbox = Sandbox::new("cli-extra-operands");
+    sandbox.write("tis.asm", "NODE (0,0)\nMOV IN, ACC\nADD 1, ACC\nMOV ACC, OUT\n");
+    sandbox
+        .run(&["assemble", "tis.asm", "-i", "1", "-e", "2"])
+        .code(1)
+        .stderr_has("line 3, col 8, node (0,0): ADD takes exactly one operand, found 2 (extra: ACC)");
+    assert!(!sandbox.exists("args.json"));
+    sandbox
+        .run(&["assemble", "tis.asm", "-i", "1", "-e", "2", "--lenient", "--metadata", "metadata.json"])
+        .success()
+        .stderr_has("warning: tis.asm: line 3: ADD takes exactly one operand, found 2 (extra: ACC); ignoring them (--lenient)");
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("metadata.json")).unwrap();
+    assert_eq!(metadata["options"]["lenient"], true);
+}
+
+#[test]
+fn label_case_follows_the_flag() {
+    let sandbox = Sandbox::new("cli-label-case");
+    sandbox.write("case.asm", "NODE (0,0)\nLoop: MOV IN, ACC\nMOV ACC, OUT\nJMP LOOP\n");
+    sandbox.run(&["assemble", "case.asm", "-i", "1", "-e", "1", "--metadata", "metadata.json"]).success();
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("metadata.json")).unwrap();
+    assert!(metadata["options"].get("case_sensitive_labels").is_none());
+    sandbox
+        .run(&["assemble", "case.asm", "-i", "1", "-e", "1", "-o", "sensitive.json", "--case-sensitive-labels"])
+        .code(1)
+        .stderr_has("undefined label 'LOOP' in node (0,0) (defined: Loop)");
+    assert!(!sandbox.exists("sensitive.json"));
+}
+
+#[test]
+fn defines_select_if_branches() {
+    let sandbox = Sandbox::new("cli-define");
+    sandbox.write("variant.asm", "NODE (0,0)\nMOV IN, ACC\n.if DEBUG\nADD 100\n.endif\nMOV ACC, OUT\n");
+    sandbox
+        .run(&["assemble", "variant.asm", "-i", "1", "-e", "1"])
+        .code(1)
+        .stderr_has("line 3: undefined constant 'DEBUG'");
+    sandbox.run(&["assemble", "variant.asm", "-i", "1", "-e", "1", "--define", "DEBUG=0", "--metadata", "release.json"]).success();
+    sandbox.run(&["assemble", "variant.asm", "-i", "1", "-e", "101", "--define", "DEBUG", "--metadata", "debug.json"]).success();
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("debug.json")).unwrap();
+    assert_eq!(metadata["options"]["defines"], serde_json::json!({ "DEBUG": 1 }));
+    sandbox.run(&["assemble", "variant.asm", "--define", "ACC=1"]).code(2).stderr_has("'ACC' cannot name a constant");
+}
+
+#[test]
+fn source_streams_stand_in_for_flags() {
+    let sandbox = Sandbox::new("cli-source-streams");
+    sandbox.write("double.asm", "## IN: 1, 2\n## IN: 3\n## OUT: 2, 4, 6\nNODE (0,0)\nMOV IN, ACC\nADD ACC\nMOV ACC, OUT\n");
+    sandbox.run(&["assemble", "double.asm", "--metadata", "metadata.json"]).success();
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("metadata.json")).unwrap();
+    assert_eq!(metadata["options"]["inputs"], serde_json::json!([1, 2, 3]));
+    assert_eq!(metadata["options"]["expected"], serde_json::json!([2, 4, 6]));
+
+    sandbox
+        .run(&["assemble", "double.asm", "-i", "5", "-e", "10", "--metadata", "flags.json"])
+        .success()
+        .stderr_has("notice: --inputs overrides the ## IN: lines of the source (from line 1)")
+        .stderr_has("notice: --expected overrides the ## OUT: lines of the source (from line 3)");
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("flags.json")).unwrap();
+    assert_eq!(metadata["options"]["inputs"], serde_json::json!([5]));
+
+    sandbox.write("bad.asm", "## IN: 1, x\nNODE (0,0)\n");
+    sandbox.run(&["assemble", "bad.asm"]).code(1).stderr_has("line 1: ## IN: 'x' is not a value");
+    sandbox.run(&["assemble", "double.asm", "-i", "1,y"]).code(1).stderr_has("--inputs: 'y' is not a value");
+}
+
+#[cfg(unix)]
+mod with_stubs {
+    use super::common::{CAIRO_PROVE_FAILING, DOUBLER, SCARB, Sandbox, fixture};
+    use std::fs;
+
+    fn proof() -> String {
+        fs::read_to_string(fixture("proofs/claim.json")).unwrap()
+    }
+
+    #[test]
+    fn prove_runs_the_prover_and_reads_the_proof() {
+        let sandbox = Sandbox::new("cli-prove");
+        sandbox.write("doubler.asm", DOUBLER).write("guest.json", "{}").stub_prover(&proof());
+        sandbox
+            .run(&["prove", "doubler.asm", "-i", "1", "-e", "2", "--guest", "guest.json"])
+            .success()
+            .stderr_has("Proof written to: proof.json")
+            .stdout_has("Program root: 0x5a17c0ffee")
+            .stdout_has("Solved: yes");
+        assert_eq!(sandbox.read("proof.json"), proof());
+        let calls = sandbox.prover_calls();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].starts_with("prove guest.json "), "{}", calls[0]);
+        assert!(calls[0].ends_with("--arguments-file args.json"), "{}", calls[0]);
+    }
+
+    #[test]
+    fn verify_proof_prints_public_output() {
+        let sandbox = Sandbox::new("cli-verify");
+        sandbox.write("proof.json", proof()).stub_prover("{}");
+        sandbox
+            .run(&["verify-proof", "proof.json"])
+            .success()
+            .stderr_has("Proof verified: proof.json")
+            .stdout_has("Score: 12 cycles, 3 messages, 3 nodes");
+        assert_eq!(sandbox.prover_calls(), ["verify proof.json"]);
+    }
+
+    #[test]
+    fn failing_prover_leaves_no_proof() {
+        let sandbox = Sandbox::new("cli-prove-fail");
+        sandbox.write("doubler.asm", DOUBLER).write("guest.json", "{}").stub("cairo-prove", CAIRO_PROVE_FAILING);
+        sandbox
+            .run(&["prove", "doubler.asm", "-i", "1", "-e", "2", "--guest", "guest.json"])
+            .code(1)
+            .stderr_has("constraint not satisfied")
+            .stderr_has("error: cairo-prove failed");
+        assert!(sandbox.exists("args.json"));
+        assert!(!sandbox.exists("proof.json"));
+    }
+
+    #[test]
+    fn doctor_checks_the_stubbed_toolchain() {
+        let sandbox = Sandbox::with_fixture("scarb_workspace");
+        sandbox.stub("scarb", SCARB).stub_prover("{}");
+        let args = ["doctor", "--scarb-dir", "crates/zk100_guest", "--scarb-package", "zk100_guest"];
+        sandbox
+            .run(&args)
+            .code(1)
+            .stdout_has("ok    scarb: scarb 2.11.4 (stub)")
+            .stdout_has("ok    cairo-prove: cairo-prove 0.1.0 (stub)")
+            .stdout_has("scarb --profile dev build --package zk100_guest")
+            .stderr_has("error: doctor found problems");
+
+        sandbox.write("target/dev/zk100_guest.executable.json", "{}");
+        sandbox.run(&args).success().stdout_has("ok    guest: ");
+    }
+}
@@ -0,0 +1,184 @@
+//! Shared harness for tests that drive the real `zk100-host` binary.
+//!
+//! A `Sandbox` is a `tempfile` scratch directory, removed on drop, that
+//! fixture trees are copied into. Stub tools written to its `bin/` come first
+//! on `PATH` for every command it runs, so `cairo-prove` and `scarb` never reach
+//! the real ones. `Run` keeps the process output and checks it with
+//! `assert_cmd` assertions.
+#![allow(dead_code)]
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use predicates::str::contains;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Stub `cairo-prove`: `prove` copies `bin/proof.json` to the proof path and
+/// `verify` succeeds when the proof exists. Each call is logged, one per line,
+/// to `bin/cairo-prove.log`.
+pub const CAIRO_PROVE: &str = r#"#!/bin/sh
+bin="$(dirname "$0")"
+echo "$*" >> "$bin/cairo-prove.log"
+case "$1" in
+  --version) echo "cairo-prove 0.1.0 (stub)" ;;
+  prove) cp "$bin/proof.json" "$3" ;;
+  verify) [ -f "$2" ] ;;
+  *) echo "stub cairo-prove: unknown command $1" >&2; exit 2 ;;
+esac
+"#;
+
+/// Stub `cairo-prove` whose every call fails like an unsatisfied constraint.
+pub const CAIRO_PROVE_FAILING: &str = "#!/bin/sh\necho \"constraint not satisfied\" >&2\nexit 1\n";
+
+/// Stub `scarb` that answers `--version` and accepts any build.
+pub const SCARB: &str = "#!/bin/sh\n[ \"$1\" = --version ] && echo \"scarb 2.11.4 (stub)\"\nexit 0\n";
+
+/// Doubles every input on its way from (0,0) to (1,1).
+pub const DOUBLER: &str = "NODE (0,0)\nMOV IN, ACC\nADD ACC\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+pub struct Sandbox {
+    dir: TempDir,
+}
+
+impl Sandbox {
+    pub fn new(name: &str) -> Self {
+        let dir = tempfile::Builder::new().prefix(&format!("zk100-{}-", name)).tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("bin")).unwrap();
+        Sandbox { dir }
+    }
+
+    /// A sandbox holding a copy of `tests/fixtures/<name>`.
+    pub fn with_fixture(name: &str) -> Self {
+        let sandbox = Sandbox::new(&name.replace('/', "-"));
+        copy_tree(&fixture(name), sandbox.dir.path());
+        sandbox
+    }
+
+    pub fn path(&self, rel: &str) -> PathBuf {
+        self.dir.path().join(rel)
+    }
+
+    pub fn write(&self, rel: &str, contents: impl AsRef<[u8]>) -> &Self {
+        let path = self.path(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        self
+    }
+
+    pub fn read(&self, rel: &str) -> String {
+        fs::read_to_string(self.path(rel)).unwrap_or_else(|e| panic!("cannot read {}: {}", rel, e))
+    }
+
+    pub fn exists(&self, rel: &str) -> bool {
+        self.path(rel).exists()
+    }
+
+    /// Install an executable script as `bin/<tool>`.
+    #[cfg(unix)]
+    pub fn stub(&self, tool: &str, script: &str) -> &Self {
+        use std::os::unix::fs::PermissionsExt;
+        let path = self.path(&format!("bin/{}", tool));
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        self
+    }
+
+    /// Install the `CAIRO_PROVE` stub, proving `proof` for every program.
+    #[cfg(unix)]
+    pub fn stub_prover(&self, proof: &str) -> &Self {
+        self.write("bin/proof.json", proof).stub("cairo-prove", CAIRO_PROVE)
+    }
+
+    /// Calls the stub `cairo-prove` received, in order.
+    pub fn prover_calls(&self) -> Vec<String> {
+        fs::read_to_string(self.path("bin/cairo-prove.log")).unwrap_or_default().lines().map(str::to_string).collect()
+    }
+
+    /// `zk100-host` with the sandbox as working directory and `bin/` first on PATH.
+    pub fn command(&self) -> Command {
+        let path = format!("{}:{}", self.path("bin").display(), std::env::var("PATH").unwrap_or_default());
+        let mut cmd = Command::cargo_bin("zk100-host").unwrap();
+        cmd.current_dir(self.dir.path()).env("PATH", path);
+        cmd
+    }
+
+    pub fn run(&self, args: &[&str]) -> Run {
+        self.run_env(args, &[])
+    }
+
+    pub fn run_env(&self, args: &[&str], env: &[(&str, &str)]) -> Run {
+        let output = self.command().args(args).envs(env.iter().copied()).output().unwrap();
+        Run { args: args.join(" "), output }
+    }
+
+    /// `run` with `input` on stdin, for the `-` arguments of a pipeline.
+    pub fn run_stdin(&self, args: &[&str], input: &[u8]) -> Run {
+        let output = assert_cmd::Command::from_std(self.command()).args(args).write_stdin(input).output().unwrap();
+        Run { args: args.join(" "), output }
+    }
+}
+
+fn copy_tree(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap_or_else(|e| panic!("cannot read {}: {}", from.display(), e)) {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_tree(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
+
+/// A finished command. The assertions print the command and both streams
+/// when they fail.
+pub struct Run {
+    args: String,
+    pub output: Output,
+}
+
+impl Run {
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.output.stdout).into_owned()
+    }
+
+    pub fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.output.stderr).into_owned()
+    }
+
+    fn assert(&self) -> assert_cmd::assert::Assert {
+        self.output.clone().assert().append_context("command", format!("zk100 {}", self.args))
+    }
+
+    pub fn success(&self) -> &Self {
+        self.assert().success();
+        self
+    }
+
+    pub fn code(&self, code: i32) -> &Self {
+        self.assert().code(code);
+        self
+    }
+
+    pub fn stdout_has(&self, needle: &str) -> &Self {
+        self.assert().stdout(contains(needle));
+        self
+    }
+
+    pub fn stderr_has(&self, needle: &str) -> &Self {
+        self.assert().stderr(contains(needle));
+        self
+    }
+
+    pub fn stderr_lacks(&self, needle: &str) -> &Self {
+        self.assert().stderr(contains(needle).not());
+        self
+    }
+}
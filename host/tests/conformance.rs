@@ -0,0 +1,79 @@
+//! `conformance` against stub guests: agreement, a reported divergence, the
+//! `scarb execute` runner, and skipping when the toolchain is missing.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{CAIRO_PROVE, Sandbox};
+use zk100_host::{assembler, examples, sim};
+
+/// The public output the guest would return for `doubler`'s case, solved or not.
+fn doubler_output(solved: bool) -> Vec<String> {
+    let example = examples::find("doubler").unwrap();
+    let programs = assembler::parse(example.source).unwrap().grid.programs;
+    let run = sim::simulate(&programs, example.cases[0].inputs, sim::DEFAULT_MAX_CYCLES).unwrap();
+    [0, 0, 0, run.cycles, run.msgs, u64::from(run.nodes_used), u64::from(solved)].iter().map(u64::to_string).collect()
+}
+
+fn proof(solved: bool) -> String {
+    serde_json::json!({ "public_input": { "output": doubler_output(solved) } }).to_string()
+}
+
+const ONLY_DOUBLER: [&str; 6] = ["--only", "example/doubler", "--random", "0", "--guest", "zk100_exec.executable.json"];
+
+#[test]
+fn agreement_and_divergence_are_reported() {
+    let sandbox = Sandbox::new("conformance-prove");
+    sandbox.write("zk100_exec.executable.json", "{}").stub_prover(&proof(true));
+    let run = sandbox.run(&[&["conformance", "--porcelain"][..], &ONLY_DOUBLER].concat());
+    assert_eq!(run.success().stdout(), "agree\texample/doubler [case 0]\n");
+    assert_eq!(sandbox.prover_calls().len(), 2);
+    assert!(!sandbox.exists(".zk100/conformance/000.args.json"));
+
+    sandbox.write("bin/proof.json", proof(false));
+    let run = sandbox.run(&[&["conformance"][..], &ONLY_DOUBLER].concat());
+    run.code(1)
+        .stdout_has("example/doubler [case 0] diverges:\n  - outputs: the guest's OUT stream is not the host's [2, 4, 6]\n")
+        .stdout_has("  inputs: [1, 2, 3]\n")
+        .stdout_has("    MOV IN, ACC\n")
+        .stderr_has("     Diverge example/doubler [case 0]\n")
+        .stderr_has("1 of 1 cases diverge from the guest");
+    assert!(sandbox.read(".zk100/conformance/000.asm").contains("MOV IN, ACC"));
+    assert!(sandbox.read(".zk100/conformance/000.args.json").starts_with("[\"0x3\""));
+}
+
+#[test]
+fn execute_runner_reads_printed_output() {
+    let sandbox = Sandbox::new("conformance-execute");
+    let printed = doubler_output(true).join("\n");
+    sandbox
+        .write("ws/target/dev/zk100_exec.executable.json", "{}")
+        .stub(
+            "scarb",
+            &format!(
+                "#!/bin/sh\necho \"$*\" >> \"$(dirname \"$0\")/scarb.log\"\n[ \"$1\" = --version ] && exit 0\necho \"Program output:\"\necho \"{}\"\n",
+                printed.replace('\n', "\\n")
+            ),
+        );
+    let run = sandbox.run(&[
+        "conformance", "--runner", "execute", "--only", "example/doubler", "--random", "0",
+        "--guest", "ws/target/dev/zk100_exec.executable.json",
+    ]);
+    run.success().stderr_has("       Agree example/doubler [case 0]\n");
+    let calls = sandbox.read("bin/scarb.log");
+    assert!(calls.contains("--profile dev execute --no-build --print-program-output --package zk100_exec --arguments-file "), "{}", calls);
+}
+
+#[test]
+fn missing_toolchain_is_skipped() {
+    let sandbox = Sandbox::new("conformance-skip");
+    let bin = sandbox.path("bin").display().to_string();
+    sandbox.write("zk100_exec.executable.json", "{}");
+    let run = sandbox.run_env(&["conformance", "--guest", "zk100_exec.executable.json"], &[("PATH", &bin)]);
+    run.success().stderr_has("notice: skipping the guest comparison: cairo-prove is not runnable");
+
+    sandbox.stub("cairo-prove", CAIRO_PROVE);
+    sandbox.run(&["conformance", "--guest", "missing.executable.json"]).success().stderr_has("does not exist");
+    sandbox.run(&["conformance"]).code(1).stderr_has("specify the guest");
+}
@@ -0,0 +1,97 @@
+//! Starts the JSON-RPC daemon on an ephemeral port and exercises every
+//! method over TCP, plus the `client` subcommand.
+
+#![cfg(feature = "cli")]
+
+use serde_json::json;
+use std::net::{SocketAddr, TcpListener};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use zk100_host::daemon::{self, RpcError, Server, ServerConfig};
+
+const DOUBLER: &str = "\
+NODE (0,0)
+MOV IN, ACC
+ADD ACC
+MOV ACC, P:DOWN
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT
+";
+
+fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Server::new(ServerConfig {
+        max_concurrency: 2,
+        timeout: Duration::from_secs(10),
+    });
+    thread::spawn(move || server.serve(listener));
+    addr
+}
+
+#[test]
+fn every_method_over_tcp() {
+    let addr = start_server();
+
+    let assembled = daemon::call(addr, "assemble", json!({ "source": DOUBLER, "inputs": [1, 2], "expected": [2, 4] })).unwrap();
+    let words = assembled["prog_words"].clone();
+    assert_eq!(words[0], 3, "(0,0) has three instructions");
+    assert_eq!(assembled["args"][0], "0x2");
+
+    let simulated = daemon::call(addr, "simulate", json!({ "prog_words": words, "inputs": [1, 2, 3] })).unwrap();
+    assert_eq!(simulated["outputs"], json!([2, 4, 6]));
+    assert_eq!(simulated["termination"], "deadlock");
+
+    let verified = daemon::call(addr, "verify", json!({ "source": DOUBLER, "inputs": [5], "expected": [10] })).unwrap();
+    assert_eq!(verified["passed"], true);
+    let verified = daemon::call(addr, "verify", json!({ "source": DOUBLER, "inputs": [5], "expected": [11] })).unwrap();
+    assert_eq!(verified["passed"], false);
+
+    let from_source = daemon::call(addr, "hash", json!({ "source": DOUBLER })).unwrap();
+    let from_words = daemon::call(addr, "hash", json!({ "prog_words": words })).unwrap();
+    assert_eq!(from_source, from_words);
+    assert_eq!(from_source["program_root"].as_str().unwrap().len(), 66);
+
+    let disassembled = daemon::call(addr, "disassemble", json!({ "prog_words": words })).unwrap();
+    let tokens = |s: &str| s.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+    assert_eq!(tokens(disassembled["assembly"].as_str().unwrap()), tokens(DOUBLER));
+
+    // prog_words do not record the grid; the 3x3 size comes back from
+    // assemble and goes into disassemble.
+    let source = "GRID 3 3\nNODE (2,2)\nMOV IN, OUT\n";
+    let assembled = daemon::call(addr, "assemble", json!({ "source": source, "abi": 7 })).unwrap();
+    assert_eq!(assembled["grid"], json!({ "rows": 3, "cols": 3 }));
+    let params = json!({ "prog_words": assembled["prog_words"], "grid": assembled["grid"] });
+    let disassembled = daemon::call(addr, "disassemble", params.clone()).unwrap();
+    assert_eq!(tokens(disassembled["assembly"].as_str().unwrap()), tokens(source));
+    assert_eq!(daemon::call(addr, "hash", params).unwrap(), daemon::call(addr, "hash", json!({ "source": source })).unwrap());
+
+    let err = daemon::call(addr, "assemble", json!({ "source": "NODE (5,5)\n" })).unwrap_err();
+    let err = err.downcast::<RpcError>().unwrap();
+    assert_eq!(err.code, daemon::EXECUTION_ERROR);
+    assert!(!err.data.diagnostics.is_empty());
+}
+
+#[test]
+fn client_subcommand_prints_result() {
+    let addr = start_server();
+    let dir = std::env::temp_dir().join(format!("zk100-daemon-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let params = dir.join("params.json");
+    std::fs::write(&params, json!({ "source": DOUBLER, "inputs": [4], "expected": [8] }).to_string()).unwrap();
+
+    let out = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .args(["client", "verify"])
+        .arg(&params)
+        .args(["--connect", &addr.to_string()])
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let result: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(result["passed"], true);
+}
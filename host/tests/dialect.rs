@@ -0,0 +1,75 @@
+//! Mnemonic aliases: the built-in table and `[aliases]` in zk100.toml.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+
+const DIALECT: &str = "NODE (0,0)\nstart:\nmov in, acc\nJEZ start\nNOOP\nGO done\ndone:\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, P:RIGHT\nHALT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+const CANONICAL: &str = "NODE (0,0)\nstart:\nmov in, acc\nJZ start\nNOP\nJMP done\ndone:\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, P:RIGHT\nHLT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+#[test]
+fn builtin_aliases_assemble_like_canonical_names() {
+    let sandbox = Sandbox::new("dialect-builtin");
+    sandbox
+        .write("dialect.asm", "NODE (0,0)\nNOOP\nJEZ 0\nHALT\n")
+        .write("canonical.asm", "NODE (0,0)\nNOP\nJZ 0\nHLT\n");
+    sandbox.run(&["assemble", "dialect.asm", "-o", "a.json"]).success();
+    sandbox.run(&["assemble", "canonical.asm", "-o", "b.json"]).success();
+    assert_eq!(sandbox.read("a.json"), sandbox.read("b.json"));
+}
+
+#[test]
+fn config_aliases_assemble_and_convert_to_canonical_names() {
+    let sandbox = Sandbox::new("dialect-config");
+    sandbox.write("dialect.asm", DIALECT).write("canonical.asm", CANONICAL);
+    sandbox.run(&["assemble", "dialect.asm"]).code(1).stderr_has("Unknown operation: GO");
+
+    sandbox.write("zk100.toml", "[aliases]\nGO = \"JMP\"\n");
+    sandbox.run(&["assemble", "dialect.asm", "-o", "a.json", "--metadata", "metadata.json"]).success();
+    sandbox.run(&["assemble", "canonical.asm", "-o", "b.json"]).success();
+    assert_eq!(sandbox.read("a.json"), sandbox.read("b.json"));
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("metadata.json")).unwrap();
+    assert_eq!(metadata["options"]["aliases"], serde_json::json!({"GO": "JMP"}));
+
+    // Formatting writes only canonical mnemonics.
+    let run = sandbox.run(&["convert", "dialect.asm", "--to", "asm"]);
+    run.success();
+    for alias in ["JEZ", "NOOP", "GO", "HALT"] {
+        assert!(!run.stdout().contains(alias), "{} left in:\n{}", alias, run.stdout());
+    }
+    let expected = sandbox.run(&["convert", "canonical.asm", "--to", "asm"]);
+    assert_eq!(run.stdout(), expected.stdout());
+
+    // The recorded aliases let attest reproduce the build without the config.
+    sandbox.write("zk100.toml", "");
+    sandbox.run(&["attest", "dialect.asm", "--against", "metadata.json"]).success();
+}
+
+#[test]
+fn colliding_aliases_are_rejected() {
+    let sandbox = Sandbox::new("dialect-collision");
+    sandbox.write("doubler.asm", DOUBLER);
+    for (table, message) in [
+        ("ADD = \"SUB\"", "alias 'ADD' collides with the ADD instruction"),
+        ("JEZ = \"JNZ\"", "alias 'JEZ' is defined more than once"),
+        ("GO = \"JMP\"\nGO = \"JZ\"", "alias 'GO' is defined more than once"),
+        ("GO = \"LEAP\"", "alias 'GO' names 'LEAP', which is not an instruction"),
+    ] {
+        sandbox.write("zk100.toml", format!("[aliases]\n{}\n", table));
+        sandbox.run(&["assemble", "doubler.asm"]).code(1).stderr_has(&format!("zk100.toml: {}", message));
+    }
+}
+
+#[test]
+fn capabilities_list_active_aliases() {
+    let sandbox = Sandbox::new("dialect-capabilities");
+    sandbox.run(&["capabilities"]).success().stdout_has("HALT=HLT JEZ=JZ NOOP=NOP");
+    sandbox.write("zk100.toml", "profile = \"guest-v1\"\n[aliases]\nGO = \"jmp\"\n");
+    let run = sandbox.run(&["capabilities", "--json"]);
+    run.success();
+    let caps: serde_json::Value = serde_json::from_slice(&run.output.stdout).unwrap();
+    assert_eq!(caps["aliases"], serde_json::json!({"GO": "JMP", "HALT": "HLT", "JEZ": "JZ", "NOOP": "NOP"}));
+}
@@ -0,0 +1,5 @@
+//! The core crate's encoding vectors, run through the host's re-exported paths.
+
+use zk100_host::instruction::{Dst, Inst, Op, PortTag, Src};
+
+include!("../core/tests/common/vectors.rs");
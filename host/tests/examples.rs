@@ -0,0 +1,52 @@
+//! Every embedded example must assemble, hash, round-trip through the args
+//! encoding and pass its cases in the simulator.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::process::Command;
+use zk100_host::examples::EXAMPLES;
+use zk100_host::{assembler, cairo_abi};
+
+#[test]
+fn every_example_passes_its_cases() {
+    for example in EXAMPLES {
+        assert!(!example.cases.is_empty(), "{} has no cases", example.name);
+        let run = example.run().unwrap_or_else(|e| panic!("{}: {}", example.name, e));
+        for case in &run.cases {
+            assert!(
+                case.passed,
+                "{}: inputs {:?} gave {:?}, expected {:?}",
+                example.name, case.case.inputs, case.outputs, case.case.expected
+            );
+        }
+
+        let words = assembler::encode_programs(&assembler::parse_assembly(example.source).unwrap()).unwrap();
+        let args = cairo_abi::generate_args(example.cases[0].inputs, example.cases[0].expected, &words).unwrap();
+        let parsed = cairo_abi::parse_args(&serde_json::to_string(&args).unwrap()).unwrap();
+        assert_eq!(parsed.prog_words, words, "{}", example.name);
+    }
+}
+
+#[test]
+fn example_cli() {
+    let zk100 = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_zk100-host")).args(args).output().unwrap();
+
+    let list = String::from_utf8(zk100(&["example", "list"]).stdout).unwrap();
+    for example in EXAMPLES {
+        assert!(list.contains(example.name), "{}", list);
+    }
+
+    let run = zk100(&["example", "run", "adder"]);
+    assert!(run.status.success());
+    assert!(String::from_utf8_lossy(&run.stdout).contains("-> [3, 7]"));
+    assert!(!zk100(&["example", "run", "missing"]).status.success());
+
+    let dir = std::env::temp_dir().join(format!("zk100-example-{}", std::process::id()));
+    assert!(zk100(&["example", "export", "doubler", dir.to_str().unwrap()]).status.success());
+    let source = fs::read_to_string(dir.join("doubler.asm")).unwrap();
+    let cases: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("cases.json")).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).ok();
+    assert!(source.contains("ADD ACC"));
+    assert_eq!(cases[0]["expected"], serde_json::json!([2, 4, 6]));
+}
@@ -0,0 +1,48 @@
+//! Expected-output patterns: a mixed spec judged by `report`, refused by
+//! `assemble`, and concretized with `--expected-from-simulation`.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+use zk100_host::cairo_abi;
+
+#[test]
+fn report_judges_a_mixed_spec() {
+    let sandbox = Sandbox::new("expected-report");
+    let case = |inputs: &str| format!("\n[[case]]\ninputs = [{}]\nexpected = [2, {{ min = 3, max = 5 }}, \"*\", \"len >= 3\"]\n", inputs);
+    let manifest = format!("[puzzle]\nname = \"doubler\"\nprogram = \"doubler.asm\"\n{}{}{}", case("1, 2, 3"), case("1, 2, 3, 4"), case("1, 3, 9"));
+    sandbox.write("doubler.asm", DOUBLER).write("puzzle.toml", manifest);
+
+    sandbox.run(&["report", "--manifest", "puzzle.toml", "--json"]).success();
+    let json: serde_json::Value = serde_json::from_str(&sandbox.read("report.json")).unwrap();
+    let passed: Vec<bool> = json["cases"].as_array().unwrap().iter().map(|c| c["passed"].as_bool().unwrap()).collect();
+    assert_eq!(passed, [true, true, false]);
+    assert_eq!(json["cases"][2]["failures"], serde_json::json!(["outputs[1]: expected 3..=5, got 6"]));
+    assert_eq!(json["cases"][0]["expected"], serde_json::json!([2, {"min": 3, "max": 5}, "*", "len >= 3"]));
+    assert!(sandbox.read("report.html").contains("outputs[1]: expected 3..=5, got 6"));
+}
+
+#[test]
+fn assemble_concretizes_patterns_from_simulation() {
+    let sandbox = Sandbox::new("expected-assemble");
+    sandbox.write("doubler.asm", DOUBLER);
+
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,2,3", "-e", "2,*,len>=3"])
+        .code(1)
+        .stderr_has("use --expected-from-simulation");
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,2,3", "-e", "2,7..=9,*", "--expected-from-simulation"])
+        .code(1)
+        .stderr_has("outputs[1]: expected 7..=9, got 4");
+
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,2,3", "-e", "2,*,len>=3", "--expected-from-simulation", "-o", "a.json"])
+        .success();
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1,2,3", "--expected-from-simulation", "-o", "b.json"]).success();
+    for name in ["a.json", "b.json"] {
+        assert_eq!(cairo_abi::parse_args(&sandbox.read(name)).unwrap().expected, [2, 4, 6]);
+    }
+}
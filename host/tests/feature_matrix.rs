@@ -0,0 +1,35 @@
+//! Every combination of the library's feature chunks must compile on its
+//! own, without warnings. Each combination is a `cargo check --lib
+//! --no-default-features` in a separate target directory, so it takes a
+//! while:
+//!
+//!     cargo test --test feature_matrix -- --ignored
+
+use std::path::Path;
+use std::process::Command;
+
+/// The chunks `lib.rs` gates modules on.
+const FEATURES: [&str; 7] = ["cli", "merkle", "abi-json", "sim", "schema", "lsp", "tracing"];
+
+#[test]
+#[ignore = "runs cargo check once per feature combination; run with -- --ignored"]
+fn every_feature_combination_compiles() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut failures = Vec::new();
+    for mask in 0..1u32 << FEATURES.len() {
+        let features: Vec<&str> =
+            FEATURES.iter().enumerate().filter(|(i, _)| mask & (1 << i) != 0).map(|(_, f)| *f).collect();
+        let output = Command::new(&cargo)
+            .current_dir(manifest_dir)
+            .env("CARGO_TARGET_DIR", manifest_dir.join("target/feature-matrix"))
+            .env("RUSTFLAGS", "-D warnings")
+            .args(["check", "--lib", "--quiet", "--no-default-features", "--features", &features.join(",")])
+            .output()
+            .unwrap();
+        if !output.status.success() {
+            failures.push(format!("[{}]\n{}", features.join(","), String::from_utf8_lossy(&output.stderr)));
+        }
+    }
+    assert!(failures.is_empty(), "{} combinations failed:\n{}", failures.len(), failures.join("\n"));
+}
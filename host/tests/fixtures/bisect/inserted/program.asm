@@ -0,0 +1,10 @@
+NODE (0,0)
+MOV IN, ACC
+ADD ACC
+NOP
+MOV ACC, P:DOWN
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT
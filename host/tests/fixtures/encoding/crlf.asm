@@ -0,0 +1,12 @@
+﻿# doubler, saved on Windows
+NODE (0,0)
+LOOP:
+MOV IN, ACC
+ADD ACC
+MOV ACC, P:DOWN
+JMP LOOP
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT
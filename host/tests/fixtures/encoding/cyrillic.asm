@@ -0,0 +1,4 @@
+NODE (0,0)
+MOV IN, ACC
+ADD АCC
+MOV ACC, P:DOWN
@@ -0,0 +1,10 @@
+# rsultat: le double de l'entre
+NODE (0,0)
+MOV IN, ACC # lire l'entre
+ADD ACC
+MOV ACC, P:DOWN
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT // crire
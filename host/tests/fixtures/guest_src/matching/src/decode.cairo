@@ -0,0 +1,16 @@
+use super::consts::{SRC_PORT_DIVISOR, SRC_PORT_MASK};
+
+fn decode_port(word: u32) -> PortTag {
+    let src_port_val = (word / 0x400000) & 0x3;
+    if src_port_val == 0 {
+        PortTag::Up
+    } else if src_port_val == 1 {
+        PortTag::Down
+    } else if src_port_val == 2 {
+        PortTag::Left
+    } else if src_port_val == 3 {
+        PortTag::Right
+    } else {
+        panic!("bad port")
+    }
+}
@@ -0,0 +1,59 @@
+// Generated by `zk100 gen-cairo-consts` from the host instruction tables.
+// Do not edit; regenerate instead.
+
+// Instruction word layout, most significant field first.
+// Cairo decodes by dividing by FIELD_DIVISOR and masking with FIELD_MASK.
+pub const LIT_SHIFT: u32 = 24;
+pub const LIT_DIVISOR: u32 = 0x1000000;
+pub const LIT_MASK: u32 = 0xff;
+pub const SRC_PORT_SHIFT: u32 = 22;
+pub const SRC_PORT_DIVISOR: u32 = 0x400000;
+pub const SRC_PORT_MASK: u32 = 0x3;
+pub const DST_PORT_SHIFT: u32 = 20;
+pub const DST_PORT_DIVISOR: u32 = 0x100000;
+pub const DST_PORT_MASK: u32 = 0x3;
+pub const OP_SHIFT: u32 = 16;
+pub const OP_DIVISOR: u32 = 0x10000;
+pub const OP_MASK: u32 = 0xf;
+pub const SRC_SHIFT: u32 = 8;
+pub const SRC_DIVISOR: u32 = 0x100;
+pub const SRC_MASK: u32 = 0xff;
+pub const DST_SHIFT: u32 = 0;
+pub const DST_DIVISOR: u32 = 0x1;
+pub const DST_MASK: u32 = 0xff;
+
+// Opcodes (OP field).
+pub const OP_MOV: felt252 = 1;
+pub const OP_ADD: felt252 = 2;
+pub const OP_SUB: felt252 = 3;
+pub const OP_NEG: felt252 = 4;
+pub const OP_SAV: felt252 = 5;
+pub const OP_SWP: felt252 = 6;
+pub const OP_JMP: felt252 = 7;
+pub const OP_JZ: felt252 = 8;
+pub const OP_JNZ: felt252 = 12;
+pub const OP_JGZ: felt252 = 10;
+pub const OP_JLZ: felt252 = 11;
+pub const OP_NOP: felt252 = 12;
+pub const OP_HLT: felt252 = 13;
+
+// Source operand codes (SRC field).
+pub const SRC_LIT: felt252 = 0;
+pub const SRC_ACC: felt252 = 1;
+pub const SRC_NIL: felt252 = 2;
+pub const SRC_IN: felt252 = 3;
+pub const SRC_P: felt252 = 4;
+pub const SRC_LAST: felt252 = 5;
+
+// Destination operand codes (DST field).
+pub const DST_ACC: felt252 = 0;
+pub const DST_NIL: felt252 = 1;
+pub const DST_OUT: felt252 = 2;
+pub const DST_P: felt252 = 3;
+pub const DST_LAST: felt252 = 4;
+
+// Port tags (SRC_PORT / DST_PORT fields).
+pub const PORT_UP: felt252 = 0;
+pub const PORT_DOWN: felt252 = 1;
+pub const PORT_LEFT: felt252 = 2;
+pub const PORT_RIGHT: felt252 = 3;
@@ -0,0 +1,15 @@
+NODE (0,0)
+start:
+MOV IN, ACC
+JZ done
+ADD 1
+MOV ACC, P:DOWN
+JMP start
+a:
+done:
+NOP
+end:
+NODE (1,0)
+MOV P:UP, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT
@@ -0,0 +1,3 @@
+NODE (0,0)
+MOV IN, ACC
+JMP nowhere
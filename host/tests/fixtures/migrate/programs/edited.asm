@@ -0,0 +1,10 @@
+# Edited after it was registered: ADD 1 used to be ADD ACC.
+NODE (0,0)
+MOV IN, ACC
+ADD 1
+MOV ACC, P:DOWN
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT
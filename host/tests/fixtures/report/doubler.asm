@@ -0,0 +1,12 @@
+NODE (0,0)
+MOV IN, ACC
+ADD ACC
+MOV ACC, P:DOWN
+
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+
+NODE (1,1)
+# doubled values leave here
+MOV P:LEFT, OUT
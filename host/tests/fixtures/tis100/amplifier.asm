@@ -0,0 +1,12 @@
+# SIGNAL AMPLIFIER: every input doubled.
+## IN: 1, 7, 40, 0
+## OUT: 2, 14, 80, 0
+NODE (0,0)
+MOV UP ACC
+ADD ACC
+MOV ACC DOWN
+NODE (1,0)
+MOV UP ACC
+MOV ACC RIGHT
+NODE (1,1)
+MOV LEFT DOWN
@@ -0,0 +1,14 @@
+# SIGNAL COMPARATOR: 1 for a positive input, 0 for zero.
+## IN: 3, 0, 12, 0, 0, 1
+## OUT: 1, 0, 1, 0, 0, 1
+NODE (0,0)
+START: MOV UP ACC
+JGZ POSITIVE
+MOV 0 DOWN
+JMP START
+POSITIVE: MOV 1 DOWN
+NODE (1,0)
+MOV UP ACC
+MOV ACC RIGHT
+NODE (1,1)
+MOV LEFT DOWN
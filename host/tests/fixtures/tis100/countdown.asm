@@ -0,0 +1,14 @@
+# Every input counted down to 1.
+## IN: 3, 2
+## OUT: 3, 2, 1, 2, 1
+NODE (0,0)
+MOV UP ACC
+LOOP:
+MOV ACC DOWN
+SUB 1
+JNZ LOOP
+NODE (1,0)
+MOV UP ACC
+MOV ACC RIGHT
+NODE (1,1)
+MOV LEFT DOWN
@@ -0,0 +1,11 @@
+# SELF-TEST DIAGNOSTIC: the input stream, unchanged.
+## IN: 51, 62, 0, 99
+## OUT: 51, 62, 0, 99
+NODE (0,0)
+MOV UP, ACC
+MOV ACC, DOWN
+NODE (1,0)
+MOV UP, ACC
+MOV ACC, RIGHT
+NODE (1,1)
+MOV LEFT, DOWN
@@ -0,0 +1,15 @@
+# Each pair of inputs in the other order, held in BAK.
+## IN: 4, 9, 2, 5
+## OUT: 9, 4, 5, 2
+NODE (0,0)
+MOV UP ACC
+SAV
+MOV UP ACC
+MOV ACC DOWN
+SWP
+MOV ACC DOWN
+NODE (1,0)
+MOV UP ACC
+MOV ACC RIGHT
+NODE (1,1)
+MOV LEFT DOWN
@@ -0,0 +1,59 @@
+//! `gen-program`: generated programs assemble and pass their own case in the
+//! simulator, and the same seed writes the same files.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::Sandbox;
+use zk100_host::assembler;
+use zk100_host::sim::{self, DEFAULT_MAX_CYCLES, Termination};
+
+const ARGS: [&str; 9] = ["gen-program", "--nodes", "4", "--instructions", "200", "--loops", "3", "--ports", "heavy"];
+
+#[test]
+fn generated_program_runs_its_case() {
+    let sandbox = Sandbox::new("gen-program");
+    let run = sandbox.run(&[&ARGS[..], &["--seed", "9", "--output", "synth.asm"]].concat());
+    run.success().stderr_has("Wrote synth.asm").stderr_has("Wrote synth.cases.json");
+
+    let source = sandbox.read("synth.asm");
+    let programs = assembler::parse_assembly(&source).unwrap();
+    for row in &programs {
+        for program in row {
+            assert_eq!(program.len(), 200);
+        }
+    }
+    let cases: serde_json::Value = serde_json::from_str(&sandbox.read("synth.cases.json")).unwrap();
+    let list = |key: &str| -> Vec<u32> {
+        cases[0][key].as_array().unwrap().iter().map(|v| v.as_u64().unwrap() as u32).collect()
+    };
+    let (inputs, expected) = (list("inputs"), list("expected"));
+    assert_eq!(inputs.len(), 4);
+    let result = sim::simulate(&programs, &inputs, DEFAULT_MAX_CYCLES).unwrap();
+    assert_eq!(result.termination, Termination::Deadlock);
+    assert_eq!(result.outputs, expected);
+
+    let join = |values: &[u32]| values.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+    sandbox.run(&["assemble", "synth.asm", "-i", &join(&inputs), "-e", &join(&expected)]).success();
+}
+
+#[test]
+fn same_seed_same_files() {
+    let sandbox = Sandbox::new("gen-program-seed");
+    for (seed, output) in [("9", "a.asm"), ("9", "b.asm"), ("10", "c.asm")] {
+        sandbox.run(&[&ARGS[..], &["--seed", seed, "--output", output]].concat()).success();
+    }
+    assert_eq!(sandbox.read("a.asm"), sandbox.read("b.asm"));
+    assert_eq!(sandbox.read("a.cases.json"), sandbox.read("b.cases.json"));
+    assert_ne!(sandbox.read("a.asm"), sandbox.read("c.asm"));
+
+    // Without --output the program goes to stdout.
+    let run = sandbox.run(&[&ARGS[..], &["--seed", "9"]].concat());
+    assert_eq!(run.success().stdout(), sandbox.read("a.asm"));
+
+    sandbox
+        .run(&["gen-program", "--instructions", "10", "--loops", "2"])
+        .code(1)
+        .stderr_has("node (0,0) needs at least 20 for 2 loops");
+}
@@ -0,0 +1,11 @@
+//! Golden outputs (prog_words, program root, args.json) for every case under
+//! tests/fixtures/golden/, checked with the exported testkit. Bless changes
+//! with `UPDATE_GOLDENS=1 cargo test --test golden`.
+
+use std::path::Path;
+use zk100_host::testkit;
+
+#[test]
+fn golden_cases() {
+    testkit::assert_goldens(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/golden"));
+}
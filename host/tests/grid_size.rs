@@ -0,0 +1,35 @@
+//! `GRID` and `--rows`/`--cols`: a grid other than 2x2 assembles, runs and
+//! travels in ABI 7 args.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::Sandbox;
+
+/// A 1x3 pipeline: double in (0,0), add one in (0,1), write out of (0,2).
+const PIPELINE: &str = "GRID 1 3\nNODE (0,0)\nMOV IN, ACC\nADD ACC\nMOV ACC, P:RIGHT\nNODE (0,1)\nMOV P:LEFT, ACC\nADD 1\nMOV ACC, P:RIGHT\nNODE (0,2)\nMOV P:LEFT, OUT\n";
+
+#[test]
+fn grid_directive_assembles_into_abi_7() {
+    let sandbox = Sandbox::new("grid-size");
+    sandbox.write("pipeline.asm", PIPELINE);
+    let args = ["assemble", "pipeline.asm", "-i", "1,2", "-e", "3,5", "--out-node", "0,2"];
+    sandbox.run(&args).code(1).stderr_has("error: a 1x3 grid needs ABI 7");
+
+    sandbox.run(&[&args[..], &["--abi", "7"]].concat()).success().stderr_lacks("warning");
+    let words: Vec<String> = serde_json::from_str(&sandbox.read("args.json")).unwrap();
+    assert_eq!(words[..2], ["0x1", "0x3"]);
+    sandbox.run(&["inspect-args", "args.json", "--abi", "7"]).success().stdout_has("ABI 7: ").stdout_has("grid        1x3");
+
+    // The flags must agree with the directive, and size a source without one.
+    sandbox
+        .run(&[&args[..], &["--abi", "7", "--rows", "3", "--cols", "1"]].concat())
+        .code(1)
+        .stderr_has("the source declares a 1x3 grid, but 3x1 was asked for");
+    sandbox.write("plain.asm", &PIPELINE["GRID 1 3\n".len()..]);
+    sandbox.run(&["assemble", "plain.asm", "-i", "1"]).code(1).stderr_has("Node coordinates must be in 2x2 grid: (0,2)");
+    sandbox
+        .run(&["assemble", "plain.asm", "-i", "1,2", "-e", "3,5", "--out-node", "0,2", "--abi", "7", "--rows", "1", "--cols", "3"])
+        .success();
+}
@@ -0,0 +1,67 @@
+//! `check-guest` against fake guest trees, and against the real guest when
+//! the host is built inside the full repo.
+
+#![cfg(feature = "cli")]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use zk100_host::guest_check::{self, Finding};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/guest_src").join(name)
+}
+
+fn check_guest(dir: &Path) -> (bool, String) {
+    let out = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .arg("check-guest")
+        .arg("--guest-src")
+        .arg(dir)
+        .output()
+        .unwrap();
+    let text = String::from_utf8_lossy(&out.stdout).into_owned() + &String::from_utf8_lossy(&out.stderr);
+    (out.status.success(), text)
+}
+
+#[test]
+fn matching_tree_passes() {
+    let report = guest_check::check(&fixture("matching")).unwrap();
+    assert!(report.is_ok(), "{}", report);
+    assert!(check_guest(&fixture("matching")).0);
+}
+
+#[test]
+fn mismatch_names_both_values_and_location() {
+    let report = guest_check::check(&fixture("mismatching")).unwrap();
+    let mismatches: Vec<&Finding> = report.findings.iter().filter(|f| f.is_failure()).collect();
+    assert_eq!(mismatches.len(), 1, "{}", report);
+    let line = mismatches[0].to_string();
+    assert!(line.contains("OP_JNZ"), "{}", line);
+    assert!(line.contains("host 9") && line.contains("guest 12"), "{}", line);
+    assert!(line.contains("src/consts.cairo:34"), "{}", line);
+
+    let (ok, text) = check_guest(&fixture("mismatching"));
+    assert!(!ok);
+    assert!(text.contains("OP_JNZ"), "{}", text);
+}
+
+#[test]
+fn missing_constant_fails() {
+    let report = guest_check::check(&fixture("missing")).unwrap();
+    assert!(!report.is_ok());
+    assert!(
+        report.findings.iter().any(|f| matches!(f, Finding::Missing { name, .. } if name == "OP_NEG")),
+        "{}",
+        report
+    );
+    assert!(!check_guest(&fixture("missing")).0);
+}
+
+#[test]
+fn real_guest_has_no_drift() {
+    let guest = Path::new(env!("CARGO_MANIFEST_DIR")).join("../crates");
+    if !guest.is_dir() {
+        return;
+    }
+    let report = guest_check::check(&guest).unwrap();
+    assert!(report.is_ok(), "{}", report);
+}
@@ -0,0 +1,61 @@
+//! The host simulator against the real Cairo guest: every conformance case
+//! and a batch of seeded random programs must agree. It needs the guest
+//! toolchain, so it is opt-in:
+//!
+//!     cargo test --features guest-compare -- --ignored
+//!
+//! The guest is `ZK100_GUEST` if set, else the `zk100_exec` artifact of
+//! `crates/exec` (build it with `scarb build` first). `ZK100_GUEST_RUNNER=
+//! execute` runs it with `scarb execute` instead of proving every case, and
+//! `ZK100_CONFORMANCE_SEED` picks other random programs. Without the
+//! toolchain the test prints a notice and passes.
+
+#![cfg(feature = "guest-compare")]
+
+use std::path::{Path, PathBuf};
+use zk100_host::conformance::{self, Guest, Runner};
+use zk100_host::scarb;
+
+const RANDOM_PROGRAMS: usize = 50;
+
+fn guest() -> Result<Guest, String> {
+    let runner = match std::env::var("ZK100_GUEST_RUNNER").as_deref() {
+        Ok("execute") => Runner::Execute,
+        Ok("prove") | Err(_) => Runner::Prove,
+        Ok(other) => panic!("ZK100_GUEST_RUNNER must be prove or execute, not {}", other),
+    };
+    let artifact = match std::env::var_os("ZK100_GUEST") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let exec = Path::new(env!("CARGO_MANIFEST_DIR")).join("../crates/exec");
+            scarb::locate_guest(&exec, "zk100_exec", scarb::DEFAULT_PROFILE, false).map_err(|e| e.to_string())?
+        }
+    };
+    let guest = Guest { artifact, runner };
+    match guest.unavailable() {
+        Some(reason) => Err(reason),
+        None => Ok(guest),
+    }
+}
+
+#[test]
+#[ignore = "runs every case through the Cairo guest; run with --features guest-compare -- --ignored"]
+fn host_and_guest_agree() {
+    let guest = match guest() {
+        Ok(guest) => guest,
+        Err(reason) => {
+            eprintln!("notice: skipping the guest comparison: {}", reason);
+            return;
+        }
+    };
+    let seed = std::env::var("ZK100_CONFORMANCE_SEED").map_or(0, |seed| seed.parse().expect("a u64 seed"));
+    let mut cases = conformance::corpus().unwrap();
+    cases.extend(conformance::random(seed, RANDOM_PROGRAMS).unwrap());
+    let work = Path::new(env!("CARGO_TARGET_TMPDIR")).join("guest-compare");
+    let summary = conformance::run(&cases, &guest, &work, |case, differences| {
+        eprintln!("{} {}", if differences.is_empty() { "agree  " } else { "DIVERGE" }, case.name);
+    })
+    .unwrap();
+    let report: Vec<String> = summary.divergences.iter().map(ToString::to_string).collect();
+    assert!(report.is_empty(), "{} of {} cases diverge:\n{}", report.len(), cases.len(), report.join("\n"));
+}
@@ -0,0 +1,34 @@
+//! `--hints`: the hints file `assemble` writes and `prove` passes on.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox, fixture};
+use std::fs;
+
+#[test]
+fn assemble_writes_an_empty_hints_file() {
+    let sandbox = Sandbox::new("hints-assemble");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "3", "-e", "6", "-o", "args.json", "--hints", "hints.json"])
+        .success()
+        .stderr_has("Generated hints file: hints.json");
+    let hints: serde_json::Value = serde_json::from_str(&sandbox.read("hints.json")).unwrap();
+    assert_eq!(hints, serde_json::json!({"version": 1, "hints": []}));
+}
+
+#[test]
+fn prove_passes_the_hints_file_to_the_prover() {
+    let sandbox = Sandbox::new("hints-prove");
+    sandbox.write("doubler.asm", DOUBLER).write("guest.json", "{}").stub_prover(&fs::read_to_string(fixture("proofs/claim.json")).unwrap());
+    sandbox
+        .run(&["prove", "doubler.asm", "-i", "3", "-e", "6", "--guest", "guest.json", "--proof", "proof.json", "--hints", "hints.json"])
+        .success();
+    assert!(sandbox.exists("hints.json"));
+    assert!(sandbox.prover_calls()[0].ends_with("--hints-file hints.json"), "{:?}", sandbox.prover_calls());
+
+    sandbox.run(&["prove", "doubler.asm", "-i", "3", "-e", "6", "--guest", "guest.json", "--proof", "proof.json"]).success();
+    assert!(!sandbox.prover_calls()[1].contains("--hints-file"));
+}
@@ -0,0 +1,62 @@
+//! `import-corpus` over a fixture corpus with one good, one unsupported, one
+//! corrupt save and one save for a puzzle missing from the spec.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use zk100_host::corpus::{self, ImportManifest, Outcome};
+use zk100_host::{assembler, sim};
+
+fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tis_corpus")
+}
+
+fn scratch(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("zk100-corpus-{}-{}", name, std::process::id()))
+}
+
+#[test]
+fn every_save_is_attributed() {
+    let out = scratch("lib");
+    let summary = corpus::import_dir(&corpus_dir(), &corpus_dir().join(corpus::SPEC_FILE), &out).unwrap();
+    let outcomes: Vec<(String, &Outcome)> = summary
+        .files
+        .iter()
+        .map(|f| (f.path.to_string_lossy().into_owned(), &f.outcome))
+        .collect();
+    assert_eq!(outcomes.len(), 4);
+    assert!(matches!(outcomes[0], (ref p, Outcome::Imported { .. }) if p == "saves/90001.0.txt"));
+    assert!(matches!(outcomes[1].1, Outcome::Skipped { reason } if reason.contains("ANY port")));
+    assert!(matches!(outcomes[2].1, Outcome::Failed { error } if error.contains("bad node header")));
+    assert!(matches!(outcomes[3].1, Outcome::Skipped { reason } if reason.contains("no spec for puzzle '90002'")));
+    assert_eq!((summary.imported(), summary.skipped(), summary.failed()), (1, 2, 1));
+
+    // The imported program runs and produces the puzzle's output stream.
+    let program = fs::read_to_string(out.join("90001.0/program.asm")).unwrap();
+    let manifest: ImportManifest =
+        serde_json::from_str(&fs::read_to_string(out.join("90001.0/manifest.json")).unwrap()).unwrap();
+    fs::remove_dir_all(&out).ok();
+    assert_eq!(manifest.name, "SIGNAL RELAY");
+    let result = sim::simulate(&assembler::parse_assembly(&program).unwrap(), &manifest.inputs, sim::DEFAULT_MAX_CYCLES)
+        .unwrap();
+    assert!(result.matches(&manifest.expected), "{:?}", result.outputs);
+}
+
+#[test]
+fn cli_prints_summary() {
+    let out = scratch("cli");
+    let output = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .arg("import-corpus")
+        .arg(corpus_dir())
+        .arg("--out-dir")
+        .arg(&out)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&out).ok();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("4 saves: 1 imported, 2 skipped, 1 failed"), "{}", stdout);
+    assert!(stdout.contains("FAILED    saves/90001.2.txt"), "{}", stdout);
+}
@@ -0,0 +1,53 @@
+//! `IncrementalAssembler` against full rebuilds.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::DOUBLER;
+use zk100_host::assembler;
+use zk100_host::incremental::{Counters, IncrementalAssembler};
+use zk100_host::merkle;
+
+/// What a one-shot build produces for `source`.
+fn full(source: &str) -> (Vec<u32>, String) {
+    let programs = assembler::parse_assembly(source).unwrap();
+    (assembler::encode_programs(&programs).unwrap(), merkle::program_root_hex(&programs).unwrap())
+}
+
+#[test]
+fn editing_one_node_rebuilds_only_that_node() {
+    let mut incremental = IncrementalAssembler::new();
+    let build = incremental.update(DOUBLER).unwrap();
+    assert_eq!((build.prog_words.unwrap(), build.root.unwrap()), full(DOUBLER));
+    assert_eq!(incremental.counters(), Counters { updates: 1, nodes_built: 3, nodes_reused: 0 });
+
+    let edited = DOUBLER.replace("MOV P:LEFT, OUT", "MOV P:LEFT, ACC\nMOV ACC, OUT");
+    let build = incremental.update(&edited).unwrap();
+    assert_eq!(incremental.counters(), Counters { updates: 2, nodes_built: 4, nodes_reused: 2 });
+    assert_eq!(build.programs, assembler::parse_assembly(&edited).unwrap());
+    assert_eq!((build.prog_words.unwrap(), build.root.unwrap()), full(&edited));
+
+    // Shifting a node's lines without changing its text reuses it too.
+    let shifted = format!("# header comment\n\n{}", edited);
+    let build = incremental.update(&shifted).unwrap();
+    assert_eq!(incremental.counters().nodes_built, 4);
+    assert_eq!(build.root.unwrap(), full(&shifted).1);
+}
+
+#[test]
+fn a_broken_node_is_rebuilt_once_fixed() {
+    let mut incremental = IncrementalAssembler::new();
+    let broken = DOUBLER.replace("MOV P:LEFT, OUT", "MOV P:LEFT");
+    let build = incremental.update(&broken).unwrap();
+    assert_eq!(build.errors.keys().copied().collect::<Vec<_>>(), [(1, 1)]);
+    assert!(build.root.is_none());
+    // Typing elsewhere does not rebuild the good nodes or the broken one.
+    let build = incremental.update(&broken).unwrap();
+    assert!(!build.is_ok());
+    assert_eq!(incremental.counters().nodes_built, 3);
+
+    let build = incremental.update(DOUBLER).unwrap();
+    assert_eq!(incremental.counters().nodes_built, 4);
+    assert_eq!(build.root.unwrap(), full(DOUBLER).1);
+}
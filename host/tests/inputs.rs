@@ -0,0 +1,70 @@
+//! Generated inputs through the CLI: `--inputs-gen` expands before the args
+//! are written and the spec lands in the metadata sidecar.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const DOUBLER: &str = "NODE (0,0)\nMOV IN, ACC\nADD ACC\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zk100-inputs-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn csv_column_is_expanded_and_recorded() {
+    let dir = scratch("csv");
+    fs::write(dir.join("doubler.asm"), DOUBLER).unwrap();
+    fs::write(dir.join("data.csv"), "id,value\n1,10\n2,20\n3,30\n").unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .current_dir(&dir)
+        .args(["assemble", "doubler.asm", "--inputs-gen", r#"{ file = "data.csv", column = 2 }"#])
+        .args(["--expected", "20,40,60", "--metadata", "metadata.json"])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let args: Vec<serde_json::Value> = serde_json::from_str(&fs::read_to_string(dir.join("args.json")).unwrap()).unwrap();
+    let metadata: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("metadata.json")).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).ok();
+
+    let words: Vec<String> = args.iter().map(|v| v.as_str().unwrap_or_default().to_string()).collect();
+    let inputs = ["0x3", "0xa", "0x14", "0x1e"];
+    assert!(words.windows(4).any(|w| w == inputs), "inputs not in args: {:?}", words);
+    assert_eq!(metadata["options"]["inputs_gen"], serde_json::json!({"file": "data.csv", "column": 2}));
+}
+
+#[test]
+fn conflicting_input_flags_are_rejected() {
+    let out = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .args(["assemble", "x.asm", "-i", "1", "--inputs-gen", "{ range = [1, 3] }"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn report_cases_accept_generators() {
+    let dir = scratch("report");
+    fs::write(dir.join("doubler.asm"), DOUBLER).unwrap();
+    fs::write(
+        dir.join("puzzle.toml"),
+        "[puzzle]\nname = \"doubler\"\nprogram = \"doubler.asm\"\n\n[[case]]\ninputs = { range = [1, 4] }\nexpected = [2, 4, 6, 8]\n",
+    )
+    .unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .current_dir(&dir)
+        .args(["report", "--manifest", "puzzle.toml", "--output", "report.html", "--json"])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(json["cases"][0]["inputs"], serde_json::json!([1, 2, 3, 4]));
+    assert_eq!(json["cases"][0]["inputs_gen"], serde_json::json!({"range": [1, 4]}));
+    assert_eq!(json["passed"], 1);
+}
@@ -0,0 +1,50 @@
+//! `inspect-args` over args files of each layout.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+use zk100_host::cairo_abi;
+
+#[test]
+fn sections_and_projections_match_the_eager_parse() {
+    let sandbox = Sandbox::new("inspect-args");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1,2,3", "-e", "2,4,6"]).success();
+    let args = cairo_abi::parse_args(&sandbox.read("args.json")).unwrap();
+
+    sandbox
+        .run(&["inspect-args", "args.json"])
+        .success()
+        .stdout_has("ABI 1: 19 values")
+        .stdout_has("prog_words          10 values  args[8..19]");
+
+    let run = sandbox.run(&["inspect-args", "args.json", "--only", "prog-words"]);
+    run.success();
+    let words: Vec<u32> = run.stdout().lines().map(|line| line.parse().unwrap()).collect();
+    assert_eq!(words, args.prog_words);
+
+    let run = sandbox.run_stdin(&["inspect-args", "-", "--only", "inputs", "--json"], sandbox.read("args.json").as_bytes());
+    run.success();
+    let json: serde_json::Value = serde_json::from_slice(&run.output.stdout).unwrap();
+    assert_eq!(json["only"], serde_json::json!([1, 2, 3]));
+    assert_eq!(json["sections"]["expected"], serde_json::json!({"values": 3, "start": 4, "end": 8}));
+}
+
+#[test]
+fn batch_and_malformed_files() {
+    let sandbox = Sandbox::new("inspect-args-batch");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1", "-e", "2", "-o", "a.json"]).success();
+    sandbox.run(&["assemble", "doubler.asm", "-i", "5,6", "-e", "10,12", "-o", "b.json"]).success();
+    sandbox.run(&["batch", "merge", "a.json", "b.json", "-o", "batch.json"]).success();
+    sandbox.run(&["inspect-args", "batch.json", "--abi", "3"]).success().stdout_has("ABI 3: 22 values, 2 cases");
+    let run = sandbox.run(&["inspect-args", "batch.json", "--abi", "3", "--only", "expected"]);
+    assert_eq!(run.stdout(), "2\n10\n12\n");
+
+    sandbox.write("short.json", r#"["0x5", "0x1", "0x0", "0x0"]"#);
+    sandbox.run(&["inspect-args", "short.json"]).code(1).stderr_has("inputs declares 5 values at args[0] but only 3 remain");
+    sandbox.write("bad.json", r#"["0x0", "0x0", "0x1", "zz"]"#);
+    sandbox.run(&["inspect-args", "bad.json"]).code(1).stderr_has("args[3]: 'zz' is not a u32 value");
+}
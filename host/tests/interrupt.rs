@@ -0,0 +1,53 @@
+//! Ctrl-C during `prove`: the stub prover is killed, no proof (partial or
+//! temporary) is left behind and the exit code is the interruption code.
+#![cfg(all(unix, feature = "cli"))]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use zk100_host::exec::INTERRUPTED_EXIT_CODE;
+
+#[test]
+fn sigint_during_prove_cleans_up() {
+    let dir = std::env::temp_dir().join(format!("zk100-interrupt-{}", std::process::id()));
+    fs::create_dir_all(dir.join("bin")).unwrap();
+    // Stub prover: writes half a proof to its output argument, records its
+    // pid, then sleeps.
+    let stub = dir.join("bin/cairo-prove");
+    fs::write(&stub, "#!/bin/sh\necho '{\"partial' > \"$3\"\necho $$ > \"$(dirname \"$3\")/stub.pid\"\nexec sleep 30\n").unwrap();
+    fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(dir.join("guest.executable.json"), "{}").unwrap();
+    fs::write(dir.join("program.asm"), "NODE (0,0)\nNOP\n").unwrap();
+
+    let path = format!("{}:{}", dir.join("bin").display(), std::env::var("PATH").unwrap_or_default());
+    let mut child = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .current_dir(&dir)
+        .env("PATH", path)
+        .args(["prove", "program.asm", "--guest", "guest.executable.json"])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let started = Instant::now();
+    while !dir.join("stub.pid").exists() {
+        assert!(started.elapsed() < Duration::from_secs(20), "stub prover never started");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let stub_pid = fs::read_to_string(dir.join("stub.pid")).unwrap();
+    Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    let status = child.wait().unwrap();
+
+    let mut leftovers: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    leftovers.sort();
+    let stub_alive = fs::read_to_string(format!("/proc/{}/stat", stub_pid.trim()))
+        .is_ok_and(|stat| !stat.rsplit(')').next().unwrap_or("").trim_start().starts_with('Z'));
+    fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(status.code(), Some(INTERRUPTED_EXIT_CODE));
+    assert_eq!(leftovers, ["args.json", "bin", "guest.executable.json", "program.asm", "stub.pid"]);
+    assert!(!stub_alive, "stub prover survived the interrupt");
+}
@@ -0,0 +1,45 @@
+//! `--in-node` / `--out-node`: the coordinates travel in ABI 5 args and the
+//! metadata sidecar, and reading IN elsewhere is warned about.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+const SIDEWAYS: &str = "NODE (0,1)\nMOV IN, ACC\nMOV ACC, P:LEFT\nNODE (0,0)\nMOV P:RIGHT, ACC\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, OUT\n";
+
+fn zk100(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_zk100-host")).current_dir(dir).args(args).output().unwrap()
+}
+
+#[test]
+fn io_nodes_travel_in_args_and_metadata() {
+    let dir = std::env::temp_dir().join(format!("zk100-io-nodes-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("prog.asm"), SIDEWAYS).unwrap();
+    let moved = ["assemble", "prog.asm", "-i", "4", "-e", "4", "--in-node", "0,1", "--out-node", "1,0"];
+
+    let legacy = zk100(&dir, &moved);
+    assert!(!legacy.status.success());
+    assert!(String::from_utf8_lossy(&legacy.stderr).contains("need ABI 5"));
+
+    let out = zk100(&dir, &[&moved[..], &["--abi", "5", "--metadata", "metadata.json"]].concat());
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("reads IN"));
+    let args: Vec<String> = serde_json::from_str(&fs::read_to_string(dir.join("args.json")).unwrap()).unwrap();
+    assert_eq!(args[..7], ["0x0", "0x1", "0x1", "0x0", "0x1", "0x4", "0x1"]);
+    let metadata: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("metadata.json")).unwrap()).unwrap();
+    assert_eq!(metadata["options"]["io"], serde_json::json!({"in_node": [0, 1], "out_node": [1, 0]}));
+    let attest = zk100(&dir, &["attest", "prog.asm", "--against", "metadata.json"]);
+    assert!(attest.status.success(), "{}", String::from_utf8_lossy(&attest.stderr));
+
+    // With the default nodes, (0,1) reading IN is flagged.
+    let default = zk100(&dir, &["assemble", "prog.asm"]);
+    assert!(default.status.success());
+    assert!(String::from_utf8_lossy(&default.stderr).contains("line 2: node (0,1) reads IN, but the input stream goes to node (0,0)"));
+
+    let outside = zk100(&dir, &["assemble", "prog.asm", "--abi", "5", "--in-node", "2,0"]);
+    assert!(!outside.status.success());
+    fs::remove_dir_all(&dir).ok();
+}
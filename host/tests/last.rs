@@ -0,0 +1,30 @@
+//! `LAST`: the reachability warning and the `last` setting of zk100.toml.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::Sandbox;
+
+const USES_LAST: &str = "NODE (1,1)\nMOV 5, ACC\nMOV LAST, ACC\nMOV ACC, OUT\nMOV P:UP, ACC\n";
+
+#[test]
+fn last_warns_and_follows_the_setting() {
+    let sandbox = Sandbox::new("last");
+    sandbox.write("last.asm", USES_LAST);
+    let run = sandbox.run(&["assemble", "last.asm", "--expected-from-simulation", "-o", "args.json"]);
+    run.success().stderr_has("warning: last.asm: line 3: node (1,1) can reach LAST before any ANY transfer sets it");
+    // No inputs, and no outputs: the run blocks at LAST.
+    assert!(sandbox.read("args.json").starts_with("[\"0x0\",\"0x0\",\"0x8\""), "{}", sandbox.read("args.json"));
+
+    sandbox.write("zk100.toml", "last = \"nil\"\n");
+    sandbox.run(&["assemble", "last.asm", "--expected-from-simulation", "-o", "args.json"]).success();
+    // Reading LAST as 0, the node writes that 0 to OUT.
+    assert!(sandbox.read("args.json").starts_with("[\"0x0\",\"0x1\",\"0x0\",\"0x8\""), "{}", sandbox.read("args.json"));
+
+    sandbox.write("zk100.toml", "last = \"any\"\n");
+    sandbox
+        .run(&["assemble", "last.asm", "--expected-from-simulation"])
+        .code(1)
+        .stderr_has("zk100.toml: last must be block or nil, found any");
+}
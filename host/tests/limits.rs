@@ -0,0 +1,70 @@
+//! Limits: `[limits]` in zk100.toml, `--limit` on top, and errors that say
+//! which limit bit and where it was set.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+
+#[test]
+fn config_and_flag_limits_name_their_origin() {
+    let sandbox = Sandbox::new("limits-assemble");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1,2,3", "--expected-from-simulation"]).success();
+
+    sandbox.write("zk100.toml", "[limits]\nnode_instructions = 2\n");
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,2,3", "--expected-from-simulation"])
+        .code(1)
+        .stderr_has("node (0,0) has 3 instructions, over the node_instructions limit of 2 (from zk100.toml)");
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,2,3", "--expected-from-simulation", "--limit", "node_instructions=3"])
+        .success()
+        .stderr_has("Node (0,0): 3 of 3 instructions")
+        .stderr_has("Node (0,1): 0 of 3 instructions");
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,2,3", "--expected-from-simulation", "--max-instructions", "2"])
+        .code(1)
+        .stderr_has("node (0,0) has 3 instructions, over the node_instructions limit of 2 (from --limit)");
+
+    sandbox.write("zk100.toml", "# no limits\n");
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,2,3", "--expected-from-simulation", "--limit", "outputs=2"])
+        .code(1)
+        .stderr_has("OUT has produced 3 values, over the outputs limit of 2 (from --limit)");
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,2,3", "-e", "2,4,6", "--limit", "inputs=2"])
+        .code(1)
+        .stderr_has("the input stream has 3 values, over the inputs limit of 2 (from --limit)");
+    sandbox.run(&["assemble", "doubler.asm", "--limit", "stack=3"]).code(2).stderr_has("unknown limit stack");
+}
+
+#[test]
+fn capabilities_report_the_active_limits() {
+    let sandbox = Sandbox::new("limits-capabilities");
+    sandbox.write("zk100.toml", "[limits]\ncycles = 500\n");
+    let run = sandbox.run(&["capabilities", "--json", "--limit", "inputs=8"]);
+    run.success();
+    let caps: serde_json::Value = serde_json::from_str(&run.stdout()).unwrap();
+    assert_eq!(caps["limits"]["cycles"], serde_json::json!({"value": 500, "origin": "config"}));
+    assert_eq!(caps["limits"]["inputs"], serde_json::json!({"value": 8, "origin": "flag"}));
+    assert_eq!(caps["limits"]["node_instructions"], serde_json::json!({"value": 256, "origin": "default"}));
+    sandbox.run(&["capabilities"]).success().stdout_has("limit               cycles = 500 (zk100.toml)\n");
+}
+
+#[test]
+fn build_applies_the_project_limits() {
+    let sandbox = Sandbox::new("limits-build");
+    sandbox
+        .write("d/d.asm", DOUBLER)
+        .write("d/d.toml", "[puzzle]\nprogram = \"d.asm\"\n\n[[case]]\ninputs = [1, 2, 3]\nexpected = [2, 4, 6]\n")
+        .write("zk100.toml", "[limits]\ninputs = 2\n");
+    sandbox
+        .run(&["build"])
+        .code(1)
+        .stderr_has("the input stream has 3 values, over the inputs limit of 2 (from zk100.toml)");
+
+    sandbox.write("zk100.toml", "[limits]\ninputs = 3\n");
+    sandbox.run(&["build"]).success().stderr_has("2 built, 0 fresh");
+}
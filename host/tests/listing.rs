@@ -0,0 +1,122 @@
+//! `disassemble` listings and the `--style` of `inspect-args`. The plain
+//! outputs are pinned in tests/snapshots/; bless intended changes with
+//! `UPDATE_GOLDENS=1 cargo test --test listing`.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox, fixture};
+use std::fs;
+use std::path::Path;
+
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(name);
+    if std::env::var("UPDATE_GOLDENS").is_ok_and(|v| !v.is_empty() && v != "0") {
+        fs::write(&path, actual).unwrap();
+    }
+    assert_eq!(actual, fs::read_to_string(&path).unwrap(), "{} is stale; rerun with UPDATE_GOLDENS=1", name);
+}
+
+#[test]
+fn plain_listings_are_pinned() {
+    let sandbox = Sandbox::new("listing-plain");
+    let source = fixture("listing/loop.asm");
+    let source = source.to_str().unwrap();
+    let run = sandbox.run(&["disassemble", source]);
+    run.success();
+    assert_snapshot("loop.listing", &run.stdout());
+    let run = sandbox.run(&["disassemble", source, "--width", "30", "--style", "plain"]);
+    run.success();
+    assert_snapshot("loop-30.listing", &run.stdout());
+
+    // The listing file is the same listing; decoded words have no labels.
+    sandbox.run(&["disassemble", source, "-o", "loop.lst"]).success().stderr_has("Wrote loop.lst");
+    assert_eq!(sandbox.read("loop.lst"), fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/loop.listing")).unwrap());
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1", "-e", "2"]).success();
+    let words = sandbox.run(&["inspect-args", "args.json", "--only", "prog-words"]).stdout();
+    sandbox.write("words.json", format!("[{}]", words.lines().collect::<Vec<_>>().join(",")));
+    sandbox.run(&["disassemble", "words.json"]).success().stdout_has("NODE (0,0)\n0  0x00010300  MOV IN, ACC\n");
+
+    let run = sandbox.run(&["inspect-args", "args.json"]);
+    run.success();
+    assert_snapshot("doubler.inspect", &run.stdout());
+}
+
+#[test]
+fn color_only_when_asked() {
+    let sandbox = Sandbox::new("listing-color");
+    sandbox.write("doubler.asm", DOUBLER);
+    // Piped output is not a terminal, so auto is plain.
+    let run = sandbox.run(&["disassemble", "doubler.asm"]);
+    assert!(!run.success().stdout().contains('\x1b'));
+    sandbox.run(&["disassemble", "doubler.asm", "--style", "color"]).success().stdout_has("\x1b[1;34mMOV\x1b[0m");
+    sandbox
+        .run_env(&["disassemble", "doubler.asm", "--style", "color"], &[("NO_COLOR", "1")])
+        .success()
+        .stdout_has("\x1b[");
+
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1", "-e", "2"]).success();
+    assert!(!sandbox.run(&["inspect-args", "args.json"]).success().stdout().contains('\x1b'));
+    sandbox.run(&["inspect-args", "args.json", "--style", "color"]).success().stdout_has("\x1b[32mprog_words  \x1b[0m");
+}
+
+#[test]
+fn pad_to_lengthens_every_node() {
+    let sandbox = Sandbox::new("listing-pad-to");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1", "-e", "2", "--pad-to", "8", "--listing", "doubler.lst", "--metadata", "metadata.json"])
+        .success();
+    let listing = sandbox.read("doubler.lst");
+    assert!(listing.contains("[ 0]     0x00000008  length 8\n"), "{}", listing);
+    assert!(listing.contains("  NOP (padding)\n"), "{}", listing);
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("metadata.json")).unwrap();
+    assert_eq!(metadata["options"]["pad_to"], 8);
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1", "-e", "2", "--pad-to", "1"])
+        .code(1)
+        .stderr_has("more than the 1 every node is padded to");
+}
+
+#[test]
+fn padding_after_a_jump_assembles() {
+    let sandbox = Sandbox::new("listing-pad-jump");
+    sandbox.write("loop.asm", "NODE (0,0)\nMOV IN, ACC\nJMP 0\n");
+    sandbox
+        .run(&["assemble", "loop.asm", "-i", "1", "-e", "1", "--pad-to", "4", "--listing", "loop.lst"])
+        .success()
+        .stderr_lacks("never reached");
+    assert!(sandbox.read("loop.lst").contains("  NOP (padding)\n"));
+}
+
+#[test]
+fn assemble_listing_matches_prog_words() {
+    let sandbox = Sandbox::new("listing-assemble");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1", "-e", "2", "--listing", "doubler.lst"])
+        .success()
+        .stderr_has("Generated listing file: doubler.lst");
+    let listing = sandbox.read("doubler.lst");
+    // The empty node keeps its length word.
+    assert!(listing.contains("NODE (0,1)\n[4]     0x00000000  length 0\n"), "{}", listing);
+    assert!(listing.contains("[1]  0  0x00010300  2: MOV IN, ACC\n"), "{}", listing);
+
+    // Every `[index]` word is the word at that index of prog_words.
+    let words: Vec<u32> = sandbox
+        .run(&["inspect-args", "args.json", "--only", "prog-words"])
+        .stdout()
+        .lines()
+        .map(|line| line.trim().parse().unwrap())
+        .collect();
+    let mut listed = 0;
+    for line in listing.lines().filter(|line| line.starts_with('[')) {
+        let index: usize = line[1..line.find(']').unwrap()].trim().parse().unwrap();
+        let hex = line.split_whitespace().find(|field| field.starts_with("0x")).unwrap();
+        assert_eq!(u32::from_str_radix(&hex[2..], 16).unwrap(), words[index], "{}", line);
+        listed += 1;
+    }
+    assert_eq!(listed, words.len());
+}
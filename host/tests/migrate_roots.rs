@@ -0,0 +1,47 @@
+//! `migrate-roots` over `tests/fixtures/migrate`: one row per status, and
+//! no new root for any row whose old root did not verify.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::Sandbox;
+
+const ROOT: &str = "0x8c844d7564801398c0da0f561b9dd69758c46415e51860bd6d5060a148370421";
+
+#[test]
+fn every_row_gets_its_own_status() {
+    let sandbox = Sandbox::with_fixture("migrate");
+    let args = ["migrate-roots", "--input", "roots.csv", "--from", "v1", "--to", "v1", "--sources-dir", "programs"];
+    let run = sandbox.run(&[&args[..], &["-o", "migrated.csv", "--jobs", "3"]].concat());
+    run.code(1)
+        .stderr_has("      Failed zk100-retired (source-missing): no file at programs/retired.asm\n")
+        .stderr_has("      Failed zk100-edited (old-root-mismatch): the source has v1 root 0x")
+        .stderr_has("      Failed zk100-broken (source-invalid): ")
+        .stderr_has("4 roots, 1 ok, 3 failed")
+        .stderr_has("3 of 4 roots did not migrate");
+
+    let csv = sandbox.read("migrated.csv");
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "program_id,path,old_root,new_root,status,detail");
+    let upper = ROOT.to_uppercase().replacen("0X", "0x", 1);
+    assert_eq!(lines[1], format!("zk100-3sphprm0h8frude0pjsxmp8ze6a23ctmfcrc9x,doubler.asm,{},{},ok,", upper, ROOT));
+    assert_eq!(lines[2], format!("zk100-retired,retired.asm,{},,source-missing,no file at programs/retired.asm", ROOT));
+    assert!(lines[3].starts_with(&format!("zk100-edited,edited.asm,{},,old-root-mismatch,", ROOT)), "{}", lines[3]);
+    assert!(lines[4].starts_with(&format!("zk100-broken,broken.asm,{},,source-invalid,", ROOT)), "{}", lines[4]);
+    assert!(lines[4].contains("nowhere"), "{}", lines[4]);
+
+    let run = sandbox.run(&[&args[..], &["--porcelain"]].concat());
+    run.code(1).stdout_has("migrated\tzk100-edited\told-root-mismatch\n");
+}
+
+#[test]
+fn unimplemented_versions_fail_up_front() {
+    let sandbox = Sandbox::with_fixture("migrate");
+    sandbox
+        .run(&["migrate-roots", "--input", "roots.csv", "--from", "v1", "--to", "v2", "-o", "migrated.csv"])
+        .code(1)
+        .stderr_has("commitment version 2 is not implemented; this build only has version 1");
+    assert!(!sandbox.exists("migrated.csv"));
+    sandbox.run(&["migrate-roots", "--input", "roots.csv", "--from", "one", "--to", "v1"]).code(2);
+}
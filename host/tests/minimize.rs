@@ -0,0 +1,49 @@
+//! `minimize`: a padded doubler shrinks to the tight one, and the tight one
+//! comes back unchanged.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::Sandbox;
+use zk100_host::assembler;
+
+const PADDED: &str = "NODE (0,0)\nMOV IN, ACC\nNOP\nADD ACC\nADD 3\nSUB 3\nMOV ACC, P:DOWN\n\
+NODE (1,0)\nMOV P:UP, ACC\nNOP\nMOV ACC, P:RIGHT\n\
+NODE (1,1)\nLOOP:\nMOV P:LEFT, OUT\nNOP\nJMP LOOP\n";
+
+const TIGHT: &str = "NODE (0,0)\nMOV IN, ACC\nADD ACC\nMOV ACC, P:DOWN\n\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\n\nNODE (1,1)\nLOOP:\nMOV P:LEFT, OUT\n";
+
+const MANIFEST: &str = "[puzzle]\nname = \"doubler\"\nprogram = \"padded.asm\"\n\n[[case]]\ninputs = [1, 2, 3]\nexpected = [2, 4, 6]\n\n[[case]]\ninputs = [40, 7]\nexpected = [80, 14]\n";
+
+#[test]
+fn padded_solution_shrinks() {
+    let sandbox = Sandbox::new("minimize-padded");
+    sandbox.write("padded.asm", PADDED).write("puzzle.toml", MANIFEST);
+    let run = sandbox.run(&["minimize", "padded.asm", "--manifest", "puzzle.toml", "-o", "min.asm", "--porcelain"]);
+    run.success().stdout_has("instructions\t12\t6\n");
+
+    assert_eq!(sandbox.read("min.asm"), TIGHT);
+    let programs = assembler::parse_assembly(&sandbox.read("min.asm")).unwrap();
+    assert_eq!(programs.iter().flatten().map(Vec::len).sum::<usize>(), 6);
+
+    let run = sandbox.run(&["minimize", "padded.asm", "--manifest", "puzzle.toml"]);
+    assert_eq!(run.success().stdout(), TIGHT);
+    run.stderr_has("12 -> 6 instructions (-6)");
+}
+
+#[test]
+fn tight_solution_is_unchanged() {
+    let sandbox = Sandbox::new("minimize-tight");
+    sandbox.write("tight.asm", TIGHT).write("puzzle.toml", MANIFEST);
+    let run = sandbox.run(&["minimize", "tight.asm", "--manifest", "puzzle.toml"]);
+    assert_eq!(run.success().stdout(), TIGHT);
+    run.stderr_has("6 -> 6 instructions (+0)").stderr_has("0 kept");
+
+    // A program that already fails a case is refused.
+    sandbox.write("broken.asm", "NODE (0,0)\nMOV IN, ACC\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n");
+    sandbox
+        .run(&["minimize", "broken.asm", "--manifest", "puzzle.toml"])
+        .code(1)
+        .stderr_has("the program fails case 1 before minimizing: outputs[0]: expected 2, got 1");
+}
@@ -0,0 +1,75 @@
+//! Node-scoped `--expected` and the per-node args ABI, through the CLI.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use zk100_host::cairo_abi::{self, NodeOutputs};
+
+const PROGRAM: &str = "NODE (0,1)\nMOV 0, OUT\nMOV P:DOWN, ACC\nNODE (1,1)\nMOV 5, OUT\nMOV 10, OUT\nMOV P:UP, ACC\n";
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zk100-node-expected-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("program.asm"), PROGRAM).unwrap();
+    dir
+}
+
+fn assemble(dir: &Path, extra: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .arg("assemble")
+        .arg(dir.join("program.asm"))
+        .arg("--output")
+        .arg(dir.join("args.json"))
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn node_scoped_expectations_use_the_node_abi() {
+    let dir = scratch("node");
+    let out = assemble(
+        &dir,
+        &["--abi", "2", "-e", "1,1=5,10", "--expected", "0,1=0", "--metadata", dir.join("metadata.json").to_str().unwrap()],
+    );
+    let args = fs::read_to_string(dir.join("args.json")).unwrap_or_default();
+    let attest = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .arg("attest")
+        .arg(dir.join("program.asm"))
+        .arg("--against")
+        .arg(dir.join("metadata.json"))
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).ok();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Expected from (0,1): [0]"), "{}", stderr);
+    assert!(stderr.contains("Expected from (1,1): [5, 10]"), "{}", stderr);
+
+    let parsed = cairo_abi::parse_args_abi(&args, cairo_abi::NODE_ABI).unwrap();
+    assert_eq!(parsed.node_expected, NodeOutputs::from([((0, 1), vec![0]), ((1, 1), vec![5, 10])]));
+    let values: Vec<String> = serde_json::from_str(&args).unwrap();
+    assert_eq!(&values[..10], ["0x0", "0x2", "0x0", "0x1", "0x1", "0x0", "0x1", "0x1", "0x2", "0x5"]);
+    assert!(attest.status.success(), "{}", String::from_utf8_lossy(&attest.stdout));
+}
+
+#[test]
+fn legacy_abi_rejects_node_scoped_flags() {
+    let dir = scratch("legacy");
+    let legacy = assemble(&dir, &["-e", "1,1=5,10"]);
+    let explicit = assemble(&dir, &["--abi", "1", "-e", "5,10", "-e", "0,1=0"]);
+    let flat_in_node_abi = assemble(&dir, &["--abi", "2", "-e", "5,10"]);
+    let unknown_abi = assemble(&dir, &["--abi", "3"]);
+    let legacy_ok = assemble(&dir, &["-e", "5,10"]);
+    fs::remove_dir_all(&dir).ok();
+
+    for out in [&legacy, &explicit] {
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("need --abi 2"), "{}", String::from_utf8_lossy(&out.stderr));
+    }
+    assert!(String::from_utf8_lossy(&flat_in_node_abi.stderr).contains("write row,col=values"));
+    assert!(!unknown_abi.status.success());
+    assert!(legacy_ok.status.success(), "{}", String::from_utf8_lossy(&legacy_ok.stderr));
+}
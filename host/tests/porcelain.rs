@@ -0,0 +1,79 @@
+//! Notices on stderr, payloads on stdout, and `--porcelain` records.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox, fixture};
+use std::fs;
+
+/// `key<TAB>fields` records of a porcelain run, in order.
+fn records(stdout: &str) -> Vec<Vec<String>> {
+    stdout.lines().map(|line| line.split('\t').map(str::to_string).collect()).collect()
+}
+
+#[test]
+fn stdin_pipeline_matches_file_round_trip() {
+    let sandbox = Sandbox::new("porcelain-pipeline");
+    sandbox.write("doubler.asm", DOUBLER);
+
+    // convert doubler.asm --to json | convert - --to asm | assemble - -o - | bisect-encoding - doubler.asm
+    let json = sandbox.run(&["convert", "doubler.asm", "--to", "json"]);
+    json.success();
+    let asm = sandbox.run_stdin(&["convert", "-", "--to", "asm"], &json.output.stdout);
+    asm.success();
+    let args = sandbox.run_stdin(&["assemble", "-", "-o", "-"], &asm.output.stdout);
+    args.success().stderr_has("Generated args file: -");
+    serde_json::from_slice::<serde_json::Value>(&args.output.stdout).expect("stdout is only the args JSON");
+    let piped = sandbox.run_stdin(&["bisect-encoding", "-", "doubler.asm", "--porcelain"], &args.output.stdout);
+    piped.success();
+
+    sandbox.run(&["assemble", "doubler.asm"]).success();
+    let direct = sandbox.run(&["bisect-encoding", "args.json", "doubler.asm", "--porcelain"]);
+    direct.success();
+
+    let verdict = |stdout: &[u8]| serde_json::from_slice::<serde_json::Value>(stdout).unwrap()["verdict"].clone();
+    assert_eq!(verdict(&piped.output.stdout), "identical");
+    assert_eq!(verdict(&piped.output.stdout), verdict(&direct.output.stdout));
+}
+
+#[test]
+fn assemble_porcelain_is_records_only() {
+    let sandbox = Sandbox::new("porcelain-assemble");
+    sandbox.write("doubler.asm", DOUBLER);
+    let run = sandbox.run(&["assemble", "doubler.asm", "--metadata", "metadata.json", "--porcelain"]);
+    run.success().stderr_has("Generated args file: args.json");
+    let records = records(&run.stdout());
+    let keys: Vec<&str> = records.iter().map(|r| r[0].as_str()).collect();
+    assert_eq!(keys, ["args", "metadata", "words", "root"]);
+    assert_eq!(records[0][1], "args.json");
+    assert_eq!(records[1][1], "metadata.json");
+    assert!(records[2][1].parse::<usize>().unwrap() > 0);
+
+    let programs = zk100_host::assembler::parse_assembly(DOUBLER).unwrap();
+    assert_eq!(records[3][1], zk100_host::merkle::program_root_hex(&programs).unwrap());
+
+    // Without --porcelain, stdout carries no notices.
+    assert_eq!(sandbox.run(&["assemble", "doubler.asm"]).stdout(), "");
+}
+
+#[cfg(unix)]
+#[test]
+fn proof_commands_under_porcelain() {
+    let sandbox = Sandbox::new("porcelain-proof");
+    let proof = fs::read_to_string(fixture("proofs/claim.json")).unwrap();
+    sandbox.write("doubler.asm", DOUBLER).write("guest.json", "{}").stub_prover(&proof);
+
+    let run = sandbox.run(&["prove", "doubler.asm", "--guest", "guest.json", "--porcelain"]);
+    run.success().stderr_has("Proof written to: proof.json");
+    let records = records(&run.stdout());
+    let keys: Vec<&str> = records.iter().map(|r| r[0].as_str()).collect();
+    assert_eq!(keys, ["args", "words", "root", "proof", "program_root", "solved", "cycles"]);
+    assert_eq!(records[3][1], "proof.json");
+    assert_eq!(records[4][1], "0x5a17c0ffee");
+
+    let run = sandbox.run(&["verify-proof", "proof.json", "--porcelain"]);
+    run.success().stderr_has("Proof verified: proof.json");
+    let json: serde_json::Value = serde_json::from_slice(&run.output.stdout).unwrap();
+    assert_eq!(json["program_root"], "0x5a17c0ffee");
+}
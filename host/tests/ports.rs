@@ -0,0 +1,36 @@
+//! Ports that point off the grid: the warning, and `--strict-ports` turning
+//! it into an error.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::Sandbox;
+
+const OFF_GRID: &str = "NODE (0,0)\nMOV IN, ACC\nMOV ACC, P:UP\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+#[test]
+fn off_grid_ports_warn_unless_strict() {
+    let sandbox = Sandbox::new("ports");
+    sandbox.write("edge.asm", OFF_GRID);
+    let warning = "edge.asm: line 3: node (0,0) pc 1 writes P:UP, but the node is on the top edge of the 2x2 grid; the transfer blocks forever";
+    sandbox
+        .run(&["assemble", "edge.asm", "-o", "args.json"])
+        .success()
+        .stderr_has(&format!("warning: {}", warning));
+    assert!(sandbox.exists("args.json"));
+
+    sandbox
+        .run(&["assemble", "edge.asm", "-o", "strict.json", "--strict-ports"])
+        .code(1)
+        .stderr_has(&format!("error: {}", warning))
+        .stderr_has("1 port(s) point off the grid and --strict-ports is set; nothing was written");
+    assert!(!sandbox.exists("strict.json"));
+
+    // IN and OUT are not ports, whatever edge their node is on.
+    sandbox.write("io.asm", OFF_GRID.replace("MOV ACC, P:UP\n", ""));
+    sandbox
+        .run(&["assemble", "io.asm", "-o", "io.json", "--strict-ports"])
+        .success()
+        .stderr_lacks("edge of the");
+}
@@ -0,0 +1,58 @@
+//! `--profile` and the `profile` key in zk100.toml.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+use serde_json::Value;
+
+fn metadata(sandbox: &Sandbox) -> Value {
+    serde_json::from_str(&sandbox.read("metadata.json")).unwrap()
+}
+
+#[test]
+fn profile_sets_abi_and_is_recorded() {
+    let sandbox = Sandbox::new("profile-flag");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1", "-e", "2", "--profile", "guest-v2", "--metadata", "metadata.json"])
+        .success()
+        .stderr_has("Profile: guest-v2 (ABI 4)")
+        .stderr_lacks("warning");
+    let metadata = metadata(&sandbox);
+    assert_eq!(metadata["options"]["profile"], "guest-v2");
+    assert_eq!(metadata["abi_version"], 4);
+    assert_eq!(metadata["encoding_version"], 1);
+    assert_eq!(metadata["commitment_version"], 1);
+    assert_eq!(metadata["hash_function"], "sha256");
+    assert_eq!(metadata["schedule"], "row-major");
+    sandbox.run(&["attest", "doubler.asm", "--against", "metadata.json"]).success();
+}
+
+#[test]
+fn config_profile_and_flag_override() {
+    let sandbox = Sandbox::new("profile-config");
+    sandbox.write("doubler.asm", DOUBLER).write("zk100.toml", "profile = \"experimental\"\n");
+    sandbox
+        .run(&["assemble", "doubler.asm", "--abi", "1", "--metadata", "metadata.json"])
+        .success()
+        .stderr_has("warning: --abi 1 overrides abi_version 5 of profile experimental");
+    let metadata = metadata(&sandbox);
+    assert_eq!(metadata["options"]["profile"], "experimental");
+    assert_eq!(metadata["abi_version"], 1);
+
+    // The flag wins over the config key.
+    sandbox.run(&["assemble", "doubler.asm", "--profile", "guest-v1"]).success().stderr_lacks("warning");
+}
+
+#[test]
+fn unknown_profile_is_an_error() {
+    let sandbox = Sandbox::new("profile-unknown");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox
+        .run(&["assemble", "doubler.asm", "--profile", "guest-v9"])
+        .code(1)
+        .stderr_has("unknown profile 'guest-v9'; valid profiles: guest-v1, guest-v2, experimental");
+    assert!(!sandbox.exists("args.json"));
+}
@@ -0,0 +1,44 @@
+//! `id`: the same program written as assembly, grid JSON or instruction words
+//! has one ID, and the metadata sidecar records it.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+use zk100_host::cairo_abi;
+use zk100_host::program_id::ProgramId;
+
+#[test]
+fn id_is_stable_across_program_formats() {
+    let sandbox = Sandbox::new("program-id");
+    sandbox.write("doubler.asm", DOUBLER);
+    let id = |path: &str| {
+        let run = sandbox.run(&["id", path]);
+        run.success();
+        run.stdout().trim().to_string()
+    };
+    let from_asm = id("doubler.asm");
+    assert!(ProgramId::parse(&from_asm).is_ok(), "{}", from_asm);
+
+    sandbox.run(&["convert", "doubler.asm", "--to", "json", "-o", "doubler.json"]).success();
+    sandbox.run(&["assemble", "doubler.asm", "--metadata", "metadata.json"]).success();
+    let args = cairo_abi::parse_args(&sandbox.read("args.json")).unwrap();
+    sandbox.write("words.json", serde_json::to_string(&args.prog_words).unwrap());
+    // Comments and blank lines do not reach the commitment.
+    sandbox.write("noisy.asm", format!("# doubler\n\n{}", DOUBLER.replace("ADD ACC", "ADD ACC  // x2")));
+
+    for path in ["doubler.json", "words.json", "noisy.asm"] {
+        assert_eq!(id(path), from_asm, "{}", path);
+    }
+    let run = sandbox.run_stdin(&["id", "-"], DOUBLER.as_bytes());
+    run.success().stdout_has(&from_asm);
+
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("metadata.json")).unwrap();
+    assert_eq!(metadata["program_id"], from_asm.as_str());
+    sandbox.run(&["attest", "doubler.asm", "--against", "metadata.json"]).success().stdout_has("ok           program_id: ");
+
+    sandbox.write("other.asm", "NODE (0,0)\nMOV IN, OUT\n");
+    assert_ne!(id("other.asm"), from_asm);
+    sandbox.run(&["id", "missing.asm"]).code(1);
+}
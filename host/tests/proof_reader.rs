@@ -0,0 +1,63 @@
+//! Proof public output across the cairo-prove proof layouts, and the
+//! `check-root` / `verify-proof` commands built on it.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{Sandbox, fixture};
+use std::fs;
+use zk100_host::proof_reader::{self, Schema};
+use zk100_host::{assembler, merkle};
+
+#[test]
+fn known_schemas_read_the_same_public_output() {
+    let cases = [
+        ("top_level.json", Schema::TopLevel),
+        ("claim.json", Schema::Claim),
+        ("public_memory.json", Schema::PublicMemory),
+    ];
+    for (file, schema) in cases {
+        let public = proof_reader::read(&fs::read_to_string(fixture("proofs").join(file)).unwrap()).unwrap();
+        assert_eq!(public.schema, schema, "{}", file);
+        assert_eq!(public.inputs, "0x1234abcd", "{}", file);
+        assert_eq!(public.program_root, "0x5a17c0ffee", "{}", file);
+        assert_eq!(public.outputs, "0xbeef", "{}", file);
+        assert_eq!((public.cycles, public.msgs, public.nodes_used, public.solved), (12, 3, 3, true), "{}", file);
+    }
+}
+
+#[test]
+fn unknown_schema_names_its_keys() {
+    let err = proof_reader::read(&fs::read_to_string(fixture("proofs/unknown.json")).unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "unrecognized proof schema; found top-level keys: [stark_proof, version]");
+}
+
+#[test]
+fn check_root_compares_the_committed_root() {
+    let sandbox = Sandbox::new("proof-check-root");
+    let program = fixture("golden/negate/program.asm");
+    let root = merkle::program_root_hex(&assembler::parse_assembly(&fs::read_to_string(&program).unwrap()).unwrap()).unwrap();
+    sandbox.write("proof.json", format!(r#"{{"public_input": {{"output": ["0x1", "{}", "0x2", 5, 2, 2, 1]}}}}"#, root));
+
+    sandbox.run(&["check-root", "proof.json", program.to_str().unwrap()]).success();
+
+    let other = fixture("golden/passthrough/program.asm");
+    sandbox.run(&["check-root", "proof.json", other.to_str().unwrap()]).code(1).stderr_has("commits to program root");
+
+    sandbox.write("unknown.json", fs::read(fixture("proofs/unknown.json")).unwrap());
+    sandbox.run(&["check-root", "unknown.json", program.to_str().unwrap()]).stderr_has("unrecognized proof schema");
+}
+
+#[cfg(unix)]
+#[test]
+fn verify_proof_reports_public_output() {
+    let sandbox = Sandbox::new("proof-verify");
+    sandbox.write("proof.json", fs::read(fixture("proofs/claim.json")).unwrap()).stub_prover("{}");
+    let run = sandbox.run(&["verify-proof", "proof.json", "--json"]);
+    run.success();
+    let json: serde_json::Value = serde_json::from_slice(&run.output.stdout).unwrap();
+    assert_eq!(json["schema"], "claim");
+    assert_eq!(json["program_root"], "0x5a17c0ffee");
+    assert_eq!(json["solved"], true);
+}
@@ -0,0 +1,176 @@
+//! `queue run` against a stub `cairo-prove`: success, failure, timeout,
+//! invalid jobs and resuming after an interrupted run.
+#![cfg(all(unix, feature = "cli"))]
+
+use serde_json::Value;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::time::{Duration, Instant};
+use zk100_host::exec::INTERRUPTED_EXIT_CODE;
+
+/// Stub prover, keyed on the job name in the args path (`$5`): `fail*`
+/// exits 1, `slow*` (or any job with STUB_SLOW set) records its pid and
+/// sleeps, everything else writes a proof. Every call is logged.
+const STUB: &str = r#"#!/bin/sh
+echo "$5" >> "$(dirname "$0")/calls.log"
+case "$(basename "$5")" in
+  fail*) echo "constraint not satisfied" >&2; exit 1 ;;
+  slow*) echo $$ > "$(dirname "$0")/stub.pid"; exec sleep 30 ;;
+esac
+if [ -n "$STUB_SLOW" ]; then echo $$ > "$(dirname "$0")/stub.pid"; exec sleep 30; fi
+echo '{"proof": true}' > "$3"
+"#;
+
+/// Doubles every input on its way from (0,0) to (1,1).
+const PROGRAM: &str = "NODE (0,0)\nMOV IN, ACC\nADD ACC\nMOV ACC, P:DOWN\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zk100-queue-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(dir.join("bin")).unwrap();
+    fs::create_dir_all(dir.join("jobs")).unwrap();
+    let stub = dir.join("bin/cairo-prove");
+    fs::write(&stub, STUB).unwrap();
+    fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(dir.join("guest.executable.json"), "{}").unwrap();
+    fs::write(dir.join("jobs/doubler.asm"), PROGRAM).unwrap();
+    dir
+}
+
+fn add_job(dir: &Path, name: &str, cases: &str) {
+    let manifest = format!(r#"{{"program": "doubler.asm", "cases": {}}}"#, cases);
+    fs::write(dir.join("jobs").join(format!("{}.json", name)), manifest).unwrap();
+}
+
+fn queue(dir: &Path, extra: &[&str]) -> Command {
+    let path = format!("{}:{}", dir.join("bin").display(), std::env::var("PATH").unwrap_or_default());
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_zk100-host"));
+    cmd.current_dir(dir)
+        .env("PATH", path)
+        .args(["queue", "run", "jobs", "--guest", "guest.executable.json"])
+        .args(extra);
+    cmd
+}
+
+fn run(dir: &Path, extra: &[&str]) -> Output {
+    queue(dir, extra).output().unwrap()
+}
+
+fn result(dir: &Path, name: &str) -> Value {
+    serde_json::from_str(&fs::read_to_string(dir.join(format!("jobs/results/{}.json", name))).unwrap()).unwrap()
+}
+
+fn calls(dir: &Path) -> String {
+    fs::read_to_string(dir.join("bin/calls.log")).unwrap_or_default()
+}
+
+fn wait_for_stub(dir: &Path, child: &mut Child) {
+    let started = Instant::now();
+    while !dir.join("bin/stub.pid").exists() {
+        assert!(child.try_wait().unwrap().is_none(), "runner exited before proving");
+        assert!(started.elapsed() < Duration::from_secs(20), "stub prover never started");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn runs_success_failure_timeout_and_invalid_jobs() {
+    let dir = scratch("mixed");
+    add_job(&dir, "ok", r#"[{"inputs": [1, 2, 3], "expected": [2, 4, 6]}, {"inputs": [9], "expected": [18]}]"#);
+    add_job(&dir, "fail", r#"[{"inputs": [7], "expected": [14]}]"#);
+    add_job(&dir, "slow", r#"[{"inputs": [7], "expected": [14]}]"#);
+    add_job(&dir, "wrong", r#"[{"inputs": [7], "expected": [15]}]"#);
+
+    let started = Instant::now();
+    let out = run(&dir, &["--concurrency", "2", "--job-timeout", "1s"]);
+    let stdout = String::from_utf8_lossy(&out.stdout).into_owned();
+    assert!(!out.status.success(), "{}", stdout);
+    assert!(started.elapsed() < Duration::from_secs(20), "timeout did not kill the slow prover");
+    assert!(stdout.contains("4 jobs: 1 succeeded, 3 failed, 0 skipped"), "{}", stdout);
+
+    let ok = result(&dir, "ok");
+    assert_eq!(ok["status"], "succeeded");
+    assert_eq!(ok["proof"], "results/ok.proof.json");
+    assert!(ok["program_root"].as_str().unwrap().starts_with("0x"));
+    assert!(dir.join("jobs/results/ok.proof.json").is_file());
+    let args: Value = serde_json::from_str(&fs::read_to_string(dir.join("jobs/results/ok.args.json")).unwrap()).unwrap();
+    assert_eq!(args[0], "0x2", "two cases are proved as one batch");
+
+    let fail = result(&dir, "fail");
+    assert_eq!(fail["status"], "failed");
+    assert!(fail["failure"].as_str().unwrap().contains("constraint not satisfied"), "{}", fail);
+    assert!(!dir.join("jobs/results/fail.proof.json").exists());
+
+    assert_eq!(result(&dir, "slow")["status"], "timed_out");
+    let wrong = result(&dir, "wrong");
+    assert_eq!(wrong["status"], "invalid");
+    assert!(wrong["failure"].as_str().unwrap().contains("expected [15]"), "{}", wrong);
+    assert!(!calls(&dir).contains("wrong"), "invalid job reached the prover");
+
+    assert!(dir.join("jobs/done/ok.json").is_file());
+    for name in ["fail", "slow", "wrong"] {
+        assert!(dir.join(format!("jobs/failed/{}.json", name)).is_file(), "{} not moved", name);
+    }
+    assert_eq!(fs::read_dir(dir.join("jobs/state")).unwrap().count(), 0);
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn resumes_a_job_interrupted_mid_prove() {
+    let dir = scratch("resume");
+    add_job(&dir, "job", r#"[{"inputs": [7], "expected": [14]}]"#);
+
+    let mut child = queue(&dir, &[]).env("STUB_SLOW", "1").stdout(Stdio::null()).stderr(Stdio::null()).spawn().unwrap();
+    wait_for_stub(&dir, &mut child);
+    Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    assert_eq!(child.wait().unwrap().code(), Some(INTERRUPTED_EXIT_CODE));
+    assert!(dir.join("jobs/state/job.json").is_file(), "state file removed on interrupt");
+    assert!(dir.join("jobs/job.json").is_file(), "manifest moved on interrupt");
+
+    let out = run(&dir, &[]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let job = result(&dir, "job");
+    assert_eq!(job["status"], "succeeded");
+    assert_eq!(job["attempt"], 2);
+    assert!(!dir.join("jobs/state/job.json").exists());
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let dir = scratch("attempts");
+    add_job(&dir, "job", r#"[{"inputs": [7], "expected": [14]}]"#);
+    let mut gone = Command::new("true").spawn().unwrap();
+    let dead_pid = gone.id();
+    gone.wait().unwrap();
+    fs::create_dir_all(dir.join("jobs/state")).unwrap();
+    fs::write(dir.join("jobs/state/job.json"), format!(r#"{{"pid": {}, "attempt": 2, "started_unix": 0}}"#, dead_pid)).unwrap();
+
+    let out = run(&dir, &["--max-attempts", "2"]);
+    assert!(!out.status.success());
+    let job = result(&dir, "job");
+    assert_eq!(job["status"], "failed");
+    assert!(job["failure"].as_str().unwrap().contains("interrupted"), "{}", job);
+    assert!(calls(&dir).is_empty());
+    assert!(dir.join("jobs/failed/job.json").is_file());
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn skips_jobs_held_by_a_live_runner() {
+    let dir = scratch("locked");
+    add_job(&dir, "job", r#"[{"inputs": [7], "expected": [14]}]"#);
+    fs::create_dir_all(dir.join("jobs/state")).unwrap();
+    let state = format!(r#"{{"pid": {}, "attempt": 1, "started_unix": 0}}"#, std::process::id());
+    fs::write(dir.join("jobs/state/job.json"), state).unwrap();
+
+    let out = run(&dir, &[]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "{}", stdout);
+    assert!(stdout.contains("skipped   job: being proved by runner pid"), "{}", stdout);
+    assert!(dir.join("jobs/job.json").is_file());
+    assert!(calls(&dir).is_empty());
+    fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,53 @@
+//! Renders the fixture report through the CLI and checks the HTML and its
+//! JSON sibling carry the same facts.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use zk100_host::program_id::ProgramId;
+use zk100_host::{assembler, merkle};
+
+#[test]
+fn report_renders_root_cases_and_proof() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/report");
+    let dir = std::env::temp_dir().join(format!("zk100-report-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let html_path = dir.join("report.html");
+    let out = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .arg("report")
+        .arg("--manifest")
+        .arg(fixture.join("puzzle.toml"))
+        .arg("--output")
+        .arg(&html_path)
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let html = fs::read_to_string(&html_path).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).ok();
+
+    let source = fs::read_to_string(fixture.join("doubler.asm")).unwrap();
+    let root = merkle::program_root_hex(&assembler::parse_assembly(&source).unwrap()).unwrap();
+    assert!(html.contains(&root), "root missing");
+    assert!(html.contains("2 of 3 cases passed"));
+    assert_eq!(html.matches("class=\"fail\">FAIL").count(), 1);
+    assert!(html.contains("<h1>Doubler &lt;demo&gt;</h1>"), "name not escaped");
+    assert!(html.contains("<code>MOV P:LEFT, OUT</code>"));
+    assert!(html.contains("Proving time</th><td>5.1 s"));
+    assert!(!html.contains("<link") && !html.contains("<script"), "report pulls in external assets");
+
+    assert_eq!(json["commitments"]["program_root"], root.as_str());
+    let id = ProgramId::of_programs(&assembler::parse_assembly(&source).unwrap()).unwrap().to_string();
+    assert_eq!(json["commitments"]["program_id"], id.as_str());
+    assert!(html.contains(&id), "program ID missing");
+    assert_eq!(json["passed"], 2);
+    assert_eq!(json["args"]["abi"], 3);
+    assert_eq!(json["proof"]["public"]["solved"], true);
+    let lines = json["source"].as_array().unwrap();
+    let out_line = lines.iter().find(|l| l["text"] == "MOV P:LEFT, OUT").unwrap();
+    assert_eq!(out_line["node"], serde_json::json!([1, 1]));
+    assert_eq!(out_line["pc"], 0);
+}
@@ -0,0 +1,69 @@
+//! `schema`: every JSON artifact the binary writes, and the checked-in grid
+//! fixture, validates against the schema `zk100 schema` prints for it.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox, fixture};
+use serde_json::Value;
+use zk100_host::schema;
+
+const CHECKED: &str = "NODE (0,0)\nMOV IN, ACC\nASSERT ACC < 100\nADD ACC\nLOOP:\nMOV ACC, P:DOWN\nJMP LOOP\nNODE (1,0)\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\nNODE (1,1)\nMOV P:LEFT, OUT\n";
+
+/// Where `document` breaks `schema`, as `path: problem`.
+fn errors(schema: &Value, document: &Value) -> Vec<String> {
+    let validator = jsonschema::validator_for(schema).unwrap();
+    validator.iter_errors(document).map(|e| format!("{}: {}", e.instance_path(), e)).collect()
+}
+
+fn assert_valid(sandbox: &Sandbox, artifact: &str, document: &str) {
+    let schema: Value = serde_json::from_str(&sandbox.run(&["schema", artifact]).success().stdout()).unwrap();
+    let document: Value = serde_json::from_str(document).unwrap();
+    assert_eq!(errors(&schema, &document), Vec::<String>::new(), "{} does not match its schema", artifact);
+}
+
+#[test]
+fn emitted_artifacts_match_their_schemas() {
+    let sandbox = Sandbox::new("schema");
+    sandbox.write("doubler.asm", DOUBLER).write("checked.asm", CHECKED);
+    let assemble = [
+        "assemble", "checked.asm", "--inputs-gen", "{ range = [1, 3] }", "-e", "2,4,6", "--metadata", "meta.json",
+        "--symbols", "symbols.json", "--timings-json", "timings.json", "--stats-log",
+    ];
+    sandbox.run(&assemble).success();
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1", "-e", "2", "--abi", "6", "--input-sentinel", "0", "--metadata", "meta6.json"]).success();
+    sandbox.run(&["convert", "doubler.asm", "--to", "json", "-o", "program.json"]).success();
+
+    assert_valid(&sandbox, "metadata", &sandbox.read("meta.json"));
+    assert_valid(&sandbox, "metadata", &sandbox.read("meta6.json"));
+    assert_valid(&sandbox, "symbols", &sandbox.read("symbols.json"));
+    assert_valid(&sandbox, "timings", &sandbox.read("timings.json"));
+    assert_valid(&sandbox, "program", &sandbox.read("program.json"));
+    assert_valid(&sandbox, "program", &std::fs::read_to_string(fixture("schema/v1.json")).unwrap());
+    for record in sandbox.read(".zk100/stats.jsonl").lines() {
+        assert_valid(&sandbox, "stats-record", record);
+    }
+    let summary = sandbox.run(&["stats-log", "summarize", "--json"]).success().stdout();
+    assert_valid(&sandbox, "stats-summary", &summary);
+}
+
+#[test]
+fn schemas_are_described_documents() {
+    let sandbox = Sandbox::new("schema-documents");
+    for artifact in schema::Artifact::ALL {
+        let printed: Value = serde_json::from_str(&sandbox.run(&["schema", artifact.name()]).success().stdout()).unwrap();
+        assert_eq!(printed, artifact.schema());
+        assert_eq!(printed["$schema"], schema::DIALECT);
+        assert_eq!(printed["title"], format!("zk-100 {}", artifact.name()));
+    }
+
+    // A document that drifted from the schema is caught.
+    let metadata = schema::Artifact::Metadata.schema();
+    let stale = serde_json::json!({ "tool": "zk100-host", "created_unix": "yesterday" });
+    let found = errors(&metadata, &stale);
+    assert!(found.contains(&"/created_unix: \"yesterday\" is not of type \"integer\"".to_string()), "{:?}", found);
+    assert!(found.contains(&": \"program_root\" is a required property".to_string()), "{:?}", found);
+
+    sandbox.run(&["schema", "proofs"]).code(2).stderr_has("invalid value 'proofs'");
+}
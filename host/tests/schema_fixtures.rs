@@ -0,0 +1,33 @@
+//! Every historical program schema must keep deserializing. Each file in
+//! tests/fixtures/schema/ encodes test_program.asm in one schema version.
+
+use std::fs;
+use std::path::Path;
+use zk100_host::assembler::parse_assembly;
+use zk100_host::grid::{self, ProgramGrid, CURRENT_SCHEMA};
+
+#[test]
+fn historical_schemas_still_load() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let source = fs::read_to_string(manifest.join("test_program.asm")).unwrap();
+    let expected = ProgramGrid::new(parse_assembly(&source).unwrap());
+
+    let mut seen = Vec::new();
+    for entry in fs::read_dir(manifest.join("tests/fixtures/schema")).unwrap() {
+        let path = entry.unwrap().path();
+        let text = fs::read_to_string(&path).unwrap();
+        let loaded = grid::from_json(&text)
+            .unwrap_or_else(|e| panic!("{} no longer loads: {}", path.display(), e));
+        assert_eq!(loaded, expected, "{}", path.display());
+
+        // Re-serializing always upgrades to the current schema.
+        let upgraded = grid::to_json(&loaded).unwrap();
+        assert!(upgraded.contains(&format!("\"schema\": {}", CURRENT_SCHEMA)));
+        seen.push(path.file_stem().unwrap().to_string_lossy().into_owned());
+    }
+
+    // One fixture per schema version, named v<N>.json.
+    for version in 1..=CURRENT_SCHEMA {
+        assert!(seen.contains(&format!("v{}", version)), "missing fixture for schema {}", version);
+    }
+}
@@ -0,0 +1,102 @@
+//! `seal` / `unseal` through the CLI.
+
+#![cfg(feature = "cli")]
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn zk100(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_zk100-host")).args(args).output().unwrap()
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zk100-seal-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/fixtures/golden/{}/program.asm", name))
+}
+
+fn seal(program: &Path, sealed: &Path, passphrase: &str) -> Value {
+    let out = zk100(&[
+        "seal",
+        program.to_str().unwrap(),
+        "-o",
+        sealed.to_str().unwrap(),
+        "--passphrase",
+        passphrase,
+    ]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    serde_json::from_str(&fs::read_to_string(sealed).unwrap()).unwrap()
+}
+
+fn hash(program: &Path) -> String {
+    let source = fs::read_to_string(program).unwrap();
+    let programs = zk100_host::assembler::parse_assembly(&source).unwrap();
+    zk100_host::merkle::program_root_hex(&programs).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let dir = scratch("round-trip");
+    let sealed_path = dir.join("sealed.json");
+    let sealed = seal(&fixture("passthrough"), &sealed_path, "hunter2");
+    assert_eq!(sealed["root"].as_str().unwrap(), hash(&fixture("passthrough")));
+    assert_eq!(sealed["grid"], serde_json::json!({ "rows": 2, "cols": 2 }));
+    let original = fs::read_to_string(fixture("passthrough")).unwrap();
+    let ciphertext = sealed["encrypted_source"]["ciphertext"].as_str().unwrap();
+    assert_ne!(ciphertext, hex::encode(&original));
+
+    let revealed = dir.join("revealed.asm");
+    let out = zk100(&[
+        "unseal",
+        sealed_path.to_str().unwrap(),
+        "--passphrase",
+        "hunter2",
+        "-o",
+        revealed.to_str().unwrap(),
+    ]);
+    let decrypted = fs::read_to_string(&revealed).unwrap();
+    let checked = zk100(&["unseal", sealed_path.to_str().unwrap(), "--source", revealed.to_str().unwrap()]);
+    fs::remove_dir_all(&dir).ok();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(decrypted, original);
+    assert!(checked.status.success(), "{}", String::from_utf8_lossy(&checked.stderr));
+}
+
+#[test]
+fn wrong_passphrase_is_rejected() {
+    let dir = scratch("wrong-passphrase");
+    let sealed_path = dir.join("sealed.json");
+    seal(&fixture("passthrough"), &sealed_path, "hunter2");
+    let out = zk100(&["unseal", sealed_path.to_str().unwrap(), "--passphrase", "hunter3"]);
+    fs::remove_dir_all(&dir).ok();
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("wrong passphrase"));
+}
+
+#[test]
+fn swapped_source_is_detected() {
+    // Splice another program's encrypted source into the sealed file: it
+    // decrypts under the same passphrase but no longer hashes to the root.
+    let dir = scratch("swapped");
+    let sealed_path = dir.join("sealed.json");
+    let other_path = dir.join("other.json");
+    let mut sealed = seal(&fixture("passthrough"), &sealed_path, "hunter2");
+    let other = seal(&fixture("negate"), &other_path, "hunter2");
+    sealed["encrypted_source"] = other["encrypted_source"].clone();
+    fs::write(&sealed_path, serde_json::to_string(&sealed).unwrap()).unwrap();
+
+    let out = zk100(&["unseal", sealed_path.to_str().unwrap(), "--passphrase", "hunter2"]);
+    let revealed = zk100(&["unseal", sealed_path.to_str().unwrap(), "--source", fixture("negate").to_str().unwrap()]);
+    fs::remove_dir_all(&dir).ok();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("swapped after sealing"), "{}", stderr);
+    assert!(!revealed.status.success());
+}
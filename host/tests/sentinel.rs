@@ -0,0 +1,50 @@
+//! `--input-sentinel`: the sentinel travels in ABI 6 args and the metadata,
+//! and `inspect-args` reads it back.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+
+#[test]
+fn sentinel_is_recorded_in_args_and_metadata() {
+    let sandbox = Sandbox::new("sentinel");
+    sandbox.write("doubler.asm", DOUBLER);
+    let args = ["assemble", "doubler.asm", "-i", "1,2,0", "-e", "2,4", "--input-sentinel", "0"];
+    sandbox
+        .run(&[&args[..], &["--sentinel-behavior", "end-of-stream", "--abi", "6", "--metadata", "metadata.json"]].concat())
+        .success()
+        .stderr_has("Sentinel: 0 (end-of-stream)")
+        .stderr_lacks("warning");
+    let words: Vec<String> = serde_json::from_str(&sandbox.read("args.json")).unwrap();
+    assert_eq!(words[..2], ["0x2", "0x0"]);
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("metadata.json")).unwrap();
+    assert_eq!(metadata["options"]["sentinel"], serde_json::json!({"value": 0, "behavior": "end-of-stream"}));
+
+    sandbox
+        .run(&["inspect-args", "args.json", "--abi", "6"])
+        .success()
+        .stdout_has("ABI 6: ")
+        .stdout_has("sentinel    0 (end-of-stream)");
+
+    // Without a sentinel, metadata stays as it was.
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1", "--abi", "6", "--metadata", "plain.json"]).success();
+    let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("plain.json")).unwrap();
+    assert!(metadata["options"].get("sentinel").is_none());
+}
+
+#[test]
+fn early_sentinel_warns_and_old_abis_refuse() {
+    let sandbox = Sandbox::new("sentinel-warn");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,0,2,3", "--input-sentinel", "0", "--abi", "6"])
+        .success()
+        .stderr_has("warning: inputs[1] is the sentinel 0 but 2 more inputs follow it");
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1,0", "--input-sentinel", "0"])
+        .code(1)
+        .stderr_has("error: an input sentinel needs ABI 6");
+    sandbox.run(&["assemble", "doubler.asm", "--sentinel-behavior", "end-of-stream"]).code(2);
+}
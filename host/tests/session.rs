@@ -0,0 +1,44 @@
+//! `--record-session` and `replay`: a recorded run replays to the same
+//! outputs, and a changed input shows up as a mismatch.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+
+#[test]
+fn recorded_session_replays_and_detects_a_changed_input() {
+    let sandbox = Sandbox::new("session-replay");
+    sandbox.write("src/doubler.asm", DOUBLER);
+    sandbox
+        .run(&[
+            "assemble", "src/doubler.asm", "-i", "3", "-e", "6", "-o", "args.json", "--metadata", "meta.json",
+            "--record-session", "session",
+        ])
+        .success()
+        .stderr_has("Recorded session to session");
+    assert!(sandbox.exists("session/inputs/src/doubler.asm"));
+    assert!(sandbox.exists("session/outputs/args.json"));
+    assert!(sandbox.exists("session/capabilities.json"));
+    let manifest: serde_json::Value = serde_json::from_str(&sandbox.read("session/replay.json")).unwrap();
+    assert!(!manifest["args"].to_string().contains("--record-session"), "{}", manifest);
+    assert!(manifest["resolved"].as_str().unwrap().contains("Assemble"), "{}", manifest);
+
+    // Metadata carries the time it was written, which replay ignores.
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    sandbox
+        .run(&["--porcelain", "replay", "session"])
+        .success()
+        .stdout_has("output\targs.json\tsame")
+        .stdout_has("output\tmeta.json\tsame")
+        .stdout_has("result\tmatch");
+
+    sandbox.write("session/inputs/src/doubler.asm", DOUBLER.replace("ADD ACC\n", "ADD ACC\nNOP\n"));
+    sandbox
+        .run(&["--porcelain", "replay", "session"])
+        .code(1)
+        .stdout_has("output\targs.json\tdiffers")
+        .stdout_has("result\tmismatch")
+        .stderr_has("outputs differ");
+}
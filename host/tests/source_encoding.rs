@@ -0,0 +1,92 @@
+//! Source files from Windows editors: a BOM, CRLF line endings and Latin-1
+//! comments load as if they were plain UTF-8, tabs and non-breaking spaces
+//! separate tokens as spaces do, and Latin-1 or other non-ASCII in code is
+//! an error that says where.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+use zk100_host::{assembler, cairo_abi};
+
+fn assembled_words(sandbox: &Sandbox, name: &str) -> Vec<u32> {
+    let run = sandbox.run(&["assemble", name, "-i", "1", "-e", "2", "-o", "args.json"]);
+    run.success().stderr_lacks("warning");
+    cairo_abi::parse_args(&sandbox.read("args.json")).unwrap().prog_words
+}
+
+#[test]
+fn bom_crlf_and_latin1_comments_assemble() {
+    let sandbox = Sandbox::with_fixture("encoding");
+    let doubler = assembler::encode_programs(&assembler::parse_assembly(DOUBLER).unwrap()).unwrap();
+    // The BOM used to hide the first NODE header, leaving (0,0) empty.
+    assert_eq!(assembled_words(&sandbox, "bom.asm"), doubler);
+    assert_eq!(assembled_words(&sandbox, "latin1_comment.asm"), doubler);
+
+    // Line numbers in the symbols file match what the editor shows.
+    sandbox.run(&["assemble", "crlf.asm", "-i", "1", "-e", "2", "--symbols", "symbols.json"]).success();
+    let symbols: serde_json::Value = serde_json::from_str(&sandbox.read("symbols.json")).unwrap();
+    assert_eq!(symbols[0]["node"], serde_json::json!([0, 0]));
+    assert_eq!((symbols[0]["labels"][0]["name"].as_str(), symbols[0]["labels"][0]["line"].as_u64()), (Some("LOOP"), Some(3)));
+
+    let id = |name: &str| sandbox.run(&["id", name]).success().stdout();
+    sandbox.write("plain.asm", DOUBLER);
+    assert_eq!(id("bom.asm"), id("plain.asm"));
+}
+
+#[test]
+fn tabs_and_non_breaking_spaces_are_whitespace() {
+    let sandbox = Sandbox::with_fixture("encoding");
+    let doubler = assembler::encode_programs(&assembler::parse_assembly(DOUBLER).unwrap()).unwrap();
+    assert_eq!(assembled_words(&sandbox, "tabs.asm"), doubler);
+    assert_eq!(assembled_words(&sandbox, "nbsp.asm"), doubler);
+}
+
+#[test]
+fn latin1_in_code_names_the_spot() {
+    let sandbox = Sandbox::with_fixture("encoding");
+    sandbox
+        .run(&["assemble", "latin1_code.asm"])
+        .code(1)
+        .stderr_has("latin1_code.asm: line 4, byte 48: invalid UTF-8 outside a comment");
+    // Valid UTF-8, but a Cyrillic А where ACC's A should be.
+    sandbox
+        .run(&["assemble", "cyrillic.asm"])
+        .code(1)
+        .stderr_has("line 3, col 5, node (0,0): 'АCC' has the non-ASCII character 'А' (U+0410)");
+}
+
+#[test]
+fn includes_resolve_against_the_file_not_stdin() {
+    let sandbox = Sandbox::new("includes");
+    sandbox.write("lib/doubler.asm", DOUBLER);
+    sandbox.write("main.asm", ".include \"lib/doubler.asm\" ; shared\n");
+    let doubler = assembler::encode_programs(&assembler::parse_assembly(DOUBLER).unwrap()).unwrap();
+    assert_eq!(assembled_words(&sandbox, "main.asm"), doubler);
+    sandbox
+        .run_stdin(&["assemble", "-", "-i", "1", "-e", "2"], b".include \"lib/doubler.asm\"\n")
+        .code(1)
+        .stderr_has("<stdin>: line 1: .include needs a source file to resolve its path against");
+}
+
+#[test]
+fn errors_in_included_lines_name_their_file() {
+    let sandbox = Sandbox::new("include-origins");
+    sandbox.write("relay.inc", "NODE (1,0)\nMOV P:UP, ACC\nMVO ACC, P:RIGHT\n");
+    sandbox.write("main.asm", "NODE (0,0)\nMOV IN, ACC\nMOV ACC, P:DOWN\nINCLUDE \"relay.inc\"\nNODE (1,1)\nMOV P:LEFT, OUTT\n");
+    // Lines after the include count from the top of main.asm, not of the
+    // expanded text.
+    sandbox
+        .run(&["assemble", "main.asm", "-i", "1", "-e", "2"])
+        .code(1)
+        .stderr_has("relay.inc:3, col 1, node (1,0): Unknown operation: MVO in `MVO ACC, P:RIGHT`")
+        .stderr_has("main.asm:6, col 13, node (1,1): Invalid destination operand: OUTT");
+
+    sandbox.write("relay.inc", "NODE (1,0)\nunused:\nMOV P:UP, ACC\nMOV ACC, P:RIGHT\n");
+    sandbox.write("main.asm", "NODE (0,0)\nMOV IN, ACC\nMOV ACC, P:DOWN\nINCLUDE \"relay.inc\"\nNODE (1,1)\nMOV P:LEFT, OUT\n");
+    sandbox
+        .run(&["assemble", "main.asm", "-i", "1", "-e", "1", "-o", "args.json"])
+        .success()
+        .stderr_has("warning: relay.inc:2: label 'unused' in node (1,0) is never referenced");
+}
@@ -0,0 +1,103 @@
+//! `--stats-log` records and `stats-log summarize`.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+use serde_json::Value;
+use std::process::Stdio;
+use std::thread;
+use zk100_host::stats::{self, Facts, StatsRecord};
+
+fn records(sandbox: &Sandbox) -> Vec<Value> {
+    sandbox.read(".zk100/stats.jsonl").lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+#[test]
+fn assemble_record_schema() {
+    let sandbox = Sandbox::new("stats-schema");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1", "-e", "2", "--stats-log"]).success();
+    let records = records(&sandbox);
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    let mut keys: Vec<&str> = record.as_object().unwrap().keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    assert_eq!(
+        keys,
+        [
+            "cases_failed", "cases_passed", "command", "duration_ms", "git_hash", "instructions", "ok",
+            "program_root", "started_unix", "tool", "version"
+        ]
+    );
+    assert_eq!(record["command"], "assemble");
+    assert_eq!(record["ok"], true);
+    assert_eq!(record["instructions"], 6);
+    assert!(record["program_root"].as_str().unwrap().starts_with("0x"));
+    assert_eq!(record["tool"], "zk100-host");
+}
+
+#[test]
+fn config_key_enables_logging_and_failures_are_recorded() {
+    let sandbox = Sandbox::new("stats-config");
+    sandbox.write("doubler.asm", DOUBLER).write("bad.asm", "NODE (0,0)\nFROB\n");
+    sandbox.run(&["assemble", "doubler.asm"]).success();
+    assert!(!sandbox.exists(".zk100/stats.jsonl"));
+
+    sandbox.write(".zk100/config.toml", "# local only\nstats_log = true\n");
+    sandbox.run(&["assemble", "doubler.asm"]).success();
+    sandbox.run(&["assemble", "bad.asm"]).code(1);
+    // Commands other than assemble, prove and verify-proof are not logged.
+    sandbox.run(&["capabilities"]).success();
+    let oks: Vec<bool> = records(&sandbox).iter().map(|r| r["ok"].as_bool().unwrap()).collect();
+    assert_eq!(oks, [true, false]);
+
+    sandbox
+        .run(&["stats-log", "summarize"])
+        .success()
+        .stdout_has("assemble            2         1")
+        .stdout_has("failure rate: 50.0% (1 of 2 runs)");
+
+    sandbox.write(".zk100/config.toml", "stats_log = yes\n");
+    sandbox.run(&["assemble", "doubler.asm"]).code(1).stderr_has("stats_log must be true or false");
+}
+
+#[test]
+fn parallel_runs_append_whole_lines() {
+    let sandbox = Sandbox::new("stats-parallel");
+    sandbox.write("doubler.asm", DOUBLER);
+    let children: Vec<_> = (0..8)
+        .map(|i| {
+            let out = format!("args{}.json", i);
+            sandbox
+                .command()
+                .args(["assemble", "doubler.asm", "-o", &out, "--stats-log"])
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+
+    // Records well past PIPE_BUF from many writers at once.
+    let project = sandbox.path("");
+    thread::scope(|scope| {
+        for writer in 0..8 {
+            let project = &project;
+            scope.spawn(move || {
+                for n in 0..50 {
+                    let root = format!("{}-{}-{}", writer, n, "f".repeat(8_000));
+                    let facts = Facts { program_root: Some(root), ..Default::default() };
+                    stats::append(project, &StatsRecord::new("prove", 0, n, true, facts)).unwrap();
+                }
+            });
+        }
+    });
+
+    let all = stats::read(&stats::log_path(&project)).unwrap();
+    assert_eq!(all.len(), 8 + 8 * 50);
+    assert_eq!(all.iter().filter(|r| r.command == "assemble").count(), 8);
+}
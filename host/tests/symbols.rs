@@ -0,0 +1,34 @@
+//! `assemble --symbols`: labels with their references, jump provenance, and
+//! the unused-label warning built from the same table.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn symbols_cross_reference_labels_and_jumps() {
+    let dir = std::env::temp_dir().join(format!("zk100-symbols-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = "NODE (0,0)\ntop:\nMOV IN, ACC\nJZ top\nJMP top\nidle:\nNOP\nNODE (1,1)\nJMP 0\n";
+    fs::write(dir.join("prog.asm"), source).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_zk100-host"))
+        .current_dir(&dir)
+        .args(["assemble", "prog.asm", "--symbols", "symbols.json"])
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let symbols: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("symbols.json")).unwrap()).unwrap();
+    fs::remove_dir_all(&dir).ok();
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("line 6: label 'idle' in node (0,0) is never referenced"), "{}", stderr);
+    assert!(!stderr.contains("'top'"), "{}", stderr);
+
+    let node = &symbols[0];
+    assert_eq!(node["node"], serde_json::json!([0, 0]));
+    assert_eq!(node["labels"][0], serde_json::json!({"name": "idle", "pc": 3, "line": 6, "references": []}));
+    assert_eq!(node["labels"][1], serde_json::json!({"name": "top", "pc": 0, "line": 2, "references": [1, 2]}));
+    assert_eq!(node["jumps"][1], serde_json::json!({"pc": 2, "target": 0, "label": "top"}));
+    assert_eq!(symbols[1]["jumps"][0], serde_json::json!({"pc": 0, "target": 0, "label": null}));
+}
@@ -0,0 +1,59 @@
+//! `--synthesize-literals`: wide `MOV`s into ACC built from several
+//! instructions, with the node's jumps following them.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::Sandbox;
+
+/// Node (0,0) skips the wide constant when the input is 0, once through a
+/// numeric target and once through a label.
+const SKIP: &str = "NODE (0,0)
+MOV IN, ACC
+JZ 3
+MOV 1000, ACC
+MOV ACC, P:DOWN
+MOV IN, ACC
+JZ done
+MOV 70000, ACC
+done:
+MOV ACC, P:DOWN
+NODE (1,0)
+MOV P:UP, ACC
+MOV ACC, P:RIGHT
+NODE (1,1)
+MOV P:LEFT, OUT
+";
+
+fn assemble(sandbox: &Sandbox, extra: &[&str]) -> common::Run {
+    let mut args = vec!["assemble", "skip.asm", "-i", "0,0,5,5", "--expected-from-simulation", "-o", "args.json"];
+    args.extend(extra);
+    sandbox.run(&args)
+}
+
+#[test]
+fn jumps_follow_the_expansion() {
+    let sandbox = Sandbox::new("synthesize-literals");
+    sandbox.write("skip.asm", SKIP);
+    assemble(&sandbox, &["--synthesize-literals"])
+        .success()
+        .stderr_has("warning: skip.asm: line 4: synthesized 1000 in ACC with 3 instructions: MOV 255, ACC; ADD 245; ADD ACC")
+        .stderr_has("warning: skip.asm: line 8: synthesized 70000 in ACC with ")
+        .stderr_has("Expected: [0, 0, 1000, 70000]");
+
+    // Without the flag a wide literal is an error, not its low 8 bits.
+    assemble(&sandbox, &[])
+        .code(1)
+        .stderr_has("line 4, col 5, node (0,0): literal 1000 does not fit the 8-bit literal field (0..=255); --synthesize-literals builds it in ACC");
+}
+
+#[test]
+fn only_acc_is_synthesized() {
+    let sandbox = Sandbox::new("synthesize-literals-port");
+    sandbox.write("port.asm", "NODE (0,0)\nMOV 1000, P:DOWN\n");
+    sandbox
+        .run(&["assemble", "port.asm", "--synthesize-literals"])
+        .code(1)
+        .stderr_has("line 2, col 5, node (0,0): cannot synthesize 1000 into P:DOWN: only ACC can be built over several instructions");
+}
@@ -0,0 +1,42 @@
+//! `assemble --tis100`: TIS-100 programs in tests/fixtures/tis100/ assemble
+//! as written and pass the `## IN:`/`## OUT:` streams they carry, and zk-100
+//! source fails with every violation listed under its node.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+use std::fs;
+
+#[test]
+fn tis100_programs_pass_untouched() {
+    let sandbox = Sandbox::with_fixture("tis100");
+    let mut programs: Vec<String> = fs::read_dir(sandbox.path(""))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".asm"))
+        .collect();
+    programs.sort();
+    assert_eq!(programs.len(), 5);
+    for program in &programs {
+        let before = sandbox.read(program);
+        sandbox.run(&["assemble", program, "--tis100", "--expected-from-simulation", "--metadata", "metadata.json"]).success();
+        assert_eq!(sandbox.read(program), before);
+        let metadata: serde_json::Value = serde_json::from_str(&sandbox.read("metadata.json")).unwrap();
+        assert_eq!(metadata["options"]["tis100"], true, "{}", program);
+    }
+}
+
+#[test]
+fn zk100_source_fails_by_node() {
+    let sandbox = Sandbox::new("tis100-reject");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox.run(&["assemble", "doubler.asm", "-i", "1", "-e", "2"]).success();
+    sandbox
+        .run(&["assemble", "doubler.asm", "-i", "1", "-e", "2", "--tis100"])
+        .code(1)
+        .stderr_has("5 problems under --tis100")
+        .stderr_has("node (0,0):\n  line 2: IN is a zk-100 operand; TIS-100 reads the input stream as UP in node (0,0)\n")
+        .stderr_has("node (1,1):\n  line 9: the P: prefix is zk-100 syntax; TIS-100 writes LEFT");
+}
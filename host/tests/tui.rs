@@ -0,0 +1,15 @@
+//! `tui` refuses to start without a terminal, after checking the program.
+
+#![cfg(feature = "tui")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+
+#[test]
+fn non_tty_is_a_clear_error() {
+    let sandbox = Sandbox::new("tui-non-tty");
+    sandbox.write("doubler.asm", DOUBLER).write("broken.asm", "NODE (0,0)\nJMP NOWHERE\n");
+    sandbox.run(&["tui", "doubler.asm", "-i", "1,2,3"]).code(1).stderr_has("tui needs an interactive terminal");
+    sandbox.run(&["tui", "broken.asm"]).code(1).stderr_has("NOWHERE");
+}
@@ -0,0 +1,35 @@
+//! Unreachable instructions: the warning, and `--deny-warnings` turning it
+//! into an error.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::Sandbox;
+
+const DEAD_CODE: &str = "NODE (0,0)\nloop:\nMOV IN, ACC\nMOV ACC, OUT\nJMP loop\nNEG\n";
+
+#[test]
+fn unreachable_warns_and_denies() {
+    let sandbox = Sandbox::new("unreachable");
+    sandbox.write("dead.asm", DEAD_CODE);
+    sandbox
+        .run(&["assemble", "dead.asm", "-o", "args.json"])
+        .success()
+        .stderr_has("warning: dead.asm: line 6: node (0,0) pc 3 is never reached from pc 0");
+    assert!(sandbox.exists("args.json"));
+
+    sandbox
+        .run(&["assemble", "dead.asm", "-o", "denied.json", "--deny-warnings"])
+        .code(1)
+        .stderr_has("warning: dead.asm: line 6: node (0,0) pc 3 is never reached from pc 0")
+        .stderr_has("1 warning(s) and --deny-warnings is set; nothing was written");
+    assert!(!sandbox.exists("denied.json"));
+
+    // HLT does not stop a node in the guest, so what follows it runs.
+    sandbox.write("halt.asm", "NODE (0,0)\nMOV IN, ACC\nHLT\nMOV ACC, OUT\n");
+    sandbox
+        .run(&["assemble", "halt.asm", "-o", "halt.json", "--deny-warnings"])
+        .success()
+        .stderr_lacks("never reached");
+}
@@ -0,0 +1,40 @@
+//! `convert --to words` / `--from words` in each word list format.
+
+#![cfg(feature = "cli")]
+
+mod common;
+
+use common::{DOUBLER, Sandbox};
+
+#[test]
+fn word_lists_round_trip_through_convert() {
+    let sandbox = Sandbox::new("word-lists");
+    sandbox.write("doubler.asm", DOUBLER);
+    sandbox.run(&["convert", "doubler.asm", "--to", "json", "-o", "doubler.json"]).success();
+    for file in ["words.txt", "words.rs", "words.csv"] {
+        sandbox.run(&["convert", "doubler.asm", "--to", "words", "-o", file]).success();
+        sandbox.run(&["convert", file, "--from", "words", "--to", "json", "-o", "back.json"]).success();
+        assert_eq!(sandbox.read("back.json"), sandbox.read("doubler.json"), "{}", file);
+    }
+    assert!(sandbox.read("words.rs").contains("const PROG: [u32; 10] = ["));
+    assert!(sandbox.read("words.csv").starts_with("node,pc,word\n0,0,0x"));
+
+    // Without a file name the format comes from --word-format, else text.
+    let run = sandbox.run(&["convert", "doubler.asm", "--to", "words", "--word-format", "csv"]);
+    run.success();
+    assert_eq!(run.stdout(), sandbox.read("words.csv"));
+    sandbox
+        .run_stdin(&["convert", "-", "--from", "words", "--to", "asm"], sandbox.read("words.txt").as_bytes())
+        .success()
+        .stdout_has("MOV P:LEFT, OUT");
+}
+
+#[test]
+fn malformed_word_list_names_the_line() {
+    let sandbox = Sandbox::new("word-lists-bad");
+    sandbox.write("bad.txt", "# header\n0\n0\nnope\n");
+    sandbox
+        .run(&["convert", "bad.txt", "--from", "words", "--to", "asm"])
+        .code(1)
+        .stderr_has("error: bad.txt: line 4: 'nope' is not a u32 word");
+}